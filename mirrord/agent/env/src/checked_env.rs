@@ -6,6 +6,7 @@ use std::{
     fmt,
     marker::PhantomData,
     net::{AddrParseError, IpAddr, SocketAddr},
+    ops::Not,
     str::{FromStr, Utf8Error},
 };
 
@@ -14,7 +15,10 @@ use base64::{Engine, engine::general_purpose};
 use k8s_openapi::api::core::v1::EnvVar;
 use thiserror::Error;
 
-use crate::steal_tls::StealPortTlsConfig;
+use crate::{
+    redaction::AgentLogRedaction, steal_conflict::StealConflictPolicy,
+    steal_tls::StealPortTlsConfig,
+};
 
 /// Type of an environment variable value.
 pub trait EnvValue: Sized {
@@ -183,6 +187,8 @@ impl StoredAsString for SocketAddr {}
 
 impl StoredAsString for String {}
 
+impl StoredAsString for StealConflictPolicy {}
+
 impl EnvValue for Vec<IpAddr> {
     type IntoReprError = Infallible;
     type FromReprError = ParseEnvError<AddrParseError>;
@@ -206,6 +212,29 @@ impl EnvValue for Vec<IpAddr> {
     }
 }
 
+/// For [`PROBE_PATHS`](crate::envs::PROBE_PATHS) and
+/// [`PASSTHROUGH_CACHE_HEADERS`](crate::envs::PASSTHROUGH_CACHE_HEADERS) variables.
+///
+/// The value is stored as a comma-separated list.
+impl EnvValue for Vec<String> {
+    type IntoReprError = Infallible;
+    type FromReprError = Utf8Error;
+
+    fn as_repr(&self) -> Result<String, Self::IntoReprError> {
+        Ok(self.join(","))
+    }
+
+    fn from_repr(repr: &[u8]) -> Result<Self, Self::FromReprError> {
+        let as_str = std::str::from_utf8(repr)?;
+
+        Ok(as_str
+            .split(',')
+            .filter(|item| item.is_empty().not())
+            .map(ToString::to_string)
+            .collect())
+    }
+}
+
 /// Errors that can occur when parsing [`STEAL_TLS_CONFIG`](crate::envs::STEAL_TLS_CONFIG) value.
 #[derive(Error, Debug)]
 pub enum ParseStealTlsConfigError {
@@ -236,3 +265,34 @@ impl EnvValue for Vec<StealPortTlsConfig> {
         Ok(deserialized)
     }
 }
+
+/// Errors that can occur when parsing [`LOG_REDACTION`](crate::envs::LOG_REDACTION) value.
+#[derive(Error, Debug)]
+pub enum ParseLogRedactionError {
+    #[error("failed to decode as base64: {0}")]
+    DecodeBase64Error(#[from] base64::DecodeError),
+    #[error("failed to deserialize as JSON: {0}")]
+    DeserializeError(#[from] serde_json::Error),
+}
+
+/// For [`LOG_REDACTION`](crate::envs::LOG_REDACTION) variable.
+///
+/// The value is stored as JSON encoded with base64.
+impl EnvValue for AgentLogRedaction {
+    type IntoReprError = Infallible;
+    type FromReprError = ParseLogRedactionError;
+
+    fn as_repr(&self) -> Result<String, Self::IntoReprError> {
+        let as_bytes = serde_json::to_vec(self).expect("serializing to memory should not fail");
+        let encoded = general_purpose::STANDARD_NO_PAD.encode(as_bytes);
+
+        Ok(encoded)
+    }
+
+    fn from_repr(repr: &[u8]) -> Result<Self, Self::FromReprError> {
+        let decoded = general_purpose::STANDARD_NO_PAD.decode(repr)?;
+        let deserialized = serde_json::from_slice(&decoded)?;
+
+        Ok(deserialized)
+    }
+}
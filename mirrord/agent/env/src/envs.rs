@@ -4,7 +4,10 @@
 
 use std::net::{IpAddr, SocketAddr};
 
-use crate::{checked_env::CheckedEnv, steal_tls::StealPortTlsConfig};
+use crate::{
+    checked_env::CheckedEnv, redaction::AgentLogRedaction, steal_conflict::StealConflictPolicy,
+    steal_tls::StealPortTlsConfig,
+};
 
 /// Used to pass operator's x509 certificate to the agent.
 ///
@@ -53,6 +56,17 @@ pub const LOG_LEVEL: CheckedEnv<String> = CheckedEnv::new("RUST_LOG");
 pub const STEAL_TLS_CONFIG: CheckedEnv<Vec<StealPortTlsConfig>> =
     CheckedEnv::new("MIRRORD_AGENT_STEAL_TLS_CONFIG");
 
+/// When set, the agent additionally accepts client connections over QUIC, on the UDP port with the
+/// same number as its TCP listener.
+///
+/// The agent generates the TLS identity of the QUIC endpoint itself, and reports the certificate
+/// in its `agent ready` message, so that the private key never leaves the agent.
+pub const QUIC: CheckedEnv<bool> = CheckedEnv::new("MIRRORD_AGENT_QUIC");
+
+/// Configures masking of secrets in the agent logs of HTTP traffic.
+pub const LOG_REDACTION: CheckedEnv<AgentLogRedaction> =
+    CheckedEnv::new("MIRRORD_AGENT_LOG_REDACTION");
+
 /// Container id of the target we're attaching to, e.g. `mirrord exec -t
 /// pod/glorious-cat/container/[cat-container]`, this is the id of `cat-container` that you
 /// can retrieve with `kubectl describe glorious-cat`.
@@ -94,3 +108,74 @@ pub const MAX_BODY_BUFFER_TIMEOUT: CheckedEnv<u32> =
 /// When set, the agent will clean any existing iptables rules.
 pub const CLEAN_IPTABLES_ON_START: CheckedEnv<bool> =
     CheckedEnv::new("MIRRORD_AGENT_CLEAN_IPTABLES_ON_START");
+
+/// Enables the session audit log in the agent.
+pub const AUDIT_LOG: CheckedEnv<bool> = CheckedEnv::new("MIRRORD_AGENT_AUDIT_LOG");
+
+/// `http://` URL to which the agent sends the audit log entries.
+pub const AUDIT_WEBHOOK: CheckedEnv<String> = CheckedEnv::new("MIRRORD_AGENT_AUDIT_WEBHOOK");
+
+/// Makes the agent refuse all requests that could modify the target: file writes, stealing and
+/// outgoing traffic.
+pub const READ_ONLY: CheckedEnv<bool> = CheckedEnv::new("MIRRORD_AGENT_READ_ONLY");
+
+/// Makes the agent resolve `/proc/self` and `/proc/thread-self` in file requests to the target
+/// process, set from the client's `feature.fs.procfs`.
+pub const PROCFS: CheckedEnv<bool> = CheckedEnv::new("MIRRORD_AGENT_PROCFS");
+
+/// Enables TCP keepalive on stolen connections, with the given idle time and probe interval (in
+/// seconds).
+pub const TCP_KEEPALIVE: CheckedEnv<u32> = CheckedEnv::new("MIRRORD_AGENT_TCP_KEEPALIVE");
+
+/// HTTP paths of the target's probes, which the agent answers itself during whole-port steal.
+pub const PROBE_PATHS: CheckedEnv<Vec<String>> = CheckedEnv::new("MIRRORD_AGENT_PROBE_PATHS");
+
+/// When set (and not `0`), the agent caches responses to passed through `GET` and `HEAD` requests
+/// on stolen ports for this many seconds.
+pub const PASSTHROUGH_CACHE_TTL: CheckedEnv<u32> =
+    CheckedEnv::new("MIRRORD_AGENT_PASSTHROUGH_CACHE_TTL");
+
+/// Maximal number of responses in the passthrough cache, see [`PASSTHROUGH_CACHE_TTL`].
+pub const PASSTHROUGH_CACHE_MAX_ENTRIES: CheckedEnv<u32> =
+    CheckedEnv::new("MIRRORD_AGENT_PASSTHROUGH_CACHE_MAX_ENTRIES");
+
+/// Request headers that are part of the passthrough cache key, see [`PASSTHROUGH_CACHE_TTL`].
+pub const PASSTHROUGH_CACHE_HEADERS: CheckedEnv<Vec<String>> =
+    CheckedEnv::new("MIRRORD_AGENT_PASSTHROUGH_CACHE_HEADERS");
+
+/// When set, the agent writes the raw bytes of stolen connections to files in this directory.
+pub const CAPTURE_STOLEN_DIR: CheckedEnv<String> = CheckedEnv::new("MIRRORD_AGENT_CAPTURE_STOLEN");
+
+/// Maximal number of bytes captured in each direction of a single stolen connection, see
+/// [`CAPTURE_STOLEN_DIR`].
+pub const CAPTURE_STOLEN_MAX_CONNECTION_BYTES: CheckedEnv<u64> =
+    CheckedEnv::new("MIRRORD_AGENT_CAPTURE_STOLEN_MAX_CONNECTION_BYTES");
+
+/// Maximal total size (in bytes) of the stolen connection captures, see [`CAPTURE_STOLEN_DIR`].
+pub const CAPTURE_STOLEN_MAX_TOTAL_BYTES: CheckedEnv<u64> =
+    CheckedEnv::new("MIRRORD_AGENT_CAPTURE_STOLEN_MAX_TOTAL_BYTES");
+
+/// Decides which client gets a stolen HTTP request when the filters of many clients match it.
+///
+/// Set by the operator, as only then many clients share the same agent.
+pub const STEAL_CONFLICT_POLICY: CheckedEnv<StealConflictPolicy> =
+    CheckedEnv::new("MIRRORD_AGENT_STEAL_CONFLICT_POLICY");
+
+/// Regex for HTTP headers that must be a part of every steal subscription, e.g. set by the
+/// operator from a `MirrordPolicy`. Steal subscriptions that do not include it are rejected.
+pub const MANDATORY_HEADER_FILTER: CheckedEnv<String> =
+    CheckedEnv::new("MIRRORD_AGENT_MANDATORY_HEADER_FILTER");
+
+/// Maximum number of clients that this agent serves at the same time, set by the operator from
+/// the user's quota.
+///
+/// Counts only the clients of this agent, not the user's sessions across agents.
+pub const MAX_CLIENTS: CheckedEnv<u32> = CheckedEnv::new("MIRRORD_AGENT_MAX_CLIENTS");
+
+/// Maximum number of ports that a client can steal at the same time, set by the operator from the
+/// user's quota.
+pub const MAX_STOLEN_PORTS: CheckedEnv<u32> = CheckedEnv::new("MIRRORD_AGENT_MAX_STOLEN_PORTS");
+
+/// Maximum duration of a client session (in seconds), set by the operator from the user's quota.
+pub const MAX_SESSION_DURATION: CheckedEnv<u64> =
+    CheckedEnv::new("MIRRORD_AGENT_MAX_SESSION_DURATION");
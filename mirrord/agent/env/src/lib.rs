@@ -17,4 +17,6 @@
 pub mod checked_env;
 pub mod envs;
 pub mod mesh;
+pub mod redaction;
+pub mod steal_conflict;
 pub mod steal_tls;
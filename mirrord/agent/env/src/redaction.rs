@@ -0,0 +1,24 @@
+//! This module contains definition of the log redaction configuration for the agent.
+//!
+//! As with all definitions in this crate, keep this backwards compatible.
+
+use serde::{Deserialize, Serialize};
+
+/// Masking of secrets in the agent logs of HTTP traffic, see
+/// [`LOG_REDACTION`](crate::envs::LOG_REDACTION).
+///
+/// When the variable is not set, the agent masks only the default headers (`authorization`,
+/// `cookie` and `set-cookie`).
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentLogRedaction {
+    /// Disables the redaction completely, including the default headers.
+    #[serde(default)]
+    pub disabled: bool,
+    /// Names of headers masked in addition to the default ones.
+    #[serde(default)]
+    pub headers: Vec<String>,
+    /// Regexes matched against HTTP bodies, matches are masked.
+    #[serde(default)]
+    pub body_patterns: Vec<String>,
+}
@@ -0,0 +1,51 @@
+//! This module contains the policy that the agent uses to pick a client for a stolen HTTP request
+//! when the filters of many clients match it.
+//!
+//! The policy is also used in the CRDs fetched by the operator.
+//!
+//! As with all definitions in this crate, keep this backwards compatible.
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Decides which client gets a stolen HTTP request when the HTTP filters of many clients match it.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum StealConflictPolicy {
+    /// The client that connected to the agent first gets the request.
+    #[default]
+    OldestClient,
+    /// The client whose filter has the most conditions that a request must meet gets the request,
+    /// e.g. `all_of` a header and a path filter is more specific than a single header filter.
+    ///
+    /// Ties are resolved as with `oldest_client`.
+    MostSpecificFilter,
+}
+
+impl fmt::Display for StealConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OldestClient => f.write_str("oldest_client"),
+            Self::MostSpecificFilter => f.write_str("most_specific_filter"),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("unknown steal conflict policy `{0}`, expected `oldest_client` or `most_specific_filter`")]
+pub struct ParseStealConflictPolicyError(String);
+
+impl FromStr for StealConflictPolicy {
+    type Err = ParseStealConflictPolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "oldest_client" => Ok(Self::OldestClient),
+            "most_specific_filter" => Ok(Self::MostSpecificFilter),
+            other => Err(ParseStealConflictPolicyError(other.to_string())),
+        }
+    }
+}
@@ -0,0 +1,513 @@
+//! Session audit log.
+//!
+//! When enabled with [`envs::AUDIT_LOG`], the agent records what its clients do in the cluster:
+//! when they connect and disconnect, which ports they steal or mirror (with which filters), which
+//! files they modify and which environment variables they read.
+//!
+//! Every [`AuditEvent`] is emitted as a [`tracing`] event with the [`AUDIT_TARGET`], and
+//! [`AuditLayer`] prints it to the agent's stdout as a single JSON line, so it ends up in the
+//! agent's logs regardless of the log level. When [`envs::AUDIT_WEBHOOK`] is set, the events are
+//! also sent to the webhook as JSON `POST` requests, one request per event.
+
+use std::{
+    fmt,
+    io::{self, Write},
+    ops::Not,
+    path::PathBuf,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use http::{Request, StatusCode, Uri, header};
+use http_body_util::Full;
+use hyper::client::conn::http1;
+use hyper_util::rt::TokioIo;
+use mirrord_agent_env::envs;
+use mirrord_protocol::{
+    FileRequest, Port,
+    file::{OpenFileRequest, OpenOptionsInternal, OpenRelativeFileRequest},
+    tcp::{LayerTcp, LayerTcpSteal, StealType},
+};
+use serde::Serialize;
+use thiserror::Error;
+use tokio::{net::TcpStream, sync::mpsc};
+use tracing::{
+    Event, Level, Subscriber,
+    field::{Field, Visit},
+};
+use tracing_subscriber::{
+    Layer,
+    filter::{Filtered, Targets},
+    fmt::MakeWriter,
+    layer::Context,
+};
+
+use crate::{task::BgTaskRuntime, util::ClientId};
+
+/// How many events can wait for the webhook before we start dropping them.
+const WEBHOOK_CHANNEL_CAPACITY: usize = 1024;
+
+/// Target of the [`tracing`] events that carry the audit records, see [`AuditLayer`].
+///
+/// The other layers of the agent's registry should not print these events.
+pub(crate) const AUDIT_TARGET: &str = "mirrord_audit";
+
+/// Something a client did, that is recorded in the audit log.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum AuditEvent {
+    /// The client connected to the agent.
+    SessionStarted {
+        /// Address of the client, this is the operator or a port-forward when not connecting
+        /// directly.
+        peer: Option<String>,
+        /// Id of the target container, [`None`] when targetless.
+        target_container: Option<String>,
+    },
+    /// The client disconnected.
+    SessionEnded,
+    /// The client negotiated its [`mirrord_protocol`] version.
+    ProtocolVersion { version: String },
+    /// The client subscribed to a port, to steal or mirror its traffic.
+    PortSubscribed {
+        port: Port,
+        mode: SubscriptionMode,
+        /// Filter of the subscription, [`None`] when all traffic is stolen or mirrored.
+        filter: Option<String>,
+    },
+    /// The client unsubscribed from a port.
+    PortUnsubscribed { port: Port, mode: SubscriptionMode },
+    /// The client requested an operation that modifies the target's filesystem.
+    FileModified {
+        operation: FileOperation,
+        /// The path as sent by the client, it can be relative to a directory the client opened.
+        path: PathBuf,
+    },
+    /// The client read environment variables of the target. Only the names are recorded.
+    EnvVarsRead { names: Vec<String> },
+    /// An HTTP request was stolen and sent to the client.
+    RequestStolen {
+        /// Also present in the agent and intproxy logs, and in the
+        /// `x-mirrord-correlation-id` header when the client injects metadata headers.
+        correlation_id: String,
+        port: Port,
+        method: String,
+        /// Path of the request, without the query.
+        path: String,
+    },
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SubscriptionMode {
+    Steal,
+    Mirror,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FileOperation {
+    /// The file was opened for writing.
+    Write,
+    MakeDir,
+    RemoveDir,
+    Unlink,
+    Rename,
+}
+
+impl AuditEvent {
+    /// Returns the event for a [`FileRequest`], if the request can modify the filesystem.
+    pub(crate) fn from_file_request(request: &FileRequest) -> Option<Self> {
+        let writes = |options: &OpenOptionsInternal| options.is_read_only().not();
+
+        let (operation, path) = match request {
+            FileRequest::Open(OpenFileRequest { path, open_options })
+            | FileRequest::OpenRelative(OpenRelativeFileRequest {
+                path, open_options, ..
+            }) if writes(open_options) => (FileOperation::Write, path),
+            FileRequest::MakeDir(request) => (FileOperation::MakeDir, &request.pathname),
+            FileRequest::MakeDirAt(request) => (FileOperation::MakeDir, &request.pathname),
+            FileRequest::RemoveDir(request) => (FileOperation::RemoveDir, &request.pathname),
+            FileRequest::Unlink(request) => (FileOperation::Unlink, &request.pathname),
+            FileRequest::UnlinkAt(request) => (FileOperation::Unlink, &request.pathname),
+            FileRequest::Rename(request) => (FileOperation::Rename, &request.old_path),
+            _ => return None,
+        };
+
+        Some(Self::FileModified {
+            operation,
+            path: path.clone(),
+        })
+    }
+
+    /// Returns the event for a [`LayerTcpSteal`] message, if it changes the subscriptions.
+    pub(crate) fn from_steal_message(message: &LayerTcpSteal) -> Option<Self> {
+        let mode = SubscriptionMode::Steal;
+
+        if let LayerTcpSteal::PortUnsubscribe(port) = message {
+            return Some(Self::PortUnsubscribed { port: *port, mode });
+        }
+
+        let (port, filter) = match message.steal_type()? {
+            StealType::All(port) => (*port, None),
+            StealType::FilteredHttp(port, filter) => (*port, Some(format!("header={filter}"))),
+            StealType::FilteredHttpEx(port, filter) => (*port, Some(filter.to_string())),
+            StealType::FilteredSni(port, filter) => (*port, Some(format!("sni={filter}"))),
+            StealType::FilteredPostgres(port, filter) => (*port, Some(filter.to_string())),
+            StealType::FilteredRedis(port, filter) => (*port, Some(filter.to_string())),
+        };
+
+        Some(Self::PortSubscribed { port, mode, filter })
+    }
+
+    /// Returns the event for a [`LayerTcp`] message, if it changes the subscriptions.
+    pub(crate) fn from_mirror_message(message: &LayerTcp) -> Option<Self> {
+        let mode = SubscriptionMode::Mirror;
+
+        match message {
+            LayerTcp::PortSubscribe(port) => Some(Self::PortSubscribed {
+                port: *port,
+                mode,
+                filter: None,
+            }),
+            LayerTcp::PortSubscribeFilteredHttp(port, filter) => Some(Self::PortSubscribed {
+                port: *port,
+                mode,
+                filter: Some(filter.to_string()),
+            }),
+            LayerTcp::PortSubscribeWithLimits(subscribe) => Some(Self::PortSubscribed {
+                port: subscribe.port,
+                mode,
+                filter: subscribe.filter.as_ref().map(ToString::to_string),
+            }),
+            LayerTcp::PortUnsubscribe(port) => Some(Self::PortUnsubscribed { port: *port, mode }),
+            LayerTcp::ConnectionUnsubscribe(..)
+            | LayerTcp::PortPause(..)
+            | LayerTcp::PortResume(..) => None,
+        }
+    }
+}
+
+/// A single entry of the audit log.
+#[derive(Debug, Serialize)]
+struct AuditRecord<'a> {
+    /// Always `mirrord_audit`, allows for picking the audit entries out of the agent's logs.
+    r#type: &'static str,
+    /// Milliseconds since the Unix epoch.
+    timestamp_ms: u64,
+    client_id: ClientId,
+    #[serde(flatten)]
+    event: &'a AuditEvent,
+}
+
+/// Handle to the audit log, can be cheaply cloned.
+///
+/// Does nothing when the audit log is disabled.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AuditLog(Option<Arc<AuditLogInner>>);
+
+#[derive(Debug)]
+struct AuditLogInner {
+    /// Sends the serialized records to the [`webhook_task`].
+    webhook_tx: Option<mpsc::Sender<String>>,
+}
+
+impl AuditLog {
+    /// Creates the audit log according to [`envs::AUDIT_LOG`] and [`envs::AUDIT_WEBHOOK`].
+    ///
+    /// The webhook requests are made from the given runtime, so from the target's network
+    /// namespace when there is a target.
+    pub(crate) fn from_env(runtime: &BgTaskRuntime) -> Self {
+        if envs::AUDIT_LOG.from_env_or_default().not() {
+            return Self(None);
+        }
+
+        let webhook_tx = envs::AUDIT_WEBHOOK
+            .try_from_env()
+            .ok()
+            .flatten()
+            .and_then(|webhook| match webhook.parse::<Uri>() {
+                Ok(uri) => Some(uri),
+                Err(error) => {
+                    tracing::error!(webhook, %error, "Invalid audit webhook URL, not using it");
+                    None
+                }
+            })
+            .map(|uri| {
+                let (tx, rx) = mpsc::channel(WEBHOOK_CHANNEL_CAPACITY);
+                runtime.handle().spawn(webhook_task(uri, rx));
+                tx
+            });
+
+        Self(Some(Arc::new(AuditLogInner { webhook_tx })))
+    }
+
+    /// Records the event of the given client, if the audit log is enabled.
+    pub(crate) fn record(&self, client_id: ClientId, event: AuditEvent) {
+        let Some(inner) = &self.0 else {
+            return;
+        };
+
+        let record = AuditRecord {
+            r#type: "mirrord_audit",
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            client_id,
+            event: &event,
+        };
+
+        let record = match serde_json::to_string(&record) {
+            Ok(record) => record,
+            Err(error) => {
+                tracing::error!(?event, %error, "Failed to serialize an audit event");
+                return;
+            }
+        };
+
+        tracing::info!(target: AUDIT_TARGET, record = record.as_str());
+
+        if let Some(webhook_tx) = &inner.webhook_tx
+            && webhook_tx.try_send(record).is_err()
+        {
+            tracing::warn!(?event, "Audit webhook is lagging behind, dropped an event");
+        }
+    }
+}
+
+/// [`Layer`] that writes the records of [`AuditLog::record`] to stdout, one per line, without any
+/// other formatting.
+pub(crate) struct AuditLayer<W> {
+    make_writer: W,
+}
+
+impl AuditLayer<fn() -> io::Stdout> {
+    /// Returns this layer with a per-layer filter, so that only the [`AUDIT_TARGET`] events are
+    /// written, regardless of `RUST_LOG`.
+    pub(crate) fn filtered<S>() -> Filtered<Self, Targets, S>
+    where
+        S: Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        Self::with_writer(io::stdout)
+    }
+}
+
+impl<W> AuditLayer<W>
+where
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    fn with_writer<S>(make_writer: W) -> Filtered<Self, Targets, S>
+    where
+        S: Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        Self { make_writer }.with_filter(Targets::new().with_target(AUDIT_TARGET, Level::INFO))
+    }
+}
+
+impl<S, W> Layer<S> for AuditLayer<W>
+where
+    S: Subscriber,
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let mut visitor = RecordVisitor::default();
+        event.record(&mut visitor);
+
+        let Some(record) = visitor.record else {
+            return;
+        };
+
+        if let Err(error) = writeln!(self.make_writer.make_writer(), "{record}") {
+            eprintln!("Failed to write an audit record: {error}");
+        }
+    }
+}
+
+/// Extracts the `record` field of an [`AUDIT_TARGET`] event.
+#[derive(Default)]
+struct RecordVisitor {
+    record: Option<String>,
+}
+
+impl Visit for RecordVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "record" {
+            self.record = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, _: &Field, _: &dyn fmt::Debug) {}
+}
+
+#[derive(Debug, Error)]
+enum WebhookError {
+    #[error("the URL has no host")]
+    NoHost,
+    #[error("failed to connect: {0}")]
+    Connect(#[from] std::io::Error),
+    #[error(transparent)]
+    Http(#[from] hyper::Error),
+    #[error(transparent)]
+    Request(#[from] http::Error),
+    #[error("the webhook responded with {0}")]
+    Status(StatusCode),
+}
+
+/// Sends the records to the webhook, one by one.
+async fn webhook_task(uri: Uri, mut rx: mpsc::Receiver<String>) {
+    while let Some(record) = rx.recv().await {
+        if let Err(error) = post_record(&uri, record).await {
+            tracing::warn!(%uri, %error, "Failed to send an audit event to the webhook");
+        }
+    }
+}
+
+#[tracing::instrument(level = Level::TRACE, skip(record), err)]
+async fn post_record(uri: &Uri, record: String) -> Result<(), WebhookError> {
+    let host = uri.host().ok_or(WebhookError::NoHost)?;
+    let port = uri.port_u16().unwrap_or(80);
+
+    let stream = TcpStream::connect((host, port)).await?;
+    let (mut sender, connection) = http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(connection);
+
+    let authority = uri.authority().map(ToString::to_string).unwrap_or_default();
+    let path = uri
+        .path_and_query()
+        .map(ToString::to_string)
+        .unwrap_or_else(|| "/".into());
+
+    let request = Request::post(path)
+        .header(header::HOST, authority)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(record)))?;
+
+    let status = sender.send_request(request).await?.status();
+    if status.is_success().not() {
+        return Err(WebhookError::Status(status));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use mirrord_protocol::{
+        file::UnlinkRequest,
+        tcp::{Filter, HttpFilter},
+    };
+
+    use super::*;
+
+    #[test]
+    fn read_only_open_is_not_recorded() {
+        let open = |open_options| {
+            FileRequest::Open(OpenFileRequest {
+                path: "/app/data".into(),
+                open_options,
+            })
+        };
+
+        let read_only = OpenOptionsInternal {
+            read: true,
+            ..Default::default()
+        };
+        assert_eq!(AuditEvent::from_file_request(&open(read_only)), None);
+
+        let append = OpenOptionsInternal {
+            append: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            AuditEvent::from_file_request(&open(append)),
+            Some(AuditEvent::FileModified {
+                operation: FileOperation::Write,
+                path: "/app/data".into(),
+            })
+        );
+
+        assert_eq!(
+            AuditEvent::from_file_request(&FileRequest::Unlink(UnlinkRequest {
+                pathname: "/app/data".into(),
+            })),
+            Some(AuditEvent::FileModified {
+                operation: FileOperation::Unlink,
+                path: "/app/data".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn record_format() {
+        let event = AuditEvent::from_steal_message(&LayerTcpSteal::PortSubscribe(
+            StealType::FilteredHttpEx(80, HttpFilter::Path(Filter::new("^/api".into()).unwrap())),
+        ))
+        .unwrap();
+
+        let record = serde_json::to_value(AuditRecord {
+            r#type: "mirrord_audit",
+            timestamp_ms: 1,
+            client_id: 7,
+            event: &event,
+        })
+        .unwrap();
+
+        assert_eq!(
+            record,
+            serde_json::json!({
+                "type": "mirrord_audit",
+                "timestamp_ms": 1,
+                "client_id": 7,
+                "event": "port_subscribed",
+                "port": 80,
+                "mode": "steal",
+                "filter": "path=^/api",
+            })
+        );
+    }
+
+    /// Verifies that [`AuditLayer`] writes only the audit records, one per line, even when the
+    /// record is made inside of a span.
+    #[test]
+    fn layer_writes_records() {
+        use std::sync::Mutex;
+
+        use tracing_subscriber::layer::SubscriberExt;
+
+        #[derive(Clone, Default)]
+        struct Output(Arc<Mutex<Vec<u8>>>);
+
+        impl io::Write for Output {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber =
+            tracing_subscriber::registry().with(AuditLayer::with_writer(move || writer.clone()));
+
+        let audit = AuditLog(Some(Arc::new(AuditLogInner { webhook_tx: None })));
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("client", client_id = 7).entered();
+            tracing::warn!("not an audit record");
+            audit.record(7, AuditEvent::SessionEnded);
+        });
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 1);
+
+        let record = serde_json::from_str::<serde_json::Value>(lines[0]).unwrap();
+        assert_eq!(record["type"], "mirrord_audit");
+        assert_eq!(record["client_id"], 7);
+        assert_eq!(record["event"], "session_ended");
+    }
+}
@@ -64,6 +64,42 @@ pub struct Args {
     /// are existing mirrord rules in the target's iptables.
     #[arg(long, default_value_t = false, env = envs::CLEAN_IPTABLES_ON_START.name)]
     pub clean_iptables_on_start: bool,
+
+    /// Refuse all requests that could modify the target, regardless of the client's config.
+    ///
+    /// Meant to be set by cluster admins in the agent's spec.
+    #[arg(long, default_value_t = false, env = envs::READ_ONLY.name)]
+    pub read_only: bool,
+
+    /// Resolve `/proc/self` and `/proc/thread-self` in file requests to the target process.
+    ///
+    /// Set from the client's `feature.fs.procfs`.
+    #[arg(long, default_value_t = false, env = envs::PROCFS.name)]
+    pub procfs: bool,
+
+    /// Regex for HTTP headers that must be a part of every steal subscription.
+    ///
+    /// Meant to be set by the operator, from the policies that apply to the target.
+    #[arg(long, env = envs::MANDATORY_HEADER_FILTER.name)]
+    pub mandatory_header_filter: Option<String>,
+
+    /// Maximum number of clients served at the same time.
+    ///
+    /// Meant to be set by the operator, from the quota of the user.
+    #[arg(long, env = envs::MAX_CLIENTS.name)]
+    pub max_clients: Option<u32>,
+
+    /// Maximum number of ports that a client can steal at the same time.
+    ///
+    /// Meant to be set by the operator, from the quota of the user.
+    #[arg(long, env = envs::MAX_STOLEN_PORTS.name)]
+    pub max_stolen_ports: Option<u32>,
+
+    /// Maximum duration of a client session, in seconds.
+    ///
+    /// Meant to be set by the operator, from the quota of the user.
+    #[arg(long, env = envs::MAX_SESSION_DURATION.name)]
+    pub max_session_duration: Option<u64>,
 }
 
 #[derive(Clone, Debug, Default, Subcommand)]
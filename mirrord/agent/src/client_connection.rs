@@ -18,7 +18,7 @@ use tokio_rustls::{
 use tracing::Level;
 use x509_parser::{error::PEMError, nom, pem};
 
-use crate::util::ClientId;
+use crate::{quic::QuicStream, util::ClientId};
 
 /// Wrapper over [`TlsConnector`] that can make successful TLS connections only to the server using
 /// a predefined certificate.
@@ -81,6 +81,12 @@ pub(crate) enum TlsSetupError {
     AddToRootStoreError(#[from] tokio_rustls::rustls::Error),
 }
 
+/// Client's network connection with the agent, as accepted by one of the agent's listeners.
+pub enum ClientStream {
+    Tcp(TcpStream),
+    Quic(QuicStream),
+}
+
 /// Wrapper over client's network connection with the agent.
 pub struct ClientConnection {
     framed: ConnectionFramed,
@@ -88,15 +94,31 @@ pub struct ClientConnection {
 }
 
 impl ClientConnection {
-    /// Wraps the given [`TcpStream`] into this struct.
+    /// Wraps the given [`ClientStream`] into this struct.
     /// If an [`AgentTlsConnector`] is given, it is used to first make a TLS connection using the
     /// given [`TcpStream`].
-    #[tracing::instrument(level = "trace", skip(tls), fields(use_tls = tls.is_some()), err)]
+    ///
+    /// QUIC connections are already secured, and are only made by clients that spawned the agent
+    /// themselves, so the [`AgentTlsConnector`] is not used for them.
+    #[tracing::instrument(level = "trace", skip(stream, tls), fields(use_tls = tls.is_some()), err)]
     pub async fn new(
-        stream: TcpStream,
+        stream: ClientStream,
         client_id: u32,
         tls: Option<AgentTlsConnector>,
     ) -> io::Result<Self> {
+        let stream = match stream {
+            ClientStream::Tcp(stream) => stream,
+            ClientStream::Quic(stream) => {
+                return Ok(Self {
+                    framed: ConnectionFramed::Quic(Box::new(Framed::new(
+                        stream,
+                        DaemonCodec::default(),
+                    ))),
+                    client_id,
+                });
+            }
+        };
+
         let framed = match tls {
             Some(connector) => {
                 let tls_stream = connector
@@ -118,6 +140,7 @@ impl ClientConnection {
         match &mut self.framed {
             ConnectionFramed::Tcp(framed) => framed.send(message).await?,
             ConnectionFramed::Tls(framed) => framed.send(message).await?,
+            ConnectionFramed::Quic(framed) => framed.send(message).await?,
         }
 
         Ok(())
@@ -129,6 +152,7 @@ impl ClientConnection {
         match &mut self.framed {
             ConnectionFramed::Tcp(framed) => framed.try_next().await,
             ConnectionFramed::Tls(framed) => framed.try_next().await,
+            ConnectionFramed::Quic(framed) => framed.try_next().await,
         }
     }
 }
@@ -141,6 +165,10 @@ impl fmt::Debug for ClientConnection {
                 "uses_tls",
                 &matches!(self.framed, ConnectionFramed::Tls(..)),
             )
+            .field(
+                "uses_quic",
+                &matches!(self.framed, ConnectionFramed::Quic(..)),
+            )
             .finish()
     }
 }
@@ -150,6 +178,7 @@ impl fmt::Debug for ClientConnection {
 enum ConnectionFramed {
     Tcp(Framed<TcpStream, DaemonCodec>),
     Tls(Box<Framed<TlsStream<TcpStream>, DaemonCodec>>),
+    Quic(Box<Framed<QuicStream, DaemonCodec>>),
 }
 
 #[cfg(test)]
@@ -195,9 +224,10 @@ mod test {
         tokio::join!(
             async move {
                 let stream = TcpStream::connect(addr).await.unwrap();
-                let mut connection = ClientConnection::new(stream, 0, Some(connector))
-                    .await
-                    .unwrap();
+                let mut connection =
+                    ClientConnection::new(ClientStream::Tcp(stream), 0, Some(connector))
+                        .await
+                        .unwrap();
                 connection
                     .send(DaemonMessage::Close("it works".into()))
                     .await
@@ -250,7 +280,7 @@ mod test {
                 .unwrap();
 
                 let stream = TcpStream::connect(addr).await.unwrap();
-                ClientConnection::new(stream, 0, Some(connector))
+                ClientConnection::new(ClientStream::Tcp(stream), 0, Some(connector))
                     .await
                     .unwrap_err();
             },
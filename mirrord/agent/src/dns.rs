@@ -5,7 +5,7 @@ use std::{
 use futures::{StreamExt, stream::FuturesOrdered};
 use hickory_resolver::{
     Hosts, TokioAsyncResolver,
-    config::{LookupIpStrategy, ServerOrderingStrategy},
+    config::{LookupIpStrategy, ResolverConfig, ResolverOpts, ServerOrderingStrategy},
     error::{ResolveError, ResolveErrorKind},
     lookup_ip::LookupIp,
     proto::error::ProtoErrorKind,
@@ -152,7 +152,7 @@ impl DnsWorker {
             let resolv_conf = fs::read(resolv_conf_path).await?;
             let hosts_conf = fs::read(hosts_path).await?;
 
-            let (config, mut options) = parse_resolv_conf(resolv_conf)?;
+            let (config, mut options) = parse_target_resolv_conf(&resolv_conf)?;
             tracing::debug!(?config, ?options, "Parsed resolv configuration");
 
             options.server_ordering_strategy = ServerOrderingStrategy::UserProvidedOrder;
@@ -322,6 +322,31 @@ impl DnsApi {
     }
 }
 
+/// Parses the target's `/etc/resolv.conf`, so that names are resolved just like in the target.
+///
+/// Unqualified names with fewer dots than `ndots` (e.g. Kubernetes service names like
+/// `payments`) are first tried with each of the `search` domains (e.g.
+/// `payments.default.svc.cluster.local`), and only then as given.
+///
+/// [`parse_resolv_conf`] also falls back to a local domain taken from the hostname of the agent,
+/// which is not the target's hostname. Like the libc resolver, we use the local domain only when
+/// there is no `search` list.
+fn parse_target_resolv_conf(resolv_conf: &[u8]) -> io::Result<(ResolverConfig, ResolverOpts)> {
+    let (config, options) = parse_resolv_conf(resolv_conf)?;
+
+    if config.search().is_empty() {
+        return Ok((config, options));
+    }
+
+    let config = ResolverConfig::from_parts(
+        None,
+        config.search().to_vec(),
+        config.name_servers().to_vec(),
+    );
+
+    Ok((config, options))
+}
+
 /// Errors that can occur in [`DnsWorker::do_lookup`].
 #[derive(Error, Debug)]
 enum InternalLookupError {
@@ -424,3 +449,39 @@ impl ProtocolConversion<LookupIpStrategy> for AddressFamily {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn kubernetes_resolv_conf() {
+        let resolv_conf = b"\
+search payments.svc.cluster.local svc.cluster.local cluster.local
+nameserver 10.96.0.10
+options ndots:5
+";
+
+        let (config, options) = parse_target_resolv_conf(resolv_conf).unwrap();
+
+        let search = config
+            .search()
+            .iter()
+            .map(|name| name.to_string().trim_end_matches('.').to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            search,
+            [
+                "payments.svc.cluster.local",
+                "svc.cluster.local",
+                "cluster.local"
+            ]
+        );
+        assert_eq!(config.domain(), None);
+        assert_eq!(options.ndots, 5);
+        assert_eq!(
+            config.name_servers()[0].socket_addr,
+            "10.96.0.10:53".parse().unwrap()
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
-    collections::HashMap,
-    mem,
+    collections::{HashMap, VecDeque},
+    io, mem,
     net::{Ipv4Addr, Ipv6Addr, SocketAddr},
     ops::Not,
     path::PathBuf,
@@ -20,37 +20,59 @@ use mirrord_agent_iptables::{
     IPTablesWrapper, SafeIpTables,
     error::{IPTablesError, IPTablesResult},
 };
-use mirrord_protocol::{ClientMessage, DaemonMessage, GetEnvVarsRequest};
+use mirrord_protocol::{
+    ClientMessage, DISABLED_FEATURES_VERSION, DaemonMessage, DisabledFeatures, FileRequest,
+    FileResponse, GetEnvVarsRequest, MANDATORY_HTTP_FILTER_VERSION,
+    cancel::CancelRequest,
+    capabilities::{AGENT_CAPABILITIES_VERSION, AgentCapabilities},
+    quota::SessionQuotas,
+    redact::Redactor,
+    shutdown::GOING_DOWN_VERSION,
+    tcp::{Filter, HttpFilter},
+};
+use nix::{sys::signal, unistd::Pid};
 use tokio::{
-    net::{TcpListener, TcpSocket, TcpStream},
+    net::{TcpListener, TcpSocket},
     process::Command,
     select,
-    signal::unix::SignalKind,
+    signal::unix::{Signal, SignalKind},
     sync::mpsc::Sender,
     task::JoinSet,
-    time::{Duration, timeout},
+    time::{self, Duration, timeout},
 };
 use tokio_util::sync::CancellationToken;
 use tracing::{Level, debug, error, trace, warn};
 use tracing_subscriber::{fmt::format::FmtSpan, prelude::*};
 
 use crate::{
+    audit::{AUDIT_TARGET, AuditEvent, AuditLayer, AuditLog},
     cli::{self, Args},
-    client_connection::{self, ClientConnection},
+    client_connection::{self, ClientConnection, ClientStream},
     container_handle::ContainerHandle,
     dns::{self, DnsApi},
     env,
     error::{AgentError, AgentResult},
-    file::FileManager,
+    file::{FileManager, ReadAheadChunks},
     incoming::MirrorHandle,
-    metrics,
+    interfaces::InterfacesApi,
+    listening_ports,
+    log_forward::{LogEventsReceiver, LogForwardLayer},
+    mandatory_filter, metrics,
     mirror::TcpMirrorApi,
     namespace::NamespaceType,
-    outgoing::{TcpOutgoingApi, UdpOutgoingApi},
+    outgoing::{IcmpEchoApi, TcpOutgoingApi, UdpOutgoingApi},
+    quic::QuicListener,
+    quota::ClientQuotas,
+    read_only, resources,
     reverse_dns::ReverseDnsApi,
     runtime::{self, get_container},
+    shutdown::{SHUTDOWN_GRACE_PERIOD, Shutdown, ShutdownNotice},
     steal::{StealerCommand, TcpStealerApi},
-    task::{BgTaskRuntime, RuntimeNamespace, status::BgTaskStatus},
+    task::{
+        BgTaskRuntime, RuntimeNamespace,
+        status::BgTaskStatus,
+        supervisor::{Criticality, RestartPolicy, Supervisor},
+    },
     util::{ClientId, protocol_version::ClientProtocolVersion},
 };
 
@@ -94,6 +116,19 @@ struct State {
     tls_connector: Option<AgentTlsConnector>,
     /// [`tokio::runtime`] that should be used for network operations ([`BackgroundTasks`]).
     network_runtime: Arc<BgTaskRuntime>,
+    /// Id of the target container, recorded in the [`AuditLog`].
+    target_container: Option<String>,
+    audit: AuditLog,
+    /// Whether the agent refuses requests that could modify the target, see [`read_only`].
+    read_only: bool,
+    /// Whether the file manager resolves `/proc/self` to the target, see [`envs::PROCFS`].
+    procfs: bool,
+    /// HTTP filter that must be a part of every steal subscription, see [`mandatory_filter`].
+    mandatory_http_filter: Option<HttpFilter>,
+    /// Quotas of the clients, see [`quota`](crate::quota).
+    quotas: SessionQuotas,
+    /// Started when the agent is about to exit, see [`Shutdown`].
+    shutdown: Shutdown,
 }
 
 impl State {
@@ -106,7 +141,16 @@ impl State {
             .map(AgentTlsConnector::new)
             .transpose()?;
 
+        let mandatory_http_filter = args
+            .mandatory_header_filter
+            .clone()
+            .map(Filter::new)
+            .transpose()
+            .map_err(AgentError::InvalidMandatoryHttpFilter)?
+            .map(HttpFilter::Header);
+
         let mut env: HashMap<String, String> = HashMap::new();
+        let mut target_container = None;
 
         let (ephemeral, container) = match &args.mode {
             cli::Mode::Targeted {
@@ -115,6 +159,7 @@ impl State {
                 ..
             } => {
                 let container = get_container(container_id.clone(), container_runtime).await?;
+                target_container = Some(container_id.clone());
 
                 let container_handle = ContainerHandle::new(container).await?;
 
@@ -123,14 +168,15 @@ impl State {
                 (false, Some(container_handle))
             }
             cli::Mode::Ephemeral { .. } => {
+                let container_id = envs::EPHEMERAL_TARGET_CONTAINER_ID
+                    .try_from_env()
+                    .ok()
+                    .flatten()
+                    .unwrap_or_default();
+                target_container = Some(container_id.clone());
+
                 let container_handle = ContainerHandle::new(runtime::Container::Ephemeral(
-                    runtime::EphemeralContainer {
-                        container_id: envs::EPHEMERAL_TARGET_CONTAINER_ID
-                            .try_from_env()
-                            .ok()
-                            .flatten()
-                            .unwrap_or_default(),
-                    },
+                    runtime::EphemeralContainer { container_id },
                 ))
                 .await?;
 
@@ -163,6 +209,8 @@ impl State {
             }
         };
 
+        let audit = AuditLog::from_env(&network_runtime);
+
         Ok(State {
             next_client_id: Default::default(),
             container,
@@ -170,6 +218,17 @@ impl State {
             ephemeral,
             tls_connector,
             network_runtime: Arc::new(network_runtime),
+            target_container,
+            audit,
+            read_only: args.read_only,
+            procfs: args.procfs,
+            mandatory_http_filter,
+            quotas: SessionQuotas {
+                max_clients: args.max_clients,
+                max_stolen_ports: args.max_stolen_ports,
+                max_session_duration: args.max_session_duration,
+            },
+            shutdown: Default::default(),
         })
     }
 
@@ -180,11 +239,21 @@ impl State {
 
     pub async fn serve_client_connection(
         self,
-        stream: TcpStream,
+        stream: ClientStream,
+        peer: SocketAddr,
         tasks: BackgroundTasks,
         cancellation_token: CancellationToken,
     ) -> u32 {
         let client_id = self.next_client_id.fetch_add(1, Ordering::Relaxed);
+        let audit = self.audit.clone();
+
+        audit.record(
+            client_id,
+            AuditEvent::SessionStarted {
+                peer: Some(peer.to_string()),
+                target_container: self.target_container.clone(),
+            },
+        );
 
         let result = ClientConnection::new(stream, client_id, self.tls_connector.clone())
             .map_err(AgentError::from)
@@ -192,6 +261,8 @@ impl State {
             .and_then(|client| client.start(cancellation_token))
             .await;
 
+        audit.record(client_id, AuditEvent::SessionEnded);
+
         match result {
             Ok(()) => {
                 trace!(client_id, "serve_client_connection -> Client disconnected");
@@ -258,12 +329,18 @@ struct BackgroundTasks {
     stealer: BackgroundTask<StealerCommand>,
     dns: BackgroundTask<DnsCommand>,
     mirror_handle: Option<MirrorHandle>,
+    supervisor: Supervisor,
 }
 
 struct ClientConnectionHandler {
     id: ClientId,
     /// Handles mirrord's file operations, see [`FileManager`].
     file_manager: FileManager,
+    /// Responses to [`FileRequest::ReadAhead`]s that were not yet sent in whole.
+    ///
+    /// The chunks are sent one at a time, so that the client can cancel the prefetching in the
+    /// meantime (see [`CancelRequest::ReadAhead`]).
+    read_ahead: VecDeque<ReadAheadChunks>,
     connection: ClientConnection,
     /// [`None`] when targetless.
     tcp_mirror_api: Option<TcpMirrorApi>,
@@ -273,17 +350,23 @@ struct ClientConnectionHandler {
     udp_outgoing_api: UdpOutgoingApi,
     dns_api: DnsApi,
     reverse_dns_api: ReverseDnsApi,
+    icmp_echo_api: IcmpEchoApi,
+    interfaces_api: InterfacesApi,
     state: State,
     /// Whether the client has sent us [`ClientMessage::ReadyForLogs`].
     ready_for_logs: bool,
+    /// Present when the client has sent us [`ClientMessage::ReadyForAgentLogs`].
+    agent_logs: Option<LogEventsReceiver>,
     /// Client's version of [`mirrord_protocol`].
     protocol_version: ClientProtocolVersion,
-}
-
-impl Drop for ClientConnectionHandler {
-    fn drop(&mut self) {
-        CLIENT_COUNT.fetch_sub(1, Ordering::Relaxed);
-    }
+    quotas: ClientQuotas,
+    /// Tells us when a critical background task failed, so that we can notify the client.
+    supervisor: Supervisor,
+    /// Present when the agent is shutting down and the client was sent
+    /// [`DaemonMessage::GoingDown`].
+    going_down: Option<ShutdownNotice>,
+    /// Whether the client has sent us [`ClientMessage::GoingDownAck`].
+    going_down_acked: bool,
 }
 
 impl ClientConnectionHandler {
@@ -295,11 +378,24 @@ impl ClientConnectionHandler {
         bg_tasks: BackgroundTasks,
         state: State,
     ) -> AgentResult<Self> {
+        let quotas = match ClientQuotas::new(state.quotas, &CLIENT_COUNT) {
+            Ok(quotas) => quotas,
+            Err(quota) => {
+                let error = AgentError::from(quota);
+                let _ = connection
+                    .send(DaemonMessage::Close(error.to_string()))
+                    .await; // Ignore message send error.
+
+                return Err(error);
+            }
+        };
+
         let protocol_version = ClientProtocolVersion::default();
 
         let pid = state.container_pid();
 
-        let file_manager = FileManager::new(pid.or_else(|| state.ephemeral.then_some(1)));
+        let file_manager =
+            FileManager::new(pid.or_else(|| state.ephemeral.then_some(1)), state.procfs);
 
         let tcp_mirror_api = bg_tasks
             .mirror_handle
@@ -309,16 +405,20 @@ impl ClientConnectionHandler {
             protocol_version.clone(),
             bg_tasks.stealer,
             &mut connection,
+            state.audit.clone(),
         )
         .await?;
         let dns_api = Self::create_dns_api(bg_tasks.dns);
         let reverse_dns_api = ReverseDnsApi::new(&state.network_runtime);
         let tcp_outgoing_api = TcpOutgoingApi::new(&state.network_runtime);
         let udp_outgoing_api = UdpOutgoingApi::new(&state.network_runtime);
+        let icmp_echo_api = IcmpEchoApi::new(&state.network_runtime);
+        let interfaces_api = InterfacesApi::new(&state.network_runtime);
 
         let client_handler = Self {
             id,
             file_manager,
+            read_ahead: Default::default(),
             connection,
             tcp_mirror_api,
             tcp_stealer_api,
@@ -326,13 +426,18 @@ impl ClientConnectionHandler {
             udp_outgoing_api,
             dns_api,
             reverse_dns_api,
+            icmp_echo_api,
+            interfaces_api,
             state,
             ready_for_logs: false,
+            agent_logs: None,
             protocol_version,
+            quotas,
+            supervisor: bg_tasks.supervisor,
+            going_down: None,
+            going_down_acked: false,
         };
 
-        CLIENT_COUNT.fetch_add(1, Ordering::Relaxed);
-
         Ok(client_handler)
     }
 
@@ -341,10 +446,18 @@ impl ClientConnectionHandler {
         protocol_version: ClientProtocolVersion,
         task: BackgroundTask<StealerCommand>,
         connection: &mut ClientConnection,
+        audit: AuditLog,
     ) -> AgentResult<Option<TcpStealerApi>> {
         match task {
             BackgroundTask::Running(stealer_status, stealer_sender) => {
-                match TcpStealerApi::new(id, protocol_version, stealer_sender, stealer_status).await
+                match TcpStealerApi::new(
+                    id,
+                    protocol_version,
+                    stealer_sender,
+                    stealer_status,
+                    audit,
+                )
+                .await
                 {
                     Ok(api) => Ok(Some(api)),
                     Err(e) => {
@@ -377,6 +490,25 @@ impl ClientConnectionHandler {
     #[tracing::instrument(level = Level::TRACE, skip(self))]
     async fn start(mut self, cancellation_token: CancellationToken) -> AgentResult<()> {
         let error = loop {
+            if self.going_down.is_some()
+                && self.going_down_acked
+                && self
+                    .tcp_stealer_api
+                    .as_ref()
+                    .is_none_or(TcpStealerApi::is_drained)
+            {
+                debug!(
+                    "Client {} finished its in-flight traffic, closing the connection",
+                    self.id
+                );
+                return Ok(());
+            }
+
+            let going_down_deadline = self
+                .going_down
+                .as_ref()
+                .map(|notice| time::sleep_until(notice.deadline));
+
             select! {
                 message = self.connection.receive() => {
                     let Some(message) = message? else {
@@ -393,7 +525,7 @@ impl ClientConnectionHandler {
                         }
                     }
                 },
-                // poll the sniffer API only when it's available
+                // poll the mirror API only when it's available
                 // exit when it stops (means something bad happened if
                 // it ran and then stopped)
                 message = async {
@@ -415,7 +547,10 @@ impl ClientConnectionHandler {
                         unreachable!()
                     }}
                 }, if self.tcp_stealer_api.is_some() => match message {
-                    Ok(message) => self.respond(message).await?,
+                    Ok(message) => {
+                        self.quotas.stealer_message(&message);
+                        self.respond(message).await?
+                    }
                     Err(e) => break e,
                 },
                 message = self.tcp_outgoing_api.recv_from_task() => match message {
@@ -444,6 +579,44 @@ impl ClientConnectionHandler {
                     Ok(message) => self.respond(DaemonMessage::ReverseDnsLookup(Ok(message))).await?,
                     Err(e) => break e,
                 },
+                message = self.icmp_echo_api.recv() => match message {
+                    Ok(message) => self.respond(DaemonMessage::IcmpEcho(message)).await?,
+                    Err(e) => break e,
+                },
+                message = self.interfaces_api.recv() => match message {
+                    Ok(message) => self.respond(DaemonMessage::GetInterfaces(message)).await?,
+                    Err(e) => break e,
+                },
+                Some(event) = async {
+                    match self.agent_logs { Some(ref mut agent_logs) => {
+                        agent_logs.recv().await
+                    } _ => {
+                        unreachable!()
+                    }}
+                }, if self.agent_logs.is_some() => self.respond(DaemonMessage::LogEvent(event)).await?,
+                quota = self.quotas.expired() => break quota.into(),
+                error = self.supervisor.critical_failure() => break error,
+                notice = self.state.shutdown.started(), if self.going_down.is_none() => {
+                    if self.protocol_version.matches(&GOING_DOWN_VERSION).not() {
+                        return Ok(());
+                    }
+
+                    self.respond(DaemonMessage::GoingDown(notice.going_down())).await?;
+                    if let Some(stealer_api) = self.tcp_stealer_api.as_mut() {
+                        stealer_api.drain().await?;
+                    }
+                    self.going_down = Some(notice);
+                },
+                _ = std::future::ready(()), if self.read_ahead.is_empty().not() => {
+                    self.send_read_ahead_chunk().await?;
+                },
+                Some(()) = OptionFuture::from(going_down_deadline) => {
+                    debug!(
+                        "Client {} did not finish its in-flight traffic before the shutdown deadline",
+                        self.id
+                    );
+                    return Ok(());
+                },
                 _ = cancellation_token.cancelled() => return Ok(()),
             }
         };
@@ -455,6 +628,39 @@ impl ClientConnectionHandler {
         Err(error)
     }
 
+    /// Sends the next chunk of the oldest [`FileRequest::ReadAhead`] in progress.
+    async fn send_read_ahead_chunk(&mut self) -> AgentResult<()> {
+        let Some(chunks) = self.read_ahead.front_mut() else {
+            return Ok(());
+        };
+
+        match chunks.next() {
+            Some(chunk) => {
+                self.respond(DaemonMessage::File(FileResponse::ReadAhead(chunk)))
+                    .await
+            }
+            None => {
+                self.read_ahead.pop_front();
+                Ok(())
+            }
+        }
+    }
+
+    /// Sends all the remaining chunks of the [`FileRequest::ReadAhead`]s in progress.
+    ///
+    /// The client matches the file responses with its requests by their order, so this must be
+    /// called before handling the next [`FileRequest`].
+    async fn flush_read_ahead(&mut self) -> AgentResult<()> {
+        while let Some(chunks) = self.read_ahead.pop_front() {
+            for chunk in chunks {
+                self.respond(DaemonMessage::File(FileResponse::ReadAhead(chunk)))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Sends a [`DaemonMessage`] response to the connected client (`mirrord-layer`).
     #[tracing::instrument(level = "trace", skip(self))]
     async fn respond(&mut self, response: DaemonMessage) -> AgentResult<()> {
@@ -470,8 +676,47 @@ impl ClientConnectionHandler {
     /// Returns `false` if the client disconnected.
     #[tracing::instrument(level = Level::TRACE, skip(self), ret, err(level = Level::DEBUG))]
     async fn handle_client_message(&mut self, message: ClientMessage) -> AgentResult<bool> {
+        if self.state.read_only
+            && let Some(response) = read_only::reject(&message, &self.protocol_version)
+        {
+            self.respond(response).await?;
+            return Ok(true);
+        }
+
+        if let Some(mandatory) = &self.state.mandatory_http_filter
+            && let Some(response) =
+                mandatory_filter::reject(&message, mandatory, &self.protocol_version)
+        {
+            self.respond(response).await?;
+            return Ok(true);
+        }
+
+        if let Some(response) = self.quotas.reject(&message, &self.protocol_version) {
+            self.respond(response).await?;
+            return Ok(true);
+        }
+        self.quotas.client_message(&message);
+
+        let audit_event = match &message {
+            ClientMessage::FileRequest(request) => AuditEvent::from_file_request(request),
+            ClientMessage::TcpSteal(message) => AuditEvent::from_steal_message(message),
+            ClientMessage::Tcp(message) => AuditEvent::from_mirror_message(message),
+            ClientMessage::SwitchProtocolVersion(version) => Some(AuditEvent::ProtocolVersion {
+                version: version.to_string(),
+            }),
+            _ => None,
+        };
+        if let Some(event) = audit_event {
+            self.state.audit.record(self.id, event);
+        }
+
         match message {
+            ClientMessage::FileRequest(FileRequest::ReadAhead(request)) => {
+                let chunks = self.file_manager.read_ahead(request);
+                self.read_ahead.push_back(chunks);
+            }
             ClientMessage::FileRequest(req) => {
+                self.flush_read_ahead().await?;
                 if let Some(response) = self.file_manager.handle_message(req)? {
                     self.respond(DaemonMessage::File(response))
                         .await
@@ -501,6 +746,14 @@ impl ClientConnectionHandler {
                 let env_vars_result =
                     env::select_env_vars(&self.state.env, env_vars_filter, env_vars_select);
 
+                if let Ok(env_vars) = &env_vars_result {
+                    let mut names = env_vars.keys().cloned().collect::<Vec<_>>();
+                    names.sort();
+                    self.state
+                        .audit
+                        .record(self.id, AuditEvent::EnvVarsRead { names });
+                }
+
                 self.respond(DaemonMessage::GetEnvVarsResponse(env_vars_result))
                     .await?
             }
@@ -518,6 +771,26 @@ impl ClientConnectionHandler {
                 self.reverse_dns_api
                     .request_reverse_lookup(request.ip_address);
             }
+            ClientMessage::IcmpEcho(request) => self.icmp_echo_api.request_echo(request),
+            ClientMessage::GetInterfaces(..) => self.interfaces_api.request_interfaces(),
+            ClientMessage::GetResourceUsage(..) => {
+                let pid = self
+                    .state
+                    .container_pid()
+                    .or_else(|| self.state.ephemeral.then_some(1));
+                self.respond(DaemonMessage::ResourceUsage(resources::resource_usage(pid)))
+                    .await?
+            }
+            ClientMessage::GetListeningPorts(..) => {
+                let pid = self
+                    .state
+                    .container_pid()
+                    .or_else(|| self.state.ephemeral.then_some(1));
+                self.respond(DaemonMessage::ListeningPorts(
+                    listening_ports::listening_ports(pid),
+                ))
+                .await?
+            }
             ClientMessage::Ping => self.respond(DaemonMessage::Pong).await?,
             // Message handled exclusively by the operator, see its docs for details.
             ClientMessage::OperatorPong(_) => (),
@@ -551,6 +824,23 @@ impl ClientConnectionHandler {
             ClientMessage::Close => {
                 return Ok(false);
             }
+            ClientMessage::GoingDownAck => {
+                self.going_down_acked = true;
+            }
+            ClientMessage::Cancel(CancelRequest::ReadAhead { remote_fd }) => {
+                self.read_ahead
+                    .iter_mut()
+                    .filter(|chunks| chunks.remote_fd() == remote_fd)
+                    .for_each(ReadAheadChunks::cancel);
+            }
+            ClientMessage::Cancel(CancelRequest::HttpRequest {
+                connection_id,
+                request_id,
+            }) => {
+                if let Some(stealer_api) = self.tcp_stealer_api.as_mut() {
+                    stealer_api.cancel_http_request(connection_id, request_id);
+                }
+            }
             ClientMessage::PauseTargetRequest(_) => {
                 self.respond(DaemonMessage::Close(
                     "Pause isn't supported anymore.".to_string(),
@@ -562,14 +852,45 @@ impl ClientConnectionHandler {
 
                 self.protocol_version.replace(client_version);
 
+                let read_only =
+                    self.state.read_only && DISABLED_FEATURES_VERSION.matches(&settled_version);
+                let mandatory_http_filter = self
+                    .state
+                    .mandatory_http_filter
+                    .clone()
+                    .filter(|_| MANDATORY_HTTP_FILTER_VERSION.matches(&settled_version));
+                let quotas = self.quotas.to_send(&settled_version);
+                let capabilities = AGENT_CAPABILITIES_VERSION.matches(&settled_version);
+
                 self.respond(DaemonMessage::SwitchProtocolVersionResponse(
                     settled_version,
                 ))
                 .await?;
+
+                // Sent right after the response, so that the client knows about the disabled
+                // features before it gets responses to any of its requests.
+                if capabilities {
+                    self.respond(DaemonMessage::Capabilities(AgentCapabilities::CURRENT))
+                        .await?;
+                }
+                if read_only {
+                    self.respond(DaemonMessage::DisabledFeatures(DisabledFeatures::READ_ONLY))
+                        .await?;
+                }
+                if let Some(filter) = mandatory_http_filter {
+                    self.respond(DaemonMessage::MandatoryHttpFilter(filter))
+                        .await?;
+                }
+                if let Some(quotas) = quotas {
+                    self.respond(DaemonMessage::SessionQuotas(quotas)).await?;
+                }
             }
             ClientMessage::ReadyForLogs => {
                 self.ready_for_logs = true;
             }
+            ClientMessage::ReadyForAgentLogs => {
+                self.agent_logs = Some(LogEventsReceiver::subscribe());
+            }
             ClientMessage::Vpn(_message) => {
                 self.respond(DaemonMessage::Close("VPN is not supported".into()))
                     .await?;
@@ -580,6 +901,25 @@ impl ClientConnectionHandler {
     }
 }
 
+/// Accepts the next client connection, either from the TCP `listener` or from the optional
+/// `quic_listener`.
+///
+/// Cancel safe.
+async fn accept_client(
+    listener: &TcpListener,
+    quic_listener: Option<&mut QuicListener>,
+) -> io::Result<(ClientStream, SocketAddr)> {
+    let quic_accept = OptionFuture::from(quic_listener.map(|listener| listener.accept()));
+
+    select! {
+        result = listener.accept() => {
+            result.map(|(stream, addr)| (ClientStream::Tcp(stream), addr))
+        }
+
+        Some((stream, addr)) = quic_accept => Ok((ClientStream::Quic(stream), addr)),
+    }
+}
+
 /// Upon first client connection, immediately sends [`DaemonMessage::Close`] to the client due to
 /// the presence of dirty IP tables.
 pub async fn notify_client_about_dirty_iptables(
@@ -603,7 +943,8 @@ pub async fn notify_client_about_dirty_iptables(
     // ungracefully).
     match first_connection {
         Ok(Ok((stream, ..))) => {
-            let mut connection = ClientConnection::new(stream, 0, tls_connector).await?;
+            let mut connection =
+                ClientConnection::new(ClientStream::Tcp(stream), 0, tls_connector).await?;
             connection
                 .send(DaemonMessage::Close(
                     DIRTY_IPTABLES_ERROR_MESSAGE.to_string(),
@@ -630,7 +971,7 @@ pub async fn notify_client_about_dirty_iptables(
     Ok(())
 }
 
-/// Monitors the main container process and cancels `cancel` when it
+/// Monitors the main container process and starts the graceful `shutdown` when it
 /// exits.
 ///
 /// This not only makes for a better UX (no unused agent pods
@@ -639,7 +980,7 @@ pub async fn notify_client_about_dirty_iptables(
 /// container dies, the veth interface will be deleted while
 /// redirected connections are still open, and thus the other ends
 /// will stay open with no way to terminate them correctly.
-fn monitor_main_container(cancel: CancellationToken, pid: libc::pid_t) {
+fn monitor_main_container(shutdown: Shutdown, pid: libc::pid_t) {
     let fd = match AsyncPidFd::from_pid(pid) {
         Ok(fd) => fd,
         Err(error) => {
@@ -656,7 +997,7 @@ fn monitor_main_container(cancel: CancellationToken, pid: libc::pid_t) {
         match fd.wait().await {
             Err(error) if error.raw_os_error() == Some(libc::ECHILD) => {
                 tracing::warn!(?error, "Target container process exited, shutting down");
-                cancel.cancel();
+                shutdown.start("target container exited");
             }
             Err(error) => {
                 tracing::warn!(
@@ -754,6 +1095,15 @@ async fn start_agent(args: Args) -> AgentResult<()> {
     let client_listener_address = listener.local_addr()?;
     debug!(address = %client_listener_address, "Created the client listener.");
 
+    // Listen for client connections over QUIC, on the UDP port with the same number.
+    let mut quic_listener = envs::QUIC
+        .from_env_or_default()
+        .then(|| QuicListener::bind(client_listener_address))
+        .transpose()?;
+    if let Some(quic_listener) = &quic_listener {
+        debug!(address = %quic_listener.local_addr(), "Created the QUIC client listener.");
+    }
+
     let state = State::new(&args).await?;
 
     let cancellation_token = CancellationToken::new();
@@ -800,22 +1150,25 @@ async fn start_agent(args: Args) -> AgentResult<()> {
 
         // Casting u64 to i32 but linux pids shouldn't exceed 2^22
         let pid = target_pid.try_into().unwrap();
-        monitor_main_container(cancellation_token.clone(), pid);
+        monitor_main_container(state.shutdown.clone(), pid);
     }
 
     // To make sure that background tasks are cancelled when we exit early from this function.
     let cancel_guard = cancellation_token.clone().drop_guard();
 
+    let supervisor = Supervisor::default();
+
     if let Some(metrics_address) = args.metrics {
         let cancellation_token = cancellation_token.clone();
-        tokio::spawn(async move {
-            start_metrics(metrics_address, cancellation_token.clone())
-                .await
-                .inspect_err(|fail| {
-                    tracing::error!(?fail, "Failed starting metrics server!");
-                    cancellation_token.cancel();
-                })
-        });
+        supervisor.spawn_restartable(
+            "MetricsServer",
+            Criticality::Critical,
+            RestartPolicy::OnFailure {
+                max_restarts: 3,
+                backoff: Duration::from_secs(1),
+            },
+            move || start_metrics(metrics_address, cancellation_token.clone()),
+        );
     }
 
     let (stealer, mirror_handle) = match state.container_pid() {
@@ -823,6 +1176,7 @@ async fn start_agent(args: Args) -> AgentResult<()> {
         Some(pid) => {
             let (steal_handle, mirror_handle) = setup::start_traffic_redirector(
                 &state.network_runtime,
+                &supervisor,
                 pid,
                 state
                     .is_with_mesh_exclusion()
@@ -832,6 +1186,7 @@ async fn start_agent(args: Args) -> AgentResult<()> {
             (
                 setup::start_stealer(
                     &state.network_runtime,
+                    &supervisor,
                     steal_handle,
                     cancellation_token.clone(),
                 ),
@@ -840,25 +1195,38 @@ async fn start_agent(args: Args) -> AgentResult<()> {
         }
     };
 
-    let dns = setup::start_dns(&args, &state.network_runtime, cancellation_token.clone());
+    let dns = setup::start_dns(
+        &args,
+        &state.network_runtime,
+        &supervisor,
+        cancellation_token.clone(),
+    );
 
     let bg_tasks = BackgroundTasks {
         stealer,
         dns,
         mirror_handle,
+        supervisor: supervisor.clone(),
     };
 
     // WARNING: `wait_for_agent_startup` in `mirrord/kube/src/api/container.rs` expects a line
     // containing "agent_ready" to be printed. If you change this then mirrord fails to
     // initialize.
-    println!("agent ready - version {}", env!("CARGO_PKG_VERSION"));
+    match &quic_listener {
+        Some(quic_listener) => println!(
+            "agent ready - version {} - quic certificate {}",
+            env!("CARGO_PKG_VERSION"),
+            quic_listener.certificate()
+        ),
+        None => println!("agent ready - version {}", env!("CARGO_PKG_VERSION")),
+    }
 
     let mut clients: JoinSet<ClientId> = JoinSet::new();
 
     // We wait for the first client until `communication_timeout` elapses.
     let first_connection = timeout(
         Duration::from_secs(args.communication_timeout.into()),
-        listener.accept(),
+        accept_client(&listener, quic_listener.as_mut()),
     )
     .await;
 
@@ -867,6 +1235,7 @@ async fn start_agent(args: Args) -> AgentResult<()> {
             trace!(peer = %addr, "start_agent -> First connection accepted");
             clients.spawn(state.clone().serve_client_connection(
                 stream,
+                addr,
                 bg_tasks.clone(),
                 cancellation_token.clone(),
             ));
@@ -887,18 +1256,21 @@ async fn start_agent(args: Args) -> AgentResult<()> {
         Err(AgentError::TestError)?
     }
 
+    let mut sigterm = tokio::signal::unix::signal(SignalKind::terminate())?;
+
     let idle_ttl = Duration::from_secs(envs::IDDLE_TTL.from_env_or_default());
     loop {
         let exit_idle =
             OptionFuture::from(clients.is_empty().then_some(tokio::time::sleep(idle_ttl)));
 
         select! {
-            Ok((stream, addr)) = listener.accept() => {
+            Ok((stream, addr)) = accept_client(&listener, quic_listener.as_mut()) => {
                 trace!(peer = %addr, "start_agent -> Connection accepted");
                 clients.spawn(state
                     .clone()
                     .serve_client_connection(
                         stream,
+                        addr,
                         bg_tasks.clone(),
                         cancellation_token.clone()
                     )
@@ -916,6 +1288,39 @@ async fn start_agent(args: Args) -> AgentResult<()> {
                 }
             }
 
+            error = supervisor.critical_failure() => {
+                error!(%error, "start_agent -> Critical background task failed, shutting down");
+
+                // The client handlers notice the failure too, give them a moment to notify the
+                // clients before they are aborted.
+                let _ = timeout(Duration::from_secs(5), async {
+                    while clients.join_next().await.is_some() {}
+                })
+                .await;
+
+                break;
+            }
+
+            Some(()) = sigterm.recv() => {
+                state.shutdown.start("agent was asked to stop, e.g. its pod is being deleted");
+            }
+
+            notice = state.shutdown.started() => {
+                // The client handlers notify their clients, and wait for them to finish their
+                // in-flight traffic.
+                let finished = time::timeout_at(notice.deadline, async {
+                    while clients.join_next().await.is_some() {}
+                })
+                .await;
+                debug!(
+                    reason = notice.reason,
+                    all_clients_finished = finished.is_ok(),
+                    "start_agent -> Graceful shutdown finished, exiting main agent loop"
+                );
+
+                break;
+            }
+
             Some(..) = exit_idle => {
                 trace!(
                     ?idle_ttl,
@@ -941,7 +1346,10 @@ async fn start_agent(args: Args) -> AgentResult<()> {
 
     trace!("start_agent -> Agent shutdown");
 
-    Ok(())
+    match supervisor.failure() {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
 }
 
 async fn clear_iptable_chain(
@@ -980,8 +1388,11 @@ async fn clear_iptable_chain(
 /// Runs the current binary as a child process,
 /// using the exact same command line.
 ///
+/// When `sigterm` receives a signal, it is forwarded to the child process, which then shuts down
+/// gracefully (see [`Shutdown`]). If the child process does not exit in time, it is killed.
+///
 /// When this future is aborted before completion, the child process is automatically killed.
-async fn run_child_agent() -> AgentResult<()> {
+async fn run_child_agent(sigterm: &mut Signal) -> AgentResult<()> {
     let command_args = std::env::args().collect::<Vec<_>>();
     let (command, args) = command_args
         .split_first()
@@ -993,7 +1404,33 @@ async fn run_child_agent() -> AgentResult<()> {
         .kill_on_drop(true)
         .spawn()?;
 
-    let status = child_agent.wait().await?;
+    let status = select! {
+        status = child_agent.wait() => status?,
+
+        _ = sigterm.recv() => {
+            debug!("run_child_agent -> SIGTERM received, forwarding to the agent process");
+
+            if let Some(pid) = child_agent.id()
+                && let Err(error) = signal::kill(Pid::from_raw(pid as i32), signal::Signal::SIGTERM)
+            {
+                warn!(%error, "run_child_agent -> Failed to forward SIGTERM to the agent process");
+            }
+
+            // Give the agent process a moment more than its own deadline, so that it can clean
+            // up after the clients.
+            let grace_period = SHUTDOWN_GRACE_PERIOD + Duration::from_secs(5);
+            if timeout(grace_period, child_agent.wait()).await.is_err() {
+                warn!(
+                    ?grace_period,
+                    "run_child_agent -> Agent process did not shut down in time, killing it"
+                );
+                child_agent.kill().await?;
+            }
+
+            return Ok(());
+        }
+    };
+
     if !status.success() {
         Err(AgentError::AgentFailed(status))
     } else {
@@ -1001,13 +1438,42 @@ async fn run_child_agent() -> AgentResult<()> {
     }
 }
 
+/// Installs the global [`Redactor`] configured with [`envs::LOG_REDACTION`].
+///
+/// Invalid configuration is logged and ignored, leaving the default [`Redactor`].
+fn install_log_redaction() {
+    let redaction = match envs::LOG_REDACTION.try_from_env() {
+        Ok(Some(redaction)) => redaction,
+        Ok(None) => return,
+        Err(error) => {
+            warn!(%error, "Failed to read the log redaction configuration");
+            return;
+        }
+    };
+
+    let redactor = if redaction.disabled {
+        Ok(Redactor::disabled())
+    } else {
+        Redactor::new(&redaction.headers, &redaction.body_patterns)
+    };
+
+    match redactor {
+        Ok(redactor) => {
+            let _ = redactor.install();
+        }
+        Err(error) => warn!(%error, "Invalid log redaction configuration"),
+    }
+}
+
 /// Targeted agent's parent process.
 ///
 /// Spawns the main agent routine in the child process and handles cleanup of iptables
 /// when the child process exits.
 ///
 /// Captures SIGTERM signals sent by Kubernetes when the pod is being gracefully deleted.
-/// When a signal is captured, the child process is killed and the iptables are cleaned.
+/// When a signal is captured, it is forwarded to the child process, which notifies the clients
+/// and lets them finish their in-flight traffic. The iptables are cleaned only after the child
+/// process exits, so that they're not cleaned while it still redirects traffic.
 async fn start_iptable_guard(args: Args) -> AgentResult<()> {
     debug!("start_iptable_guard -> Initializing iptable-guard.");
 
@@ -1016,20 +1482,15 @@ async fn start_iptable_guard(args: Args) -> AgentResult<()> {
 
     let mut sigterm = tokio::signal::unix::signal(SignalKind::terminate())?;
 
-    let result = tokio::select! {
-        _ = sigterm.recv() => {
-            debug!("start_iptable_guard -> SIGTERM received, killing agent process");
-            Ok(())
+    let result = match run_child_agent(&mut sigterm).await {
+        Err(AgentError::AgentFailed(status))
+            if status.code() == Some(IPTABLES_DIRTY_EXIT_CODE as i32) =>
+        {
+            // Err status `IPTABLES_DIRTY_EXIT_CODE` means dirty IP tables detected, skip cleanup
+            tracing::warn!("dirty IP tables, cleanup skipped");
+            return Err(AgentError::AgentFailed(status));
         }
-
-        result = run_child_agent() => match result {
-            Err(AgentError::AgentFailed(status)) if status.code() == Some(IPTABLES_DIRTY_EXIT_CODE as i32) => {
-                // Err status `IPTABLES_DIRTY_EXIT_CODE` means dirty IP tables detected, skip cleanup
-                tracing::warn!("dirty IP tables, cleanup skipped");
-                return result;
-            }
-            _ => result,
-        },
+        result => result,
     };
 
     state
@@ -1080,15 +1541,26 @@ pub async fn main() -> AgentResult<()> {
     rustls::crypto::CryptoProvider::install_default(rustls::crypto::aws_lc_rs::default_provider())
         .expect("Failed to install crypto provider");
 
+    // The audit records are printed only by the `AuditLayer`.
+    let env_filter = || {
+        tracing_subscriber::EnvFilter::from_default_env().add_directive(
+            format!("{AUDIT_TARGET}=off")
+                .parse()
+                .expect("audit target directive should be valid"),
+        )
+    };
+
     if envs::JSON_LOG.from_env_or_default() {
         tracing_subscriber::registry()
             .with(
                 tracing_subscriber::fmt::layer()
                     .with_thread_ids(true)
                     .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
-                    .json(),
+                    .json()
+                    .with_filter(env_filter()),
             )
-            .with(tracing_subscriber::EnvFilter::from_default_env())
+            .with(LogForwardLayer::filtered())
+            .with(AuditLayer::filtered())
             .init();
     } else {
         tracing_subscriber::registry()
@@ -1097,9 +1569,11 @@ pub async fn main() -> AgentResult<()> {
                     .with_thread_ids(true)
                     .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
                     .pretty()
-                    .with_line_number(true),
+                    .with_line_number(true)
+                    .with_filter(env_filter()),
             )
-            .with(tracing_subscriber::EnvFilter::from_default_env())
+            .with(LogForwardLayer::filtered())
+            .with(AuditLayer::filtered())
             .init();
     }
 
@@ -1108,6 +1582,8 @@ pub async fn main() -> AgentResult<()> {
         env!("CARGO_PKG_VERSION")
     );
 
+    install_log_redaction();
+
     let args = cli::parse_args();
     let second_process = std::env::var(CHILD_PROCESS_ENV).is_ok();
 
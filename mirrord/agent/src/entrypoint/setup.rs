@@ -1,3 +1,5 @@
+use std::convert::Infallible;
+
 use mirrord_agent_env::envs;
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
@@ -10,8 +12,11 @@ use crate::{
         self, MirrorHandle, RedirectorTask, RedirectorTaskConfig, StealHandle,
         tls::StealTlsHandlerStore,
     },
-    steal::{StealerCommand, TcpStealerTask},
-    task::{BgTaskRuntime, status::IntoStatus},
+    steal::{PassthroughCache, ProbeResponder, StealerCommand, TcpStealerTask},
+    task::{
+        BgTaskRuntime,
+        supervisor::{Criticality, Supervisor},
+    },
     util::path_resolver::InTargetPathResolver,
 };
 
@@ -20,6 +25,7 @@ use crate::{
 /// Returns the [`StealHandle`] that can be used to steal incoming traffic.
 pub(super) async fn start_traffic_redirector(
     runtime: &BgTaskRuntime,
+    supervisor: &Supervisor,
     target_pid: u64,
     with_mesh_exclusion: Option<u16>,
 ) -> AgentResult<(StealHandle, MirrorHandle)> {
@@ -51,13 +57,14 @@ pub(super) async fn start_traffic_redirector(
     .map_err(|error| AgentError::IPTablesSetupError(error.into()))?
     .map_err(|error| AgentError::IPTablesSetupError(error.into()))?;
 
-    tokio::spawn(task.run());
+    supervisor.spawn("RedirectorTask", Criticality::Critical, task.run());
 
     Ok((steal_handle, mirror_handle))
 }
 
 pub(super) fn start_stealer(
     runtime: &BgTaskRuntime,
+    supervisor: &Supervisor,
     steal_handle: StealHandle,
     cancellation_token: CancellationToken,
 ) -> BackgroundTask<StealerCommand> {
@@ -67,9 +74,21 @@ pub(super) fn start_stealer(
 
     let (command_tx, command_rx) = mpsc::channel::<StealerCommand>(1000);
 
-    let task_status =
-        tokio::spawn(TcpStealerTask::new(command_rx, steal_handle).run(cancellation_token))
-            .into_status("TcpStealerTask");
+    let conflict_policy = envs::STEAL_CONFLICT_POLICY.from_env_or_default();
+    let probes = ProbeResponder::new(envs::PROBE_PATHS.from_env_or_default());
+    let passthrough_cache = PassthroughCache::from_env();
+    let task_status = supervisor.spawn(
+        "TcpStealerTask",
+        Criticality::Critical,
+        TcpStealerTask::new(
+            command_rx,
+            steal_handle,
+            conflict_policy,
+            probes,
+            passthrough_cache,
+        )
+        .run(cancellation_token),
+    );
 
     BackgroundTask::Running(task_status, command_tx)
 }
@@ -77,6 +96,7 @@ pub(super) fn start_stealer(
 pub(super) fn start_dns(
     args: &super::Args,
     runtime: &BgTaskRuntime,
+    supervisor: &Supervisor,
     cancellation_token: CancellationToken,
 ) -> BackgroundTask<DnsCommand> {
     // IMPORTANT: this makes tokio tasks spawn on `runtime`.
@@ -85,10 +105,11 @@ pub(super) fn start_dns(
 
     let (command_tx, command_rx) = mpsc::channel::<DnsCommand>(1000);
 
-    let task_status = tokio::spawn(
-        DnsWorker::new(runtime.target_pid(), command_rx, args.ipv6).run(cancellation_token.clone()),
-    )
-    .into_status("DnsTask");
+    let worker = DnsWorker::new(runtime.target_pid(), command_rx, args.ipv6);
+    let task_status = supervisor.spawn("DnsTask", Criticality::Critical, async move {
+        worker.run(cancellation_token).await;
+        Ok::<_, Infallible>(())
+    });
 
     BackgroundTask::Running(task_status, command_tx)
 }
@@ -1,5 +1,6 @@
 use std::{process::ExitStatus, sync::Arc};
 
+use mirrord_protocol::quota::QuotaExceeded;
 use thiserror::Error;
 
 use crate::{
@@ -51,6 +52,18 @@ pub(crate) enum AgentError {
         Box<FilterCreationError>,
     ),
 
+    #[error("Failed to parse the mandatory HTTP header filter: {0}")]
+    InvalidMandatoryHttpFilter(
+        /// Boxed due to large size difference.
+        Box<fancy_regex::Error>,
+    ),
+
+    #[error("Failed to parse the given connection filter: {0}")]
+    InvalidConnectionFilter(
+        /// Boxed due to large size difference.
+        Box<fancy_regex::Error>,
+    ),
+
     #[error("Timeout on accepting first client connection")]
     FirstConnectionTimeout,
 
@@ -63,11 +76,17 @@ pub(crate) enum AgentError {
     #[error("IP tables dirty")]
     IPTablesDirty,
 
+    #[error("QUIC setup failed: {0}")]
+    QuicSetupError(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+
     #[error("Failed to start a tokio runtime in the target's namespace: {0}")]
     RemoteRuntimeError(#[from] AgentRuntimeError),
 
     #[error(transparent)]
     Timeout(#[from] tokio::time::error::Elapsed),
+
+    #[error("mirrord quota exceeded: {0}")]
+    QuotaExceeded(#[from] QuotaExceeded),
 }
 
 pub(crate) type AgentResult<T, E = AgentError> = std::result::Result<T, E>;
@@ -94,6 +94,95 @@ impl Iterator for GetDEnts64Stream {
     }
 }
 
+/// Upper bound for [`ReadAheadRequest::chunk_size`].
+const READ_AHEAD_MAX_CHUNK: u64 = 1024 * 1024;
+
+/// Reads the chunks of a [`ReadAheadRequest`] one by one, see [`FileManager::read_ahead`].
+#[derive(Debug)]
+pub(crate) struct ReadAheadChunks {
+    remote_fd: u64,
+    /// Duplicated descriptor of the file, so that this iterator does not borrow the
+    /// [`FileManager`].
+    ///
+    /// [`None`] after the last chunk was read.
+    file: Option<File>,
+    /// Set in [`Self::cancel`], the next chunk is the final empty one.
+    cancelled: bool,
+    /// Error to be returned as the only item.
+    error: Option<ResponseError>,
+    position: u64,
+    end: u64,
+    chunk_size: u64,
+}
+
+impl Iterator for ReadAheadChunks {
+    type Item = RemoteResult<ReadAheadChunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+
+        if self.cancelled {
+            self.cancelled = false;
+            return Some(Ok(ReadAheadChunk {
+                start_from: self.position,
+                bytes: Default::default(),
+                eof: false,
+                last: true,
+            }));
+        }
+
+        let file = self.file.as_ref()?;
+        let amount = self.chunk_size.min(self.end.saturating_sub(self.position));
+        let mut buffer = vec![0; amount as usize];
+
+        let read_amount = match file.read_at(&mut buffer, self.position) {
+            Ok(read_amount) => read_amount,
+            Err(error) => {
+                self.file = None;
+                return Some(Err(error.into()));
+            }
+        };
+        buffer.truncate(read_amount);
+
+        let start_from = self.position;
+        self.position += read_amount as u64;
+
+        // Regular files return short reads only at the end.
+        let eof = (read_amount as u64) < amount;
+        let last = eof || self.position >= self.end;
+        if last {
+            self.file = None;
+        }
+
+        Some(Ok(ReadAheadChunk {
+            start_from,
+            bytes: buffer.into(),
+            eof,
+            last,
+        }))
+    }
+}
+
+impl ReadAheadChunks {
+    /// Descriptor of the prefetched file.
+    pub(crate) fn remote_fd(&self) -> u64 {
+        self.remote_fd
+    }
+
+    /// Stops reading the file, see
+    /// [`CancelRequest::ReadAhead`](mirrord_protocol::cancel::CancelRequest::ReadAhead).
+    ///
+    /// If the series of chunks is not finished yet, it ends with an empty chunk marked as
+    /// [`ReadAheadChunk::last`].
+    pub(crate) fn cancel(&mut self) {
+        if self.file.take().is_some() {
+            self.cancelled = true;
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct FileManager {
     /// [`None`] when targetless.
@@ -264,12 +353,24 @@ impl FileManager {
             FileRequest::Fchmod(FchmodRequest { fd, mode }) => {
                 Some(FileResponse::Fchmod(self.fchmod(fd, mode)))
             }
+            FileRequest::Fsync(FsyncRequest { fd, data_only }) => {
+                Some(FileResponse::Fsync(self.fsync(fd, data_only)))
+            }
+            FileRequest::FileLock(FileLockRequest { fd, kind, range }) => {
+                Some(FileResponse::FileLock(self.lock(fd, kind, range)))
+            }
+            FileRequest::GetCwd(GetCwdRequest) => Some(FileResponse::GetCwd(self.cwd())),
+            FileRequest::ReadAhead(..) => {
+                // Results in multiple responses, must be handled with `Self::read_ahead`.
+                error!("file_worker -> unexpected read ahead request");
+                Some(FileResponse::ReadAhead(Err(ResponseError::NotImplemented)))
+            }
         })
     }
 
     #[tracing::instrument(level = Level::TRACE, ret)]
-    pub fn new(pid: Option<u64>) -> Self {
-        let path_resolver = pid.map(InTargetPathResolver::new);
+    pub fn new(pid: Option<u64>, procfs: bool) -> Self {
+        let path_resolver = pid.map(|pid| InTargetPathResolver::new(pid).remap_proc_self(procfs));
 
         Self {
             path_resolver,
@@ -416,6 +517,42 @@ impl FileManager {
             })
     }
 
+    /// Prepares the chunks to be sent in response to a [`ReadAheadRequest`].
+    ///
+    /// The chunks are read lazily, when the returned iterator is advanced.
+    #[tracing::instrument(level = Level::TRACE, skip(self))]
+    pub(crate) fn read_ahead(&mut self, request: ReadAheadRequest) -> ReadAheadChunks {
+        let ReadAheadRequest {
+            remote_fd,
+            start_from,
+            chunk_size,
+            window,
+        } = request;
+
+        let file = self
+            .open_files
+            .get(&remote_fd)
+            .ok_or(ResponseError::NotFound(remote_fd))
+            .and_then(|remote_file| match remote_file {
+                RemoteFile::File(file) => Ok(file.try_clone()?),
+                RemoteFile::Directory(..) => Err(ResponseError::NotFile(remote_fd)),
+            });
+        let (file, error) = match file {
+            Ok(file) => (Some(file), None),
+            Err(error) => (None, Some(error)),
+        };
+
+        ReadAheadChunks {
+            remote_fd,
+            file,
+            cancelled: false,
+            error,
+            position: start_from,
+            end: start_from.saturating_add(window.min(READ_AHEAD_MAX_WINDOW)),
+            chunk_size: chunk_size.clamp(1, READ_AHEAD_MAX_CHUNK),
+        }
+    }
+
     /// Handles our `readlink_detour` with [`std::fs::read_link`].
     #[tracing::instrument(level = Level::TRACE, skip_all)]
     pub(crate) fn read_link(&mut self, path: PathBuf) -> RemoteResult<ReadLinkFileResponse> {
@@ -644,6 +781,83 @@ impl FileManager {
         }
     }
 
+    /// Flushes the file to its storage device, with [`File::sync_data`] if `data_only` is set,
+    /// otherwise with [`File::sync_all`].
+    pub(crate) fn fsync(&mut self, fd: u64, data_only: bool) -> RemoteResult<()> {
+        let file = self
+            .open_files
+            .get(&fd)
+            .ok_or(ResponseError::NotFound(fd))?;
+
+        match file {
+            RemoteFile::File(file) if data_only => Ok(file.sync_data()?),
+            RemoteFile::File(file) => Ok(file.sync_all()?),
+            _ => Err(ResponseError::NotFile(fd)),
+        }
+    }
+
+    /// Places or removes an advisory lock on the file, without blocking.
+    ///
+    /// Whole file locks use `flock(2)`, record locks use open file description locks
+    /// (`F_OFD_SETLK`), so that locks taken through different remote fds conflict with each
+    /// other, even though they are all held by the agent process.
+    pub(crate) fn lock(
+        &mut self,
+        fd: u64,
+        kind: FileLockKind,
+        range: Option<FileLockRange>,
+    ) -> RemoteResult<()> {
+        let file = match self.open_files.get(&fd) {
+            Some(RemoteFile::File(file)) => file,
+            Some(RemoteFile::Directory(..)) => return Err(ResponseError::NotFile(fd)),
+            None => return Err(ResponseError::NotFound(fd)),
+        };
+
+        let result = match range {
+            None => {
+                let operation = match kind {
+                    FileLockKind::Shared => libc::LOCK_SH,
+                    FileLockKind::Exclusive => libc::LOCK_EX,
+                    FileLockKind::Unlock => libc::LOCK_UN,
+                };
+
+                unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) }
+            }
+            Some(FileLockRange { whence, start, len }) => {
+                let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+                lock.l_type = match kind {
+                    FileLockKind::Shared => libc::F_RDLCK,
+                    FileLockKind::Exclusive => libc::F_WRLCK,
+                    FileLockKind::Unlock => libc::F_UNLCK,
+                } as _;
+                lock.l_whence = match whence {
+                    FileLockWhence::Start => libc::SEEK_SET,
+                    FileLockWhence::Current => libc::SEEK_CUR,
+                    FileLockWhence::End => libc::SEEK_END,
+                } as _;
+                lock.l_start = start;
+                lock.l_len = len;
+
+                unsafe { libc::fcntl(file.as_raw_fd(), libc::F_OFD_SETLK, &lock) }
+            }
+        };
+
+        match result {
+            -1 => Err(ResponseError::from(io::Error::last_os_error())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the working directory of the target process, or of the agent when targetless.
+    pub(crate) fn cwd(&self) -> RemoteResult<GetCwdResponse> {
+        let path = match self.path_resolver.as_ref() {
+            Some(resolver) => resolver.target_cwd()?,
+            None => std::env::current_dir()?,
+        };
+
+        Ok(GetCwdResponse { path })
+    }
+
     pub(crate) fn seek(&mut self, fd: u64, seek_from: SeekFrom) -> RemoteResult<SeekFileResponse> {
         trace!(
             "FileManager::seek -> fd {:#?} | seek_from {:#?}",
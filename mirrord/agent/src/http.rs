@@ -2,14 +2,16 @@ use std::{io, ops::Not, time::Duration};
 
 use bytes::{Bytes, BytesMut};
 use futures::future::OptionFuture;
-use http::Response;
+use http::{
+    HeaderMap, HeaderValue, Response, Version,
+    header::{CACHE_CONTROL, CONTENT_LENGTH, EXPECT},
+    request::Parts,
+};
 use http_body_util::combinators::BoxBody;
-use httparse::Status;
 use tokio::{
     io::{AsyncRead, AsyncReadExt},
     time::Instant,
 };
-use tracing::Level;
 
 use crate::util::rolledback_stream::RolledBackStream;
 
@@ -18,6 +20,9 @@ pub mod error;
 pub mod extract_requests;
 pub mod filter;
 pub mod sender;
+mod version;
+
+pub use version::{DetectedHttpVersion, HttpVersion};
 
 /// When the corresponding config flag is enabled, a header with this
 /// name is injected into http responses. See
@@ -27,90 +32,31 @@ pub(crate) const MIRRORD_AGENT_HTTP_HEADER_NAME: &str = "Mirrord-Agent";
 /// [`Response`] type with a boxed body.
 pub type BoxResponse = Response<BoxBody<Bytes, hyper::Error>>;
 
-/// Helper enum for representing HTTP/1.x and HTTP/2, which are handled very differently in some
-/// parts of the code.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub enum HttpVersion {
-    /// HTTP/1.X
-    V1,
-
-    /// HTTP/2
-    V2,
-}
-
-impl HttpVersion {
-    /// Default start of an HTTP/2 request.
-    ///
-    /// Used in [`Self::detect`] to check if the connection should be treated as HTTP/2.
-    const H2_PREFACE: &'static [u8; 14] = b"PRI * HTTP/2.0";
-
-    /// Checks if the given `buffer` contains a prefix of a valid HTTP/1.x or HTTP/2 request.
-    #[tracing::instrument(level = Level::TRACE, ret)]
-    pub fn detect(buffer: &[u8]) -> DetectedHttpVersion {
-        if buffer.starts_with(Self::H2_PREFACE) {
-            return DetectedHttpVersion::Http(Self::V2);
-        }
-
-        // We parse only the first line of the request,
-        // so we don't have to worry about header edge cases.
-        let buffer = buffer
-            .split_inclusive(|b| *b == b'\n')
-            .next()
-            .unwrap_or(buffer);
-        let mut empty_headers = [httparse::EMPTY_HEADER; 0];
-        let mut request = httparse::Request::new(&mut empty_headers);
-        let result = httparse::ParserConfig::default()
-            .allow_multiple_spaces_in_request_line_delimiters(true)
-            .parse_request(&mut request, buffer);
-
-        match result {
-            Ok(Status::Complete(..)) => DetectedHttpVersion::Http(Self::V1),
-            Ok(Status::Partial) => match request.version {
-                Some(..) => DetectedHttpVersion::Http(Self::V1),
-                // If we haven't read enough bytes to consume the HTTP version,
-                // we're not certain yet.
-                None => DetectedHttpVersion::Unknown,
-            },
-            // We use a zero-length header array,
-            // so this means we successfully parsed the method, uri and version.
-            Err(httparse::Error::TooManyHeaders) => DetectedHttpVersion::Http(Self::V1),
-            Err(..) => DetectedHttpVersion::NotHttp,
-        }
-    }
-}
-
-/// Output of HTTP version detection on an prefix of an incoming stream.
-#[derive(PartialEq, Eq, Debug)]
-pub enum DetectedHttpVersion {
-    /// We're certain that the stream is an HTTP connection.
-    Http(HttpVersion),
-    /// We're not sure yet.
-    Unknown,
-    /// We're certain that the stream is **not** an HTTP connection.
-    NotHttp,
+/// Whether the client waits for an interim `100 Continue` response before sending the request
+/// body.
+///
+/// hyper sends the interim response when the request body is first polled.
+pub fn expects_continue(parts: &Parts) -> bool {
+    parts.version == Version::HTTP_11
+        && parts
+            .headers
+            .get(EXPECT)
+            .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"100-continue"))
 }
 
-impl DetectedHttpVersion {
-    /// If the stream is known to be an HTTP connection,
-    /// returns its version.
-    ///
-    /// Otherwise, returns [`None`].
-    pub fn into_version(self) -> Option<HttpVersion> {
-        match self {
-            Self::Http(version) => Some(version),
-            Self::Unknown => None,
-            Self::NotHttp => None,
-        }
-    }
-
-    /// Returns whether it's known whether the stream is an HTTP connection or not.
-    pub fn is_known(&self) -> bool {
-        match self {
-            Self::Http(_) => true,
-            Self::Unknown => false,
-            Self::NotHttp => true,
-        }
-    }
+/// Prepares the headers of a server-sent events response, so that the events reach the original
+/// HTTP client as soon as the local application produces them.
+///
+/// The response body is streamed, so we drop any `Content-Length`, and ask the proxies between us
+/// and the client (e.g. an nginx ingress) not to buffer or cache the response.
+pub fn disable_event_stream_buffering(headers: &mut HeaderMap) {
+    headers.remove(CONTENT_LENGTH);
+    headers
+        .entry(CACHE_CONTROL)
+        .or_insert(HeaderValue::from_static("no-cache"));
+    headers
+        .entry("x-accel-buffering")
+        .or_insert(HeaderValue::from_static("no"));
 }
 
 /// Attempts to detect HTTP version from the first bytes of a stream.
@@ -159,30 +105,33 @@ where
 
 #[cfg(test)]
 mod test {
-    use rstest::rstest;
-
-    use super::{DetectedHttpVersion, HttpVersion};
-
-    #[rstest]
-    #[case::known_bug(b"hello ther", DetectedHttpVersion::Unknown)]
-    #[case::http2(b"PRI * HTTP/2.0", DetectedHttpVersion::Http(HttpVersion::V2))]
-    #[case::http11_full(b"GET / HTTP/1.1\r\n\r\n", DetectedHttpVersion::Http(HttpVersion::V1))]
-    #[case::http10_full(b"GET / HTTP/1.0\r\n\r\n", DetectedHttpVersion::Http(HttpVersion::V1))]
-    #[case::custom_method(b"FOO / HTTP/1.1\r\n\r\n", DetectedHttpVersion::Http(HttpVersion::V1))]
-    #[case::extra_spaces(b"GET / asd d HTTP/1.1\r\n\r\n", DetectedHttpVersion::NotHttp)]
-    #[case::bad_version_1(b"GET / HTTP/a\r\n\r\n", DetectedHttpVersion::NotHttp)]
-    #[case::bad_version_2(b"GET / HTTP/2\r\n\r\n", DetectedHttpVersion::NotHttp)]
-    #[case::multiple_spaces(
-        b"GET   /  HTTP/1.1\r\n\r\n",
-        DetectedHttpVersion::Http(HttpVersion::V1)
-    )]
-    #[case::bad_header(
-        b"GET / HTTP/1.1\r\n Host: \r\n\r\n",
-        DetectedHttpVersion::Http(HttpVersion::V1)
-    )]
+    use std::ops::Not;
+
+    use http::{HeaderMap, HeaderValue, header};
+    use mirrord_protocol::tcp::is_event_stream;
+
+    use super::disable_event_stream_buffering;
+
     #[test]
-    fn http_detect(#[case] input: &[u8], #[case] expected: DetectedHttpVersion) {
-        let detected = HttpVersion::detect(input);
-        assert_eq!(detected, expected,)
+    fn event_stream_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("Text/Event-Stream; charset=utf-8"),
+        );
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from_static("42"));
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        assert!(is_event_stream(&headers));
+
+        disable_event_stream_buffering(&mut headers);
+        assert!(headers.get(header::CONTENT_LENGTH).is_none());
+        assert_eq!(headers.get(header::CACHE_CONTROL).unwrap(), "no-store");
+        assert_eq!(headers.get("x-accel-buffering").unwrap(), "no");
+
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        assert!(is_event_stream(&headers).not());
     }
 }
@@ -19,10 +19,13 @@ use hyper::{
     upgrade::OnUpgrade,
 };
 use hyper_util::rt::TokioExecutor;
-use mirrord_protocol::batched_body::{BatchedBody, Frames};
+use mirrord_protocol::{
+    batched_body::{BatchedBody, Frames},
+    redact::redactor,
+};
 use tokio::sync::{mpsc, oneshot};
 
-use super::{BoxResponse, HttpVersion, error::MirrordErrorResponse};
+use super::{BoxResponse, HttpVersion, error::MirrordErrorResponse, expects_continue};
 use crate::metrics::{MetricGuard, REDIRECTED_REQUESTS};
 
 /// An HTTP request extracted from an HTTP connection
@@ -31,6 +34,8 @@ pub struct ExtractedRequest {
     /// Parts of the request.
     pub parts: Parts,
     /// First frames of the request body.
+    ///
+    /// Always empty if the client sent `Expect: 100-continue`, see [`expects_continue`].
     pub body_head: Vec<Frame<Bytes>>,
     /// Rest of the request body frames (if any).
     pub body_tail: Option<Incoming>,
@@ -44,11 +49,24 @@ pub struct ExtractedRequest {
     pub response_tx: oneshot::Sender<BoxResponse>,
 }
 
+/// Headers and body are formatted with the global
+/// [`Redactor`](mirrord_protocol::redact::Redactor).
 impl fmt::Debug for ExtractedRequest {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redactor = redactor();
+        let body_head = self
+            .body_head
+            .iter()
+            .filter_map(|frame| frame.data_ref())
+            .map(|data| redactor.body(data))
+            .collect::<Vec<_>>();
+
         f.debug_struct("ExtractedRequest")
-            .field("parts", &self.parts)
-            .field("body_head", &self.body_head)
+            .field("method", &self.parts.method)
+            .field("uri", &self.parts.uri)
+            .field("version", &self.parts.version)
+            .field("headers", &redactor.headers(&self.parts.headers))
+            .field("body_head", &body_head)
             .field("has_more_body", &self.body_tail.is_some())
             .finish()
     }
@@ -123,6 +141,20 @@ where
             if let Poll::Ready(Some((mut request, response_tx))) = this.request_rx.poll_recv(cx) {
                 let upgrade = hyper::upgrade::on(&mut request);
                 let (parts, mut body) = request.into_parts();
+
+                // Polling the body would make hyper send `100 Continue` right away, before we
+                // know whether anyone is going to read the body. We leave it to whoever handles
+                // the request (the stealing client, the original destination, or a body filter).
+                if expects_continue(&parts) {
+                    break Poll::Ready(Some(Ok(ExtractedRequest {
+                        parts,
+                        body_head: Vec::new(),
+                        body_tail: body.is_end_stream().not().then_some(body),
+                        upgrade,
+                        response_tx,
+                    })));
+                }
+
                 let Frames { frames, is_last } = match body.ready_frames() {
                     Ok(frames) => frames,
                     Err(error) => {
@@ -241,7 +273,7 @@ impl Body for MetricGuardedBody {
 
 #[cfg(test)]
 mod test {
-    use std::sync::Arc;
+    use std::{sync::Arc, time::Duration};
 
     use bytes::Bytes;
     use futures::StreamExt;
@@ -250,6 +282,7 @@ mod test {
     use hyper_util::rt::TokioIo;
     use rstest::rstest;
     use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
         net::{TcpListener, TcpStream},
         sync::Notify,
     };
@@ -345,4 +378,53 @@ mod test {
 
         client.await.unwrap();
     }
+
+    /// Verifies that [`ExtractedRequests`] does not poll the body of a request with
+    /// `Expect: 100-continue`, so that the interim response is sent only when the body is read.
+    #[tokio::test]
+    async fn extract_requests_expect_continue() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(
+                b"POST / HTTP/1.1\r\nHost: test\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut requests = ExtractedRequests::new(TokioIo::new(stream), HttpVersion::V1);
+
+        let request = requests.next().await.unwrap().unwrap();
+        assert!(request.body_head.is_empty());
+        let mut body = request.body_tail.unwrap();
+
+        // The connection makes progress only when `requests` is polled.
+        let mut buf = [0_u8; 64];
+        tokio::select! {
+            _ = requests.next() => unreachable!("connection should wait for the response"),
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+            _ = client.read(&mut buf) => panic!("interim response sent before the body was polled"),
+        }
+
+        let client_side = async {
+            let read = client.read(&mut buf).await.unwrap();
+            assert!(
+                buf.get(..read)
+                    .unwrap()
+                    .starts_with(b"HTTP/1.1 100 Continue\r\n")
+            );
+            client.write_all(b"hello").await.unwrap();
+        };
+        let frame = tokio::select! {
+            _ = requests.next() => unreachable!("connection should wait for the response"),
+            (frame, ()) = futures::future::join(body.frame(), client_side) => frame,
+        };
+        assert_eq!(
+            frame.unwrap().unwrap().into_data().unwrap(),
+            Bytes::from_static(b"hello")
+        );
+    }
 }
@@ -1,8 +1,15 @@
-use std::{fmt::Debug, io::Read};
+//! [`HttpFilter`] matching on the stolen requests.
+//!
+//! Does not depend on the rest of the agent, so that it can be included in the `http` benchmark.
+
+use std::{fmt::Debug, io::Read, ops::Not};
 
 use fancy_regex::Regex;
-use hyper::http::request::Parts;
-use mirrord_protocol::tcp::HttpMethodFilter;
+use hyper::http::{HeaderName, header::InvalidHeaderName, request::Parts};
+use mirrord_protocol::{
+    redact::redactor,
+    tcp::{self, HttpMethodFilter},
+};
 use serde_json::Value;
 use serde_json_path::JsonPath;
 use tracing::Level;
@@ -27,6 +34,15 @@ pub enum HttpFilter {
 
     /// Filter based on request body
     Body(HttpBodyFilter),
+
+    /// Matches requests that do not match the inner filter.
+    Not(Box<HttpFilter>),
+
+    /// Matches requests with a `name` header whose value starts with `prefix`.
+    HeaderPrefix {
+        name: HeaderName,
+        prefix: String,
+    },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -36,6 +52,9 @@ pub enum FilterCreationError {
 
     #[error("error compiling jsonpath query: {0}")]
     JsonPath(#[from] serde_json_path::ParseError),
+
+    #[error("invalid header name: {0}")]
+    HeaderName(#[from] InvalidHeaderName),
 }
 
 impl TryFrom<&mirrord_protocol::tcp::HttpFilter> for HttpFilter {
@@ -44,7 +63,7 @@ impl TryFrom<&mirrord_protocol::tcp::HttpFilter> for HttpFilter {
     fn try_from(filter: &mirrord_protocol::tcp::HttpFilter) -> Result<Self, Self::Error> {
         match filter {
             mirrord_protocol::tcp::HttpFilter::Header(header) => {
-                Ok(Self::Header(Regex::new(&format!("(?i){header}"))?))
+                Ok(Self::Header(header.header_regex()?))
             }
             mirrord_protocol::tcp::HttpFilter::Path(path) => {
                 Ok(Self::Path(Regex::new(&format!("(?i){path}"))?))
@@ -61,6 +80,15 @@ impl TryFrom<&mirrord_protocol::tcp::HttpFilter> for HttpFilter {
             mirrord_protocol::tcp::HttpFilter::Body(http_body_filter) => {
                 Ok(Self::Body(http_body_filter.try_into()?))
             }
+            mirrord_protocol::tcp::HttpFilter::Not(filter) => {
+                Ok(Self::Not(Box::new(filter.as_ref().try_into()?)))
+            }
+            mirrord_protocol::tcp::HttpFilter::HeaderPrefix { name, prefix } => {
+                Ok(Self::HeaderPrefix {
+                    name: HeaderName::from_bytes(name.as_bytes())?,
+                    prefix: prefix.clone(),
+                })
+            }
         }
     }
 }
@@ -90,18 +118,7 @@ impl HttpFilter {
         match self {
             Self::Header(filter) => {
                 let headers = parts.extensions.get_or_insert_with(|| {
-                    let normalized = parts
-                        .headers
-                        .iter()
-                        .filter_map(|(header_name, header_value)| {
-                            header_value
-                                .to_str()
-                                .ok()
-                                .map(|header_value| format!("{header_name}: {header_value}"))
-                        })
-                        .collect::<Vec<_>>();
-
-                    NormalizedHeaders(normalized)
+                    NormalizedHeaders(tcp::header_lines(&parts.headers, &parts.uri))
                 });
 
                 headers.has_match(filter)
@@ -172,6 +189,38 @@ impl HttpFilter {
                     }
                 }
             }
+            Self::Not(filter) => filter.matches(parts, body).not(),
+            Self::HeaderPrefix { name, prefix } => {
+                tcp::header_values_with_prefix(&parts.headers, name, prefix)
+                    .next()
+                    .is_some()
+            }
+        }
+    }
+
+    /// Number of conditions that a request must meet to match this filter.
+    ///
+    /// Used to pick the most specific filter when the filters of many clients match the same
+    /// request, see [`StealConflictPolicy::MostSpecificFilter`].
+    ///
+    /// [`StealConflictPolicy::MostSpecificFilter`]: mirrord_agent_env::steal_conflict::StealConflictPolicy::MostSpecificFilter
+    pub fn specificity(&self) -> usize {
+        match self {
+            Self::Composite { all: true, filters } => filters.iter().map(Self::specificity).sum(),
+            Self::Composite {
+                all: false,
+                filters,
+            } => filters
+                .iter()
+                .map(Self::specificity)
+                .min()
+                .unwrap_or_default(),
+            Self::Not(filter) => filter.specificity(),
+            Self::Header(..)
+            | Self::Path(..)
+            | Self::Method(..)
+            | Self::Body(..)
+            | Self::HeaderPrefix { .. } => 1,
         }
     }
 
@@ -179,6 +228,7 @@ impl HttpFilter {
         match self {
             HttpFilter::Composite { filters, .. } => filters.iter().any(HttpFilter::needs_body),
             HttpFilter::Body(_) => true,
+            HttpFilter::Not(filter) => filter.needs_body(),
             _ => false,
         }
     }
@@ -197,7 +247,12 @@ impl NormalizedHeaders {
             regex
                 .is_match(header)
                 .inspect_err(|error| {
-                    tracing::error!(header, ?regex, ?error, "Error while matching header");
+                    tracing::error!(
+                        header = %redactor().header_line(header),
+                        ?regex,
+                        ?error,
+                        "Error while matching header",
+                    );
                 })
                 .unwrap_or_default()
         })
@@ -208,7 +263,7 @@ impl NormalizedHeaders {
 mod test {
     use std::{ops::Not, str::FromStr};
 
-    use hyper::Request;
+    use hyper::{Request, Version};
     use mirrord_protocol::tcp::{self, Filter, HttpMethodFilter};
 
     use super::HttpFilter;
@@ -285,4 +340,152 @@ mod test {
         let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
         assert!(!filter.matches::<&[u8]>(&mut input, None));
     }
+
+    #[test]
+    fn matching_not_filter() {
+        let tcp_filter = tcp::HttpFilter::Composite {
+            all: true,
+            filters: vec![
+                tcp::HttpFilter::Path(Filter::new("^/api/".to_string()).unwrap()),
+                tcp::HttpFilter::Not(Box::new(tcp::HttpFilter::Header(
+                    Filter::new("^x-canary: true$".to_string()).unwrap(),
+                ))),
+            ],
+        };
+        let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
+
+        // should match
+        let mut input = Request::builder()
+            .uri("https://www.balconia.gov/api/path/to/v1")
+            .header("x-canary", "false")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches::<&[u8]>(&mut input, None));
+
+        // should fail
+        let mut input = Request::builder()
+            .uri("https://www.balconia.gov/api/path/to/v1")
+            .header("x-canary", "true")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches::<&[u8]>(&mut input, None).not());
+    }
+
+    #[test]
+    fn matching_header_prefix_filter() {
+        let tcp_filter = tcp::HttpFilter::HeaderPrefix {
+            name: "X-Request-Id".to_string(),
+            prefix: "alice-".to_string(),
+        };
+        let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
+
+        // should match, header names are case-insensitive
+        let mut input = Request::builder()
+            .uri("/api")
+            .header("x-request-id", "alice-1234")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches::<&[u8]>(&mut input, None));
+
+        // should fail, values are case-sensitive
+        let mut input = Request::builder()
+            .uri("/api")
+            .header("x-request-id", "Alice-1234")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches::<&[u8]>(&mut input, None).not());
+
+        // should fail, the prefix is in another header
+        let mut input = Request::builder()
+            .uri("/api")
+            .header("x-correlation-id", "alice-1234")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches::<&[u8]>(&mut input, None).not());
+    }
+
+    #[test]
+    fn matching_host_without_header() {
+        let tcp_filter =
+            tcp::HttpFilter::Header(Filter::new("^host: www.balconia.gov$".to_string()).unwrap());
+        let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
+
+        // should match, absolute URI of an HTTP/1.0 request
+        let mut input = Request::builder()
+            .uri("http://www.balconia.gov/health")
+            .version(Version::HTTP_10)
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches::<&[u8]>(&mut input, None));
+
+        // should match, the `host` header takes precedence over the URI
+        let mut input = Request::builder()
+            .uri("http://10.0.0.1/health")
+            .header("host", "www.balconia.gov")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches::<&[u8]>(&mut input, None));
+
+        // should fail, no host at all
+        let mut input = Request::builder()
+            .uri("/health")
+            .version(Version::HTTP_10)
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches::<&[u8]>(&mut input, None).not());
+    }
+
+    #[test]
+    fn filter_specificity() {
+        let header = || tcp::HttpFilter::Header(Filter::new("x-user: me".to_string()).unwrap());
+        let path = || tcp::HttpFilter::Path(Filter::new("^/api/".to_string()).unwrap());
+
+        let specificity =
+            |filter: tcp::HttpFilter| HttpFilter::try_from(&filter).unwrap().specificity();
+
+        assert_eq!(specificity(header()), 1);
+        assert_eq!(
+            specificity(tcp::HttpFilter::Composite {
+                all: true,
+                filters: vec![header(), path()],
+            }),
+            2
+        );
+        assert_eq!(
+            specificity(tcp::HttpFilter::Composite {
+                all: false,
+                filters: vec![
+                    header(),
+                    tcp::HttpFilter::Composite {
+                        all: true,
+                        filters: vec![header(), path()],
+                    },
+                ],
+            }),
+            1
+        );
+        assert_eq!(
+            specificity(tcp::HttpFilter::Not(Box::new(tcp::HttpFilter::Composite {
+                all: true,
+                filters: vec![header(), path()],
+            }))),
+            2
+        );
+    }
 }
@@ -1,13 +1,20 @@
 //! This module contains components that implement redirecting incoming traffic.
 
+mod capture;
 mod composed;
 mod connection;
 mod error;
 mod iptables;
 mod mirror_handle;
+mod peek;
+pub mod postgres;
+mod probation;
+pub mod redis;
 mod steal_handle;
 mod task;
 pub mod tls;
+#[cfg(feature = "io-uring")]
+mod uring;
 
 use std::{
     fmt,
@@ -18,8 +25,11 @@ use std::{
 
 use composed::ComposedRedirector;
 pub use connection::{
-    IncomingStream, IncomingStreamItem,
-    http::{MirroredHttp, RedirectedHttp, ResponseBodyProvider, ResponseProvider, StolenHttp},
+    ConnectionInfo, ConnectionPeeks, IncomingStream, IncomingStreamItem,
+    http::{
+        MirroredHttp, RedirectedHttp, ResponseBodyProvider, ResponseProvider, StolenHttp,
+        send_to_original_destination,
+    },
     tcp::{RedirectedTcp, StolenTcp},
 };
 pub use error::{ConnError, RedirectorTaskError};
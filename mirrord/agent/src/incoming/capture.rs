@@ -0,0 +1,572 @@
+//! Capturing raw bytes of stolen connections, enabled with
+//! [`CAPTURE_STOLEN_DIR`](mirrord_agent_env::envs::CAPTURE_STOLEN_DIR).
+//!
+//! Bytes are captured before any HTTP parsing, but after TLS is terminated (when the agent
+//! terminates TLS on the port). Each connection gets two files in the capture directory:
+//! `<id>.in.bin` with the bytes received from the peer, and `<id>.out.bin` with the bytes sent to
+//! the peer. `index.json` describes all captured connections.
+//!
+//! The files are written in a background task, so that the connections never wait on the disk.
+
+use std::{
+    collections::VecDeque,
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+    time::SystemTime,
+};
+
+use bytes::Bytes;
+use mirrord_agent_env::envs;
+use serde::Serialize;
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf},
+    sync::mpsc,
+};
+
+use super::ConnectionInfo;
+
+/// Name of the index file in the capture directory.
+const INDEX_FILE: &str = "index.json";
+
+/// Where and how much of the stolen traffic is captured.
+#[derive(Debug, Clone)]
+pub struct CaptureConfig {
+    /// Directory where the captures are written.
+    pub dir: PathBuf,
+    /// Maximal number of bytes captured in each direction of a single connection.
+    pub max_connection_bytes: u64,
+    /// Maximal total size of the captures.
+    ///
+    /// When exceeded, captures of the oldest finished connections are removed.
+    pub max_total_bytes: u64,
+}
+
+impl CaptureConfig {
+    /// Default for [`CaptureConfig::max_connection_bytes`], 1 MiB.
+    const DEFAULT_MAX_CONNECTION_BYTES: u64 = 1024 * 1024;
+    /// Default for [`CaptureConfig::max_total_bytes`], 64 MiB.
+    const DEFAULT_MAX_TOTAL_BYTES: u64 = 64 * 1024 * 1024;
+
+    /// Returns [`None`] if [`envs::CAPTURE_STOLEN_DIR`] is not set.
+    pub fn from_env() -> Option<Self> {
+        let dir = envs::CAPTURE_STOLEN_DIR.try_from_env().ok().flatten()?;
+
+        Some(Self {
+            dir: dir.into(),
+            max_connection_bytes: envs::CAPTURE_STOLEN_MAX_CONNECTION_BYTES
+                .try_from_env()
+                .ok()
+                .flatten()
+                .unwrap_or(Self::DEFAULT_MAX_CONNECTION_BYTES),
+            max_total_bytes: envs::CAPTURE_STOLEN_MAX_TOTAL_BYTES
+                .try_from_env()
+                .ok()
+                .flatten()
+                .unwrap_or(Self::DEFAULT_MAX_TOTAL_BYTES),
+        })
+    }
+}
+
+/// Direction of the captured bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    /// Received from the peer.
+    In,
+    /// Sent to the peer.
+    Out,
+}
+
+impl Direction {
+    fn file_name(self, id: u64) -> String {
+        match self {
+            Self::In => format!("{id}.in.bin"),
+            Self::Out => format!("{id}.out.bin"),
+        }
+    }
+}
+
+/// Sent from the [`CapturedIo`]s to the [`CaptureWriter`].
+#[derive(Debug)]
+enum CaptureEvent {
+    Started {
+        id: u64,
+        source: SocketAddr,
+        destination: SocketAddr,
+        server_name: Option<String>,
+    },
+    Data {
+        id: u64,
+        direction: Direction,
+        data: Bytes,
+        /// Whether the rest of the traffic in this direction is not captured.
+        truncated: bool,
+    },
+    Finished {
+        id: u64,
+    },
+}
+
+/// Handle to the background task that writes the captures.
+#[derive(Debug, Clone)]
+pub struct StolenCapture {
+    tx: mpsc::UnboundedSender<CaptureEvent>,
+    next_id: Arc<AtomicU64>,
+    max_connection_bytes: u64,
+}
+
+impl StolenCapture {
+    /// Spawns the background task that writes the captures.
+    pub fn spawn(config: CaptureConfig) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let max_connection_bytes = config.max_connection_bytes;
+
+        tokio::spawn(CaptureWriter::new(config).run(rx));
+
+        Self {
+            tx,
+            next_id: Default::default(),
+            max_connection_bytes,
+        }
+    }
+
+    /// Wraps the IO stream of a stolen connection, capturing all bytes that go through it.
+    pub fn capture<T>(&self, io: T, info: &ConnectionInfo) -> CapturedIo<T> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let _ = self.tx.send(CaptureEvent::Started {
+            id,
+            source: info.peer_addr,
+            destination: info.original_destination,
+            server_name: info.server_name.clone(),
+        });
+
+        CapturedIo {
+            io,
+            id,
+            tx: self.tx.clone(),
+            remaining_in: self.max_connection_bytes,
+            remaining_out: self.max_connection_bytes,
+        }
+    }
+}
+
+/// IO stream of a stolen connection, wrapped with [`StolenCapture::capture`].
+///
+/// Transparently implements [`AsyncRead`] and [`AsyncWrite`].
+pub struct CapturedIo<T> {
+    io: T,
+    id: u64,
+    tx: mpsc::UnboundedSender<CaptureEvent>,
+    remaining_in: u64,
+    remaining_out: u64,
+}
+
+impl<T> CapturedIo<T> {
+    fn record(&mut self, direction: Direction, data: &[u8]) {
+        let remaining = match direction {
+            Direction::In => &mut self.remaining_in,
+            Direction::Out => &mut self.remaining_out,
+        };
+
+        if data.is_empty() || *remaining == 0 {
+            return;
+        }
+
+        let len = data
+            .len()
+            .min(usize::try_from(*remaining).unwrap_or(usize::MAX));
+        *remaining -= len as u64;
+
+        let _ = self.tx.send(CaptureEvent::Data {
+            id: self.id,
+            direction,
+            data: Bytes::copy_from_slice(&data[..len]),
+            truncated: len < data.len(),
+        });
+    }
+}
+
+impl<T> Drop for CapturedIo<T> {
+    fn drop(&mut self) {
+        let _ = self.tx.send(CaptureEvent::Finished { id: self.id });
+    }
+}
+
+impl<T> AsyncRead for CapturedIo<T>
+where
+    T: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled_before = buf.filled().len();
+
+        let result = std::task::ready!(Pin::new(&mut this.io).poll_read(cx, buf));
+        if result.is_ok() {
+            this.record(Direction::In, &buf.filled()[filled_before..]);
+        }
+
+        Poll::Ready(result)
+    }
+}
+
+impl<T> AsyncWrite for CapturedIo<T>
+where
+    T: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+
+        let result = std::task::ready!(Pin::new(&mut this.io).poll_write(cx, buf));
+        if let Ok(written) = result {
+            this.record(Direction::Out, &buf[..written]);
+        }
+
+        Poll::Ready(result)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+    }
+}
+
+/// Entry in the `index.json` file.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CaptureEntry {
+    id: u64,
+    source: SocketAddr,
+    destination: SocketAddr,
+    server_name: Option<String>,
+    /// Milliseconds since the UNIX epoch.
+    started_at: u64,
+    finished: bool,
+    inbound: CaptureFile,
+    outbound: CaptureFile,
+    /// Open files of an unfinished connection.
+    #[serde(skip)]
+    files: Option<(File, File)>,
+}
+
+impl CaptureEntry {
+    fn size(&self) -> u64 {
+        self.inbound.bytes + self.outbound.bytes
+    }
+}
+
+/// Captured bytes in one direction of a connection.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CaptureFile {
+    file: String,
+    bytes: u64,
+    truncated: bool,
+}
+
+/// Background task that writes the captures, see [`StolenCapture::spawn`].
+struct CaptureWriter {
+    config: CaptureConfig,
+    /// Ordered from the oldest connection.
+    entries: VecDeque<CaptureEntry>,
+    total_bytes: u64,
+}
+
+impl CaptureWriter {
+    fn new(config: CaptureConfig) -> Self {
+        Self {
+            config,
+            entries: Default::default(),
+            total_bytes: 0,
+        }
+    }
+
+    async fn run(mut self, mut rx: mpsc::UnboundedReceiver<CaptureEvent>) {
+        if let Err(error) = tokio::fs::create_dir_all(&self.config.dir).await {
+            tracing::error!(
+                %error,
+                dir = %self.config.dir.display(),
+                "Failed to create the capture directory, stolen connections will not be captured",
+            );
+            return;
+        }
+
+        while let Some(event) = rx.recv().await {
+            if let Err(error) = self.handle_event(event).await {
+                tracing::warn!(%error, "Failed to write a stolen connection capture");
+            }
+        }
+    }
+
+    async fn handle_event(&mut self, event: CaptureEvent) -> io::Result<()> {
+        match event {
+            CaptureEvent::Started {
+                id,
+                source,
+                destination,
+                server_name,
+            } => {
+                let inbound = Direction::In.file_name(id);
+                let outbound = Direction::Out.file_name(id);
+                let files = (
+                    File::create(self.config.dir.join(&inbound)).await?,
+                    File::create(self.config.dir.join(&outbound)).await?,
+                );
+
+                self.entries.push_back(CaptureEntry {
+                    id,
+                    source,
+                    destination,
+                    server_name,
+                    started_at: SystemTime::now()
+                        .duration_since(SystemTime::UNIX_EPOCH)
+                        .map(|since| since.as_millis().try_into().unwrap_or(u64::MAX))
+                        .unwrap_or_default(),
+                    finished: false,
+                    inbound: CaptureFile {
+                        file: inbound,
+                        bytes: 0,
+                        truncated: false,
+                    },
+                    outbound: CaptureFile {
+                        file: outbound,
+                        bytes: 0,
+                        truncated: false,
+                    },
+                    files: Some(files),
+                });
+
+                self.write_index().await
+            }
+
+            CaptureEvent::Data {
+                id,
+                direction,
+                mut data,
+                truncated,
+            } => {
+                let evicted = self.make_room(data.len() as u64).await?;
+                let available = self.config.max_total_bytes - self.total_bytes;
+                let over_total = data.len() as u64 > available;
+                if over_total {
+                    data.truncate(available as usize);
+                }
+
+                if let Some(entry) = self.entries.iter_mut().find(|entry| entry.id == id)
+                    && let Some((inbound_file, outbound_file)) = entry.files.as_mut()
+                {
+                    let (file, capture) = match direction {
+                        Direction::In => (inbound_file, &mut entry.inbound),
+                        Direction::Out => (outbound_file, &mut entry.outbound),
+                    };
+
+                    file.write_all(&data).await?;
+                    capture.bytes += data.len() as u64;
+                    capture.truncated |= truncated || over_total;
+                    self.total_bytes += data.len() as u64;
+                }
+
+                if evicted {
+                    self.write_index().await?;
+                }
+
+                Ok(())
+            }
+
+            CaptureEvent::Finished { id } => {
+                let Some(entry) = self.entries.iter_mut().find(|entry| entry.id == id) else {
+                    return Ok(());
+                };
+
+                entry.finished = true;
+                if let Some((mut inbound, mut outbound)) = entry.files.take() {
+                    inbound.flush().await?;
+                    outbound.flush().await?;
+                }
+
+                self.write_index().await
+            }
+        }
+    }
+
+    /// Removes the captures of the oldest finished connections, until `len` more bytes fit in
+    /// [`CaptureConfig::max_total_bytes`], or there are no more finished connections.
+    ///
+    /// Returns whether any capture was removed.
+    async fn make_room(&mut self, len: u64) -> io::Result<bool> {
+        let mut evicted = false;
+
+        while self.total_bytes + len > self.config.max_total_bytes {
+            let Some(position) = self.entries.iter().position(|entry| entry.finished) else {
+                break;
+            };
+            let Some(entry) = self.entries.remove(position) else {
+                break;
+            };
+
+            self.total_bytes -= entry.size();
+            evicted = true;
+
+            for file in [&entry.inbound.file, &entry.outbound.file] {
+                remove_if_exists(&self.config.dir.join(file)).await?;
+            }
+        }
+
+        Ok(evicted)
+    }
+
+    /// Replaces the `index.json` file.
+    async fn write_index(&self) -> io::Result<()> {
+        #[derive(Serialize)]
+        struct Index<'a> {
+            connections: &'a VecDeque<CaptureEntry>,
+        }
+
+        let index = serde_json::to_vec_pretty(&Index {
+            connections: &self.entries,
+        })?;
+
+        // Written to a temporary file first, so that the index is never read half-written.
+        let temp_path = self.config.dir.join(format!(".{INDEX_FILE}.tmp"));
+        tokio::fs::write(&temp_path, index).await?;
+        tokio::fs::rename(temp_path, self.config.dir.join(INDEX_FILE)).await
+    }
+}
+
+async fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        ops::Not,
+        time::Duration,
+    };
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::{CaptureConfig, StolenCapture};
+    use crate::incoming::ConnectionInfo;
+
+    fn connection_info() -> ConnectionInfo {
+        ConnectionInfo {
+            original_destination: SocketAddr::from((Ipv4Addr::LOCALHOST, 80)),
+            local_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 8080)),
+            peer_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 50000)),
+            tls_connector: None,
+            server_name: None,
+            postgres: None,
+        }
+    }
+
+    /// Waits until the connection with the given id is the last one in the index, and all
+    /// connections in the index are finished.
+    async fn wait_for_index(dir: &std::path::Path, last_id: u64) -> serde_json::Value {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Ok(raw) = tokio::fs::read(dir.join("index.json")).await {
+                    let index: serde_json::Value = serde_json::from_slice(&raw).unwrap();
+                    let entries = index["connections"].as_array().unwrap();
+                    if entries.last().and_then(|entry| entry["id"].as_u64()) == Some(last_id)
+                        && entries.iter().all(|entry| entry["finished"] == true)
+                    {
+                        break index;
+                    }
+                }
+
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Verifies that bytes in both directions are captured, and the per-connection cap is
+    /// respected.
+    #[tokio::test]
+    async fn captures_both_directions() {
+        let dir = tempfile::tempdir().unwrap();
+        let capture = StolenCapture::spawn(CaptureConfig {
+            dir: dir.path().to_path_buf(),
+            max_connection_bytes: 8,
+            max_total_bytes: 1024,
+        });
+
+        let (agent, mut peer) = tokio::io::duplex(64);
+        let mut agent = capture.capture(agent, &connection_info());
+
+        peer.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        let mut request = [0; 16];
+        agent.read_exact(&mut request).await.unwrap();
+        agent.write_all(b"OK").await.unwrap();
+        drop(agent);
+
+        let index = wait_for_index(dir.path(), 0).await;
+        let entry = &index["connections"][0];
+        assert_eq!(entry["inbound"]["bytes"], 8);
+        assert_eq!(entry["inbound"]["truncated"], true);
+        assert_eq!(entry["outbound"]["bytes"], 2);
+        assert_eq!(entry["outbound"]["truncated"], false);
+
+        let inbound = tokio::fs::read(dir.path().join("0.in.bin")).await.unwrap();
+        assert_eq!(inbound, b"GET / HT");
+        let outbound = tokio::fs::read(dir.path().join("0.out.bin")).await.unwrap();
+        assert_eq!(outbound, b"OK");
+    }
+
+    /// Verifies that captures of the oldest finished connections are removed when the total cap
+    /// is exceeded.
+    #[tokio::test]
+    async fn evicts_oldest_captures() {
+        let dir = tempfile::tempdir().unwrap();
+        let capture = StolenCapture::spawn(CaptureConfig {
+            dir: dir.path().to_path_buf(),
+            max_connection_bytes: 1024,
+            max_total_bytes: 10,
+        });
+
+        for _ in 0..2 {
+            let (agent, _peer) = tokio::io::duplex(64);
+            let mut agent = capture.capture(agent, &connection_info());
+            agent.write_all(b"hello").await.unwrap();
+        }
+
+        let (agent, _peer) = tokio::io::duplex(64);
+        let mut agent = capture.capture(agent, &connection_info());
+        agent.write_all(b"world").await.unwrap();
+        drop(agent);
+
+        let index = wait_for_index(dir.path(), 2).await;
+        let ids = index["connections"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["id"].as_u64().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [1, 2]);
+        assert!(dir.path().join("0.out.bin").exists().not());
+    }
+}
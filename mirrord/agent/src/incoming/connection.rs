@@ -19,6 +19,8 @@ use tokio_stream::wrappers::{BroadcastStream, errors::BroadcastStreamRecvError};
 use super::{
     Redirected,
     error::{ConnError, HttpDetectError},
+    peek::peek,
+    postgres::{self, PostgresStartup},
     tls::{self, StealTlsHandlerStore, handler::PassThroughTlsConnector},
 };
 use crate::{
@@ -28,6 +30,7 @@ use crate::{
 
 mod body_utils;
 mod copy_bidirectional;
+mod copy_redis;
 pub mod http;
 mod http_task;
 mod optional_broadcast;
@@ -52,6 +55,12 @@ pub struct ConnectionInfo {
     /// TLS connector that should be used when passing this connection
     /// through to its original destination.
     pub tls_connector: Option<PassThroughTlsConnector>,
+    /// Server name from the TLS ClientHello of this connection (SNI), if any.
+    ///
+    /// When the agent does not terminate TLS on this port, the ClientHello is only peeked.
+    pub server_name: Option<String>,
+    /// Parameters from the PostgreSQL startup message of this connection, if any.
+    pub postgres: Option<PostgresStartup>,
 }
 
 impl ConnectionInfo {
@@ -140,6 +149,20 @@ where
     }
 }
 
+/// Initial messages that [`MaybeHttp::detect`] peeks from a redirected connection that is not
+/// HTTP.
+///
+/// Peeking waits until the client sends enough data (or for [`MaybeHttp::HTTP_DETECTION_TIMEOUT`]),
+/// which would delay server-first protocols. Therefore, we only peek on the ports that have
+/// connection filters in need of the data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConnectionPeeks {
+    /// Peek the TLS ClientHello, for [`ConnectionInfo::server_name`].
+    pub sni: bool,
+    /// Peek the PostgreSQL startup message, for [`ConnectionInfo::postgres`].
+    pub postgres: bool,
+}
+
 /// A redirected connection that went through HTTP detection.
 ///
 /// # Metrics
@@ -161,9 +184,13 @@ impl MaybeHttp {
 
     /// Accepts the (possibly TLS) connection and detects if the redirected connection is
     /// HTTP.
+    ///
+    /// When the connection is not HTTP, also peeks its initial message, as requested with
+    /// `peeks`.
     pub async fn detect(
         redirected: Redirected,
         tls_handlers: &StealTlsHandlerStore,
+        peeks: ConnectionPeeks,
     ) -> Result<Self, HttpDetectError> {
         let metric_guard = MetricGuard::new(&REDIRECTED_CONNECTIONS);
 
@@ -176,11 +203,39 @@ impl MaybeHttp {
         let tls_handler = tls_handlers.get(original_destination.port()).await?;
 
         let Some(tls_handler) = tls_handler else {
+            let stream = redirected.stream;
+            #[cfg(feature = "io-uring")]
+            let stream = super::uring::wrap(stream).map_err(HttpDetectError::IoUring)?;
+
             let (stream, http_version) =
-                crate::http::detect_http_version(redirected.stream, Self::HTTP_DETECTION_TIMEOUT)
+                crate::http::detect_http_version(stream, Self::HTTP_DETECTION_TIMEOUT)
                     .await
                     .map_err(HttpDetectError::HttpDetect)?;
 
+            let (stream, server_name) = match http_version {
+                None if peeks.sni => peek(
+                    stream,
+                    Self::HTTP_DETECTION_TIMEOUT,
+                    tls::sni::MAX_RECORD_LEN,
+                    tls::sni::parse_sni,
+                )
+                .await
+                .map_err(HttpDetectError::HttpDetect)?,
+                _ => (stream, None),
+            };
+
+            let (stream, postgres) = match (http_version, &server_name) {
+                (None, None) if peeks.postgres => peek(
+                    stream,
+                    Self::HTTP_DETECTION_TIMEOUT,
+                    postgres::MAX_STARTUP_MESSAGE_LEN,
+                    postgres::parse_startup,
+                )
+                .await
+                .map_err(HttpDetectError::HttpDetect)?,
+                _ => (stream, None),
+            };
+
             return Ok(Self {
                 stream: Box::new(IncomingIoWrapper {
                     io: stream,
@@ -192,6 +247,8 @@ impl MaybeHttp {
                     local_addr,
                     peer_addr,
                     tls_connector: None,
+                    server_name,
+                    postgres,
                 },
             });
         };
@@ -202,6 +259,11 @@ impl MaybeHttp {
             .await
             .map_err(HttpDetectError::TlsAccept)?;
         let tls_connector = tls_handler.connector(stream.get_ref().1);
+        let server_name = stream
+            .get_ref()
+            .1
+            .server_name()
+            .map(str::to_ascii_lowercase);
 
         let (stream, http_version): (Box<dyn IncomingIO>, _) = match tls_connector.alpn_protocol() {
             Some(tls::HTTP_2_ALPN_NAME) => (
@@ -248,6 +310,8 @@ impl MaybeHttp {
                 local_addr,
                 peer_addr,
                 tls_connector: Some(tls_connector),
+                server_name,
+                postgres: None,
             },
         })
     }
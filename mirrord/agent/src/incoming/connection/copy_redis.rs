@@ -0,0 +1,344 @@
+use std::{collections::VecDeque, ops::Not};
+
+use bytes::BytesMut;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::incoming::{
+    ConnError, IncomingStreamItem,
+    connection::{
+        copy_bidirectional::{CowBytes, OutgoingDestination},
+        optional_broadcast::OptionalBroadcast,
+    },
+    peek::Peeked,
+    redis::{self, RedisRouter, Route},
+};
+
+/// Reply sent to the peer in place of the replies that the local application will never send.
+const LOCAL_CLOSED_REPLY: &[u8] = b"-ERR mirrord: the local application closed the connection\r\n";
+
+/// Copies Redis commands from an incoming stream to two outgoing destinations, and returns the
+/// replies to the incoming stream in the order of the commands.
+///
+/// # Params
+///
+/// * `incoming` - an incoming data stream
+/// * `remote` - a passthrough connection to the original destination
+/// * `local` - the stealing client
+/// * `router` - decides where each command is sent
+/// * `mirror_data_tx` - receives all data sent by the peer
+pub async fn copy_redis<I, R, L>(
+    incoming: &mut I,
+    remote: &mut R,
+    local: &mut L,
+    mut router: RedisRouter,
+    mirror_data_tx: &mut OptionalBroadcast,
+) -> Result<(), ConnError>
+where
+    I: AsyncRead + AsyncWrite + Unpin,
+    R: OutgoingDestination,
+    L: OutgoingDestination,
+{
+    let mut commands = BytesMut::with_capacity(64 * 1024);
+    let mut replies = Replies::default();
+    let mut incoming_reads = true;
+    let mut local_reads = true;
+
+    loop {
+        tokio::select! {
+            result = incoming.read_buf(&mut commands), if incoming_reads => {
+                let read = result.map_err(From::from).map_err(ConnError::IncomingIoError)?;
+
+                if read == 0 {
+                    incoming_reads = false;
+                    mirror_data_tx.send_item(IncomingStreamItem::NoMoreData);
+
+                    // Whatever is left is not a complete command, the server will handle it.
+                    if commands.is_empty().not() {
+                        remote.send_data(CowBytes::Borrowed(commands.as_ref())).await?;
+                        commands.clear();
+                    }
+                    remote.shutdown().await?;
+                    if local_reads {
+                        let _ = local.shutdown().await;
+                    }
+                } else {
+                    let new_data = commands.get(commands.len() - read..).unwrap_or_default();
+                    mirror_data_tx.send_data(CowBytes::Borrowed(new_data));
+
+                    route_commands(&mut commands, remote, local, &mut router, &mut replies).await?;
+                }
+            },
+
+            result = remote.recv() => {
+                let data = result?;
+                if data.as_ref().is_empty() {
+                    // The original destination closed the connection, the local replies that are
+                    // still pending will not be delivered.
+                    break;
+                }
+                replies.remote.extend_from_slice(data.as_ref());
+            },
+
+            result = local.recv(), if local_reads => {
+                let data = result?;
+                if data.as_ref().is_empty() {
+                    local_reads = false;
+                    router.close_local();
+                    replies.close_local();
+                } else {
+                    replies.local.extend_from_slice(data.as_ref());
+                }
+            },
+        }
+
+        replies.flush(incoming).await?;
+
+        if incoming_reads.not() && replies.pending.is_empty() && router.is_passthrough().not() {
+            break;
+        }
+    }
+
+    replies.flush(incoming).await?;
+    if incoming_reads && local_reads {
+        let _ = local.shutdown().await;
+    }
+
+    incoming
+        .shutdown()
+        .await
+        .map_err(From::from)
+        .map_err(ConnError::IncomingIoError)
+}
+
+/// Sends all complete commands from the given buffer to their destinations.
+async fn route_commands<R, L>(
+    commands: &mut BytesMut,
+    remote: &mut R,
+    local: &mut L,
+    router: &mut RedisRouter,
+    replies: &mut Replies,
+) -> Result<(), ConnError>
+where
+    R: OutgoingDestination,
+    L: OutgoingDestination,
+{
+    loop {
+        if router.is_passthrough() {
+            if commands.is_empty().not() {
+                remote
+                    .send_data(CowBytes::Borrowed(commands.as_ref()))
+                    .await?;
+                commands.clear();
+            }
+
+            return Ok(());
+        }
+
+        let (len, route) = match redis::parse_command(commands) {
+            Peeked::Found(command) => {
+                // Empty inline commands are ignored by the server, there will be no reply.
+                let route = command
+                    .args
+                    .is_empty()
+                    .not()
+                    .then(|| router.route(&command.args));
+                (command.len, route)
+            }
+            Peeked::Incomplete => return Ok(()),
+            Peeked::Missing => {
+                // We don't understand the peer, let the server decide what to do.
+                router.pass_through();
+                continue;
+            }
+        };
+
+        let command = commands.split_to(len);
+        match route {
+            Some(Route::Local) => {
+                if local
+                    .send_data(CowBytes::Borrowed(command.as_ref()))
+                    .await
+                    .is_ok()
+                {
+                    replies.pending.push_back(Route::Local);
+                } else {
+                    router.close_local();
+                    remote
+                        .send_data(CowBytes::Borrowed(command.as_ref()))
+                        .await?;
+                    replies.pending.push_back(Route::Remote);
+                }
+            }
+            Some(Route::Remote) => {
+                remote
+                    .send_data(CowBytes::Borrowed(command.as_ref()))
+                    .await?;
+                replies.pending.push_back(Route::Remote);
+            }
+            None => {
+                remote
+                    .send_data(CowBytes::Borrowed(command.as_ref()))
+                    .await?;
+            }
+        }
+    }
+}
+
+/// Replies received from both destinations.
+#[derive(Default)]
+struct Replies {
+    /// Destinations of the commands that were not answered yet, in order.
+    pending: VecDeque<Route>,
+    /// Data received from the original destination.
+    remote: BytesMut,
+    /// Data received from the local application.
+    local: BytesMut,
+}
+
+impl Replies {
+    /// Writes all complete replies to the incoming stream, in the order of the commands.
+    async fn flush<I>(&mut self, incoming: &mut I) -> Result<(), ConnError>
+    where
+        I: AsyncWrite + Unpin,
+    {
+        loop {
+            let (buffer, source) = match self.pending.front() {
+                Some(Route::Remote) => (&mut self.remote, "original destination"),
+                Some(Route::Local) => (&mut self.local, "local application"),
+                None => {
+                    // Data that does not answer any command, e.g. messages after `SUBSCRIBE`.
+                    if self.remote.is_empty().not() {
+                        write(incoming, &self.remote).await?;
+                        self.remote.clear();
+                    }
+                    self.local.clear();
+
+                    return Ok(());
+                }
+            };
+
+            let len = match redis::reply_len(buffer) {
+                Peeked::Found(len) => len,
+                Peeked::Incomplete => return Ok(()),
+                Peeked::Missing => return Err(ConnError::InvalidRedisReply(source)),
+            };
+
+            let reply = buffer.split_to(len);
+            write(incoming, &reply).await?;
+            self.pending.pop_front();
+        }
+    }
+
+    /// Replaces the local replies that will never arrive with errors.
+    fn close_local(&mut self) {
+        let mut complete = 0;
+        let mut waiting = 0;
+
+        for route in &self.pending {
+            if *route == Route::Remote {
+                continue;
+            }
+
+            match redis::reply_len(self.local.get(complete..).unwrap_or_default()) {
+                Peeked::Found(len) => complete += len,
+                Peeked::Incomplete | Peeked::Missing => waiting += 1,
+            }
+        }
+
+        self.local.truncate(complete);
+        for _ in 0..waiting {
+            self.local.extend_from_slice(LOCAL_CLOSED_REPLY);
+        }
+    }
+}
+
+async fn write<I>(incoming: &mut I, data: &[u8]) -> Result<(), ConnError>
+where
+    I: AsyncWrite + Unpin,
+{
+    incoming
+        .write_all(data)
+        .await
+        .map_err(From::from)
+        .map_err(ConnError::IncomingIoError)
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use mirrord_protocol::tcp::{Filter, RedisFilter};
+    use tokio::{io::AsyncReadExt, sync::mpsc};
+
+    use super::*;
+    use crate::incoming::{
+        connection::copy_bidirectional::{PassthroughConnection, StealingClient},
+        redis::RedisCommandFilter,
+    };
+
+    /// Verifies that the replies are returned in the order of the commands, even if the local
+    /// application replies after the original destination.
+    #[tokio::test]
+    async fn replies_in_order() {
+        let (mut peer, mut incoming) = tokio::io::duplex(1024);
+        let (mut server, remote_stream) = tokio::io::duplex(1024);
+        let (incoming_tx, mut incoming_rx) = mpsc::channel(8);
+        let (outgoing_tx, outgoing_rx) = mpsc::channel(8);
+
+        let router = RedisRouter::new(
+            RedisCommandFilter::new(&RedisFilter {
+                command: None,
+                key: Some(Filter::new("^session:".into()).unwrap()),
+            })
+            .unwrap(),
+        );
+
+        let task = tokio::spawn(async move {
+            let mut remote = PassthroughConnection {
+                stream: remote_stream,
+                buffer: BytesMut::new(),
+                mirror_data_tx: None.into(),
+            };
+            let mut local = StealingClient {
+                data_tx: incoming_tx,
+                data_rx: outgoing_rx,
+                mirror_data_tx: None.into(),
+            };
+
+            copy_redis(
+                &mut incoming,
+                &mut remote,
+                &mut local,
+                router,
+                &mut None.into(),
+            )
+            .await
+        });
+
+        peer.write_all(b"GET session:1\r\n*2\r\n$3\r\nGET\r\n$6\r\nuser:1\r\n")
+            .await
+            .unwrap();
+
+        let mut buf = [0_u8; 25];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"*2\r\n$3\r\nGET\r\n$6\r\nuser:1\r\n");
+        server.write_all(b"$6\r\nremote\r\n").await.unwrap();
+
+        let Some(IncomingStreamItem::Data(data)) = incoming_rx.recv().await else {
+            panic!("expected the local command");
+        };
+        assert_eq!(data.as_ref(), b"GET session:1\r\n");
+        outgoing_tx
+            .send(Bytes::from_static(b"$5\r\nlocal\r\n"))
+            .await
+            .unwrap();
+
+        let mut buf = [0_u8; 23];
+        peer.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"$5\r\nlocal\r\n$6\r\nremote\r\n");
+
+        peer.shutdown().await.unwrap();
+        server.shutdown().await.unwrap();
+        drop(outgoing_tx);
+        task.await.unwrap().unwrap();
+    }
+}
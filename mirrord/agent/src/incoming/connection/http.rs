@@ -1,21 +1,20 @@
 use std::{
     fmt::{self, Debug},
-    str::FromStr,
     sync::{Arc, LazyLock},
     time::Duration,
 };
 
 use bytes::Bytes;
 use futures::StreamExt;
-use http::{header::CONTENT_LENGTH, request::Parts};
+use http::request::Parts;
 use http_body_util::{BodyExt, StreamBody, combinators::BoxBody};
 use hyper::{
-    Response,
-    body::Frame,
+    Request, Response,
+    body::{Body, Frame, Incoming},
     http::{StatusCode, request, response},
 };
 use mirrord_agent_env::envs;
-use mirrord_protocol::tcp::InternalHttpBodyFrame;
+use mirrord_protocol::tcp::{HTTP_BODY_PREVIEW_MAX_SIZE, InternalHttpBodyFrame};
 use tokio::{
     runtime::Handle,
     sync::{
@@ -34,7 +33,7 @@ use crate::{
     incoming::{
         ConnError, IncomingStreamItem, RedirectorTaskConfig,
         connection::{
-            http_task::{HttpTask, StealingClient, UpgradeDataRx},
+            http_task::{HttpTask, PassthroughConnection, StealingClient, UpgradeDataRx},
             optional_broadcast::OptionalBroadcast,
         },
     },
@@ -57,14 +56,16 @@ pub struct RedirectedHttp {
 
     /// Configuration of the RedirectorTask that created this
     redirector_config: RedirectorTaskConfig,
+
+    /// Whether the body is larger than [`MAX_BODY_BUFFER_SIZE`], and only its preview was
+    /// buffered in [`Self::buffer_body`].
+    body_preview: bool,
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum BufferBodyError {
     #[error(transparent)]
     Conn(#[from] ConnError),
-    #[error("body size exceeded max configured size of {} bytes", *MAX_BODY_BUFFER_SIZE)]
-    BodyTooBig,
     #[error("receiving body took longer than the max configured timeout of {}ms", MAX_BODY_BUFFER_TIMEOUT.as_millis())]
     Timeout(#[from] Elapsed),
 }
@@ -84,6 +85,7 @@ impl RedirectedHttp {
             mirror_tx: None,
             runtime_handle: Handle::current(),
             redirector_config,
+            body_preview: false,
         }
     }
 
@@ -95,6 +97,16 @@ impl RedirectedHttp {
         &self.request.parts
     }
 
+    /// Returns whether the request has no body, e.g. a regular `GET` request.
+    pub fn has_empty_body(&self) -> bool {
+        self.request.body_tail.is_none()
+            && self
+                .request
+                .body_head
+                .iter()
+                .all(|frame| frame.data_ref().is_none_or(Bytes::is_empty))
+    }
+
     /// Acquires a mirror handle to this request.
     ///
     /// For the data to flow, you must start the request task with either [`Self::steal`] or
@@ -135,6 +147,7 @@ impl RedirectedHttp {
                 body_finished: self.request.body_tail.is_none(),
             },
             stream: IncomingStream::Mirror(BroadcastStream::new(rx)),
+            body_preview: self.body_preview,
         }
     }
 
@@ -193,34 +206,30 @@ impl RedirectedHttp {
         self.runtime_handle.spawn(task.run());
     }
 
+    /// Returns a mutable reference to the request parts and a shared reference to the buffered
+    /// body, if the whole body or its preview was buffered.
     pub fn parts_and_body(&mut self) -> (&mut Parts, Option<FramesReader<'_, Frame<Bytes>>>) {
         (
             &mut self.request.parts,
-            self.request
-                .body_tail
-                .is_none()
+            (self.request.body_tail.is_none() || self.body_preview)
                 .then_some(FramesReader::from(&*self.request.body_head)),
         )
     }
 
+    /// Buffers the request body, so that it can be matched against body filters.
+    ///
+    /// Stops after [`MAX_BODY_BUFFER_SIZE`] bytes. The rest of a larger body is streamed when
+    /// the request task is started, and the filters only see the buffered preview.
     #[instrument(level = "trace", ret)]
     pub async fn buffer_body(&mut self) -> Result<(), BufferBodyError> {
+        if self.body_preview {
+            return Ok(());
+        }
+
         let Some(tail) = self.request.body_tail.as_mut() else {
             return Ok(());
         };
 
-        let content_length = self
-            .request
-            .parts
-            .headers
-            .get(CONTENT_LENGTH)
-            .and_then(|t| t.to_str().ok())
-            .and_then(|t| usize::from_str(t).ok());
-
-        if content_length.is_some_and(|l| l > *MAX_BODY_BUFFER_SIZE) {
-            return Err(BufferBodyError::BodyTooBig);
-        }
-
         let mut rxd: usize = self
             .request
             .body_head
@@ -234,7 +243,7 @@ impl RedirectedHttp {
                 match tail.frame().await {
                     None => {
                         mirror.send_item(IncomingStreamItem::NoMoreFrames);
-                        return Ok(());
+                        return Ok(true);
                     }
                     Some(Ok(frame)) => {
                         rxd += frame.data_ref().map(|f| f.len()).unwrap_or(0);
@@ -248,12 +257,18 @@ impl RedirectedHttp {
                     }
                 }
             }
-            Err(BufferBodyError::BodyTooBig)
+            Ok::<_, BufferBodyError>(false)
         })
-        .await?;
+        .await??;
+
+        if result {
+            // Set body_tail to none since we've extracted everything from it
+            self.request.body_tail = None;
+        } else {
+            self.body_preview = true;
+        }
 
-        // Set body_tail to none since we've extracted everything from it
-        result.inspect(|_| self.request.body_tail = None)
+        Ok(())
     }
 }
 
@@ -266,8 +281,16 @@ impl Debug for RedirectedHttp {
     }
 }
 
+/// Capped at [`HTTP_BODY_PREVIEW_MAX_SIZE`].
 static MAX_BODY_BUFFER_SIZE: LazyLock<usize> = LazyLock::new(|| {
     match envs::MAX_BODY_BUFFER_SIZE.try_from_env() {
+        Ok(Some(t)) if t > HTTP_BODY_PREVIEW_MAX_SIZE => {
+            tracing::warn!(
+                "{} exceeds the limit of {HTTP_BODY_PREVIEW_MAX_SIZE} bytes, using the limit",
+                envs::MAX_BODY_BUFFER_SIZE.name
+            );
+            Some(HTTP_BODY_PREVIEW_MAX_SIZE as usize)
+        }
         Ok(Some(t)) => Some(t as usize),
         Ok(None) => {
             tracing::warn!("{} not set, using default", envs::MAX_BODY_BUFFER_SIZE.name);
@@ -309,6 +332,18 @@ static MAX_BODY_BUFFER_TIMEOUT: LazyLock<Duration> = LazyLock::new(|| {
         .into(),
     )
 });
+/// Sends the request to the original destination of the connection, the same way
+/// [`RedirectedHttp::pass_through`] does, and returns the response.
+pub async fn send_to_original_destination<B>(
+    info: &ConnectionInfo,
+    request: Request<B>,
+) -> Result<Response<Incoming>, ConnError>
+where
+    B: 'static + Body<Data = Bytes, Error = hyper::Error> + Send + Unpin,
+{
+    HttpTask::<PassthroughConnection>::send_request(info, request).await
+}
+
 /// Steal handle to a redirected HTTP request.
 pub struct StolenHttp {
     pub info: Arc<ConnectionInfo>,
@@ -420,11 +455,14 @@ pub struct MirroredHttp {
     pub request_head: RequestHead,
     /// Will not return frames that are already in [`Self::request_head`].
     pub stream: IncomingStream,
+    /// Whether the body is larger than [`MAX_BODY_BUFFER_SIZE`], and only its preview was
+    /// buffered in [`Self::buffer_body`].
+    body_preview: bool,
 }
 
 impl MirroredHttp {
     /// Returns a mutable reference to the request parts and a shared
-    /// reference to buffered body, if any.
+    /// reference to buffered body, if the whole body or its preview was buffered.
     pub fn parts_and_body(
         &mut self,
     ) -> (
@@ -433,30 +471,20 @@ impl MirroredHttp {
     ) {
         (
             &mut self.request_head.parts,
-            self.request_head
-                .body_finished
+            (self.request_head.body_finished || self.body_preview)
                 .then_some(FramesReader::from(&*self.request_head.body_head)),
         )
     }
 
+    /// Buffers the request body, so that it can be matched against body filters.
+    ///
+    /// Stops after [`MAX_BODY_BUFFER_SIZE`] bytes, see [`RedirectedHttp::buffer_body`].
     #[instrument(level = "trace", ret)]
     pub async fn buffer_body(&mut self) -> Result<(), BufferBodyError> {
-        if self.request_head.body_finished {
+        if self.request_head.body_finished || self.body_preview {
             return Ok(());
         }
 
-        let content_length = self
-            .request_head
-            .parts
-            .headers
-            .get(CONTENT_LENGTH)
-            .and_then(|t| t.to_str().ok())
-            .and_then(|t| usize::from_str(t).ok());
-
-        if content_length.is_some_and(|l| l > *MAX_BODY_BUFFER_SIZE) {
-            return Err(BufferBodyError::BodyTooBig);
-        }
-
         let mut rxd: usize = self
             .request_head
             .body_head
@@ -476,7 +504,7 @@ impl MirroredHttp {
                         }
                         self.request_head.body_head.push(f);
                     }
-                    Some(IncomingStreamItem::NoMoreFrames) => return Ok(()),
+                    Some(IncomingStreamItem::NoMoreFrames) => return Ok(true),
                     Some(IncomingStreamItem::Finished(error @ Err(_))) => error?,
                     other =>
                         Err(ConnError::AgentBug(format!(
@@ -484,12 +512,17 @@ impl MirroredHttp {
                         )))?
                 }
             }
-            Err(BufferBodyError::BodyTooBig)
+            Ok::<_, BufferBodyError>(false)
         })
-        .await?;
+        .await??;
+
+        if result {
+            self.request_head.body_finished = true;
+        } else {
+            self.body_preview = true;
+        }
 
-        // Set body_tail to none since we've extracted everything from it
-        result.inspect(|_| self.request_head.body_finished = true)
+        Ok(())
     }
 }
 
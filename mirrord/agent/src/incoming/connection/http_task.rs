@@ -10,7 +10,10 @@ use hyper::{
     upgrade::{OnUpgrade, Upgraded},
 };
 use hyper_util::rt::TokioIo;
-use mirrord_protocol::{Payload, tcp::InternalHttpBodyFrame};
+use mirrord_protocol::{
+    Payload,
+    tcp::{InternalHttpBodyFrame, declare_trailers},
+};
 use mirrord_tls_util::MaybeTls;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
@@ -109,7 +112,7 @@ impl HttpTask<PassthroughConnection> {
     pub fn new(
         info: Arc<ConnectionInfo>,
         mirror_data_tx: OptionalBroadcast,
-        request: ExtractedRequest,
+        mut request: ExtractedRequest,
         redirector_config: RedirectorTaskConfig,
     ) -> Self {
         let metric = GaugeVecMetricGuard::new(
@@ -117,6 +120,14 @@ impl HttpTask<PassthroughConnection> {
             vec![info.original_destination.port().to_string()],
         );
 
+        // We have the whole body, so we can declare the trailers for HTTP/1 destinations.
+        if request.body_tail.is_none() {
+            declare_trailers(
+                &mut request.parts.headers,
+                request.body_head.iter().filter_map(Frame::trailers_ref),
+            );
+        }
+
         let (request_frame_tx, request_frame_rx) = request
             .body_tail
             .is_some()
@@ -182,7 +193,7 @@ impl HttpTask<PassthroughConnection> {
         }
     }
 
-    async fn send_request<B>(
+    pub(super) async fn send_request<B>(
         info: &ConnectionInfo,
         request: Request<B>,
     ) -> Result<Response<Incoming>, ConnError>
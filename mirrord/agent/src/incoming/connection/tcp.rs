@@ -17,8 +17,10 @@ use crate::incoming::{
     ConnError, IncomingStreamItem,
     connection::{
         copy_bidirectional::{self, PassthroughConnection, StealingClient},
+        copy_redis,
         optional_broadcast::OptionalBroadcast,
     },
+    redis::RedisRouter,
 };
 
 /// A redirected TCP connection.
@@ -124,6 +126,85 @@ impl RedirectedTcp {
         )
     }
 
+    /// Acquires a steal handle to this Redis connection,
+    /// and starts the connection task in the background.
+    ///
+    /// Only the commands routed to [`Route::Local`](crate::incoming::redis::Route::Local) by the
+    /// given [`RedisRouter`] will be directed to this handle, other commands will be directed to
+    /// the original destination. The replies are returned to the peer in the order of the
+    /// commands. The returned [`JoinHandle`] is for the spawned IO task.
+    pub fn steal_redis(
+        mut self,
+        router: RedisRouter,
+        shutdown: CancellationToken,
+    ) -> (StolenTcp, JoinHandle<()>) {
+        let (incoming_tx, incoming_rx) = mpsc::channel(32);
+        let (outgoing_tx, outgoing_rx) = mpsc::channel(32);
+        let info = self.info.clone();
+
+        let handle = self.runtime_handle.clone();
+        let task = async move {
+            let mut mirror_data_tx = OptionalBroadcast::from(self.mirror_tx.take());
+            let mut local = StealingClient {
+                data_tx: incoming_tx,
+                data_rx: outgoing_rx,
+                mirror_data_tx: None.into(),
+            };
+
+            let passthrough = tokio::select! {
+                conn = self.make_pass_through_connection() => conn,
+                _ = shutdown.cancelled() => Err(ConnError::AgentExiting),
+            };
+
+            let result = match passthrough {
+                Ok(stream) => {
+                    let mut remote = PassthroughConnection {
+                        stream,
+                        buffer: BytesMut::with_capacity(64 * 1024),
+                        mirror_data_tx: None.into(),
+                    };
+
+                    tokio::select! {
+                        r = copy_redis::copy_redis(&mut self.io, &mut remote, &mut local, router, &mut mirror_data_tx) => r,
+                        _ = shutdown.cancelled() => {
+                            tracing::debug!("Gracefully shutting down stolen Redis connection");
+                            if let Err(err) = self.io.shutdown().await {
+                                tracing::error!(?err, "Error shutting down stolen Redis connection")
+                            };
+
+                            Err(ConnError::AgentExiting)
+                        }
+                    }
+                }
+                Err(error) => {
+                    tracing::warn!(
+                        error = %Report::new(&error),
+                        info = ?self.info,
+                        "Failed to make a passthrough TCP connection for a stolen Redis connection",
+                    );
+                    Err(error)
+                }
+            };
+
+            mirror_data_tx.send_item(IncomingStreamItem::Finished(result.clone()));
+            let _ = local
+                .data_tx
+                .send(IncomingStreamItem::Finished(result))
+                .await;
+        };
+
+        let join_handle = handle.spawn(task);
+
+        (
+            StolenTcp {
+                info,
+                stream: IncomingStream::Steal(incoming_rx),
+                data_tx: outgoing_tx,
+            },
+            join_handle,
+        )
+    }
+
     /// Starts the connection task in the background.
     ///
     /// All data will be directed to the original destination.
@@ -179,6 +260,21 @@ impl RedirectedTcp {
         })
     }
 
+    /// Starts a task that closes the connection in the background.
+    ///
+    /// No data is sent to the peer, nor to the original destination.
+    pub fn reject(mut self) -> JoinHandle<()> {
+        let handle = self.runtime_handle.clone();
+        handle.spawn(async move {
+            if let Err(err) = self.io.shutdown().await {
+                tracing::debug!(?err, "Error shutting down rejected tcp connection");
+            }
+
+            OptionalBroadcast::from(self.mirror_tx.take())
+                .send_item(IncomingStreamItem::Finished(Err(ConnError::BacklogFull)));
+        })
+    }
+
     async fn make_pass_through_connection(&self) -> Result<MaybeTls, ConnError> {
         let tcp_stream = TcpStream::connect(self.info.pass_through_address())
             .await
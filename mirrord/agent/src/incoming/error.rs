@@ -30,6 +30,9 @@ pub enum HttpDetectError {
     HttpDetect(#[source] io::Error),
     #[error("failed to accept the TLS connection: {0}")]
     TlsAccept(#[source] io::Error),
+    #[cfg(feature = "io-uring")]
+    #[error("failed to move the connection to io_uring: {0}")]
+    IoUring(#[source] io::Error),
 }
 
 /// Errors that can occur when handling a redirected incoming connection.
@@ -49,10 +52,14 @@ pub enum ConnError {
     PassthroughHttpError(#[source] Arc<hyper::Error>),
     #[error("stealing client dropped the connection/request")]
     StealerDropped,
+    #[error("connection rejected because the stealing client's backlog is full")]
+    BacklogFull,
     #[error("broadcast receiver lagged behind")]
     BroadcastLag,
     #[error("bug in the mirrord-agent, please report it: {0}")]
     AgentBug(String),
     #[error("connection cancelled because mirrord-agent is exiting")]
     AgentExiting,
+    #[error("received an invalid Redis reply from the {0}")]
+    InvalidRedisReply(&'static str),
 }
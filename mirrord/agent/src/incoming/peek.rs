@@ -0,0 +1,106 @@
+//! Inspection of the first bytes of redirected connections, without consuming them.
+//!
+//! Used to route whole stolen connections based on their initial message, e.g. the TLS
+//! ClientHello ([`tls::sni`](super::tls::sni)) or the PostgreSQL startup message
+//! ([`postgres`](super::postgres)).
+
+use std::{io, time::Duration};
+
+use bytes::BytesMut;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    time::Instant,
+};
+
+use crate::util::rolledback_stream::RolledBackStream;
+
+/// Result of parsing the beginning of a connection.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Peeked<T> {
+    /// The expected message was found and parsed.
+    Found(T),
+    /// The data does not contain the expected message.
+    Missing,
+    /// The data looks like the beginning of the expected message, but the message is not
+    /// complete.
+    Incomplete,
+}
+
+/// Reads the beginning of the given stream until `parse` returns something other than
+/// [`Peeked::Incomplete`], the stream ends, the `timeout` elapses, or `max_len` bytes are read.
+///
+/// The read data is rolled back, so the returned stream can be passed to the original destination
+/// or to the client unchanged.
+pub async fn peek<IO, T>(
+    stream: RolledBackStream<IO, BytesMut>,
+    timeout: Duration,
+    max_len: usize,
+    parse: fn(&[u8]) -> Peeked<T>,
+) -> io::Result<(RolledBackStream<IO, BytesMut>, Option<T>)>
+where
+    IO: AsyncRead + Unpin,
+{
+    let (mut stream, prefix) = stream.into_parts();
+    let mut buf = prefix.unwrap_or_default();
+    let timeout_at = Instant::now() + timeout;
+
+    let found = loop {
+        match parse(&buf) {
+            Peeked::Found(found) => break Some(found),
+            Peeked::Missing => break None,
+            Peeked::Incomplete if buf.len() >= max_len => break None,
+            Peeked::Incomplete => {}
+        }
+
+        let result = tokio::select! {
+            _ = tokio::time::sleep_until(timeout_at) => break None,
+            result = stream.read_buf(&mut buf) => result,
+        };
+
+        if result? == 0 {
+            break None;
+        }
+    };
+
+    Ok((RolledBackStream::new(stream, buf), found))
+}
+
+/// Minimal big-endian reader over a binary message.
+pub struct Reader<'a>(pub &'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let (head, tail) = self.0.split_at_checked(len)?;
+        self.0 = tail;
+        Some(head)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.take(1)?.first().copied()
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        self.take(2)?.try_into().ok().map(u16::from_be_bytes)
+    }
+
+    pub fn u24(&mut self) -> Option<usize> {
+        let [a, b, c]: [u8; 3] = self.take(3)?.try_into().ok()?;
+        Some(usize::from_be_bytes([0, 0, 0, 0, 0, a, b, c]))
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        self.take(4)?.try_into().ok().map(u32::from_be_bytes)
+    }
+
+    /// Reads a nul-terminated string, without the terminator.
+    pub fn c_str(&mut self) -> Option<&'a [u8]> {
+        let len = self.0.iter().position(|byte| *byte == 0)?;
+        let string = self.take(len)?;
+        self.take(1)?;
+        Some(string)
+    }
+}
@@ -0,0 +1,171 @@
+//! Extraction of the connection parameters from a PostgreSQL startup message.
+//!
+//! Used to route stolen PostgreSQL connections based on
+//! [`StealType::FilteredPostgres`](mirrord_protocol::tcp::StealType::FilteredPostgres)
+//! subscriptions.
+//!
+//! Connections that start with an `SSLRequest` or a `GSSENCRequest` are encrypted before the
+//! startup message is sent, so they cannot be inspected.
+
+use std::ops::Not;
+
+use super::peek::{Peeked, Reader};
+
+/// Protocol version 3.0, the only one sent in startup messages by supported clients.
+const PROTOCOL_VERSION_3: u32 = 196608;
+
+/// Maximum length of a startup message accepted by the PostgreSQL server.
+pub const MAX_STARTUP_MESSAGE_LEN: usize = 10000;
+
+/// Length of the length and the protocol version fields.
+const STARTUP_HEADER_LEN: usize = 8;
+
+/// Parameters from a PostgreSQL startup message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PostgresStartup {
+    /// Name of the database. Defaults to the user name, like in the PostgreSQL server.
+    pub database: Option<String>,
+    /// Name of the user.
+    pub user: Option<String>,
+}
+
+/// Parses the PostgreSQL startup message at the beginning of the given buffer.
+pub fn parse_startup(buf: &[u8]) -> Peeked<PostgresStartup> {
+    let mut reader = Reader(buf);
+
+    let Some(len) = reader.u32().and_then(|len| usize::try_from(len).ok()) else {
+        // All valid lengths fit in 2 bytes.
+        return if buf.iter().take(2).all(|byte| *byte == 0) {
+            Peeked::Incomplete
+        } else {
+            Peeked::Missing
+        };
+    };
+
+    if (STARTUP_HEADER_LEN..=MAX_STARTUP_MESSAGE_LEN)
+        .contains(&len)
+        .not()
+    {
+        return Peeked::Missing;
+    }
+
+    match reader.u32() {
+        Some(PROTOCOL_VERSION_3) => {}
+        Some(..) => return Peeked::Missing,
+        None => return Peeked::Incomplete,
+    }
+
+    let Some(parameters) = reader.take(len - STARTUP_HEADER_LEN) else {
+        return Peeked::Incomplete;
+    };
+
+    match parameters_from(Reader(parameters)) {
+        Some(startup) => Peeked::Found(startup),
+        None => Peeked::Missing,
+    }
+}
+
+/// Reads the `name\0value\0` parameter pairs from the body of a startup message.
+fn parameters_from(mut parameters: Reader<'_>) -> Option<PostgresStartup> {
+    let mut startup = PostgresStartup {
+        database: None,
+        user: None,
+    };
+
+    loop {
+        let name = parameters.c_str()?;
+        if name.is_empty() {
+            break;
+        }
+
+        let value = std::str::from_utf8(parameters.c_str()?).ok()?.to_owned();
+        match name {
+            b"database" => startup.database = Some(value),
+            b"user" => startup.user = Some(value),
+            _ => {}
+        }
+    }
+
+    if startup.database.is_none() {
+        startup.database = startup.user.clone();
+    }
+
+    Some(startup)
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn startup_message(parameters: &[(&str, &str)]) -> Vec<u8> {
+        let mut body = PROTOCOL_VERSION_3.to_be_bytes().to_vec();
+        for (name, value) in parameters {
+            body.extend_from_slice(name.as_bytes());
+            body.push(0);
+            body.extend_from_slice(value.as_bytes());
+            body.push(0);
+        }
+        body.push(0);
+
+        let mut message = u32::try_from(body.len() + 4)
+            .unwrap()
+            .to_be_bytes()
+            .to_vec();
+        message.extend(body);
+        message
+    }
+
+    #[test]
+    fn finds_parameters() {
+        let message = startup_message(&[
+            ("user", "alice"),
+            ("database", "mydb_test"),
+            ("application_name", "psql"),
+        ]);
+
+        assert_eq!(
+            parse_startup(&message),
+            Peeked::Found(PostgresStartup {
+                database: Some("mydb_test".into()),
+                user: Some("alice".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn database_defaults_to_user() {
+        let message = startup_message(&[("user", "alice")]);
+
+        assert_eq!(
+            parse_startup(&message),
+            Peeked::Found(PostgresStartup {
+                database: Some("alice".into()),
+                user: Some("alice".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn incomplete() {
+        let message = startup_message(&[("user", "alice"), ("database", "mydb_test")]);
+
+        for len in 0..message.len() {
+            assert_eq!(
+                parse_startup(message.get(..len).unwrap()),
+                Peeked::Incomplete,
+                "{len}"
+            );
+        }
+    }
+
+    #[rstest]
+    #[case::http(b"GET / HTTP/1.1\r\n\r\n")]
+    #[case::tls(b"\x16\x03\x01\x00\x10")]
+    #[case::ssl_request(b"\x00\x00\x00\x08\x04\xd2\x16\x2f")]
+    #[case::too_long(b"\x00\x01\x00\x00\x00\x03\x00\x00")]
+    fn missing(#[case] buf: &[u8]) {
+        assert_eq!(parse_startup(buf), Peeked::Missing);
+    }
+}
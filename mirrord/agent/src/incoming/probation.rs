@@ -0,0 +1,235 @@
+//! Falling back to a raw passthrough when hyper fails to parse the first request on a redirected
+//! HTTP/1 connection, see [`ProbationIo`].
+
+use std::{
+    io,
+    ops::Not,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use super::connection::IncomingIO;
+
+/// We give up on the fallback when hyper reads more than this without parsing a request.
+const MAX_RECORDED: usize = 64 * 1024;
+
+struct Shared {
+    /// [`None`] after the IO was taken with [`ProbationHandle::recover`].
+    io: Option<Box<dyn IncomingIO>>,
+
+    /// Bytes read from the IO while on probation.
+    ///
+    /// [`None`] after the probation ended.
+    recorded: Option<BytesMut>,
+
+    /// Bytes written to the IO while on probation, written for real when the probation ends.
+    held: BytesMut,
+}
+
+impl Shared {
+    /// Writes out the [`Shared::held`] bytes.
+    fn poll_write_held(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let Some(io) = self.io.as_mut() else {
+            return Poll::Ready(Err(io::ErrorKind::NotConnected.into()));
+        };
+
+        while self.held.is_empty().not() {
+            let written = std::task::ready!(Pin::new(&mut *io).poll_write(cx, &self.held))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.held.advance(written);
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn end_probation(&mut self) {
+        self.recorded = None;
+    }
+}
+
+/// Wrapper over a redirected HTTP/1 connection, given to hyper.
+///
+/// Hyper sometimes fails to parse what looked like HTTP during the detection (exotic clients,
+/// non-standard framing). When this happens on the first request, the connection is not HTTP we
+/// can handle, so instead of dropping it, we pass it through to its original destination.
+///
+/// Until the first request is parsed (the probation), this wrapper records everything read
+/// from the connection, and holds everything hyper writes to it (e.g. its `400 Bad Request`
+/// response). After a parse error, [`ProbationHandle::recover`] returns the connection together
+/// with the recorded bytes, and the client never sees the hyper response.
+///
+/// After the probation, hyper errors are handled as usual: hyper answers the broken request with
+/// an error response and closes the connection. Requests that were already extracted are not
+/// affected.
+pub struct ProbationIo {
+    shared: Arc<Mutex<Shared>>,
+}
+
+/// Ends the probation of a [`ProbationIo`], or recovers the connection from it.
+pub struct ProbationHandle {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl ProbationIo {
+    pub fn new(io: Box<dyn IncomingIO>) -> (Self, ProbationHandle) {
+        let shared = Arc::new(Mutex::new(Shared {
+            io: Some(io),
+            recorded: Some(BytesMut::new()),
+            held: BytesMut::new(),
+        }));
+
+        (
+            Self {
+                shared: shared.clone(),
+            },
+            ProbationHandle { shared },
+        )
+    }
+}
+
+impl ProbationHandle {
+    /// Ends the probation, should be called when hyper parses the first request.
+    pub fn end(&self) {
+        self.shared.lock().unwrap().end_probation();
+    }
+
+    /// Returns the connection and the bytes read from it, if the probation has not ended yet.
+    ///
+    /// The bytes written by hyper are discarded.
+    pub fn recover(self) -> Option<(Box<dyn IncomingIO>, Bytes)> {
+        let mut shared = self.shared.lock().unwrap();
+        let recorded = shared.recorded.take()?;
+        let io = shared.io.take()?;
+        Some((io, recorded.freeze()))
+    }
+}
+
+impl AsyncRead for ProbationIo {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut shared = self.shared.lock().unwrap();
+        let Some(io) = shared.io.as_mut() else {
+            return Poll::Ready(Err(io::ErrorKind::NotConnected.into()));
+        };
+
+        let filled_before = buf.filled().len();
+        std::task::ready!(Pin::new(io).poll_read(cx, buf))?;
+
+        if let Some(recorded) = shared.recorded.as_mut() {
+            let read = buf.filled().get(filled_before..).unwrap_or_default();
+            if recorded.len() + read.len() > MAX_RECORDED {
+                shared.end_probation();
+            } else {
+                recorded.extend_from_slice(read);
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for ProbationIo {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.recorded.is_some() {
+            shared.held.extend_from_slice(buf);
+            return Poll::Ready(Ok(buf.len()));
+        }
+
+        std::task::ready!(shared.poll_write_held(cx))?;
+        let Some(io) = shared.io.as_mut() else {
+            return Poll::Ready(Err(io::ErrorKind::NotConnected.into()));
+        };
+        Pin::new(io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.recorded.is_some() {
+            return Poll::Ready(Ok(()));
+        }
+
+        std::task::ready!(shared.poll_write_held(cx))?;
+        let Some(io) = shared.io.as_mut() else {
+            return Poll::Ready(Err(io::ErrorKind::NotConnected.into()));
+        };
+        Pin::new(io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.recorded.is_some() {
+            return Poll::Ready(Ok(()));
+        }
+
+        std::task::ready!(shared.poll_write_held(cx))?;
+        let Some(io) = shared.io.as_mut() else {
+            return Poll::Ready(Err(io::ErrorKind::NotConnected.into()));
+        };
+        Pin::new(io).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use futures::StreamExt;
+    use hyper_util::rt::TokioIo;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::ProbationIo;
+    use crate::http::{HttpVersion, extract_requests::ExtractedRequests};
+
+    /// Verifies that hyper's error response is held back, and the connection can be recovered
+    /// with the bytes hyper consumed.
+    #[tokio::test]
+    async fn recovers_after_parse_error() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let (io, handle) = ProbationIo::new(Box::new(server));
+        let mut requests = ExtractedRequests::new(TokioIo::new(io), HttpVersion::V1);
+
+        let garbage = b"GET / HTTP/1.1\r\nbad header\r\n\r\n";
+        client.write_all(garbage).await.unwrap();
+
+        requests.next().await.unwrap().unwrap_err();
+        std::mem::drop(requests);
+
+        let (mut server, recorded) = handle.recover().unwrap();
+        assert_eq!(recorded, Bytes::from_static(garbage));
+
+        server.write_all(b"raw").await.unwrap();
+        let mut buf = [0_u8; 3];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"raw");
+    }
+
+    /// Verifies that the connection cannot be recovered after the probation.
+    #[tokio::test]
+    async fn no_recovery_after_first_request() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let (io, handle) = ProbationIo::new(Box::new(server));
+        let mut requests = ExtractedRequests::new(TokioIo::new(io), HttpVersion::V1);
+
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: test\r\n\r\n")
+            .await
+            .unwrap();
+        let _request = requests.next().await.unwrap().unwrap();
+        handle.end();
+
+        assert!(handle.recover().is_none());
+    }
+}
@@ -0,0 +1,486 @@
+//! Routing of the commands of a stolen Redis connection, based on
+//! [`StealType::FilteredRedis`](mirrord_protocol::tcp::StealType::FilteredRedis) subscriptions.
+//!
+//! The commands are parsed from the [RESP](https://redis.io/docs/latest/develop/reference/protocol-spec/)
+//! stream sent by the peer, and the replies from the local application and the original
+//! destination are merged back in the order of the commands.
+
+use std::ops::Not;
+
+use fancy_regex::Regex;
+use mirrord_protocol::tcp::RedisFilter;
+
+use super::peek::Peeked;
+
+/// Maximum length of an inline command or a RESP header line, same as in the Redis server.
+const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// Maximum length of a bulk string, same as in the Redis server.
+const MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
+/// Commands that do not take a key as their first argument, or that change the state of the
+/// connection.
+///
+/// These are always sent to the original destination.
+const KEYLESS_COMMANDS: &[&str] = &[
+    "ACL",
+    "AUTH",
+    "BGREWRITEAOF",
+    "BGSAVE",
+    "BITOP",
+    "BLMPOP",
+    "BZMPOP",
+    "CLIENT",
+    "CLUSTER",
+    "COMMAND",
+    "CONFIG",
+    "DBSIZE",
+    "DEBUG",
+    "DISCARD",
+    "ECHO",
+    "EVAL",
+    "EVALSHA",
+    "EVALSHA_RO",
+    "EVAL_RO",
+    "EXEC",
+    "FCALL",
+    "FCALL_RO",
+    "FLUSHALL",
+    "FLUSHDB",
+    "FUNCTION",
+    "HELLO",
+    "INFO",
+    "KEYS",
+    "LASTSAVE",
+    "LATENCY",
+    "LMPOP",
+    "MEMORY",
+    "MIGRATE",
+    "MODULE",
+    "MONITOR",
+    "MULTI",
+    "OBJECT",
+    "PING",
+    "PSUBSCRIBE",
+    "PUBLISH",
+    "PUBSUB",
+    "PUNSUBSCRIBE",
+    "QUIT",
+    "RANDOMKEY",
+    "READONLY",
+    "READWRITE",
+    "REPLICAOF",
+    "RESET",
+    "ROLE",
+    "SAVE",
+    "SCAN",
+    "SCRIPT",
+    "SELECT",
+    "SHUTDOWN",
+    "SINTERCARD",
+    "SLAVEOF",
+    "SLOWLOG",
+    "SPUBLISH",
+    "SSUBSCRIBE",
+    "SUBSCRIBE",
+    "SUNSUBSCRIBE",
+    "SWAPDB",
+    "TIME",
+    "UNSUBSCRIBE",
+    "UNWATCH",
+    "WAIT",
+    "WAITAOF",
+    "WATCH",
+    "XREAD",
+    "XREADGROUP",
+    "ZDIFF",
+    "ZINTER",
+    "ZINTERCARD",
+    "ZMPOP",
+    "ZUNION",
+];
+
+/// Commands after which the server starts pushing data that does not answer any command.
+const PUSH_MODE_COMMANDS: &[&str] = &["MONITOR", "PSUBSCRIBE", "SSUBSCRIBE", "SUBSCRIBE"];
+
+/// Compiled [`RedisFilter`].
+#[derive(Debug, Clone)]
+pub struct RedisCommandFilter {
+    /// Case-insensitive regex for the command name.
+    command: Option<Regex>,
+    /// Regex for the first key of the command.
+    key: Option<Regex>,
+}
+
+impl RedisCommandFilter {
+    pub fn new(filter: &RedisFilter) -> Result<Self, fancy_regex::Error> {
+        Ok(Self {
+            command: filter
+                .command
+                .as_ref()
+                .map(|command| Regex::new(&format!("(?i){command}")))
+                .transpose()?,
+            key: filter.key.as_deref().map(Regex::new).transpose()?,
+        })
+    }
+
+    /// Checks whether the command with the given uppercase name and arguments matches this
+    /// filter.
+    fn matches(&self, name: &str, args: &[&[u8]]) -> bool {
+        if KEYLESS_COMMANDS.contains(&name) {
+            return false;
+        }
+
+        let Some(key) = args.first() else {
+            return false;
+        };
+        let key = String::from_utf8_lossy(key);
+
+        self.command
+            .iter()
+            .all(|regex| regex.is_match(name).unwrap_or_default())
+            && self
+                .key
+                .iter()
+                .all(|regex| regex.is_match(&key).unwrap_or_default())
+    }
+}
+
+/// Destination of a Redis command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Route {
+    /// The local application, via the stealing client.
+    Local,
+    /// The original destination.
+    Remote,
+}
+
+/// Routes the commands of a single Redis connection.
+#[derive(Debug)]
+pub struct RedisRouter {
+    filter: RedisCommandFilter,
+    /// Whether we're inside of a `MULTI` transaction.
+    in_transaction: bool,
+    /// Whether the rest of the connection should be passed through to the original destination,
+    /// without parsing the commands.
+    passthrough: bool,
+    /// Whether the local application is gone.
+    local_closed: bool,
+}
+
+impl RedisRouter {
+    pub fn new(filter: RedisCommandFilter) -> Self {
+        Self {
+            filter,
+            in_transaction: false,
+            passthrough: false,
+            local_closed: false,
+        }
+    }
+
+    /// Returns the destination for the command with the given arguments, and updates the state
+    /// of the connection.
+    pub fn route(&mut self, args: &[&[u8]]) -> Route {
+        let Some((name, args)) = args.split_first() else {
+            return Route::Remote;
+        };
+        let name = String::from_utf8_lossy(name).to_ascii_uppercase();
+
+        let route = if self.in_transaction.not()
+            && self.local_closed.not()
+            && self.filter.matches(&name, args)
+        {
+            Route::Local
+        } else {
+            Route::Remote
+        };
+
+        match name.as_str() {
+            "MULTI" => self.in_transaction = true,
+            "EXEC" | "DISCARD" => self.in_transaction = false,
+            name if PUSH_MODE_COMMANDS.contains(&name) => self.passthrough = true,
+            _ => {}
+        }
+
+        route
+    }
+
+    /// Whether the rest of the connection should be passed through to the original destination.
+    pub fn is_passthrough(&self) -> bool {
+        self.passthrough
+    }
+
+    /// Stops parsing the commands, the rest of the connection will be passed through to the
+    /// original destination.
+    pub fn pass_through(&mut self) {
+        self.passthrough = true;
+    }
+
+    /// Routes all further commands to the original destination.
+    pub fn close_local(&mut self) {
+        self.local_closed = true;
+    }
+}
+
+/// A complete command parsed from the beginning of a buffer.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RedisCommand<'a> {
+    /// Name and arguments of the command.
+    ///
+    /// Empty when the peer sent an empty inline command, which is ignored by the server.
+    pub args: Vec<&'a [u8]>,
+    /// Length of the command in the buffer.
+    pub len: usize,
+}
+
+/// Parses the command at the beginning of the given buffer.
+///
+/// Supports both RESP arrays and inline commands. Returns [`Peeked::Missing`] when the data is
+/// not valid RESP.
+pub fn parse_command(buf: &[u8]) -> Peeked<RedisCommand<'_>> {
+    let mut reader = RespReader { buf, position: 0 };
+
+    match reader.command() {
+        Ok(args) => Peeked::Found(RedisCommand {
+            args,
+            len: reader.position,
+        }),
+        Err(RespError::Incomplete) => Peeked::Incomplete,
+        Err(RespError::Malformed) => Peeked::Missing,
+    }
+}
+
+/// Returns the length of the reply at the beginning of the given buffer.
+///
+/// Supports both RESP2 and RESP3 replies. Returns [`Peeked::Missing`] when the data is not valid
+/// RESP.
+pub fn reply_len(buf: &[u8]) -> Peeked<usize> {
+    let mut reader = RespReader { buf, position: 0 };
+
+    match reader.value() {
+        Ok(()) => Peeked::Found(reader.position),
+        Err(RespError::Incomplete) => Peeked::Incomplete,
+        Err(RespError::Malformed) => Peeked::Missing,
+    }
+}
+
+enum RespError {
+    Incomplete,
+    Malformed,
+}
+
+/// Reader over RESP data.
+struct RespReader<'a> {
+    buf: &'a [u8],
+    position: usize,
+}
+
+impl<'a> RespReader<'a> {
+    /// Reads a line, without the line terminator.
+    fn line(&mut self) -> Result<&'a [u8], RespError> {
+        let rest = self.buf.get(self.position..).unwrap_or_default();
+
+        let Some(end) = rest.iter().position(|byte| *byte == b'\n') else {
+            return Err(if rest.len() > MAX_LINE_LEN {
+                RespError::Malformed
+            } else {
+                RespError::Incomplete
+            });
+        };
+
+        self.position += end + 1;
+        let line = rest.get(..end).unwrap_or_default();
+        Ok(line.strip_suffix(b"\r").unwrap_or(line))
+    }
+
+    fn integer(data: &[u8]) -> Result<i64, RespError> {
+        std::str::from_utf8(data)
+            .ok()
+            .and_then(|data| data.parse().ok())
+            .ok_or(RespError::Malformed)
+    }
+
+    /// Reads the contents of a bulk string with the given length.
+    ///
+    /// Negative length denotes a null bulk string.
+    fn bulk(&mut self, len: i64) -> Result<Option<&'a [u8]>, RespError> {
+        let Ok(len) = usize::try_from(len) else {
+            return Ok(None);
+        };
+        if len > MAX_BULK_LEN {
+            return Err(RespError::Malformed);
+        }
+
+        let data = self
+            .buf
+            .get(self.position..self.position + len)
+            .ok_or(RespError::Incomplete)?;
+        let terminator = self
+            .buf
+            .get(self.position + len..self.position + len + 2)
+            .ok_or(RespError::Incomplete)?;
+        if terminator != b"\r\n" {
+            return Err(RespError::Malformed);
+        }
+
+        self.position += len + 2;
+        Ok(Some(data))
+    }
+
+    /// Reads a command, either an array of bulk strings or an inline command.
+    fn command(&mut self) -> Result<Vec<&'a [u8]>, RespError> {
+        let line = self.line()?;
+
+        let Some((b'*', count)) = line.split_first() else {
+            return Ok(line
+                .split(u8::is_ascii_whitespace)
+                .filter(|arg| arg.is_empty().not())
+                .collect());
+        };
+
+        (0..Self::integer(count)?)
+            .map(|_| match self.line()?.split_first() {
+                Some((b'$', len)) => self.bulk(Self::integer(len)?)?.ok_or(RespError::Malformed),
+                _ => Err(RespError::Malformed),
+            })
+            .collect()
+    }
+
+    /// Skips a single value, including nested values of aggregates.
+    fn value(&mut self) -> Result<(), RespError> {
+        let mut remaining: i64 = 1;
+
+        while remaining > 0 {
+            remaining -= 1;
+
+            let line = self.line()?;
+            let Some((kind, rest)) = line.split_first() else {
+                return Err(RespError::Malformed);
+            };
+
+            match kind {
+                // Simple string, error, integer, null, double, boolean, big number.
+                b'+' | b'-' | b':' | b'_' | b',' | b'#' | b'(' => {}
+                // Bulk string, bulk error, verbatim string.
+                b'$' | b'!' | b'=' => {
+                    self.bulk(Self::integer(rest)?)?;
+                }
+                // Array, set, push.
+                b'*' | b'~' | b'>' => remaining += Self::integer(rest)?.max(0),
+                // Map.
+                b'%' => remaining += Self::integer(rest)?.max(0).saturating_mul(2),
+                // Attributes, followed by the actual value.
+                b'|' => remaining += Self::integer(rest)?.max(0).saturating_mul(2) + 1,
+                _ => return Err(RespError::Malformed),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mirrord_protocol::tcp::Filter;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn router(command: Option<&str>, key: Option<&str>) -> RedisRouter {
+        let filter = RedisCommandFilter::new(&RedisFilter {
+            command: command.map(|filter| Filter::new(filter.into()).unwrap()),
+            key: key.map(|filter| Filter::new(filter.into()).unwrap()),
+        })
+        .unwrap();
+
+        RedisRouter::new(filter)
+    }
+
+    fn args<'a>(args: &[&'a str]) -> Vec<&'a [u8]> {
+        args.iter().map(|arg| arg.as_bytes()).collect()
+    }
+
+    #[test]
+    fn parses_commands() {
+        let buf = b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\nGET key\r\n*1\r\n$4\r\nPI";
+
+        let Peeked::Found(first) = parse_command(buf) else {
+            panic!("expected a command");
+        };
+        assert_eq!(first.args, [&b"SET"[..], b"key", b"value"]);
+
+        let rest = buf.get(first.len..).unwrap();
+        let Peeked::Found(second) = parse_command(rest) else {
+            panic!("expected an inline command");
+        };
+        assert_eq!(second.args, [&b"GET"[..], b"key"]);
+
+        let rest = rest.get(second.len..).unwrap();
+        assert_eq!(parse_command(rest), Peeked::Incomplete);
+    }
+
+    #[rstest]
+    #[case::simple(b"+OK\r\n")]
+    #[case::null_bulk(b"$-1\r\n")]
+    #[case::bulk(b"$5\r\nhello\r\n")]
+    #[case::nested(b"*2\r\n*1\r\n:1\r\n$2\r\nab\r\n")]
+    #[case::map(b"%1\r\n+key\r\n,1.5\r\n")]
+    #[case::attribute(b"|1\r\n+ttl\r\n:10\r\n+value\r\n")]
+    fn reply_lengths(#[case] reply: &[u8]) {
+        let mut buf = reply.to_vec();
+        buf.extend_from_slice(b"+NEXT\r\n");
+
+        assert_eq!(reply_len(&buf), Peeked::Found(reply.len()));
+
+        for len in 0..reply.len() {
+            assert_eq!(
+                reply_len(reply.get(..len).unwrap()),
+                Peeked::Incomplete,
+                "{len}"
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_reply() {
+        assert_eq!(reply_len(b"$3\r\nabcd\r\n"), Peeked::Missing);
+        assert_eq!(reply_len(b"?\r\n"), Peeked::Missing);
+    }
+
+    #[rstest]
+    #[case::key(None, Some("^session:"), &["GET", "session:1"], Route::Local)]
+    #[case::key_mismatch(None, Some("^session:"), &["GET", "user:1"], Route::Remote)]
+    #[case::command(Some("^get$"), None, &["get", "user:1"], Route::Local)]
+    #[case::command_mismatch(Some("^get$"), Some("^user:"), &["SET", "user:1", "a"], Route::Remote)]
+    #[case::keyless(Some(".*"), None, &["PING", "session:1"], Route::Remote)]
+    #[case::no_key(Some(".*"), None, &["GET"], Route::Remote)]
+    fn routes_commands(
+        #[case] command: Option<&str>,
+        #[case] key: Option<&str>,
+        #[case] args: &[&str],
+        #[case] expected: Route,
+    ) {
+        assert_eq!(router(command, key).route(&self::args(args)), expected);
+    }
+
+    #[test]
+    fn transactions_are_routed_remotely() {
+        let mut router = router(None, Some("^session:"));
+
+        assert_eq!(router.route(&args(&["MULTI"])), Route::Remote);
+        assert_eq!(router.route(&args(&["GET", "session:1"])), Route::Remote);
+        assert_eq!(router.route(&args(&["EXEC"])), Route::Remote);
+        assert_eq!(router.route(&args(&["GET", "session:1"])), Route::Local);
+
+        router.close_local();
+        assert_eq!(router.route(&args(&["GET", "session:1"])), Route::Remote);
+    }
+
+    #[test]
+    fn subscribe_passes_through() {
+        let mut router = router(None, Some(".*"));
+
+        router.route(&args(&["subscribe", "channel"]));
+        assert!(router.is_passthrough());
+    }
+}
@@ -9,9 +9,9 @@ use tokio_stream::{StreamMap, StreamNotifyClose, wrappers::ReceiverStream};
 use tokio_util::sync::CancellationToken;
 
 use super::{
-    connection::{ConnectionInfo, http::RedirectedHttp, tcp::RedirectedTcp},
+    connection::{ConnectionInfo, ConnectionPeeks, http::RedirectedHttp, tcp::RedirectedTcp},
     error::RedirectorTaskError,
-    task::{RedirectRequest, TaskError},
+    task::{PortPeeks, RedirectRequest, TaskError},
 };
 
 /// Handle to a running [`RedirectorTask`](super::task::RedirectorTask).
@@ -29,14 +29,21 @@ pub struct StealHandle {
     task_error: TaskError,
     /// For receiving stolen connections.
     stolen_ports: StreamMap<u16, StreamNotifyClose<ReceiverStream<StolenTraffic>>>,
+    /// Initial messages that the task should peek from the stolen connections.
+    peeks: PortPeeks,
 }
 
 impl StealHandle {
-    pub(super) fn new(message_tx: mpsc::Sender<RedirectRequest>, task_error: TaskError) -> Self {
+    pub(super) fn new(
+        message_tx: mpsc::Sender<RedirectRequest>,
+        task_error: TaskError,
+        peeks: PortPeeks,
+    ) -> Self {
         Self {
             message_tx,
             task_error,
             stolen_ports: Default::default(),
+            peeks,
         }
     }
 
@@ -79,6 +86,30 @@ impl StealHandle {
         // This drops our traffic `mpsc::Receiver`,
         // which should be detected by the `RedirectorTask`.
         self.stolen_ports.remove(&port);
+        self.peeks.write().unwrap().remove(&port);
+    }
+
+    /// Sets the initial messages to peek from the new connections stolen from the given port.
+    ///
+    /// Connections that are already being detected are not affected.
+    pub fn set_peeks(&mut self, port: u16, peeks: ConnectionPeeks) {
+        let mut all_peeks = self.peeks.write().unwrap();
+        if peeks == ConnectionPeeks::default() {
+            all_peeks.remove(&port);
+        } else {
+            all_peeks.insert(port, peeks);
+        }
+    }
+
+    /// Returns the initial messages peeked from the new connections stolen from the given port.
+    #[cfg(test)]
+    pub fn peeks(&self, port: u16) -> ConnectionPeeks {
+        self.peeks
+            .read()
+            .unwrap()
+            .get(&port)
+            .copied()
+            .unwrap_or_default()
     }
 
     /// Returns stolen traffic.
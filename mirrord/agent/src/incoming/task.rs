@@ -3,12 +3,14 @@ use std::{
     error::{Error, Report},
     fmt,
     ops::Not,
-    sync::Arc,
+    sync::{Arc, RwLock},
+    time::Duration,
 };
 
 use futures::{FutureExt, StreamExt, future::Shared};
 use hyper_util::rt::TokioIo;
 use mirrord_agent_env::envs;
+use socket2::{SockRef, TcpKeepalive};
 use tokio::{
     sync::{
         mpsc::{self, error::TrySendError},
@@ -21,14 +23,22 @@ use tracing::Level;
 
 use super::{
     PortRedirector, Redirected,
-    connection::{ConnectionInfo, MaybeHttp, http::RedirectedHttp, tcp::RedirectedTcp},
+    capture::{CaptureConfig, StolenCapture},
+    connection::{
+        ConnectionInfo, ConnectionPeeks, MaybeHttp, http::RedirectedHttp, tcp::RedirectedTcp,
+    },
     error::RedirectorTaskError,
+    probation::{ProbationHandle, ProbationIo},
     steal_handle::{StealHandle, StolenTraffic},
     tls::StealTlsHandlerStore,
 };
 use crate::{
-    http::extract_requests::{ExtractedRequest, ExtractedRequests},
+    http::{
+        HttpVersion,
+        extract_requests::{ExtractedRequest, ExtractedRequests},
+    },
     incoming::{MirroredTraffic, mirror_handle::MirrorHandle},
+    util::rolledback_stream::RolledBackStream,
 };
 
 /// A task responsible for redirecting incoming connections.
@@ -62,8 +72,14 @@ pub struct RedirectorTask<R> {
     internal_tx: mpsc::Sender<InternalMessage>,
     /// For accepting redirected TLS connections.
     tls_store: StealTlsHandlerStore,
+    /// Initial messages to peek from the redirected connections, set by the [`StealHandle`].
+    peeks: PortPeeks,
     /// Configuration
     config: RedirectorTaskConfig,
+    /// Captures the stolen connections, see [`RedirectorTaskConfig::capture_stolen`].
+    ///
+    /// Started in [`Self::run`].
+    capture: Option<StolenCapture>,
 }
 
 impl<R> RedirectorTask<R>
@@ -82,6 +98,7 @@ where
         let (error_tx, error_rx) = oneshot::channel();
         let (message_tx, message_rx) = mpsc::channel(16);
         let (internal_tx, internal_rx) = mpsc::channel(16);
+        let peeks = PortPeeks::default();
 
         let task = Self {
             redirector,
@@ -91,11 +108,13 @@ where
             internal_rx,
             internal_tx,
             tls_store,
+            peeks: peeks.clone(),
             config,
+            capture: None,
         };
 
         let task_error = TaskError(error_rx.shared());
-        let steal_handle = StealHandle::new(message_tx.clone(), task_error.clone());
+        let steal_handle = StealHandle::new(message_tx.clone(), task_error.clone(), peeks);
         let mirror_handle = MirrorHandle::new(message_tx, task_error);
 
         (task, steal_handle, mirror_handle)
@@ -169,9 +188,32 @@ where
             return;
         };
 
+        if state.steal_tx.is_some()
+            && let Some(keepalive) = self.config.tcp_keepalive
+        {
+            let keepalive = TcpKeepalive::new()
+                .with_time(keepalive)
+                .with_interval(keepalive);
+            if let Err(error) = SockRef::from(&conn.stream).set_tcp_keepalive(&keepalive) {
+                tracing::warn!(
+                    %error,
+                    %source,
+                    %destination,
+                    "Failed to enable TCP keepalive on a redirected connection",
+                );
+            }
+        }
+
         if state.mirror_txs.is_empty().not() || state.steal_tx.is_some() {
             let tx = self.internal_tx.clone();
             let tls_store = self.tls_store.clone();
+            let peeks = self
+                .peeks
+                .read()
+                .unwrap()
+                .get(&destination.port())
+                .copied()
+                .unwrap_or_default();
             let shutdown = state.shutdown.child_token();
             Self::spawn_tracked_connection(
                 self.internal_tx.clone(),
@@ -179,7 +221,7 @@ where
                 state,
                 async move {
                     let detection_result = tokio::select! {
-                        r = MaybeHttp::detect(conn, &tls_store) => r,
+                        r = MaybeHttp::detect(conn, &tls_store, peeks) => r,
                         _ = shutdown.cancelled() => {
                             tracing::debug!("Shutting down redirected connection during HTTP detection");
                             return;
@@ -218,6 +260,8 @@ where
                 local_addr,
                 peer_addr: source,
                 tls_connector: None,
+                server_name: None,
+                postgres: None,
             };
 
             let shutdown = state.shutdown.child_token();
@@ -242,7 +286,7 @@ where
     }
 
     #[tracing::instrument(level = Level::TRACE, ret)]
-    async fn handle_initialized_connection(&mut self, conn: MaybeHttp) {
+    async fn handle_initialized_connection(&mut self, mut conn: MaybeHttp) {
         let port = conn.info.original_destination.port();
         let Some(port_state) = self.ports.get_mut(&port) else {
             tracing::warn!(
@@ -252,6 +296,12 @@ where
             return;
         };
 
+        if port_state.steal_tx.is_some()
+            && let Some(capture) = &self.capture
+        {
+            conn.stream = Box::new(capture.capture(conn.stream, &conn.info));
+        }
+
         let Some(http_version) = conn.http_version else {
             let mut redirected = RedirectedTcp::new(conn.stream, conn.info);
 
@@ -294,7 +344,15 @@ where
 
         let tx = self.internal_tx.clone();
         let token = port_state.shutdown.clone();
-        let mut requests = ExtractedRequests::new(TokioIo::new(conn.stream), http_version);
+        let (io, probation) = ProbationIo::new(conn.stream);
+        let mut probation = match http_version {
+            HttpVersion::V1 => Some(probation),
+            HttpVersion::V2 => {
+                probation.end();
+                None
+            }
+        };
+        let mut requests = ExtractedRequests::new(TokioIo::new(io), http_version);
 
         Self::spawn_tracked_connection(self.internal_tx.clone(), port, port_state, async move {
             let mut shutting_down = false;
@@ -316,13 +374,46 @@ where
 
                 let request = match result {
                     None => break,
-                    Some(Ok(request)) => request,
+                    Some(Ok(request)) => {
+                        if let Some(probation) = probation.take() {
+                            probation.end();
+                        }
+                        request
+                    }
                     Some(Err(error)) => {
+                        // Hyper failed to parse the first request, this is not HTTP we can
+                        // handle. Instead of dropping the connection, we pass it through.
+                        let recovered = if error.is_parse() {
+                            probation.take().and_then(ProbationHandle::recover)
+                        } else {
+                            None
+                        };
+
+                        let Some((io, consumed)) = recovered else {
+                            tracing::warn!(
+                                error = %Report::new(error),
+                                connection = ?conn.info,
+                                "Redirected HTTP connection failed",
+                            );
+                            break;
+                        };
+
                         tracing::warn!(
                             error = %Report::new(error),
                             connection = ?conn.info,
-                            "Redirected HTTP connection failed",
+                            "Failed to parse the first request on a redirected HTTP connection, \
+                            passing the connection through",
                         );
+                        let stream = RolledBackStream::new(io, consumed);
+                        if let Err(error) = RedirectedTcp::new(Box::new(stream), conn.info.clone())
+                            .pass_through(token.child_token())
+                            .await
+                        {
+                            tracing::warn!(
+                                ?error,
+                                "Redirected passthrough task returned JoinError"
+                            );
+                        }
                         break;
                     }
                 };
@@ -531,6 +622,8 @@ where
     ///
     /// This should be called only in the target's network namespace.
     pub async fn run(mut self) -> Result<(), RedirectorTaskError> {
+        self.capture = self.config.capture_stolen.clone().map(StolenCapture::spawn);
+
         let main_result = self.run_inner().await;
         let cleanup_result = self.redirector.cleanup().await;
 
@@ -558,16 +651,28 @@ impl<R> fmt::Debug for RedirectorTask<R> {
 pub struct RedirectorTaskConfig {
     /// Inject `Mirrord-Agent` headers into responses to stolen requests
     pub inject_headers: bool,
+    /// Idle time and probe interval of TCP keepalive on connections redirected from stolen ports
+    pub tcp_keepalive: Option<Duration>,
+    /// Capture raw bytes of stolen connections
+    pub capture_stolen: Option<CaptureConfig>,
 }
 
 impl RedirectorTaskConfig {
     pub fn from_env() -> Self {
         Self {
             inject_headers: envs::INJECT_HEADERS.from_env_or_default(),
+            tcp_keepalive: Some(envs::TCP_KEEPALIVE.from_env_or_default())
+                .filter(|secs| *secs > 0)
+                .map(|secs| Duration::from_secs(secs.into())),
+            capture_stolen: CaptureConfig::from_env(),
         }
     }
 }
 
+/// [`ConnectionPeeks`] for the stolen ports, shared between the [`RedirectorTask`] and its
+/// [`StealHandle`].
+pub type PortPeeks = Arc<RwLock<HashMap<u16, ConnectionPeeks>>>;
+
 /// Channel that represents a port steal made with a [`StealHandle`].
 ///
 /// The handle uses it to receive stolen connections.
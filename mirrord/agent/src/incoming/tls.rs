@@ -26,6 +26,7 @@ use crate::util::path_resolver::InTargetPathResolver;
 
 pub mod error;
 pub mod handler;
+pub mod sni;
 #[cfg(test)]
 pub mod test;
 
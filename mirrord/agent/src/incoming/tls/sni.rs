@@ -0,0 +1,184 @@
+//! Extraction of the server name (SNI) from a TLS ClientHello, without terminating TLS.
+//!
+//! Used to route stolen TLS connections based on
+//! [`StealType::FilteredSni`](mirrord_protocol::tcp::StealType::FilteredSni) subscriptions.
+
+use std::ops::Not;
+
+use crate::incoming::peek::{Peeked, Reader};
+
+/// TLS record content type of handshake messages.
+const CONTENT_TYPE_HANDSHAKE: u8 = 0x16;
+
+/// TLS handshake message type of the ClientHello.
+const HANDSHAKE_TYPE_CLIENT_HELLO: u8 = 0x01;
+
+/// TLS extension type of the server name indication.
+const EXTENSION_SERVER_NAME: u16 = 0x0000;
+
+/// Server name type of a DNS hostname.
+const SERVER_NAME_TYPE_HOST_NAME: u8 = 0x00;
+
+/// Length of a TLS record header (content type, protocol version, length).
+const RECORD_HEADER_LEN: usize = 5;
+
+/// Maximum length of a TLS record, including the header and the allowed expansion.
+pub const MAX_RECORD_LEN: usize = RECORD_HEADER_LEN + (1 << 14) + 2048;
+
+/// Parses the server name from the first TLS record in the given buffer.
+///
+/// Only ClientHellos that fit in a single TLS record are supported.
+pub fn parse_sni(buf: &[u8]) -> Peeked<String> {
+    let Some(&content_type) = buf.first() else {
+        return Peeked::Incomplete;
+    };
+    if content_type != CONTENT_TYPE_HANDSHAKE {
+        return Peeked::Missing;
+    }
+
+    let mut header = Reader(buf);
+    let Some(record_len) = header.take(3).and_then(|_| header.u16()).map(usize::from) else {
+        return Peeked::Incomplete;
+    };
+
+    let Some(record) = header.take(record_len) else {
+        return Peeked::Incomplete;
+    };
+
+    match server_name(Reader(record)) {
+        Some(name) => Peeked::Found(name),
+        None => Peeked::Missing,
+    }
+}
+
+/// Walks the ClientHello in the given TLS record and returns the host name from its SNI
+/// extension.
+fn server_name(mut record: Reader<'_>) -> Option<String> {
+    if record.u8()? != HANDSHAKE_TYPE_CLIENT_HELLO {
+        return None;
+    }
+
+    let mut hello = Reader(record.take(record.u24()?)?);
+    // Legacy version and random.
+    hello.take(2 + 32)?;
+    // Session id.
+    let len = hello.u8()?.into();
+    hello.take(len)?;
+    // Cipher suites.
+    let len = hello.u16()?.into();
+    hello.take(len)?;
+    // Compression methods.
+    let len = hello.u8()?.into();
+    hello.take(len)?;
+
+    let len = hello.u16()?.into();
+    let mut extensions = Reader(hello.take(len)?);
+    while extensions.is_empty().not() {
+        let extension_type = extensions.u16()?;
+        let len = extensions.u16()?.into();
+        let data = extensions.take(len)?;
+
+        if extension_type != EXTENSION_SERVER_NAME {
+            continue;
+        }
+
+        let mut data = Reader(data);
+        let len = data.u16()?.into();
+        let mut names = Reader(data.take(len)?);
+        while names.is_empty().not() {
+            let name_type = names.u8()?;
+            let len = names.u16()?.into();
+            let name = names.take(len)?;
+
+            if name_type == SERVER_NAME_TYPE_HOST_NAME {
+                return std::str::from_utf8(name).ok().map(str::to_ascii_lowercase);
+            }
+        }
+
+        return None;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use bytes::BytesMut;
+    use rstest::rstest;
+    use rustls::{
+        ClientConfig, ClientConnection, RootCertStore, crypto::CryptoProvider,
+        pki_types::ServerName,
+    };
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::{incoming::peek::peek, util::rolledback_stream::RolledBackStream};
+
+    /// Produces the first TLS record sent by a [`rustls`] client connecting to `server_name`.
+    fn client_hello(server_name: &str) -> Vec<u8> {
+        let _ = CryptoProvider::install_default(rustls::crypto::aws_lc_rs::default_provider());
+
+        let config = ClientConfig::builder()
+            .with_root_certificates(RootCertStore::empty())
+            .with_no_client_auth();
+        let server_name = ServerName::try_from(server_name.to_owned()).unwrap();
+        let mut connection = ClientConnection::new(Arc::new(config), server_name).unwrap();
+
+        let mut buf = Vec::new();
+        connection.write_tls(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn finds_server_name() {
+        let hello = client_hello("API.example.com");
+        assert_eq!(parse_sni(&hello), Peeked::Found("api.example.com".into()));
+    }
+
+    #[test]
+    fn ip_address_has_no_server_name() {
+        let hello = client_hello("10.0.0.1");
+        assert_eq!(parse_sni(&hello), Peeked::Missing);
+    }
+
+    #[rstest]
+    #[case::empty(b"")]
+    #[case::partial_header(b"\x16\x03\x01")]
+    #[case::partial_record(b"\x16\x03\x01\x00\x10\x01\x00")]
+    fn incomplete(#[case] buf: &[u8]) {
+        assert_eq!(parse_sni(buf), Peeked::Incomplete);
+    }
+
+    #[rstest]
+    #[case::http(b"GET / HTTP/1.1\r\n\r\n")]
+    #[case::not_client_hello(b"\x16\x03\x01\x00\x01\x02")]
+    #[case::truncated_hello(b"\x16\x03\x01\x00\x04\x01\x00\x00\x10")]
+    fn missing(#[case] buf: &[u8]) {
+        assert_eq!(parse_sni(buf), Peeked::Missing);
+    }
+
+    #[tokio::test]
+    async fn detect_reads_fragmented_hello() {
+        let hello = client_hello("api.example.com");
+        let (first, rest) = hello.split_at(20);
+
+        let (client, mut server) = tokio::io::duplex(hello.len() * 2);
+        let stream = RolledBackStream::new(client, BytesMut::from(first));
+
+        tokio::io::AsyncWriteExt::write_all(&mut server, rest)
+            .await
+            .unwrap();
+
+        let (mut stream, server_name) =
+            peek(stream, Duration::from_secs(5), MAX_RECORD_LEN, parse_sni)
+                .await
+                .unwrap();
+        assert_eq!(server_name.as_deref(), Some("api.example.com"));
+
+        let mut read = vec![0; hello.len()];
+        stream.read_exact(&mut read).await.unwrap();
+        assert_eq!(read, hello);
+    }
+}
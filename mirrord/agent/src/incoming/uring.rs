@@ -0,0 +1,17 @@
+//! Experimental io_uring IO path for redirected connections, enabled with the `io-uring` feature.
+
+use std::io;
+
+use mirrord_agent_uring::{Driver, UringStream};
+use tokio::net::TcpStream;
+use tokio_util::either::Either;
+
+/// Moves the redirected connection to the shared io_uring [`Driver`].
+///
+/// Returns the connection unchanged if io_uring is not available.
+pub fn wrap(stream: TcpStream) -> io::Result<Either<TcpStream, UringStream>> {
+    match Driver::global() {
+        Some(driver) => UringStream::from_tokio(stream, driver).map(Either::Right),
+        None => Ok(Either::Left(stream)),
+    }
+}
@@ -0,0 +1,90 @@
+use std::{io, net::IpAddr, sync::Arc};
+
+use futures::{StreamExt, stream::FuturesOrdered};
+use mirrord_protocol::{
+    ResponseError,
+    interfaces::{GetInterfacesResult, InterfaceAddress},
+};
+use nix::sys::socket::SockaddrStorage;
+use tokio::{runtime::Handle, task::JoinHandle};
+
+use crate::{
+    error::{AgentError, AgentResult},
+    task::BgTaskRuntime,
+};
+
+/// Handles [`ClientMessage::GetInterfaces`](mirrord_protocol::codec::ClientMessage::GetInterfaces)
+/// requests.
+///
+/// Every client connection should use its own instance.
+pub struct InterfacesApi {
+    handle: Handle,
+    /// [`FuturesOrdered`] guarantee that we produce responses in the correct order.
+    results: FuturesOrdered<JoinHandle<io::Result<Vec<InterfaceAddress>>>>,
+}
+
+impl InterfacesApi {
+    /// Creates a new instance, which will list the interfaces using tasks spawned on
+    /// [`BgTaskRuntime::handle`].
+    ///
+    /// If this agent has a target, this runtime should live in the target's network namespace.
+    pub fn new(network_runtime: &BgTaskRuntime) -> Self {
+        Self {
+            handle: network_runtime.handle().clone(),
+            results: Default::default(),
+        }
+    }
+
+    /// Issues an asynchronous request for the list of interfaces.
+    ///
+    /// When available, the result will be returned from [`Self::recv`].
+    pub fn request_interfaces(&mut self) {
+        let task = self.handle.spawn_blocking(list_interfaces);
+        self.results.push_back(task);
+    }
+
+    /// Returns the result of the oldest request made with [`Self::request_interfaces`].
+    pub async fn recv(&mut self) -> AgentResult<GetInterfacesResult> {
+        let Some(result) = self.results.next().await else {
+            return std::future::pending().await;
+        };
+
+        Ok(result
+            .map_err(|error| AgentError::BackgroundTaskFailed {
+                task: "list_interfaces",
+                error: Arc::new(error),
+            })?
+            .map_err(ResponseError::from))
+    }
+}
+
+/// Lists IP addresses of the network interfaces visible in the current network namespace.
+fn list_interfaces() -> io::Result<Vec<InterfaceAddress>> {
+    let interfaces = nix::ifaddrs::getifaddrs()?
+        .filter_map(|interface| {
+            let address = interface.address.as_ref().and_then(ip_address)?;
+
+            Some(InterfaceAddress {
+                name: interface.interface_name,
+                flags: interface.flags.bits() as u32,
+                address,
+                netmask: interface.netmask.as_ref().and_then(ip_address),
+                broadcast_or_destination: interface
+                    .broadcast
+                    .as_ref()
+                    .or(interface.destination.as_ref())
+                    .and_then(ip_address),
+            })
+        })
+        .collect();
+
+    Ok(interfaces)
+}
+
+/// Extracts the IP from the given address, if it's an IPv4 or IPv6 address.
+fn ip_address(address: &SockaddrStorage) -> Option<IpAddr> {
+    address
+        .as_sockaddr_in()
+        .map(|addr| IpAddr::V4(addr.ip()))
+        .or_else(|| address.as_sockaddr_in6().map(|addr| IpAddr::V6(addr.ip())))
+}
@@ -0,0 +1,129 @@
+//! Reading the TCP ports the target is listening on, see [`listening_ports`].
+
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+};
+
+use mirrord_protocol::listening_ports::{GetListeningPortsResult, ListeningPort};
+
+/// `TCP_LISTEN` in the `st` column of `/proc/net/tcp`.
+const TCP_LISTEN: &str = "0A";
+
+/// Reads the TCP sockets listening in the network namespace of the process with the given pid.
+///
+/// Without a pid (targetless agent), reads the sockets of the agent's own network namespace.
+pub fn listening_ports(pid: Option<u64>) -> GetListeningPortsResult {
+    let net = match pid {
+        Some(pid) => PathBuf::from(format!("/proc/{pid}/net")),
+        None => PathBuf::from("/proc/self/net"),
+    };
+
+    let mut ports = parse_listening(&fs::read_to_string(net.join("tcp"))?);
+    // IPv6 might be disabled in the namespace.
+    if let Ok(tcp6) = fs::read_to_string(net.join("tcp6")) {
+        ports.extend(parse_listening(&tcp6));
+    }
+
+    ports.sort();
+    ports.dedup();
+
+    Ok(ports)
+}
+
+/// Parses the listening sockets from the contents of `/proc/net/tcp` or `/proc/net/tcp6`.
+///
+/// Malformed lines are skipped.
+fn parse_listening(table: &str) -> Vec<ListeningPort> {
+    table
+        .lines()
+        // Skip the header.
+        .skip(1)
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let local_address = columns.nth(1)?;
+            let state = columns.nth(1)?;
+            if state != TCP_LISTEN {
+                return None;
+            }
+
+            let (address, port) = local_address.split_once(':')?;
+
+            Some(ListeningPort {
+                address: parse_address(address)?,
+                port: u16::from_str_radix(port, 16).ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Parses an address from `/proc/net/tcp`, printed by the kernel as hex 32-bit words in the host
+/// byte order.
+fn parse_address(hex: &str) -> Option<IpAddr> {
+    let mut octets = Vec::with_capacity(16);
+    for word in 0..hex.len() / 8 {
+        let word = hex.get(word * 8..(word + 1) * 8)?;
+        octets.extend(u32::from_str_radix(word, 16).ok()?.to_ne_bytes());
+    }
+
+    match hex.len() {
+        8 => <[u8; 4]>::try_from(octets)
+            .ok()
+            .map(|octets| Ipv4Addr::from(octets).into()),
+        32 => <[u8; 16]>::try_from(octets)
+            .ok()
+            .map(|octets| Ipv6Addr::from(octets).into()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use mirrord_protocol::listening_ports::ListeningPort;
+
+    use super::parse_listening;
+
+    /// Verifies that only the listening sockets are returned, with their addresses decoded.
+    #[test]
+    fn parses_listening_sockets() {
+        let [localhost, any] = [Ipv4Addr::LOCALHOST, Ipv4Addr::UNSPECIFIED]
+            .map(|address| format!("{:08X}", u32::from_ne_bytes(address.octets())));
+        let tcp = format!(
+            "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n   \
+            0: {any}:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1001 1 0 100 0 0 10 0\n   \
+            1: {localhost}:1538 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1002 1 0 100 0 0 10 0\n   \
+            2: {localhost}:1F90 {localhost}:A2C4 01 00000000:00000000 00:00000000 00000000     0        0 1003 1 0 20 4 30 10 -1\n"
+        );
+
+        assert_eq!(
+            parse_listening(&tcp),
+            [
+                ListeningPort {
+                    address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                    port: 8080,
+                },
+                ListeningPort {
+                    address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+                    port: 5432,
+                },
+            ]
+        );
+
+        let tcp6 = format!(
+            "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n   \
+            0: {}:0050 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 2001 1 0 100 0 0 10 0\n",
+            "0".repeat(32)
+        );
+
+        assert_eq!(
+            parse_listening(&tcp6),
+            [ListeningPort {
+                address: IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+                port: 80,
+            }]
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Forwarding of the agent's own logs to the connected clients.
+//!
+//! [`LogForwardLayer`] is registered in the agent's [`tracing_subscriber`] registry and pushes
+//! every captured event into a [`broadcast`] channel. Each client that sends
+//! [`ClientMessage::ReadyForAgentLogs`](mirrord_protocol::ClientMessage::ReadyForAgentLogs)
+//! gets its own [`LogEventsReceiver`], which applies rate limiting before the events are sent
+//! as [`DaemonMessage::LogEvent`](mirrord_protocol::DaemonMessage::LogEvent)s.
+
+use std::{fmt, sync::LazyLock};
+
+use mirrord_protocol::{LogEvent, LogLevel};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time::{Duration, Instant},
+};
+use tracing::{
+    Event, Level, Subscriber,
+    field::{Field, Visit},
+};
+use tracing_subscriber::{
+    Layer,
+    filter::{Filtered, Targets},
+    layer::Context,
+};
+
+/// Capacity of the [`AGENT_LOG_EVENTS`] channel.
+///
+/// Slow clients lag behind, and the skipped events are reported as dropped.
+const LOG_EVENTS_CHANNEL_CAPACITY: usize = 256;
+
+/// How many [`LogEvent`]s a single client can receive within [`RATE_LIMIT_WINDOW`].
+const MAX_EVENTS_PER_WINDOW: u64 = 50;
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
+
+/// Agent-wide channel of captured log events, fed by [`LogForwardLayer`].
+static AGENT_LOG_EVENTS: LazyLock<broadcast::Sender<LogEvent>> =
+    LazyLock::new(|| broadcast::channel(LOG_EVENTS_CHANNEL_CAPACITY).0);
+
+/// [`Layer`] that captures the agent's log events and broadcasts them to the clients.
+///
+/// Does nothing when no client is subscribed.
+pub(crate) struct LogForwardLayer;
+
+impl LogForwardLayer {
+    /// Returns this layer with a per-layer filter, so that only `mirrord_agent` events at
+    /// [`Level::INFO`] and above are captured, regardless of `RUST_LOG`.
+    pub(crate) fn filtered<S>() -> Filtered<Self, Targets, S>
+    where
+        S: Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        Self.with_filter(Targets::new().with_target("mirrord_agent", Level::INFO))
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogForwardLayer {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        if AGENT_LOG_EVENTS.receiver_count() == 0 {
+            return;
+        }
+
+        let metadata = event.metadata();
+        let level = match *metadata.level() {
+            Level::ERROR => LogLevel::Error,
+            Level::WARN => LogLevel::Warn,
+            _ => LogLevel::Info,
+        };
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        // Fails only when there are no receivers left, which is fine.
+        let _ = AGENT_LOG_EVENTS.send(LogEvent {
+            level,
+            target: metadata.target().to_owned(),
+            message: visitor.message,
+        });
+    }
+}
+
+/// Formats an [`Event`] into a single line: the `message` field first, followed by the other
+/// fields as `name=value`.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        use std::fmt::Write;
+
+        // Writing into a `String` cannot fail.
+        let _ = if field.name() == "message" {
+            let fields = std::mem::take(&mut self.message);
+            write!(self.message, "{value:?}{fields}")
+        } else {
+            write!(self.message, " {}={value:?}", field.name())
+        };
+    }
+}
+
+/// A client's subscription to the agent's log events, rate limited to
+/// [`MAX_EVENTS_PER_WINDOW`] events per [`RATE_LIMIT_WINDOW`].
+///
+/// Events above the limit are dropped, and a single warning with the number of dropped events is
+/// sent once the next window starts.
+pub(crate) struct LogEventsReceiver {
+    rx: broadcast::Receiver<LogEvent>,
+    window_start: Instant,
+    sent_in_window: u64,
+    dropped: u64,
+    /// Event that arrived together with the dropped events warning, already counted in
+    /// `sent_in_window`.
+    pending: Option<LogEvent>,
+}
+
+impl LogEventsReceiver {
+    pub(crate) fn subscribe() -> Self {
+        Self::new(AGENT_LOG_EVENTS.subscribe())
+    }
+
+    fn new(rx: broadcast::Receiver<LogEvent>) -> Self {
+        Self {
+            rx,
+            window_start: Instant::now(),
+            sent_in_window: 0,
+            dropped: 0,
+            pending: None,
+        }
+    }
+
+    /// Returns the next [`LogEvent`] that should be sent to the client.
+    ///
+    /// Cancel safe. Returns [`None`] if the channel was closed.
+    pub(crate) async fn recv(&mut self) -> Option<LogEvent> {
+        if let Some(event) = self.pending.take() {
+            return Some(event);
+        }
+
+        loop {
+            let event = match self.rx.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    self.dropped += skipped;
+                    continue;
+                }
+                Err(RecvError::Closed) => return None,
+            };
+
+            let now = Instant::now();
+            if now.duration_since(self.window_start) >= RATE_LIMIT_WINDOW {
+                self.window_start = now;
+                self.sent_in_window = 0;
+            }
+
+            if self.sent_in_window >= MAX_EVENTS_PER_WINDOW {
+                self.dropped += 1;
+                continue;
+            }
+
+            self.sent_in_window += 1;
+
+            if self.dropped > 0 {
+                let dropped = std::mem::take(&mut self.dropped);
+                self.pending = Some(event);
+
+                break Some(LogEvent {
+                    level: LogLevel::Warn,
+                    target: module_path!().to_owned(),
+                    message: format!("dropped {dropped} agent log events due to rate limiting"),
+                });
+            }
+
+            break Some(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(message: &str) -> LogEvent {
+        LogEvent {
+            level: LogLevel::Info,
+            target: "mirrord_agent::test".to_owned(),
+            message: message.to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn rate_limit_reports_dropped() {
+        let (tx, rx) = broadcast::channel(LOG_EVENTS_CHANNEL_CAPACITY);
+        let mut receiver = LogEventsReceiver::new(rx);
+
+        for i in 0..MAX_EVENTS_PER_WINDOW + 5 {
+            tx.send(event(&i.to_string())).unwrap();
+        }
+
+        for i in 0..MAX_EVENTS_PER_WINDOW {
+            assert_eq!(receiver.recv().await.unwrap(), event(&i.to_string()));
+        }
+
+        // The remaining events are over the limit, so nothing should come out.
+        tokio::time::timeout(Duration::from_millis(10), receiver.recv())
+            .await
+            .unwrap_err();
+
+        receiver.window_start = Instant::now() - RATE_LIMIT_WINDOW;
+        tx.send(event("next")).unwrap();
+
+        let warning = receiver.recv().await.unwrap();
+        assert_eq!(warning.level, LogLevel::Warn);
+        assert!(warning.message.contains("dropped 5 "));
+        assert_eq!(receiver.recv().await.unwrap(), event("next"));
+    }
+}
@@ -10,6 +10,8 @@ use std::process::ExitCode;
 #[cfg(target_os = "linux")]
 use crate::{entrypoint::IPTABLES_DIRTY_EXIT_CODE, error::AgentError};
 
+#[cfg(target_os = "linux")]
+mod audit;
 #[cfg(target_os = "linux")]
 mod cli;
 #[cfg(target_os = "linux")]
@@ -31,6 +33,14 @@ mod http;
 #[cfg(target_os = "linux")]
 mod incoming;
 #[cfg(target_os = "linux")]
+mod interfaces;
+#[cfg(target_os = "linux")]
+mod listening_ports;
+#[cfg(target_os = "linux")]
+mod log_forward;
+#[cfg(target_os = "linux")]
+mod mandatory_filter;
+#[cfg(target_os = "linux")]
 mod metrics;
 #[cfg(target_os = "linux")]
 mod mirror;
@@ -39,10 +49,20 @@ mod namespace;
 #[cfg(target_os = "linux")]
 mod outgoing;
 #[cfg(target_os = "linux")]
+mod quic;
+#[cfg(target_os = "linux")]
+mod quota;
+#[cfg(target_os = "linux")]
+mod read_only;
+#[cfg(target_os = "linux")]
+mod resources;
+#[cfg(target_os = "linux")]
 mod reverse_dns;
 #[cfg(target_os = "linux")]
 mod runtime;
 #[cfg(target_os = "linux")]
+mod shutdown;
+#[cfg(target_os = "linux")]
 mod steal;
 #[cfg(target_os = "linux")]
 mod task;
@@ -0,0 +1,98 @@
+//! Mandatory HTTP filter of the agent.
+//!
+//! When set with
+//! [`envs::MANDATORY_HEADER_FILTER`](mirrord_agent_env::envs::MANDATORY_HEADER_FILTER) (usually by
+//! the operator, from a `MirrordPolicy`), every steal subscription must include the filter, so
+//! that the users can steal only the requests meant for them.
+//!
+//! The clients are informed about the filter with [`DaemonMessage::MandatoryHttpFilter`] during
+//! the protocol version negotiation, so that they can add it to their subscriptions, see
+//! [`StealType::with_http_filter`](mirrord_protocol::tcp::StealType::with_http_filter).
+
+use mirrord_protocol::{
+    BlockedAction, ClientMessage, DaemonMessage, MIRROR_POLICY_REASON_VERSION, ResponseError,
+    tcp::{DaemonTcp, HttpFilter},
+};
+
+use crate::util::protocol_version::ClientProtocolVersion;
+
+/// Returns the response to a steal subscription that does not include the `mandatory` filter.
+///
+/// Returns [`None`] if the request is allowed.
+pub(crate) fn reject(
+    message: &ClientMessage,
+    mandatory: &HttpFilter,
+    protocol_version: &ClientProtocolVersion,
+) -> Option<DaemonMessage> {
+    let ClientMessage::TcpSteal(message) = message else {
+        return None;
+    };
+    let steal_type = message.steal_type()?;
+
+    if steal_type.includes_http_filter(mandatory) {
+        return None;
+    }
+
+    let blocked_action = BlockedAction::Steal(steal_type.clone());
+    let error = if protocol_version.matches(&MIRROR_POLICY_REASON_VERSION) {
+        ResponseError::ForbiddenWithReason {
+            blocked_action,
+            policy_name: None,
+            reason: format!("steal subscriptions must include the HTTP filter `{mandatory}`"),
+        }
+    } else {
+        ResponseError::Forbidden {
+            blocked_action,
+            policy_name: None,
+        }
+    };
+
+    Some(DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Err(
+        error,
+    ))))
+}
+
+#[cfg(test)]
+mod test {
+    use mirrord_protocol::tcp::{Filter, LayerTcpSteal, StealType};
+
+    use super::*;
+
+    fn subscribe(steal_type: StealType) -> ClientMessage {
+        ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(steal_type))
+    }
+
+    #[test]
+    fn subscriptions_with_the_filter_are_allowed() {
+        let version = ClientProtocolVersion::default();
+        let mandatory = HttpFilter::Header(Filter::new("x-user: alice".into()).unwrap());
+
+        let subscription = StealType::All(80).with_http_filter(mandatory.clone());
+        assert_eq!(reject(&subscribe(subscription), &mandatory, &version), None);
+
+        let subscription =
+            StealType::FilteredHttpEx(80, HttpFilter::Path(Filter::new("/api".into()).unwrap()))
+                .with_http_filter(mandatory.clone());
+        assert_eq!(reject(&subscribe(subscription), &mandatory, &version), None);
+    }
+
+    #[test]
+    fn subscriptions_without_the_filter_are_forbidden() {
+        let version = "1.32.0".parse().unwrap();
+        let mandatory = HttpFilter::Header(Filter::new("x-user: alice".into()).unwrap());
+
+        let Some(DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Err(error)))) =
+            reject(&subscribe(StealType::All(80)), &mandatory, &version)
+        else {
+            panic!("steal subscription should be rejected");
+        };
+
+        assert!(matches!(
+            error,
+            ResponseError::ForbiddenWithReason {
+                blocked_action: BlockedAction::Steal(StealType::All(80)),
+                ..
+            }
+        ));
+    }
+}
@@ -26,9 +26,10 @@ pub(crate) static DNS_REQUEST_COUNT: AtomicUsize = AtomicUsize::new(0);
 /// Also gets decremented when `FileManager` is dropped.
 pub(crate) static OPEN_FD_COUNT: AtomicUsize = AtomicUsize::new(0);
 
-/// Follows the amount of subscribed ports in `update_packet_filter`. We don't really
-/// increment/decrement this one, and mostly `set` it to the latest amount of ports, zeroing it when
-/// the `TcpConnectionSniffer` gets dropped.
+/// Amount of ports subscribed in the mirror mode.
+///
+/// Incremented and decremented in `TcpMirrorApi` port (un)subscriptions, also gets decremented
+/// when `TcpMirrorApi` is dropped.
 pub(crate) static MIRROR_PORT_SUBSCRIPTION: AtomicUsize = AtomicUsize::new(0);
 
 pub(crate) static MIRROR_CONNECTION_SUBSCRIPTION: AtomicUsize = AtomicUsize::new(0);
@@ -58,6 +59,20 @@ pub(crate) static BYPASSED_REQUESTS: LazyLock<prometheus::GaugeVec> = LazyLock::
     .expect("BYPASSED_REQUESTS should be valid")
 });
 
+/// Counts stolen connections that overflowed the backlog of a client (the client had too many
+/// connections not yet accepted by the user application).
+///
+/// Labeled with the port and the action taken (`reject` or `passthrough`).
+pub(crate) static BACKLOG_OVERFLOW_CONNECTIONS: LazyLock<prometheus::IntCounterVec> =
+    LazyLock::new(|| {
+        prometheus::register_int_counter_vec!(
+            "mirrord_agent_backlog_overflow_connections",
+            "amount of stolen connections that overflowed the backlog of a mirrord-agent client",
+            &["port", "action"]
+        )
+        .expect("BACKLOG_OVERFLOW_CONNECTIONS should be valid")
+    });
+
 /// Convenience trait for static metrics variables.
 ///
 /// We store them as [`AtomicUsize`], which is the correct type (they're all counters).
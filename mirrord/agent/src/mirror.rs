@@ -1,7 +1,9 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     error::Report,
     ops::{Not, RangeInclusive},
+    sync::atomic::Ordering,
+    time::Duration,
 };
 
 use futures::StreamExt;
@@ -9,12 +11,16 @@ use mirrord_protocol::{
     ConnectionId, DaemonMessage, LogMessage, Port, RequestId,
     tcp::{
         ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, DaemonTcp,
-        HttpRequestMetadata, IncomingTrafficTransportType, InternalHttpBodyNew,
-        InternalHttpRequest, LayerTcp, MODE_AGNOSTIC_HTTP_REQUESTS, NewTcpConnectionV1,
+        HttpFilter as ProtocolHttpFilter, HttpRequestMetadata, IncomingTrafficTransportType,
+        InternalHttpBodyFrame, InternalHttpBodyNew, InternalHttpRequest, LayerTcp,
+        MODE_AGNOSTIC_HTTP_REQUESTS, MirrorDropped, MirrorLimits, NewTcpConnectionV1,
         NewTcpConnectionV2, TcpClose, TcpData,
     },
 };
-use tokio::task::JoinSet;
+use tokio::{
+    task::JoinSet,
+    time::{Instant, Interval, MissedTickBehavior},
+};
 use tokio_stream::StreamMap;
 use tracing::{Level, instrument};
 
@@ -26,12 +32,111 @@ use crate::{
         IncomingStream, IncomingStreamItem, MirrorHandle, MirroredHttp, MirroredTraffic,
         RedirectorTaskError,
     },
+    metrics::MIRROR_PORT_SUBSCRIPTION,
     util::protocol_version::ClientProtocolVersion,
 };
 
+/// Enforces the [`MirrorLimits`] of a single mirrored port.
+///
+/// The limits are enforced in one second windows. When the traffic in the current window exceeds
+/// the limit, the connection that exceeded it is closed, and new connections to the port are not
+/// mirrored until the next window.
+#[derive(Debug)]
+struct PortLimiter {
+    limits: MirrorLimits,
+    /// Start of the current window.
+    window_start: Instant,
+    /// Bytes sent to the client in the current window.
+    sent: u64,
+    /// Traffic dropped since the last [`MirrorDropped`] report.
+    dropped: MirrorDropped,
+}
+
+impl PortLimiter {
+    const WINDOW: Duration = Duration::from_secs(1);
+
+    fn new(port: Port, limits: MirrorLimits) -> Self {
+        Self {
+            limits,
+            window_start: Instant::now(),
+            sent: 0,
+            dropped: MirrorDropped {
+                port,
+                connections: 0,
+                bytes: 0,
+            },
+        }
+    }
+
+    /// Starts a new window if the current one is over.
+    fn refresh(&mut self) {
+        let now = Instant::now();
+        if now.duration_since(self.window_start) >= Self::WINDOW {
+            self.window_start = now;
+            self.sent = 0;
+        }
+    }
+
+    /// Returns whether a new connection can be mirrored in the current window.
+    ///
+    /// If not, the connection is counted as dropped.
+    fn admit_connection(&mut self) -> bool {
+        self.refresh();
+
+        let admitted = self.sent < self.limits.max_bytes_per_second;
+        if admitted.not() {
+            self.dropped.connections += 1;
+        }
+
+        admitted
+    }
+
+    /// Accounts for `bytes` of data sent to the client, returns whether they fit in the current
+    /// window.
+    ///
+    /// If not, the data and its connection are counted as dropped.
+    fn consume(&mut self, bytes: u64) -> bool {
+        self.refresh();
+
+        let sent = self.sent.saturating_add(bytes);
+        if sent > self.limits.max_bytes_per_second {
+            self.sent = self.limits.max_bytes_per_second;
+            self.dropped.connections += 1;
+            self.dropped.bytes += bytes;
+            false
+        } else {
+            self.sent = sent;
+            true
+        }
+    }
+
+    /// Returns the drops since the last report, if there were any.
+    fn take_report(&mut self) -> Option<MirrorDropped> {
+        if self.dropped.connections == 0 && self.dropped.bytes == 0 {
+            return None;
+        }
+
+        let port = self.dropped.port;
+        Some(std::mem::replace(
+            &mut self.dropped,
+            MirrorDropped {
+                port,
+                connections: 0,
+                bytes: 0,
+            },
+        ))
+    }
+}
+
 /// Agent client's API for using the TCP mirror feature.
 ///
 /// Wrapper over a [`MirrorHandle`].
+///
+/// Mirrored traffic does not come from packet capture. Connections to mirrored ports are
+/// redirected with iptables to the [`RedirectorTask`](crate::incoming::RedirectorTask), which
+/// passes them through to the original destination and copies the data read from the socket to
+/// the clients. The kernel reassembles the TCP streams, so the agent never handles single
+/// packets.
 pub struct TcpMirrorApi {
     mirror_handle: MirrorHandle,
     incoming_streams: StreamMap<ConnectionId, IncomingStream>,
@@ -40,6 +145,17 @@ pub struct TcpMirrorApi {
     queued_messages: VecDeque<DaemonTcp>,
     port_filters: HashMap<Port, HttpFilter>,
     ongoing_requests: JoinSet<MirroredHttp>,
+    /// Limits of subscriptions made with [`LayerTcp::PortSubscribeWithLimits`].
+    port_limits: HashMap<Port, PortLimiter>,
+    /// Ports of the mirrored connections in [`Self::incoming_streams`], used to find their
+    /// [`PortLimiter`]s.
+    connection_ports: HashMap<ConnectionId, Port>,
+    /// Drives periodic [`DaemonTcp::MirrorDropped`] reports.
+    drop_reports: Interval,
+    /// Ports paused with [`LayerTcp::PortPause`], new connections to them are not mirrored.
+    paused_ports: HashSet<Port>,
+    /// Ports mirrored for this client, counted in [`MIRROR_PORT_SUBSCRIPTION`].
+    subscribed_ports: HashSet<Port>,
 }
 
 impl TcpMirrorApi {
@@ -60,33 +176,117 @@ impl TcpMirrorApi {
             queued_messages: Default::default(),
             port_filters: Default::default(),
             ongoing_requests: Default::default(),
+            port_limits: Default::default(),
+            connection_ports: Default::default(),
+            drop_reports: {
+                let mut interval = tokio::time::interval(PortLimiter::WINDOW);
+                interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                interval
+            },
+            paused_ports: Default::default(),
+            subscribed_ports: Default::default(),
+        }
+    }
+
+    /// Starts mirroring the port, optionally only the HTTP requests matching the `filter`.
+    async fn subscribe(
+        &mut self,
+        port: Port,
+        filter: Option<&ProtocolHttpFilter>,
+    ) -> AgentResult<()> {
+        // Convert from protocol HttpFilter to agent HttpFilter
+        let agent_filter = filter
+            .map(HttpFilter::try_from)
+            .transpose()
+            .map_err(Box::new)
+            .map_err(AgentError::InvalidHttpFilter)?;
+
+        self.mirror_handle.mirror(port).await?;
+        if self.subscribed_ports.insert(port) {
+            MIRROR_PORT_SUBSCRIPTION.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(filter) = agent_filter {
+            self.port_filters.insert(port, filter);
+        }
+        self.queued_messages
+            .push_back(DaemonTcp::SubscribeResult(Ok(port)));
+
+        Ok(())
+    }
+
+    /// Removes the [`PortLimiter`] of the port, queueing its last report.
+    fn remove_limits(&mut self, port: Port) {
+        if let Some(report) = self
+            .port_limits
+            .remove(&port)
+            .and_then(|mut limiter| limiter.take_report())
+        {
+            self.queued_messages
+                .push_back(DaemonTcp::MirrorDropped(report));
         }
     }
 
+    /// Returns whether a new connection to the port can be mirrored, see
+    /// [`PortLimiter::admit_connection`].
+    fn admit_connection(&mut self, port: Port) -> bool {
+        self.port_limits
+            .get_mut(&port)
+            .is_none_or(PortLimiter::admit_connection)
+    }
+
+    /// Returns whether `bytes` of data from the connection can be mirrored, see
+    /// [`PortLimiter::consume`].
+    fn consume(&mut self, connection_id: ConnectionId, bytes: usize) -> bool {
+        self.connection_ports
+            .get(&connection_id)
+            .and_then(|port| self.port_limits.get_mut(port))
+            .is_none_or(|limiter| limiter.consume(bytes as u64))
+    }
+
+    /// Stops mirroring the connection, because it exceeded the [`MirrorLimits`] of its port.
+    fn drop_connection(&mut self, connection_id: ConnectionId) -> DaemonTcp {
+        self.incoming_streams.remove(&connection_id);
+        self.connection_ports.remove(&connection_id);
+        DaemonTcp::Close(TcpClose { connection_id })
+    }
+
     pub async fn handle_client_message(&mut self, message: LayerTcp) -> AgentResult<()> {
         match message {
             LayerTcp::ConnectionUnsubscribe(id) => {
                 self.incoming_streams.remove(&id);
+                self.connection_ports.remove(&id);
             }
             LayerTcp::PortSubscribe(port) => {
-                self.mirror_handle.mirror(port).await?;
-                self.queued_messages
-                    .push_back(DaemonTcp::SubscribeResult(Ok(port)));
+                self.remove_limits(port);
+                self.subscribe(port, None).await?;
             }
             LayerTcp::PortSubscribeFilteredHttp(port, filter) => {
-                // Convert from protocol HttpFilter to agent HttpFilter
-                let agent_filter = HttpFilter::try_from(&filter)
-                    .map_err(Box::new)
-                    .map_err(AgentError::InvalidHttpFilter)?;
-
-                self.mirror_handle.mirror(port).await?;
-                self.port_filters.insert(port, agent_filter);
-                self.queued_messages
-                    .push_back(DaemonTcp::SubscribeResult(Ok(port)));
+                self.remove_limits(port);
+                self.subscribe(port, Some(&filter)).await?;
+            }
+            LayerTcp::PortSubscribeWithLimits(subscribe) => {
+                self.remove_limits(subscribe.port);
+                self.subscribe(subscribe.port, subscribe.filter.as_ref())
+                    .await?;
+                self.port_limits.insert(
+                    subscribe.port,
+                    PortLimiter::new(subscribe.port, subscribe.limits),
+                );
             }
             LayerTcp::PortUnsubscribe(port) => {
                 self.port_filters.remove(&port);
+                self.paused_ports.remove(&port);
+                self.remove_limits(port);
                 self.mirror_handle.stop_mirror(port);
+                if self.subscribed_ports.remove(&port) {
+                    MIRROR_PORT_SUBSCRIPTION.fetch_sub(1, Ordering::Relaxed);
+                }
+            }
+            LayerTcp::PortPause(port) => {
+                self.paused_ports.insert(port);
+            }
+            LayerTcp::PortResume(port) => {
+                self.paused_ports.remove(&port);
             }
         }
 
@@ -177,12 +377,30 @@ impl TcpMirrorApi {
     }
 
     pub async fn recv(&mut self) -> AgentResult<DaemonMessage> {
+        loop {
+            if let Some(message) = self.next_message().await? {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Returns the next message for the client, or [`None`] if the traffic was dropped because of
+    /// the [`MirrorLimits`].
+    async fn next_message(&mut self) -> AgentResult<Option<DaemonMessage>> {
         if let Some(message) = self.queued_messages.pop_front() {
-            return Ok(DaemonMessage::Tcp(message));
+            return Ok(Some(DaemonMessage::Tcp(message)));
         }
 
         let message = tokio::select! {
             Some((id, item)) = self.incoming_streams.next() => match item {
+                IncomingStreamItem::Data(data) if self.consume(id, data.len()).not() => {
+                    self.drop_connection(id)
+                }
+                IncomingStreamItem::Frame(InternalHttpBodyFrame::Data(data))
+                    if self.consume(id, data.len()).not() =>
+                {
+                    self.drop_connection(id)
+                }
                 IncomingStreamItem::Data(data) => DaemonTcp::Data(TcpData {
                     connection_id: id,
                     bytes: data.into(),
@@ -208,18 +426,34 @@ impl TcpMirrorApi {
                     }))
                 }
                 IncomingStreamItem::Finished(Ok(())) => {
+                    self.connection_ports.remove(&id);
                     DaemonTcp::Close(TcpClose { connection_id: id })
                 }
                 IncomingStreamItem::Finished(Err(error)) => {
+                    self.connection_ports.remove(&id);
                     self.queued_messages.push_back(DaemonTcp::Close(TcpClose { connection_id: id }));
-                    return Ok(DaemonMessage::LogMessage(LogMessage::warn(format!(
+                    return Ok(Some(DaemonMessage::LogMessage(LogMessage::warn(format!(
                         "Mirrored connection {id} failed: {}",
                         Report::new(error)
-                    ))));
+                    )))));
                 }
             },
 
+            _ = self.drop_reports.tick(), if self.port_limits.is_empty().not() => {
+                let reports = self
+                    .port_limits
+                    .values_mut()
+                    .filter_map(PortLimiter::take_report)
+                    .map(DaemonTcp::MirrorDropped);
+                self.queued_messages.extend(reports);
+                return Ok(None);
+            }
+
             traffic = Self::next(&mut self.mirror_handle, &mut self.ongoing_requests, &self.protocol_version, &self.port_filters) => match traffic? {
+                traffic if self.paused_ports.contains(&traffic_port(&traffic)) => return Ok(None),
+
+                traffic if self.admit_connection(traffic_port(&traffic)).not() => return Ok(None),
+
                 MirroredTraffic::Tcp(tcp) if self.protocol_version.matches(&MODE_AGNOSTIC_HTTP_REQUESTS) => {
                     let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
                     let connection = NewTcpConnectionV1 {
@@ -241,25 +475,27 @@ impl TcpMirrorApi {
                             .unwrap_or(IncomingTrafficTransportType::Tcp),
                     };
                     self.incoming_streams.insert(id, tcp.stream);
+                    self.connection_ports.insert(id, tcp.info.original_destination.port());
                     DaemonTcp::NewConnectionV2(message)
                 }
 
                 MirroredTraffic::Tcp(tcp) => {
                     if tcp.info.tls_connector.is_some() {
-                        return Ok(DaemonMessage::LogMessage(LogMessage::error(format!(
+                        return Ok(Some(DaemonMessage::LogMessage(LogMessage::error(format!(
                             "A TLS connection was not mirrored due to mirrord-protocol version requirement: {}",
                             &*MODE_AGNOSTIC_HTTP_REQUESTS,
-                        ))));
+                        )))));
                     }
 
                     if self.port_filters.contains_key(&tcp.info.original_destination.port()) {
-                        return Ok(DaemonMessage::LogMessage(LogMessage::warn(
+                        return Ok(Some(DaemonMessage::LogMessage(LogMessage::warn(
                             "TCP traffic skipped due to HTTP filter on this port".to_string()
-                        )));
+                        ))));
                     }
 
                     let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
                     self.incoming_streams.insert(id, tcp.stream);
+                    self.connection_ports.insert(id, tcp.info.original_destination.port());
 
                     let message = NewTcpConnectionV1 {
                         connection_id: id,
@@ -275,6 +511,7 @@ impl TcpMirrorApi {
                     let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
 
                     self.incoming_streams.insert(id, http.stream);
+                    self.connection_ports.insert(id, http.info.original_destination.port());
 
                     let message = ChunkedRequestStartV2 {
                         connection_id: id,
@@ -307,16 +544,64 @@ impl TcpMirrorApi {
                 }
 
                 MirroredTraffic::Http(..) => {
-                    return Ok(DaemonMessage::LogMessage(LogMessage::error(format!(
+                    return Ok(Some(DaemonMessage::LogMessage(LogMessage::error(format!(
                         "An HTTP request was not mirrored due to mirrord-protocol version requirement: {}",
                         &*MODE_AGNOSTIC_HTTP_REQUESTS,
-                    ))));
+                    )))));
                 }
             },
 
             else => std::future::pending().await,
         };
 
-        Ok(DaemonMessage::Tcp(message))
+        Ok(Some(DaemonMessage::Tcp(message)))
+    }
+}
+
+/// Returns the destination port of the mirrored traffic.
+fn traffic_port(traffic: &MirroredTraffic) -> Port {
+    match traffic {
+        MirroredTraffic::Tcp(tcp) => tcp.info.original_destination.port(),
+        MirroredTraffic::Http(http) => http.info.original_destination.port(),
+    }
+}
+
+impl Drop for TcpMirrorApi {
+    fn drop(&mut self) {
+        MIRROR_PORT_SUBSCRIPTION.fetch_sub(self.subscribed_ports.len(), Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Not;
+
+    use mirrord_protocol::tcp::{MirrorDropped, MirrorLimits};
+
+    use super::PortLimiter;
+
+    #[test]
+    fn port_limiter_drops_over_limit() {
+        let mut limiter = PortLimiter::new(
+            80,
+            MirrorLimits {
+                max_bytes_per_second: 100,
+            },
+        );
+
+        assert!(limiter.admit_connection());
+        assert!(limiter.consume(60));
+        assert!(limiter.consume(60).not());
+        assert!(limiter.admit_connection().not());
+
+        assert_eq!(
+            limiter.take_report(),
+            Some(MirrorDropped {
+                port: 80,
+                connections: 2,
+                bytes: 60,
+            })
+        );
+        assert_eq!(limiter.take_report(), None);
     }
 }
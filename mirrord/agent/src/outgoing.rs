@@ -38,10 +38,12 @@ use crate::{
     },
 };
 
+mod icmp;
 mod socket_stream;
 mod throttle;
 mod udp;
 
+pub(crate) use icmp::IcmpEchoApi;
 pub(crate) use udp::UdpOutgoingApi;
 
 /// Possibly throttled message.
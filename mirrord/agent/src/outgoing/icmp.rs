@@ -0,0 +1,220 @@
+use std::{
+    io::{self, Read},
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use futures::{StreamExt, stream::FuturesOrdered};
+use mirrord_protocol::outgoing::icmp::{IcmpEchoRequest, IcmpEchoResponse, IcmpEchoResult};
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use tokio::{io::unix::AsyncFd, runtime::Handle, task::JoinHandle};
+use tracing::Level;
+
+use crate::{
+    error::{AgentError, AgentResult},
+    task::BgTaskRuntime,
+};
+
+/// ICMP message type of an echo request.
+const ECHO_REQUEST: u8 = 8;
+
+/// ICMP message type of an echo reply.
+const ECHO_REPLY: u8 = 0;
+
+/// Length of the ICMP echo header: type, code, checksum, identifier and sequence number.
+const ECHO_HEADER_LEN: usize = 8;
+
+/// Handles [`ClientMessage::IcmpEcho`](mirrord_protocol::ClientMessage::IcmpEcho) requests.
+///
+/// Every request gets its own raw ICMP socket (the agent has `CAP_NET_RAW`), which sees all the
+/// ICMP traffic of the network namespace. The echo reply is the one that comes from the
+/// destination, with the identifier and sequence number of the request.
+///
+/// Every client connection should use its own instance.
+pub(crate) struct IcmpEchoApi {
+    handle: Handle,
+    /// [`FuturesOrdered`] guarantee that we produce responses in the correct order.
+    results: FuturesOrdered<JoinHandle<IcmpEchoResult>>,
+}
+
+impl IcmpEchoApi {
+    /// Creates a new instance, which will send the echo requests from tasks spawned on
+    /// [`BgTaskRuntime::handle`].
+    ///
+    /// If this agent has a target, this runtime should live in the target's network namespace.
+    pub(crate) fn new(network_runtime: &BgTaskRuntime) -> Self {
+        Self {
+            handle: network_runtime.handle().clone(),
+            results: Default::default(),
+        }
+    }
+
+    /// Sends an echo request in the background.
+    ///
+    /// When available, the result will be returned from [`Self::recv`].
+    pub(crate) fn request_echo(&mut self, request: IcmpEchoRequest) {
+        let task = self.handle.spawn(echo(request));
+        self.results.push_back(task);
+    }
+
+    /// Returns the result of the oldest request made with [`Self::request_echo`].
+    pub(crate) async fn recv(&mut self) -> AgentResult<IcmpEchoResult> {
+        let Some(result) = self.results.next().await else {
+            return std::future::pending().await;
+        };
+
+        result.map_err(|error| AgentError::BackgroundTaskFailed {
+            task: "icmp_echo",
+            error: Arc::new(error),
+        })
+    }
+}
+
+#[tracing::instrument(level = Level::TRACE, ret)]
+async fn echo(request: IcmpEchoRequest) -> IcmpEchoResult {
+    let IcmpEchoRequest {
+        destination,
+        identifier,
+        sequence,
+        payload,
+        timeout_ms,
+    } = request;
+
+    let socket = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4))?;
+    socket.set_nonblocking(true)?;
+    let socket = AsyncFd::new(socket)?;
+
+    let packet = echo_request(identifier, sequence, &payload);
+    let address = SockAddr::from(SocketAddr::from((destination, 0)));
+    loop {
+        let mut guard = socket.writable().await?;
+        if let Ok(result) = guard.try_io(|socket| socket.get_ref().send_to(&packet, &address)) {
+            result?;
+            break;
+        }
+    }
+
+    let reply = async {
+        // Raw sockets receive the whole IP datagram.
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let mut guard = socket.readable().await?;
+            let Ok(read) = guard.try_io(|socket| {
+                let mut socket: &Socket = socket.get_ref();
+                socket.read(&mut buffer)
+            }) else {
+                continue;
+            };
+
+            let datagram = buffer.get(..read?).unwrap_or_default();
+            if let Some(reply) = echo_reply(datagram, destination, identifier, sequence) {
+                break io::Result::Ok(reply);
+            }
+        }
+    };
+
+    let reply = tokio::time::timeout(Duration::from_millis(timeout_ms.into()), reply)
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+
+    Ok(reply)
+}
+
+/// Builds an ICMP echo request message, with the checksum.
+fn echo_request(identifier: u16, sequence: u16, payload: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(ECHO_HEADER_LEN + payload.len());
+    packet.extend_from_slice(&[ECHO_REQUEST, 0, 0, 0]);
+    packet.extend_from_slice(&identifier.to_be_bytes());
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    packet.extend_from_slice(payload);
+
+    let checksum = checksum(&packet).to_be_bytes();
+    if let Some(field) = packet.get_mut(2..4) {
+        field.copy_from_slice(&checksum);
+    }
+
+    packet
+}
+
+/// Extracts the echo reply for our request from an IPv4 datagram read from the raw socket.
+///
+/// Returns [`None`] for any other ICMP message, e.g. our own request when pinging the loopback
+/// address, or the replies to other requests.
+fn echo_reply(
+    datagram: &[u8],
+    destination: Ipv4Addr,
+    identifier: u16,
+    sequence: u16,
+) -> Option<IcmpEchoResponse> {
+    let header_len = usize::from(datagram.first()? & 0x0f) * 4;
+    let source = Ipv4Addr::from(<[u8; 4]>::try_from(datagram.get(12..16)?).ok()?);
+    let packet = datagram.get(header_len..)?;
+
+    let is_our_reply = source == destination
+        && packet.first() == Some(&ECHO_REPLY)
+        && packet.get(4..6)? == identifier.to_be_bytes()
+        && packet.get(6..8)? == sequence.to_be_bytes();
+
+    is_our_reply.then(|| IcmpEchoResponse {
+        source,
+        packet: packet.to_vec().into(),
+    })
+}
+
+/// The Internet checksum (RFC 1071), used by ICMP.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum = data
+        .chunks(2)
+        .map(|chunk| match chunk {
+            [high, low] => u32::from(u16::from_be_bytes([*high, *low])),
+            [high] => u32::from(u16::from_be_bytes([*high, 0])),
+            _ => 0,
+        })
+        .sum::<u32>();
+
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn echo_request_checksum() {
+        let packet = echo_request(0x1234, 1, b"ping");
+        assert_eq!(packet.get(..2), Some([ECHO_REQUEST, 0].as_slice()));
+        assert_eq!(packet.get(4..8), Some([0x12, 0x34, 0, 1].as_slice()));
+        // Checksum of a message with a valid checksum is zero.
+        assert_eq!(checksum(&packet), 0);
+    }
+
+    #[test]
+    fn echo_reply_matching() {
+        let destination = Ipv4Addr::new(10, 0, 0, 7);
+
+        let mut icmp = echo_request(7, 3, b"data");
+        *icmp.first_mut().unwrap() = ECHO_REPLY;
+
+        let mut datagram = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, 1, 0, 0];
+        datagram.extend_from_slice(&destination.octets());
+        datagram.extend_from_slice(&[10, 0, 0, 1]);
+        datagram.extend_from_slice(&icmp);
+
+        let reply = echo_reply(&datagram, destination, 7, 3).unwrap();
+        assert_eq!(reply.source, destination);
+        assert_eq!(reply.packet.into_vec(), icmp);
+
+        assert!(echo_reply(&datagram, destination, 7, 4).is_none());
+        assert!(echo_reply(&datagram, Ipv4Addr::new(10, 0, 0, 8), 7, 3).is_none());
+
+        // Our own request, seen when pinging the loopback address.
+        let mut request = datagram.clone();
+        *request.get_mut(20).unwrap() = ECHO_REQUEST;
+        assert!(echo_reply(&request, destination, 7, 3).is_none());
+    }
+}
@@ -192,8 +192,9 @@ impl UdpOutgoingTask {
     /// 2. DNS special-case connection that comes on port `53`, where we have a hack that fakes a
     ///    connected udp socket. This case in particular requires that the user enable file ops with
     ///    read access to `/etc/resolv.conf`, otherwise they'll be getting a mismatched connection;
-    /// 3. User is trying to use `sendto` and `recvfrom`, we use the same hack as in DNS to fake a
-    ///    connection.
+    /// 3. User is trying to use `sendto` and `recvfrom` on an unconnected socket. The layer
+    ///    requests a separate connection for every destination, and the connected [`UdpSocket`]
+    ///    makes sure that each connection gets only the datagrams from its destination.
     #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::DEBUG))]
     async fn connect(&mut self, remote_address: SocketAddress) -> RemoteResult<DaemonConnect> {
         let peer_addr = remote_address.clone().try_into()?;
@@ -0,0 +1,194 @@
+//! Accepting client connections over QUIC, enabled with
+//! [`QUIC`](mirrord_agent_env::envs::QUIC).
+//!
+//! Each client opens a single bidirectional stream on its QUIC connection, which then carries the
+//! `mirrord-protocol` messages, same as a TCP connection would.
+//!
+//! The agent generates the TLS identity of its QUIC endpoint, and reports the certificate in its
+//! `agent ready` message. The clients trust only that certificate.
+
+use std::{future, net::SocketAddr, sync::Arc, time::Duration};
+
+use base64::{Engine, engine::general_purpose};
+use quinn::{
+    Endpoint, IdleTimeout, Incoming, RecvStream, SendStream, ServerConfig, TransportConfig, VarInt,
+};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use tokio::{io::Join, sync::mpsc};
+use tracing::Level;
+
+use crate::error::{AgentError, AgentResult};
+
+/// Name in the certificate of the QUIC endpoint, verified by the clients.
+const SERVER_NAME: &str = "mirrord-agent";
+
+/// How long we wait for any packet from the client before we consider the connection lost.
+///
+/// Generous, so that the connection survives short network outages on the client side (e.g. a
+/// VPN reconnect). The clients send keep-alives much more often.
+const MAX_IDLE_TIMEOUT_MS: u32 = 60_000;
+
+/// A bidirectional QUIC stream with a client.
+///
+/// The stream keeps the QUIC connection alive.
+pub(crate) type QuicStream = Join<RecvStream, SendStream>;
+
+/// Accepts client connections over QUIC.
+///
+/// The connections are accepted in a background task, so that [`QuicListener::accept`] is cancel
+/// safe, and a slow handshake does not block other clients.
+pub(crate) struct QuicListener {
+    streams: mpsc::Receiver<(QuicStream, SocketAddr)>,
+    local_addr: SocketAddr,
+    /// Certificate of the QUIC endpoint, generated in [`QuicListener::bind`].
+    certificate: CertificateDer<'static>,
+}
+
+impl QuicListener {
+    /// Binds a QUIC endpoint to the given UDP address, using a newly generated TLS identity.
+    ///
+    /// Clients are allowed to migrate their connections to other addresses.
+    #[tracing::instrument(level = Level::TRACE, err)]
+    pub(crate) fn bind(address: SocketAddr) -> AgentResult<Self> {
+        let identity = mirrord_tls_util::generate_cert(SERVER_NAME, None, false)
+            .map_err(|error| AgentError::QuicSetupError(error.into()))?;
+        let certificate = identity.cert.der().clone();
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(identity.key_pair.serialize_der()));
+
+        let mut transport = TransportConfig::default();
+        transport.max_idle_timeout(Some(IdleTimeout::from(VarInt::from_u32(
+            MAX_IDLE_TIMEOUT_MS,
+        ))));
+
+        let mut config = ServerConfig::with_single_cert(vec![certificate.clone()], key)
+            .map_err(|error| AgentError::QuicSetupError(error.into()))?;
+        config.transport_config(Arc::new(transport)).migration(true);
+
+        let endpoint = Endpoint::server(config, address)?;
+        let local_addr = endpoint.local_addr()?;
+
+        let (tx, streams) = mpsc::channel(16);
+        tokio::spawn(accept_connections(endpoint, tx));
+
+        Ok(Self {
+            streams,
+            local_addr,
+            certificate,
+        })
+    }
+
+    /// Returns the local UDP address of this listener.
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns the certificate of the QUIC endpoint as base64-encoded DER, reported to the clients
+    /// in the `agent ready` message.
+    pub(crate) fn certificate(&self) -> String {
+        general_purpose::STANDARD.encode(&self.certificate)
+    }
+
+    /// Returns the stream from the next client connection, along with the client's address.
+    ///
+    /// Never resolves if the QUIC endpoint is gone.
+    pub(crate) async fn accept(&mut self) -> (QuicStream, SocketAddr) {
+        match self.streams.recv().await {
+            Some(accepted) => accepted,
+            None => future::pending().await,
+        }
+    }
+}
+
+/// Accepts incoming QUIC connections and sends their streams to the [`QuicListener`].
+async fn accept_connections(endpoint: Endpoint, tx: mpsc::Sender<(QuicStream, SocketAddr)>) {
+    while let Some(incoming) = endpoint.accept().await {
+        let tx = tx.clone();
+
+        tokio::spawn(async move {
+            match accept_stream(incoming).await {
+                Ok(accepted) => {
+                    let _ = tx.send(accepted).await;
+                }
+                Err(error) => {
+                    tracing::warn!(%error, "Failed to accept a QUIC client connection");
+                }
+            }
+        });
+    }
+}
+
+/// Completes the handshake and waits for the client to open its stream.
+async fn accept_stream(
+    incoming: Incoming,
+) -> Result<(QuicStream, SocketAddr), quinn::ConnectionError> {
+    let connection = tokio::time::timeout(Duration::from_secs(30), incoming)
+        .await
+        .map_err(|_| quinn::ConnectionError::TimedOut)??;
+    let peer = connection.remote_address();
+    let (send, recv) = connection.accept_bi().await?;
+
+    Ok((tokio::io::join(recv, send), peer))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::Ipv4Addr, sync::Arc};
+
+    use base64::{Engine, engine::general_purpose};
+    use quinn::{ClientConfig, Endpoint};
+    use rustls::{RootCertStore, pki_types::CertificateDer};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::QuicListener;
+
+    /// Verifies that a client trusting the reported certificate can exchange data with the agent
+    /// over the accepted stream.
+    #[tokio::test]
+    async fn accepts_stream() {
+        let _ = rustls::crypto::CryptoProvider::install_default(
+            rustls::crypto::aws_lc_rs::default_provider(),
+        );
+
+        let mut listener = QuicListener::bind((Ipv4Addr::LOCALHOST, 0).into()).unwrap();
+        let agent_address = listener.local_addr();
+
+        let certificate = general_purpose::STANDARD
+            .decode(listener.certificate())
+            .unwrap();
+        let mut roots = RootCertStore::empty();
+        roots.add(CertificateDer::from(certificate)).unwrap();
+        let mut endpoint = Endpoint::client((Ipv4Addr::LOCALHOST, 0).into()).unwrap();
+        endpoint.set_default_client_config(
+            ClientConfig::with_root_certificates(Arc::new(roots)).unwrap(),
+        );
+
+        let client = async {
+            let connection = endpoint
+                .connect(agent_address, "mirrord-agent")
+                .unwrap()
+                .await
+                .unwrap();
+            let (mut send, mut recv) = connection.open_bi().await.unwrap();
+            send.write_all(b"hello").await.unwrap();
+
+            let mut response = [0; 5];
+            recv.read_exact(&mut response).await.unwrap();
+            assert_eq!(&response, b"agent");
+        };
+
+        let agent = async {
+            let (mut stream, _) = listener.accept().await;
+
+            let mut request = [0; 5];
+            stream.read_exact(&mut request).await.unwrap();
+            assert_eq!(&request, b"hello");
+            stream.write_all(b"agent").await.unwrap();
+            stream.flush().await.unwrap();
+
+            // Dropping the stream closes the connection.
+            stream
+        };
+
+        let (_, _stream) = tokio::join!(client, agent);
+    }
+}
@@ -0,0 +1,250 @@
+//! Quotas of the agent's clients.
+//!
+//! Set with [`envs::MAX_CLIENTS`](mirrord_agent_env::envs::MAX_CLIENTS),
+//! [`envs::MAX_STOLEN_PORTS`](mirrord_agent_env::envs::MAX_STOLEN_PORTS) and
+//! [`envs::MAX_SESSION_DURATION`](mirrord_agent_env::envs::MAX_SESSION_DURATION), usually by the
+//! operator, from the quota of the user that started the session.
+//!
+//! The clients are informed about the quotas with [`DaemonMessage::SessionQuotas`] during the
+//! protocol version negotiation. Requests that would exceed a quota are rejected with
+//! [`ResponseError::QuotaExceeded`], and sessions that run out of quota are closed with a
+//! [`DaemonMessage::Close`] that tells the user what to do next.
+
+use std::{
+    collections::HashSet,
+    ops::Not,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use mirrord_protocol::{
+    BlockedAction, ClientMessage, DaemonMessage, MIRROR_POLICY_REASON_VERSION, Port, ResponseError,
+    quota::{QuotaExceeded, SESSION_QUOTAS_VERSION, SessionQuotas},
+    tcp::{DaemonTcp, LayerTcpSteal},
+};
+use tokio::time::Instant;
+
+use crate::util::protocol_version::ClientProtocolVersion;
+
+/// Enforces [`SessionQuotas`] on a single client.
+#[derive(Debug)]
+pub(crate) struct ClientQuotas {
+    quotas: SessionQuotas,
+    /// Clients served by the agent, this one included.
+    clients: &'static AtomicUsize,
+    /// Ports that the client successfully subscribed to steal.
+    stolen_ports: HashSet<Port>,
+    /// When the session runs out of [`SessionQuotas::max_session_duration`].
+    deadline: Option<Instant>,
+}
+
+impl ClientQuotas {
+    /// Starts tracking a new client session, taking its slot in `clients` until dropped.
+    ///
+    /// Fails if the agent already serves [`SessionQuotas::max_clients`] clients. The check and
+    /// the increment are a single atomic update, so concurrent clients can't both take the last
+    /// slot.
+    pub(crate) fn new(
+        quotas: SessionQuotas,
+        clients: &'static AtomicUsize,
+    ) -> Result<Self, QuotaExceeded> {
+        clients
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                match quotas.max_clients {
+                    Some(limit) if count >= limit as usize => None,
+                    _ => Some(count + 1),
+                }
+            })
+            .map_err(|_| QuotaExceeded::AgentClients {
+                limit: quotas.max_clients.unwrap_or_default(),
+            })?;
+
+        Ok(Self {
+            quotas,
+            clients,
+            stolen_ports: Default::default(),
+            deadline: quotas
+                .max_session_duration
+                .map(|secs| Instant::now() + Duration::from_secs(secs)),
+        })
+    }
+
+    /// Returns the quotas if the client should be informed about them.
+    pub(crate) fn to_send(&self, protocol_version: &semver::Version) -> Option<SessionQuotas> {
+        (self.quotas.is_empty().not() && SESSION_QUOTAS_VERSION.matches(protocol_version))
+            .then_some(self.quotas)
+    }
+
+    /// Returns the response to a steal subscription that would exceed
+    /// [`SessionQuotas::max_stolen_ports`].
+    ///
+    /// Returns [`None`] if the request is allowed.
+    pub(crate) fn reject(
+        &self,
+        message: &ClientMessage,
+        protocol_version: &ClientProtocolVersion,
+    ) -> Option<DaemonMessage> {
+        let ClientMessage::TcpSteal(message) = message else {
+            return None;
+        };
+        let steal_type = message.steal_type()?;
+        let limit = self.quotas.max_stolen_ports?;
+
+        if self.stolen_ports.contains(&steal_type.get_port())
+            || self.stolen_ports.len() < limit as usize
+        {
+            return None;
+        }
+
+        let blocked_action = BlockedAction::Steal(steal_type.clone());
+        let quota = QuotaExceeded::StolenPorts { limit };
+        let error = if protocol_version.matches(&SESSION_QUOTAS_VERSION) {
+            ResponseError::QuotaExceeded {
+                blocked_action,
+                quota,
+            }
+        } else if protocol_version.matches(&MIRROR_POLICY_REASON_VERSION) {
+            ResponseError::ForbiddenWithReason {
+                blocked_action,
+                policy_name: None,
+                reason: quota.to_string(),
+            }
+        } else {
+            ResponseError::Forbidden {
+                blocked_action,
+                policy_name: None,
+            }
+        };
+
+        Some(DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Err(
+            error,
+        ))))
+    }
+
+    /// Updates the stolen ports with a message from the client.
+    pub(crate) fn client_message(&mut self, message: &ClientMessage) {
+        if let ClientMessage::TcpSteal(LayerTcpSteal::PortUnsubscribe(port)) = message {
+            self.stolen_ports.remove(port);
+        }
+    }
+
+    /// Updates the stolen ports with a message from the stealer.
+    ///
+    /// Ports are counted only once the subscription is confirmed, so that failed subscriptions
+    /// don't use up the quota.
+    pub(crate) fn stealer_message(&mut self, message: &DaemonMessage) {
+        if let DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Ok(port))) = message {
+            self.stolen_ports.insert(*port);
+        }
+    }
+
+    /// Resolves when the session runs out of [`SessionQuotas::max_session_duration`].
+    ///
+    /// Never resolves if there is no such quota.
+    ///
+    /// Cancel safe.
+    pub(crate) async fn expired(&self) -> QuotaExceeded {
+        match (self.deadline, self.quotas.max_session_duration) {
+            (Some(deadline), Some(limit)) => {
+                tokio::time::sleep_until(deadline).await;
+                QuotaExceeded::SessionDuration { limit }
+            }
+            _ => std::future::pending().await,
+        }
+    }
+}
+
+impl Drop for ClientQuotas {
+    fn drop(&mut self) {
+        self.clients.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mirrord_protocol::tcp::StealType;
+
+    use super::*;
+
+    /// Shared by the tests that don't check [`SessionQuotas::max_clients`].
+    static CLIENTS: AtomicUsize = AtomicUsize::new(0);
+
+    fn subscribe(port: Port) -> ClientMessage {
+        ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(StealType::All(port)))
+    }
+
+    fn subscribed(port: Port) -> DaemonMessage {
+        DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Ok(port)))
+    }
+
+    #[test]
+    fn clients_over_the_quota_are_rejected() {
+        static CLIENTS: AtomicUsize = AtomicUsize::new(0);
+        let quotas = SessionQuotas {
+            max_clients: Some(2),
+            ..Default::default()
+        };
+
+        let first = ClientQuotas::new(quotas, &CLIENTS).unwrap();
+        let _second = ClientQuotas::new(quotas, &CLIENTS).unwrap();
+        assert_eq!(
+            ClientQuotas::new(quotas, &CLIENTS).unwrap_err(),
+            QuotaExceeded::AgentClients { limit: 2 }
+        );
+        assert_eq!(CLIENTS.load(Ordering::Relaxed), 2);
+
+        // Disconnected clients free their slot.
+        drop(first);
+        assert_eq!(CLIENTS.load(Ordering::Relaxed), 1);
+        assert!(ClientQuotas::new(quotas, &CLIENTS).is_ok());
+    }
+
+    #[test]
+    fn stolen_ports_over_the_quota_are_rejected() {
+        let version = "1.38.0".parse().unwrap();
+        let quotas = SessionQuotas {
+            max_stolen_ports: Some(1),
+            ..Default::default()
+        };
+        let mut client = ClientQuotas::new(quotas, &CLIENTS).unwrap();
+
+        assert_eq!(client.reject(&subscribe(80), &version), None);
+        client.stealer_message(&subscribed(80));
+
+        // Subscribing again to the same port does not use up more of the quota.
+        assert_eq!(client.reject(&subscribe(80), &version), None);
+
+        let Some(DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Err(error)))) =
+            client.reject(&subscribe(81), &version)
+        else {
+            panic!("steal subscription should be rejected");
+        };
+        assert_eq!(
+            error,
+            ResponseError::QuotaExceeded {
+                blocked_action: BlockedAction::Steal(StealType::All(81)),
+                quota: QuotaExceeded::StolenPorts { limit: 1 },
+            }
+        );
+
+        client.client_message(&ClientMessage::TcpSteal(LayerTcpSteal::PortUnsubscribe(80)));
+        assert_eq!(client.reject(&subscribe(81), &version), None);
+    }
+
+    #[test]
+    fn stolen_ports_quota_for_old_clients() {
+        let version = "1.32.0".parse().unwrap();
+        let quotas = SessionQuotas {
+            max_stolen_ports: Some(0),
+            ..Default::default()
+        };
+        let client = ClientQuotas::new(quotas, &CLIENTS).unwrap();
+
+        let Some(DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Err(error)))) =
+            client.reject(&subscribe(80), &version)
+        else {
+            panic!("steal subscription should be rejected");
+        };
+        assert!(matches!(error, ResponseError::ForbiddenWithReason { .. }));
+    }
+}
@@ -0,0 +1,214 @@
+//! Read-only mode of the agent.
+//!
+//! When enabled with [`envs::READ_ONLY`](mirrord_agent_env::envs::READ_ONLY), the agent refuses
+//! all requests that could modify the target, regardless of the client's config:
+//!
+//! 1. Files opened for writing are opened locally ([`ResponseError::OpenLocal`]), other
+//!    modifications of the filesystem fail with `EROFS`;
+//! 2. Stealing incoming traffic is forbidden, mirroring is still allowed;
+//! 3. Outgoing connections and ICMP echo requests fail with `EACCES`.
+//!
+//! The clients are informed about the
+//! [`DisabledFeatures`](mirrord_protocol::DisabledFeatures) during the protocol version
+//! negotiation, so that they can avoid making these requests in the first place.
+
+use mirrord_protocol::{
+    BlockedAction, ClientMessage, DaemonMessage, ErrorKindInternal, FileRequest, FileResponse,
+    MIRROR_POLICY_REASON_VERSION, RemoteIOError, ResponseError,
+    file::{OpenFileRequest, OpenRelativeFileRequest},
+    outgoing::{
+        DaemonConnectV2, LayerConnect, LayerConnectV2,
+        tcp::{DaemonTcpOutgoing, LayerTcpOutgoing},
+        udp::{DaemonUdpOutgoing, LayerUdpOutgoing},
+    },
+    tcp::{DaemonTcp, LayerTcpSteal, PortSubscribeWithSources},
+};
+
+use crate::util::protocol_version::ClientProtocolVersion;
+
+/// Returns the response to a request that is not allowed in read-only mode.
+///
+/// Returns [`None`] if the request is allowed.
+pub(crate) fn reject(
+    message: &ClientMessage,
+    protocol_version: &ClientProtocolVersion,
+) -> Option<DaemonMessage> {
+    let response = match message {
+        ClientMessage::FileRequest(request) => DaemonMessage::File(reject_file(request)?),
+
+        ClientMessage::TcpSteal(
+            LayerTcpSteal::PortSubscribe(steal_type)
+            | LayerTcpSteal::PortSubscribeWithSources(PortSubscribeWithSources {
+                steal_type, ..
+            }),
+        ) => DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Err(forbidden(
+            BlockedAction::Steal(steal_type.clone()),
+            protocol_version,
+        )))),
+
+        ClientMessage::TcpOutgoing(LayerTcpOutgoing::Connect(LayerConnect { .. })) => {
+            DaemonMessage::TcpOutgoing(DaemonTcpOutgoing::Connect(Err(permission_denied())))
+        }
+        ClientMessage::TcpOutgoing(LayerTcpOutgoing::ConnectV2(LayerConnectV2 { uid, .. })) => {
+            DaemonMessage::TcpOutgoing(DaemonTcpOutgoing::ConnectV2(DaemonConnectV2 {
+                uid: *uid,
+                connect: Err(permission_denied()),
+            }))
+        }
+        ClientMessage::UdpOutgoing(LayerUdpOutgoing::Connect(LayerConnect { .. })) => {
+            DaemonMessage::UdpOutgoing(DaemonUdpOutgoing::Connect(Err(permission_denied())))
+        }
+        ClientMessage::UdpOutgoing(LayerUdpOutgoing::ConnectV2(LayerConnectV2 { uid, .. })) => {
+            DaemonMessage::UdpOutgoing(DaemonUdpOutgoing::ConnectV2(DaemonConnectV2 {
+                uid: *uid,
+                connect: Err(permission_denied()),
+            }))
+        }
+        ClientMessage::IcmpEcho(..) => DaemonMessage::IcmpEcho(Err(permission_denied())),
+
+        _ => return None,
+    };
+
+    Some(response)
+}
+
+fn reject_file(request: &FileRequest) -> Option<FileResponse> {
+    let response = match request {
+        FileRequest::Open(OpenFileRequest { open_options, .. })
+        | FileRequest::OpenRelative(OpenRelativeFileRequest { open_options, .. })
+            if open_options.is_read_only() =>
+        {
+            return None;
+        }
+        FileRequest::Open(..) | FileRequest::OpenRelative(..) => {
+            FileResponse::Open(Err(ResponseError::OpenLocal))
+        }
+        FileRequest::Write(..) => FileResponse::Write(Err(read_only_fs())),
+        FileRequest::WriteLimited(..) => FileResponse::WriteLimited(Err(read_only_fs())),
+        FileRequest::MakeDir(..) | FileRequest::MakeDirAt(..) => {
+            FileResponse::MakeDir(Err(read_only_fs()))
+        }
+        FileRequest::RemoveDir(..) => FileResponse::RemoveDir(Err(read_only_fs())),
+        FileRequest::Unlink(..) | FileRequest::UnlinkAt(..) => {
+            FileResponse::Unlink(Err(read_only_fs()))
+        }
+        FileRequest::Rename(..) => FileResponse::Rename(Err(read_only_fs())),
+        FileRequest::Ftruncate(..) => FileResponse::Ftruncate(Err(read_only_fs())),
+        FileRequest::Futimens(..) => FileResponse::Futimens(Err(read_only_fs())),
+        FileRequest::Fchown(..) => FileResponse::Fchown(Err(read_only_fs())),
+        FileRequest::Fchmod(..) => FileResponse::Fchmod(Err(read_only_fs())),
+        _ => return None,
+    };
+
+    Some(response)
+}
+
+fn read_only_fs() -> ResponseError {
+    ResponseError::RemoteIO(RemoteIOError {
+        raw_os_error: Some(libc::EROFS),
+        kind: ErrorKindInternal::ReadOnlyFilesystem,
+    })
+}
+
+fn permission_denied() -> ResponseError {
+    ResponseError::RemoteIO(RemoteIOError {
+        raw_os_error: Some(libc::EACCES),
+        kind: ErrorKindInternal::PermissionDenied,
+    })
+}
+
+fn forbidden(
+    blocked_action: BlockedAction,
+    protocol_version: &ClientProtocolVersion,
+) -> ResponseError {
+    if protocol_version.matches(&MIRROR_POLICY_REASON_VERSION) {
+        ResponseError::ForbiddenWithReason {
+            blocked_action,
+            policy_name: None,
+            reason: "the mirrord agent runs in read-only mode".into(),
+        }
+    } else {
+        ResponseError::Forbidden {
+            blocked_action,
+            policy_name: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mirrord_protocol::{
+        file::{OpenOptionsInternal, UnlinkRequest},
+        tcp::{LayerTcp, StealType},
+    };
+
+    use super::*;
+
+    fn open(open_options: OpenOptionsInternal) -> ClientMessage {
+        ClientMessage::FileRequest(FileRequest::Open(OpenFileRequest {
+            path: "/app/data".into(),
+            open_options,
+        }))
+    }
+
+    #[test]
+    fn reads_and_mirroring_are_allowed() {
+        let version = ClientProtocolVersion::default();
+
+        let read_only = OpenOptionsInternal {
+            read: true,
+            ..Default::default()
+        };
+        assert_eq!(reject(&open(read_only), &version), None);
+        assert_eq!(
+            reject(&ClientMessage::Tcp(LayerTcp::PortSubscribe(80)), &version),
+            None
+        );
+    }
+
+    #[test]
+    fn writes_are_rejected() {
+        let version = ClientProtocolVersion::default();
+
+        let write = OpenOptionsInternal {
+            write: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            reject(&open(write), &version),
+            Some(DaemonMessage::File(FileResponse::Open(Err(
+                ResponseError::OpenLocal
+            ))))
+        );
+
+        let unlink = ClientMessage::FileRequest(FileRequest::Unlink(UnlinkRequest {
+            pathname: "/app/data".into(),
+        }));
+        assert_eq!(
+            reject(&unlink, &version),
+            Some(DaemonMessage::File(FileResponse::Unlink(Err(
+                read_only_fs()
+            ))))
+        );
+    }
+
+    #[test]
+    fn steal_is_forbidden_with_reason() {
+        let version = "1.30.0".parse().unwrap();
+
+        let Some(DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Err(error)))) = reject(
+            &ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(StealType::All(80))),
+            &version,
+        ) else {
+            panic!("steal subscription should be rejected");
+        };
+
+        assert!(matches!(
+            error,
+            ResponseError::ForbiddenWithReason {
+                blocked_action: BlockedAction::Steal(StealType::All(80)),
+                ..
+            }
+        ));
+    }
+}
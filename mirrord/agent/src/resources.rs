@@ -0,0 +1,160 @@
+//! Reading the resource usage of the target container, see [`resource_usage`].
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use mirrord_protocol::resources::{GetResourceUsageResult, ProcessUsage, ResourceUsage};
+
+/// Limits above this value mean no limit in cgroup v1 (the kernel uses `PAGE_COUNTER_MAX` scaled
+/// to bytes, which is close to [`i64::MAX`]).
+const CGROUP_V1_NO_LIMIT: u64 = 1 << 62;
+
+/// Reads the resource usage and limits of the container with the given pid, from its cgroup.
+///
+/// Without a pid (targetless agent), reads the usage of the agent's own container.
+pub fn resource_usage(pid: Option<u64>) -> GetResourceUsageResult {
+    let root = match pid {
+        Some(pid) => PathBuf::from(format!("/proc/{pid}/root")),
+        None => PathBuf::from("/"),
+    };
+    let cgroup = root.join("sys/fs/cgroup");
+    fs::metadata(&cgroup)?;
+
+    let mut usage = if cgroup.join("cgroup.controllers").exists() {
+        cgroup_v2_usage(&cgroup)
+    } else {
+        cgroup_v1_usage(&cgroup)
+    };
+
+    usage.processes = process_usage(&usage_procs_dir(&cgroup));
+    usage.processes.sort_by_key(|process| process.pid);
+
+    Ok(usage)
+}
+
+fn cgroup_v2_usage(cgroup: &Path) -> ResourceUsage {
+    let cpu_time_usec = read(cgroup, "cpu.stat").and_then(|stat| {
+        stat.lines()
+            .find_map(|line| line.strip_prefix("usage_usec "))
+            .and_then(|value| value.trim().parse().ok())
+    });
+
+    ResourceUsage {
+        cpu_time_usec,
+        cpu_limit_millicores: read(cgroup, "cpu.max").and_then(|max| parse_cpu_max(&max)),
+        memory_bytes: read_u64(cgroup, "memory.current"),
+        memory_limit_bytes: read(cgroup, "memory.max").and_then(|max| max.trim().parse().ok()),
+        processes: Default::default(),
+    }
+}
+
+fn cgroup_v1_usage(cgroup: &Path) -> ResourceUsage {
+    let cpu_limit_millicores = read(cgroup, "cpu/cpu.cfs_quota_us")
+        .zip(read(cgroup, "cpu/cpu.cfs_period_us"))
+        .and_then(|(quota, period)| parse_cpu_max(&format!("{} {}", quota.trim(), period.trim())));
+
+    ResourceUsage {
+        cpu_time_usec: read_u64(cgroup, "cpuacct/cpuacct.usage").map(|nanos| nanos / 1000),
+        cpu_limit_millicores,
+        memory_bytes: read_u64(cgroup, "memory/memory.usage_in_bytes"),
+        memory_limit_bytes: read_u64(cgroup, "memory/memory.limit_in_bytes")
+            .filter(|limit| *limit < CGROUP_V1_NO_LIMIT),
+        processes: Default::default(),
+    }
+}
+
+/// Parses the CPU quota in the `cpu.max` format (`$MAX $PERIOD`) into millicores.
+///
+/// Returns [`None`] when there's no quota (`max` in cgroup v2, `-1` in cgroup v1).
+fn parse_cpu_max(max: &str) -> Option<u64> {
+    let mut parts = max.split_whitespace();
+    let quota = parts.next()?.parse::<u64>().ok()?;
+    let period = parts
+        .next()?
+        .parse::<u64>()
+        .ok()
+        .filter(|period| *period > 0)?;
+
+    Some(quota * 1000 / period)
+}
+
+/// Directory whose `cgroup.procs` lists the processes of the container.
+fn usage_procs_dir(cgroup: &Path) -> PathBuf {
+    let v1_dir = cgroup.join("cpuacct");
+    if v1_dir.join("cgroup.procs").exists() {
+        v1_dir
+    } else {
+        cgroup.to_path_buf()
+    }
+}
+
+/// Reads the usage of all processes in the cgroup and its descendants.
+///
+/// Processes that exit while we read are skipped.
+fn process_usage(cgroup: &Path) -> Vec<ProcessUsage> {
+    let ticks_per_second = procfs::ticks_per_second().max(1);
+    let page_size = procfs::page_size();
+
+    let mut dirs = vec![cgroup.to_path_buf()];
+    let mut processes = vec![];
+
+    while let Some(dir) = dirs.pop() {
+        if let Ok(entries) = fs::read_dir(&dir) {
+            dirs.extend(
+                entries
+                    .filter_map(Result::ok)
+                    .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+                    .map(|entry| entry.path()),
+            );
+        }
+
+        let pids = read(&dir, "cgroup.procs").unwrap_or_default();
+        processes.extend(
+            pids.lines()
+                .filter_map(|pid| pid.trim().parse::<i32>().ok())
+                .filter_map(|pid| {
+                    let stat = procfs::process::Process::new(pid).ok()?.stat().ok()?;
+
+                    Some(ProcessUsage {
+                        pid: pid as u32,
+                        name: stat.comm,
+                        cpu_time_usec: (stat.utime + stat.stime) * 1_000_000 / ticks_per_second,
+                        memory_bytes: stat.rss * page_size,
+                    })
+                }),
+        );
+    }
+
+    processes
+}
+
+fn read(dir: &Path, file: &str) -> Option<String> {
+    fs::read_to_string(dir.join(file))
+        .inspect_err(|error: &io::Error| {
+            tracing::trace!(%error, file, dir = %dir.display(), "Failed to read a cgroup file")
+        })
+        .ok()
+}
+
+fn read_u64(dir: &Path, file: &str) -> Option<u64> {
+    read(dir, file)?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::parse_cpu_max;
+
+    #[rstest]
+    #[case("200000 100000", Some(2000))]
+    #[case("50000 100000\n", Some(500))]
+    #[case("max 100000", None)]
+    #[case("-1 100000", None)]
+    #[case("50000 0", None)]
+    fn cpu_max(#[case] max: &str, #[case] expected: Option<u64>) {
+        assert_eq!(parse_cpu_max(max), expected);
+    }
+}
@@ -0,0 +1,102 @@
+//! Graceful shutdown of the agent, see [`Shutdown`].
+
+use std::{sync::Arc, time::Duration};
+
+use mirrord_protocol::shutdown::GoingDown;
+use tokio::{sync::watch, time::Instant};
+
+/// How long the clients have to finish their in-flight stolen traffic after the shutdown starts.
+///
+/// Well below the default `terminationGracePeriodSeconds` of the agent pod.
+pub const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Started graceful shutdown, see [`Shutdown::start`].
+#[derive(Clone, Debug)]
+pub struct ShutdownNotice {
+    pub reason: String,
+    /// When the agent stops waiting for the clients.
+    pub deadline: Instant,
+}
+
+impl ShutdownNotice {
+    /// Returns the [`GoingDown`] message for the clients.
+    pub fn going_down(&self) -> GoingDown {
+        GoingDown {
+            reason: self.reason.clone(),
+            deadline_ms: self
+                .deadline
+                .saturating_duration_since(Instant::now())
+                .as_millis() as u64,
+        }
+    }
+}
+
+/// Shared between the main agent loop and all client handlers.
+///
+/// When the agent is about to exit (e.g. SIGTERM, target container exited), the shutdown is
+/// [`Shutdown::start`]ed. The client handlers then notify their clients with
+/// [`GoingDown`], and wait (until the deadline) for them to finish their in-flight stolen
+/// traffic, while the main agent loop waits for the client handlers.
+#[derive(Clone, Debug)]
+pub struct Shutdown(Arc<watch::Sender<Option<ShutdownNotice>>>);
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self(Arc::new(watch::Sender::new(None)))
+    }
+}
+
+impl Shutdown {
+    /// Starts the graceful shutdown, with the [`SHUTDOWN_GRACE_PERIOD`].
+    ///
+    /// Does nothing if it was already started.
+    pub fn start(&self, reason: impl Into<String>) {
+        let reason = reason.into();
+        self.0.send_if_modified(|notice| {
+            if notice.is_some() {
+                return false;
+            }
+
+            tracing::info!(reason, "Starting graceful shutdown");
+            *notice = Some(ShutdownNotice {
+                reason,
+                deadline: Instant::now() + SHUTDOWN_GRACE_PERIOD,
+            });
+            true
+        });
+    }
+
+    /// Resolves when the shutdown is started.
+    ///
+    /// Cancel safe.
+    pub async fn started(&self) -> ShutdownNotice {
+        let mut rx = self.0.subscribe();
+        match rx.wait_for(Option::is_some).await {
+            Ok(notice) => notice.clone().expect("checked in wait_for"),
+            // We hold the sender.
+            Err(..) => std::future::pending().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn first_reason_wins() {
+        let shutdown = Shutdown::default();
+        let started = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.started().await }
+        });
+
+        shutdown.start("target container exited");
+        shutdown.start("agent pod is being deleted");
+
+        let notice = started.await.unwrap();
+        assert_eq!(notice.reason, "target container exited");
+        assert!(notice.going_down().deadline_ms <= SHUTDOWN_GRACE_PERIOD.as_millis() as u64);
+        assert_eq!(shutdown.started().await.reason, notice.reason);
+    }
+}
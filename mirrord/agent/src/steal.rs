@@ -1,4 +1,8 @@
-use mirrord_protocol::{LogMessage, Port};
+use connection_filter::ConnectionFilter;
+use mirrord_protocol::{
+    LogMessage, Port,
+    tcp::{BacklogOverflow, SourceFilter},
+};
 use tokio::sync::mpsc::Sender;
 
 use crate::{
@@ -8,12 +12,17 @@ use crate::{
 };
 
 mod api;
+mod connection_filter;
+mod passthrough_cache;
+mod probes;
 mod subscriptions;
 mod task;
 #[cfg(test)]
 mod test;
 
 pub use api::TcpStealerApi;
+pub use passthrough_cache::PassthroughCache;
+pub use probes::ProbeResponder;
 pub use task::TcpStealerTask;
 
 /// Commands from the agent that are passed down to the stealer worker, through [`TcpStealerApi`].
@@ -31,10 +40,42 @@ enum Command {
     /// The agent starts stealing traffic from this [`Port`].
     PortSubscribe(Port, Option<HttpFilter>),
 
+    /// The layer wants to subscribe to whole connections to this [`Port`] that match the given
+    /// [`ConnectionFilter`].
+    ///
+    /// The agent starts stealing traffic from this [`Port`].
+    PortSubscribeConnection(Port, ConnectionFilter),
+
     /// The layer wants to unsubscribe from this [`Port`].
     ///
     /// The agent stops stealing traffic from this [`Port`].
     PortUnsubscribe(Port),
+
+    /// The layer's backlog of stolen connections on this [`Port`] became full (`Some`), or is no
+    /// longer full (`None`).
+    ///
+    /// While the backlog is full, new connections that would be stolen by the layer are handled
+    /// according to the [`BacklogOverflow`] policy.
+    PortBacklogFull(Port, Option<BacklogOverflow>),
+
+    /// The layer paused (`true`) or resumed (`false`) this [`Port`].
+    ///
+    /// While the port is paused, traffic that would be stolen by the layer is passed through to
+    /// its original destination.
+    PortPause(Port, bool),
+
+    /// The layer restricted its subscription to this [`Port`] to the given source addresses
+    /// (`Some`), or lifted the restriction (`None`).
+    ///
+    /// Sent right before the subscription. Traffic from other addresses, that would be stolen by
+    /// the layer, is passed through to its original destination.
+    PortSources(Port, Option<SourceFilter>),
+
+    /// The agent is shutting down, and the layer should only finish its in-flight traffic.
+    ///
+    /// From now on, traffic that would be stolen by the layer is passed through to its original
+    /// destination.
+    Drain,
 }
 
 /// Sent from [`TcpStealerApi`]s to the [`TcpStealerTask`].`
@@ -12,31 +12,34 @@ use http_body_util::{BodyExt, combinators::BoxBody};
 use hyper::{Response, body::Frame};
 use mirrord_protocol::{
     ConnectionId, DaemonMessage, LogMessage, Payload, RequestId,
+    experimental::ExperimentalFeature,
     tcp::{
         ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, ChunkedResponse, DaemonTcp,
         HTTP_CHUNKED_REQUEST_V2_VERSION, HTTP_CHUNKED_REQUEST_VERSION, HTTP_FRAMED_VERSION,
         HttpRequest, HttpRequestMetadata, HttpResponse, IncomingTrafficTransportType,
         InternalHttpBody, InternalHttpBodyFrame, InternalHttpBodyNew, InternalHttpRequest,
         LayerTcpSteal, MODE_AGNOSTIC_HTTP_REQUESTS, NewTcpConnectionV1, NewTcpConnectionV2,
-        StealType, TcpClose, TcpData,
+        PortSubscribeWithSources, SourceFilter, StealType, TCP_SHUTDOWN_WRITE_VERSION,
+        TcpBacklogFull, TcpClose, TcpData, TcpShutdownWrite, declare_trailers, is_event_stream,
     },
 };
 use tokio::sync::mpsc::{self, Receiver, Sender, error::SendError};
 use tokio_stream::StreamMap;
 use tracing::Level;
 
-use super::{Command, StealerCommand, StealerMessage};
+use super::{Command, StealerCommand, StealerMessage, connection_filter::ConnectionFilter};
 use crate::{
     AgentError,
+    audit::{AuditEvent, AuditLog},
     error::AgentResult,
-    http::{MIRRORD_AGENT_HTTP_HEADER_NAME, filter::HttpFilter},
+    http::{MIRRORD_AGENT_HTTP_HEADER_NAME, disable_event_stream_buffering, filter::HttpFilter},
     incoming::{
         ConnError, IncomingStream, IncomingStreamItem, RedirectorTaskConfig, ResponseBodyProvider,
         ResponseProvider, StolenHttp, StolenTcp,
     },
     steal::api::wait_body::WaitForFullBody,
     task::status::BgTaskStatus,
-    util::{ClientId, protocol_version::ClientProtocolVersion},
+    util::{ClientId, protocol_version::ClientProtocolVersion, ulid::new_ulid},
 };
 
 mod wait_body;
@@ -77,6 +80,8 @@ pub struct TcpStealerApi {
     ///
     /// We use this queue to store them and return from [`Self::recv`] one by one.
     queued_messages: VecDeque<DaemonMessage>,
+    /// Records the stolen requests, with their correlation ids.
+    audit: AuditLog,
 }
 
 impl TcpStealerApi {
@@ -102,6 +107,7 @@ impl TcpStealerApi {
         protocol_version: ClientProtocolVersion,
         command_tx: Sender<StealerCommand>,
         task_status: BgTaskStatus,
+        audit: AuditLog,
     ) -> AgentResult<Self> {
         let (message_tx, message_rx) = mpsc::channel(Self::CHANNEL_SIZE);
 
@@ -126,6 +132,7 @@ impl TcpStealerApi {
             requests_in_progress: Default::default(),
             connection_ids_iter: 0..=ConnectionId::MAX,
             queued_messages: Default::default(),
+            audit,
         })
     }
 
@@ -143,6 +150,50 @@ impl TcpStealerApi {
         }
     }
 
+    /// Stops stealing new traffic for this client, see [`Command::Drain`].
+    ///
+    /// Connections and requests that are already stolen are still handled.
+    pub(crate) async fn drain(&mut self) -> AgentResult<()> {
+        self.send_command(Command::Drain).await
+    }
+
+    /// Whether all stolen connections and requests of this client are finished.
+    pub(crate) fn is_drained(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    /// Drops the stolen HTTP request, see
+    /// [`CancelRequest::HttpRequest`](mirrord_protocol::cancel::CancelRequest::HttpRequest).
+    ///
+    /// We stop sending the request body to the client. If the response was not sent in whole,
+    /// the connection with the HTTP client is closed.
+    pub(crate) fn cancel_http_request(
+        &mut self,
+        connection_id: ConnectionId,
+        request_id: RequestId,
+    ) {
+        if request_id != Self::REQUEST_ID {
+            return;
+        }
+
+        let is_request = self
+            .connections
+            .get(&connection_id)
+            .is_some_and(|connection| {
+                matches!(
+                    connection,
+                    ClientConnectionState::HttpRequestSent { .. }
+                        | ClientConnectionState::HttpResponseReceived { .. }
+                        | ClientConnectionState::Closed
+                )
+            });
+        if is_request {
+            tracing::debug!(connection_id, "Stolen HTTP request cancelled by the client");
+            self.connections.remove(&connection_id);
+            self.incoming_streams.remove(&connection_id);
+        }
+    }
+
     /// Returns a [`DaemonMessage`] to be sent to the client.
     #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE))]
     pub(crate) async fn recv(&mut self) -> AgentResult<DaemonMessage> {
@@ -183,6 +234,10 @@ impl TcpStealerApi {
     }
 
     /// Handles a stolen HTTP request received from the stealer task.
+    ///
+    /// Each request gets a new correlation id (a ULID), that is logged here, recorded in the
+    /// audit log and sent to clients that support [`HttpRequestMetadata::V2`], so that the
+    /// request can be found in the logs of the agent, the intproxy and the local application.
     #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE))]
     fn handle_request(&mut self, request: StolenHttp) -> AgentResult<()> {
         let need_to_wait = self
@@ -207,10 +262,45 @@ impl TcpStealerApi {
             redirector_config,
         } = request;
 
+        let correlation_id = new_ulid();
+        tracing::debug!(
+            connection_id,
+            correlation_id,
+            method = %request_head.parts.method,
+            uri = %request_head.parts.uri,
+            destination = %info.original_destination,
+            "Stealing an HTTP request",
+        );
+        self.audit.record(
+            self.client_id,
+            AuditEvent::RequestStolen {
+                correlation_id: correlation_id.clone(),
+                port: info.original_destination.port(),
+                method: request_head.parts.method.to_string(),
+                path: request_head.parts.uri.path().to_string(),
+            },
+        );
+
         if self
             .protocol_version
             .matches(&HTTP_CHUNKED_REQUEST_V2_VERSION)
         {
+            let metadata = if self
+                .protocol_version
+                .matches(ExperimentalFeature::HttpCorrelationId.version_req())
+            {
+                HttpRequestMetadata::V2 {
+                    source: info.peer_addr,
+                    destination: info.original_destination,
+                    correlation_id,
+                }
+            } else {
+                HttpRequestMetadata::V1 {
+                    source: info.peer_addr,
+                    destination: info.original_destination,
+                }
+            };
+
             let message = DaemonMessage::TcpSteal(DaemonTcp::HttpRequestChunked(
                 ChunkedRequest::StartV2(ChunkedRequestStartV2 {
                     connection_id,
@@ -225,10 +315,7 @@ impl TcpStealerApi {
                             is_last: request_head.body_finished,
                         },
                     },
-                    metadata: HttpRequestMetadata::V1 {
-                        source: info.peer_addr,
-                        destination: info.original_destination,
-                    },
+                    metadata,
                     transport: info
                         .tls_connector
                         .as_ref()
@@ -391,11 +478,16 @@ impl TcpStealerApi {
             }
 
             IncomingStreamItem::NoMoreData => {
-                self.queued_messages
-                    .push_back(DaemonMessage::TcpSteal(DaemonTcp::Data(TcpData {
+                let message = if self.protocol_version.matches(&TCP_SHUTDOWN_WRITE_VERSION) {
+                    DaemonTcp::ShutdownWrite(TcpShutdownWrite { connection_id })
+                } else {
+                    DaemonTcp::Data(TcpData {
                         connection_id,
                         bytes: Default::default(),
-                    })))
+                    })
+                };
+                self.queued_messages
+                    .push_back(DaemonMessage::TcpSteal(message));
             }
 
             IncomingStreamItem::Finished(result) => {
@@ -482,6 +574,58 @@ impl TcpStealerApi {
         Ok(())
     }
 
+    /// Handles [`LayerTcpSteal::PortSubscribe`] and
+    /// [`LayerTcpSteal::PortSubscribeWithSources`].
+    async fn subscribe(
+        &mut self,
+        steal_type: StealType,
+        sources: Option<SourceFilter>,
+    ) -> AgentResult<()> {
+        let port = steal_type.get_port();
+        let command = match steal_type {
+            StealType::All(port) => Command::PortSubscribe(port, None),
+            StealType::FilteredHttp(port, filter) => Command::PortSubscribe(
+                port,
+                Some(
+                    HttpFilter::try_from(&mirrord_protocol::tcp::HttpFilter::Header(filter))
+                        .map_err(Box::new)
+                        .map_err(AgentError::InvalidHttpFilter)?,
+                ),
+            ),
+            StealType::FilteredHttpEx(port, filter) => Command::PortSubscribe(
+                port,
+                Some(
+                    HttpFilter::try_from(&filter)
+                        .map_err(Box::new)
+                        .map_err(AgentError::InvalidHttpFilter)?,
+                ),
+            ),
+            StealType::FilteredSni(port, filter) => Command::PortSubscribeConnection(
+                port,
+                ConnectionFilter::sni(&filter)
+                    .map_err(Box::new)
+                    .map_err(AgentError::InvalidConnectionFilter)?,
+            ),
+            StealType::FilteredPostgres(port, filter) => Command::PortSubscribeConnection(
+                port,
+                ConnectionFilter::postgres(&filter)
+                    .map_err(Box::new)
+                    .map_err(AgentError::InvalidConnectionFilter)?,
+            ),
+            StealType::FilteredRedis(port, filter) => Command::PortSubscribeConnection(
+                port,
+                ConnectionFilter::redis(&filter)
+                    .map_err(Box::new)
+                    .map_err(AgentError::InvalidConnectionFilter)?,
+            ),
+        };
+
+        // Sent first, so that no traffic is stolen from the other addresses.
+        self.send_command(Command::PortSources(port, sources))
+            .await?;
+        self.send_command(command).await
+    }
+
     /// Handles a [`LayerTcpSteal`] message from the client.
     #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE))]
     pub(crate) async fn handle_client_message(
@@ -490,36 +634,37 @@ impl TcpStealerApi {
     ) -> AgentResult<()> {
         match message {
             LayerTcpSteal::PortSubscribe(steal_type) => {
-                let (port, filter) = match steal_type {
-                    StealType::All(port) => (port, None),
-                    StealType::FilteredHttp(port, filter) => (
-                        port,
-                        Some(
-                            HttpFilter::try_from(&mirrord_protocol::tcp::HttpFilter::Header(
-                                filter,
-                            ))
-                            .map_err(Box::new)
-                            .map_err(AgentError::InvalidHttpFilter)?,
-                        ),
-                    ),
-                    StealType::FilteredHttpEx(port, filter) => (
-                        port,
-                        Some(
-                            HttpFilter::try_from(&filter)
-                                .map_err(Box::new)
-                                .map_err(AgentError::InvalidHttpFilter)?,
-                        ),
-                    ),
-                };
+                self.subscribe(steal_type, None).await?;
+            }
 
-                self.send_command(Command::PortSubscribe(port, filter))
-                    .await?;
+            LayerTcpSteal::PortSubscribeWithSources(PortSubscribeWithSources {
+                steal_type,
+                sources,
+            }) => {
+                self.subscribe(steal_type, Some(sources)).await?;
             }
 
             LayerTcpSteal::PortUnsubscribe(port) => {
                 self.send_command(Command::PortUnsubscribe(port)).await?;
             }
 
+            LayerTcpSteal::BacklogFull(TcpBacklogFull {
+                port,
+                full,
+                overflow,
+            }) => {
+                self.send_command(Command::PortBacklogFull(port, full.then_some(overflow)))
+                    .await?;
+            }
+
+            LayerTcpSteal::PortPause(port) => {
+                self.send_command(Command::PortPause(port, true)).await?;
+            }
+
+            LayerTcpSteal::PortResume(port) => {
+                self.send_command(Command::PortPause(port, false)).await?;
+            }
+
             LayerTcpSteal::ConnectionUnsubscribe(connection_id) => {
                 self.connections.remove(&connection_id);
                 self.incoming_streams.remove(&connection_id);
@@ -532,6 +677,12 @@ impl TcpStealerApi {
                 connection.send_data(data.bytes.0).await;
             }
 
+            LayerTcpSteal::ShutdownWrite(TcpShutdownWrite { connection_id }) => {
+                if let Some(connection) = self.connections.get_mut(&connection_id) {
+                    connection.shutdown_write();
+                }
+            }
+
             LayerTcpSteal::HttpResponse(response) => {
                 if response.request_id != Self::REQUEST_ID {
                     return Ok(());
@@ -704,13 +855,21 @@ impl ClientConnectionState {
         };
 
         if data.is_empty() {
-            *self = Self::Closed;
+            self.shutdown_write();
             return;
         }
 
         let _ = sender.send(data).await;
     }
 
+    /// Drops the data channel of a TCP or upgraded HTTP connection, which shuts down the writing
+    /// half of the stolen connection once all data is flushed.
+    fn shutdown_write(&mut self) {
+        if matches!(self, Self::Tcp { .. } | Self::HttpUpgraded { .. }) {
+            *self = Self::Closed;
+        }
+    }
+
     async fn send_response_frame(
         &mut self,
         frame: Option<InternalHttpBodyFrame>,
@@ -760,7 +919,7 @@ impl ClientConnectionState {
             }
         };
 
-        let parts = {
+        let mut parts = {
             let mut hyper_response = Response::new(());
 
             *hyper_response.status_mut() = response.internal_response.status;
@@ -772,9 +931,25 @@ impl ClientConnectionState {
             hyper_response.into_parts().0
         };
 
+        if body_finished.not() && is_event_stream(&parts.headers) {
+            tracing::debug!("Streaming server-sent events to the HTTP client");
+            disable_event_stream_buffering(&mut parts.headers);
+        }
+
         if body_finished {
-            let body = InternalHttpBody(response.internal_response.body.into_iter().collect())
-                .map_err(|_| unreachable!());
+            let frames = response
+                .internal_response
+                .body
+                .into_iter()
+                .collect::<VecDeque<_>>();
+            // We have the whole body, so we can declare the trailers for HTTP/1 clients.
+            declare_trailers(
+                &mut parts.headers,
+                frames
+                    .iter()
+                    .filter_map(InternalHttpBodyFrame::trailers_ref),
+            );
+            let body = InternalHttpBody(frames).map_err(|_| unreachable!());
             let body = BoxBody::new(body);
             let response = Response::from_parts(parts, body);
             match response_provider.send_finished(response) {
@@ -809,12 +984,20 @@ impl ClientConnectionState {
 
 /// Converts a vec of [`InternalHttpBodyFrame`]s to [`Payload`] (body format used in
 /// [`DaemonTcp::HttpRequest`]).
+///
+/// The legacy format has no room for trailers, so they are dropped.
 fn frames_to_legacy(frames: Vec<InternalHttpBodyFrame>) -> Payload {
     frames
         .into_iter()
         .filter_map(|frame| match frame {
             InternalHttpBodyFrame::Data(data) => Some(data.0),
-            InternalHttpBodyFrame::Trailers(..) => None,
+            InternalHttpBodyFrame::Trailers(trailers) => {
+                tracing::warn!(
+                    trailers = trailers.len(),
+                    "Dropping HTTP request trailers, the client does not support them",
+                );
+                None
+            }
         })
         .fold(Vec::new(), |mut add, data| {
             add.extend_from_slice(&data);
@@ -0,0 +1,147 @@
+use fancy_regex::Regex;
+use mirrord_protocol::tcp::{Filter, PostgresFilter, RedisFilter};
+
+use crate::incoming::{ConnectionInfo, ConnectionPeeks, redis::RedisCommandFilter};
+
+/// Filter for stealing whole connections based on their initial message, without terminating
+/// the application protocol.
+///
+/// Matched against the [`ConnectionInfo`] of a redirected connection.
+#[derive(Debug, Clone)]
+pub enum ConnectionFilter {
+    /// Matches TLS connections by the server name (SNI) from their ClientHello.
+    Sni(Regex),
+    /// Matches PostgreSQL connections by the parameters from their startup message.
+    ///
+    /// All of the given regexes must match.
+    Postgres {
+        database: Option<Regex>,
+        user: Option<Regex>,
+    },
+    /// Matches all connections. The connections are not stolen whole, only the Redis commands
+    /// matching the [`RedisCommandFilter`] are.
+    Redis(RedisCommandFilter),
+}
+
+impl ConnectionFilter {
+    /// Compiles the case-insensitive regex from the
+    /// [`StealType::FilteredSni`](mirrord_protocol::tcp::StealType::FilteredSni) subscription.
+    pub fn sni(filter: &Filter) -> Result<Self, fancy_regex::Error> {
+        Ok(Self::Sni(Regex::new(&format!("(?i){filter}"))?))
+    }
+
+    /// Compiles the regexes from the
+    /// [`StealType::FilteredPostgres`](mirrord_protocol::tcp::StealType::FilteredPostgres)
+    /// subscription.
+    ///
+    /// Database and user names are case-sensitive in PostgreSQL, so are the regexes.
+    pub fn postgres(filter: &PostgresFilter) -> Result<Self, fancy_regex::Error> {
+        Ok(Self::Postgres {
+            database: filter.database.as_deref().map(Regex::new).transpose()?,
+            user: filter.user.as_deref().map(Regex::new).transpose()?,
+        })
+    }
+
+    /// Compiles the regexes from the
+    /// [`StealType::FilteredRedis`](mirrord_protocol::tcp::StealType::FilteredRedis)
+    /// subscription.
+    pub fn redis(filter: &RedisFilter) -> Result<Self, fancy_regex::Error> {
+        RedisCommandFilter::new(filter).map(Self::Redis)
+    }
+
+    /// Returns the [`RedisCommandFilter`], if this is a Redis filter.
+    pub fn as_redis(&self) -> Option<&RedisCommandFilter> {
+        match self {
+            Self::Redis(filter) => Some(filter),
+            _ => None,
+        }
+    }
+
+    /// Initial messages that need to be peeked from the connections, so that this filter can be
+    /// matched against them.
+    pub fn peeks(&self) -> ConnectionPeeks {
+        ConnectionPeeks {
+            sni: matches!(self, Self::Sni(..)),
+            postgres: matches!(self, Self::Postgres { .. }),
+        }
+    }
+
+    pub fn matches(&self, info: &ConnectionInfo) -> bool {
+        let matches = |regex: &Regex, value: Option<&str>| {
+            value.is_some_and(|value| regex.is_match(value).unwrap_or_default())
+        };
+
+        match self {
+            Self::Sni(regex) => matches(regex, info.server_name.as_deref()),
+            Self::Postgres { database, user } => {
+                let Some(startup) = &info.postgres else {
+                    return false;
+                };
+
+                database
+                    .iter()
+                    .all(|regex| matches(regex, startup.database.as_deref()))
+                    && user
+                        .iter()
+                        .all(|regex| matches(regex, startup.user.as_deref()))
+            }
+            Self::Redis(..) => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Not;
+
+    use rstest::rstest;
+
+    use super::*;
+    use crate::incoming::postgres::PostgresStartup;
+
+    fn info(server_name: Option<&str>, postgres: Option<(&str, &str)>) -> ConnectionInfo {
+        ConnectionInfo {
+            original_destination: "10.0.0.1:5432".parse().unwrap(),
+            local_addr: "127.0.0.1:30000".parse().unwrap(),
+            peer_addr: "10.0.0.2:40000".parse().unwrap(),
+            tls_connector: None,
+            server_name: server_name.map(ToOwned::to_owned),
+            postgres: postgres.map(|(database, user)| PostgresStartup {
+                database: Some(database.into()),
+                user: Some(user.into()),
+            }),
+        }
+    }
+
+    #[rstest]
+    #[case::database(Some("^mydb_test$"), None, ("mydb_test", "alice"), true)]
+    #[case::database_mismatch(Some("^mydb_test$"), None, ("mydb", "alice"), false)]
+    #[case::both(Some("_test$"), Some("^alice$"), ("mydb_test", "alice"), true)]
+    #[case::user_mismatch(Some("_test$"), Some("^alice$"), ("mydb_test", "bob"), false)]
+    #[case::case_sensitive(None, Some("^alice$"), ("mydb", "Alice"), false)]
+    fn postgres_filter(
+        #[case] database: Option<&str>,
+        #[case] user: Option<&str>,
+        #[case] startup: (&str, &str),
+        #[case] expected: bool,
+    ) {
+        let filter = ConnectionFilter::postgres(&PostgresFilter {
+            database: database.map(|filter| Filter::new(filter.into()).unwrap()),
+            user: user.map(|filter| Filter::new(filter.into()).unwrap()),
+        })
+        .unwrap();
+
+        assert_eq!(filter.matches(&info(None, Some(startup))), expected);
+        assert!(filter.matches(&info(None, None)).not());
+    }
+
+    #[test]
+    fn sni_filter_is_case_insensitive() {
+        let filter =
+            ConnectionFilter::sni(&Filter::new("^api\\.example\\.com$".into()).unwrap()).unwrap();
+
+        assert!(filter.matches(&info(Some("API.example.com"), None)));
+        assert!(filter.matches(&info(Some("web.example.com"), None)).not());
+        assert!(filter.matches(&info(None, None)).not());
+    }
+}
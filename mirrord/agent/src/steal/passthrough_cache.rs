@@ -0,0 +1,497 @@
+//! Caching responses to passed through idempotent requests, see [`PassthroughCache`].
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fmt,
+    net::SocketAddr,
+    ops::Not,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use http::{
+    HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri, Version,
+    header::{
+        AGE, CACHE_CONTROL, CONNECTION, CONTENT_LENGTH, HOST, SET_COOKIE, TRANSFER_ENCODING,
+        UPGRADE, VARY,
+    },
+    request,
+};
+use http_body_util::{BodyExt, Empty, Full, combinators::BoxBody};
+use hyper::{Request, Response, body::Incoming};
+use mirrord_agent_env::envs;
+use tracing::Level;
+
+use crate::{
+    http::{BoxResponse, MIRRORD_AGENT_HTTP_HEADER_NAME, error::MirrordErrorResponse},
+    incoming::{ConnError, RedirectedHttp, StolenHttp, send_to_original_destination},
+};
+
+/// Responses with larger bodies are passed through, but not cached.
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+/// Used when [`envs::PASSTHROUGH_CACHE_MAX_ENTRIES`] is not set.
+const DEFAULT_MAX_ENTRIES: u32 = 1024;
+
+/// Used when [`envs::PASSTHROUGH_CACHE_HEADERS`] is not set.
+const DEFAULT_HEADERS: &[&str] = &[
+    "accept",
+    "accept-encoding",
+    "accept-language",
+    "authorization",
+    "cookie",
+];
+
+/// Identifies requests that get the same cached response.
+///
+/// One cache is shared by all stolen ports, and HTTP/1 URIs usually don't contain the authority,
+/// so the key includes the original destination and the `Host` header too.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct CacheKey {
+    destination: SocketAddr,
+    method: Method,
+    uri: Uri,
+    host: Option<HeaderValue>,
+    /// Values of [`PassthroughCache::headers`], in the same order.
+    headers: Vec<Option<HeaderValue>>,
+}
+
+/// Response of the original destination, with the whole body.
+#[derive(Clone, Debug)]
+struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    stored_at: Instant,
+}
+
+impl CachedResponse {
+    fn into_response(self, version: Version) -> BoxResponse {
+        let age = self.stored_at.elapsed().as_secs();
+
+        let mut response = Response::new(
+            Full::new(self.body)
+                .map_err(|never: Infallible| match never {})
+                .boxed(),
+        );
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers;
+        *response.version_mut() = version;
+        response.headers_mut().insert(AGE, HeaderValue::from(age));
+        response
+    }
+}
+
+/// Answers repeated `GET` and `HEAD` requests that are passed through to their original
+/// destination from a short-lived cache, to reduce the additional load on the target while
+/// stealing with a filter (or while the local application restarts).
+///
+/// Enabled with [`envs::PASSTHROUGH_CACHE_TTL`]. Requests are identified by their original
+/// destination, method, URI, `Host` and the values of [`envs::PASSTHROUGH_CACHE_HEADERS`]. Only
+/// requests without a body are cached, and only successful responses that are small enough, are not
+/// `Cache-Control: no-store/no-cache/private` and don't set cookies.
+#[derive(Clone, Default)]
+pub struct PassthroughCache {
+    /// [`None`] when the cache is disabled.
+    ttl: Option<Duration>,
+    max_entries: usize,
+    headers: Arc<[HeaderName]>,
+    entries: Arc<Mutex<HashMap<CacheKey, CachedResponse>>>,
+}
+
+impl PassthroughCache {
+    pub fn new(ttl: Duration, max_entries: usize, headers: Vec<HeaderName>) -> Self {
+        Self {
+            ttl: Some(ttl).filter(|ttl| ttl.is_zero().not()),
+            max_entries,
+            headers: headers.into(),
+            entries: Default::default(),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let Some(ttl) =
+            Some(envs::PASSTHROUGH_CACHE_TTL.from_env_or_default()).filter(|ttl| *ttl > 0)
+        else {
+            return Self::default();
+        };
+
+        let max_entries = envs::PASSTHROUGH_CACHE_MAX_ENTRIES
+            .try_from_env()
+            .ok()
+            .flatten()
+            .unwrap_or(DEFAULT_MAX_ENTRIES);
+
+        let headers = envs::PASSTHROUGH_CACHE_HEADERS
+            .try_from_env()
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| DEFAULT_HEADERS.iter().map(ToString::to_string).collect())
+            .into_iter()
+            .filter_map(|name| match HeaderName::try_from(name.as_str()) {
+                Ok(name) => Some(name),
+                Err(error) => {
+                    tracing::warn!(%name, %error, "Invalid header name in the passthrough cache");
+                    None
+                }
+            })
+            .collect();
+
+        Self::new(
+            Duration::from_secs(ttl.into()),
+            max_entries as usize,
+            headers,
+        )
+    }
+
+    /// Passes the request through to its original destination, using the cache when possible.
+    #[tracing::instrument(level = Level::TRACE, skip(self))]
+    pub fn pass_through(&self, http: RedirectedHttp) {
+        let Some(ttl) = self.ttl else {
+            http.pass_through();
+            return;
+        };
+
+        if http.has_empty_body().not() || Self::is_cacheable_request(http.parts()).not() {
+            http.pass_through();
+            return;
+        }
+
+        let key = self.key(http.info().original_destination, http.parts());
+        let cached = self.get(&key, ttl);
+
+        let stolen = http.steal();
+        let version = stolen.request_head.parts.version;
+
+        if let Some(cached) = cached {
+            tracing::trace!("Answering a passed through request from the cache");
+            let response = Self::modify_response(&stolen, cached.into_response(version));
+            stolen.response_provider.send_finished(response);
+            return;
+        }
+
+        let cache = self.clone();
+        tokio::spawn(async move {
+            let response = match cache.fetch(&stolen, key).await {
+                Ok(response) => response,
+                Err(error) => MirrordErrorResponse::new(
+                    version,
+                    format!("failed to pass the request to its original destination: {error}"),
+                )
+                .into(),
+            };
+
+            let response = Self::modify_response(&stolen, response);
+            stolen.response_provider.send_finished(response);
+        });
+    }
+
+    /// Sends the request to the original destination, and caches the response if possible.
+    async fn fetch(&self, stolen: &StolenHttp, key: CacheKey) -> Result<BoxResponse, ConnError> {
+        let mut parts = stolen.request_head.parts.clone();
+        let version = parts.version;
+        parts.headers.remove(TRANSFER_ENCODING);
+        let body = Empty::<Bytes>::new().map_err(|never: Infallible| match never {});
+
+        let response =
+            send_to_original_destination(&stolen.info, Request::from_parts(parts, body)).await?;
+
+        if Self::is_cacheable_response(&key.method, response.status(), response.headers()).not() {
+            return Ok(response.map(BoxBody::new));
+        }
+
+        let (mut parts, body) = response.into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(From::from)
+            .map_err(ConnError::PassthroughHttpError)?
+            .to_bytes();
+
+        // We send the whole body at once, possibly over a different HTTP version.
+        parts.headers.remove(TRANSFER_ENCODING);
+        parts.headers.remove(CONNECTION);
+
+        let cached = CachedResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+            stored_at: Instant::now(),
+        };
+        self.insert(key, cached.clone());
+
+        Ok(cached.into_response(version))
+    }
+
+    /// Returns the cached response, if it is not older than the `ttl`.
+    fn get(&self, key: &CacheKey, ttl: Duration) -> Option<CachedResponse> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .filter(|cached| cached.stored_at.elapsed() < ttl)
+            .cloned()
+    }
+
+    /// Inserts a new response, making room for it if the cache is full.
+    fn insert(&self, key: CacheKey, response: CachedResponse) {
+        let Some(ttl) = self.ttl else {
+            return;
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.max_entries && entries.contains_key(&key).not() {
+            entries.retain(|_, cached| cached.stored_at.elapsed() < ttl);
+        }
+        if entries.len() >= self.max_entries
+            && entries.contains_key(&key).not()
+            && let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.stored_at)
+                .map(|(key, _)| key.clone())
+        {
+            entries.remove(&oldest);
+        }
+
+        if self.max_entries > 0 {
+            entries.insert(key, response);
+        }
+    }
+
+    fn key(&self, destination: SocketAddr, parts: &request::Parts) -> CacheKey {
+        CacheKey {
+            destination,
+            method: parts.method.clone(),
+            uri: parts.uri.clone(),
+            host: parts.headers.get(HOST).cloned(),
+            headers: self
+                .headers
+                .iter()
+                .map(|name| parts.headers.get(name).cloned())
+                .collect(),
+        }
+    }
+
+    fn is_cacheable_request(parts: &request::Parts) -> bool {
+        matches!(parts.method, Method::GET | Method::HEAD)
+            && parts.headers.contains_key(UPGRADE).not()
+            && Self::cache_control_allows(&parts.headers)
+    }
+
+    fn is_cacheable_response(method: &Method, status: StatusCode, headers: &HeaderMap) -> bool {
+        let small_enough = *method == Method::HEAD
+            || headers
+                .get(CONTENT_LENGTH)
+                .and_then(|length| length.to_str().ok()?.parse::<u64>().ok())
+                .is_some_and(|length| length <= MAX_BODY_SIZE);
+
+        status.is_success()
+            && small_enough
+            && headers.contains_key(SET_COOKIE).not()
+            && headers
+                .get_all(VARY)
+                .iter()
+                .any(|vary| vary.as_bytes() == b"*")
+                .not()
+            && Self::cache_control_allows(headers)
+    }
+
+    /// Whether the `Cache-Control` headers allow serving the message from a shared cache.
+    fn cache_control_allows(headers: &HeaderMap) -> bool {
+        headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|directive| directive.trim().to_ascii_lowercase())
+            .any(|directive| {
+                directive == "no-store" || directive == "no-cache" || directive == "private"
+            })
+            .not()
+    }
+
+    /// Marks the response like [`RedirectedHttp::pass_through`] does.
+    fn modify_response(stolen: &StolenHttp, mut response: BoxResponse) -> BoxResponse {
+        if stolen.redirector_config.inject_headers {
+            response.headers_mut().insert(
+                MIRRORD_AGENT_HTTP_HEADER_NAME,
+                HeaderValue::from_static("passed-through"),
+            );
+        }
+
+        response
+    }
+}
+
+impl fmt::Debug for PassthroughCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PassthroughCache")
+            .field("ttl", &self.ttl)
+            .field("max_entries", &self.max_entries)
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        net::SocketAddr,
+        ops::Not,
+        time::{Duration, Instant},
+    };
+
+    use bytes::Bytes;
+    use http::{
+        HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode,
+        header::{CACHE_CONTROL, CONTENT_LENGTH, HOST, SET_COOKIE, UPGRADE, VARY},
+        request,
+    };
+    use rstest::rstest;
+
+    use super::{CacheKey, CachedResponse, PassthroughCache};
+
+    const TTL: Duration = Duration::from_secs(30);
+
+    fn parts(method: Method, uri: &str, headers: &[(HeaderName, &str)]) -> request::Parts {
+        let mut request = Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            request = request.header(name, *value);
+        }
+        request.body(()).unwrap().into_parts().0
+    }
+
+    fn response(age: Duration) -> CachedResponse {
+        CachedResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(b"hello"),
+            stored_at: Instant::now().checked_sub(age).unwrap(),
+        }
+    }
+
+    fn key(cache: &PassthroughCache, destination: &str, host: &str) -> CacheKey {
+        cache.key(
+            destination.parse::<SocketAddr>().unwrap(),
+            &parts(Method::GET, "/api", &[(HOST, host)]),
+        )
+    }
+
+    /// Verifies that requests to different ports or virtual hosts don't share cached responses.
+    #[test]
+    fn key_includes_destination_and_host() {
+        let cache = PassthroughCache::new(TTL, 8, vec![]);
+
+        let key = key(&cache, "10.0.0.1:80", "a.example.com");
+        assert_eq!(key, self::key(&cache, "10.0.0.1:80", "a.example.com"));
+        assert_ne!(key, self::key(&cache, "10.0.0.1:8080", "a.example.com"));
+        assert_ne!(key, self::key(&cache, "10.0.0.1:80", "b.example.com"));
+    }
+
+    /// Verifies that only the configured headers are part of the key.
+    #[test]
+    fn key_includes_configured_headers() {
+        let cache = PassthroughCache::new(TTL, 8, vec![HeaderName::from_static("x-tenant")]);
+        let destination = "10.0.0.1:80".parse().unwrap();
+        let key = |headers: &[(HeaderName, &str)]| {
+            cache.key(destination, &parts(Method::GET, "/api", headers))
+        };
+
+        let tenant = HeaderName::from_static("x-tenant");
+        let request_id = HeaderName::from_static("x-request-id");
+        assert_eq!(
+            key(&[(tenant.clone(), "a"), (request_id.clone(), "1")]),
+            key(&[(tenant.clone(), "a"), (request_id, "2")]),
+        );
+        assert_ne!(key(&[(tenant.clone(), "a")]), key(&[(tenant, "b")]));
+    }
+
+    #[test]
+    fn expired_responses_are_not_served() {
+        let cache = PassthroughCache::new(TTL, 8, vec![]);
+        let fresh = key(&cache, "10.0.0.1:80", "fresh");
+        let expired = key(&cache, "10.0.0.1:80", "expired");
+
+        cache.insert(fresh.clone(), response(Duration::ZERO));
+        cache.insert(expired.clone(), response(TTL * 2));
+
+        assert!(cache.get(&fresh, TTL).is_some());
+        assert!(cache.get(&expired, TTL).is_none());
+    }
+
+    /// Verifies that a full cache drops the expired responses first, and then the oldest one.
+    #[test]
+    fn full_cache_evicts_oldest() {
+        let cache = PassthroughCache::new(TTL, 2, vec![]);
+        let keys = ["a", "b", "c", "d"].map(|host| key(&cache, "10.0.0.1:80", host));
+
+        cache.insert(keys[0].clone(), response(TTL * 2));
+        cache.insert(keys[1].clone(), response(Duration::from_secs(2)));
+        cache.insert(keys[2].clone(), response(Duration::from_secs(1)));
+        assert!(cache.entries.lock().unwrap().contains_key(&keys[0]).not());
+
+        cache.insert(keys[3].clone(), response(Duration::ZERO));
+        let entries = cache.entries.lock().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.contains_key(&keys[2]));
+        assert!(entries.contains_key(&keys[3]));
+    }
+
+    #[test]
+    fn disabled_cache_stores_nothing() {
+        let cache = PassthroughCache::new(Duration::ZERO, 8, vec![]);
+        let key = key(&cache, "10.0.0.1:80", "a");
+
+        cache.insert(key.clone(), response(Duration::ZERO));
+
+        assert!(cache.entries.lock().unwrap().is_empty());
+    }
+
+    #[rstest]
+    #[case(Method::GET, &[], true)]
+    #[case(Method::HEAD, &[], true)]
+    #[case(Method::POST, &[], false)]
+    #[case(Method::GET, &[(UPGRADE, "websocket")], false)]
+    #[case(Method::GET, &[(CACHE_CONTROL, "max-age=0, no-cache")], false)]
+    #[case(Method::GET, &[(CACHE_CONTROL, "No-Store")], false)]
+    #[case(Method::GET, &[(CACHE_CONTROL, "max-age=60")], true)]
+    fn cacheable_requests(
+        #[case] method: Method,
+        #[case] headers: &[(HeaderName, &str)],
+        #[case] cacheable: bool,
+    ) {
+        assert_eq!(
+            PassthroughCache::is_cacheable_request(&parts(method, "/", headers)),
+            cacheable
+        );
+    }
+
+    #[rstest]
+    #[case(Method::GET, StatusCode::OK, &[(CONTENT_LENGTH, "5")], true)]
+    #[case(Method::GET, StatusCode::OK, &[], false)]
+    #[case(Method::HEAD, StatusCode::OK, &[], true)]
+    #[case(Method::GET, StatusCode::OK, &[(CONTENT_LENGTH, "1000000000")], false)]
+    #[case(Method::GET, StatusCode::NOT_FOUND, &[(CONTENT_LENGTH, "5")], false)]
+    #[case(Method::GET, StatusCode::OK, &[(CONTENT_LENGTH, "5"), (SET_COOKIE, "a=b")], false)]
+    #[case(Method::GET, StatusCode::OK, &[(CONTENT_LENGTH, "5"), (VARY, "*")], false)]
+    #[case(Method::GET, StatusCode::OK, &[(CONTENT_LENGTH, "5"), (VARY, "accept")], true)]
+    #[case(Method::GET, StatusCode::OK, &[(CONTENT_LENGTH, "5"), (CACHE_CONTROL, "private")], false)]
+    fn cacheable_responses(
+        #[case] method: Method,
+        #[case] status: StatusCode,
+        #[case] headers: &[(HeaderName, &str)],
+        #[case] cacheable: bool,
+    ) {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+            .collect::<HeaderMap>();
+
+        assert_eq!(
+            PassthroughCache::is_cacheable_response(&method, status, &headers),
+            cacheable
+        );
+    }
+}
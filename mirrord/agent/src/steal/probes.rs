@@ -0,0 +1,180 @@
+//! Answering the target's HTTP probes during whole-port steal, see [`ProbeResponder`].
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use http::{
+    HeaderMap, StatusCode, Version,
+    header::{CONNECTION, TRANSFER_ENCODING},
+    request,
+};
+use http_body_util::{BodyExt, Empty, Full};
+use hyper::{Request, Response};
+use tracing::Level;
+
+use crate::{
+    http::{BoxResponse, error::MirrordErrorResponse},
+    incoming::{ConnError, ConnectionInfo, RedirectedHttp, send_to_original_destination},
+};
+
+/// How long we wait for the target's application to respond to a probe, before falling back to
+/// the last response.
+///
+/// Kubernetes probes time out after 1 second by default.
+const UPSTREAM_TIMEOUT: Duration = Duration::from_millis(800);
+
+/// Response of the target's application to a probe.
+#[derive(Clone, Debug)]
+struct ProbeResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl ProbeResponse {
+    fn into_response(self, version: Version) -> BoxResponse {
+        let mut response = Response::new(
+            Full::new(self.body)
+                .map_err(|never: Infallible| match never {})
+                .boxed(),
+        );
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers;
+        *response.version_mut() = version;
+        response
+    }
+}
+
+/// Keeps the target's readiness and liveness probes away from the clients that steal all
+/// traffic on a port.
+///
+/// Requests to the probe paths (configured with
+/// [`envs::PROBE_PATHS`](mirrord_agent_env::envs::PROBE_PATHS)) are sent to their original
+/// destination, and the last response for each path is remembered. When the original
+/// destination does not respond within [`UPSTREAM_TIMEOUT`], the probe gets the last response
+/// instead, so that the pod does not flap between ready and not ready.
+#[derive(Clone, Default)]
+pub struct ProbeResponder {
+    paths: Arc<[String]>,
+    last_responses: Arc<Mutex<HashMap<String, ProbeResponse>>>,
+}
+
+impl ProbeResponder {
+    pub fn new(paths: Vec<String>) -> Self {
+        Self {
+            paths: paths.into(),
+            last_responses: Default::default(),
+        }
+    }
+
+    /// Returns whether the request is a probe.
+    pub fn matches(&self, request: &request::Parts) -> bool {
+        self.paths.iter().any(|path| path == request.uri.path())
+    }
+
+    /// Answers the probe from a background task.
+    ///
+    /// The request body is discarded, probes don't have one.
+    #[tracing::instrument(level = Level::TRACE, skip(self))]
+    pub fn respond(&self, http: RedirectedHttp) {
+        let stolen = http.steal();
+        let last_responses = self.last_responses.clone();
+
+        tokio::spawn(async move {
+            let mut parts = stolen.request_head.parts;
+            let version = parts.version;
+            let path = parts.uri.path().to_string();
+            parts.headers.remove(TRANSFER_ENCODING);
+
+            let result =
+                tokio::time::timeout(UPSTREAM_TIMEOUT, Self::send_upstream(&stolen.info, parts))
+                    .await;
+
+            let response = match result {
+                Ok(Ok(response)) => {
+                    last_responses
+                        .lock()
+                        .unwrap()
+                        .insert(path, response.clone());
+                    response.into_response(version)
+                }
+                Ok(Err(error)) => Self::fallback(&last_responses, &path, version, error),
+                Err(..) => Self::fallback(
+                    &last_responses,
+                    &path,
+                    version,
+                    format_args!("no response within {UPSTREAM_TIMEOUT:?}"),
+                ),
+            };
+
+            stolen.response_provider.send_finished(response);
+        });
+    }
+
+    async fn send_upstream(
+        info: &ConnectionInfo,
+        parts: request::Parts,
+    ) -> Result<ProbeResponse, ConnError> {
+        let body = Empty::<Bytes>::new().map_err(|never: Infallible| match never {});
+        let response = send_to_original_destination(info, Request::from_parts(parts, body)).await?;
+
+        let (mut parts, body) = response.into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(From::from)
+            .map_err(ConnError::PassthroughHttpError)?
+            .to_bytes();
+
+        // We send the whole body at once, possibly over a different HTTP version.
+        parts.headers.remove(TRANSFER_ENCODING);
+        parts.headers.remove(CONNECTION);
+
+        Ok(ProbeResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        })
+    }
+
+    /// The last response to the probe, or an error response if the original destination never
+    /// responded.
+    fn fallback<E: fmt::Display>(
+        last_responses: &Mutex<HashMap<String, ProbeResponse>>,
+        path: &str,
+        version: Version,
+        error: E,
+    ) -> BoxResponse {
+        let last_response = last_responses.lock().unwrap().get(path).cloned();
+
+        match last_response {
+            Some(response) => {
+                tracing::debug!(
+                    path,
+                    %error,
+                    "Probe was not answered by its original destination, using the last response",
+                );
+                response.into_response(version)
+            }
+            None => MirrordErrorResponse::new(
+                version,
+                format!("probe {path} was not answered by its original destination: {error}"),
+            )
+            .into(),
+        }
+    }
+}
+
+impl fmt::Debug for ProbeResponder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProbeResponder")
+            .field("paths", &self.paths)
+            .finish()
+    }
+}
@@ -7,9 +7,10 @@ use std::{
 
 use tracing::Level;
 
+use super::connection_filter::ConnectionFilter;
 use crate::{
     http::filter::HttpFilter,
-    incoming::{RedirectorTaskError, StealHandle, StolenTraffic},
+    incoming::{ConnectionPeeks, RedirectorTaskError, StealHandle, StolenTraffic},
     metrics::{STEAL_FILTERED_PORT_SUBSCRIPTION, STEAL_UNFILTERED_PORT_SUBSCRIPTION},
     util::ClientId,
 };
@@ -35,6 +36,9 @@ impl Drop for PortSubscriptions {
                         PortSubscription::Filtered(filters) => {
                             (unfiltered, filtered + filters.len())
                         }
+                        PortSubscription::Connection(filters) => {
+                            (unfiltered, filtered + filters.len())
+                        }
                         PortSubscription::Unfiltered(..) => (unfiltered + 1, filtered),
                     },
                 );
@@ -70,6 +74,7 @@ impl PortSubscriptions {
     ///
     /// * A single client may have only one subscription for the given port
     /// * A single port may have only one unfiltered subscription
+    /// * A single port cannot have both HTTP and connection filtered subscriptions
     ///
     /// When a new subscription clashes with an existing one, the old one is replaced.
     ///
@@ -115,6 +120,17 @@ impl PortSubscriptions {
                     e.insert(PortSubscription::Unfiltered(client_id));
                     true
                 }
+
+                (PortSubscription::Connection(filters), filter) => {
+                    STEAL_FILTERED_PORT_SUBSCRIPTION.fetch_sub(filters.len(), Ordering::Relaxed);
+                    if filter.is_some() {
+                        STEAL_FILTERED_PORT_SUBSCRIPTION.fetch_add(1, Ordering::Relaxed);
+                    } else {
+                        STEAL_UNFILTERED_PORT_SUBSCRIPTION.fetch_add(1, Ordering::Relaxed);
+                    }
+                    e.insert(PortSubscription::new(client_id, filter));
+                    true
+                }
             },
 
             Entry::Vacant(e) => {
@@ -134,6 +150,70 @@ impl PortSubscriptions {
             tracing::debug!("An existing port subscription was evicted.");
         }
 
+        self.handle.set_peeks(port, ConnectionPeeks::default());
+
+        Ok(())
+    }
+
+    /// Try adding a new [`ConnectionFilter`] subscription to this set.
+    ///
+    /// Follows the same clash rules as [`Self::add`]. A connection filtered subscription evicts
+    /// all unfiltered and HTTP filtered subscriptions on the port.
+    ///
+    /// # Params
+    ///
+    /// * `client_id` - identifier of the client that issued the subscription
+    /// * `port` - number of the port to steal from
+    /// * `filter` - [`ConnectionFilter`] for the whole connection
+    #[tracing::instrument(level = Level::DEBUG, err(level = Level::DEBUG))]
+    pub async fn add_connection_filter(
+        &mut self,
+        client_id: ClientId,
+        port: u16,
+        filter: ConnectionFilter,
+    ) -> Result<(), RedirectorTaskError> {
+        let replaced = match self.subscriptions.entry(port) {
+            Entry::Occupied(mut e) => match e.get_mut() {
+                PortSubscription::Connection(filters) => match filters.insert(client_id, filter) {
+                    Some(..) => true,
+                    None => {
+                        STEAL_FILTERED_PORT_SUBSCRIPTION.fetch_add(1, Ordering::Relaxed);
+                        false
+                    }
+                },
+
+                PortSubscription::Unfiltered(..) => {
+                    STEAL_UNFILTERED_PORT_SUBSCRIPTION.fetch_sub(1, Ordering::Relaxed);
+                    STEAL_FILTERED_PORT_SUBSCRIPTION.fetch_add(1, Ordering::Relaxed);
+                    e.insert(PortSubscription::Connection([(client_id, filter)].into()));
+                    true
+                }
+
+                PortSubscription::Filtered(filters) => {
+                    STEAL_FILTERED_PORT_SUBSCRIPTION.fetch_sub(filters.len(), Ordering::Relaxed);
+                    STEAL_FILTERED_PORT_SUBSCRIPTION.fetch_add(1, Ordering::Relaxed);
+                    e.insert(PortSubscription::Connection([(client_id, filter)].into()));
+                    true
+                }
+            },
+
+            Entry::Vacant(e) => {
+                self.handle.steal(port).await?;
+                STEAL_FILTERED_PORT_SUBSCRIPTION.fetch_add(1, Ordering::Relaxed);
+                e.insert(PortSubscription::Connection([(client_id, filter)].into()));
+                false
+            }
+        };
+
+        if replaced {
+            // All info already be contained in the span.
+            tracing::debug!("An existing port subscription was evicted.");
+        }
+
+        if let Some(subscription) = self.subscriptions.get(&port) {
+            self.handle.set_peeks(port, subscription.peeks());
+        }
+
         Ok(())
     }
 
@@ -159,14 +239,17 @@ impl PortSubscriptions {
             }
             PortSubscription::Unfiltered(..) => {}
             PortSubscription::Filtered(filters) => {
-                if filters.remove(&client_id).is_some() {
-                    STEAL_FILTERED_PORT_SUBSCRIPTION
-                        .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+                if remove_filter(filters, client_id) {
+                    e.remove();
+                    self.handle.stop_steal(port);
                 }
-
-                if filters.is_empty() {
+            }
+            PortSubscription::Connection(filters) => {
+                if remove_filter(filters, client_id) {
                     e.remove();
                     self.handle.stop_steal(port);
+                } else {
+                    self.handle.set_peeks(port, e.get().peeks());
                 }
             }
         }
@@ -193,16 +276,22 @@ impl PortSubscriptions {
                 }
                 PortSubscription::Unfiltered(..) => true,
                 PortSubscription::Filtered(filters) => {
-                    if filters.remove(&client_id).is_some() {
-                        STEAL_FILTERED_PORT_SUBSCRIPTION
-                            .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+                    let empty = remove_filter(filters, client_id);
+                    if empty {
+                        self.handle.stop_steal(*port);
                     }
 
-                    if filters.is_empty() {
+                    empty.not()
+                }
+                PortSubscription::Connection(filters) => {
+                    let empty = remove_filter(filters, client_id);
+                    if empty {
                         self.handle.stop_steal(*port);
+                    } else {
+                        self.handle.set_peeks(*port, subscription.peeks());
                     }
 
-                    filters.is_empty().not()
+                    empty.not()
                 }
             });
     }
@@ -246,6 +335,14 @@ pub enum PortSubscription {
     ///
     /// Can be shared by multiple clients.
     Filtered(HashMap<ClientId, HttpFilter>),
+    /// Only connections matching one of the [`ConnectionFilter`]s should be stolen whole (on
+    /// behalf of the filter owner), e.g. TLS connections with a matching server name (SNI). The
+    /// connections are not decrypted.
+    ///
+    /// With a [`ConnectionFilter::Redis`], only the matching Redis commands are stolen.
+    ///
+    /// Can be shared by multiple clients.
+    Connection(HashMap<ClientId, ConnectionFilter>),
 }
 
 impl PortSubscription {
@@ -256,14 +353,47 @@ impl PortSubscription {
             None => Self::Unfiltered(client_id),
         }
     }
+
+    /// Initial messages that need to be peeked from the connections, so that the
+    /// [`ConnectionFilter`]s can be matched against them.
+    fn peeks(&self) -> ConnectionPeeks {
+        let Self::Connection(filters) = self else {
+            return ConnectionPeeks::default();
+        };
+
+        filters.values().map(ConnectionFilter::peeks).fold(
+            ConnectionPeeks::default(),
+            |peeks, filter_peeks| ConnectionPeeks {
+                sni: peeks.sni || filter_peeks.sni,
+                postgres: peeks.postgres || filter_peeks.postgres,
+            },
+        )
+    }
+}
+
+/// Removes the client's filter from a filtered subscription, updating the
+/// [`STEAL_FILTERED_PORT_SUBSCRIPTION`] metric.
+///
+/// Returns whether the subscription is left without any filters.
+fn remove_filter<F>(filters: &mut HashMap<ClientId, F>, client_id: ClientId) -> bool {
+    if filters.remove(&client_id).is_some() {
+        STEAL_FILTERED_PORT_SUBSCRIPTION.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    filters.is_empty()
 }
 
 #[cfg(test)]
 mod test {
+    use std::ops::Not;
+
     use crate::{
         http::filter::HttpFilter,
-        incoming::{RedirectorTask, RedirectorTaskConfig, test::DummyRedirector},
-        steal::subscriptions::{PortSubscription, PortSubscriptions},
+        incoming::{ConnectionPeeks, RedirectorTask, RedirectorTaskConfig, test::DummyRedirector},
+        steal::{
+            connection_filter::ConnectionFilter,
+            subscriptions::{PortSubscription, PortSubscriptions},
+        },
         util::ClientId,
     };
 
@@ -272,6 +402,7 @@ mod test {
         fn has_client(&self, client_id: ClientId) -> bool {
             match self {
                 Self::Filtered(filters) => filters.contains_key(&client_id),
+                Self::Connection(filters) => filters.contains_key(&client_id),
                 Self::Unfiltered(subscribed_client) => *subscribed_client == client_id,
             }
         }
@@ -328,6 +459,116 @@ mod test {
         assert!(sub.is_none(), "{sub:?}");
     }
 
+    #[tokio::test]
+    async fn connection_subscriptions_evict_http() {
+        let (redirector, mut state, _tx) = DummyRedirector::new();
+        let (redirector_task, steal_handle, _) = RedirectorTask::new(
+            redirector,
+            Default::default(),
+            RedirectorTaskConfig::from_env(),
+        );
+        tokio::spawn(redirector_task.run());
+        let mut subscriptions = PortSubscriptions::new(steal_handle);
+
+        subscriptions
+            .add(0, 443, Some(dummy_filter()))
+            .await
+            .unwrap();
+
+        // SNI subscription should evict the HTTP filtered one.
+        subscriptions
+            .add_connection_filter(1, 443, ConnectionFilter::Sni("^api\\.".parse().unwrap()))
+            .await
+            .unwrap();
+        subscriptions
+            .add_connection_filter(2, 443, ConnectionFilter::Sni("^web\\.".parse().unwrap()))
+            .await
+            .unwrap();
+        assert!(state.borrow().has_redirections([443]));
+        let sub = subscriptions.subscriptions.get(&443).unwrap();
+        assert!(
+            matches!(sub, PortSubscription::Connection(filters) if filters.len() == 2 && sub.has_client(0).not()),
+            "{sub:?}"
+        );
+
+        subscriptions.remove(1, 443);
+        assert!(state.borrow().has_redirections([443]));
+        subscriptions.remove(2, 443);
+
+        state
+            .wait_for(|state| state.has_redirections([]))
+            .await
+            .unwrap();
+        let sub = subscriptions.subscriptions.get(&443);
+        assert!(sub.is_none(), "{sub:?}");
+    }
+
+    /// Verifies that the initial messages are peeked only on the ports with connection filters
+    /// that need them.
+    #[tokio::test]
+    async fn connection_filters_set_peeks() {
+        let (redirector, _state, _tx) = DummyRedirector::new();
+        let (redirector_task, steal_handle, _) = RedirectorTask::new(
+            redirector,
+            Default::default(),
+            RedirectorTaskConfig::from_env(),
+        );
+        tokio::spawn(redirector_task.run());
+        let mut subscriptions = PortSubscriptions::new(steal_handle);
+        let postgres = ConnectionFilter::Postgres {
+            database: Some("^orders$".parse().unwrap()),
+            user: None,
+        };
+
+        subscriptions
+            .add_connection_filter(0, 443, ConnectionFilter::Sni("^api\\.".parse().unwrap()))
+            .await
+            .unwrap();
+        subscriptions
+            .add_connection_filter(1, 443, postgres.clone())
+            .await
+            .unwrap();
+        subscriptions
+            .add_connection_filter(2, 5432, postgres)
+            .await
+            .unwrap();
+        subscriptions
+            .add(3, 80, Some(dummy_filter()))
+            .await
+            .unwrap();
+        assert_eq!(
+            subscriptions.handle.peeks(443),
+            ConnectionPeeks {
+                sni: true,
+                postgres: true
+            }
+        );
+        assert_eq!(
+            subscriptions.handle.peeks(5432),
+            ConnectionPeeks {
+                sni: false,
+                postgres: true
+            }
+        );
+        assert_eq!(subscriptions.handle.peeks(80), ConnectionPeeks::default());
+
+        subscriptions.remove(1, 443);
+        assert_eq!(
+            subscriptions.handle.peeks(443),
+            ConnectionPeeks {
+                sni: true,
+                postgres: false
+            }
+        );
+
+        // HTTP subscription evicts the connection filters.
+        subscriptions.add(3, 5432, None).await.unwrap();
+        assert_eq!(subscriptions.handle.peeks(5432), ConnectionPeeks::default());
+
+        subscriptions.remove_all(0);
+        assert_eq!(subscriptions.handle.peeks(443), ConnectionPeeks::default());
+    }
+
     #[tokio::test]
     async fn multiple_subscriptions_multiple_ports() {
         let (redirector, mut state, _tx) = DummyRedirector::new();
@@ -1,16 +1,20 @@
 use std::{
     borrow::Cow,
-    collections::{HashMap, hash_map::Entry},
+    cmp::Reverse,
+    collections::{HashMap, HashSet, hash_map::Entry},
     fmt,
+    net::IpAddr,
     ops::Not,
 };
 
 use futures::{StreamExt, stream::FuturesUnordered};
 use http::header::UPGRADE;
+use mirrord_agent_env::steal_conflict::StealConflictPolicy;
 use mirrord_protocol::{
-    LogMessage,
+    LogMessage, Port,
     tcp::{
-        HTTP_CHUNKED_REQUEST_V2_VERSION, HTTP_FILTERED_UPGRADE_VERSION, MODE_AGNOSTIC_HTTP_REQUESTS,
+        BacklogOverflow, HTTP_CHUNKED_REQUEST_V2_VERSION, HTTP_FILTERED_UPGRADE_VERSION,
+        MODE_AGNOSTIC_HTTP_REQUESTS, SourceFilter,
     },
 };
 use tokio::{sync::mpsc, task::JoinSet};
@@ -19,11 +23,18 @@ use tracing::Level;
 
 use super::{
     Command, StealerCommand, StealerMessage,
+    connection_filter::ConnectionFilter,
+    passthrough_cache::PassthroughCache,
+    probes::ProbeResponder,
     subscriptions::{PortSubscription, PortSubscriptions},
 };
 use crate::{
     http::filter::HttpFilter,
-    incoming::{RedirectedHttp, RedirectedTcp, RedirectorTaskError, StealHandle, StolenTraffic},
+    incoming::{
+        ConnectionInfo, RedirectedHttp, RedirectedTcp, RedirectorTaskError, StealHandle,
+        StolenTraffic, redis::RedisRouter,
+    },
+    metrics::BACKLOG_OVERFLOW_CONNECTIONS,
     util::{ChannelClosedFuture, ClientId, protocol_version::ClientProtocolVersion},
 };
 
@@ -42,16 +53,31 @@ pub struct TcpStealerTask {
     disconnected_clients: FuturesUnordered<ChannelClosedFuture>,
     /// For tracking http requests whose bodies are being buffered
     ongoing_requests: JoinSet<RedirectedHttp>,
+    /// Picks the client for a stolen HTTP request that matches the filters of many clients.
+    conflict_policy: StealConflictPolicy,
+    /// Answers the target's probes when all traffic on a port is stolen.
+    probes: ProbeResponder,
+    /// Answers repeated requests that are passed through to their original destination.
+    passthrough_cache: PassthroughCache,
 }
 
 impl TcpStealerTask {
-    pub fn new(command_rx: mpsc::Receiver<StealerCommand>, handle: StealHandle) -> Self {
+    pub fn new(
+        command_rx: mpsc::Receiver<StealerCommand>,
+        handle: StealHandle,
+        conflict_policy: StealConflictPolicy,
+        probes: ProbeResponder,
+        passthrough_cache: PassthroughCache,
+    ) -> Self {
         Self {
             subscriptions: PortSubscriptions::new(handle),
             command_rx,
             clients: Default::default(),
             disconnected_clients: Default::default(),
             ongoing_requests: Default::default(),
+            conflict_policy,
+            probes,
+            passthrough_cache,
         }
     }
 
@@ -67,7 +93,15 @@ impl TcpStealerTask {
 
                 Some(result) = self.subscriptions.next() => {
                     let (traffic, subscription) = result?;
-                    Self::handle_stolen_traffic(&self.clients, traffic, subscription, &mut self.ongoing_requests).await;
+                    Self::handle_stolen_traffic(
+                        &self.clients,
+                        traffic,
+                        subscription,
+                        &mut self.ongoing_requests,
+                        self.conflict_policy,
+                        &self.probes,
+                        &self.passthrough_cache,
+                    ).await;
                 }
 
                 Some(client_id) = self.disconnected_clients.next() => {
@@ -123,22 +157,39 @@ impl TcpStealerTask {
         subscription: &PortSubscription,
         http: &RedirectedHttp,
     ) -> Cow<'static, semver::VersionReq> {
-        matches!(subscription, PortSubscription::Unfiltered(..))
-            .then_some(&*MODE_AGNOSTIC_HTTP_REQUESTS)
-            .or_else(|| {
-                http.info()
-                    .tls_connector
-                    .is_some()
-                    .then_some(&*HTTP_CHUNKED_REQUEST_V2_VERSION)
-            })
-            .or_else(|| {
-                http.parts()
-                    .headers
-                    .contains_key(UPGRADE)
-                    .then_some(&*HTTP_FILTERED_UPGRADE_VERSION)
-            })
-            .map(Cow::Borrowed)
-            .unwrap_or(Cow::Owned(semver::VersionReq::STAR))
+        matches!(
+            subscription,
+            PortSubscription::Unfiltered(..) | PortSubscription::Connection(..)
+        )
+        .then_some(&*MODE_AGNOSTIC_HTTP_REQUESTS)
+        .or_else(|| {
+            http.info()
+                .tls_connector
+                .is_some()
+                .then_some(&*HTTP_CHUNKED_REQUEST_V2_VERSION)
+        })
+        .or_else(|| {
+            http.parts()
+                .headers
+                .contains_key(UPGRADE)
+                .then_some(&*HTTP_FILTERED_UPGRADE_VERSION)
+        })
+        .map(Cow::Borrowed)
+        .unwrap_or(Cow::Owned(semver::VersionReq::STAR))
+    }
+
+    /// Returns the client whose [`ConnectionFilter`] matches the given connection.
+    ///
+    /// If there are multiple such clients, the one that connected first is returned.
+    fn connection_owner(
+        filters: &HashMap<ClientId, ConnectionFilter>,
+        info: &ConnectionInfo,
+    ) -> Option<ClientId> {
+        filters
+            .iter()
+            .filter(|(_, filter)| filter.matches(info))
+            .map(|(client_id, _)| *client_id)
+            .min()
     }
 
     #[tracing::instrument(level = Level::TRACE, ret)]
@@ -147,12 +198,34 @@ impl TcpStealerTask {
         traffic: StolenTraffic,
         subscription: &PortSubscription,
         ongoing: &mut JoinSet<RedirectedHttp>,
+        conflict_policy: StealConflictPolicy,
+        probes: &ProbeResponder,
+        passthrough_cache: &PassthroughCache,
     ) {
         let protocol_version_req = match &traffic {
             StolenTraffic::Tcp { conn, .. } => Self::protocol_version_req_tcp(subscription, conn),
             StolenTraffic::Http(http) => Self::protocol_version_req_http(subscription, http),
         };
 
+        // Unfiltered and connection filtered subscriptions steal the whole traffic on behalf of a
+        // single client.
+        let owner = match subscription {
+            PortSubscription::Unfiltered(client_id) => Some(*client_id),
+            PortSubscription::Connection(filters) => {
+                Self::connection_owner(filters, traffic.info())
+            }
+            PortSubscription::Filtered(..) => None,
+        };
+
+        // Redis filtered subscriptions steal only some of the commands on the connection.
+        let redis_filter = match (subscription, owner) {
+            (PortSubscription::Connection(filters), Some(client_id)) => filters
+                .get(&client_id)
+                .and_then(ConnectionFilter::as_redis)
+                .cloned(),
+            _ => None,
+        };
+
         let (filters, mut http) = match (subscription, traffic) {
             (PortSubscription::Filtered(filters), StolenTraffic::Http(http)) => (filters, http),
 
@@ -171,14 +244,22 @@ impl TcpStealerTask {
             }
 
             (
-                PortSubscription::Unfiltered(client_id),
+                PortSubscription::Unfiltered(..) | PortSubscription::Connection(..),
                 StolenTraffic::Tcp {
                     conn,
                     join_handle_tx,
                     shutdown,
                 },
             ) => {
-                let Some(client) = clients.get(client_id) else {
+                let Some(client_id) = owner else {
+                    // No connection filter matched.
+                    join_handle_tx
+                        .send(conn.pass_through(shutdown))
+                        .expect("RedirectorTask dropped oneshot rx for receiving JoinHandle to IO task for TCP connection");
+                    return;
+                };
+
+                let Some(client) = clients.get(&client_id) else {
                     tracing::error!(
                         client_id,
                         "TcpStealerTask failed to find a connected client for a stolen TCP connection, \
@@ -191,8 +272,35 @@ impl TcpStealerTask {
                     return;
                 };
 
+                let port = conn.info().original_destination.port();
+                if client.skips(port, conn.info().peer_addr.ip()) {
+                    join_handle_tx
+                        .send(conn.pass_through(shutdown))
+                        .expect("RedirectorTask dropped oneshot rx for receiving JoinHandle to IO task for TCP connection");
+                    return;
+                }
+
+                if let Some(overflow) = client.full_backlogs.get(&port) {
+                    let (action, join_handle) = match overflow {
+                        BacklogOverflow::Reject => ("reject", conn.reject()),
+                        BacklogOverflow::PassThrough => {
+                            ("passthrough", conn.pass_through(shutdown))
+                        }
+                    };
+                    BACKLOG_OVERFLOW_CONNECTIONS
+                        .with_label_values(&[port.to_string().as_str(), action])
+                        .inc();
+                    join_handle_tx
+                        .send(join_handle)
+                        .expect("RedirectorTask dropped oneshot rx for receiving JoinHandle to IO task for TCP connection");
+                    return;
+                }
+
                 let message = if client.protocol_version.matches(&protocol_version_req) {
-                    let (steal_handle, join_handle) = conn.steal(shutdown);
+                    let (steal_handle, join_handle) = match redis_filter {
+                        Some(filter) => conn.steal_redis(RedisRouter::new(filter), shutdown),
+                        None => conn.steal(shutdown),
+                    };
                     join_handle_tx
                         .send(join_handle)
                         .expect("RedirectorTask dropped oneshot rx for receiving JoinHandle to IO task for TCP connection");
@@ -213,8 +321,22 @@ impl TcpStealerTask {
                 return;
             }
 
-            (PortSubscription::Unfiltered(client_id), StolenTraffic::Http(http)) => {
-                let Some(client) = clients.get(client_id) else {
+            (
+                PortSubscription::Unfiltered(..) | PortSubscription::Connection(..),
+                StolenTraffic::Http(http),
+            ) => {
+                if probes.matches(http.parts()) {
+                    probes.respond(http);
+                    return;
+                }
+
+                let Some(client_id) = owner.filter(|_| redis_filter.is_none()) else {
+                    // No connection filter matched, or the connection is not Redis.
+                    passthrough_cache.pass_through(http);
+                    return;
+                };
+
+                let Some(client) = clients.get(&client_id) else {
                     tracing::error!(
                         client_id,
                         "TcpStealerTask failed to find a connected client for a stolen HTTP request, \
@@ -225,6 +347,14 @@ impl TcpStealerTask {
                     return;
                 };
 
+                if client.skips(
+                    http.info().original_destination.port(),
+                    http.info().peer_addr.ip(),
+                ) {
+                    passthrough_cache.pass_through(http);
+                    return;
+                }
+
                 let message = if client.protocol_version.matches(&protocol_version_req) {
                     StealerMessage::StolenHttp(http.steal())
                 } else {
@@ -248,7 +378,15 @@ impl TcpStealerTask {
                 http
             });
         } else {
-            Self::finish_stealing(clients, filters, http, protocol_version_req).await
+            Self::finish_stealing(
+                clients,
+                filters,
+                http,
+                protocol_version_req,
+                conflict_policy,
+                passthrough_cache,
+            )
+            .await
         }
     }
 
@@ -257,18 +395,28 @@ impl TcpStealerTask {
         filters: &HashMap<ClientId, HttpFilter>,
         mut http: RedirectedHttp,
         protocol_version_req: Cow<'static, semver::VersionReq>,
+        conflict_policy: StealConflictPolicy,
+        passthrough_cache: &PassthroughCache,
     ) {
         let mut send_to = None; // the client that will receive the request
         let mut preempted = vec![]; // other clients that could receive the request as well
         let mut blocked_on_protocol = vec![]; // clients that cannot receive the request due to their protocol version
 
+        let port = http.info().original_destination.port();
+        let source = http.info().peer_addr.ip();
         let (parts, body_reader) = http.parts_and_body();
 
-        for (client_id, filter) in filters {
-            if filter.matches(parts, body_reader).not() {
-                continue;
-            }
+        let mut matching = filters
+            .iter()
+            .filter(|(_, filter)| filter.matches(parts, body_reader))
+            .collect::<Vec<_>>();
+        match conflict_policy {
+            StealConflictPolicy::OldestClient => matching.sort_by_key(|(client_id, _)| **client_id),
+            StealConflictPolicy::MostSpecificFilter => matching
+                .sort_by_key(|(client_id, filter)| (Reverse(filter.specificity()), **client_id)),
+        }
 
+        for (client_id, _) in matching {
             let Some(client) = clients.get(client_id) else {
                 tracing::error!(
                     client_id,
@@ -278,6 +426,10 @@ impl TcpStealerTask {
                 continue;
             };
 
+            if client.skips(port, source) {
+                continue;
+            }
+
             if client.protocol_version.matches(&protocol_version_req).not() {
                 blocked_on_protocol.push(client);
             } else if send_to.is_none() {
@@ -319,7 +471,7 @@ impl TcpStealerTask {
                 .send(StealerMessage::StolenHttp(http.steal()))
                 .await;
         } else {
-            http.pass_through();
+            passthrough_cache.pass_through(http);
         }
     }
 
@@ -339,6 +491,10 @@ impl TcpStealerTask {
                 e.insert(Client {
                     message_tx,
                     protocol_version,
+                    full_backlogs: Default::default(),
+                    paused_ports: Default::default(),
+                    source_filters: Default::default(),
+                    draining: false,
                 });
             }
 
@@ -358,8 +514,84 @@ impl TcpStealerTask {
                     .await;
             }
 
+            Command::PortSubscribeConnection(port, filter) => {
+                let Some(client) = self.clients.get(&command.client_id) else {
+                    // The client disconnected after sending the message.
+                    return Ok(());
+                };
+
+                self.subscriptions
+                    .add_connection_filter(command.client_id, port, filter)
+                    .await?;
+
+                let _ = client
+                    .message_tx
+                    .send(StealerMessage::PortSubscribed(port))
+                    .await;
+            }
+
             Command::PortUnsubscribe(port) => {
                 self.subscriptions.remove(command.client_id, port);
+
+                if let Some(client) = self.clients.get_mut(&command.client_id) {
+                    client.full_backlogs.remove(&port);
+                    client.paused_ports.remove(&port);
+                    client.source_filters.remove(&port);
+                }
+            }
+
+            Command::PortBacklogFull(port, overflow) => {
+                let Some(client) = self.clients.get_mut(&command.client_id) else {
+                    // The client disconnected after sending the message.
+                    return Ok(());
+                };
+
+                match overflow {
+                    Some(overflow) => {
+                        client.full_backlogs.insert(port, overflow);
+                    }
+                    None => {
+                        client.full_backlogs.remove(&port);
+                    }
+                }
+            }
+
+            Command::PortPause(port, paused) => {
+                let Some(client) = self.clients.get_mut(&command.client_id) else {
+                    // The client disconnected after sending the message.
+                    return Ok(());
+                };
+
+                if paused {
+                    client.paused_ports.insert(port);
+                } else {
+                    client.paused_ports.remove(&port);
+                }
+            }
+
+            Command::Drain => {
+                let Some(client) = self.clients.get_mut(&command.client_id) else {
+                    // The client disconnected after sending the message.
+                    return Ok(());
+                };
+
+                client.draining = true;
+            }
+
+            Command::PortSources(port, filter) => {
+                let Some(client) = self.clients.get_mut(&command.client_id) else {
+                    // The client disconnected after sending the message.
+                    return Ok(());
+                };
+
+                match filter {
+                    Some(filter) => {
+                        client.source_filters.insert(port, filter);
+                    }
+                    None => {
+                        client.source_filters.remove(&port);
+                    }
+                }
             }
         }
 
@@ -396,7 +628,15 @@ impl TcpStealerTask {
         };
 
         let protocol_version_req = Self::protocol_version_req_http(subscription, &http);
-        Self::finish_stealing(&self.clients, filters, http, protocol_version_req).await;
+        Self::finish_stealing(
+            &self.clients,
+            filters,
+            http,
+            protocol_version_req,
+            self.conflict_policy,
+            &self.passthrough_cache,
+        )
+        .await;
     }
 }
 
@@ -413,4 +653,26 @@ impl fmt::Debug for TcpStealerTask {
 struct Client {
     message_tx: mpsc::Sender<StealerMessage>,
     protocol_version: ClientProtocolVersion,
+    /// Ports on which the client's backlog of stolen connections is full, see
+    /// [`Command::PortBacklogFull`].
+    full_backlogs: HashMap<Port, BacklogOverflow>,
+    /// Ports paused by the client, see [`Command::PortPause`].
+    paused_ports: HashSet<Port>,
+    /// Source addresses the client steals from, see [`Command::PortSources`].
+    source_filters: HashMap<Port, SourceFilter>,
+    /// Whether the agent is shutting down, see [`Command::Drain`].
+    draining: bool,
+}
+
+impl Client {
+    /// Whether traffic from the given source to the given [`Port`] should not be stolen by this
+    /// client, because the port is paused, the source is filtered out, or the client is draining.
+    fn skips(&self, port: Port, source: IpAddr) -> bool {
+        self.draining
+            || self.paused_ports.contains(&port)
+            || self
+                .source_filters
+                .get(&port)
+                .is_some_and(|filter| filter.matches(source).not())
+    }
 }
@@ -10,11 +10,12 @@ use bytes::{Buf, Bytes};
 use futures::StreamExt;
 use http_body_util::{BodyExt, Full, StreamBody};
 use hyper::body::SizeHint;
+use mirrord_agent_env::steal_conflict::StealConflictPolicy;
 use mirrord_protocol::{
     DaemonMessage, LogLevel,
     tcp::{
         DaemonTcp, Filter, HttpBodyFilter, HttpFilter, IncomingTrafficTransportType, JsonPathQuery,
-        StealType,
+        LayerTcpSteal, StealType,
     },
 };
 use mirrord_tls_util::MaybeTls;
@@ -28,7 +29,7 @@ use tokio_stream::wrappers::ReceiverStream;
 use tokio_util::sync::CancellationToken;
 use utils::{StealingClient, TestBody, TestHttpKind, TestRequest, TestTcpProtocol, WithSizeHint};
 
-use super::{StealerCommand, TcpStealerTask};
+use super::{PassthroughCache, ProbeResponder, StealerCommand, TcpStealerTask};
 use crate::{
     incoming::{
         RedirectorTask, RedirectorTaskConfig,
@@ -61,6 +62,7 @@ async fn request_upgrade(
         TestTcpProtocol::ServerTalks
     )]
     upgraded_protocol: TestTcpProtocol,
+    #[values("1.19.4", "1.33.0")] protocol_version: &str,
 ) {
     let mut setup = TestSetup::new_http(http_kind, RedirectorTaskConfig::from_env()).await;
 
@@ -78,7 +80,7 @@ async fn request_upgrade(
     let mut stealing_client = StealingClient::new(
         0,
         setup.stealer_tx.clone(),
-        "1.19.4",
+        protocol_version,
         StealType::FilteredHttpEx(
             setup.original_server.local_addr().unwrap().port(),
             HttpFilter::Header(Filter::new(format!("{}: 0", TestRequest::USER_ID_HEADER)).unwrap()),
@@ -176,12 +178,67 @@ async fn http_with_unfiltered_subscription(
     );
 }
 
+/// Verifies that probe requests are not stolen with an unfiltered subscription, and that the last
+/// response of the original destination is used when it stops responding.
+#[rstest]
+#[timeout(Duration::from_secs(5))]
+#[tokio::test]
+async fn probes_with_unfiltered_subscription(
+    #[values(TestHttpKind::Http1, TestHttpKind::Http2)] http_kind: TestHttpKind,
+) {
+    let mut setup = TestSetup::new_with_probes(
+        http_kind.uses_tls(),
+        RedirectorTaskConfig::from_env(),
+        StealConflictPolicy::default(),
+        ProbeResponder::new(vec!["/healthz".into()]),
+    )
+    .await;
+
+    let request = TestRequest {
+        path: "/healthz".into(),
+        id_header: 0,
+        user_header: 0,
+        upgrade: None,
+        kind: http_kind,
+        connector: None,
+        acceptor: None,
+        body: None,
+    };
+
+    let _client = StealingClient::new(
+        0,
+        setup.stealer_tx.clone(),
+        "1.19.4",
+        StealType::All(setup.original_server.local_addr().unwrap().port()),
+        setup.stealer_status.clone(),
+    )
+    .await;
+    let conn = setup
+        .conn_tx
+        .make_connection(setup.original_server.local_addr().unwrap())
+        .await;
+    let mut sender = request.make_connection(conn).await;
+
+    tokio::join!(request.send(&mut sender, 7), async {
+        let (stream, _) = setup.original_server.accept().await.unwrap();
+        request.accept(stream, 7).await;
+    });
+
+    // The original destination no longer accepts connections.
+    request.send(&mut sender, 7).await;
+}
+
 /// Verifies stealing and passthrough of TCP connections.
 #[rstest]
 #[timeout(Duration::from_secs(5))]
 #[tokio::test]
 async fn tcp_stealing(#[values(false, true)] with_tls: bool, #[values(false, true)] stolen: bool) {
-    let mut setup = TestSetup::new_tcp(with_tls, RedirectorTaskConfig::from_env()).await;
+    let mut setup = TestSetup::new_tcp(
+        with_tls,
+        RedirectorTaskConfig::from_env(),
+        StealConflictPolicy::default(),
+    )
+    .await;
 
     let steal_type = if stolen {
         StealType::All(setup.original_server.local_addr().unwrap().port())
@@ -253,13 +310,70 @@ async fn tcp_stealing(#[values(false, true)] with_tls: bool, #[values(false, tru
     );
 }
 
+/// Verifies that connections to a port paused with [`LayerTcpSteal::PortPause`] are passed
+/// through to their original destination, and stolen again after [`LayerTcpSteal::PortResume`].
+#[rstest]
+#[timeout(Duration::from_secs(5))]
+#[tokio::test]
+async fn paused_port() {
+    let mut setup = TestSetup::new_tcp(
+        false,
+        RedirectorTaskConfig::from_env(),
+        StealConflictPolicy::default(),
+    )
+    .await;
+    let port = setup.original_server.local_addr().unwrap().port();
+
+    let mut client = StealingClient::new(
+        0,
+        setup.stealer_tx.clone(),
+        "1.43.0",
+        StealType::All(port),
+        setup.stealer_status.clone(),
+    )
+    .await;
+
+    client.send(LayerTcpSteal::PortPause(port)).await;
+    let conn = setup
+        .conn_tx
+        .make_connection(setup.original_server.local_addr().unwrap())
+        .await;
+    tokio::join!(
+        TestTcpProtocol::Echo.run(MaybeTls::NoTls(conn), false),
+        async {
+            let (conn, _) = setup.original_server.accept().await.unwrap();
+            TestTcpProtocol::Echo.run(MaybeTls::NoTls(conn), true).await;
+        },
+    );
+
+    client.send(LayerTcpSteal::PortResume(port)).await;
+    let conn = setup
+        .conn_tx
+        .make_connection(setup.original_server.local_addr().unwrap())
+        .await;
+    tokio::join!(
+        TestTcpProtocol::Echo.run(MaybeTls::NoTls(conn), false),
+        async {
+            let conn = client.expect_connection().await;
+            client
+                .expect_tcp(conn.connection.connection_id, TestTcpProtocol::Echo)
+                .await;
+        },
+    );
+}
+
 /// Verifies scenario where the client cannot steal a TLS connection,
 /// because their mirrord-protocol version is too low.
 #[rstest]
 #[timeout(Duration::from_secs(5))]
 #[tokio::test]
 async fn tls_protocol_version_check() {
-    let mut setup = TestSetup::new_tcp(true, RedirectorTaskConfig::from_env()).await;
+    let mut setup = TestSetup::new_tcp(
+        true,
+        RedirectorTaskConfig::from_env(),
+        StealConflictPolicy::default(),
+    )
+    .await;
 
     let mut client = StealingClient::new(
         0,
@@ -294,6 +408,9 @@ async fn tls_protocol_version_check() {
 }
 
 /// Verifies scenario where a request matches multiple filters.
+///
+/// The last client has the most specific filter, so the request goes to the first or the last
+/// client, depending on the [`StealConflictPolicy`].
 #[rstest]
 #[timeout(Duration::from_secs(5))]
 #[tokio::test]
@@ -307,8 +424,18 @@ async fn multiple_matching_filters(
         TestHttpKind::Http2NoAlpn
     )]
     http_kind: TestHttpKind,
+    #[values(
+        StealConflictPolicy::OldestClient,
+        StealConflictPolicy::MostSpecificFilter
+    )]
+    conflict_policy: StealConflictPolicy,
 ) {
-    let mut setup = TestSetup::new_http(http_kind, RedirectorTaskConfig::from_env()).await;
+    let mut setup = TestSetup::new_tcp(
+        http_kind.uses_tls(),
+        RedirectorTaskConfig::from_env(),
+        conflict_policy,
+    )
+    .await;
 
     let request = TestRequest {
         path: "/api/v1".into(),
@@ -323,13 +450,26 @@ async fn multiple_matching_filters(
 
     let clients = futures::stream::iter(0..3)
         .then(|id| {
+            let path_filter = HttpFilter::Path(Filter::new("/api/v1".into()).unwrap());
+            let filter = if id == 2 {
+                HttpFilter::Composite {
+                    all: true,
+                    filters: vec![
+                        path_filter,
+                        HttpFilter::Header(Filter::new("^user-id: 0$".into()).unwrap()),
+                    ],
+                }
+            } else {
+                path_filter
+            };
+
             StealingClient::new(
                 id,
                 setup.stealer_tx.clone(),
                 "1.19.4",
                 StealType::FilteredHttpEx(
                     setup.original_server.local_addr().unwrap().port(),
-                    HttpFilter::Path(Filter::new("/api/v1".into()).unwrap()),
+                    filter,
                 ),
                 setup.stealer_status.clone(),
             )
@@ -347,9 +487,9 @@ async fn multiple_matching_filters(
     });
 
     let mut logs = 0;
-    let mut requests = 0;
+    let mut stolen_by = vec![];
 
-    for mut client in clients {
+    for (id, mut client) in clients.into_iter().enumerate() {
         match client.recv().await {
             DaemonMessage::LogMessage(log) => {
                 assert_eq!(log.level, LogLevel::Warn);
@@ -357,14 +497,18 @@ async fn multiple_matching_filters(
                 logs += 1;
             }
             DaemonMessage::TcpSteal(DaemonTcp::HttpRequestChunked(..)) => {
-                requests += 1;
+                stolen_by.push(id);
             }
             other => panic!("unexpected message: {other:?}"),
         }
     }
 
+    let expected_owner = match conflict_policy {
+        StealConflictPolicy::OldestClient => 0,
+        StealConflictPolicy::MostSpecificFilter => 2,
+    };
     assert_eq!(logs, 2);
-    assert_eq!(requests, 1);
+    assert_eq!(stolen_by, [expected_owner]);
 }
 
 /// Verifies scenario where we have multiple filtered subscriptions.
@@ -464,6 +608,8 @@ async fn header_injection(
         http_kind,
         RedirectorTaskConfig {
             inject_headers: true,
+            tcp_keepalive: None,
+            capture_stolen: None,
         },
     )
     .await;
@@ -804,6 +950,109 @@ async fn body_filters_pass(
     );
 }
 
+/// Verifies that body filters are matched only against a preview of the bodies larger than the
+/// agent's `MAX_BODY_BUFFER_SIZE` (64KiB by default), and that the whole body is delivered
+/// either way.
+///
+/// A JSON filter cannot parse a truncated preview, so it does not match a larger body, and its
+/// negation does.
+#[rstest]
+#[tokio::test(flavor = "current_thread")]
+#[timeout(Duration::from_secs(5))]
+async fn body_filters_preview(
+    #[values(TestHttpKind::Http1, TestHttpKind::Http2)] http_kind: TestHttpKind,
+    #[values(false, true)] negated: bool,
+    #[values(60 * 1024, 128 * 1024)] payload_len: usize,
+    #[values(SizeHintType::Missing, SizeHintType::Set)] size_hint: SizeHintType,
+) {
+    let mut setup = TestSetup::new_http(http_kind, RedirectorTaskConfig::from_env()).await;
+
+    let payload = Bytes::from(
+        json!({
+            "pass": true,
+            "payload": Alphanumeric.sample_string(&mut rand::rng(), payload_len)
+        })
+        .to_string(),
+    );
+    let above_limit = payload.len() > 64 * 1024;
+    let stolen = above_limit == negated;
+
+    let payload_2 = payload.clone();
+
+    let request = TestRequest {
+        path: "/".into(),
+        id_header: 0,
+        user_header: 1,
+        upgrade: None,
+        kind: http_kind,
+        connector: None,
+        acceptor: None,
+        body: Some(TestBody::new(
+            move || {
+                WithSizeHint::new(
+                    Full::new(payload.clone()).map_err(|_| unreachable!()),
+                    size_hint.hint(payload.len() as u64),
+                )
+            },
+            move |_parts, mut body| {
+                let mut remaining = payload_2.clone();
+                Box::pin(async move {
+                    while let Some(frame) = body.frame().await {
+                        let frame = frame
+                            .expect("invalid frame")
+                            .into_data()
+                            .expect("received non-data frame");
+
+                        assert!(remaining.len() >= frame.len());
+                        assert_eq!(&remaining[..frame.len()], &frame[..]);
+                        remaining.advance(frame.len());
+                    }
+                    assert!(remaining.is_empty());
+                })
+            },
+        )),
+    };
+
+    let filter = HttpFilter::Body(HttpBodyFilter::Json {
+        query: JsonPathQuery::new_unchecked("$.pass".into()),
+        matches: Filter::new("true".into()).unwrap(),
+    });
+    let filter = if negated {
+        HttpFilter::Not(Box::new(filter))
+    } else {
+        filter
+    };
+
+    let mut client = StealingClient::new(
+        0,
+        setup.stealer_tx.clone(),
+        "1.22.1",
+        StealType::FilteredHttpEx(setup.original_server.local_addr().unwrap().port(), filter),
+        setup.stealer_status.clone(),
+    )
+    .await;
+
+    let conn = setup
+        .conn_tx
+        .make_connection(setup.original_server.local_addr().unwrap())
+        .await;
+
+    tokio::join!(
+        async {
+            if stolen {
+                client.expect_request(&request).await;
+            } else {
+                let (stream, _) = setup.original_server.accept().await.unwrap();
+                request.accept(stream, 1).await;
+            }
+        },
+        async {
+            let mut sender = request.make_connection(conn).await;
+            request.send(&mut sender, if stolen { 0 } else { 1 }).await;
+        }
+    );
+}
+
 struct TestSetup {
     /// Simulates the app that would be running on the cluster.
     original_server: TcpListener,
@@ -816,10 +1065,34 @@ struct TestSetup {
 
 impl TestSetup {
     async fn new_http(http_kind: TestHttpKind, redirector_config: RedirectorTaskConfig) -> Self {
-        Self::new_tcp(http_kind.uses_tls(), redirector_config).await
+        Self::new_tcp(
+            http_kind.uses_tls(),
+            redirector_config,
+            StealConflictPolicy::default(),
+        )
+        .await
     }
 
-    async fn new_tcp(with_tls: bool, redirector_config: RedirectorTaskConfig) -> Self {
+    async fn new_tcp(
+        with_tls: bool,
+        redirector_config: RedirectorTaskConfig,
+        conflict_policy: StealConflictPolicy,
+    ) -> Self {
+        Self::new_with_probes(
+            with_tls,
+            redirector_config,
+            conflict_policy,
+            ProbeResponder::default(),
+        )
+        .await
+    }
+
+    async fn new_with_probes(
+        with_tls: bool,
+        redirector_config: RedirectorTaskConfig,
+        conflict_policy: StealConflictPolicy,
+        probes: ProbeResponder,
+    ) -> Self {
         let original_server = TcpListener::bind("127.0.0.1:0").await.unwrap();
         let original_destination = original_server.local_addr().unwrap();
         let tls_setup = if with_tls {
@@ -837,7 +1110,13 @@ impl TestSetup {
             redirector_config,
         );
         let (stealer_tx, stealer_rx) = mpsc::channel(8);
-        let stealer_task = TcpStealerTask::new(stealer_rx, handle);
+        let stealer_task = TcpStealerTask::new(
+            stealer_rx,
+            handle,
+            conflict_policy,
+            probes,
+            PassthroughCache::default(),
+        );
         tokio::spawn(redirector.run());
 
         let local_bg_task_runtime = BgTaskRuntime::spawn(None).await.unwrap();
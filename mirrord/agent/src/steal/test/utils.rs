@@ -24,7 +24,7 @@ use mirrord_protocol::{
         HTTP_CHUNKED_REQUEST_V2_VERSION, HTTP_CHUNKED_RESPONSE_VERSION, HttpRequestMetadata,
         HttpResponse, IncomingTrafficTransportType, InternalHttpBodyNew, InternalHttpRequest,
         InternalHttpResponse, LayerTcpSteal, MODE_AGNOSTIC_HTTP_REQUESTS, NewTcpConnectionV2,
-        StealType, TcpClose, TcpData,
+        StealType, TCP_SHUTDOWN_WRITE_VERSION, TcpClose, TcpData, TcpShutdownWrite,
     },
 };
 use mirrord_tls_util::MaybeTls;
@@ -38,6 +38,7 @@ use tokio_rustls::{TlsAcceptor, TlsConnector, TlsStream};
 use tokio_stream::wrappers::ReceiverStream;
 
 use crate::{
+    audit::AuditLog,
     http::{
         HttpVersion, body::RolledBackBody, extract_requests::ExtractedRequests, sender::HttpSender,
     },
@@ -616,9 +617,15 @@ impl StealingClient {
         let protocol_version = protocol_version.parse::<ClientProtocolVersion>().unwrap();
         assert!(protocol_version.matches(&HTTP_CHUNKED_RESPONSE_VERSION));
 
-        let mut api = TcpStealerApi::new(id, protocol_version.clone(), command_tx, stealer_status)
-            .await
-            .unwrap();
+        let mut api = TcpStealerApi::new(
+            id,
+            protocol_version.clone(),
+            command_tx,
+            stealer_status,
+            AuditLog::default(),
+        )
+        .await
+        .unwrap();
         api.handle_client_message(LayerTcpSteal::PortSubscribe(steal_type.clone()))
             .await
             .unwrap();
@@ -727,8 +734,10 @@ impl StealingClient {
                     self.protocol_version
                         .matches(&HTTP_CHUNKED_REQUEST_V2_VERSION)
                 );
-                let HttpRequestMetadata::V1 { destination, .. } = &request.metadata;
-                assert_eq!(destination.port(), self.steal_type.get_port(),);
+                assert_eq!(
+                    request.metadata.destination().port(),
+                    self.steal_type.get_port(),
+                );
                 request
             }
             other => panic!(
@@ -873,24 +882,9 @@ impl StealingClient {
                         .unwrap();
                 }
 
-                match self.api.recv().await.unwrap() {
-                    DaemonMessage::TcpSteal(DaemonTcp::Data(TcpData {
-                        bytes,
-                        connection_id,
-                    })) => {
-                        assert_eq!(connection_id, expect_connection_id);
-                        assert!(bytes.is_empty());
-                    }
-                    other => panic!("unexpected message: {other:?}"),
-                };
-
-                self.api
-                    .handle_client_message(LayerTcpSteal::Data(TcpData {
-                        connection_id: expect_connection_id,
-                        bytes: Default::default(),
-                    }))
-                    .await
-                    .unwrap();
+                self.expect_shutdown_write(expect_connection_id).await;
+
+                self.shutdown_write(expect_connection_id).await;
 
                 assert_eq!(
                     self.api.recv().await.unwrap(),
@@ -901,13 +895,7 @@ impl StealingClient {
             }
 
             TestTcpProtocol::ClientTalks => {
-                self.api
-                    .handle_client_message(LayerTcpSteal::Data(TcpData {
-                        connection_id: expect_connection_id,
-                        bytes: Default::default(),
-                    }))
-                    .await
-                    .unwrap();
+                self.shutdown_write(expect_connection_id).await;
 
                 while expected != got_bytes {
                     match self.api.recv().await.unwrap() {
@@ -923,16 +911,7 @@ impl StealingClient {
                     }
                 }
 
-                match self.api.recv().await.unwrap() {
-                    DaemonMessage::TcpSteal(DaemonTcp::Data(TcpData {
-                        bytes,
-                        connection_id,
-                    })) => {
-                        assert_eq!(connection_id, expect_connection_id);
-                        assert!(bytes.is_empty());
-                    }
-                    other => panic!("unexpected message: {other:?}"),
-                };
+                self.expect_shutdown_write(expect_connection_id).await;
 
                 assert_eq!(
                     self.api.recv().await.unwrap(),
@@ -943,13 +922,7 @@ impl StealingClient {
             }
 
             TestTcpProtocol::ServerTalks => {
-                assert_eq!(
-                    self.api.recv().await.unwrap(),
-                    DaemonMessage::TcpSteal(DaemonTcp::Data(TcpData {
-                        connection_id: expect_connection_id,
-                        bytes: Default::default(),
-                    })),
-                );
+                self.expect_shutdown_write(expect_connection_id).await;
 
                 for _ in 0..3 {
                     self.api
@@ -961,13 +934,7 @@ impl StealingClient {
                         .unwrap();
                 }
 
-                self.api
-                    .handle_client_message(LayerTcpSteal::Data(TcpData {
-                        connection_id: expect_connection_id,
-                        bytes: Default::default(),
-                    }))
-                    .await
-                    .unwrap();
+                self.shutdown_write(expect_connection_id).await;
 
                 assert_eq!(
                     self.api.recv().await.unwrap(),
@@ -979,9 +946,48 @@ impl StealingClient {
         }
     }
 
+    /// Expects the agent to notify about the remote peer shutting down its writing half, in the
+    /// way appropriate for the client's protocol version.
+    async fn expect_shutdown_write(&mut self, expect_connection_id: ConnectionId) {
+        let expected = if self.protocol_version.matches(&TCP_SHUTDOWN_WRITE_VERSION) {
+            DaemonTcp::ShutdownWrite(TcpShutdownWrite {
+                connection_id: expect_connection_id,
+            })
+        } else {
+            DaemonTcp::Data(TcpData {
+                connection_id: expect_connection_id,
+                bytes: Default::default(),
+            })
+        };
+
+        assert_eq!(
+            self.api.recv().await.unwrap(),
+            DaemonMessage::TcpSteal(expected)
+        );
+    }
+
+    /// Shuts down the client's writing half, in the way appropriate for the client's protocol
+    /// version.
+    async fn shutdown_write(&mut self, connection_id: ConnectionId) {
+        let message = if self.protocol_version.matches(&TCP_SHUTDOWN_WRITE_VERSION) {
+            LayerTcpSteal::ShutdownWrite(TcpShutdownWrite { connection_id })
+        } else {
+            LayerTcpSteal::Data(TcpData {
+                connection_id,
+                bytes: Default::default(),
+            })
+        };
+
+        self.api.handle_client_message(message).await.unwrap();
+    }
+
     pub async fn recv(&mut self) -> DaemonMessage {
         self.api.recv().await.unwrap()
     }
+
+    pub async fn send(&mut self, message: LayerTcpSteal) {
+        self.api.handle_client_message(message).await.unwrap();
+    }
 }
 
 pub struct WithSizeHint<B> {
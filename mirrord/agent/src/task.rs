@@ -12,8 +12,9 @@ use crate::{
 };
 
 pub(super) mod status;
+pub(super) mod supervisor;
 
-/// Runtime for the agent background tasks, such as: `BackgroundTask<SnifferCommand>`,
+/// Runtime for the agent background tasks, such as: the `RedirectorTask`,
 /// `BackgroundTask<StealerCommand>`, `BackgroundTask<DnsCommand>`.
 ///
 /// Use [`BgTaskRuntime::spawn`] to create a new [`tokio::runtime::Runtime`], and you can spawn
@@ -21,7 +22,7 @@ pub(super) mod status;
 ///
 /// **Attention**: Keep the runtime alive! In the [`Drop`] impl we call
 /// [`Notify::notify_one`] which will end the `BackgroundTask`. If you see an error like
-/// `BackgroundTaskFailed { task: "TcpSnifferTask", error: BgTaskPanicked }`, this means that
+/// `BackgroundTaskFailed { task: "TcpStealerTask", error: BgTaskPanicked }`, this means that
 /// the runtime was dropped before the `BackgroundTask` could start.
 ///
 /// Because of the custom [`Drop`] implementation, this struct should not implement [`Clone`].
@@ -0,0 +1,246 @@
+use std::{
+    error::Error,
+    future::Future,
+    sync::{Arc, OnceLock},
+};
+
+use thiserror::Error;
+use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::Level;
+
+use super::status::{BgTaskStatus, IntoStatus};
+use crate::error::AgentError;
+
+/// What to do when a supervised task fails or panics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RestartPolicy {
+    /// The task is not restarted.
+    Never,
+
+    /// The task is started again after `backoff`, at most `max_restarts` times.
+    OnFailure {
+        max_restarts: u32,
+        backoff: Duration,
+    },
+}
+
+/// Whether the agent can keep working without a supervised task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Criticality {
+    /// When the task fails for good (after using up its [`RestartPolicy`]), the agent notifies
+    /// the clients and shuts down, see [`Supervisor::critical_failure`].
+    Critical,
+
+    /// When the task fails for good, we only log the error.
+    NonCritical,
+}
+
+/// Why a supervised task failed.
+#[derive(Error, Debug, Clone)]
+pub(crate) enum SupervisedTaskError {
+    #[error("task panicked")]
+    Panicked,
+
+    #[error(transparent)]
+    Failed(Arc<dyn Error + Send + Sync>),
+}
+
+/// Spawns the long-lived agent tasks with a name, a [`RestartPolicy`] and a [`Criticality`].
+///
+/// Tasks are spawned on the current [`tokio::runtime`] (enter the [`super::BgTaskRuntime`]
+/// before spawning background tasks). A panic in a task is caught and handled like an error.
+///
+/// When a [`Criticality::Critical`] task fails for good, [`Supervisor::critical_failure`]
+/// resolves, so that the client connections and the main agent loop can shut down cleanly,
+/// instead of leaving the agent half-working.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Supervisor {
+    /// Cancelled when [`Supervisor::failure`] is set.
+    failed: CancellationToken,
+
+    /// First critical task that failed for good, with its error.
+    failure: Arc<OnceLock<(&'static str, SupervisedTaskError)>>,
+}
+
+impl Supervisor {
+    /// Spawns a task that is never restarted.
+    pub(crate) fn spawn<Fut, E>(
+        &self,
+        name: &'static str,
+        criticality: Criticality,
+        future: Fut,
+    ) -> BgTaskStatus
+    where
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<Box<dyn Error + Send + Sync>> + Send + 'static,
+    {
+        let mut future = Some(future);
+        self.spawn_restartable(name, criticality, RestartPolicy::Never, move || {
+            future
+                .take()
+                .expect("tasks with `RestartPolicy::Never` are started only once")
+        })
+    }
+
+    /// Spawns a task, using `make` to start it again according to the `restart` policy.
+    ///
+    /// The returned [`BgTaskStatus`] resolves when the task finishes successfully, or fails for
+    /// good.
+    pub(crate) fn spawn_restartable<F, Fut, E>(
+        &self,
+        name: &'static str,
+        criticality: Criticality,
+        restart: RestartPolicy,
+        mut make: F,
+    ) -> BgTaskStatus
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<Box<dyn Error + Send + Sync>> + Send + 'static,
+    {
+        let supervisor = self.clone();
+
+        tokio::spawn(async move {
+            let mut restarts = 0;
+
+            loop {
+                let error = match tokio::spawn(make()).await {
+                    Ok(Ok(())) => {
+                        tracing::debug!(task = name, "Supervised task finished");
+                        return Ok(());
+                    }
+                    Ok(Err(error)) => {
+                        let error: Box<dyn Error + Send + Sync> = error.into();
+                        SupervisedTaskError::Failed(error.into())
+                    }
+                    Err(error) if error.is_panic() => SupervisedTaskError::Panicked,
+                    Err(error) => SupervisedTaskError::Failed(Arc::new(error)),
+                };
+
+                if let RestartPolicy::OnFailure {
+                    max_restarts,
+                    backoff,
+                } = restart
+                    && restarts < max_restarts
+                {
+                    restarts += 1;
+                    tracing::warn!(
+                        task = name,
+                        %error,
+                        restarts,
+                        max_restarts,
+                        "Supervised task failed, restarting",
+                    );
+                    tokio::time::sleep(backoff).await;
+                    continue;
+                }
+
+                tracing::error!(task = name, %error, ?criticality, "Supervised task failed");
+                if criticality == Criticality::Critical {
+                    supervisor.escalate(name, error.clone());
+                }
+
+                break Err(error);
+            }
+        })
+        .into_status(name)
+    }
+
+    /// Records the failure of a critical task, and wakes up everyone waiting in
+    /// [`Supervisor::critical_failure`].
+    #[tracing::instrument(level = Level::DEBUG, skip(self))]
+    fn escalate(&self, task: &'static str, error: SupervisedTaskError) {
+        let _ = self.failure.set((task, error));
+        self.failed.cancel();
+    }
+
+    /// Returns the failure of a critical task, if there was one.
+    pub(crate) fn failure(&self) -> Option<AgentError> {
+        self.failure
+            .get()
+            .map(|(task, error)| AgentError::BackgroundTaskFailed {
+                task: *task,
+                error: Arc::new(error.clone()),
+            })
+    }
+
+    /// Resolves when a critical task fails for good.
+    pub(crate) async fn critical_failure(&self) -> AgentError {
+        self.failed.cancelled().await;
+        self.failure()
+            .expect("failure is set before the token is cancelled")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn restarts_until_success() {
+        let supervisor = Supervisor::default();
+        let attempts = Arc::new(AtomicU32::new(0));
+
+        let status = supervisor.spawn_restartable(
+            "flaky",
+            Criticality::Critical,
+            RestartPolicy::OnFailure {
+                max_restarts: 2,
+                backoff: Duration::ZERO,
+            },
+            {
+                let attempts = attempts.clone();
+                move || {
+                    let attempt = attempts.fetch_add(1, Ordering::Relaxed);
+                    async move {
+                        if attempt < 2 {
+                            Err(std::io::Error::other("flaky"))
+                        } else {
+                            Ok(())
+                        }
+                    }
+                }
+            },
+        );
+
+        status.wait().await.unwrap();
+        assert_eq!(attempts.load(Ordering::Relaxed), 3);
+        assert!(supervisor.failure().is_none());
+    }
+
+    async fn panicking() -> Result<(), std::io::Error> {
+        panic!("boom")
+    }
+
+    #[tokio::test]
+    async fn critical_panic_escalates() {
+        let supervisor = Supervisor::default();
+
+        let status = supervisor.spawn("panicking", Criticality::Critical, panicking());
+
+        let error = supervisor.critical_failure().await;
+        assert!(matches!(
+            error,
+            AgentError::BackgroundTaskFailed {
+                task: "panicking",
+                ..
+            }
+        ));
+        status.wait().await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn non_critical_failure_does_not_escalate() {
+        let supervisor = Supervisor::default();
+
+        let status = supervisor.spawn("optional", Criticality::NonCritical, async {
+            Err(std::io::Error::other("failed"))
+        });
+
+        status.wait().await.unwrap_err();
+        assert!(supervisor.failure().is_none());
+    }
+}
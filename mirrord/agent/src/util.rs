@@ -11,6 +11,7 @@ pub mod error;
 pub mod path_resolver;
 pub mod protocol_version;
 pub mod rolledback_stream;
+pub mod ulid;
 
 /// Id of an agent's client. Each new client connection is assigned with a unique id.
 pub type ClientId = u32;
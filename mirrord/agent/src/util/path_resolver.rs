@@ -13,6 +13,11 @@ use tracing::Level;
 #[derive(Debug, Clone)]
 pub struct InTargetPathResolver {
     root: PathBuf,
+    /// `/proc/{target_pid}`, the target's process directory.
+    proc_self: Option<PathBuf>,
+    /// Whether `/proc/self` and `/proc/thread-self` are resolved to [`Self::proc_self`], see
+    /// [`Self::remap_proc_self`].
+    remap_proc_self: bool,
 }
 
 impl InTargetPathResolver {
@@ -22,15 +27,38 @@ impl InTargetPathResolver {
 
         Self {
             root: PathBuf::from(root),
+            proc_self: Some(PathBuf::from(format!("/proc/{target_pid}"))),
+            remap_proc_self: false,
         }
     }
 
+    /// Resolves `/proc/self` and `/proc/thread-self` to the target's process directory, which
+    /// would otherwise point to the agent (or nowhere, as the agent is not in the target's pid
+    /// namespace).
+    ///
+    /// Enabled with the client's `feature.fs.procfs`.
+    pub fn remap_proc_self(mut self, remap: bool) -> Self {
+        self.remap_proc_self = remap;
+        self
+    }
+
     pub fn root_path(&self) -> &Path {
         &self.root
     }
 
+    /// Returns the working directory of the target process, as seen in the target container.
+    pub fn target_cwd(&self) -> io::Result<PathBuf> {
+        let proc_self = self.proc_self.as_ref().ok_or(io::ErrorKind::NotFound)?;
+
+        std::fs::read_link(proc_self.join("cwd"))
+    }
+
     #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE))]
     pub fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        if let Some(resolved) = self.resolve_proc_self(path)? {
+            return Ok(resolved);
+        }
+
         let mut temp_path = PathBuf::new();
 
         for component in path.components() {
@@ -82,6 +110,40 @@ impl InTargetPathResolver {
 
         Ok(self.root.join(temp_path))
     }
+
+    /// Resolves paths under `/proc/self` and `/proc/thread-self` to the target's process
+    /// directory.
+    ///
+    /// Returns [`None`] if the path is not one of those.
+    fn resolve_proc_self(&self, path: &Path) -> io::Result<Option<PathBuf>> {
+        let Some(proc_self) = self.proc_self.as_ref().filter(|_| self.remap_proc_self) else {
+            return Ok(None);
+        };
+
+        let Some(relative) = ["/proc/self", "/proc/thread-self"]
+            .into_iter()
+            .find_map(|prefix| path.strip_prefix(prefix).ok())
+        else {
+            return Ok(None);
+        };
+
+        // The target's process directory is outside of its root, don't let the path escape it.
+        if relative
+            .components()
+            .any(|component| matches!(component, Component::ParentDir))
+        {
+            tracing::warn!(?path, "Detected a possible LFI attempt",);
+
+            return Err(io::ErrorKind::NotFound.into());
+        }
+
+        let mut resolved = proc_self.join(relative);
+        if path.as_os_str().as_bytes().ends_with(b"/") {
+            resolved.push("");
+        }
+
+        Ok(Some(resolved))
+    }
 }
 
 #[cfg(test)]
@@ -90,6 +152,64 @@ impl InTargetPathResolver {
     ///
     /// Makes it easy to test with [`tempfile::tempdir`].
     pub fn with_root_path(root: PathBuf) -> Self {
-        Self { root }
+        Self {
+            root,
+            proc_self: None,
+            remap_proc_self: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("/proc/self/cgroup", "/proc/42/cgroup")]
+    #[case("/proc/thread-self/mountinfo", "/proc/42/mountinfo")]
+    #[case("/proc/self", "/proc/42")]
+    #[case("/proc/meminfo", "/proc/42/root/proc/meminfo")]
+    #[case("/proc/selfish", "/proc/42/root/proc/selfish")]
+    fn proc_self(#[case] path: &str, #[case] expected: &str) {
+        let resolver = InTargetPathResolver::new(42).remap_proc_self(true);
+
+        assert_eq!(
+            resolver.resolve(Path::new(path)).unwrap(),
+            Path::new(expected)
+        );
+    }
+
+    /// Without `feature.fs.procfs`, `/proc/self` is resolved in the target's root like any other
+    /// path.
+    #[test]
+    fn proc_self_not_remapped() {
+        let resolver = InTargetPathResolver::new(42);
+
+        assert_eq!(
+            resolver.resolve(Path::new("/proc/self/cgroup")).unwrap(),
+            Path::new("/proc/42/root/proc/self/cgroup")
+        );
+    }
+
+    #[test]
+    fn proc_self_parent_dir() {
+        let resolver = InTargetPathResolver::new(42).remap_proc_self(true);
+
+        let error = resolver
+            .resolve(Path::new("/proc/self/../../etc/shadow"))
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn target_cwd() {
+        let resolver = InTargetPathResolver::new(std::process::id().into());
+
+        assert_eq!(
+            resolver.target_cwd().unwrap(),
+            std::env::current_dir().unwrap()
+        );
     }
 }
@@ -31,6 +31,11 @@ where
             prefix: prefix.has_remaining().then_some(prefix),
         }
     }
+
+    /// Returns the inner stream and the remaining prefix, if any.
+    pub fn into_parts(self) -> (IO, Option<B>) {
+        (self.stream, self.prefix)
+    }
 }
 
 impl<IO, B> AsyncRead for RolledBackStream<IO, B>
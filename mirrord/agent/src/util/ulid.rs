@@ -0,0 +1,56 @@
+//! Generating [ULIDs](https://github.com/ulid/spec), used as correlation ids of stolen HTTP
+//! requests.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+
+/// Crockford's base32 alphabet.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Generates a new ULID: 48 bits of a millisecond timestamp and 80 random bits, encoded as 26
+/// characters.
+///
+/// ULIDs sort by their creation time, which makes them easy to find in the logs.
+pub fn new_ulid() -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u128;
+    let random = rand::rng().random::<u128>() & ((1 << 80) - 1);
+
+    encode(((timestamp & ((1 << 48) - 1)) << 80) | random)
+}
+
+fn encode(value: u128) -> String {
+    (0..26)
+        .rev()
+        .map(|i| {
+            let index = (value >> (i * 5)) & 0x1f;
+            ALPHABET
+                .get(index as usize)
+                .copied()
+                .map(char::from)
+                .unwrap_or('0')
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{encode, new_ulid};
+
+    #[test]
+    fn encodes_crockford_base32() {
+        assert_eq!(encode(0), "00000000000000000000000000");
+        assert_eq!(encode(u128::MAX), "7ZZZZZZZZZZZZZZZZZZZZZZZZZ");
+        assert_eq!(new_ulid().len(), 26);
+    }
+
+    #[test]
+    fn sorts_by_time() {
+        let first = new_ulid();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        assert!(first < new_ulid());
+    }
+}
@@ -0,0 +1,112 @@
+//! Compares the default tokio IO path with [`UringStream`], on the loop that the agent runs for
+//! every passed-through connection: data copied both ways between two TCP connections.
+//!
+//! Every iteration pushes [`PAYLOAD_SIZE`] bytes through each of the [`CONNECTIONS`] concurrent
+//! connections, and reads them back.
+
+use std::{hint::black_box, sync::Arc, time::Duration};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use mirrord_agent_uring::{Driver, UringStream};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    runtime::Runtime,
+};
+
+const PAYLOAD_SIZE: usize = 256 * 1024;
+
+const CONNECTIONS: [usize; 3] = [1, 64, 512];
+
+/// Connects a client to a relay, which copies the data to an echo server and back.
+///
+/// Returns the client stream, and the relay's streams to the client and to the echo server.
+async fn relay_pair() -> (TcpStream, TcpStream, TcpStream) {
+    let echo = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let echo_address = echo.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = echo.accept().await.unwrap();
+        let (mut read, mut write) = stream.split();
+        let _ = tokio::io::copy(&mut read, &mut write).await;
+    });
+
+    let relay = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let client = TcpStream::connect(relay.local_addr().unwrap())
+        .await
+        .unwrap();
+    let (incoming, _) = relay.accept().await.unwrap();
+    let outgoing = TcpStream::connect(echo_address).await.unwrap();
+
+    (client, incoming, outgoing)
+}
+
+async fn relay<I, O>(mut incoming: I, mut outgoing: O)
+where
+    I: AsyncRead + AsyncWrite + Unpin,
+    O: AsyncRead + AsyncWrite + Unpin,
+{
+    let _ = tokio::io::copy_bidirectional(&mut incoming, &mut outgoing).await;
+}
+
+async fn round_trip(mut client: TcpStream) {
+    let payload = vec![7_u8; PAYLOAD_SIZE];
+    let mut echoed = vec![0_u8; PAYLOAD_SIZE];
+
+    let (mut read, mut write) = client.split();
+    tokio::try_join!(write.write_all(&payload), read.read_exact(&mut echoed)).unwrap();
+    black_box(&echoed);
+}
+
+async fn run(connections: usize, driver: Option<&Arc<Driver>>) {
+    let mut tasks = Vec::with_capacity(connections);
+
+    for _ in 0..connections {
+        let (client, incoming, outgoing) = relay_pair().await;
+        match driver {
+            Some(driver) => {
+                let incoming = UringStream::from_tokio(incoming, driver.clone()).unwrap();
+                tokio::spawn(relay(incoming, outgoing));
+            }
+            None => {
+                tokio::spawn(relay(incoming, outgoing));
+            }
+        }
+
+        tasks.push(tokio::spawn(round_trip(client)));
+    }
+
+    for task in tasks {
+        task.await.unwrap();
+    }
+}
+
+fn copy(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let driver = Driver::global();
+
+    let mut group = c.benchmark_group("copy");
+    group.measurement_time(Duration::from_secs(10));
+
+    for connections in CONNECTIONS {
+        group.throughput(Throughput::Bytes((connections * PAYLOAD_SIZE) as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("tokio", connections),
+            &connections,
+            |b, &connections| b.iter(|| runtime.block_on(run(connections, None))),
+        );
+
+        if let Some(driver) = &driver {
+            group.bench_with_input(
+                BenchmarkId::new("io_uring", connections),
+                &connections,
+                |b, &connections| b.iter(|| runtime.block_on(run(connections, Some(driver)))),
+            );
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, copy);
+criterion_main!(benches);
@@ -0,0 +1,328 @@
+use std::{
+    collections::HashMap,
+    io,
+    ops::Range,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread,
+};
+
+use io_uring::{IoUring, opcode, squeue, types};
+use tokio::sync::oneshot;
+
+use crate::pool::{BufferPool, PooledBuffer};
+
+/// User data of the read operation on [`Driver::wake_fd`].
+const WAKE_ID: u64 = 0;
+
+/// An operation requested from the [`Driver`].
+enum Submission {
+    /// Receive into the whole buffer.
+    Recv {
+        id: u64,
+        fd: Arc<OwnedFd>,
+        buffer: PooledBuffer,
+        tx: oneshot::Sender<Completion>,
+    },
+    /// Send the given range of the buffer.
+    Send {
+        id: u64,
+        fd: Arc<OwnedFd>,
+        buffer: PooledBuffer,
+        range: Range<usize>,
+        tx: oneshot::Sender<Completion>,
+    },
+    /// Cancel the operation with the given id.
+    Cancel(u64),
+}
+
+/// Result of an operation submitted to the [`Driver`].
+#[derive(Debug)]
+pub(crate) struct Completion {
+    /// Result of the operation, as returned by the corresponding syscall (negated errno on
+    /// failure).
+    pub result: i32,
+    /// The buffer used in the operation.
+    pub buffer: PooledBuffer,
+}
+
+impl Completion {
+    /// Converts [`Self::result`] into the number of bytes transferred.
+    pub fn bytes(&self) -> io::Result<usize> {
+        usize::try_from(self.result).map_err(|_| io::Error::from_raw_os_error(-self.result))
+    }
+}
+
+/// An operation in progress in the [`Driver`] thread.
+struct InProgress {
+    /// Keeps the file descriptor open until the operation completes.
+    _fd: Arc<OwnedFd>,
+    buffer: PooledBuffer,
+    tx: oneshot::Sender<Completion>,
+}
+
+/// Runs a single io_uring instance in a dedicated thread.
+///
+/// Operations are queued by the [`UringStream`](crate::UringStream)s, and the thread is woken up
+/// with an eventfd, which it reads in the ring. Results are delivered back through
+/// [`oneshot`] channels.
+pub struct Driver {
+    queue: Mutex<Vec<Submission>>,
+    wake_fd: OwnedFd,
+    next_id: AtomicU64,
+    pool: Arc<BufferPool>,
+}
+
+impl Driver {
+    /// Number of entries in the submission queue.
+    const ENTRIES: u32 = 1024;
+
+    /// Size of the buffers used for reads and writes.
+    pub const BUFFER_SIZE: usize = 64 * 1024;
+
+    /// Maximum number of released buffers kept in the [`BufferPool`].
+    pub const MAX_POOLED_BUFFERS: usize = 1024;
+
+    /// Returns the [`Driver`] shared by the whole process, starting it on the first call.
+    ///
+    /// Returns [`None`] if io_uring is not available, e.g. due to an old kernel or a seccomp
+    /// profile.
+    pub fn global() -> Option<Arc<Self>> {
+        static DRIVER: OnceLock<Option<Arc<Driver>>> = OnceLock::new();
+
+        DRIVER
+            .get_or_init(|| {
+                Self::start()
+                    .inspect_err(|error| {
+                        tracing::warn!(%error, "Failed to start the io_uring driver, using the default IO path");
+                    })
+                    .ok()
+            })
+            .clone()
+    }
+
+    /// Starts a new [`Driver`] thread.
+    pub fn start() -> io::Result<Arc<Self>> {
+        let ring = IoUring::new(Self::ENTRIES)?;
+
+        // SAFETY: we check the returned descriptor, and take ownership of it.
+        let wake_fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+        if wake_fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the descriptor was just created, and is not owned by anything else.
+        let wake_fd = unsafe { OwnedFd::from_raw_fd(wake_fd) };
+
+        let driver = Arc::new(Self {
+            queue: Default::default(),
+            wake_fd,
+            next_id: AtomicU64::new(WAKE_ID + 1),
+            pool: BufferPool::new(Self::BUFFER_SIZE, Self::MAX_POOLED_BUFFERS),
+        });
+
+        let thread_driver = driver.clone();
+        thread::Builder::new()
+            .name("mirrord-io-uring".into())
+            .spawn(move || {
+                if let Err(error) = thread_driver.run(ring) {
+                    tracing::error!(%error, "io_uring driver failed");
+                }
+            })?;
+
+        Ok(driver)
+    }
+
+    /// [`BufferPool`] used for the operations.
+    pub fn pool(&self) -> &Arc<BufferPool> {
+        &self.pool
+    }
+
+    /// Queues a receive into `buffer`.
+    ///
+    /// Returns the id of the operation, which can be used to cancel it.
+    pub(crate) fn recv(
+        &self,
+        fd: Arc<OwnedFd>,
+        buffer: PooledBuffer,
+    ) -> (u64, oneshot::Receiver<Completion>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.submit(Submission::Recv { id, fd, buffer, tx });
+        (id, rx)
+    }
+
+    /// Queues a send of the given range of `buffer`.
+    pub(crate) fn send(
+        &self,
+        fd: Arc<OwnedFd>,
+        buffer: PooledBuffer,
+        range: Range<usize>,
+    ) -> oneshot::Receiver<Completion> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.submit(Submission::Send {
+            id,
+            fd,
+            buffer,
+            range,
+            tx,
+        });
+        rx
+    }
+
+    /// Queues a cancellation of the operation with the given id.
+    pub(crate) fn cancel(&self, id: u64) {
+        self.submit(Submission::Cancel(id));
+    }
+
+    fn submit(&self, submission: Submission) {
+        self.queue
+            .lock()
+            .expect("io_uring driver queue mutex poisoned")
+            .push(submission);
+
+        let value = 1_u64.to_ne_bytes();
+        // SAFETY: the buffer is valid for 8 bytes, as required for eventfd writes.
+        unsafe {
+            libc::write(self.wake_fd.as_raw_fd(), value.as_ptr().cast(), value.len());
+        }
+    }
+
+    /// Runs the driver loop, which only finishes on a fatal ring error.
+    fn run(&self, mut ring: IoUring) -> io::Result<()> {
+        let mut wake_buffer = [0_u8; 8];
+        let mut in_progress: HashMap<u64, InProgress> = HashMap::new();
+
+        let wake_entry = opcode::Read::new(
+            types::Fd(self.wake_fd.as_raw_fd()),
+            wake_buffer.as_mut_ptr(),
+            8,
+        )
+        .build()
+        .user_data(WAKE_ID);
+        Self::push(&mut ring, &wake_entry)?;
+
+        loop {
+            match ring.submit_and_wait(1) {
+                Ok(..) => {}
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            }
+
+            let completed = ring
+                .completion()
+                .map(|entry| (entry.user_data(), entry.result()))
+                .collect::<Vec<_>>();
+
+            for (id, result) in completed {
+                if id == WAKE_ID {
+                    let queued = std::mem::take(
+                        &mut *self
+                            .queue
+                            .lock()
+                            .expect("io_uring driver queue mutex poisoned"),
+                    );
+                    for submission in queued {
+                        self.push_submission(&mut ring, submission, &mut in_progress)?;
+                    }
+                    Self::push(&mut ring, &wake_entry)?;
+                    continue;
+                }
+
+                // Completions of cancellations are not tracked.
+                let Some(operation) = in_progress.remove(&id) else {
+                    continue;
+                };
+
+                let _ = operation.tx.send(Completion {
+                    result,
+                    buffer: operation.buffer,
+                });
+            }
+        }
+    }
+
+    fn push_submission(
+        &self,
+        ring: &mut IoUring,
+        submission: Submission,
+        in_progress: &mut HashMap<u64, InProgress>,
+    ) -> io::Result<()> {
+        match submission {
+            Submission::Recv {
+                id,
+                fd,
+                mut buffer,
+                tx,
+            } => {
+                let entry = opcode::Recv::new(
+                    types::Fd(fd.as_raw_fd()),
+                    buffer.as_mut_ptr(),
+                    u32::try_from(buffer.len()).unwrap_or(u32::MAX),
+                )
+                .build()
+                .user_data(id);
+                Self::push(ring, &entry)?;
+                in_progress.insert(
+                    id,
+                    InProgress {
+                        _fd: fd,
+                        buffer,
+                        tx,
+                    },
+                );
+            }
+
+            Submission::Send {
+                id,
+                fd,
+                buffer,
+                range,
+                tx,
+            } => {
+                let data = &buffer[range];
+                let entry = opcode::Send::new(
+                    types::Fd(fd.as_raw_fd()),
+                    data.as_ptr(),
+                    u32::try_from(data.len()).unwrap_or(u32::MAX),
+                )
+                .build()
+                .user_data(id);
+                Self::push(ring, &entry)?;
+                in_progress.insert(
+                    id,
+                    InProgress {
+                        _fd: fd,
+                        buffer,
+                        tx,
+                    },
+                );
+            }
+
+            Submission::Cancel(id) => {
+                // The operation may have completed already, in which case the cancellation fails
+                // with `ENOENT`, which we ignore.
+                let entry = opcode::AsyncCancel::new(id).build().user_data(u64::MAX);
+                Self::push(ring, &entry)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pushes the entry to the submission queue, submitting the queue first if it is full.
+    fn push(ring: &mut IoUring, entry: &squeue::Entry) -> io::Result<()> {
+        loop {
+            // SAFETY: the buffers used by the entries are kept alive in `InProgress` (or in the
+            // driver loop, for the wake entry) until the operations complete.
+            if unsafe { ring.submission().push(entry) }.is_ok() {
+                return Ok(());
+            }
+
+            ring.submit()?;
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! Experimental io_uring IO path for the mirrord-agent.
+//!
+//! The agent handles every redirected connection with its own tokio task, and at thousands of
+//! concurrent connections the readiness-based reads and writes of these tasks take a lot of CPU.
+//! This crate provides [`UringStream`], a TCP stream that implements the tokio IO traits on top of
+//! a single io_uring instance, shared by all connections (see [`Driver`]). Buffers for the reads
+//! and writes are taken from a [`BufferPool`].
+//!
+//! Used by the agent when built with the `io-uring` feature. Compare the two IO paths with the
+//! `copy` benchmark of this crate (`cargo bench -p mirrord-agent-uring`).
+#![cfg(target_os = "linux")]
+
+mod driver;
+mod pool;
+mod stream;
+
+pub use driver::Driver;
+pub use pool::{BufferPool, PooledBuffer};
+pub use stream::UringStream;
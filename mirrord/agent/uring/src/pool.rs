@@ -0,0 +1,139 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+};
+
+/// A pool of fixed size buffers, reused between the IO operations.
+///
+/// The buffers are allocated lazily, and at most [`BufferPool::max_pooled`] of them are kept when
+/// released.
+pub struct BufferPool {
+    buffer_size: usize,
+    max_pooled: usize,
+    free: Mutex<Vec<Box<[u8]>>>,
+}
+
+impl BufferPool {
+    pub fn new(buffer_size: usize, max_pooled: usize) -> Arc<Self> {
+        Arc::new(Self {
+            buffer_size,
+            max_pooled,
+            free: Default::default(),
+        })
+    }
+
+    /// Size of every buffer in this pool.
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// Maximum number of released buffers kept in this pool.
+    pub fn max_pooled(&self) -> usize {
+        self.max_pooled
+    }
+
+    /// Number of released buffers currently kept in this pool.
+    pub fn pooled(&self) -> usize {
+        self.free.lock().expect("buffer pool mutex poisoned").len()
+    }
+
+    /// Takes a buffer from this pool, or allocates a new one.
+    ///
+    /// The buffer returns to the pool when dropped.
+    pub fn get(self: &Arc<Self>) -> PooledBuffer {
+        let buffer = self
+            .free
+            .lock()
+            .expect("buffer pool mutex poisoned")
+            .pop()
+            .unwrap_or_else(|| vec![0; self.buffer_size].into_boxed_slice());
+
+        PooledBuffer {
+            buffer: Some(buffer),
+            pool: self.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("buffer_size", &self.buffer_size)
+            .field("max_pooled", &self.max_pooled)
+            .field("pooled", &self.pooled())
+            .finish()
+    }
+}
+
+/// A buffer taken from a [`BufferPool`].
+///
+/// The memory of the buffer does not move when this struct is moved, so it can be safely used by
+/// the kernel while an io_uring operation is in progress.
+pub struct PooledBuffer {
+    buffer: Option<Box<[u8]>>,
+    pool: Arc<BufferPool>,
+}
+
+impl Deref for PooledBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.buffer
+            .as_deref()
+            .expect("buffer is present until dropped")
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.buffer
+            .as_deref_mut()
+            .expect("buffer is present until dropped")
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        let Some(buffer) = self.buffer.take() else {
+            return;
+        };
+
+        let mut free = self.pool.free.lock().expect("buffer pool mutex poisoned");
+        if free.len() < self.pool.max_pooled {
+            free.push(buffer);
+        }
+    }
+}
+
+impl fmt::Debug for PooledBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledBuffer")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BufferPool;
+
+    #[test]
+    fn buffers_are_reused() {
+        let pool = BufferPool::new(16, 1);
+
+        let mut first = pool.get();
+        first[0] = 1;
+        let address = first.as_ptr();
+        let second = pool.get();
+        assert_eq!(second.len(), 16);
+
+        drop(first);
+        drop(second);
+        assert_eq!(pool.pooled(), 1);
+
+        let reused = pool.get();
+        assert_eq!(reused.as_ptr(), address);
+        assert_eq!(pool.pooled(), 0);
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    fmt, io,
+    net::TcpStream,
+    ops::{Not, Range},
+    os::fd::{AsRawFd, OwnedFd},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::oneshot,
+};
+
+use crate::{
+    driver::{Completion, Driver},
+    pool::PooledBuffer,
+};
+
+/// A receive in progress.
+struct PendingRecv {
+    id: u64,
+    rx: oneshot::Receiver<Completion>,
+}
+
+/// A send in progress.
+struct PendingSend {
+    range: Range<usize>,
+    rx: oneshot::Receiver<Completion>,
+}
+
+/// A TCP stream that does its IO through the io_uring [`Driver`].
+///
+/// Writes are buffered: [`AsyncWrite::poll_write`] copies the data into a pooled buffer and returns
+/// immediately, the send is awaited on the next write or flush. As with [`std::io::BufWriter`],
+/// errors of a send are reported on the next write or flush.
+pub struct UringStream {
+    fd: Arc<OwnedFd>,
+    driver: Arc<Driver>,
+    /// Received data not yet returned from [`AsyncRead::poll_read`].
+    received: Option<(PooledBuffer, Range<usize>)>,
+    recv: Option<PendingRecv>,
+    send: Option<PendingSend>,
+}
+
+impl UringStream {
+    /// Moves the given stream to the [`Driver`].
+    pub fn new(stream: TcpStream, driver: Arc<Driver>) -> io::Result<Self> {
+        // io_uring reports `EAGAIN` for non-blocking sockets instead of waiting for readiness.
+        stream.set_nonblocking(false)?;
+
+        Ok(Self {
+            fd: Arc::new(stream.into()),
+            driver,
+            received: None,
+            recv: None,
+            send: None,
+        })
+    }
+
+    /// Moves the given tokio stream to the [`Driver`].
+    pub fn from_tokio(stream: tokio::net::TcpStream, driver: Arc<Driver>) -> io::Result<Self> {
+        Self::new(stream.into_std()?, driver)
+    }
+
+    /// Waits for the send in progress, sending the rest of the data if the send was partial.
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while let Some(send) = &mut self.send {
+            let completion = ready!(Pin::new(&mut send.rx).poll(cx)).map_err(|_| driver_gone())?;
+            let range = send.range.clone();
+            self.send = None;
+
+            let sent = completion.bytes()?;
+            if sent == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+
+            let rest = range.start + sent..range.end;
+            if rest.is_empty().not() {
+                self.send = Some(PendingSend {
+                    range: rest.clone(),
+                    rx: self.driver.send(self.fd.clone(), completion.buffer, rest),
+                });
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for UringStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some((received, range)) = &mut this.received {
+                let len = range.len().min(buf.remaining());
+                buf.put_slice(&received[range.start..range.start + len]);
+                range.start += len;
+                if range.is_empty() {
+                    this.received = None;
+                }
+
+                return Poll::Ready(Ok(()));
+            }
+
+            let Some(recv) = &mut this.recv else {
+                let (id, rx) = this.driver.recv(this.fd.clone(), this.driver.pool().get());
+                this.recv = Some(PendingRecv { id, rx });
+                continue;
+            };
+
+            let completion = ready!(Pin::new(&mut recv.rx).poll(cx)).map_err(|_| driver_gone())?;
+            this.recv = None;
+
+            let received = completion.bytes()?;
+            if received == 0 {
+                // EOF.
+                return Poll::Ready(Ok(()));
+            }
+
+            this.received = Some((completion.buffer, 0..received));
+        }
+    }
+}
+
+impl AsyncWrite for UringStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_send(cx))?;
+
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let mut buffer = this.driver.pool().get();
+        let len = buf.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&buf[..len]);
+        this.send = Some(PendingSend {
+            range: 0..len,
+            rx: this.driver.send(this.fd.clone(), buffer, 0..len),
+        });
+
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_send(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_send(cx))?;
+
+        // SAFETY: the descriptor is kept open by `this.fd`.
+        if unsafe { libc::shutdown(this.fd.as_raw_fd(), libc::SHUT_WR) } < 0 {
+            return Poll::Ready(Err(io::Error::last_os_error()));
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for UringStream {
+    fn drop(&mut self) {
+        // A pending receive might never complete, sends are left to finish on their own.
+        if let Some(recv) = self.recv.take() {
+            self.driver.cancel(recv.id);
+        }
+    }
+}
+
+impl fmt::Debug for UringStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UringStream")
+            .field("fd", &self.fd.as_raw_fd())
+            .field("recv_pending", &self.recv.is_some())
+            .field("send_pending", &self.send.is_some())
+            .finish()
+    }
+}
+
+fn driver_gone() -> io::Error {
+    io::Error::other("io_uring driver stopped")
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::UringStream;
+    use crate::Driver;
+
+    #[tokio::test]
+    async fn echo() {
+        let Some(driver) = Driver::global() else {
+            // io_uring is not available in this environment.
+            return;
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = UringStream::from_tokio(stream, driver).unwrap();
+            let mut buffer = vec![0; 3 * Driver::BUFFER_SIZE];
+            stream.read_exact(&mut buffer).await.unwrap();
+            stream.write_all(&buffer).await.unwrap();
+            stream.shutdown().await.unwrap();
+        });
+
+        let data = (0..3 * Driver::BUFFER_SIZE)
+            .map(|i| i as u8)
+            .collect::<Vec<_>>();
+        let mut client = TcpStream::connect(address).await.unwrap();
+        client.write_all(&data).await.unwrap();
+        let mut echoed = Vec::new();
+        client.read_to_end(&mut echoed).await.unwrap();
+
+        assert_eq!(echoed, data);
+        server.await.unwrap();
+    }
+}
@@ -42,6 +42,9 @@ pub enum ExecutionKind {
     PortForward = 3,
     Dump = 4,
     Wizard = 5,
+    Cp = 6,
+    Top = 7,
+    ListPorts = 8,
     Other = 0,
 }
 
@@ -53,6 +56,9 @@ impl From<u32> for ExecutionKind {
             3 => ExecutionKind::PortForward,
             4 => ExecutionKind::Dump,
             5 => ExecutionKind::Wizard,
+            6 => ExecutionKind::Cp,
+            7 => ExecutionKind::Top,
+            8 => ExecutionKind::ListPorts,
             _ => ExecutionKind::Other,
         }
     }
@@ -8,6 +8,15 @@ fn recheck_and_setup_layer_file() {
     #[cfg(target_os = "macos")]
     println!("cargo::rerun-if-env-changed=MIRRORD_LAYER_FILE_MACOS_ARM64");
 
+    println!("cargo::rerun-if-env-changed=MIRRORD_LAYER_FILE_MUSL");
+
+    // The layer built for musl is optional, as it requires a different toolchain.
+    if std::env::var("MIRRORD_LAYER_FILE_MUSL").is_ok()
+        && std::env::var("CARGO_CFG_TARGET_OS").is_ok_and(|t| t.eq("linux"))
+    {
+        println!("cargo::rustc-cfg=musl_layer");
+    }
+
     if std::env::var("MIRRORD_LAYER_FILE").is_err() {
         #[cfg(target_os = "windows")]
         {
@@ -94,6 +103,8 @@ fn build_wizard_frontend() {
 }
 
 fn main() {
+    println!("cargo::rustc-check-cfg=cfg(musl_layer)");
+
     // don't run on clippy
     if std::env::var("CLIPPY_ARGS").is_ok() {
         // stupid hack so we don't need dependencies or anything
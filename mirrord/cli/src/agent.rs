@@ -0,0 +1,97 @@
+//! Implementation of `mirrord agent` commands.
+
+use std::{ops::Not, process::Stdio};
+
+use mirrord_config::agent::AgentImageConfig;
+use mirrord_progress::{Progress, ProgressTracker};
+use tokio::process::Command;
+
+use crate::{
+    CliResult,
+    config::{AgentArgs, AgentCommand, AgentPushArgs, ImageCopyTool},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum AgentPushError {
+    #[error("failed to run `{0}`: {1}")]
+    Spawn(&'static str, std::io::Error),
+
+    #[error("`{0}` exited with {1}")]
+    Failed(&'static str, std::process::ExitStatus),
+}
+
+pub async fn agent_command(args: AgentArgs) -> CliResult<()> {
+    match args.command {
+        AgentCommand::Push(args) => agent_push(args).await?,
+    }
+
+    Ok(())
+}
+
+/// Copies the agent image matching this version of mirrord to the given registry.
+async fn agent_push(args: AgentPushArgs) -> Result<(), AgentPushError> {
+    let mut progress = ProgressTracker::from_env("mirrord agent push");
+
+    let source = AgentImageConfig::default().0;
+    let registry = args.registry.trim_end_matches('/');
+    let tag = args
+        .tag
+        .unwrap_or_else(|| env!("CARGO_PKG_VERSION").to_string());
+    let destination = format!("{registry}/mirrord:{tag}");
+
+    let mut copy_progress = progress.subtask(&format!("copying {source} to {destination}"));
+
+    let (program, mut command) = copy_command(args.tool, &source, &destination);
+    let status = command
+        .stdin(Stdio::null())
+        .status()
+        .await
+        .map_err(|error| AgentPushError::Spawn(program, error))?;
+
+    if status.success().not() {
+        copy_progress.failure(None);
+        return Err(AgentPushError::Failed(program, status));
+    }
+
+    copy_progress.success(Some(&format!("pushed {destination}")));
+    progress.print(&format!(
+        "Use the image in your mirrord config with `\"agent\": {{ \"image\": \"{destination}\" }}`."
+    ));
+    progress.success(None);
+
+    Ok(())
+}
+
+/// Builds the command that copies the image with all of its platforms, without pulling it to the
+/// local machine.
+fn copy_command(tool: ImageCopyTool, source: &str, destination: &str) -> (&'static str, Command) {
+    match tool {
+        ImageCopyTool::Docker => {
+            let mut command = Command::new("docker");
+            command.args([
+                "buildx",
+                "imagetools",
+                "create",
+                "--tag",
+                destination,
+                source,
+            ]);
+            ("docker", command)
+        }
+        ImageCopyTool::Crane => {
+            let mut command = Command::new("crane");
+            command.args(["copy", source, destination]);
+            ("crane", command)
+        }
+        ImageCopyTool::Skopeo => {
+            let mut command = Command::new("skopeo");
+            command.args([
+                "copy",
+                "--all",
+                &format!("docker://{source}"),
+                &format!("docker://{destination}"),
+            ]);
+            ("skopeo", command)
+        }
+    }
+}
@@ -23,6 +23,9 @@ use mirrord_config::{
     target::TargetType,
 };
 use thiserror::Error;
+
+use crate::cp::CpPath;
+
 /// Macro to automatically handle Windows unsupported commands.
 /// Usage: `windows_unsupported!(args, "command_name", { command_execution })`
 #[macro_export]
@@ -68,6 +71,18 @@ pub(super) enum Commands {
     #[cfg_attr(target_os = "windows", command(hide = true))]
     Dump(Box<DumpArgs>),
 
+    /// Copy a file between the local machine and the remote target.
+    ///
+    /// Paths in the target are prefixed with `pod:`, e.g.
+    /// `mirrord cp -t deploy/app pod:/var/log/app.log ./app.log`.
+    Cp(Box<CpArgs>),
+
+    /// Periodically print the CPU and memory usage of the remote target and its processes.
+    ///
+    /// Shows the usage next to the container limits, so that the load of the remote application
+    /// can be compared with your local one, e.g. while stealing traffic.
+    Top(Box<TopArgs>),
+
     /// Generate shell completions for the provided shell.
     /// Supported shells: bash, elvish, fish, powershell, zsh
     Completions(CompletionsArgs),
@@ -88,6 +103,12 @@ pub(super) enum Commands {
     #[command(hide = true, name = "ls")]
     ListTargets(Box<ListTargetArgs>),
 
+    /// List the TCP ports the remote target is listening on.
+    ///
+    /// Used by the IDE extensions to pre-fill the ports to steal.
+    #[command(hide = true, name = "ls-ports")]
+    ListPorts(Box<ListPortsArgs>),
+
     /// Spawned by the IDE extensions.
     ///
     /// Works like [`Commands::Container`],
@@ -148,6 +169,13 @@ pub(super) enum Commands {
         #[arg(long, default_value_t = false)]
         mirrord_for_ci: bool,
 
+        /// Set this when starting the internal proxy from `mirrord start`.
+        ///
+        /// Saves the intproxy pid with the state of the named session, and disables the idle
+        /// timeouts, so that the intproxy runs until `mirrord stop`.
+        #[arg(long)]
+        session: Option<String>,
+
         /// Debug arguments.
         ///
         /// These are passed only for visibility in `ps` output,
@@ -158,6 +186,13 @@ pub(super) enum Commands {
         _debug_args: Vec<OsString>,
     },
 
+    /// Spawned by [`Commands::Exec`], [`Commands::ExtensionExec`], [`Commands::Container`] and
+    /// [`Commands::ExtensionContainer`] when [`LayerConfig::remote_logs`] is enabled.
+    ///
+    /// Streams the logs of the target container to stdout, until its parent process exits.
+    #[command(hide = true, name = "remote-logs")]
+    RemoteLogs,
+
     /// Forward local ports to hosts available from the cluster
     /// or intercept traffic and direct it to local ports (unstable).
     #[command(name = "port-forward")]
@@ -206,6 +241,41 @@ pub(super) enum Commands {
 
     /// Fix issues related to mirrord.
     Fix(FixArgs),
+
+    /// Manage mirrord config files.
+    Config(ConfigArgs),
+
+    /// Manage the mirrord-agent image.
+    Agent(Box<AgentArgs>),
+
+    /// Start a named mirrord session in the background, without running any process.
+    ///
+    /// Takes the same arguments as `mirrord exec`, apart from the binary. The agent and the
+    /// internal proxy are kept alive until `mirrord stop`.
+    #[cfg_attr(target_os = "windows", command(hide = true))]
+    Start(Box<StartArgs>),
+
+    /// Run a process against a session started with `mirrord start`.
+    ///
+    /// All processes attached to a session share its agent connection.
+    #[cfg_attr(target_os = "windows", command(hide = true))]
+    Attach(Box<AttachArgs>),
+
+    /// Stop a session started with `mirrord start`.
+    #[cfg_attr(target_os = "windows", command(hide = true))]
+    Stop(StopArgs),
+
+    /// Temporarily stop receiving the incoming traffic on a port of a session started with
+    /// `mirrord start`, without unsubscribing from it.
+    ///
+    /// Meanwhile, the traffic reaches the remote target as if mirrord wasn't there. Useful while
+    /// restarting the local application. Undo with `mirrord resume`.
+    #[cfg_attr(target_os = "windows", command(hide = true))]
+    Pause(PortPauseArgs),
+
+    /// Resume the incoming traffic on a port paused with `mirrord pause`.
+    #[cfg_attr(target_os = "windows", command(hide = true))]
+    Resume(PortPauseArgs),
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
@@ -283,6 +353,10 @@ pub(super) struct ExecParams {
     #[arg(long)]
     pub no_udp_outgoing: bool,
 
+    /// Stream the logs of the target container into the output, prefixed with `[remote]`.
+    #[arg(long)]
+    pub remote_logs: bool,
+
     /// Disable telemetry. See <https://github.com/metalbear-co/mirrord/blob/main/TELEMETRY.md>
     #[arg(long)]
     pub no_telemetry: bool,
@@ -398,6 +472,12 @@ impl ExecParams {
                 Cow::Borrowed("false".as_ref()),
             );
         }
+        if self.remote_logs {
+            envs.insert(
+                "MIRRORD_REMOTE_LOGS".as_ref(),
+                Cow::Borrowed("true".as_ref()),
+            );
+        }
         if let Some(context) = &self.context {
             envs.insert(
                 "MIRRORD_KUBE_CONTEXT".as_ref(),
@@ -433,6 +513,39 @@ pub(super) struct ExecArgs {
     #[clap(flatten)]
     pub params: Box<ExecParams>,
 
+    /// Keep the session alive after the process exits, and reuse it in the next
+    /// `mirrord exec --warm` with the same configuration.
+    ///
+    /// The session is kept until `mirrord stop <NAME>`, its name is printed on start.
+    #[arg(long)]
+    #[cfg_attr(target_os = "windows", arg(hide = true))]
+    pub warm: bool,
+
+    /// Restart the binary whenever a file matching this glob changes, e.g. `--watch 'src/**'`.
+    ///
+    /// The session is kept alive between the restarts, so the agent is not started again. While
+    /// the binary restarts, the stolen traffic goes to the remote target. Can be given multiple
+    /// times.
+    ///
+    /// Version control, build output and dependency directories (e.g. `.git`, `target`,
+    /// `node_modules`) are skipped, unless the glob starts in one of them.
+    #[arg(long, value_name = "GLOB")]
+    #[cfg_attr(target_os = "windows", arg(hide = true))]
+    pub watch: Vec<String>,
+
+    /// Adjust the configuration when the binary is a known test runner (`pytest`, `go test`,
+    /// `jest`).
+    ///
+    /// mirrord skips the helper processes of the runner, keeps the project files local, and
+    /// disables the `go test` result cache.
+    #[arg(long)]
+    pub exec_adapter: bool,
+
+    /// Name of a profile from the `profiles` section of the config file, applied on top of
+    /// the rest of the config.
+    #[arg(short = 'p', long)]
+    pub config_profile: Option<String>,
+
     /// Binary to execute and connect with the remote pod.
     pub binary: String,
 
@@ -440,6 +553,47 @@ pub(super) struct ExecArgs {
     pub(super) binary_args: Vec<String>,
 }
 
+// `mirrord start` command
+#[derive(Args, Debug)]
+pub(super) struct StartArgs {
+    #[clap(flatten)]
+    pub params: Box<ExecParams>,
+
+    /// Name of the session, used later with `mirrord attach` and `mirrord stop`.
+    #[arg(long)]
+    pub name: String,
+}
+
+// `mirrord attach` command
+#[derive(Args, Debug)]
+pub(super) struct AttachArgs {
+    /// Name of the session, as given to `mirrord start --name`.
+    pub name: String,
+
+    /// Binary to execute with the session.
+    pub binary: String,
+
+    /// Arguments to pass to the binary.
+    pub(super) binary_args: Vec<String>,
+}
+
+// `mirrord stop` command
+#[derive(Args, Debug)]
+pub(super) struct StopArgs {
+    /// Name of the session, as given to `mirrord start --name`.
+    pub name: String,
+}
+
+// `mirrord pause` and `mirrord resume` commands
+#[derive(Args, Debug)]
+pub(super) struct PortPauseArgs {
+    /// Name of the session, as given to `mirrord start --name`.
+    pub name: String,
+
+    /// Remote port to pause or resume.
+    pub port: u16,
+}
+
 // `mirrord dump` command
 #[derive(Args, Debug)]
 pub(super) struct DumpArgs {
@@ -450,6 +604,45 @@ pub(super) struct DumpArgs {
     /// Can be specified multiple times.
     #[arg(short = 'p', long, required = true)]
     pub ports: Vec<u16>,
+
+    /// Dump only the HTTP requests that match `feature.network.incoming.http_filter` from the
+    /// config, i.e. the requests that would be stolen.
+    ///
+    /// The filter is evaluated by the agent against live traffic, and all requests are still
+    /// handled by the deployed application. Use it to verify the filter before stealing.
+    #[arg(long)]
+    pub steal_dry_run: bool,
+}
+
+// `mirrord cp` command
+#[derive(Args, Debug)]
+pub(super) struct CpArgs {
+    #[clap(flatten)]
+    pub params: Box<ExecParams>,
+
+    /// File to copy: a local path, or an absolute path in the target prefixed with `pod:`.
+    pub source: CpPath,
+
+    /// Where to copy the file: a local path, or an absolute path in the target prefixed with
+    /// `pod:`.
+    ///
+    /// When this is an existing directory, the file is copied into it.
+    pub destination: CpPath,
+}
+
+// `mirrord top` command
+#[derive(Args, Debug)]
+pub(super) struct TopArgs {
+    #[clap(flatten)]
+    pub params: Box<ExecParams>,
+
+    /// Seconds between the reports.
+    #[arg(long, default_value_t = 2)]
+    pub interval: u64,
+
+    /// Exit after this many reports, instead of running until interrupted.
+    #[arg(long)]
+    pub iterations: Option<u32>,
 }
 
 // `mirrord ci start` command
@@ -548,6 +741,24 @@ pub(super) struct AgentParams {
     /// Spawn the agent in an ephemeral container.
     #[arg(short, long)]
     pub ephemeral_container: bool,
+
+    /// Forward the agent's own logs to this session, they are printed to stderr and stored in the
+    /// internal proxy log file.
+    #[arg(long)]
+    pub agent_logs: bool,
+
+    /// Service account for the agent pod.
+    #[arg(long)]
+    pub agent_service_account: Option<String>,
+
+    /// Priority class for the agent pod.
+    #[arg(long)]
+    pub agent_priority_class: Option<String>,
+
+    /// Make the agent write the raw bytes of stolen connections to this directory in its
+    /// container, see `agent.capture_stolen` in the configuration docs.
+    #[arg(long, value_name = "DIR")]
+    pub capture_stolen: Option<String>,
 }
 
 impl AgentParams {
@@ -599,6 +810,30 @@ impl AgentParams {
                 Cow::Borrowed("true".as_ref()),
             );
         }
+        if self.agent_logs {
+            envs.insert(
+                "MIRRORD_AGENT_FORWARD_LOGS".as_ref(),
+                Cow::Borrowed("true".as_ref()),
+            );
+        }
+        if let Some(service_account) = &self.agent_service_account {
+            envs.insert(
+                "MIRRORD_AGENT_SERVICE_ACCOUNT".as_ref(),
+                Cow::Borrowed(service_account.as_ref()),
+            );
+        }
+        if let Some(priority_class) = &self.agent_priority_class {
+            envs.insert(
+                "MIRRORD_AGENT_PRIORITY_CLASS".as_ref(),
+                Cow::Borrowed(priority_class.as_ref()),
+            );
+        }
+        if let Some(dir) = &self.capture_stolen {
+            envs.insert(
+                "MIRRORD_AGENT_CAPTURE_STOLEN".as_ref(),
+                Cow::Borrowed(dir.as_ref()),
+            );
+        }
 
         envs
     }
@@ -890,6 +1125,23 @@ impl ListTargetArgs {
     pub(super) const RICH_OUTPUT_ENV: &str = "MIRRORD_LS_RICH_OUTPUT";
 }
 
+// `mirrord ls-ports` command
+#[derive(Args, Debug)]
+pub(super) struct ListPortsArgs {
+    #[clap(flatten)]
+    pub params: Box<ExecParams>,
+
+    /// Specify the format of the output.
+    #[arg(
+        short = 'o',
+        long = "output",
+        value_name = "FORMAT",
+        value_enum,
+        default_value_t = Format::Json
+    )]
+    pub output: Format,
+}
+
 #[derive(Args, Debug)]
 pub(super) struct ExtensionExecArgs {
     /// Specify config file to use
@@ -935,6 +1187,24 @@ pub(super) enum DiagnoseCommand {
         #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = "./.mirrord/mirrord.json", num_args = 0..=1)]
         config_file: Option<PathBuf>,
     },
+
+    /// Run environment and connectivity checks, and write a sanitized diagnostic bundle that can
+    /// be attached to bug reports.
+    Checks {
+        /// Specify config file to use
+        #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = "./.mirrord/mirrord.json", num_args = 0..=1)]
+        config_file: Option<PathBuf>,
+
+        /// Binary you want to run with mirrord, checked for library injection issues (e.g. static
+        /// linking, setuid, SIP).
+        #[arg(short, long, value_hint = ValueHint::CommandName)]
+        binary: Option<PathBuf>,
+
+        /// Where to write the diagnostic bundle, defaults to
+        /// `mirrord-diagnose-<timestamp>.json` in the current directory.
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
 }
 
 // `mirrord container` command
@@ -1011,6 +1281,10 @@ impl ContainerRuntimeCommand {
         }
     }
 
+    /// Checks if a `run` command publishes ports (`-p`/`--publish`).
+    ///
+    /// The user container shares the network namespace of the internal proxy sidecar, so the
+    /// ports have to be published with `container.cli_extra_args` instead.
     pub fn has_publish(&self) -> bool {
         let runtime_args = match self {
             ContainerRuntimeCommand::Run { runtime_args } => runtime_args,
@@ -1022,7 +1296,10 @@ impl ContainerRuntimeCommand {
         runtime_args.iter().any(|runtime_arg| {
             hit_trailing_token = hit_trailing_token || runtime_arg == "--";
 
-            !hit_trailing_token && matches!(runtime_arg.as_str(), "-p" | "--publish")
+            !hit_trailing_token
+                && (matches!(runtime_arg.as_str(), "-p" | "--publish")
+                    || runtime_arg.starts_with("--publish=")
+                    || runtime_arg.starts_with("-p="))
         })
     }
 
@@ -1142,6 +1419,36 @@ pub struct WizardArgs {
     pub telemetry: bool,
 }
 
+/// `mirrord config` args.
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    /// Command to use with `mirrord config`.
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+/// `mirrord config` commands.
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Rewrite deprecated keys in a config file to their replacements.
+    ///
+    /// Templates are not rendered, and comments are not preserved.
+    Migrate(ConfigMigrateArgs),
+}
+
+/// `mirrord config migrate` args.
+#[derive(Args, Debug)]
+pub struct ConfigMigrateArgs {
+    /// Config file to migrate. If not given, migrates the project config found in the nearest
+    /// `.mirrord` directory.
+    #[arg(short = 'f', long, value_hint = ValueHint::FilePath)]
+    pub config_file: Option<PathBuf>,
+
+    /// Do not make any actual changes, just print the migrated config.
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+}
+
 /// `mirrord fix` args.
 #[derive(Args, Debug)]
 pub struct FixArgs {
@@ -1169,6 +1476,53 @@ pub struct FixKubeconfig {
     pub dry_run: bool,
 }
 
+/// Arguments for `mirrord agent` command.
+#[derive(Args, Debug)]
+pub(super) struct AgentArgs {
+    /// Subcommand to use with `mirrord agent`.
+    #[command(subcommand)]
+    pub command: AgentCommand,
+}
+
+/// `mirrord agent` subcommands.
+#[derive(Subcommand, Debug)]
+pub(super) enum AgentCommand {
+    /// Copy the mirrord-agent image matching this version of mirrord to a private registry, for
+    /// clusters that cannot pull from `ghcr.io`.
+    ///
+    /// The image is copied with all of its platforms, so the local machine needs access to both
+    /// registries.
+    Push(AgentPushArgs),
+}
+
+/// `mirrord agent push` args.
+#[derive(Args, Debug)]
+pub(super) struct AgentPushArgs {
+    /// Registry to push the image to, e.g. `registry.internal:5000/images`.
+    ///
+    /// The image is pushed as `<registry>/mirrord:<tag>`.
+    pub registry: String,
+
+    /// Tag of the pushed image, defaults to the version of mirrord.
+    #[arg(long)]
+    pub tag: Option<String>,
+
+    /// Tool used to copy the image.
+    #[arg(long, value_enum, default_value_t = ImageCopyTool::Docker)]
+    pub tool: ImageCopyTool,
+}
+
+/// Tools that can copy a multi-platform image between registries.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub(super) enum ImageCopyTool {
+    /// `docker buildx imagetools create`
+    Docker,
+    /// `crane copy`
+    Crane,
+    /// `skopeo copy --all`
+    Skopeo,
+}
+
 /// Arguments for `mirrord preview` command.
 #[derive(Args, Debug)]
 pub(super) struct PreviewArgs {
@@ -1473,6 +1827,19 @@ mod tests {
         assert_eq!(runtime_args, vec!["-it", "--rm", "debian"]);
     }
 
+    #[rstest]
+    #[case(&["-it", "-p", "8080:80", "nginx"], true)]
+    #[case(&["--publish=8080:80", "nginx"], true)]
+    #[case(&["-it", "--rm", "nginx"], false)]
+    #[case(&["--rm", "--", "nginx", "-p", "80"], false)]
+    fn has_publish(#[case] args: &[&str], #[case] expected: bool) {
+        let command = ContainerRuntimeCommand::Run {
+            runtime_args: args.iter().map(ToString::to_string).collect(),
+        };
+
+        assert_eq!(command.has_publish(), expected);
+    }
+
     #[test]
     fn runtime_args_parsing_with_seperator() {
         let command = "mirrord container -t deploy/test -- podman run -it --rm debian";
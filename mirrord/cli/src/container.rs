@@ -217,7 +217,7 @@ pub async fn container_command(
     if runtime_args.command.has_publish() {
         progress.warning(
             "mirrord container may have problems with \"-p\" when used as part of container run command. \
-            If you want to publish ports, please add the publish arguments to the
+            If you want to publish ports, please add the publish arguments to the \
             \"container.cli_extra_args\" list in your mirrord config.",
         );
     }
@@ -235,6 +235,9 @@ pub async fn container_command(
 
     progress.success(None);
 
+    #[cfg(not(target_os = "windows"))]
+    crate::remote_logs::spawn(&config, false)?;
+
     let (binary, binary_args) = runtime_command
         .with_command(runtime_args.command)
         .into_command_args();
@@ -297,6 +300,9 @@ pub async fn container_ext_command(
     let (runtime_command, execution_info, _tls_setup) =
         prepare_proxies(&mut analytics, &progress, &mut config, container_runtime).await?;
 
+    #[cfg(not(target_os = "windows"))]
+    crate::remote_logs::spawn(&config, true)?;
+
     let output = serde_json::to_string(&runtime_command.into_command_extension_params())?;
     progress.success(Some(&output));
     execution_info.wait().await?;
@@ -0,0 +1,277 @@
+//! Support for `feature.env.jvm_limits`.
+//!
+//! Reads the target container's CPU and memory limits from its cgroups through the agent, and
+//! turns them into JVM options, so that the local JVM sizes itself like it would in the pod.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, FileRequest, FileResponse, LogLevel,
+    file::{CloseFileRequest, OpenFileRequest, OpenOptionsInternal, ReadFileRequest},
+};
+use mirrord_protocol_io::{Client, Connection};
+use tracing::{Level, error, info, warn};
+
+use crate::{CliResult, error::CliError};
+
+/// Environment variable passed to the JVM, see
+/// [the docs](https://docs.oracle.com/en/java/javase/21/troubleshoot/environment-variables-and-system-properties.html).
+const JAVA_TOOL_OPTIONS: &str = "JAVA_TOOL_OPTIONS";
+
+/// Environment variable with the CPU limit of the target container, in CPUs.
+const CPU_LIMIT_ENV: &str = "MIRRORD_CONTAINER_CPU_LIMIT";
+
+/// Environment variable with the memory limit of the target container, in bytes.
+const MEMORY_LIMIT_ENV: &str = "MIRRORD_CONTAINER_MEMORY_LIMIT";
+
+/// cgroup v1 reports a huge number (page counter max) instead of an unlimited memory limit.
+const CGROUP_V1_UNLIMITED_MEMORY: u64 = 1 << 62;
+
+/// Max size of the cgroup files we read.
+const CGROUP_FILE_BUFFER_SIZE: u64 = 4096;
+
+/// CPU and memory limits of the target container.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ContainerLimits {
+    /// CPU quota, in CPUs.
+    pub(crate) cpus: Option<f64>,
+    /// Memory limit, in bytes.
+    pub(crate) memory: Option<u64>,
+}
+
+impl ContainerLimits {
+    /// Reads the limits from the target container's cgroups, trying cgroup v2 first.
+    ///
+    /// The files are read with [`FileRequest`]s, so the agent resolves them in the target's
+    /// filesystem.
+    #[tracing::instrument(level = Level::TRACE, skip_all, ret, err)]
+    pub(crate) async fn fetch(connection: &mut Connection<Client>) -> CliResult<Self> {
+        let cpus = match read_remote_file(connection, "/sys/fs/cgroup/cpu.max").await? {
+            Some(cpu_max) => parse_cpu_max(&cpu_max),
+            None => {
+                let quota =
+                    read_remote_file(connection, "/sys/fs/cgroup/cpu/cpu.cfs_quota_us").await?;
+                let period =
+                    read_remote_file(connection, "/sys/fs/cgroup/cpu/cpu.cfs_period_us").await?;
+                quota
+                    .zip(period)
+                    .and_then(|(quota, period)| parse_cfs_quota(&quota, &period))
+            }
+        };
+
+        let memory = match read_remote_file(connection, "/sys/fs/cgroup/memory.max").await? {
+            Some(memory_max) => parse_memory_max(&memory_max),
+            None => read_remote_file(connection, "/sys/fs/cgroup/memory/memory.limit_in_bytes")
+                .await?
+                .and_then(|limit| parse_memory_max(&limit))
+                .filter(|limit| *limit < CGROUP_V1_UNLIMITED_MEMORY),
+        };
+
+        Ok(Self { cpus, memory })
+    }
+
+    /// Adds the JVM options and the limit hints to the given environment.
+    ///
+    /// Options from the remote `JAVA_TOOL_OPTIONS` come last, so that the JVM prefers them over
+    /// ours.
+    pub(crate) fn apply(&self, env_vars: &mut HashMap<String, String>) {
+        let mut options = Vec::new();
+
+        if let Some(cpus) = self.cpus {
+            // The JVM rounds the quota up in the same way.
+            options.push(format!("-XX:ActiveProcessorCount={}", cpus.ceil().max(1.0)));
+            env_vars.insert(CPU_LIMIT_ENV.into(), cpus.to_string());
+        }
+
+        if let Some(memory) = self.memory {
+            options.push(format!("-XX:MaxRAM={memory}"));
+            env_vars.insert(MEMORY_LIMIT_ENV.into(), memory.to_string());
+        }
+
+        if options.is_empty() {
+            return;
+        }
+
+        if let Some(remote) = env_vars.get(JAVA_TOOL_OPTIONS) {
+            options.push(remote.clone());
+        }
+
+        env_vars.insert(JAVA_TOOL_OPTIONS.into(), options.join(" "));
+    }
+}
+
+/// Parses the cgroup v2 `cpu.max` file, e.g. `150000 100000` or `max 100000`.
+fn parse_cpu_max(cpu_max: &str) -> Option<f64> {
+    let mut parts = cpu_max.split_whitespace();
+    let quota = parts.next()?.parse::<f64>().ok()?;
+    let period = parts.next()?.parse::<f64>().ok()?;
+
+    (period > 0.0).then(|| quota / period)
+}
+
+/// Parses the cgroup v1 `cpu.cfs_quota_us` and `cpu.cfs_period_us` files. The quota is `-1` when
+/// there is no limit.
+fn parse_cfs_quota(quota: &str, period: &str) -> Option<f64> {
+    let quota = quota.trim().parse::<i64>().ok()?;
+    let period = period.trim().parse::<i64>().ok()?;
+
+    (quota > 0 && period > 0).then(|| quota as f64 / period as f64)
+}
+
+/// Parses the cgroup v2 `memory.max` or v1 `memory.limit_in_bytes` file, e.g. `536870912` or
+/// `max`.
+fn parse_memory_max(memory_max: &str) -> Option<u64> {
+    memory_max.trim().parse().ok()
+}
+
+/// Reads a small file from the target's filesystem.
+///
+/// Returns [`None`] if the agent fails to open or read the file, e.g. when it does not exist.
+async fn read_remote_file(
+    connection: &mut Connection<Client>,
+    path: &str,
+) -> CliResult<Option<String>> {
+    connection
+        .send(ClientMessage::FileRequest(FileRequest::Open(
+            OpenFileRequest {
+                path: PathBuf::from(path),
+                open_options: OpenOptionsInternal {
+                    read: true,
+                    ..Default::default()
+                },
+            },
+        )))
+        .await;
+
+    let fd = match recv_file_response(connection).await? {
+        FileResponse::Open(Ok(response)) => response.fd,
+        FileResponse::Open(Err(error)) => {
+            tracing::debug!(path, %error, "Failed to open a cgroup file in the target");
+            return Ok(None);
+        }
+        response => return Err(unexpected_response(response)),
+    };
+
+    connection
+        .send(ClientMessage::FileRequest(FileRequest::Read(
+            ReadFileRequest {
+                remote_fd: fd,
+                buffer_size: CGROUP_FILE_BUFFER_SIZE,
+            },
+        )))
+        .await;
+
+    let contents = match recv_file_response(connection).await? {
+        FileResponse::Read(Ok(response)) => {
+            Some(String::from_utf8_lossy(&response.bytes).into_owned())
+        }
+        FileResponse::Read(Err(error)) => {
+            tracing::debug!(path, %error, "Failed to read a cgroup file in the target");
+            None
+        }
+        response => return Err(unexpected_response(response)),
+    };
+
+    connection
+        .send(ClientMessage::FileRequest(FileRequest::Close(
+            CloseFileRequest { fd },
+        )))
+        .await;
+
+    Ok(contents)
+}
+
+/// Waits for the next [`FileResponse`] from the agent.
+async fn recv_file_response(connection: &mut Connection<Client>) -> CliResult<FileResponse> {
+    loop {
+        match connection.recv().await {
+            Some(DaemonMessage::File(response)) => return Ok(response),
+            Some(DaemonMessage::LogMessage(msg)) => match msg.level {
+                LogLevel::Error => error!("Agent log: {}", msg.message),
+                LogLevel::Warn => warn!("Agent log: {}", msg.message),
+                LogLevel::Info => info!("Agent log: {}", msg.message),
+            },
+            Some(DaemonMessage::Close(msg)) => {
+                return Err(CliError::InitialAgentCommFailed(format!(
+                    "agent closed connection with message: {msg}"
+                )));
+            }
+            Some(msg) => {
+                return Err(CliError::InitialAgentCommFailed(format!(
+                    "agent responded with an unexpected message: {msg:?}"
+                )));
+            }
+            None => {
+                return Err(CliError::InitialAgentCommFailed(
+                    "agent unexpectedly closed connection".to_string(),
+                ));
+            }
+        }
+    }
+}
+
+fn unexpected_response(response: FileResponse) -> CliError {
+    CliError::InitialAgentCommFailed(format!(
+        "agent responded with an unexpected file response: {response:?}"
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("150000 100000\n", Some(1.5))]
+    #[case("max 100000\n", None)]
+    #[case("", None)]
+    fn cpu_max(#[case] contents: &str, #[case] expected: Option<f64>) {
+        assert_eq!(parse_cpu_max(contents), expected);
+    }
+
+    #[rstest]
+    #[case("200000\n", "100000\n", Some(2.0))]
+    #[case("-1\n", "100000\n", None)]
+    fn cfs_quota(#[case] quota: &str, #[case] period: &str, #[case] expected: Option<f64>) {
+        assert_eq!(parse_cfs_quota(quota, period), expected);
+    }
+
+    #[rstest]
+    #[case("536870912\n", Some(536870912))]
+    #[case("max\n", None)]
+    fn memory_max(#[case] contents: &str, #[case] expected: Option<u64>) {
+        assert_eq!(parse_memory_max(contents), expected);
+    }
+
+    /// Verifies that the options from the remote `JAVA_TOOL_OPTIONS` are kept, and come last.
+    #[test]
+    fn apply_keeps_remote_options() {
+        let limits = ContainerLimits {
+            cpus: Some(1.5),
+            memory: Some(536870912),
+        };
+        let mut env_vars = HashMap::from([(
+            JAVA_TOOL_OPTIONS.to_string(),
+            "-XX:MaxRAMPercentage=75".to_string(),
+        )]);
+
+        limits.apply(&mut env_vars);
+
+        assert_eq!(
+            env_vars.get(JAVA_TOOL_OPTIONS).unwrap(),
+            "-XX:ActiveProcessorCount=2 -XX:MaxRAM=536870912 -XX:MaxRAMPercentage=75"
+        );
+        assert_eq!(env_vars.get(CPU_LIMIT_ENV).unwrap(), "1.5");
+        assert_eq!(env_vars.get(MEMORY_LIMIT_ENV).unwrap(), "536870912");
+    }
+
+    #[test]
+    fn apply_without_limits() {
+        let mut env_vars = HashMap::new();
+
+        ContainerLimits::default().apply(&mut env_vars);
+
+        assert!(env_vars.is_empty());
+    }
+}
@@ -0,0 +1,459 @@
+//! Implements the `mirrord cp` command, see [`cp_command`].
+
+use std::{
+    fmt,
+    ops::Not,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use mirrord_analytics::{AnalyticsReporter, CollectAnalytics, ExecutionKind};
+use mirrord_config::{LayerConfig, config::ConfigContext, target::Target};
+use mirrord_progress::{Progress, ProgressTracker};
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, FileRequest, FileResponse, ResponseError,
+    file::{
+        CloseFileRequest, MetadataInternal, OpenFileRequest, OpenOptionsInternal, ReadFileRequest,
+        WriteFileRequest, XstatRequest,
+    },
+};
+use mirrord_sdk::client::{AgentClient, AgentClientError};
+use thiserror::Error;
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+use super::config::CpArgs;
+use crate::{CliError, connection::create_and_connect, error::CliResult, user_data::UserData};
+
+/// Prefix of the paths in the target's filesystem, e.g. `pod:/var/log/app.log`.
+const REMOTE_PREFIX: &str = "pod:";
+
+/// How many bytes we read or write with a single request.
+///
+/// Large requests can lead to timeouts.
+const CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// Mask of the file type bits in [`MetadataInternal::mode`].
+const S_IFMT: u32 = 0o170000;
+
+/// Regular file type in [`MetadataInternal::mode`].
+const S_IFREG: u32 = 0o100000;
+
+/// Directory file type in [`MetadataInternal::mode`].
+const S_IFDIR: u32 = 0o040000;
+
+/// Source or destination of `mirrord cp`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CpPath {
+    /// Path in the local filesystem.
+    Local(PathBuf),
+    /// Absolute path in the target's filesystem, given with [`REMOTE_PREFIX`].
+    Remote(PathBuf),
+}
+
+impl FromStr for CpPath {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.strip_prefix(REMOTE_PREFIX) {
+            Some(path) if path.starts_with('/') => Ok(Self::Remote(path.into())),
+            Some(..) => Err(format!(
+                "paths in the target must be absolute, e.g. `{REMOTE_PREFIX}/var/log/app.log`"
+            )),
+            None => Ok(Self::Local(value.into())),
+        }
+    }
+}
+
+impl fmt::Display for CpPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local(path) => write!(f, "{}", path.display()),
+            Self::Remote(path) => write!(f, "{REMOTE_PREFIX}{}", path.display()),
+        }
+    }
+}
+
+/// Errors that can occur when copying files with `mirrord cp`.
+#[derive(Debug, Error)]
+pub enum CpError {
+    #[error("exactly one of the paths must be in the target, prefixed with `{REMOTE_PREFIX}`")]
+    InvalidPaths,
+
+    #[error(transparent)]
+    Agent(#[from] AgentClientError),
+
+    #[error("failed to access `{REMOTE_PREFIX}{}`: {error}", .path.display())]
+    Remote { path: PathBuf, error: ResponseError },
+
+    #[error("failed to access `{}`: {error}", .path.display())]
+    Local {
+        path: PathBuf,
+        #[source]
+        error: std::io::Error,
+    },
+
+    #[error("`{0}` is not a regular file, only files can be copied")]
+    NotAFile(CpPath),
+}
+
+/// Implements the `mirrord cp` command.
+///
+/// Copies a single file from the target's filesystem to the local one, or the other way around.
+///
+/// The file is accessed with the same requests that the layer uses, so the agent resolves paths
+/// in the target's filesystem, and the fs policies of the operator apply.
+pub async fn cp_command(args: &CpArgs, watch: drain::Watch, user_data: &UserData) -> CliResult<()> {
+    let mut cfg_context = ConfigContext::default().override_envs(args.params.as_env_vars());
+    let mut config = LayerConfig::resolve(&mut cfg_context)?;
+
+    let mut progress = ProgressTracker::from_env("mirrord cp");
+    let mut analytics = AnalyticsReporter::new(
+        config.telemetry,
+        ExecutionKind::Cp,
+        watch,
+        user_data.machine_id(),
+    );
+
+    if matches!(config.target.path, Some(Target::Targetless)) || config.target.path.is_none() {
+        return Err(CliError::MissingArg {
+            command: "mirrord cp".to_string(),
+            arg: "target".to_string(),
+        });
+    }
+
+    let (remote_to_local, local, remote) = match (&args.source, &args.destination) {
+        (CpPath::Remote(remote), CpPath::Local(local)) => (true, local, remote),
+        (CpPath::Local(local), CpPath::Remote(remote)) => (false, local, remote),
+        _ => return Err(CpError::InvalidPaths.into()),
+    };
+
+    if !args.params.disable_version_check {
+        super::prompt_outdated_version(&progress).await;
+    }
+
+    (&config).collect_analytics(analytics.get_mut());
+
+    let (_connection_info, connection) =
+        create_and_connect(&mut config, &mut progress, &mut analytics, None, None).await?;
+
+    let mut session = CpSession {
+        client: AgentClient::new(connection).await.map_err(CpError::from)?,
+    };
+
+    let mut copying = progress.subtask(&format!("copying {} to {}", args.source, args.destination));
+    let copied = if remote_to_local {
+        session.download(remote, local, &copying).await?
+    } else {
+        session.upload(local, remote, &copying).await?
+    };
+    copying.success(Some(&format!(
+        "copied {} to {} ({})",
+        args.source,
+        args.destination,
+        DisplayBytes(copied)
+    )));
+    progress.success(None);
+
+    Ok(())
+}
+
+/// Implements `mirrord cp` logic on an established [`AgentClient`].
+struct CpSession {
+    client: AgentClient,
+}
+
+impl CpSession {
+    /// Copies the remote file at `remote` to `local`.
+    ///
+    /// If `local` is a directory, the file is copied into it.
+    ///
+    /// Returns the number of bytes copied.
+    async fn download<P: Progress>(
+        &mut self,
+        remote: &Path,
+        local: &Path,
+        progress: &P,
+    ) -> Result<u64, CpError> {
+        let metadata = self.xstat(remote).await?;
+        if metadata.mode & S_IFMT != S_IFREG {
+            return Err(CpError::NotAFile(CpPath::Remote(remote.into())));
+        }
+
+        let local = match (tokio::fs::metadata(local).await, remote.file_name()) {
+            (Ok(local_metadata), Some(name)) if local_metadata.is_dir() => local.join(name),
+            _ => local.to_path_buf(),
+        };
+        let local_error = |error| CpError::Local {
+            path: local.clone(),
+            error,
+        };
+        let mut file = File::create(&local).await.map_err(local_error)?;
+
+        let fd = self
+            .open(
+                remote,
+                OpenOptionsInternal {
+                    read: true,
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let mut copied = 0;
+        let result = loop {
+            self.client
+                .send(ClientMessage::FileRequest(FileRequest::Read(
+                    ReadFileRequest {
+                        remote_fd: fd,
+                        buffer_size: CHUNK_SIZE,
+                    },
+                )))
+                .await;
+
+            let response = match self.recv_file_response().await {
+                Ok(FileResponse::Read(Ok(response))) => response,
+                Ok(FileResponse::Read(Err(error))) => {
+                    break Err(CpError::Remote {
+                        path: remote.into(),
+                        error,
+                    });
+                }
+                Ok(other) => {
+                    break Err(AgentClientError::unexpected(DaemonMessage::File(other)).into());
+                }
+                Err(error) => break Err(error),
+            };
+
+            if response.read_amount == 0 {
+                break file.flush().await.map_err(local_error);
+            }
+
+            if let Err(error) = file.write_all(&response.bytes).await {
+                break Err(local_error(error));
+            }
+
+            copied += response.read_amount;
+            progress.update(&format!(
+                "copying {REMOTE_PREFIX}{}: {} / {}",
+                remote.display(),
+                DisplayBytes(copied),
+                DisplayBytes(metadata.size)
+            ));
+        };
+
+        self.close(fd).await;
+        result.map(|()| copied)
+    }
+
+    /// Copies the local file at `local` to `remote`.
+    ///
+    /// If `remote` is a directory, the file is copied into it.
+    ///
+    /// Returns the number of bytes copied.
+    async fn upload<P: Progress>(
+        &mut self,
+        local: &Path,
+        remote: &Path,
+        progress: &P,
+    ) -> Result<u64, CpError> {
+        let local_error = |error| CpError::Local {
+            path: local.into(),
+            error,
+        };
+        let mut file = File::open(local).await.map_err(local_error)?;
+        let size = file.metadata().await.map_err(local_error)?.len();
+
+        let remote = match (self.xstat(remote).await, local.file_name()) {
+            (Ok(metadata), Some(name)) if metadata.mode & S_IFMT == S_IFDIR => remote.join(name),
+            _ => remote.to_path_buf(),
+        };
+
+        let fd = self
+            .open(
+                &remote,
+                OpenOptionsInternal {
+                    write: true,
+                    create: true,
+                    truncate: true,
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let mut copied = 0;
+        let mut buffer = vec![0; CHUNK_SIZE as usize];
+        let result = loop {
+            let read = match file.read(&mut buffer).await {
+                Ok(0) => break Ok(()),
+                Ok(read) => read,
+                Err(error) => break Err(local_error(error)),
+            };
+
+            if let Err(error) = self
+                .write_all(fd, &remote, buffer.get(..read).unwrap_or_default())
+                .await
+            {
+                break Err(error);
+            }
+
+            copied += read as u64;
+            progress.update(&format!(
+                "copying {} to {REMOTE_PREFIX}{}: {} / {}",
+                local.display(),
+                remote.display(),
+                DisplayBytes(copied),
+                DisplayBytes(size)
+            ));
+        };
+
+        self.close(fd).await;
+        result.map(|()| copied)
+    }
+
+    /// Writes the whole `bytes` to the remote file.
+    async fn write_all(&mut self, fd: u64, remote: &Path, mut bytes: &[u8]) -> Result<(), CpError> {
+        while bytes.is_empty().not() {
+            self.client
+                .send(ClientMessage::FileRequest(FileRequest::Write(
+                    WriteFileRequest {
+                        fd,
+                        write_bytes: bytes.to_vec().into(),
+                    },
+                )))
+                .await;
+
+            match self.recv_file_response().await? {
+                FileResponse::Write(Ok(response)) => {
+                    bytes = bytes
+                        .get(response.written_amount as usize..)
+                        .unwrap_or_default();
+                }
+                FileResponse::Write(Err(error)) => {
+                    return Err(CpError::Remote {
+                        path: remote.into(),
+                        error,
+                    });
+                }
+                other => {
+                    return Err(AgentClientError::unexpected(DaemonMessage::File(other)).into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the metadata of the remote file, following symlinks.
+    async fn xstat(&mut self, path: &Path) -> Result<MetadataInternal, CpError> {
+        self.client
+            .send(ClientMessage::FileRequest(FileRequest::Xstat(
+                XstatRequest {
+                    path: Some(path.into()),
+                    fd: None,
+                    follow_symlink: true,
+                },
+            )))
+            .await;
+
+        match self.recv_file_response().await? {
+            FileResponse::Xstat(Ok(response)) => Ok(response.metadata),
+            FileResponse::Xstat(Err(error)) => Err(CpError::Remote {
+                path: path.into(),
+                error,
+            }),
+            other => Err(AgentClientError::unexpected(DaemonMessage::File(other)).into()),
+        }
+    }
+
+    /// Opens the remote file, returning its remote fd.
+    async fn open(
+        &mut self,
+        path: &Path,
+        open_options: OpenOptionsInternal,
+    ) -> Result<u64, CpError> {
+        self.client
+            .send(ClientMessage::FileRequest(FileRequest::Open(
+                OpenFileRequest {
+                    path: path.into(),
+                    open_options,
+                },
+            )))
+            .await;
+
+        match self.recv_file_response().await? {
+            FileResponse::Open(Ok(response)) => Ok(response.fd),
+            FileResponse::Open(Err(error)) => Err(CpError::Remote {
+                path: path.into(),
+                error,
+            }),
+            other => Err(AgentClientError::unexpected(DaemonMessage::File(other)).into()),
+        }
+    }
+
+    async fn close(&mut self, fd: u64) {
+        self.client
+            .send(ClientMessage::FileRequest(FileRequest::Close(
+                CloseFileRequest { fd },
+            )))
+            .await;
+    }
+
+    /// Waits for the next [`FileResponse`] from the agent.
+    async fn recv_file_response(&mut self) -> Result<FileResponse, CpError> {
+        match self.client.recv().await? {
+            DaemonMessage::File(response) => Ok(response),
+            other => Err(AgentClientError::unexpected(other).into()),
+        }
+    }
+}
+
+/// Displays a number of bytes in a human friendly way, e.g. `1.5 MiB`.
+pub(crate) struct DisplayBytes(pub(crate) u64);
+
+impl fmt::Display for DisplayBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+        let mut value = self.0 as f64;
+        let mut unit = None;
+        for next_unit in UNITS {
+            if value < 1024.0 {
+                break;
+            }
+            value /= 1024.0;
+            unit = Some(next_unit);
+        }
+
+        match unit {
+            Some(unit) => write!(f, "{value:.1} {unit}"),
+            None => write!(f, "{} B", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("pod:/var/log/app.log", Ok(CpPath::Remote("/var/log/app.log".into())))]
+    #[case("./app.log", Ok(CpPath::Local("./app.log".into())))]
+    #[case("/tmp/app.log", Ok(CpPath::Local("/tmp/app.log".into())))]
+    #[case("pod:app.log", Err(()))]
+    fn parse_path(#[case] value: &str, #[case] expected: Result<CpPath, ()>) {
+        assert_eq!(value.parse::<CpPath>().map_err(|_| ()), expected);
+    }
+
+    #[rstest]
+    #[case(512, "512 B")]
+    #[case(1536, "1.5 KiB")]
+    #[case(5 * 1024 * 1024, "5.0 MiB")]
+    fn display_bytes(#[case] bytes: u64, #[case] expected: &str) {
+        assert_eq!(DisplayBytes(bytes).to_string(), expected);
+    }
+}
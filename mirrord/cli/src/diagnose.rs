@@ -1,10 +1,17 @@
-use std::{path::Path, time::Duration};
+use std::{
+    ops::Not,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
 
+use checks::{CheckResult, CheckStatus};
 use mirrord_analytics::NullReporter;
 use mirrord_config::{LayerConfig, config::ConfigContext};
 use mirrord_progress::{Progress, ProgressTracker};
 use mirrord_protocol::{ClientMessage, DaemonMessage};
 use mirrord_protocol_io::{Client, Connection};
+use serde::Serialize;
+use serde_json::Value;
 use tokio::time::Instant;
 use tracing::Level;
 
@@ -13,6 +20,8 @@ use crate::{
     util::remove_proxy_env,
 };
 
+mod checks;
+
 /// Sends a ping the connection and expects a pong.
 async fn ping(connection: &mut Connection<Client>) -> CliResult<()> {
     connection.send(ClientMessage::Ping).await;
@@ -89,9 +98,266 @@ async fn diagnose_latency(config: Option<&Path>) -> CliResult<()> {
     Ok(())
 }
 
+/// Config paths whose values are replaced with [`REDACTED`] in the [`DiagnosticBundle`].
+///
+/// Array indices are not a part of the path, e.g. `feature.db_branches.connection.password`
+/// matches the password of every branch.
+const SENSITIVE_PATHS: &[&str] = &[
+    "key",
+    "agent.audit_webhook",
+    "feature.env.override",
+    "feature.env.mapping",
+    "feature.db_branches.connection.password",
+];
+
+/// The HTTP filter, whose header values usually contain session keys or tokens.
+const HTTP_FILTER_PATH: &str = "feature.network.incoming.http_filter";
+
+/// Fields with header values in the [HTTP filter](HTTP_FILTER_PATH), also in the filters nested
+/// in `all_of`, `any_of` and `exclude`.
+const HEADER_FILTER_FIELDS: &[&str] = &["header_filter", "header"];
+
+/// Fields with header values in the `header-prefix` and `header-equals` presets.
+const HEADER_PRESET_FIELDS: &[&str] = &["prefix", "value"];
+
+const REDACTED: &str = "<redacted>";
+
+/// Replaces values of [`SENSITIVE_PATHS`] and header filters in the serialized config, so that
+/// the bundle can be shared publicly.
+fn sanitize(value: &mut Value) {
+    sanitize_at("", value)
+}
+
+/// [`sanitize`] for the `value` found at the given config `path`.
+fn sanitize_at(path: &str, value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            let in_http_filter = path.starts_with(HTTP_FILTER_PATH);
+            let header_preset = in_http_filter
+                && map
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .is_some_and(|name| name.starts_with("header-"));
+
+            for (key, value) in map.iter_mut() {
+                // The presets have the header name in `header`, and the value in other fields.
+                let sensitive_field = if header_preset {
+                    HEADER_PRESET_FIELDS.contains(&key.as_str())
+                } else {
+                    in_http_filter && HEADER_FILTER_FIELDS.contains(&key.as_str())
+                };
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+
+                if value.is_null().not()
+                    && (sensitive_field || SENSITIVE_PATHS.contains(&path.as_str()))
+                {
+                    *value = Value::String(REDACTED.to_owned());
+                } else {
+                    sanitize_at(&path, value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|value| sanitize_at(path, value)),
+        _ => {}
+    }
+}
+
+/// Everything we know about the user's setup, written to a file by `mirrord diagnose checks`.
+#[derive(Serialize)]
+struct DiagnosticBundle {
+    mirrord_version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    checks: Vec<CheckResult>,
+    /// Resolved config, see [`sanitize`].
+    config: Value,
+}
+
+/// Displays the result of a single check.
+fn report<P: Progress>(progress: &P, result: &CheckResult) {
+    let mut subtask = progress.subtask(result.name);
+    match result.status {
+        CheckStatus::Passed | CheckStatus::Skipped => subtask.success(Some(&result.to_string())),
+        CheckStatus::Warning => {
+            subtask.warning(&result.to_string());
+            subtask.success(Some(result.name));
+        }
+        CheckStatus::Failed => subtask.failure(Some(&result.to_string())),
+    }
+}
+
+/// Run a battery of checks and write a [`DiagnosticBundle`].
+#[tracing::instrument(level = Level::TRACE, ret)]
+async fn diagnose_checks(
+    config: Option<&Path>,
+    binary: Option<&Path>,
+    output: Option<PathBuf>,
+) -> CliResult<()> {
+    let mut progress = ProgressTracker::from_env("mirrord diagnose");
+
+    let mut context = ConfigContext::default().override_env_opt(LayerConfig::FILE_PATH_ENV, config);
+    let config = LayerConfig::resolve(&mut context)?;
+
+    if !config.use_proxy {
+        remove_proxy_env();
+    }
+
+    let mut results = Vec::new();
+
+    let (connectivity, client) = checks::kube_connectivity(&config).await;
+    results.push(connectivity);
+    match client {
+        Some(client) => {
+            results.push(checks::rbac(&client, &config).await);
+            results.push(checks::agent_image(&config).await);
+            results.push(checks::mesh(&client, &config).await);
+        }
+        None => results.extend(checks::skipped_without_cluster()),
+    }
+    results.push(checks::sip_status());
+    results.push(checks::preload_viability(binary));
+
+    results.iter().for_each(|result| report(&progress, result));
+
+    let mut config = serde_json::to_value(&config)?;
+    sanitize(&mut config);
+
+    let bundle = DiagnosticBundle {
+        mirrord_version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        checks: results,
+        config,
+    };
+
+    let output = output.unwrap_or_else(|| {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        PathBuf::from(format!("mirrord-diagnose-{timestamp}.json"))
+    });
+    let bundle = serde_json::to_vec_pretty(&bundle)?;
+    std::fs::write(&output, bundle)
+        .map_err(|error| CliError::DiagnosticBundleWrite(output.clone(), error))?;
+
+    progress.success(Some(&format!(
+        "Diagnostic bundle written to `{}`, you can attach it to your bug report.",
+        output.display()
+    )));
+
+    Ok(())
+}
+
 /// Handle commands related to the operator `mirrord diagnose ...`
 pub(crate) async fn diagnose_command(args: DiagnoseArgs) -> CliResult<()> {
     match args.command {
         DiagnoseCommand::Latency { config_file } => diagnose_latency(config_file.as_deref()).await,
+        DiagnoseCommand::Checks {
+            config_file,
+            binary,
+            output,
+        } => diagnose_checks(config_file.as_deref(), binary.as_deref(), output).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{REDACTED, sanitize};
+
+    #[test]
+    fn sanitize_redacts_sensitive_values() {
+        let mut config = json!({
+            "key": { "Provided": "my-session" },
+            "agent": {
+                "namespace": "default",
+                "audit_webhook": "http://audit.local/?token=abc",
+                "passthrough_cache": { "headers": ["x-api-key"] },
+            },
+            "feature": {
+                "env": {
+                    "override": { "DB_PASSWORD": "hunter2" },
+                    "unset": ["AWS_SECRET_KEY"],
+                },
+                "db_branches": [{ "connection": { "password": { "Direct": "hunter2" } } }],
+                "network": {
+                    "incoming": {
+                        "tls_delivery": { "server_cert": "/certs/server.pem" },
+                    },
+                },
+            },
+        });
+
+        sanitize(&mut config);
+
+        assert_eq!(config["key"], REDACTED);
+        assert_eq!(config["agent"]["namespace"], "default");
+        assert_eq!(config["agent"]["audit_webhook"], REDACTED);
+        assert_eq!(config["feature"]["env"]["override"], REDACTED);
+        assert_eq!(
+            config["feature"]["db_branches"][0]["connection"]["password"],
+            REDACTED
+        );
+
+        // Config names that only look sensitive are kept.
+        assert_eq!(
+            config["agent"]["passthrough_cache"]["headers"],
+            json!(["x-api-key"])
+        );
+        assert_eq!(config["feature"]["env"]["unset"], json!(["AWS_SECRET_KEY"]));
+        assert_eq!(
+            config["feature"]["network"]["incoming"]["tls_delivery"]["server_cert"],
+            "/certs/server.pem"
+        );
+    }
+
+    #[test]
+    fn sanitize_redacts_header_filters() {
+        let mut config = json!({
+            "feature": {
+                "network": {
+                    "incoming": {
+                        "http_filter": {
+                            "header_filter": "authorization: Bearer abc",
+                            "path_filter": "^/api",
+                            "all_of": [
+                                { "header": "x-session: abc" },
+                                { "path": "^/api/v2" },
+                                {
+                                    "preset": {
+                                        "name": "header-equals",
+                                        "header": "x-session",
+                                        "value": "abc",
+                                    },
+                                },
+                            ],
+                            "exclude": [
+                                { "preset": { "name": "path-prefix", "prefix": "/health" } },
+                            ],
+                            "ports": [80],
+                        },
+                    },
+                },
+            },
+        });
+
+        sanitize(&mut config);
+
+        let http_filter = &config["feature"]["network"]["incoming"]["http_filter"];
+        assert_eq!(http_filter["header_filter"], REDACTED);
+        assert_eq!(http_filter["all_of"][0]["header"], REDACTED);
+        assert_eq!(http_filter["all_of"][2]["preset"]["value"], REDACTED);
+        assert_eq!(http_filter["all_of"][2]["preset"]["header"], "x-session");
+
+        assert_eq!(http_filter["path_filter"], "^/api");
+        assert_eq!(http_filter["all_of"][1]["path"], "^/api/v2");
+        assert_eq!(http_filter["exclude"][0]["preset"]["prefix"], "/health");
+        assert_eq!(http_filter["ports"], json!([80]));
     }
 }
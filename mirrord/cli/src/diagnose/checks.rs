@@ -0,0 +1,619 @@
+//! The checks run by `mirrord diagnose checks`.
+//!
+//! Each check produces a [`CheckResult`], which is both displayed to the user and stored in the
+//! diagnostic bundle. Checks never fail the command, a failing check is just a result.
+
+use std::{
+    fmt,
+    ops::Not,
+    path::{Path, PathBuf},
+};
+
+use k8s_openapi::api::authorization::v1::{
+    ResourceAttributes, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec,
+};
+use kube::{Api, Client, api::PostParams};
+use mirrord_config::{LayerConfig, target::Target};
+use mirrord_kube::api::runtime::RuntimeDataProvider;
+use reqwest::{
+    StatusCode,
+    header::{ACCEPT, AUTHORIZATION, WWW_AUTHENTICATE},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::kube::kube_client_from_layer_config;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum CheckStatus {
+    Passed,
+    /// The check found something that might cause issues.
+    Warning,
+    Failed,
+    /// The check is not applicable, e.g. SIP check on Linux.
+    Skipped,
+}
+
+#[derive(Serialize, Debug)]
+pub(super) struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub details: String,
+}
+
+impl CheckResult {
+    fn new(name: &'static str, status: CheckStatus, details: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            details: details.into(),
+        }
+    }
+}
+
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.details)
+    }
+}
+
+const KUBE_CONNECTIVITY: &str = "kube connectivity";
+const RBAC: &str = "RBAC";
+const AGENT_IMAGE: &str = "agent image";
+const SIP: &str = "SIP";
+const PRELOAD: &str = "library injection";
+const MESH: &str = "service mesh";
+
+/// Creates a kube client from the config and fetches the API server version.
+///
+/// The client is returned only when the cluster is reachable, the other cluster checks are skipped
+/// otherwise.
+pub(super) async fn kube_connectivity(config: &LayerConfig) -> (CheckResult, Option<Client>) {
+    let client = match kube_client_from_layer_config(config).await {
+        Ok(client) => client,
+        Err(error) => {
+            return (
+                CheckResult::new(
+                    KUBE_CONNECTIVITY,
+                    CheckStatus::Failed,
+                    format!("failed to create a kube client: {error}"),
+                ),
+                None,
+            );
+        }
+    };
+
+    match client.apiserver_version().await {
+        Ok(version) => (
+            CheckResult::new(
+                KUBE_CONNECTIVITY,
+                CheckStatus::Passed,
+                format!("connected to Kubernetes {}", version.git_version),
+            ),
+            Some(client),
+        ),
+        Err(error) => (
+            CheckResult::new(
+                KUBE_CONNECTIVITY,
+                CheckStatus::Failed,
+                format!("failed to reach the API server: {error}"),
+            ),
+            None,
+        ),
+    }
+}
+
+/// Result for the cluster checks when [`kube_connectivity`] failed.
+pub(super) fn skipped_without_cluster() -> [CheckResult; 3] {
+    [RBAC, AGENT_IMAGE, MESH]
+        .map(|name| CheckResult::new(name, CheckStatus::Skipped, "the cluster is not reachable"))
+}
+
+/// A single permission needed to spawn and connect to the agent without the operator.
+struct Permission {
+    verb: &'static str,
+    group: &'static str,
+    resource: &'static str,
+    subresource: Option<&'static str>,
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.verb, self.resource)?;
+        if let Some(subresource) = self.subresource {
+            write!(f, "/{subresource}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the permissions needed to spawn the agent with [`SelfSubjectAccessReview`]s.
+pub(super) async fn rbac(client: &Client, config: &LayerConfig) -> CheckResult {
+    if config.operator == Some(true) {
+        return CheckResult::new(
+            RBAC,
+            CheckStatus::Skipped,
+            "permissions are managed by the mirrord operator",
+        );
+    }
+
+    let namespace = config
+        .agent
+        .namespace
+        .as_deref()
+        .or(config.target.namespace.as_deref())
+        .unwrap_or(client.default_namespace());
+
+    let mut permissions = vec![
+        Permission {
+            verb: "get",
+            group: "",
+            resource: "pods",
+            subresource: None,
+        },
+        Permission {
+            verb: "create",
+            group: "",
+            resource: "pods",
+            subresource: Some("portforward"),
+        },
+    ];
+    permissions.push(if config.agent.ephemeral {
+        Permission {
+            verb: "patch",
+            group: "",
+            resource: "pods",
+            subresource: Some("ephemeralcontainers"),
+        }
+    } else {
+        Permission {
+            verb: "create",
+            group: "batch",
+            resource: "jobs",
+            subresource: None,
+        }
+    });
+
+    let api: Api<SelfSubjectAccessReview> = Api::all(client.clone());
+    let mut missing = Vec::new();
+
+    for permission in &permissions {
+        let review = SelfSubjectAccessReview {
+            spec: SelfSubjectAccessReviewSpec {
+                resource_attributes: Some(ResourceAttributes {
+                    namespace: Some(namespace.to_owned()),
+                    verb: Some(permission.verb.to_owned()),
+                    group: Some(permission.group.to_owned()),
+                    resource: Some(permission.resource.to_owned()),
+                    subresource: permission.subresource.map(ToOwned::to_owned),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        match api.create(&PostParams::default(), &review).await {
+            Ok(review) if review.status.is_some_and(|status| status.allowed) => {}
+            Ok(..) => missing.push(permission.to_string()),
+            Err(error) => {
+                return CheckResult::new(
+                    RBAC,
+                    CheckStatus::Failed,
+                    format!("failed to review permission `{permission}`: {error}"),
+                );
+            }
+        }
+    }
+
+    if missing.is_empty() {
+        CheckResult::new(
+            RBAC,
+            CheckStatus::Passed,
+            format!(
+                "all permissions needed to spawn the agent are granted in namespace `{namespace}`"
+            ),
+        )
+    } else {
+        CheckResult::new(
+            RBAC,
+            CheckStatus::Failed,
+            format!(
+                "missing permissions in namespace `{namespace}`: {}",
+                missing.join(", ")
+            ),
+        )
+    }
+}
+
+/// A parsed container image reference, e.g. `ghcr.io/metalbear-co/mirrord:3.100.0`.
+#[derive(Debug, PartialEq, Eq)]
+struct ImageReference {
+    registry: String,
+    repository: String,
+    /// Tag or digest.
+    reference: String,
+}
+
+impl ImageReference {
+    const DOCKER_HUB: &str = "registry-1.docker.io";
+
+    fn parse(image: &str) -> Self {
+        let (name, reference) = match image.split_once('@') {
+            Some((name, digest)) => (name, digest),
+            None => match image.rsplit_once(':') {
+                Some((name, tag)) if tag.contains('/').not() => (name, tag),
+                _ => (image, "latest"),
+            },
+        };
+
+        let (registry, repository) = match name.split_once('/') {
+            Some(("docker.io", repository)) => (Self::DOCKER_HUB, repository.to_owned()),
+            Some((registry, repository))
+                if registry.contains(['.', ':']) || registry == "localhost" =>
+            {
+                (registry, repository.to_owned())
+            }
+            Some(..) => (Self::DOCKER_HUB, name.to_owned()),
+            None => (Self::DOCKER_HUB, name.to_owned()),
+        };
+
+        let repository = if registry == Self::DOCKER_HUB && repository.contains('/').not() {
+            format!("library/{repository}")
+        } else {
+            repository
+        };
+
+        Self {
+            registry: registry.to_owned(),
+            repository,
+            reference: reference.to_owned(),
+        }
+    }
+
+    fn manifest_url(&self) -> String {
+        format!(
+            "https://{}/v2/{}/manifests/{}",
+            self.registry, self.repository, self.reference
+        )
+    }
+}
+
+/// Response of a registry token endpoint, different registries use different fields.
+#[derive(Deserialize)]
+struct RegistryToken {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+/// Fetches an anonymous pull token, following the `WWW-Authenticate` challenge of the registry.
+async fn anonymous_token(
+    http: &reqwest::Client,
+    challenge: &str,
+) -> Result<Option<String>, reqwest::Error> {
+    let Some(params) = challenge.strip_prefix("Bearer ") else {
+        return Ok(None);
+    };
+
+    let mut realm = None;
+    let mut query = Vec::new();
+    for param in params.split(',') {
+        let Some((key, value)) = param.trim().split_once('=') else {
+            continue;
+        };
+        let value = value.trim_matches('"');
+        match key {
+            "realm" => realm = Some(value),
+            "service" | "scope" => query.push(format!("{key}={value}")),
+            _ => {}
+        }
+    }
+
+    let Some(realm) = realm else {
+        return Ok(None);
+    };
+
+    let token = http
+        .get(format!("{realm}?{}", query.join("&")))
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<RegistryToken>()
+        .await?;
+
+    Ok(token.token.or(token.access_token))
+}
+
+/// Checks that the agent image manifest can be fetched from its registry.
+///
+/// This runs from the user's machine, so a failure here does not necessarily mean that the cluster
+/// can't pull the image (e.g. internal registries, node credentials).
+pub(super) async fn agent_image(config: &LayerConfig) -> CheckResult {
+    if config.operator == Some(true) {
+        return CheckResult::new(
+            AGENT_IMAGE,
+            CheckStatus::Skipped,
+            "the agent is spawned by the mirrord operator",
+        );
+    }
+
+    const MANIFEST_TYPES: &str = "application/vnd.oci.image.index.v1+json, \
+        application/vnd.docker.distribution.manifest.list.v2+json, \
+        application/vnd.oci.image.manifest.v1+json, \
+        application/vnd.docker.distribution.manifest.v2+json";
+
+    let image = &config.agent.image.0;
+    let reference = ImageReference::parse(image);
+    let url = reference.manifest_url();
+    let http = reqwest::Client::new();
+
+    let result: Result<StatusCode, reqwest::Error> = async {
+        let response = http
+            .head(&url)
+            .header(ACCEPT, MANIFEST_TYPES)
+            .send()
+            .await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response.status());
+        }
+
+        let challenge = response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let Some(token) = anonymous_token(&http, challenge).await? else {
+            return Ok(response.status());
+        };
+
+        http.head(&url)
+            .header(ACCEPT, MANIFEST_TYPES)
+            .header(AUTHORIZATION, format!("Bearer {token}"))
+            .send()
+            .await
+            .map(|response| response.status())
+    }
+    .await;
+
+    match result {
+        Ok(status) if status.is_success() => CheckResult::new(
+            AGENT_IMAGE,
+            CheckStatus::Passed,
+            format!("image `{image}` is available"),
+        ),
+        Ok(StatusCode::NOT_FOUND) => CheckResult::new(
+            AGENT_IMAGE,
+            CheckStatus::Failed,
+            format!("image `{image}` was not found in `{}`", reference.registry),
+        ),
+        Ok(status @ (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)) => {
+            let hint = if config.agent.image_pull_secrets.is_some() {
+                "the cluster will use the configured `agent.image_pull_secrets`"
+            } else {
+                "make sure the cluster has credentials for it, e.g. with `agent.image_pull_secrets`"
+            };
+            CheckResult::new(
+                AGENT_IMAGE,
+                CheckStatus::Warning,
+                format!("registry requires credentials for `{image}` ({status}), {hint}"),
+            )
+        }
+        Ok(status) => CheckResult::new(
+            AGENT_IMAGE,
+            CheckStatus::Warning,
+            format!("unexpected registry response for `{image}`: {status}"),
+        ),
+        Err(error) => CheckResult::new(
+            AGENT_IMAGE,
+            CheckStatus::Warning,
+            format!(
+                "could not reach `{}` from this machine ({error}), the cluster might still be \
+                able to pull the image",
+                reference.registry
+            ),
+        ),
+    }
+}
+
+/// Reports whether System Integrity Protection is enabled (macOS only).
+pub(super) fn sip_status() -> CheckResult {
+    #[cfg(target_os = "macos")]
+    {
+        match std::process::Command::new("csrutil").arg("status").output() {
+            Ok(output) => {
+                let status = String::from_utf8_lossy(&output.stdout);
+                let details = if status.contains("enabled") {
+                    "SIP is enabled, mirrord runs patched copies of SIP protected binaries"
+                } else {
+                    "SIP is disabled"
+                };
+                CheckResult::new(SIP, CheckStatus::Passed, details)
+            }
+            Err(error) => CheckResult::new(
+                SIP,
+                CheckStatus::Warning,
+                format!("failed to run `csrutil status`: {error}"),
+            ),
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    CheckResult::new(SIP, CheckStatus::Skipped, "only relevant on macOS")
+}
+
+/// Directories protected by SIP on macOS, binaries in them can't be injected with
+/// `DYLD_INSERT_LIBRARIES`.
+#[cfg(target_os = "macos")]
+const SIP_PROTECTED_DIRS: &[&str] = &[
+    "/bin",
+    "/sbin",
+    "/usr/bin",
+    "/usr/sbin",
+    "/usr/libexec",
+    "/System",
+];
+
+/// Checks whether the mirrord layer can be injected into the given binary with `LD_PRELOAD`
+/// (`DYLD_INSERT_LIBRARIES` on macOS).
+pub(super) fn preload_viability(binary: Option<&Path>) -> CheckResult {
+    if cfg!(target_os = "windows") {
+        return CheckResult::new(PRELOAD, CheckStatus::Skipped, "not relevant on Windows");
+    }
+
+    let Some(binary) = binary else {
+        return CheckResult::new(
+            PRELOAD,
+            CheckStatus::Skipped,
+            "no binary given, use `--binary <path>` to check one",
+        );
+    };
+
+    let path: PathBuf = which::which(binary).unwrap_or_else(|_| binary.to_path_buf());
+    let metadata = match std::fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(error) => {
+            return CheckResult::new(
+                PRELOAD,
+                CheckStatus::Failed,
+                format!("cannot access `{}`: {error}", path.display()),
+            );
+        }
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if metadata.permissions().mode() & 0o4000 != 0 {
+            return CheckResult::new(
+                PRELOAD,
+                CheckStatus::Failed,
+                format!(
+                    "`{}` is setuid, the dynamic loader ignores injected libraries for it",
+                    path.display()
+                ),
+            );
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = metadata;
+
+    #[cfg(target_os = "linux")]
+    if crate::is_static::is_binary_static(&path) {
+        return CheckResult::new(
+            PRELOAD,
+            CheckStatus::Warning,
+            format!(
+                "`{}` is statically linked, mirrord works only if it spawns a dynamically linked \
+                child process",
+                path.display()
+            ),
+        );
+    }
+
+    #[cfg(target_os = "macos")]
+    if SIP_PROTECTED_DIRS.iter().any(|dir| path.starts_with(dir)) {
+        return CheckResult::new(
+            PRELOAD,
+            CheckStatus::Warning,
+            format!(
+                "`{}` is SIP protected, mirrord will run a patched copy of it",
+                path.display()
+            ),
+        );
+    }
+
+    CheckResult::new(
+        PRELOAD,
+        CheckStatus::Passed,
+        format!(
+            "`{}` can be injected with the mirrord layer",
+            path.display()
+        ),
+    )
+}
+
+/// Checks whether the target runs with a service mesh sidecar.
+pub(super) async fn mesh(client: &Client, config: &LayerConfig) -> CheckResult {
+    let target = match config.target.path.as_ref() {
+        None | Some(Target::Targetless) => {
+            return CheckResult::new(MESH, CheckStatus::Skipped, "no target configured");
+        }
+        Some(target) => target,
+    };
+
+    match target
+        .runtime_data(client, config.target.namespace.as_deref())
+        .await
+    {
+        Ok(runtime_data) => match runtime_data.mesh {
+            Some(vendor) => CheckResult::new(
+                MESH,
+                CheckStatus::Warning,
+                format!(
+                    "pod `{}` runs with {vendor}, mirroring might not see traffic, consider \
+                    stealing instead",
+                    runtime_data.pod_name
+                ),
+            ),
+            None => CheckResult::new(
+                MESH,
+                CheckStatus::Passed,
+                format!(
+                    "no service mesh detected in pod `{}`",
+                    runtime_data.pod_name
+                ),
+            ),
+        },
+        Err(error) => CheckResult::new(
+            MESH,
+            CheckStatus::Failed,
+            format!("failed to inspect target `{target}`: {error}"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::ImageReference;
+
+    #[rstest]
+    #[case(
+        "ghcr.io/metalbear-co/mirrord:3.100.0",
+        "ghcr.io",
+        "metalbear-co/mirrord",
+        "3.100.0"
+    )]
+    #[case("localhost:5000/mirrord", "localhost:5000", "mirrord", "latest")]
+    #[case("busybox", "registry-1.docker.io", "library/busybox", "latest")]
+    #[case(
+        "docker.io/busybox:1.36",
+        "registry-1.docker.io",
+        "library/busybox",
+        "1.36"
+    )]
+    #[case(
+        "metalbear/mirrord@sha256:abc",
+        "registry-1.docker.io",
+        "metalbear/mirrord",
+        "sha256:abc"
+    )]
+    fn parse_image_reference(
+        #[case] image: &str,
+        #[case] registry: &str,
+        #[case] repository: &str,
+        #[case] reference: &str,
+    ) {
+        assert_eq!(
+            ImageReference::parse(image),
+            ImageReference {
+                registry: registry.to_owned(),
+                repository: repository.to_owned(),
+                reference: reference.to_owned(),
+            }
+        );
+    }
+}
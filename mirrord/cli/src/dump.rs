@@ -2,18 +2,25 @@ use std::{
     collections::{HashMap, HashSet},
     convert::Infallible,
     fmt,
+    ops::Not,
     time::Duration,
 };
 
+use hyper::header::HeaderName;
 use mirrord_analytics::{AnalyticsReporter, CollectAnalytics, ExecutionKind, Reporter};
-use mirrord_config::{LayerConfig, config::ConfigContext, target::Target};
+use mirrord_config::{
+    LayerConfig,
+    config::{ConfigContext, ConfigError},
+    feature::network::incoming::http_filter::{HttpFilterConfig, HttpFilterParseError},
+    target::Target,
+};
 use mirrord_progress::{Progress, ProgressTracker};
 use mirrord_protocol::{
     ClientMessage, ConnectionId, DaemonMessage, LogLevel, LogMessage, RequestId, ResponseError,
     tcp::{
-        ChunkedRequest, DaemonTcp, HttpRequestMetadata, IncomingTrafficTransportType,
-        InternalHttpBodyFrame, InternalHttpRequest, LayerTcp, NewTcpConnectionV1,
-        NewTcpConnectionV2, TcpData,
+        self, ChunkedRequest, DaemonTcp, HttpFilter, IncomingTrafficTransportType,
+        InternalHttpBodyFrame, InternalHttpRequest, LayerTcp, MIRROR_HTTP_FILTER_VERSION,
+        NewTcpConnectionV1, NewTcpConnectionV2, TcpData,
     },
 };
 use mirrord_protocol_io::{Client, Connection};
@@ -31,7 +38,8 @@ use crate::{CliError, connection::create_and_connect, error::CliResult, user_dat
 ///
 /// This command:
 /// 1. Starts a mirrord session using the given config file and target arguments
-/// 2. Subscribes to mirror traffic from the specified ports
+/// 2. Subscribes to mirror traffic from the specified ports (only the HTTP requests matching the
+///    configured HTTP filter with `--steal-dry-run`)
 /// 3. Prints all incoming traffic to stdout in a human friendly format
 pub async fn dump_command(
     args: &DumpArgs,
@@ -62,6 +70,20 @@ pub async fn dump_command(
     if !args.params.disable_version_check {
         super::prompt_outdated_version(&progress).await;
     }
+    let dry_run_filter = if args.steal_dry_run {
+        let config = config.feature.network.incoming.http_filter.clone();
+        if config.is_filter_set().not() {
+            return Err(DumpSessionError::MissingHttpFilter.into());
+        }
+        let filter = config
+            .as_protocol_http_filter()
+            .map_err(DumpSessionError::InvalidHttpFilter)?;
+
+        Some(DryRunFilter::new(config, filter)?)
+    } else {
+        None
+    };
+
     // Collect analytics
     (&config).collect_analytics(analytics.get_mut());
 
@@ -70,7 +92,7 @@ pub async fn dump_command(
         create_and_connect(&mut config, &mut progress, &mut analytics, None, None).await?;
 
     // Start the dump session
-    let session = DumpSession::new(connection, args.ports.clone());
+    let session = DumpSession::new(connection, args.ports.clone(), dry_run_filter);
     session.run(&mut progress).await?;
 
     Ok(())
@@ -90,6 +112,25 @@ pub enum DumpSessionError {
 
     #[error("port subscription failed: {0}")]
     PortSubscriptionFailed(ResponseError),
+
+    #[error("`--steal-dry-run` requires `feature.network.incoming.http_filter` in the config")]
+    MissingHttpFilter,
+
+    #[error("invalid HTTP filter: {0}")]
+    InvalidHttpFilter(#[source] HttpFilterParseError),
+
+    #[error("invalid HTTP header filter: {0}")]
+    InvalidHeaderFilter(String),
+
+    #[error(
+        "mirrord-agent does not support filtering mirrored HTTP traffic, \
+        protocol version used by mirrord-agent must match {}",
+        *MIRROR_HTTP_FILTER_VERSION
+    )]
+    MirrorHttpFilterNotSupported,
+
+    #[error(transparent)]
+    HttpFilterNotSupported(ConfigError),
 }
 
 impl From<mpsc::error::SendError<ClientMessage>> for DumpSessionError {
@@ -98,6 +139,100 @@ impl From<mpsc::error::SendError<ClientMessage>> for DumpSessionError {
     }
 }
 
+/// HTTP filter used with `mirrord dump --steal-dry-run`.
+struct DryRunFilter {
+    config: HttpFilterConfig,
+    filter: HttpFilter,
+    /// Header conditions found in [`Self::filter`], used to report which header made the request
+    /// match.
+    header_filters: Vec<HeaderCondition>,
+}
+
+/// [`HttpFilter::Header`] or [`HttpFilter::HeaderPrefix`] found in [`DryRunFilter::filter`],
+/// compiled like in the agent.
+enum HeaderCondition {
+    Regex(fancy_regex::Regex),
+    Prefix { name: HeaderName, prefix: String },
+}
+
+impl DryRunFilter {
+    fn new(config: HttpFilterConfig, filter: HttpFilter) -> Result<Self, DumpSessionError> {
+        fn collect(
+            filter: &HttpFilter,
+            conditions: &mut Vec<HeaderCondition>,
+        ) -> Result<(), DumpSessionError> {
+            match filter {
+                HttpFilter::Header(header) => {
+                    let regex = header.header_regex().map_err(|error| {
+                        DumpSessionError::InvalidHeaderFilter(error.to_string())
+                    })?;
+                    conditions.push(HeaderCondition::Regex(regex));
+                }
+                HttpFilter::HeaderPrefix { name, prefix } => {
+                    let name = HeaderName::from_bytes(name.as_bytes()).map_err(|error| {
+                        DumpSessionError::InvalidHeaderFilter(error.to_string())
+                    })?;
+                    conditions.push(HeaderCondition::Prefix {
+                        name,
+                        prefix: prefix.clone(),
+                    });
+                }
+                HttpFilter::Composite { filters, .. } => {
+                    for filter in filters {
+                        collect(filter, conditions)?;
+                    }
+                }
+                // Headers matching an excluded filter don't make the request match.
+                HttpFilter::Not(..)
+                | HttpFilter::Path(..)
+                | HttpFilter::Method(..)
+                | HttpFilter::Body(..) => {}
+            }
+
+            Ok(())
+        }
+
+        let mut header_filters = Vec::new();
+        collect(&filter, &mut header_filters)?;
+
+        Ok(Self {
+            config,
+            filter,
+            header_filters,
+        })
+    }
+
+    /// Returns the headers of the `request` (as `name: value`) that match any of the header
+    /// filters.
+    fn matched_headers<B>(&self, request: &InternalHttpRequest<B>) -> Vec<String> {
+        let lines = tcp::header_lines(&request.headers, &request.uri);
+
+        let mut matched = Vec::new();
+        for condition in &self.header_filters {
+            let headers = match condition {
+                HeaderCondition::Regex(regex) => lines
+                    .iter()
+                    .filter(|line| regex.is_match(line).unwrap_or(false))
+                    .cloned()
+                    .collect::<Vec<_>>(),
+                HeaderCondition::Prefix { name, prefix } => {
+                    tcp::header_values_with_prefix(&request.headers, name, prefix)
+                        .filter_map(|value| Some(format!("{name}: {}", value.to_str().ok()?)))
+                        .collect()
+                }
+            };
+
+            for header in headers {
+                if matched.contains(&header).not() {
+                    matched.push(header);
+                }
+            }
+        }
+
+        matched
+    }
+}
+
 /// Implements `mirrord dump` logic on an established [`Connection`].
 struct DumpSession {
     connection: Connection<Client>,
@@ -114,10 +249,17 @@ struct DumpSession {
     ///
     /// Used when handling [`DaemonTcp::Close`].
     conn_id_to_req_id: HashMap<ConnectionId, HashSet<RequestId>>,
+    /// If set, we only mirror HTTP requests that match this filter, and report them as requests
+    /// that would be stolen.
+    dry_run_filter: Option<DryRunFilter>,
 }
 
 impl DumpSession {
-    fn new(connection: Connection<Client>, ports: Vec<u16>) -> Self {
+    fn new(
+        connection: Connection<Client>,
+        ports: Vec<u16>,
+        dry_run_filter: Option<DryRunFilter>,
+    ) -> Self {
         let mut ping_interval = tokio::time::interval(Duration::from_secs(30));
         ping_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
@@ -128,6 +270,7 @@ impl DumpSession {
             ping_interval,
             queued_messages: Default::default(),
             conn_id_to_req_id: Default::default(),
+            dry_run_filter,
         }
     }
 
@@ -135,7 +278,7 @@ impl DumpSession {
     ///
     /// 1. Negotiates [`mirrord_protocol`] version.
     /// 2. Signals readiness for logs.
-    /// 3. Issues port subscriptions.
+    /// 3. Issues port subscriptions, filtered if [`Self::dry_run_filter`] is set.
     async fn init_connection(&mut self) -> Result<(), DumpSessionError> {
         self.connection
             .send(ClientMessage::SwitchProtocolVersion(
@@ -150,13 +293,30 @@ impl DumpSession {
         {
             DaemonMessage::SwitchProtocolVersionResponse(version) => {
                 debug!("Established mirrord-protocol version {version}");
+
+                if let Some(dry_run_filter) = &self.dry_run_filter {
+                    if MIRROR_HTTP_FILTER_VERSION.matches(&version).not() {
+                        return Err(DumpSessionError::MirrorHttpFilterNotSupported);
+                    }
+
+                    dry_run_filter
+                        .config
+                        .ensure_usable_with(Some(version))
+                        .map_err(DumpSessionError::HttpFilterNotSupported)?;
+                }
             }
             other => return Err(DumpSessionError::UnexpectedAgentMessage(Box::new(other))),
         }
         self.connection.send(ClientMessage::ReadyForLogs).await;
 
         for port in &self.ports {
-            let message = ClientMessage::Tcp(LayerTcp::PortSubscribe(*port));
+            let message = match &self.dry_run_filter {
+                Some(dry_run_filter) => ClientMessage::Tcp(LayerTcp::PortSubscribeFilteredHttp(
+                    *port,
+                    dry_run_filter.filter.clone(),
+                )),
+                None => ClientMessage::Tcp(LayerTcp::PortSubscribe(*port)),
+            };
             self.connection.send(message).await;
             info!("Issued subscription to port {} for mirroring", port);
         }
@@ -182,6 +342,13 @@ impl DumpSession {
                     self.confirmations += 1;
                     if self.confirmations == self.ports.len() {
                         tracing::debug!("All subscriptions confirmed");
+                        if let Some(dry_run_filter) = &self.dry_run_filter {
+                            progress.info(&format!(
+                                "Reporting requests matching the HTTP filter `{}`, \
+                                they are still handled by the target",
+                                dry_run_filter.filter
+                            ));
+                        }
                         progress.info("Listening for traffic... Press Ctrl+C to stop");
                         progress.success(Some("Subscribed to all ports"));
                         for message in std::mem::take(&mut self.queued_messages) {
@@ -228,11 +395,20 @@ impl DumpSession {
                     }
                 }
             }
+            DaemonTcp::MirrorDropped(dropped) => {
+                println!(
+                    "## Port {}: agent dropped {} connections ({} bytes) over the limit",
+                    dropped.port, dropped.connections, dropped.bytes
+                );
+            }
+            DaemonTcp::ShutdownWrite(shutdown) => {
+                println!(
+                    "## Connection ID {}: remote peer shut down writing",
+                    shutdown.connection_id
+                );
+            }
             DaemonTcp::HttpRequest(req) => {
-                self.conn_id_to_req_id
-                    .entry(req.connection_id)
-                    .or_default()
-                    .insert(req.request_id);
+                self.request_started(req.connection_id, req.request_id, &req.internal_request);
                 println!(
                     "## New HTTP request received: Request ID [{}:{}] to port {}",
                     req.connection_id, req.request_id, req.port,
@@ -252,10 +428,7 @@ impl DumpSession {
                 }
             }
             DaemonTcp::HttpRequestFramed(req) => {
-                self.conn_id_to_req_id
-                    .entry(req.connection_id)
-                    .or_default()
-                    .insert(req.request_id);
+                self.request_started(req.connection_id, req.request_id, &req.internal_request);
                 println!(
                     "## New HTTP request received: Request ID [{}:{}] to port {}",
                     req.connection_id, req.request_id, req.port,
@@ -274,10 +447,7 @@ impl DumpSession {
             }
             DaemonTcp::HttpRequestChunked(chunked) => match chunked {
                 ChunkedRequest::StartV1(req) => {
-                    self.conn_id_to_req_id
-                        .entry(req.connection_id)
-                        .or_default()
-                        .insert(req.request_id);
+                    self.request_started(req.connection_id, req.request_id, &req.internal_request);
                     println!(
                         "## New HTTP request received: Request ID [{}:{}] to port {}",
                         req.connection_id, req.request_id, req.port,
@@ -295,14 +465,9 @@ impl DumpSession {
                     }
                 }
                 ChunkedRequest::StartV2(req) => {
-                    self.conn_id_to_req_id
-                        .entry(req.connection_id)
-                        .or_default()
-                        .insert(req.request_id);
-                    let HttpRequestMetadata::V1 {
-                        source,
-                        destination,
-                    } = &req.metadata;
+                    self.request_started(req.connection_id, req.request_id, &req.request);
+                    let source = req.metadata.source();
+                    let destination = req.metadata.destination();
                     println!(
                         "## New {} request received: Request ID [{}:{}] from {source} to {destination}",
                         match &req.transport {
@@ -403,6 +568,34 @@ impl DumpSession {
         Ok(())
     }
 
+    /// Remembers the request for [`DaemonTcp::Close`] handling.
+    ///
+    /// With [`Self::dry_run_filter`], also prints a short summary of the request that would be
+    /// stolen, including the headers that matched the filter.
+    fn request_started<B>(
+        &mut self,
+        connection_id: ConnectionId,
+        request_id: RequestId,
+        request: &InternalHttpRequest<B>,
+    ) {
+        self.conn_id_to_req_id
+            .entry(connection_id)
+            .or_default()
+            .insert(request_id);
+
+        if let Some(dry_run_filter) = &self.dry_run_filter {
+            let matched = dry_run_filter
+                .matched_headers(request)
+                .into_iter()
+                .map(|header| format!(", matched header `{header}`"))
+                .collect::<String>();
+            println!(
+                "## Would steal: {} {} (Request ID [{connection_id}:{request_id}]{matched})",
+                request.method, request.uri,
+            );
+        }
+    }
+
     async fn run(mut self, progress: &mut ProgressTracker) -> Result<Infallible, DumpSessionError> {
         self.init_connection().await?;
 
@@ -430,12 +623,28 @@ impl DumpSession {
                 DaemonMessage::Close(message) => {
                     return Err(DumpSessionError::AgentConnClosed(Some(message)));
                 }
+                DaemonMessage::GoingDown(notice) => {
+                    tracing::warn!(%notice, "Agent is shutting down");
+                    self.connection.send(ClientMessage::GoingDownAck).await;
+                }
                 DaemonMessage::Pong => continue,
                 DaemonMessage::LogMessage(LogMessage { level, message }) => match level {
                     LogLevel::Error => tracing::error!("Received log: {message}"),
                     LogLevel::Warn => tracing::warn!("Received log: {message}"),
                     LogLevel::Info => tracing::warn!("Received log: {message}"),
                 },
+                DaemonMessage::DisabledFeatures(features) => {
+                    tracing::debug!(?features, "Agent has some features disabled");
+                }
+                DaemonMessage::MandatoryHttpFilter(filter) => {
+                    tracing::debug!(%filter, "Agent requires an HTTP filter");
+                }
+                DaemonMessage::SessionQuotas(quotas) => {
+                    tracing::debug!(?quotas, "Agent enforces session quotas");
+                }
+                DaemonMessage::Capabilities(capabilities) => {
+                    tracing::debug!(?capabilities, "Agent reported its capabilities");
+                }
                 message @ (DaemonMessage::File(..)
                 | DaemonMessage::GetAddrInfoResponse(..)
                 | DaemonMessage::GetEnvVarsResponse(..)
@@ -445,7 +654,12 @@ impl DumpSession {
                 | DaemonMessage::UdpOutgoing(..)
                 | DaemonMessage::Vpn(..)
                 | DaemonMessage::TcpSteal(..)
-                | DaemonMessage::ReverseDnsLookup(..)) => {
+                | DaemonMessage::ReverseDnsLookup(..)
+                | DaemonMessage::LogEvent(..)
+                | DaemonMessage::IcmpEcho(..)
+                | DaemonMessage::GetInterfaces(..)
+                | DaemonMessage::ResourceUsage(..)
+                | DaemonMessage::ListeningPorts(..)) => {
                     return Err(DumpSessionError::UnexpectedAgentMessage(Box::new(message)));
                 }
             }
@@ -505,3 +719,54 @@ impl fmt::Display for RequestFrame {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use hyper::{Method, Request, Version};
+    use mirrord_config::feature::network::incoming::http_filter::HttpFilterConfig;
+    use mirrord_protocol::tcp::{Filter, HttpFilter, InternalHttpRequest};
+
+    use super::DryRunFilter;
+
+    /// Verifies that the dry-run reports the headers that matched the filter, ignoring the ones
+    /// matched only by an excluded filter.
+    #[test]
+    fn reports_matched_headers() {
+        let header = |regex: &str| HttpFilter::Header(Filter::new(regex.to_string()).unwrap());
+        let filter = HttpFilter::Composite {
+            all: true,
+            filters: vec![
+                header("^x-user: alice$"),
+                HttpFilter::HeaderPrefix {
+                    name: "x-request-id".to_string(),
+                    prefix: "dev-".to_string(),
+                },
+                HttpFilter::Not(Box::new(header("^x-canary: "))),
+            ],
+        };
+        let dry_run_filter = DryRunFilter::new(HttpFilterConfig::default(), filter).unwrap();
+
+        let (parts, ()) = Request::builder()
+            .method(Method::GET)
+            .uri("/api")
+            .version(Version::HTTP_11)
+            .header("X-User", "alice")
+            .header("x-request-id", "dev-1234")
+            .header("x-canary", "false")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let request = InternalHttpRequest {
+            method: parts.method,
+            uri: parts.uri,
+            headers: parts.headers,
+            version: parts.version,
+            body: (),
+        };
+
+        assert_eq!(
+            dry_run_filter.matched_headers(&request),
+            ["x-user: alice", "x-request-id: dev-1234"]
+        );
+    }
+}
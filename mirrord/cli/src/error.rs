@@ -5,7 +5,7 @@ use axum::response::{IntoResponse, Response};
 use kube::{self, core::ErrorResponse};
 use miette::Diagnostic;
 use mirrord_auth::error::ApiKeyError;
-use mirrord_config::config::ConfigError;
+use mirrord_config::{config::ConfigError, migration::MigrateFileError};
 use mirrord_console::error::ConsoleError;
 use mirrord_intproxy::{
     agent_conn::{AgentConnectionError, ConnectionTlsError},
@@ -13,19 +13,26 @@ use mirrord_intproxy::{
 };
 use mirrord_kube::error::KubeApiError;
 use mirrord_operator::client::error::{HttpError, OperatorApiError, OperatorOperation};
+use mirrord_protocol::{ErrorCode, ResponseError};
 use mirrord_protocol_io::ProtocolError;
+use mirrord_sdk::SdkError;
 use mirrord_tls_util::SecureChannelError;
 use mirrord_vpn::error::VpnError;
 use reqwest::StatusCode;
 use thiserror::Error;
 
 use crate::{
+    agent::AgentPushError,
     ci::error::CiError,
     container::{CommandDisplay, IntproxySidecarError},
+    cp::CpError,
     dump::DumpSessionError,
     fix::FixKubeconfigError,
+    list_ports::ListPortsError,
     port_forward::PortForwardError,
     profile::ProfileError,
+    session::SessionError,
+    top::TopError,
 };
 
 pub(crate) type CliResult<T, E = CliError> = core::result::Result<T, E>;
@@ -311,6 +318,10 @@ pub(crate) enum CliError {
     #[diagnostic(help("{GENERAL_BUG}"))]
     CliPathError(std::io::Error),
 
+    #[error("Failed to spawn the process streaming the remote logs: {0}")]
+    #[diagnostic(help("{GENERAL_BUG}"))]
+    RemoteLogsSpawnError(std::io::Error),
+
     #[error("Failed to wait until internal proxy exits: {0}")]
     #[diagnostic(help("{GENERAL_BUG}"))]
     InternalProxyWaitError(std::io::Error),
@@ -452,6 +463,26 @@ pub(crate) enum CliError {
     #[error("mirrord dump session failed: {0}")]
     DumpError(#[from] DumpSessionError),
 
+    #[error("mirrord cp failed: {0}")]
+    CpError(#[from] CpError),
+
+    #[error("mirrord top failed: {0}")]
+    TopError(#[from] TopError),
+
+    #[error("mirrord ls-ports failed: {0}")]
+    ListPortsError(#[from] ListPortsError),
+
+    /// Error caused by a [`ResponseError`], displayed as `error[M0123]: <message>` with a link to
+    /// the docs of the [`ErrorCode`].
+    ///
+    /// Created with [`CliError::with_code`].
+    #[error("{}", .code.wrap(.error))]
+    #[diagnostic(help("{GENERAL_HELP}"))]
+    Coded {
+        code: ErrorCode,
+        error: Box<CliError>,
+    },
+
     #[error("Failed to copy the session target: {}", message.as_deref().unwrap_or("unknown reason"))]
     OperatorCopyTargetFailed { message: Option<String> },
 
@@ -472,6 +503,23 @@ pub(crate) enum CliError {
     ))]
     AgentPodDeleted,
 
+    #[error("Failed to pull the agent image `{image}`: {reason}")]
+    #[diagnostic(help(
+        "By default, mirrord uses the agent image with the same version as the mirrord CLI. \
+        If you're using an internal registry, push this image there with `mirrord agent push`, \
+        set `agent.image` to an image that is available, or set `agent.image_fallback_tag` to \
+        let mirrord retry with another tag.{GENERAL_HELP}"
+    ))]
+    AgentImagePullFailed { image: String, reason: String },
+
+    #[error("Agent uses mirrord-protocol {agent_version}, which this mirrord CLI does not support")]
+    #[diagnostic(help(
+        "The agent image is too old for this mirrord CLI. Use the agent image with the same \
+        version as the CLI, e.g. by removing the custom tag from `agent.image`, or update the \
+        agent image in your registry.{GENERAL_HELP}"
+    ))]
+    IncompatibleAgentVersion { agent_version: semver::Version },
+
     #[error("Detected mirrord being run within mirrord")]
     #[diagnostic(help(
         "Running mirrord within mirrord is likely to fail or introduce severe slowness. \
@@ -497,6 +545,18 @@ pub(crate) enum CliError {
     #[error("error while fixing kubeconfig")]
     FixKubeconfig(#[from] FixKubeconfigError),
 
+    #[error("Failed to migrate the config file `{0}`: {1}")]
+    #[diagnostic(help("Please check that the config file is valid.{GENERAL_HELP}"))]
+    ConfigMigrate(PathBuf, MigrateFileError),
+
+    #[error("failed to push the agent image")]
+    #[diagnostic(help("{GENERAL_HELP}"))]
+    AgentPush(#[from] AgentPushError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Session(#[from] SessionError),
+
     #[error("No image specified for preview environment")]
     #[diagnostic(help(
         "Specify the image using `-i <image>` or set `feature.preview.image` in your mirrord config file."
@@ -582,6 +642,10 @@ pub(crate) enum CliError {
         Please check that the target exists and has running pods.{GENERAL_HELP}"
     ))]
     RuntimeDataResolution(KubeApiError),
+
+    #[error("Failed to write the diagnostic bundle to `{0}`: {1}")]
+    #[diagnostic(help("Use `--output` to choose a different location.{GENERAL_HELP}"))]
+    DiagnosticBundleWrite(PathBuf, std::io::Error),
 }
 
 impl CliError {
@@ -608,9 +672,35 @@ impl CliError {
                 Self::InvalidCertificate(error)
             }
             KubeApiError::AgentPodDeleted => Self::AgentPodDeleted,
+            KubeApiError::AgentImagePullFailed { image, reason } => {
+                Self::AgentImagePullFailed { image, reason }
+            }
             error => fallback(error),
         }
     }
+
+    /// Returns the [`ResponseError`] that caused this error, if any.
+    fn response_error(&self) -> Option<&ResponseError> {
+        match self {
+            Self::DumpError(DumpSessionError::PortSubscriptionFailed(error))
+            | Self::CpError(CpError::Remote { error, .. })
+            | Self::TopError(TopError::Remote(error))
+            | Self::ListPortsError(ListPortsError::Remote(error)) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Wraps this error in [`CliError::Coded`] if it was caused by a [`ResponseError`], so that
+    /// the top-level error output starts with its [`ErrorCode`].
+    pub(crate) fn with_code(self) -> Self {
+        match self.response_error().map(ResponseError::code) {
+            Some(code) => Self::Coded {
+                code,
+                error: Box::new(self),
+            },
+            None => self,
+        }
+    }
 }
 
 impl From<OperatorApiError> for CliError {
@@ -682,6 +772,27 @@ impl From<OperatorApiError> for CliError {
     }
 }
 
+impl From<SdkError> for CliError {
+    fn from(value: SdkError) -> Self {
+        match value {
+            SdkError::OperatorNotInstalled => Self::OperatorNotInstalled,
+            SdkError::Operator(error) => error.into(),
+            SdkError::OperatorTargetResolution(error) => Self::OperatorTargetResolution(error),
+            SdkError::MissingCiApiKey => CiError::MissingCiApiKey.into(),
+            SdkError::FeatureRequiresOperator(feature) => {
+                Self::FeatureRequiresOperatorError(feature)
+            }
+            SdkError::CreateAgent(error) => {
+                Self::friendlier_error_or_else(error, Self::CreateAgentFailed)
+            }
+            SdkError::AgentConnection(error) => {
+                Self::friendlier_error_or_else(error, Self::AgentConnectionFailed)
+            }
+            SdkError::Protocol(error) => error.into(),
+        }
+    }
+}
+
 impl From<ProtocolError> for CliError {
     fn from(e: ProtocolError) -> Self {
         Self::InitialAgentCommFailed(e.to_string())
@@ -722,6 +833,29 @@ mod tests {
     use tokio::{net::TcpListener, sync::Notify};
     use tokio_rustls::TlsAcceptor;
 
+    /// Errors caused by a [`ResponseError`](mirrord_protocol::ResponseError) start with their
+    /// code, and end with a link to its docs.
+    #[test]
+    fn response_errors_are_displayed_with_code() {
+        use mirrord_protocol::{ERROR_CODES_DOCS_URL, ResponseError};
+
+        use super::CliError;
+        use crate::top::TopError;
+
+        let error = CliError::from(TopError::Remote(ResponseError::NotImplemented)).with_code();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "error[M0008]: mirrord top failed: failed to read the resource usage of the \
+                target: Operation is not yet supported by mirrord.\n  see: \
+                {ERROR_CODES_DOCS_URL}#m0008"
+            )
+        );
+
+        let error = CliError::NestedExec.with_code();
+        assert!(matches!(error, CliError::NestedExec), "{error:?}");
+    }
+
     /// With this test we're trying to `assert` that our [`kube`] crate is (somewhat)
     /// version-synced with [`rustls`]. To give a friendlier error message on kube requests
     /// when there's a certificate problem, we must dig down into the [`kube::Error`].
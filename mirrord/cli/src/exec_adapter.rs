@@ -0,0 +1,242 @@
+//! Adjusting `mirrord exec --exec-adapter` to common test runners, see [`ExecAdapter`].
+//!
+//! Test runners spawn processes of their own: workers (`pytest-xdist`, `jest-worker`), compiled
+//! test binaries (`go test`), and helper tools (`go vet`, `find`, `watchman`). The workers inherit
+//! the environment of the runner, so they load the layer and connect to the same internal proxy,
+//! sharing one agent with the runner. The helper tools should run without mirrord, and the files
+//! of the project (sources, caches) should stay local, even when `feature.fs.mode` is `"write"`.
+
+use std::{
+    ffi::OsStr,
+    fmt,
+    ops::Not,
+    path::{Path, PathBuf},
+};
+
+use mirrord_config::{LayerConfig, util::VecOrSingle};
+
+/// A test runner that `mirrord exec` knows how to wrap, detected from the command line with
+/// [`ExecAdapter::detect`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ExecAdapter {
+    /// `pytest`, possibly with `pytest-xdist` workers.
+    Pytest,
+    /// `go test`, which compiles and runs a test binary for each package.
+    GoTest,
+    /// `jest`, which runs the tests in `jest-worker` child processes.
+    Jest,
+}
+
+impl ExecAdapter {
+    /// Programs that run other programs, e.g. `npx jest` or `uv run pytest`.
+    const LAUNCHERS: &[&str] = &[
+        "npx", "pnpx", "bunx", "yarn", "pnpm", "uv", "poetry", "pipenv",
+    ];
+
+    /// Detects the test runner from the command given to `mirrord exec`.
+    pub(crate) fn detect(binary: &str, args: &[String]) -> Option<Self> {
+        let name = Path::new(binary)
+            .file_name()
+            .and_then(OsStr::to_str)
+            .unwrap_or(binary);
+
+        match name {
+            "pytest" | "py.test" => Some(Self::Pytest),
+            "jest" => Some(Self::Jest),
+            "go" => (args.first().map(String::as_str) == Some("test")).then_some(Self::GoTest),
+            python if python.starts_with("python") => match args {
+                [flag, module, ..] if flag == "-m" && module == "pytest" => Some(Self::Pytest),
+                _ => None,
+            },
+            launcher if Self::LAUNCHERS.contains(&launcher) => {
+                let command = args
+                    .iter()
+                    .position(|arg| arg.starts_with('-').not() && arg != "run" && arg != "exec")?;
+                let (binary, args) = args.get(command..)?.split_first()?;
+                Self::detect(binary, args)
+            }
+            _ => None,
+        }
+    }
+
+    /// Processes spawned by the runner that should run without mirrord, added to
+    /// `skip_extra_build_tools`.
+    fn skipped_tools(self) -> &'static [&'static str] {
+        match self {
+            Self::Pytest => &[],
+            // `go test` runs `go vet` before the tests, the rest of the toolchain is skipped
+            // by default.
+            Self::GoTest => &["vet", "cover", "buildid", "test2json"],
+            // Crawling the project for the haste map.
+            Self::Jest => &["find", "watchman"],
+        }
+    }
+
+    /// Patterns of the files that the runner keeps locally, added to `feature.fs.local`.
+    fn local_files(self) -> &'static [&'static str] {
+        match self {
+            Self::Pytest => &[r"/\.pytest_cache/", r"/__pycache__/"],
+            Self::GoTest => &[r"/go-build"],
+            Self::Jest => &[r"/node_modules/", r"/jest_[^/]*/"],
+        }
+    }
+
+    /// Adjusts the config to the runner, see the [module docs](self).
+    ///
+    /// `project_dir` is the working directory of the runner, its files are kept local. The
+    /// [`ExecAdapter::environment`] is added to `feature.env.override`, so that it reaches the
+    /// runner in every `mirrord exec` mode (`--watch`, `--warm`).
+    pub(crate) fn apply(self, config: &mut LayerConfig, project_dir: Option<PathBuf>) {
+        extend(
+            &mut config.skip_extra_build_tools,
+            self.skipped_tools().iter().map(ToString::to_string),
+        );
+
+        let project_files =
+            project_dir.map(|dir| format!("^{}/", regex::escape(&dir.to_string_lossy())));
+        extend(
+            &mut config.feature.fs.local,
+            self.local_files()
+                .iter()
+                .map(ToString::to_string)
+                .chain(project_files),
+        );
+
+        let overrides = config
+            .feature
+            .env
+            .r#override
+            .get_or_insert_with(Default::default);
+        let environment = self.environment(|key| {
+            overrides
+                .get(key)
+                .cloned()
+                .or_else(|| std::env::var(key).ok())
+        });
+        overrides.extend(
+            environment
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value)),
+        );
+        if overrides.is_empty() {
+            config.feature.env.r#override = None;
+        }
+    }
+
+    /// Environment variables to set for the runner, given a getter of the current ones.
+    ///
+    /// `go test` caches the results of the packages that didn't change, which would skip running
+    /// them against the cluster.
+    fn environment<F>(self, var: F) -> Vec<(&'static str, String)>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        match self {
+            Self::GoTest => {
+                let goflags = var("GOFLAGS").unwrap_or_default();
+                if goflags.contains("-count=") {
+                    vec![]
+                } else {
+                    vec![("GOFLAGS", format!("{goflags} -count=1").trim().to_string())]
+                }
+            }
+            Self::Pytest | Self::Jest => vec![],
+        }
+    }
+}
+
+impl fmt::Display for ExecAdapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pytest => "pytest",
+            Self::GoTest => "go test",
+            Self::Jest => "jest",
+        })
+    }
+}
+
+/// Appends the `values` to a config list, keeping the existing ones.
+fn extend<I: IntoIterator<Item = String>>(list: &mut Option<VecOrSingle<String>>, values: I) {
+    let mut extended: Vec<String> = list.take().map(Vec::from).unwrap_or_default();
+    for value in values {
+        if extended.contains(&value).not() {
+            extended.push(value);
+        }
+    }
+
+    *list = extended.is_empty().not().then(|| extended.into());
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use mirrord_config::{
+        LayerFileConfig,
+        config::{ConfigContext, MirrordConfig},
+    };
+    use rstest::rstest;
+
+    use super::ExecAdapter;
+
+    #[rstest]
+    #[case("pytest", &["-n", "4"], Some(ExecAdapter::Pytest))]
+    #[case("/usr/bin/python3", &["-m", "pytest", "tests/"], Some(ExecAdapter::Pytest))]
+    #[case("python3", &["-m", "http.server"], None)]
+    #[case("uv", &["run", "pytest"], Some(ExecAdapter::Pytest))]
+    #[case("go", &["test", "./..."], Some(ExecAdapter::GoTest))]
+    #[case("go", &["run", "."], None)]
+    #[case("npx", &["jest", "--runInBand"], Some(ExecAdapter::Jest))]
+    #[case("yarn", &["--silent", "run", "jest"], Some(ExecAdapter::Jest))]
+    #[case("node", &["server.js"], None)]
+    fn detects_runners(
+        #[case] binary: &str,
+        #[case] args: &[&str],
+        #[case] expected: Option<ExecAdapter>,
+    ) {
+        let args = args.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(ExecAdapter::detect(binary, &args), expected);
+    }
+
+    #[rstest]
+    #[case(&[], &[("GOFLAGS", "-count=1")])]
+    #[case(&[("GOFLAGS", "-race")], &[("GOFLAGS", "-race -count=1")])]
+    #[case(&[("GOFLAGS", "-count=3")], &[])]
+    fn go_test_results_are_not_cached(
+        #[case] existing: &[(&str, &str)],
+        #[case] expected: &[(&str, &str)],
+    ) {
+        let existing = existing.iter().copied().collect::<HashMap<_, _>>();
+        let environment =
+            ExecAdapter::GoTest.environment(|key| existing.get(key).map(ToString::to_string));
+
+        let environment = environment
+            .iter()
+            .map(|(key, value)| (*key, value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(environment, expected);
+    }
+
+    /// Verifies that the environment of the runner is passed with `feature.env.override`, on top
+    /// of the user's overrides.
+    #[test]
+    fn environment_is_overridden() {
+        let mut config = LayerFileConfig::default()
+            .generate_config(&mut ConfigContext::default().strict_env(true))
+            .unwrap();
+        config.feature.env.r#override = Some(HashMap::from([
+            ("GOFLAGS".to_string(), "-race".to_string()),
+            ("APP_ENV".to_string(), "test".to_string()),
+        ]));
+
+        ExecAdapter::GoTest.apply(&mut config, None);
+
+        assert_eq!(
+            config.feature.env.r#override,
+            Some(HashMap::from([
+                ("GOFLAGS".to_string(), "-race -count=1".to_string()),
+                ("APP_ENV".to_string(), "test".to_string()),
+            ]))
+        );
+    }
+}
@@ -3,17 +3,24 @@ use std::ffi::OsString;
 use std::{
     collections::{HashMap, HashSet},
     net::SocketAddr,
+    ops::Not,
     time::Duration,
 };
 
 use mirrord_analytics::{AnalyticsError, AnalyticsReporter, Reporter};
 use mirrord_config::{
-    LayerConfig, MIRRORD_LAYER_INTPROXY_ADDR, config::ConfigError,
-    external_proxy::MIRRORD_EXTPROXY_TLS_SETUP_PEM, feature::env::mapper::EnvVarsRemapper,
+    LayerConfig, MIRRORD_LAYER_INTPROXY_ADDR,
+    config::ConfigError,
+    external_proxy::MIRRORD_EXTPROXY_TLS_SETUP_PEM,
+    feature::env::{mapper::EnvVarsRemapper, rename::EnvVarsRenamer},
+    target::Target,
 };
 use mirrord_intproxy::agent_conn::AgentConnectInfo;
-use mirrord_progress::Progress;
-use mirrord_protocol::{ClientMessage, DaemonMessage, EnvVars, GetEnvVarsRequest, LogLevel};
+use mirrord_progress::{ExecPhase, Progress};
+use mirrord_protocol::{
+    COMPATIBLE_AGENT_VERSIONS, ClientMessage, DaemonMessage, EnvVars, GetEnvVarsRequest, LogLevel,
+    capabilities::{AGENT_CAPABILITIES_VERSION, AgentCapabilities},
+};
 use mirrord_protocol_io::{Client, Connection};
 #[cfg(target_os = "macos")]
 use mirrord_sip::{SipError, SipPatchOptions, sip_patch};
@@ -31,13 +38,18 @@ use tracing::{Level, debug, error, info, trace, warn};
 
 #[cfg(target_os = "macos")]
 use crate::extract::extract_arm64;
+#[cfg(not(target_os = "linux"))]
+use crate::extract::extract_library;
+#[cfg(target_os = "linux")]
+use crate::extract::extract_library_for;
 #[cfg(unix)]
 use crate::util::reparent_to_init;
 use crate::{
     CliResult, MirrordCi,
     connection::{AGENT_CONNECT_INFO_ENV_KEY, create_and_connect},
+    container_limits::ContainerLimits,
     error::CliError,
-    extract::extract_library,
+    kube::{cluster_service_ips, kube_client_from_layer_config},
     util::{get_user_git_branch, remove_proxy_env},
 };
 
@@ -72,6 +84,9 @@ pub(crate) struct MirrordExecution {
 
     /// Whether this run uses mirrord operator.
     pub uses_operator: bool,
+
+    /// Address on which the proxy process listens.
+    pub proxy_address: SocketAddr,
 }
 
 /// Struct that when dropped will cancel the token and wait on the join handle
@@ -150,6 +165,47 @@ where
 }
 
 impl MirrordExecution {
+    /// Patches the executable for SIP sidestepping, if needed.
+    ///
+    /// Returns the path to the patched executable.
+    #[cfg(target_os = "macos")]
+    pub(crate) fn sip_patch_executable(
+        config: &LayerConfig,
+        executable: &str,
+        args: Option<&[OsString]>,
+    ) -> CliResult<Option<String>> {
+        let log_info = config
+            .experimental
+            .sip_log_destination
+            .as_ref()
+            .map(|log_destination| mirrord_sip::SipLogInfo {
+                log_destination,
+                args,
+                load_type: None,
+            });
+
+        let patched_path = sip_patch(
+            executable,
+            SipPatchOptions {
+                patch: &config
+                    .sip_binaries
+                    .clone()
+                    .map(|x| x.to_vec())
+                    .unwrap_or_default(),
+                skip: &config.skip_sip,
+            },
+            log_info,
+        )
+        .inspect_err(|sip_error| {
+            // we can't recover from hitting the fd limit, so we have to exit fully
+            if let SipError::TooManyFilesOpen(..) = sip_error {
+                panic!("mirrord failed to patch SIP with: {}", sip_error);
+            }
+        })?;
+
+        Ok(patched_path)
+    }
+
     /// Makes the agent connection and starts the internal proxy child process.
     ///
     /// # Internal proxy
@@ -181,12 +237,14 @@ impl MirrordExecution {
     #[tracing::instrument(level = Level::DEBUG, skip_all, ret, err(level = Level::DEBUG))]
     pub(crate) async fn start_internal<P>(
         config: &mut LayerConfig,
-        // We only need the executable and args on macos, for SIP handling.
-        #[cfg(target_os = "macos")] executable: Option<&str>,
+        // We need the executable on macos for SIP handling, and on linux to pick the layer that
+        // it can load. The args are only needed on macos, for SIP handling.
+        #[cfg(any(target_os = "macos", target_os = "linux"))] executable: Option<&str>,
         #[cfg(target_os = "macos")] args: Option<&[OsString]>,
         progress: &mut P,
         analytics: &mut AnalyticsReporter,
         mirrord_for_ci: Option<&MirrordCi>,
+        session: Option<&str>,
     ) -> CliResult<Self>
     where
         P: Progress,
@@ -203,7 +261,14 @@ impl MirrordExecution {
             }
             Err(_) => {
                 tracing::debug!("MIRRORD_LAYER_FILE not set, extracting library from binary");
-                extract_library(None, progress, true)?
+                #[cfg(target_os = "linux")]
+                {
+                    extract_library_for(executable, progress)?
+                }
+                #[cfg(not(target_os = "linux"))]
+                {
+                    extract_library(None, progress, true)?
+                }
             }
         };
 
@@ -218,14 +283,60 @@ impl MirrordExecution {
                 .await
                 .inspect_err(|_| analytics.set_error(AnalyticsError::AgentConnection))?;
 
-        let agent_protocol_version = match &connect_info {
-            AgentConnectInfo::Operator(session) => session.operator_protocol_version.clone(),
+        let (agent_protocol_version, agent_capabilities) = match &connect_info {
+            AgentConnectInfo::Operator(session) => {
+                let capabilities = session
+                    .operator_protocol_version
+                    .as_ref()
+                    .map(AgentCapabilities::from_protocol_version);
+                (session.operator_protocol_version.clone(), capabilities)
+            }
             AgentConnectInfo::DirectKubernetes(_) => {
-                Some(MirrordExecution::get_agent_version(&mut connection).await?)
+                let (version, capabilities) =
+                    MirrordExecution::get_agent_version(&mut connection).await?;
+                (Some(version), Some(capabilities))
+            }
+            #[cfg(all(unix, feature = "fake-cluster"))]
+            AgentConnectInfo::LocalSocket(_) => {
+                let (version, capabilities) =
+                    MirrordExecution::get_agent_version(&mut connection).await?;
+                (Some(version), Some(capabilities))
             }
-            _ => None,
+            _ => (None, None),
         };
 
+        if let Some((version, capabilities)) =
+            agent_protocol_version.as_ref().zip(agent_capabilities)
+        {
+            config.ensure_supported_by(&capabilities, version)?;
+        }
+
+        let incoming = &config.feature.network.incoming;
+        if let Some(sni_filter) = incoming.sni_filter.as_ref().filter(|_| incoming.is_steal()) {
+            sni_filter.ensure_usable_with(agent_protocol_version.as_ref())?;
+        }
+        if let Some(postgres_filter) = incoming
+            .postgres_filter
+            .as_ref()
+            .filter(|_| incoming.is_steal())
+        {
+            postgres_filter.ensure_usable_with(agent_protocol_version.as_ref())?;
+        }
+        if let Some(redis_filter) = incoming
+            .redis_filter
+            .as_ref()
+            .filter(|_| incoming.is_steal())
+        {
+            redis_filter.ensure_usable_with(agent_protocol_version.as_ref())?;
+        }
+        if let Some(source_filter) = incoming
+            .source_filter
+            .as_ref()
+            .filter(|_| incoming.is_steal())
+        {
+            source_filter.ensure_usable_with(agent_protocol_version.as_ref())?;
+        }
+
         config
             .feature
             .network
@@ -236,6 +347,7 @@ impl MirrordExecution {
         let mut env_vars = if config.feature.env.load_from_process.unwrap_or(false) {
             Default::default()
         } else {
+            progress.phase(ExecPhase::FetchingEnvironment);
             Self::fetch_env_vars(config, &mut connection)
                 .await
                 .inspect_err(|_| analytics.set_error(AnalyticsError::EnvFetch))?
@@ -272,6 +384,10 @@ impl MirrordExecution {
             unsafe { std::env::set_var("MIRRORD_LAYER_FILE", lib_path) };
         }
 
+        if config.feature.network.outgoing.cluster_services {
+            Self::add_cluster_services(config, progress).await;
+        }
+
         let encoded_config = config.encode()?;
 
         let mut proxy_command =
@@ -282,13 +398,22 @@ impl MirrordExecution {
             proxy_command.arg("--mirrord-for-ci");
         }
 
+        if let Some(session) = session {
+            proxy_command.arg("--session").arg(session);
+        }
+
         proxy_command
             // Start of debug args. Don't add real args after this point,
             // `_debug_args` Clap field will swallow them.
             .arg("--log-destination")
             .arg(config.internal_proxy.log_destination.as_os_str())
             .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
+            // The internal proxy prints the forwarded agent logs straight to our stderr.
+            .stderr(if config.agent.forward_logs {
+                std::process::Stdio::inherit()
+            } else {
+                std::process::Stdio::piped()
+            })
             .stdin(std::process::Stdio::null())
             .kill_on_drop(true)
             .env(
@@ -302,12 +427,15 @@ impl MirrordExecution {
             proxy_command.pre_exec(|| reparent_to_init().map_err(Into::into));
         }
 
+        progress.phase(ExecPhase::StartingInternalProxy);
         let mut proxy_process = proxy_command.spawn().map_err(|e| {
             CliError::InternalProxySpawnError(format!("failed to spawn child process: {e}"))
         })?;
 
-        let stderr = proxy_process.stderr.take().expect("stderr was piped");
-        let _stderr_guard = watch_stderr(stderr, progress).await;
+        let _stderr_guard = match proxy_process.stderr.take() {
+            Some(stderr) => Some(watch_stderr(stderr, progress).await),
+            None => None,
+        };
 
         let stdout = proxy_process.stdout.take().expect("stdout was piped");
 
@@ -341,6 +469,7 @@ impl MirrordExecution {
                     "failed to parse port number printed by proxy: {e}"
                 ))
             })?;
+        progress.listening("intproxy", intproxy_address);
 
         env_vars.insert(LayerConfig::RESOLVED_CONFIG_ENV.into(), encoded_config);
         env_vars.insert(
@@ -348,42 +477,11 @@ impl MirrordExecution {
             intproxy_address.to_string(),
         );
 
-        #[cfg(target_os = "macos")]
-        let log_info = config
-            .experimental
-            .sip_log_destination
-            .as_ref()
-            .map(|log_destination| mirrord_sip::SipLogInfo {
-                log_destination,
-                args,
-                load_type: None,
-            });
-
         #[cfg(target_os = "macos")]
         let patched_path = executable
-            .and_then(|exe| {
-                sip_patch(
-                    exe,
-                    SipPatchOptions {
-                        patch: &config
-                            .sip_binaries
-                            .clone()
-                            .map(|x| x.to_vec())
-                            .unwrap_or_default(),
-                        skip: &config.skip_sip,
-                    },
-                    log_info,
-                )
-                .transpose() // We transpose twice to propagate a possible error out of this
-                // closure.
-            })
-            .transpose()
-            .inspect_err(|sip_error| {
-                // we can't recover from hitting the fd limit, so we have to exit fully
-                if let SipError::TooManyFilesOpen(..) = sip_error {
-                    panic!("mirrord failed to patch SIP with: {}", sip_error);
-                }
-            })?;
+            .map(|exe| Self::sip_patch_executable(config, exe, args))
+            .transpose()?
+            .flatten();
 
         #[cfg(not(target_os = "macos"))]
         let patched_path = None;
@@ -400,23 +498,96 @@ impl MirrordExecution {
                 .map(|unset| unset.to_vec())
                 .unwrap_or_default(),
             uses_operator: matches!(connect_info, AgentConnectInfo::Operator(..)),
+            proxy_address: intproxy_address,
         })
     }
 
-    async fn get_agent_version(connection: &mut Connection<Client>) -> CliResult<Version> {
+    /// Makes connections to Kubernetes Services go through the agent, see
+    /// [`OutgoingConfig::cluster_services`](mirrord_config::feature::network::outgoing::OutgoingConfig::cluster_services).
+    ///
+    /// Failing to list the Services is not fatal, the user only gets a warning.
+    async fn add_cluster_services<P: Progress>(config: &mut LayerConfig, progress: &mut P) {
+        let mut subtask = progress.subtask("listing Kubernetes Services");
+
+        let services = match kube_client_from_layer_config(config).await {
+            Ok(client) => cluster_service_ips(&client, config.target.namespace.as_deref())
+                .await
+                .map_err(|error| error.to_string()),
+            Err(error) => Err(error.to_string()),
+        };
+
+        match services {
+            Ok(services) => {
+                let count = services.len();
+                config
+                    .feature
+                    .network
+                    .outgoing
+                    .add_cluster_services(services);
+                subtask.success(Some(&format!(
+                    "connections to {count} Kubernetes Service addresses will go through the \
+                    remote pod"
+                )));
+            }
+            Err(error) => {
+                subtask.warning(&format!(
+                    "failed to list Kubernetes Services, connections to them will not go \
+                    through the remote pod: {error}"
+                ));
+                subtask.failure(Some("failed to list Kubernetes Services"));
+            }
+        }
+    }
+
+    /// Settles the protocol version with the agent and returns it, together with the agent's
+    /// [`AgentCapabilities`].
+    ///
+    /// Agents that don't send [`DaemonMessage::Capabilities`] have their capabilities inferred
+    /// from the protocol version.
+    async fn get_agent_version(
+        connection: &mut Connection<Client>,
+    ) -> CliResult<(Version, AgentCapabilities)> {
         connection
             .send(ClientMessage::SwitchProtocolVersion(
                 mirrord_protocol::VERSION.clone(),
             ))
             .await;
 
+        let version = match connection.recv().await {
+            Some(DaemonMessage::SwitchProtocolVersionResponse(version)) => version,
+            Some(msg) => {
+                return Err(CliError::InitialAgentCommFailed(format!(
+                    "received unexpected message during agent version check: {msg:?}"
+                )));
+            }
+            None => {
+                return Err(CliError::InitialAgentCommFailed(
+                    "no response received from agent connection during agent version check"
+                        .to_string(),
+                ));
+            }
+        };
+
+        if COMPATIBLE_AGENT_VERSIONS.matches(&version).not() {
+            return Err(CliError::IncompatibleAgentVersion {
+                agent_version: version,
+            });
+        }
+
+        if AGENT_CAPABILITIES_VERSION.matches(&version).not() {
+            let capabilities = AgentCapabilities::from_protocol_version(&version);
+            return Ok((version, capabilities));
+        }
+
+        // The agent sends its capabilities right after the response, before anything else.
         match connection.recv().await {
-            Some(DaemonMessage::SwitchProtocolVersionResponse(version)) => Ok(version),
+            Some(DaemonMessage::Capabilities(capabilities)) => Ok((version, capabilities)),
             Some(msg) => Err(CliError::InitialAgentCommFailed(format!(
-                "received unexpected message during agent version check: {msg:?}"
+                "received unexpected message during agent capabilities check: {msg:?}"
             ))),
             None => Err(CliError::InitialAgentCommFailed(
-                "no response received from agent connection during agent version check".to_string(),
+                "no response received from agent connection during agent capabilities check"
+                    .to_string(),
             )),
         }
     }
@@ -512,6 +683,7 @@ impl MirrordExecution {
                     "failed to parse port number printed by proxy: {e}"
                 ))
             })?;
+        progress.listening("extproxy", proxy_addr);
 
         let execution = Self {
             environment: env_vars,
@@ -525,6 +697,7 @@ impl MirrordExecution {
                 .map(|unset| unset.to_vec())
                 .unwrap_or_default(),
             uses_operator: matches!(connect_info, AgentConnectInfo::Operator(..)),
+            proxy_address: proxy_addr,
         };
 
         Ok((execution, proxy_addr))
@@ -561,10 +734,10 @@ impl MirrordExecution {
             (None, None) => (HashSet::new(), HashSet::from(EnvVars("*".to_owned()))),
         };
 
-        let mut env_vars = if !env_vars_exclude.is_empty() || !env_vars_include.is_empty() {
-            let communication_timeout =
-                Duration::from_secs(config.agent.communication_timeout.unwrap_or(30).into());
+        let communication_timeout =
+            Duration::from_secs(config.agent.communication_timeout.unwrap_or(30).into());
 
+        let mut env_vars = if !env_vars_exclude.is_empty() || !env_vars_include.is_empty() {
             tokio::time::timeout(
                 communication_timeout,
                 Self::get_remote_env(connection, env_vars_exclude, env_vars_include),
@@ -575,6 +748,24 @@ impl MirrordExecution {
             Default::default()
         };
 
+        if let Some(rename) = config.feature.env.rename.clone() {
+            env_vars = EnvVarsRenamer::new(rename, env_vars)
+                .expect("Invalid rename pattern, this should've been caught when verifying config!")
+                .renamed();
+        }
+
+        let has_target = config
+            .target
+            .path
+            .as_ref()
+            .is_some_and(|target| matches!(target, Target::Targetless).not());
+        if config.feature.env.jvm_limits && has_target {
+            tokio::time::timeout(communication_timeout, ContainerLimits::fetch(connection))
+                .await
+                .map_err(|_| CliError::InitialAgentCommFailed("timeout".to_string()))??
+                .apply(&mut env_vars);
+        }
+
         if let Some(file) = &config.feature.env.env_file {
             let envs_from_file = dotenvy::from_path_iter(file)
                 .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
@@ -631,6 +822,15 @@ impl MirrordExecution {
 
                     continue;
                 }
+                // Handled by the internal proxy, which warns the user.
+                Some(
+                    DaemonMessage::DisabledFeatures(..)
+                    | DaemonMessage::MandatoryHttpFilter(..)
+                    | DaemonMessage::SessionQuotas(..)
+                    | DaemonMessage::Capabilities(..),
+                ) => {
+                    continue;
+                }
                 Some(DaemonMessage::Close(msg)) => Err(CliError::InitialAgentCommFailed(format!(
                     "agent closed connection with message: {msg}"
                 ))),
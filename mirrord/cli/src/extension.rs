@@ -9,7 +9,7 @@ use crate::{
 
 /// Actually facilitate execution after all preparations were complete
 async fn mirrord_exec<P>(
-    #[cfg(target_os = "macos")] executable: Option<&str>,
+    #[cfg(any(target_os = "macos", target_os = "linux"))] executable: Option<&str>,
     mut config: LayerConfig,
     mut progress: P,
     analytics: &mut AnalyticsReporter,
@@ -23,13 +23,14 @@ where
 
     let execution_info = MirrordExecution::start_internal(
         &mut config,
-        #[cfg(target_os = "macos")]
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
         executable,
         #[cfg(target_os = "macos")]
         None,
         &mut progress,
         analytics,
         None,
+        None,
     )
     .await?;
 
@@ -50,6 +51,9 @@ where
         sub_progress_config.success(Some("config summary"));
     }
 
+    #[cfg(not(target_os = "windows"))]
+    crate::remote_logs::spawn(&config, true)?;
+
     let output = serde_json::to_string(&execution_info)?;
     progress.success(Some(&output));
     execution_info.wait().await?;
@@ -107,7 +111,7 @@ pub(crate) async fn extension_exec(
         }
     }
 
-    #[cfg(target_os = "macos")]
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
     let execution_result = mirrord_exec(
         args.executable.as_deref(),
         config,
@@ -116,7 +120,7 @@ pub(crate) async fn extension_exec(
         args.config_file.as_ref().and_then(|p| p.to_str()),
     )
     .await;
-    #[cfg(not(target_os = "macos"))]
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
     let execution_result = mirrord_exec(
         config,
         progress,
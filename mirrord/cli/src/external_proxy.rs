@@ -123,7 +123,7 @@ pub async fn proxy(
     print_addr(&listener).map_err(ExternalProxyError::ListenerSetup)?;
 
     #[cfg(not(target_os = "windows"))]
-    if let Err(error) = unsafe { detach_io() }.map_err(ExternalProxyError::SetSid) {
+    if let Err(error) = unsafe { detach_io(false) }.map_err(ExternalProxyError::SetSid) {
         tracing::warn!(%error, "unable to detach io");
     }
 
@@ -221,7 +221,17 @@ pub async fn proxy(
                     | message @ Some(DaemonMessage::PauseTarget(_))
                     | message @ Some(DaemonMessage::SwitchProtocolVersionResponse(_))
                     | message @ Some(DaemonMessage::Vpn(_))
-                    | message @ Some(DaemonMessage::ReverseDnsLookup(_)) => {
+                    | message @ Some(DaemonMessage::ReverseDnsLookup(_))
+                    | message @ Some(DaemonMessage::LogEvent(_))
+                    | message @ Some(DaemonMessage::IcmpEcho(_))
+                    | message @ Some(DaemonMessage::DisabledFeatures(_))
+                    | message @ Some(DaemonMessage::MandatoryHttpFilter(_))
+                    | message @ Some(DaemonMessage::SessionQuotas(_))
+                    | message @ Some(DaemonMessage::Capabilities(_))
+                    | message @ Some(DaemonMessage::GetInterfaces(_))
+                    | message @ Some(DaemonMessage::ResourceUsage(_))
+                    | message @ Some(DaemonMessage::ListeningPorts(_))
+                    | message @ Some(DaemonMessage::GoingDown(_)) => {
                         return Err(
                             ExternalProxyError::PingPongFailed(format!(
                                 "agent sent an unexpected message: {message:?}"
@@ -1,3 +1,5 @@
+#[cfg(target_os = "linux")]
+use std::ops::Not;
 use std::{
     fs::File,
     io::Write,
@@ -110,3 +112,70 @@ where
     progress.success(Some("arm64 layer library extracted"));
     Ok(file_path)
 }
+
+/// Extracts the layer that the given executable can load.
+///
+/// Executables linked against musl (e.g. built on Alpine) cannot load the default layer, which is
+/// linked against glibc, so they get the layer built for musl, when this build of mirrord
+/// includes it (`MIRRORD_LAYER_FILE_MUSL` was set at build time).
+#[cfg(target_os = "linux")]
+pub(crate) fn extract_library_for<P>(executable: Option<&str>, progress: &P) -> CliResult<PathBuf>
+where
+    P: Progress,
+{
+    let is_musl = executable
+        .is_some_and(|executable| crate::is_static::is_binary_musl(Path::new(executable)));
+    if is_musl.not() {
+        return extract_library(None, progress, true);
+    }
+
+    debug!(?executable, "Executable is linked against musl");
+
+    #[cfg(musl_layer)]
+    {
+        extract_musl(progress, true)
+    }
+
+    #[cfg(not(musl_layer))]
+    {
+        progress.warning(
+            "The target binary is linked against musl, but this build of mirrord does not include \
+            the layer built for musl. mirrord might not work!",
+        );
+        extract_library(None, progress, true)
+    }
+}
+
+/// Extract the layer built for musl, for executables that are linked against musl instead of
+/// glibc.
+/// If prefix is true, add a random prefix to the file name that identifies the specific build
+/// of the layer. This is useful for debug purposes usually.
+#[cfg(all(target_os = "linux", musl_layer))]
+fn extract_musl<P>(progress: &P, prefix: bool) -> CliResult<PathBuf>
+where
+    P: Progress,
+{
+    let mut progress = progress.subtask("extracting musl layer library");
+
+    let file_name = if prefix {
+        format!("{}-libmirrord_layer_musl.so", const_random!(u64))
+    } else {
+        "libmirrord_layer_musl.so".to_string()
+    };
+
+    let dir = temp_dir().join("mirrord");
+    if !dir.exists() {
+        std::fs::create_dir_all(&dir).map_err(|e| CliError::LayerExtractError(dir.clone(), e))?;
+    }
+    let file_path = dir.join(file_name);
+    if !file_path.exists() {
+        let mut file = File::create(&file_path)
+            .map_err(|e| CliError::LayerExtractError(file_path.clone(), e))?;
+        let bytes = include_bytes!(env!("MIRRORD_LAYER_FILE_MUSL"));
+        file.write_all(bytes).unwrap();
+        debug!("Extracted musl layer library to {:?}", &file_path);
+    }
+
+    progress.success(Some("musl layer library extracted"));
+    Ok(file_path)
+}
@@ -1,7 +1,10 @@
-//! Internal proxy is accepting connection from local layers and forward it to agent
-//! while having 1:1 relationship - each layer connection is another agent connection.
+//! Internal proxy is accepting connections from local layers and multiplexing them to a single
+//! agent connection.
 //!
-//! This might be changed later on.
+//! The layers can come from one process tree (forked children of the process started with
+//! `mirrord exec`), or from unrelated processes attached to the same named session with
+//! `mirrord attach`. Port subscriptions, open files and outgoing connections are tracked per
+//! layer, and cleaned up when the layer goes away.
 //!
 //! The main advantage of this design is that we remove kube logic from the layer itself,
 //! thus eliminating bugs that happen due to mix of remote env vars in our code
@@ -19,11 +22,16 @@ use std::{
 use std::{ops::Not, os::unix::ffi::OsStrExt};
 
 use mirrord_analytics::{AnalyticsReporter, CollectAnalytics, Reporter};
-use mirrord_config::LayerConfig;
+use mirrord_config::{LayerConfig, feature::network::incoming::source_filter::SourceFilterConfig};
 use mirrord_intproxy::{
     IntProxy,
     agent_conn::{AgentConnectInfo, AgentConnection},
 };
+use mirrord_kube::api::kubernetes::{
+    KubernetesAPI,
+    events::{SessionEventReason, SessionEventTarget},
+};
+use mirrord_progress::NullProgress;
 use mirrord_protocol::{ClientMessage, DaemonMessage, LogLevel, LogMessage};
 #[cfg(not(target_os = "windows"))]
 use nix::sys::resource::{Resource, setrlimit};
@@ -38,6 +46,7 @@ use crate::{
     connection::AGENT_CONNECT_INFO_ENV_KEY,
     error::{CliResult, InternalProxyError},
     execution::MIRRORD_EXECUTION_KIND_ENV,
+    port_selection::bind_listener,
     user_data::UserData,
     util::create_listen_socket,
 };
@@ -56,16 +65,25 @@ fn print_addr(listener: &TcpListener) -> io::Result<()> {
 pub(crate) async fn proxy(
     config: LayerConfig,
     listen_port: u16,
+    persistent: bool,
     watch: drain::Watch,
     user_data: &UserData,
 ) -> Result<(), InternalProxyError> {
     tracing::info!(
         ?config,
         listen_port,
+        persistent,
         version = env!("CARGO_PKG_VERSION"),
         "Starting mirrord-intproxy",
     );
 
+    match config.log_redaction.redactor() {
+        Ok(redactor) => {
+            let _ = redactor.install();
+        }
+        Err(error) => tracing::warn!(%error, "Invalid log redaction configuration"),
+    }
+
     // According to https://wilsonmar.github.io/maximum-limits/ this is the limit on macOS
     // so we assume Linux can be higher and set to that.
     #[cfg(not(target_os = "windows"))]
@@ -86,6 +104,13 @@ pub(crate) async fn proxy(
             })
         })?;
 
+    let target_pod = match &agent_connect_info {
+        AgentConnectInfo::DirectKubernetes(connect_info) if config.kube_events => {
+            connect_info.target_pod.clone()
+        }
+        _ => None,
+    };
+
     let execution_kind = std::env::var(MIRRORD_EXECUTION_KIND_ENV)
         .ok()
         .and_then(|execution_kind| execution_kind.parse().ok())
@@ -117,22 +142,52 @@ pub(crate) async fn proxy(
     // with the agent (it's a must, because port forwarding may be done lazily).
     let agent_conn = connect_and_ping(&config, agent_connect_info, &mut analytics).await?;
 
-    // Let it assign address for us then print it for the user.
-    let listener = create_listen_socket(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), listen_port))
-        .map_err(InternalProxyError::ListenerSetup)?;
+    // An explicit port from our parent takes precedence, otherwise we pick one from
+    // `internal_proxy.listen_ports` (or let the OS assign it). Then we print it for the user.
+    let listener = if listen_port != 0 {
+        create_listen_socket(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), listen_port))
+    } else {
+        let ports = config
+            .internal_proxy
+            .listen_ports
+            .as_ref()
+            .and_then(|ports| ports.range().ok());
+        let seed = (
+            env::current_dir().ok(),
+            config.target.path.as_ref().map(ToString::to_string),
+        );
+        bind_listener(Ipv4Addr::LOCALHOST.into(), ports, seed)
+    }
+    .map_err(InternalProxyError::ListenerSetup)?;
     print_addr(&listener).map_err(InternalProxyError::ListenerSetup)?;
 
+    // With `agent.forward_logs`, `mirrord exec` gives us its stderr, where we print the agent logs.
     #[cfg(not(target_os = "windows"))]
     if container_mode.not() {
-        unsafe { detach_io() }.map_err(InternalProxyError::SetSid)?;
+        unsafe { detach_io(config.agent.forward_logs) }.map_err(InternalProxyError::SetSid)?;
     }
 
-    let first_connection_timeout = Duration::from_secs(config.internal_proxy.start_idle_timeout);
-    let consecutive_connection_timeout = Duration::from_secs(config.internal_proxy.idle_timeout);
+    // Named sessions started with `mirrord start` run until `mirrord stop`.
+    let (first_connection_timeout, consecutive_connection_timeout) = if persistent {
+        (Duration::MAX, Duration::MAX)
+    } else {
+        (
+            Duration::from_secs(config.internal_proxy.start_idle_timeout),
+            Duration::from_secs(config.internal_proxy.idle_timeout),
+        )
+    };
     let process_logging_interval =
         Duration::from_secs(config.internal_proxy.process_logging_interval);
+    let source_filter = config
+        .feature
+        .network
+        .incoming
+        .source_filter
+        .as_ref()
+        .map(SourceFilterConfig::as_protocol_filter)
+        .transpose()?;
 
-    IntProxy::new_with_connection(
+    let result = IntProxy::new_with_connection(
         agent_conn,
         listener,
         config.feature.fs.readonly_file_buffer,
@@ -143,12 +198,42 @@ pub(crate) async fn proxy(
             .tls_delivery
             .or(config.feature.network.incoming.https_delivery)
             .unwrap_or_default(),
+        config.feature.network.incoming.request_limit,
+        config.feature.network.incoming.replicas.clone(),
+        config.feature.network.incoming.backlog,
+        config.feature.network.incoming.metadata_headers,
+        config.feature.network.incoming.mirror_max_bytes_per_second,
+        config.feature.network.incoming.wait_for_local_app,
+        source_filter,
+        config.feature.network.incoming.tee.as_ref(),
         process_logging_interval,
+        config.agent.forward_logs,
         &config.experimental,
     )
     .run(first_connection_timeout, consecutive_connection_timeout)
-    .await
-    .map_err(From::from)
+    .await;
+
+    if let Some(target_pod) = target_pod {
+        emit_session_ended(&config, &target_pod).await;
+    }
+
+    result.map_err(From::from)
+}
+
+/// Emits [`SessionEventReason::Ended`] on the target pod, see [`LayerConfig::kube_events`].
+async fn emit_session_ended(config: &LayerConfig, target_pod: &SessionEventTarget) {
+    let result = match KubernetesAPI::create(config, &NullProgress).await {
+        Ok(k8s_api) => {
+            target_pod
+                .emit(k8s_api.client(), SessionEventReason::Ended)
+                .await
+        }
+        Err(error) => Err(error),
+    };
+
+    if let Err(error) = result {
+        tracing::warn!(%error, "Failed to emit a Kubernetes event on the target pod");
+    }
 }
 
 /// Creates a connection with the agent and handles one round of ping pong.
@@ -200,7 +285,17 @@ pub(crate) async fn connect_and_ping(
             | message @ Some(DaemonMessage::PauseTarget(_))
             | message @ Some(DaemonMessage::SwitchProtocolVersionResponse(_))
             | message @ Some(DaemonMessage::Vpn(_))
-            | message @ Some(DaemonMessage::ReverseDnsLookup(_)) => {
+            | message @ Some(DaemonMessage::ReverseDnsLookup(_))
+            | message @ Some(DaemonMessage::LogEvent(_))
+            | message @ Some(DaemonMessage::IcmpEcho(_))
+            | message @ Some(DaemonMessage::DisabledFeatures(_))
+            | message @ Some(DaemonMessage::MandatoryHttpFilter(_))
+            | message @ Some(DaemonMessage::SessionQuotas(_))
+            | message @ Some(DaemonMessage::Capabilities(_))
+            | message @ Some(DaemonMessage::GetInterfaces(_))
+            | message @ Some(DaemonMessage::ResourceUsage(_))
+            | message @ Some(DaemonMessage::ListeningPorts(_))
+            | message @ Some(DaemonMessage::GoingDown(_)) => {
                 break Err(InternalProxyError::InitialPingPongFailed(format!(
                     "agent sent an unexpected message: {message:?}"
                 )));
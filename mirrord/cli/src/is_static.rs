@@ -38,3 +38,47 @@ pub fn is_binary_static(binary_path: &Path) -> bool {
         .any(|header| matches!(header.get_type(), Ok(Type::Dynamic)))
         .not()
 }
+
+/// Returns true if the binary under the given path is dynamically linked against musl instead of
+/// glibc, e.g. because it was built on Alpine.
+///
+/// Such binaries use musl's dynamic loader (e.g. `/lib/ld-musl-x86_64.so.1`) as their interpreter,
+/// and cannot load the layer built against glibc.
+pub fn is_binary_musl(binary_path: &Path) -> bool {
+    let content = match std::fs::read(binary_path) {
+        Ok(content) => content,
+        Err(error) => {
+            tracing::warn!(
+                %error,
+                binary_path = %binary_path.display(),
+                "Failed to read the file while checking if the binary is linked against musl",
+            );
+            return false;
+        }
+    };
+
+    let elf = match ElfFile::new(&content) {
+        Ok(elf) => elf,
+        Err(error) => {
+            tracing::warn!(
+                error,
+                binary_path = %binary_path.display(),
+                "Failed to parse ELF file while checking if the binary is linked against musl",
+            );
+            return false;
+        }
+    };
+
+    elf.program_iter()
+        .filter(|header| matches!(header.get_type(), Ok(Type::Interp)))
+        .filter_map(|header| {
+            let start = usize::try_from(header.offset()).ok()?;
+            let end = start.checked_add(usize::try_from(header.file_size()).ok()?)?;
+            content.get(start..end)
+        })
+        .any(|interpreter| {
+            interpreter
+                .windows(b"ld-musl-".len())
+                .any(|window| window == b"ld-musl-")
+        })
+}
@@ -1,8 +1,12 @@
-use std::fmt::Debug;
+use std::{collections::HashSet, fmt::Debug, net::IpAddr};
 
-use kube::{Resource, api::ListParams, client::ClientBuilder};
+use k8s_openapi::api::core::v1::Service;
+use kube::{Api, Resource, api::ListParams, client::ClientBuilder};
 use mirrord_config::LayerConfig;
-use mirrord_kube::{api::kubernetes::create_kube_config, retry::RetryKube};
+use mirrord_kube::{
+    api::kubernetes::{create_kube_config, get_k8s_resource_api},
+    retry::RetryKube,
+};
 use mirrord_progress::Progress;
 use serde::de::DeserializeOwned;
 use tower::{buffer::BufferLayer, retry::RetryLayer};
@@ -59,3 +63,50 @@ where
         }
     }
 }
+
+/// Lists the addresses of Kubernetes Services: their cluster IPs and load balancer IPs, see
+/// [`OutgoingConfig::cluster_services`](mirrord_config::feature::network::outgoing::OutgoingConfig::cluster_services).
+///
+/// Lists the Services in all namespaces, or only in the given `namespace` if the user is not
+/// allowed to list them cluster-wide.
+pub(crate) async fn cluster_service_ips(
+    client: &kube::Client,
+    namespace: Option<&str>,
+) -> Result<Vec<IpAddr>, kube::Error> {
+    let services = match Api::<Service>::all(client.clone())
+        .list(&ListParams::default())
+        .await
+    {
+        Ok(services) => services,
+        Err(kube::Error::Api(err)) if err.code == 403 => {
+            get_k8s_resource_api::<Service>(client, namespace)
+                .list(&ListParams::default())
+                .await?
+        }
+        Err(e) => return Err(e),
+    };
+
+    let ips = services
+        .items
+        .into_iter()
+        .flat_map(|service| {
+            let cluster_ips = service
+                .spec
+                .and_then(|spec| spec.cluster_ips)
+                .unwrap_or_default();
+            let ingress_ips = service
+                .status
+                .and_then(|status| status.load_balancer)
+                .and_then(|load_balancer| load_balancer.ingress)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|ingress| ingress.ip);
+
+            cluster_ips.into_iter().chain(ingress_ips)
+        })
+        // Headless Services have `None` instead of a cluster IP.
+        .filter_map(|ip| ip.parse::<IpAddr>().ok())
+        .collect::<HashSet<_>>();
+
+    Ok(ips.into_iter().collect())
+}
@@ -0,0 +1,214 @@
+//! Implements the `mirrord ls-ports` command, see [`list_ports_command`].
+
+use std::{collections::BTreeSet, ops::Not};
+
+use mirrord_analytics::{AnalyticsReporter, CollectAnalytics, ExecutionKind};
+use mirrord_config::{LayerConfig, config::ConfigContext, target::Target};
+use mirrord_progress::{Progress, ProgressTracker};
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, ResponseError,
+    listening_ports::{GetListeningPortsRequest, LISTENING_PORTS_VERSION, ListeningPort},
+};
+use mirrord_sdk::client::{AgentClient, AgentClientError};
+use semver::Version;
+use serde::Serialize;
+use thiserror::Error;
+
+use super::config::ListPortsArgs;
+use crate::{
+    CliError, Format, connection::create_and_connect, error::CliResult, user_data::UserData,
+};
+
+/// Errors that can occur when reading the target's listening ports with `mirrord ls-ports`.
+#[derive(Debug, Error)]
+pub enum ListPortsError {
+    #[error(transparent)]
+    Agent(#[from] AgentClientError),
+
+    #[error(
+        "mirrord-agent does not support reporting listening ports, protocol version used by \
+        mirrord-agent ({0}) must match {}",
+        *LISTENING_PORTS_VERSION
+    )]
+    NotSupported(Version),
+
+    #[error("failed to read the listening ports of the target: {0}")]
+    Remote(ResponseError),
+}
+
+/// Implements the `mirrord ls-ports` command.
+///
+/// Prints the TCP ports the target is listening on, so that the IDE plugins can pre-fill the
+/// ports to steal, and warn when a port from the config is not open in the target.
+pub async fn list_ports_command(
+    args: &ListPortsArgs,
+    watch: drain::Watch,
+    user_data: &UserData,
+) -> CliResult<()> {
+    let mut cfg_context = ConfigContext::default().override_envs(args.params.as_env_vars());
+    let mut config = LayerConfig::resolve(&mut cfg_context)?;
+
+    let mut progress = ProgressTracker::from_env("mirrord ls-ports");
+    let mut analytics = AnalyticsReporter::new(
+        config.telemetry,
+        ExecutionKind::ListPorts,
+        watch,
+        user_data.machine_id(),
+    );
+
+    if matches!(config.target.path, Some(Target::Targetless)) || config.target.path.is_none() {
+        return Err(CliError::MissingArg {
+            command: "mirrord ls-ports".to_string(),
+            arg: "target".to_string(),
+        });
+    }
+
+    (&config).collect_analytics(analytics.get_mut());
+
+    let (_connection_info, connection) =
+        create_and_connect(&mut config, &mut progress, &mut analytics, None, None).await?;
+
+    let client = AgentClient::new(connection)
+        .await
+        .map_err(ListPortsError::from)?;
+    let mut session = ListPortsSession::new(client)?;
+    let listening = session.listening_ports().await?;
+    progress.success(None);
+
+    let configured = config
+        .feature
+        .network
+        .incoming
+        .ports
+        .iter()
+        .flatten()
+        .map(|port| {
+            config
+                .feature
+                .network
+                .incoming
+                .port_mapping
+                .get_by_left(port)
+                .copied()
+                .unwrap_or(*port)
+        })
+        .collect();
+
+    match args.output {
+        Format::Json => {
+            let report = TargetPorts::new(listening, configured);
+            println!("{}", serde_json::to_string(&report).unwrap());
+        }
+    }
+
+    Ok(())
+}
+
+/// Output of `mirrord ls-ports`.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct TargetPorts {
+    /// All TCP sockets listening in the target.
+    listening: Vec<ListeningSocket>,
+
+    /// Distinct ports of the sockets that are reachable from outside of the target (not bound to
+    /// a loopback address), i.e. the ports that can be stolen.
+    ports: BTreeSet<u16>,
+
+    /// Remote ports from `feature.network.incoming.ports` (after applying
+    /// `feature.network.incoming.port_mapping`) that no socket listens on in the target.
+    not_listening: BTreeSet<u16>,
+}
+
+/// [`ListeningPort`] in the output of `mirrord ls-ports`.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct ListeningSocket {
+    address: String,
+    port: u16,
+}
+
+impl TargetPorts {
+    fn new(listening: Vec<ListeningPort>, configured: BTreeSet<u16>) -> Self {
+        let ports = listening
+            .iter()
+            .filter(|socket| socket.address.is_loopback().not())
+            .map(|socket| socket.port)
+            .collect::<BTreeSet<_>>();
+        let not_listening = configured
+            .into_iter()
+            .filter(|port| listening.iter().all(|socket| socket.port != *port))
+            .collect();
+
+        Self {
+            listening: listening
+                .into_iter()
+                .map(|socket| ListeningSocket {
+                    address: socket.address.to_string(),
+                    port: socket.port,
+                })
+                .collect(),
+            ports,
+            not_listening,
+        }
+    }
+}
+
+/// Implements `mirrord ls-ports` logic on an established [`AgentClient`].
+struct ListPortsSession {
+    client: AgentClient,
+}
+
+impl ListPortsSession {
+    /// Verifies that the agent can report the listening ports.
+    fn new(client: AgentClient) -> Result<Self, ListPortsError> {
+        let version = client.protocol_version();
+        if LISTENING_PORTS_VERSION.matches(version).not() {
+            return Err(ListPortsError::NotSupported(version.clone()));
+        }
+
+        Ok(Self { client })
+    }
+
+    /// Reads the TCP sockets listening in the target.
+    async fn listening_ports(&mut self) -> Result<Vec<ListeningPort>, ListPortsError> {
+        match self
+            .client
+            .request(ClientMessage::GetListeningPorts(GetListeningPortsRequest))
+            .await?
+        {
+            DaemonMessage::ListeningPorts(result) => result.map_err(ListPortsError::Remote),
+            other => Err(AgentClientError::unexpected(other).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    /// Verifies that loopback sockets are not offered for stealing, and that configured ports
+    /// are reported as missing only when no socket listens on them.
+    #[test]
+    fn ports_from_sockets() {
+        let socket = |address: IpAddr, port| ListeningPort { address, port };
+        let listening = vec![
+            socket(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 8080),
+            socket(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 8080),
+            socket(IpAddr::V4(Ipv4Addr::LOCALHOST), 9090),
+        ];
+
+        let report = TargetPorts::new(listening, BTreeSet::from([80, 8080, 9090]));
+
+        assert_eq!(report.ports, BTreeSet::from([8080]));
+        assert_eq!(report.not_listening, BTreeSet::from([80]));
+        assert_eq!(
+            serde_json::to_value(&report.listening).unwrap(),
+            serde_json::json!([
+                { "address": "0.0.0.0", "port": 8080 },
+                { "address": "::", "port": 8080 },
+                { "address": "127.0.0.1", "port": 9090 },
+            ])
+        );
+    }
+}
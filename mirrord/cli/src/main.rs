@@ -84,6 +84,17 @@
 //! intializes logging, either to a file in `/tmp`, or to stderr when it's being started from
 //! `mirrord container`.
 //!
+//! ### `mirrord start [OPTIONS] --name <NAME>`
+//!
+//! - [`session::start_command`]
+//!
+//! > A `mirrord exec` that outlives the process.
+//!
+//! Prepares the agent and the intproxy like `mirrord exec`, saves the environment required by the
+//! layer under the session name, and exits. The intproxy is started with `--session` and runs in
+//! the background until `mirrord stop <NAME>`. Local processes are run against the session with
+//! `mirrord attach <NAME> -- <BINARY>`, and all of them share the same agent connection.
+//!
 //! ### `mirrord container [OPTIONS] [EXEC]`
 //!
 //! - [`container_command`]
@@ -256,12 +267,15 @@ use clap_complete::generate;
 use config::*;
 use connection::create_and_connect;
 use container::{container_command, container_ext_command};
+use cp::cp_command;
 use db_branches::db_branches_command;
 use diagnose::diagnose_command;
 use dump::dump_command;
+use exec_adapter::ExecAdapter;
 use execution::MirrordExecution;
 use extension::extension_exec;
 use extract::extract_library;
+use list_ports::list_ports_command;
 use mirrord_analytics::{
     AnalyticsError, AnalyticsReporter, CollectAnalytics, ExecutionKind, Reporter,
 };
@@ -276,27 +290,33 @@ use mirrord_config::{
             incoming::IncomingMode,
         },
     },
+    profiles::MIRRORD_CONFIG_PROFILE_ENV,
 };
 use mirrord_intproxy::agent_conn::{AgentConnection, AgentConnectionError};
-use mirrord_progress::{Progress, ProgressTracker, messages::EXEC_CONTAINER_BINARY};
+use mirrord_progress::{ExecPhase, Progress, ProgressTracker, messages::EXEC_CONTAINER_BINARY};
 #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
 use nix::errno::Errno;
 use operator::operator_command;
 use port_forward::{PortForwardError, PortForwarder, ReversePortForwarder};
 use regex::Regex;
 use semver::Version;
+use top::top_command;
 use tracing::{error, info, trace, warn};
 use which::which;
 
+mod agent;
 mod browser;
 mod ci;
 mod config;
 mod connection;
 mod container;
+mod container_limits;
+mod cp;
 mod db_branches;
 mod diagnose;
 mod dump;
 mod error;
+mod exec_adapter;
 mod execution;
 mod extension;
 mod external_proxy;
@@ -306,24 +326,33 @@ mod internal_proxy;
 mod is_static;
 mod kube;
 mod list;
+mod list_ports;
 mod local_redis;
 mod logging;
 mod newsletter;
 mod operator;
 mod port_forward;
+mod port_selection;
 mod preview;
 mod profile;
+#[cfg(not(target_os = "windows"))]
+mod remote_logs;
+mod session;
 mod teams;
+mod top;
 mod user_data;
 mod util;
 mod verify_config;
 mod vpn;
+#[cfg(not(target_os = "windows"))]
+mod watch;
 mod wsl;
 
 #[cfg(feature = "wizard")]
 mod wizard;
 
 mod fix;
+mod migrate;
 
 pub(crate) use error::{CliError, CliResult};
 #[cfg(target_os = "windows")]
@@ -379,13 +408,14 @@ where
 
     let execution_info = MirrordExecution::start_internal(
         &mut config,
-        #[cfg(target_os = "macos")]
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
         Some(&args.binary),
         #[cfg(target_os = "macos")]
         Some(binary_args.as_slice()),
         &mut sub_progress,
         analytics,
         mirrord_for_ci.as_ref(),
+        None,
     )
     .await?;
 
@@ -418,6 +448,7 @@ where
         .collect::<Vec<_>>();
 
     sub_progress.success(Some("ready to launch process"));
+    progress.phase(ExecPhase::Ready);
 
     #[cfg(not(target_os = "windows"))]
     if config.experimental.browser_extension_config {
@@ -440,6 +471,9 @@ where
     // print an invitation to the newsletter on certain run count numbers
     suggest_newsletter_signup(user_data, progress).await;
 
+    #[cfg(not(target_os = "windows"))]
+    remote_logs::spawn(&config, false)?;
+
     let sub_progress = progress.subtask("running process");
 
     run_process_with_mirrord(
@@ -663,22 +697,27 @@ pub(crate) fn print_config<P>(
         DnsConfig {
             enabled: true,
             filter: None,
+            ..
         } => "remotely",
         DnsConfig {
             enabled: true,
             filter: Some(DnsFilterConfig::Remote(filters)),
+            ..
         } if filters.is_empty() => "locally",
         DnsConfig {
             enabled: true,
             filter: Some(DnsFilterConfig::Local(filters)),
+            ..
         } if filters.is_empty() => "remotely",
         DnsConfig {
             enabled: true,
             filter: Some(DnsFilterConfig::Remote(..)),
+            ..
         } => "locally with exceptions",
         DnsConfig {
             enabled: true,
             filter: Some(DnsFilterConfig::Local(..)),
+            ..
         } => "remotely with exceptions",
     };
     progress.info(&format!("dns: DNS will be resolved {}", dns_info));
@@ -726,12 +765,32 @@ async fn exec(
         )
     }
 
-    let mut cfg_context = ConfigContext::default().override_envs(args.params.as_env_vars());
+    let mut cfg_context = ConfigContext::default()
+        .override_envs(args.params.as_env_vars())
+        .override_env_opt(MIRRORD_CONFIG_PROFILE_ENV, args.config_profile.as_ref());
     let config_file_path = cfg_context.get_env(LayerConfig::FILE_PATH_ENV).ok();
     let mut config = LayerConfig::resolve(&mut cfg_context)?;
+    // Includes the config files discovered in the `.mirrord` project directory.
+    let loaded_config_files = (!cfg_context.loaded_files().is_empty()).then(|| {
+        cfg_context
+            .loaded_files()
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    });
 
     crate::profile::apply_profile_if_configured(&mut config, progress).await?;
 
+    if args.exec_adapter
+        && let Some(adapter) = ExecAdapter::detect(&args.binary, &args.binary_args)
+    {
+        adapter.apply(&mut config, std::env::current_dir().ok());
+        progress.info(&format!(
+            "detected {adapter}, adjusting the configuration to its helper processes"
+        ));
+    }
+
     let _local_redis: Option<local_redis::LocalRedis> = if let Some(redis_config) =
         config.feature.db_branches.iter().find_map(|branch| {
             if let DatabaseBranchConfig::Redis(redis_config) = branch
@@ -781,9 +840,51 @@ async fn exec(
     }
     result?;
 
+    if args.warm && mirrord_for_ci.is_some() {
+        progress.warning("`--warm` is not supported by mirrord for CI, ignoring it");
+    }
+
+    if !args.watch.is_empty() && mirrord_for_ci.is_some() {
+        progress.warning("`--watch` is not supported by mirrord for CI, ignoring it");
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    if !args.watch.is_empty() && mirrord_for_ci.is_none() {
+        let res = session::watch_exec(
+            config,
+            config_file_path.as_deref(),
+            args,
+            progress,
+            &mut analytics,
+        )
+        .await;
+
+        if res.is_err() && !analytics.has_error() {
+            analytics.set_error(AnalyticsError::Unknown);
+        }
+        return res;
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    if args.warm && mirrord_for_ci.is_none() {
+        let res = session::warm_exec(
+            config,
+            config_file_path.as_deref(),
+            args,
+            progress,
+            &mut analytics,
+        )
+        .await;
+
+        if res.is_err() && !analytics.has_error() {
+            analytics.set_error(AnalyticsError::Unknown);
+        }
+        return res;
+    }
+
     let res = exec_process(
         config,
-        config_file_path.as_deref(),
+        loaded_config_files.as_deref(),
         args,
         progress,
         &mut analytics,
@@ -977,6 +1078,8 @@ fn main() -> miette::Result<()> {
             Commands::Dump(args) => windows_unsupported!(args, "dump", {
                 dump_command(&args, watch, &user_data).await?
             }),
+            Commands::Cp(args) => cp_command(&args, watch, &user_data).await?,
+            Commands::Top(args) => top_command(&args, watch, &user_data).await?,
             Commands::Extract { path } => {
                 extract_library(
                     Some(path),
@@ -992,6 +1095,7 @@ fn main() -> miette::Result<()> {
 
                 list::print_targets(*args, rich_output).await?
             }
+            Commands::ListPorts(args) => list_ports_command(&args, watch, &user_data).await?,
             Commands::Operator(args) => {
                 operator_command(*args).await?;
             }
@@ -1001,6 +1105,7 @@ fn main() -> miette::Result<()> {
             Commands::InternalProxy {
                 port,
                 mirrord_for_ci,
+                session,
                 ..
             } => {
                 let config = mirrord_config::util::read_resolved_config()?;
@@ -1009,8 +1114,12 @@ fn main() -> miette::Result<()> {
                     MirrordCi::prepare_intproxy().await?;
                 }
 
+                if let Some(session) = session.as_deref() {
+                    session::register_intproxy(session).await?;
+                }
+
                 logging::init_intproxy_tracing_registry(&config).await?;
-                internal_proxy::proxy(config, port, watch, &user_data).await?
+                internal_proxy::proxy(config, port, session.is_some(), watch, &user_data).await?
             }
             Commands::VerifyConfig(args) => verify_config(args).await?,
             Commands::Completions(args) => {
@@ -1040,6 +1149,11 @@ fn main() -> miette::Result<()> {
                 logging::init_extproxy_tracing_registry(&config).await?;
                 external_proxy::proxy(config, port, watch, &user_data).await?
             }),
+            Commands::RemoteLogs => windows_unsupported!((), "remote-logs", {
+                #[cfg(not(target_os = "windows"))]
+                remote_logs::remote_logs_command(mirrord_config::util::read_resolved_config()?)
+                    .await;
+            }),
             Commands::PortForward(args) => port_forward(&args, watch, &user_data).await?,
             Commands::Vpn(args) => {
                 windows_unsupported!(args, "vpn", { vpn::vpn_command(*args).await? })
@@ -1061,6 +1175,27 @@ fn main() -> miette::Result<()> {
                 .await?
             }
             Commands::Fix(args) => fix::fix_command(args).await?,
+            Commands::Config(args) => migrate::config_command(args)?,
+            Commands::Agent(args) => agent::agent_command(*args).await?,
+            Commands::Start(args) => windows_unsupported!(args, "start", {
+                session::start_command(*args, watch, &user_data).await?
+            }),
+            Commands::Attach(args) => {
+                windows_unsupported!(args, "attach", { session::attach_command(*args).await? })
+            }
+            Commands::Stop(args) => {
+                windows_unsupported!(args, "stop", { session::stop_command(args).await? })
+            }
+            Commands::Pause(args) => {
+                windows_unsupported!(args, "pause", {
+                    session::port_pause_command(args, true).await?
+                })
+            }
+            Commands::Resume(args) => {
+                windows_unsupported!(args, "resume", {
+                    session::port_pause_command(args, false).await?
+                })
+            }
         };
 
         Ok(())
@@ -1075,7 +1210,7 @@ fn main() -> miette::Result<()> {
             });
     });
 
-    res.map_err(Into::into)
+    res.map_err(|error| error.with_code().into())
 }
 
 /// Make sure we're not running nested inside another mirrord exec
@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+
+use mirrord_config::{discovery, migration};
+use mirrord_progress::{Progress, ProgressTracker};
+
+use crate::{
+    CliError, CliResult,
+    config::{ConfigArgs, ConfigCommand, ConfigMigrateArgs},
+};
+
+pub(crate) fn config_command(args: ConfigArgs) -> CliResult<()> {
+    let mut progress = ProgressTracker::from_env("mirrord config");
+    match args.command {
+        ConfigCommand::Migrate(args) => migrate_config(&args, &mut progress)?,
+    }
+
+    Ok(())
+}
+
+/// Rewrites deprecated keys in the given config file, or in the discovered project config.
+fn migrate_config<P: Progress>(args: &ConfigMigrateArgs, progress: &mut P) -> CliResult<()> {
+    let paths: Vec<PathBuf> = match &args.config_file {
+        Some(path) => vec![path.clone()],
+        None => std::env::current_dir()
+            .map(|directory| discovery::discover_project_config(&directory))
+            .unwrap_or_default(),
+    };
+
+    if paths.is_empty() {
+        progress.warning("No config file given, and no project config found, nothing to do.");
+        return Ok(());
+    }
+
+    for path in paths {
+        let (migrations, migrated) = migration::migrate_file(&path)
+            .map_err(|error| CliError::ConfigMigrate(path.clone(), error))?;
+
+        if migrations.is_empty() {
+            progress.info(&format!("`{}` is up to date.", path.display()));
+            continue;
+        }
+
+        for migration in &migrations {
+            progress.info(&format!("{}: {migration}", path.display()));
+        }
+
+        if args.dry_run {
+            progress.info(&format!(
+                "Migrated `{}` (dry run, no actual changes made):\n{migrated}",
+                path.display()
+            ));
+        } else {
+            std::fs::write(&path, migrated)
+                .map_err(|error| CliError::ConfigMigrate(path.clone(), error.into()))?;
+            progress.info(&format!("Migrated `{}`.", path.display()));
+        }
+    }
+
+    progress.success(None);
+
+    Ok(())
+}
@@ -6,7 +6,10 @@ use std::{
 };
 
 use futures::StreamExt;
-use mirrord_config::feature::network::incoming::IncomingConfig;
+use mirrord_config::{
+    config::ConfigError,
+    feature::network::incoming::{IncomingConfig, source_filter::SourceFilterConfig},
+};
 use mirrord_intproxy::{
     background_tasks::{BackgroundTasks, TaskError, TaskSender, TaskUpdate},
     main_tasks::{ProxyMessage, ToLayer},
@@ -338,6 +341,24 @@ impl PortForwarder {
             DaemonMessage::Pong if self.waiting_for_pong => {
                 self.waiting_for_pong = false;
             }
+            DaemonMessage::DisabledFeatures(features) => {
+                tracing::warn!(?features, "agent has some features disabled");
+            }
+            DaemonMessage::MandatoryHttpFilter(filter) => {
+                tracing::warn!(%filter, "agent requires an HTTP filter in steal subscriptions");
+            }
+            DaemonMessage::SessionQuotas(quotas) => {
+                tracing::info!(?quotas, "agent enforces session quotas");
+            }
+            DaemonMessage::Capabilities(capabilities) => {
+                tracing::debug!(?capabilities, "agent reported its capabilities");
+            }
+            DaemonMessage::GoingDown(notice) => {
+                tracing::warn!(%notice, "agent is shutting down");
+                self.agent_connection
+                    .send(ClientMessage::GoingDownAck)
+                    .await;
+            }
             DaemonMessage::OperatorPing(id) => {
                 self.agent_connection
                     .send(ClientMessage::OperatorPong(id))
@@ -352,7 +373,12 @@ impl PortForwarder {
             | DaemonMessage::UdpOutgoing(..)
             | DaemonMessage::Vpn(..)
             | DaemonMessage::TcpSteal(..)
-            | DaemonMessage::ReverseDnsLookup(..)) => {
+            | DaemonMessage::ReverseDnsLookup(..)
+            | DaemonMessage::LogEvent(..)
+            | DaemonMessage::IcmpEcho(..)
+            | DaemonMessage::GetInterfaces(..)
+            | DaemonMessage::ResourceUsage(..)
+            | DaemonMessage::ListeningPorts(..)) => {
                 // includes unexpected DaemonMessage::Pong
                 return Err(PortForwardError::AgentError(format!(
                     "unexpected message from agent: {message:?}"
@@ -499,6 +525,18 @@ impl ReversePortForwarder {
                     .clone()
                     .or_else(|| network_config.https_delivery.clone())
                     .unwrap_or_default(),
+                network_config.request_limit,
+                network_config.replicas.clone(),
+                network_config.backlog,
+                network_config.metadata_headers,
+                network_config.mirror_max_bytes_per_second,
+                network_config.wait_for_local_app,
+                network_config
+                    .source_filter
+                    .as_ref()
+                    .map(SourceFilterConfig::as_protocol_filter)
+                    .transpose()?,
+                network_config.tee.as_ref(),
             ),
             (),
             512,
@@ -609,6 +647,24 @@ impl ReversePortForwarder {
             DaemonMessage::Pong if self.waiting_for_pong => {
                 self.waiting_for_pong = false;
             }
+            DaemonMessage::DisabledFeatures(features) => {
+                tracing::warn!(?features, "agent has some features disabled");
+            }
+            DaemonMessage::MandatoryHttpFilter(filter) => {
+                tracing::warn!(%filter, "agent requires an HTTP filter in steal subscriptions");
+            }
+            DaemonMessage::SessionQuotas(quotas) => {
+                tracing::info!(?quotas, "agent enforces session quotas");
+            }
+            DaemonMessage::Capabilities(capabilities) => {
+                tracing::debug!(?capabilities, "agent reported its capabilities");
+            }
+            DaemonMessage::GoingDown(notice) => {
+                tracing::warn!(%notice, "agent is shutting down");
+                self.agent_connection
+                    .send(ClientMessage::GoingDownAck)
+                    .await;
+            }
             message @ DaemonMessage::UdpOutgoing(_)
             | message @ DaemonMessage::TcpOutgoing(_)
             | message @ DaemonMessage::File(_)
@@ -618,7 +674,12 @@ impl ReversePortForwarder {
             | message @ DaemonMessage::SwitchProtocolVersionResponse(_)
             | message @ DaemonMessage::Vpn(_)
             | message @ DaemonMessage::Pong
-            | message @ DaemonMessage::ReverseDnsLookup(_) => {
+            | message @ DaemonMessage::ReverseDnsLookup(_)
+            | message @ DaemonMessage::LogEvent(_)
+            | message @ DaemonMessage::IcmpEcho(_)
+            | message @ DaemonMessage::GetInterfaces(_)
+            | message @ DaemonMessage::ResourceUsage(_)
+            | message @ DaemonMessage::ListeningPorts(_) => {
                 return Err(PortForwardError::AgentError(format!(
                     "unexpected message from agent: {message:?}"
                 )));
@@ -1004,6 +1065,9 @@ pub enum PortForwardError {
 
     #[error("failed to establish connection with remote process: `{0}`")]
     ConnectionError(String),
+
+    #[error("invalid port forwarding config: {0}")]
+    Config(#[from] ConfigError),
 }
 
 impl From<mpsc::error::SendError<ClientMessage>> for PortForwardError {
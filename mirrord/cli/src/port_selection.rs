@@ -0,0 +1,101 @@
+//! Picking the ports of the local listeners, see [`bind_listener`].
+//!
+//! Binding the socket is what reserves the port, so there is no window in which another process
+//! can take a port we've already checked.
+
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
+    net::{IpAddr, SocketAddr},
+    ops::RangeInclusive,
+};
+
+use tokio::net::TcpListener;
+use tracing::Level;
+
+use crate::util::create_listen_socket;
+
+/// Binds a listener on one of the given `ports`, or on a port picked by the OS when `ports` is
+/// [`None`].
+///
+/// The ports are tried in an order derived from the `seed`, so that the same seed gets the same
+/// port (as long as it's free). Ports that are in use, or that we're not allowed to bind, are
+/// skipped. When none of the ports could be bound, falls back to a port picked by the OS.
+#[tracing::instrument(level = Level::DEBUG, skip(seed), ret, err)]
+pub(crate) fn bind_listener<S: Hash>(
+    ip: IpAddr,
+    ports: Option<RangeInclusive<u16>>,
+    seed: S,
+) -> io::Result<TcpListener> {
+    let Some(ports) = ports else {
+        return create_listen_socket(SocketAddr::new(ip, 0));
+    };
+
+    for port in candidates(ports.clone(), seed) {
+        match create_listen_socket(SocketAddr::new(ip, port)) {
+            Ok(listener) => return Ok(listener),
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::AddrInUse | io::ErrorKind::PermissionDenied
+                ) =>
+            {
+                tracing::debug!(port, %error, "Port is not available, trying the next one");
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    tracing::warn!(
+        ?ports,
+        "None of the configured ports is available, falling back to a port picked by the OS",
+    );
+    create_listen_socket(SocketAddr::new(ip, 0))
+}
+
+/// All `ports`, starting from the one picked by the `seed`, and wrapping around.
+fn candidates<S: Hash>(ports: RangeInclusive<u16>, seed: S) -> impl Iterator<Item = u16> {
+    let start = u32::from(*ports.start());
+    let len = u32::from(*ports.end()) + 1 - start;
+
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    let offset = u32::try_from(hasher.finish() % u64::from(len)).unwrap_or_default();
+
+    (0..len).filter_map(move |i| u16::try_from(start + (offset + i) % len).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn candidates_cover_the_range() {
+        let ports = candidates(40000..=40099, "seed").collect::<Vec<_>>();
+
+        assert_eq!(ports.len(), 100);
+        assert_eq!(
+            ports.iter().copied().collect::<HashSet<_>>(),
+            (40000..=40099).collect::<HashSet<_>>()
+        );
+        assert_eq!(ports, candidates(40000..=40099, "seed").collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn candidates_full_range() {
+        assert_eq!(candidates(0..=u16::MAX, "seed").count(), 65536);
+        assert_eq!(candidates(8080..=8080, "seed").collect::<Vec<_>>(), [8080]);
+    }
+
+    #[tokio::test]
+    async fn skips_ports_in_use() {
+        let ip = IpAddr::from([127, 0, 0, 1]);
+        let taken = bind_listener(ip, None, ()).unwrap();
+        let taken_port = taken.local_addr().unwrap().port();
+
+        let listener = bind_listener(ip, Some(taken_port..=taken_port), ()).unwrap();
+        assert_ne!(listener.local_addr().unwrap().port(), taken_port);
+    }
+}
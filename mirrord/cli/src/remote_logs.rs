@@ -0,0 +1,190 @@
+//! Streaming the logs of the target container into the session output, see
+//! [`LayerConfig::remote_logs`].
+//!
+//! `mirrord exec` replaces itself with the user application, so the logs are streamed from a
+//! separate `mirrord remote-logs` child process ([`Commands::RemoteLogs`]), started with
+//! [`spawn`]. The child writes to the inherited stdout, and exits when its parent process is gone.
+//! The other flows ([`Commands::ExtensionExec`], [`Commands::Container`],
+//! [`Commands::ExtensionContainer`]) use the same child, so that the behavior is the same
+//! everywhere.
+//!
+//! The streaming can be paused and resumed by sending `SIGUSR1` to the child.
+//!
+//! [`Commands::RemoteLogs`]: crate::config::Commands::RemoteLogs
+//! [`Commands::ExtensionExec`]: crate::config::Commands::ExtensionExec
+//! [`Commands::Container`]: crate::config::Commands::Container
+//! [`Commands::ExtensionContainer`]: crate::config::Commands::ExtensionContainer
+
+use std::{
+    ops::Not,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use futures::StreamExt;
+use mirrord_config::{LayerConfig, target::Target};
+use mirrord_kube::api::{
+    kubernetes::{KubernetesAPI, logs::RemoteLogsSource},
+    runtime::RuntimeDataProvider,
+};
+use mirrord_progress::{
+    JsonProgress, MIRRORD_PROGRESS_ENV, NullProgress, Progress, ProgressTracker, SimpleProgress,
+};
+use nix::unistd::{Pid, getppid};
+use tokio::signal::unix::{SignalKind, signal};
+use tracing::Level;
+
+use crate::error::{CliError, CliResult};
+
+/// How long we wait before following the logs again, after the stream ended (e.g. the container
+/// restarted).
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// How often the child checks whether its parent is still alive.
+const PARENT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Spawns the `mirrord remote-logs` child process, if [`LayerConfig::remote_logs`] is enabled and
+/// the session has a target.
+///
+/// `json` forces the JSON progress in the child, for flows that default to it (IDE extensions).
+/// Otherwise, the child uses the progress mode from [`MIRRORD_PROGRESS_ENV`].
+///
+/// The child is not waited for, it exits on its own when this process (or the user application
+/// that replaced it) is gone.
+#[tracing::instrument(level = Level::DEBUG, skip(config), err)]
+pub(crate) fn spawn(config: &LayerConfig, json: bool) -> CliResult<()> {
+    let has_target = matches!(config.target.path, None | Some(Target::Targetless)).not();
+    if config.remote_logs.not() || has_target.not() {
+        return Ok(());
+    }
+
+    let mut command = Command::new(std::env::current_exe().map_err(CliError::CliPathError)?);
+    command
+        .arg("remote-logs")
+        .stdin(Stdio::null())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .env(LayerConfig::RESOLVED_CONFIG_ENV, config.encode()?);
+
+    if json {
+        command.env(MIRRORD_PROGRESS_ENV, "json");
+    }
+
+    command.spawn().map_err(CliError::RemoteLogsSpawnError)?;
+
+    Ok(())
+}
+
+/// Entry point of the `mirrord remote-logs` child process.
+///
+/// Failures are reported as progress warnings, they should never break the session.
+pub(crate) async fn remote_logs_command(config: LayerConfig) {
+    let mut progress = remote_logs_progress();
+    progress.set_fail_on_drop(false);
+
+    let parent = getppid();
+
+    let Some(target) = config.target.path.as_ref() else {
+        return;
+    };
+
+    let k8s_api = match KubernetesAPI::create(&config, &progress).await {
+        Ok(k8s_api) => k8s_api,
+        Err(error) => {
+            progress.warning(&format!("failed to stream the remote logs: {error}"));
+            return;
+        }
+    };
+
+    let source = match target
+        .runtime_data(k8s_api.client(), config.target.namespace.as_deref())
+        .await
+    {
+        Ok(runtime_data) => RemoteLogsSource::from(&runtime_data),
+        Err(error) => {
+            progress.warning(&format!("failed to stream the remote logs: {error}"));
+            return;
+        }
+    };
+
+    let mut toggle = match signal(SignalKind::user_defined1()) {
+        Ok(toggle) => toggle,
+        Err(error) => {
+            progress.warning(&format!("failed to stream the remote logs: {error}"));
+            return;
+        }
+    };
+
+    progress.info(&format!(
+        "streaming logs of container `{}` in pod `{}`, run `kill -USR1 {}` to pause/resume",
+        source.container_name,
+        source.pod_name,
+        std::process::id(),
+    ));
+
+    let mut parent_check = tokio::time::interval(PARENT_CHECK_INTERVAL);
+    let mut paused = false;
+
+    loop {
+        let mut lines = match source.follow(k8s_api.client()).await {
+            Ok(lines) => lines,
+            Err(error) => {
+                tracing::debug!(%error, "Failed to follow the remote logs");
+                futures::stream::empty().boxed()
+            }
+        };
+
+        loop {
+            tokio::select! {
+                _ = parent_check.tick() => {
+                    if parent_gone(parent) {
+                        return;
+                    }
+                }
+
+                Some(()) = toggle.recv() => {
+                    paused = !paused;
+                    progress.info(if paused {
+                        "remote logs paused"
+                    } else {
+                        "remote logs resumed"
+                    });
+                }
+
+                line = lines.next() => match line {
+                    Some(Ok(line)) if paused.not() => {
+                        progress.remote_log(&source.container_name, &line);
+                    }
+                    Some(Ok(..)) => {}
+                    Some(Err(error)) => {
+                        tracing::debug!(%error, "Remote logs stream failed");
+                        break;
+                    }
+                    None => break,
+                },
+            }
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+        if parent_gone(parent) {
+            return;
+        }
+    }
+}
+
+/// The parent died when we've been reparented.
+fn parent_gone(parent: Pid) -> bool {
+    getppid() != parent
+}
+
+/// The progress used in the child.
+///
+/// The spinner would fight over the terminal with the user application, so we fall back to the
+/// simple progress.
+fn remote_logs_progress() -> ProgressTracker {
+    match std::env::var(MIRRORD_PROGRESS_ENV).as_deref() {
+        Ok("json") => JsonProgress::new("mirrord remote logs").into(),
+        Ok("off") => NullProgress.into(),
+        _ => SimpleProgress.into(),
+    }
+}
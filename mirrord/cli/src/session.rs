@@ -0,0 +1,1034 @@
+//! Implementation of the named session commands: `mirrord start`, `mirrord attach` and
+//! `mirrord stop`.
+//!
+//! `mirrord start --name <NAME>` prepares the agent and `mirrord intproxy` just like
+//! `mirrord exec`, but instead of running a process it saves the environment required by the
+//! layer in a [`SessionStore`] and exits. The intproxy keeps running in the background with no
+//! idle timeout, holding the agent connection.
+//!
+//! `mirrord attach <NAME> -- <BINARY>` runs the binary with the saved environment, so that the
+//! layer connects to the session's intproxy. Any number of processes can be attached to the same
+//! session, at the same time or one after another, and all of them share the agent connection.
+//!
+//! `mirrord stop <NAME>` kills the intproxy, which ends the session.
+//!
+//! `mirrord pause <NAME> <PORT>` and `mirrord resume <NAME> <PORT>` connect to the intproxy like
+//! the layer does, and send it a [`PortPause`] request.
+//!
+//! `mirrord exec --warm` uses an implicit session, named after a hash of everything that the
+//! configuration is resolved from. The first run starts the session, and the following runs with
+//! the same configuration attach to it, skipping the agent startup.
+//!
+//! `mirrord exec --watch <GLOB>` runs the binary against a session too, and restarts it whenever a
+//! file matching the glob changes, without starting the agent again.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    ffi::OsStr,
+    net::SocketAddr,
+    ops::Not,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use miette::Diagnostic;
+use mirrord_analytics::{AnalyticsError, AnalyticsReporter, CollectAnalytics, Reporter};
+use mirrord_config::{LayerConfig, config::ConfigContext};
+use mirrord_intproxy_protocol::{PortPause, codec::CodecError};
+use mirrord_progress::{Progress, ProgressTracker};
+use mirrord_protocol::ResponseError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+use tracing::Level;
+
+#[cfg(target_os = "linux")]
+use crate::execution::INJECTION_ENV_VAR;
+use crate::{
+    CliResult,
+    config::{AttachArgs, PortPauseArgs, StartArgs, StopArgs},
+    execution::MirrordExecution,
+    user_data::UserData,
+};
+
+#[derive(Error, Debug, Diagnostic)]
+pub(crate) enum SessionError {
+    #[error("`{0}` is not a valid session name")]
+    #[diagnostic(help("Session names can contain only ASCII letters, digits, `-` and `_`."))]
+    InvalidName(String),
+
+    #[error("mirrord session `{0}` is already running")]
+    #[diagnostic(help("Stop it with `mirrord stop {0}`, or pick a different name."))]
+    AlreadyRunning(String),
+
+    #[error("mirrord session `{0}` is not running")]
+    #[diagnostic(help("Start it with `mirrord start --name {0}`."))]
+    NotRunning(String),
+
+    #[error("failed to access the session state: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("the session state in `{}` is not owned by the current user", .0.display())]
+    #[diagnostic(help("Remove it, and start the session again."))]
+    NotOwned(PathBuf),
+
+    #[error("failed to parse the session state: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[cfg(not(target_os = "windows"))]
+    #[error("failed to signal the session's internal proxy: {0}")]
+    Signal(#[from] nix::errno::Errno),
+
+    #[error("failed to communicate with the session's internal proxy: {0}")]
+    Codec(#[from] CodecError),
+
+    #[error("the session's internal proxy sent an unexpected response: {0}")]
+    UnexpectedResponse(String),
+
+    #[error("failed to pause/resume port {0}: {1}")]
+    #[diagnostic(help(
+        "Only ports that the session's processes listen on can be paused, \
+        and the mirrord agent must support it."
+    ))]
+    PortPause(u16, ResponseError),
+}
+
+/// Files describing a named session, kept in a per-session directory under
+/// [`SessionPaths::sessions_dir`].
+///
+/// The [`SessionStore`] holds the remote environment, which can contain secrets, and
+/// `mirrord attach` runs processes with it. So the session directory is accessible only to the
+/// current user, and we check that the files we read are owned by the current user.
+struct SessionPaths {
+    name: String,
+    dir: PathBuf,
+}
+
+impl SessionPaths {
+    /// Directory with the state of all named sessions, private to the current user:
+    /// `$XDG_RUNTIME_DIR/mirrord/sessions`, or `~/.mirrord/sessions`.
+    fn sessions_dir() -> PathBuf {
+        std::env::var_os("XDG_RUNTIME_DIR")
+            .filter(|dir| dir.is_empty().not())
+            .map(|dir| PathBuf::from(dir).join("mirrord"))
+            .unwrap_or_else(|| {
+                home::home_dir()
+                    .unwrap_or_else(|| PathBuf::from("~"))
+                    .join(".mirrord")
+            })
+            .join("sessions")
+    }
+
+    fn new(name: &str) -> Result<Self, SessionError> {
+        let valid = name.is_empty().not()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if valid.not() {
+            return Err(SessionError::InvalidName(name.to_string()));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            dir: Self::sessions_dir().join(name),
+        })
+    }
+
+    /// Creates the session directory, accessible only to the current user.
+    async fn create_dir(&self) -> Result<(), SessionError> {
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(not(target_os = "windows"))]
+        builder.mode(0o700);
+        builder.create(&self.dir).await?;
+
+        verify_owner(&fs::symlink_metadata(&self.dir).await?, &self.dir)
+    }
+
+    /// Writes a file of the session, readable only by the current user.
+    async fn write_file(path: &Path, contents: &[u8]) -> Result<(), SessionError> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(not(target_os = "windows"))]
+        options.mode(0o600);
+
+        let mut file = options.open(path).await?;
+        file.write_all(contents).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// Reads a file of the session, if it exists.
+    ///
+    /// Fails if the file or the session directory is not owned by the current user.
+    async fn read_file(&self, path: &Path) -> Result<Option<Vec<u8>>, SessionError> {
+        let mut file = match fs::File::open(path).await {
+            Ok(file) => file,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        verify_owner(&fs::symlink_metadata(&self.dir).await?, &self.dir)?;
+        verify_owner(&file.metadata().await?, path)?;
+
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).await?;
+        Ok(Some(contents))
+    }
+
+    /// File with the [`SessionStore`], written by `mirrord start`.
+    fn store(&self) -> PathBuf {
+        self.dir.join("session.json")
+    }
+
+    /// File with the pid of the session's intproxy, written by the intproxy itself.
+    fn intproxy_pid(&self) -> PathBuf {
+        self.dir.join("intproxy.pid")
+    }
+
+    /// Reads the pid of the session's intproxy, if the intproxy is still alive.
+    async fn running_intproxy(&self) -> Result<Option<u32>, SessionError> {
+        let Some(pid) = self.read_file(&self.intproxy_pid()).await? else {
+            return Ok(None);
+        };
+
+        let Ok(pid) = String::from_utf8_lossy(&pid).trim().parse::<u32>() else {
+            return Ok(None);
+        };
+
+        Ok(is_alive(pid)?.then_some(pid))
+    }
+
+    /// Removes all state of the session.
+    async fn remove(&self) -> Result<(), SessionError> {
+        match fs::remove_dir_all(&self.dir).await {
+            Ok(()) => Ok(()),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+/// What `mirrord attach` needs to run a process against a named session.
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionStore {
+    /// Variables to set in the user application environment, see
+    /// [`MirrordExecution::environment`].
+    environment: HashMap<String, String>,
+
+    /// Variables to unset in the user application environment, see
+    /// [`MirrordExecution::env_to_unset`].
+    env_to_unset: Vec<String>,
+
+    /// Address of the session's intproxy, see [`MirrordExecution::proxy_address`].
+    ///
+    /// Missing in sessions started by older versions.
+    #[serde(default)]
+    intproxy_address: Option<SocketAddr>,
+}
+
+impl SessionStore {
+    async fn write_to_file(&self, paths: &SessionPaths) -> Result<(), SessionError> {
+        paths.create_dir().await?;
+        SessionPaths::write_file(&paths.store(), &serde_json::to_vec(self)?).await
+    }
+
+    async fn read_from_file(paths: &SessionPaths) -> Result<Self, SessionError> {
+        match paths.read_file(&paths.store()).await? {
+            Some(contents) => Ok(serde_json::from_slice(&contents)?),
+            None => Err(SessionError::NotRunning(paths.name.clone())),
+        }
+    }
+
+    /// Environment for a process attached to the session: ours, with the session variables
+    /// applied.
+    #[cfg_attr(target_os = "windows", allow(dead_code))]
+    fn process_environment(self) -> HashMap<String, String> {
+        let mut env_vars: HashMap<String, String> = std::env::vars().collect();
+        env_vars.extend(self.environment);
+        env_vars.insert(mirrord_progress::MIRRORD_PROGRESS_ENV.into(), "off".into());
+        for key in &self.env_to_unset {
+            env_vars.remove(key);
+        }
+
+        env_vars
+    }
+
+    /// Adjusts the session to the binary, like [`MirrordExecution::start_internal`] does for
+    /// `mirrord exec`: picks the layer that the binary can load (linux), and patches the binary
+    /// for SIP (macos).
+    ///
+    /// The session is shared by different binaries, so this has to be done on every attach.
+    ///
+    /// Returns the path of the binary to execute.
+    #[cfg(not(target_os = "windows"))]
+    fn prepare_binary(
+        &mut self,
+        binary_path: PathBuf,
+        #[cfg(target_os = "macos")] binary_args: &[String],
+    ) -> CliResult<PathBuf> {
+        #[cfg(target_os = "linux")]
+        if std::env::var("MIRRORD_LAYER_FILE").is_err()
+            && let Some(injected) = self.environment.get_mut(INJECTION_ENV_VAR)
+        {
+            let layer = crate::extract::extract_library_for(
+                Some(&binary_path.to_string_lossy()),
+                &mirrord_progress::NullProgress,
+            )?;
+            // The session's layer comes last, see `MirrordExecution::start_internal`.
+            *injected = match injected.rsplit_once(':') {
+                Some((preloaded, _)) => format!("{preloaded}:{}", layer.display()),
+                None => layer.to_string_lossy().into_owned(),
+            };
+        }
+
+        #[cfg(target_os = "macos")]
+        if let Some(encoded) = self.environment.get(LayerConfig::RESOLVED_CONFIG_ENV) {
+            let config = LayerConfig::decode(encoded)?;
+            let args = binary_args
+                .iter()
+                .map(std::ffi::OsString::from)
+                .collect::<Vec<_>>();
+            if let Some(patched) = MirrordExecution::sip_patch_executable(
+                &config,
+                &binary_path.to_string_lossy(),
+                Some(&args),
+            )? {
+                return Ok(patched.into());
+            }
+        }
+
+        Ok(binary_path)
+    }
+}
+
+/// Called from the intproxy started by `mirrord start`, saves its pid so that the session can be
+/// found by `mirrord attach` and stopped by `mirrord stop`.
+#[tracing::instrument(level = Level::TRACE, err)]
+pub(crate) async fn register_intproxy(name: &str) -> Result<(), SessionError> {
+    let paths = SessionPaths::new(name)?;
+    paths.create_dir().await?;
+    SessionPaths::write_file(
+        &paths.intproxy_pid(),
+        std::process::id().to_string().as_bytes(),
+    )
+    .await
+}
+
+/// Handles `mirrord start`.
+pub(crate) async fn start_command(
+    args: StartArgs,
+    watch: drain::Watch,
+    user_data: &UserData,
+) -> CliResult<()> {
+    crate::ensure_not_nested()?;
+
+    let mut progress = ProgressTracker::from_env("mirrord start");
+
+    let paths = SessionPaths::new(&args.name)?;
+    if paths.running_intproxy().await?.is_some() {
+        return Err(SessionError::AlreadyRunning(args.name).into());
+    }
+    // Leftovers of a session whose intproxy is gone.
+    paths.remove().await?;
+
+    let mut cfg_context = ConfigContext::default().override_envs(args.params.as_env_vars());
+    let mut config = LayerConfig::resolve(&mut cfg_context)?;
+    crate::profile::apply_profile_if_configured(&mut config, &progress).await?;
+
+    let mut analytics = AnalyticsReporter::only_error(
+        config.telemetry,
+        Default::default(),
+        watch,
+        user_data.machine_id(),
+    );
+    (&config).collect_analytics(analytics.get_mut());
+
+    let result = config.verify(&mut cfg_context);
+    for warning in cfg_context.into_warnings() {
+        progress.warning(&warning);
+    }
+    result?;
+
+    start_session(&paths, &mut config, None, &mut progress, &mut analytics)
+        .await
+        .inspect_err(|_| {
+            if analytics.has_error().not() {
+                analytics.set_error(AnalyticsError::Unknown);
+            }
+        })?;
+
+    progress.success(Some(&format!(
+        "session `{name}` started, run processes with `mirrord attach {name} -- <BINARY>` \
+        and stop it with `mirrord stop {name}`",
+        name = args.name,
+    )));
+
+    Ok(())
+}
+
+/// Starts the agent and the intproxy of a named session, and saves the [`SessionStore`].
+///
+/// `binary` is the binary (with its args) that will be attached first, if we know it. The
+/// binaries are adjusted to the session when attached, see [`SessionStore::prepare_binary`].
+#[cfg_attr(target_os = "windows", allow(unused_variables))]
+async fn start_session<P: Progress>(
+    paths: &SessionPaths,
+    config: &mut LayerConfig,
+    binary: Option<(&str, &[String])>,
+    progress: &mut P,
+    analytics: &mut AnalyticsReporter,
+) -> CliResult<()> {
+    #[cfg(target_os = "macos")]
+    let binary_args = binary.map(|(_, args)| {
+        args.iter()
+            .map(std::ffi::OsString::from)
+            .collect::<Vec<_>>()
+    });
+
+    let execution = MirrordExecution::start_internal(
+        config,
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
+        binary.map(|(binary, _)| binary),
+        #[cfg(target_os = "macos")]
+        binary_args.as_deref(),
+        progress,
+        analytics,
+        None,
+        Some(&paths.name),
+    )
+    .await?;
+
+    SessionStore {
+        environment: execution.environment,
+        env_to_unset: execution.env_to_unset,
+        intproxy_address: Some(execution.proxy_address),
+    }
+    .write_to_file(paths)
+    .await?;
+
+    Ok(())
+}
+
+/// Name of the implicit session used by `mirrord exec --warm`.
+///
+/// Derived from everything that the [`LayerConfig`] is resolved from: the config file, the
+/// overrides from the command line, the `MIRRORD_` environment variables and the working
+/// directory. The resolved config itself can't be used, as it contains generated values (e.g. the
+/// session key).
+///
+/// The name is a SHA-256 digest of these inputs serialized to JSON, so it stays the same across
+/// mirrord releases, and a warm session started by an older CLI is reused.
+#[cfg_attr(target_os = "windows", allow(dead_code))]
+fn warm_session_name<V: AsRef<OsStr>>(
+    config_file: Option<&[u8]>,
+    overrides: &HashMap<&OsStr, V>,
+    env: impl IntoIterator<Item = (String, String)>,
+    cwd: Option<PathBuf>,
+) -> String {
+    /// Inputs of [`warm_session_name`], serialized with stable field and key order.
+    #[derive(Serialize)]
+    struct WarmSessionKey<'a> {
+        config_file: Option<&'a [u8]>,
+        overrides: BTreeMap<Cow<'a, str>, Cow<'a, str>>,
+        env: BTreeMap<String, String>,
+        cwd: Option<Cow<'a, str>>,
+    }
+
+    let key = WarmSessionKey {
+        config_file,
+        overrides: overrides
+            .iter()
+            .map(|(key, value)| (key.to_string_lossy(), value.as_ref().to_string_lossy()))
+            .collect(),
+        env: env
+            .into_iter()
+            .filter(|(key, _)| key.starts_with("MIRRORD_"))
+            .collect(),
+        cwd: cwd.as_deref().map(Path::to_string_lossy),
+    };
+    let key = serde_json::to_vec(&key).expect("string maps are always serializable to JSON");
+    let digest = Sha256::digest(&key);
+
+    format!("warm-{}", hex::encode(&digest[..8]))
+}
+
+/// Handles `mirrord exec --warm`.
+///
+/// Starts the session if there's no warm session for this configuration yet, and then attaches
+/// the user binary to it.
+#[cfg(not(target_os = "windows"))]
+pub(crate) async fn warm_exec<P: Progress>(
+    config: LayerConfig,
+    config_file_path: Option<&str>,
+    args: &crate::config::ExecArgs,
+    progress: &mut P,
+    analytics: &mut AnalyticsReporter,
+) -> CliResult<()> {
+    let paths = warm_session(config, config_file_path, args, progress, analytics).await?;
+    attach_to_session(&paths, &args.binary, &args.binary_args).await
+}
+
+/// Finds the warm session for this configuration, starting it if it's not running yet.
+#[cfg(not(target_os = "windows"))]
+async fn warm_session<P: Progress>(
+    mut config: LayerConfig,
+    config_file_path: Option<&str>,
+    args: &crate::config::ExecArgs,
+    progress: &mut P,
+    analytics: &mut AnalyticsReporter,
+) -> CliResult<SessionPaths> {
+    let config_file = match config_file_path {
+        Some(path) => Some(fs::read(path).await.map_err(SessionError::from)?),
+        None => None,
+    };
+    let name = warm_session_name(
+        config_file.as_deref(),
+        &args.params.as_env_vars(),
+        std::env::vars(),
+        std::env::current_dir().ok(),
+    );
+
+    let paths = SessionPaths::new(&name)?;
+    if paths.running_intproxy().await?.is_some() {
+        progress.success(Some(&format!("reusing warm session `{name}`")));
+    } else {
+        // Leftovers of a session whose intproxy is gone.
+        paths.remove().await?;
+        start_session(
+            &paths,
+            &mut config,
+            Some((&args.binary, &args.binary_args)),
+            progress,
+            analytics,
+        )
+        .await?;
+        progress.success(Some(&format!(
+            "warm session `{name}` started, stop it with `mirrord stop {name}`"
+        )));
+    }
+
+    Ok(paths)
+}
+
+/// How long we wait for the watched process to exit after `SIGTERM`, before we kill it.
+#[cfg(not(target_os = "windows"))]
+const WATCHED_PROCESS_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Handles `mirrord exec --watch`.
+///
+/// Runs the user binary against a session, and restarts it whenever a watched file changes. The
+/// session outlives the restarts, so the agent is not started again. While the binary is
+/// restarting, its port subscriptions are gone, and the stolen traffic goes to the remote target.
+///
+/// Uses the warm session with `--warm`, otherwise a private session that ends with this command.
+#[cfg(not(target_os = "windows"))]
+pub(crate) async fn watch_exec<P: Progress>(
+    mut config: LayerConfig,
+    config_file_path: Option<&str>,
+    args: &crate::config::ExecArgs,
+    progress: &mut P,
+    analytics: &mut AnalyticsReporter,
+) -> CliResult<()> {
+    let mut watcher = crate::watch::FileWatcher::new(&args.watch).await;
+
+    let paths = if args.warm {
+        warm_session(config, config_file_path, args, progress, analytics).await?
+    } else {
+        let paths = SessionPaths::new(&format!("watch-{}", std::process::id()))?;
+        paths.remove().await?;
+        start_session(
+            &paths,
+            &mut config,
+            Some((&args.binary, &args.binary_args)),
+            progress,
+            analytics,
+        )
+        .await?;
+        progress.success(None);
+        paths
+    };
+    progress.info(&format!(
+        "watching {} files, `{}` will be restarted when they change",
+        watcher.watched_files(),
+        args.binary,
+    ));
+
+    let result = run_watched(&paths, args, &mut watcher, progress).await;
+
+    if args.warm.not() {
+        let intproxy = paths.running_intproxy().await?;
+        paths.remove().await?;
+        if let Some(pid) = intproxy {
+            terminate(pid)?;
+        }
+    }
+
+    result
+}
+
+/// Runs the user binary with the environment of the session, restarting it on every change of
+/// the watched files, until we get `SIGINT`.
+#[cfg(not(target_os = "windows"))]
+async fn run_watched<P: Progress>(
+    paths: &SessionPaths,
+    args: &crate::config::ExecArgs,
+    watcher: &mut crate::watch::FileWatcher,
+    progress: &mut P,
+) -> CliResult<()> {
+    use tokio::process::Command;
+
+    use crate::CliError;
+
+    loop {
+        if paths.running_intproxy().await?.is_none() {
+            return Err(SessionError::NotRunning(paths.name.clone()).into());
+        }
+        let mut store = SessionStore::read_from_file(paths).await?;
+        let binary_path = store.prepare_binary(
+            crate::process_which(&args.binary)?,
+            #[cfg(target_os = "macos")]
+            &args.binary_args,
+        )?;
+        let environment = store.process_environment();
+
+        // In its own process group, so that we can stop the processes that it spawns too.
+        let mut child = Command::new(&binary_path)
+            .arg0(&args.binary)
+            .args(&args.binary_args)
+            .env_clear()
+            .envs(environment)
+            .process_group(0)
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|error| {
+                tracing::error!(%error, "Couldn't execute {}", args.binary);
+                CliError::BinaryExecuteFailed(args.binary.clone(), args.binary_args.clone())
+            })?;
+
+        let event = tokio::select! {
+            status = child.wait() => WatchEvent::Exited(status),
+            changed = watcher.changed() => WatchEvent::Changed(changed),
+            _ = tokio::signal::ctrl_c() => WatchEvent::Interrupted,
+        };
+
+        match event {
+            WatchEvent::Exited(status) => {
+                match status {
+                    Ok(status) if status.success() => {
+                        progress.info(&format!("`{}` exited, waiting for changes", args.binary));
+                    }
+                    Ok(status) => progress.warning(&format!(
+                        "`{}` exited with {status}, waiting for changes",
+                        args.binary
+                    )),
+                    Err(error) => progress.warning(&format!(
+                        "failed to wait for `{}`: {error}, waiting for changes",
+                        args.binary
+                    )),
+                }
+
+                tokio::select! {
+                    changed = watcher.changed() => progress.info(&format!(
+                        "{} changed, starting `{}`",
+                        changed.display(),
+                        args.binary
+                    )),
+                    _ = tokio::signal::ctrl_c() => return Ok(()),
+                }
+            }
+            WatchEvent::Changed(changed) => {
+                progress.info(&format!(
+                    "{} changed, restarting `{}`",
+                    changed.display(),
+                    args.binary
+                ));
+                stop_watched_process(child).await;
+            }
+            WatchEvent::Interrupted => {
+                stop_watched_process(child).await;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// What happened while the watched process was running, see [`run_watched`].
+#[cfg(not(target_os = "windows"))]
+enum WatchEvent {
+    /// The process exited on its own.
+    Exited(std::io::Result<std::process::ExitStatus>),
+    /// A watched file changed.
+    Changed(PathBuf),
+    /// We got `SIGINT`.
+    Interrupted,
+}
+
+/// Stops the process group of the watched process gracefully with `SIGTERM`, or kills it after
+/// [`WATCHED_PROCESS_STOP_TIMEOUT`].
+///
+/// The whole group is signaled, so that the children of the process (e.g. workers of a server, or
+/// the binary run by `cargo run`) don't outlive it.
+#[cfg(not(target_os = "windows"))]
+async fn stop_watched_process(mut child: tokio::process::Child) {
+    use nix::{
+        sys::signal::{Signal, killpg},
+        unistd::Pid,
+    };
+
+    let Some(pid) = child.id() else {
+        // Already reaped.
+        return;
+    };
+    let group = Pid::from_raw(pid as i32);
+
+    let _ = killpg(group, Signal::SIGTERM);
+    if tokio::time::timeout(WATCHED_PROCESS_STOP_TIMEOUT, child.wait())
+        .await
+        .is_err()
+    {
+        let _ = killpg(group, Signal::SIGKILL);
+        let _ = child.wait().await;
+    }
+}
+
+/// Handles `mirrord attach`, replacing this process with the user binary.
+#[cfg(not(target_os = "windows"))]
+pub(crate) async fn attach_command(args: AttachArgs) -> CliResult<()> {
+    crate::ensure_not_nested()?;
+
+    let paths = SessionPaths::new(&args.name)?;
+    attach_to_session(&paths, &args.binary, &args.binary_args).await
+}
+
+/// Replaces this process with the user binary, running with the environment of the session.
+#[cfg(not(target_os = "windows"))]
+async fn attach_to_session(
+    paths: &SessionPaths,
+    binary: &str,
+    binary_args: &[String],
+) -> CliResult<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    use crate::CliError;
+
+    if paths.running_intproxy().await?.is_none() {
+        paths.remove().await?;
+        return Err(SessionError::NotRunning(paths.name.clone()).into());
+    }
+    let mut store = SessionStore::read_from_file(paths).await?;
+    let binary_path = store.prepare_binary(
+        crate::process_which(binary)?,
+        #[cfg(target_os = "macos")]
+        binary_args,
+    )?;
+    let env_vars = store.process_environment();
+
+    let path = CString::new(binary_path.as_os_str().as_bytes())?;
+
+    let binary_args = std::iter::once(binary)
+        .chain(binary_args.iter().map(String::as_str))
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    let argv = binary_args
+        .iter()
+        .cloned()
+        .map(CString::new)
+        .collect::<CliResult<Vec<_>, _>>()?;
+    let env = env_vars
+        .into_iter()
+        .map(|(k, v)| CString::new(format!("{k}={v}")))
+        .collect::<CliResult<Vec<_>, _>>()?;
+
+    let errno = nix::unistd::execve(&path, argv.as_slice(), env.as_slice())
+        .expect_err("call to execve cannot succeed");
+    tracing::error!("Couldn't execute {:?}", errno);
+
+    if errno == nix::errno::Errno::E2BIG {
+        return Err(CliError::ExecveE2Big);
+    }
+
+    Err(CliError::BinaryExecuteFailed(
+        binary.to_string(),
+        binary_args,
+    ))
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) async fn attach_command(_: AttachArgs) -> CliResult<()> {
+    unimplemented!("Command not supported on windows.");
+}
+
+/// Handles `mirrord stop`.
+pub(crate) async fn stop_command(args: StopArgs) -> CliResult<()> {
+    let mut progress = ProgressTracker::from_env("mirrord stop");
+
+    let paths = SessionPaths::new(&args.name)?;
+    let intproxy = paths.running_intproxy().await?;
+    paths.remove().await?;
+
+    match intproxy {
+        Some(pid) => {
+            terminate(pid)?;
+            progress.success(Some(&format!("session `{}` stopped", args.name)));
+            Ok(())
+        }
+        None => {
+            progress.failure(None);
+            Err(SessionError::NotRunning(args.name).into())
+        }
+    }
+}
+
+/// How long we wait for the session's intproxy to respond.
+#[cfg_attr(target_os = "windows", allow(dead_code))]
+const INTPROXY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Handles `mirrord pause` (when `paused` is set) and `mirrord resume`.
+#[cfg(not(target_os = "windows"))]
+pub(crate) async fn port_pause_command(args: PortPauseArgs, paused: bool) -> CliResult<()> {
+    let mut progress = ProgressTracker::from_env(if paused {
+        "mirrord pause"
+    } else {
+        "mirrord resume"
+    });
+
+    let paths = SessionPaths::new(&args.name)?;
+    let intproxy_addr = if paths.running_intproxy().await?.is_some() {
+        let mut store = SessionStore::read_from_file(&paths).await?;
+        store
+            .intproxy_address
+            .map(|address| address.to_string())
+            .or_else(|| {
+                store
+                    .environment
+                    .remove(mirrord_config::MIRRORD_LAYER_INTPROXY_ADDR)
+            })
+    } else {
+        None
+    };
+    let Some(intproxy_addr) = intproxy_addr else {
+        progress.failure(None);
+        return Err(SessionError::NotRunning(args.name).into());
+    };
+
+    let request = PortPause {
+        port: args.port,
+        paused,
+    };
+    match send_port_pause(&intproxy_addr, request) {
+        Ok(()) => {
+            progress.success(Some(&format!(
+                "port {} {} in session `{}`",
+                args.port,
+                if paused { "paused" } else { "resumed" },
+                args.name,
+            )));
+            Ok(())
+        }
+        Err(error) => {
+            progress.failure(None);
+            Err(error.into())
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) async fn port_pause_command(_: PortPauseArgs, _: bool) -> CliResult<()> {
+    unimplemented!("Command not supported on windows.");
+}
+
+/// Sends the [`PortPause`] request to the intproxy listening on `intproxy_addr`, introducing
+/// ourselves with a new session first, like the layer does.
+#[cfg(not(target_os = "windows"))]
+fn send_port_pause(intproxy_addr: &str, request: PortPause) -> Result<(), SessionError> {
+    use std::net::TcpStream;
+
+    use mirrord_intproxy_protocol::{
+        IncomingRequest, IncomingResponse, LayerToProxyMessage, LocalMessage, NewSessionRequest,
+        ProcessInfo, ProxyToLayerMessage, codec,
+    };
+
+    let connection = TcpStream::connect(intproxy_addr)?;
+    connection.set_read_timeout(Some(INTPROXY_TIMEOUT))?;
+    connection.set_write_timeout(Some(INTPROXY_TIMEOUT))?;
+
+    let (mut sender, mut receiver) = codec::make_sync_framed::<
+        LocalMessage<LayerToProxyMessage>,
+        LocalMessage<ProxyToLayerMessage>,
+    >(connection)?;
+
+    let mut exchange = |message_id, inner| -> Result<ProxyToLayerMessage, SessionError> {
+        sender.send(&LocalMessage { message_id, inner })?;
+        receiver
+            .receive()?
+            .map(|response| response.inner)
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into())
+    };
+
+    let session = NewSessionRequest {
+        parent_layer: None,
+        process_info: ProcessInfo {
+            pid: std::process::id() as i32,
+            parent_pid: std::os::unix::process::parent_id() as i32,
+            name: "mirrord".into(),
+            cmdline: std::env::args().collect(),
+            loaded: false,
+        },
+    };
+    match exchange(0, LayerToProxyMessage::NewSession(session))? {
+        ProxyToLayerMessage::NewSession(..) => {}
+        other => return Err(SessionError::UnexpectedResponse(format!("{other:?}"))),
+    }
+
+    match exchange(
+        1,
+        LayerToProxyMessage::Incoming(IncomingRequest::PortPause(request)),
+    )? {
+        ProxyToLayerMessage::Incoming(IncomingResponse::PortPause(result)) => {
+            result.map_err(|error| SessionError::PortPause(request.port, error))
+        }
+        other => Err(SessionError::UnexpectedResponse(format!("{other:?}"))),
+    }
+}
+
+/// Verifies that the session state at `path` is owned by the current user, so that we don't trust
+/// a session planted by someone else.
+#[cfg(not(target_os = "windows"))]
+fn verify_owner(metadata: &std::fs::Metadata, path: &Path) -> Result<(), SessionError> {
+    use std::os::unix::fs::MetadataExt;
+
+    if metadata.uid() == nix::unistd::getuid().as_raw() {
+        Ok(())
+    } else {
+        Err(SessionError::NotOwned(path.to_path_buf()))
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn verify_owner(_: &std::fs::Metadata, _: &Path) -> Result<(), SessionError> {
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn is_alive(pid: u32) -> Result<bool, SessionError> {
+    use nix::{errno::Errno, sys::signal::kill, unistd::Pid};
+
+    match kill(Pid::from_raw(pid as i32), None) {
+        Ok(()) => Ok(true),
+        // ESRCH means that the process has already exited.
+        Err(Errno::ESRCH) => Ok(false),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn is_alive(_: u32) -> Result<bool, SessionError> {
+    unimplemented!("Command not supported on windows.");
+}
+
+#[cfg(not(target_os = "windows"))]
+fn terminate(pid: u32) -> Result<(), SessionError> {
+    use nix::{
+        errno::Errno,
+        sys::signal::{Signal, kill},
+        unistd::Pid,
+    };
+
+    match kill(Pid::from_raw(pid as i32), Some(Signal::SIGTERM)) {
+        Ok(()) | Err(Errno::ESRCH) => Ok(()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn terminate(_: u32) -> Result<(), SessionError> {
+    unimplemented!("Command not supported on windows.");
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, ffi::OsStr};
+
+    use rstest::rstest;
+
+    use super::{SessionPaths, warm_session_name};
+
+    #[rstest]
+    #[case("dev", true)]
+    #[case("my-session_2", true)]
+    #[case("", false)]
+    #[case("../etc", false)]
+    #[case("a b", false)]
+    fn session_name_validation(#[case] name: &str, #[case] valid: bool) {
+        assert_eq!(SessionPaths::new(name).is_ok(), valid);
+    }
+
+    #[test]
+    fn warm_session_name_is_stable() {
+        let overrides = HashMap::from([
+            (OsStr::new("MIRRORD_IMPERSONATED_TARGET"), "pod/app"),
+            (OsStr::new("MIRRORD_AGENT_NAMESPACE"), "default"),
+        ]);
+        let env = || {
+            [
+                ("MIRRORD_AGENT_TTL".to_string(), "30".to_string()),
+                ("HOME".to_string(), "/home/user".to_string()),
+            ]
+        };
+
+        let name = warm_session_name(Some(b"{}".as_slice()), &overrides, env(), None);
+        assert!(SessionPaths::new(&name).is_ok());
+        assert_eq!(
+            name,
+            warm_session_name(Some(b"{}".as_slice()), &overrides, env(), None)
+        );
+
+        // Unrelated variables don't matter.
+        let other_env = [("MIRRORD_AGENT_TTL".to_string(), "30".to_string())];
+        assert_eq!(
+            name,
+            warm_session_name(Some(b"{}".as_slice()), &overrides, other_env, None)
+        );
+
+        // The config file does.
+        assert_ne!(name, warm_session_name(None, &overrides, env(), None));
+
+        // Pinned, so that a change that breaks reusing warm sessions across releases is noticed.
+        assert_eq!(name, "warm-e292c01fb2966d87");
+    }
+
+    /// Verifies that the session state is accessible only to the current user.
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test]
+    async fn session_state_is_private() {
+        use std::{os::unix::fs::PermissionsExt, path::Path};
+
+        let root = tempfile::tempdir().unwrap();
+        let paths = SessionPaths {
+            name: "dev".into(),
+            dir: root.path().join("sessions").join("dev"),
+        };
+
+        paths.create_dir().await.unwrap();
+        SessionPaths::write_file(
+            &paths.intproxy_pid(),
+            std::process::id().to_string().as_bytes(),
+        )
+        .await
+        .unwrap();
+
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&paths.dir), 0o700);
+        assert_eq!(mode(&paths.intproxy_pid()), 0o600);
+
+        assert_eq!(
+            paths.running_intproxy().await.unwrap(),
+            Some(std::process::id())
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! Implements the `mirrord top` command, see [`top_command`].
+
+use std::{collections::HashMap, fmt::Write, ops::Not, time::Duration};
+
+use mirrord_analytics::{AnalyticsReporter, CollectAnalytics, ExecutionKind};
+use mirrord_config::{LayerConfig, config::ConfigContext, target::Target};
+use mirrord_progress::{Progress, ProgressTracker};
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, ResponseError,
+    resources::{GetResourceUsageRequest, RESOURCE_USAGE_VERSION, ResourceUsage},
+};
+use mirrord_sdk::client::{AgentClient, AgentClientError};
+use semver::Version;
+use thiserror::Error;
+use tokio::time::{self, Instant, MissedTickBehavior};
+
+use super::config::TopArgs;
+use crate::{
+    CliError, connection::create_and_connect, cp::DisplayBytes, error::CliResult,
+    user_data::UserData,
+};
+
+/// Errors that can occur when reading the target's resource usage with `mirrord top`.
+#[derive(Debug, Error)]
+pub enum TopError {
+    #[error(transparent)]
+    Agent(#[from] AgentClientError),
+
+    #[error(
+        "mirrord-agent does not support reporting resource usage, protocol version used by \
+        mirrord-agent ({0}) must match {}",
+        *RESOURCE_USAGE_VERSION
+    )]
+    NotSupported(Version),
+
+    #[error("failed to read the resource usage of the target: {0}")]
+    Remote(ResponseError),
+}
+
+/// Implements the `mirrord top` command.
+///
+/// Periodically prints the CPU and memory usage of the target container and its processes,
+/// together with the container limits, so that the load of the remote application can be compared
+/// with the local one (e.g. while stealing traffic).
+pub async fn top_command(
+    args: &TopArgs,
+    watch: drain::Watch,
+    user_data: &UserData,
+) -> CliResult<()> {
+    let mut cfg_context = ConfigContext::default().override_envs(args.params.as_env_vars());
+    let mut config = LayerConfig::resolve(&mut cfg_context)?;
+
+    let mut progress = ProgressTracker::from_env("mirrord top");
+    let mut analytics = AnalyticsReporter::new(
+        config.telemetry,
+        ExecutionKind::Top,
+        watch,
+        user_data.machine_id(),
+    );
+
+    if matches!(config.target.path, Some(Target::Targetless)) || config.target.path.is_none() {
+        return Err(CliError::MissingArg {
+            command: "mirrord top".to_string(),
+            arg: "target".to_string(),
+        });
+    }
+
+    if !args.params.disable_version_check {
+        super::prompt_outdated_version(&progress).await;
+    }
+
+    (&config).collect_analytics(analytics.get_mut());
+
+    let (_connection_info, connection) =
+        create_and_connect(&mut config, &mut progress, &mut analytics, None, None).await?;
+
+    let client = AgentClient::new(connection).await.map_err(TopError::from)?;
+    let mut session = TopSession::new(client)?;
+    progress.success(None);
+
+    let mut interval = time::interval(Duration::from_secs(args.interval.max(1)));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval.tick().await;
+
+    let mut previous = Sample {
+        usage: session.resource_usage().await?,
+        taken_at: Instant::now(),
+    };
+
+    for _ in 0..args.iterations.unwrap_or(u32::MAX) {
+        interval.tick().await;
+
+        let current = Sample {
+            usage: session.resource_usage().await?,
+            taken_at: Instant::now(),
+        };
+        println!("{}", Report::new(&previous, &current));
+        previous = current;
+    }
+
+    Ok(())
+}
+
+/// [`ResourceUsage`] read at some point in time.
+struct Sample {
+    usage: ResourceUsage,
+    taken_at: Instant,
+}
+
+/// Resource usage of the target between two [`Sample`]s, displayed as a `top` like table.
+struct Report<'a> {
+    /// Number of cores used by the container.
+    cpu_cores: Option<f64>,
+    /// Number of cores used by each process, by pid.
+    process_cores: HashMap<u32, f64>,
+    usage: &'a ResourceUsage,
+}
+
+impl<'a> Report<'a> {
+    fn new(previous: &Sample, current: &'a Sample) -> Self {
+        let elapsed_usec = current
+            .taken_at
+            .duration_since(previous.taken_at)
+            .as_micros()
+            .max(1) as f64;
+        let cores =
+            |previous: u64, current: u64| current.saturating_sub(previous) as f64 / elapsed_usec;
+
+        let cpu_cores = previous
+            .usage
+            .cpu_time_usec
+            .zip(current.usage.cpu_time_usec)
+            .map(|(previous, current)| cores(previous, current));
+
+        let previous_processes = previous
+            .usage
+            .processes
+            .iter()
+            .map(|process| (process.pid, process.cpu_time_usec))
+            .collect::<HashMap<_, _>>();
+        let process_cores = current
+            .usage
+            .processes
+            .iter()
+            .map(|process| {
+                // Processes that started after the previous sample used all their CPU time since.
+                let previous = previous_processes.get(&process.pid).copied().unwrap_or(0);
+                (process.pid, cores(previous, process.cpu_time_usec))
+            })
+            .collect();
+
+        Self {
+            cpu_cores,
+            process_cores,
+            usage: &current.usage,
+        }
+    }
+}
+
+impl std::fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut summary = String::from("CPU: ");
+        match self.cpu_cores {
+            Some(cores) => write!(summary, "{cores:.2} cores")?,
+            None => summary.push_str("unknown"),
+        }
+        match self.usage.cpu_limit_millicores {
+            Some(limit) => write!(summary, " / {:.2} cores", limit as f64 / 1000.0)?,
+            None => summary.push_str(" (no limit)"),
+        }
+
+        summary.push_str(", memory: ");
+        match self.usage.memory_bytes {
+            Some(bytes) => write!(summary, "{}", DisplayBytes(bytes))?,
+            None => summary.push_str("unknown"),
+        }
+        match self.usage.memory_limit_bytes {
+            Some(limit) => write!(summary, " / {}", DisplayBytes(limit))?,
+            None => summary.push_str(" (no limit)"),
+        }
+
+        writeln!(f, "{summary}")?;
+        writeln!(f, "{:>8} {:>7} {:>11}  NAME", "PID", "CPU%", "MEMORY")?;
+
+        let mut processes = self.usage.processes.iter().collect::<Vec<_>>();
+        let process_cores = |pid| self.process_cores.get(&pid).copied().unwrap_or_default();
+        processes.sort_by(|a, b| process_cores(b.pid).total_cmp(&process_cores(a.pid)));
+
+        for process in processes {
+            writeln!(
+                f,
+                "{:>8} {:>7.1} {:>11}  {}",
+                process.pid,
+                process_cores(process.pid) * 100.0,
+                DisplayBytes(process.memory_bytes).to_string(),
+                process.name
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Implements `mirrord top` logic on an established [`AgentClient`].
+struct TopSession {
+    client: AgentClient,
+}
+
+impl TopSession {
+    /// Verifies that the agent can report its resource usage.
+    fn new(client: AgentClient) -> Result<Self, TopError> {
+        let version = client.protocol_version();
+        if RESOURCE_USAGE_VERSION.matches(version).not() {
+            return Err(TopError::NotSupported(version.clone()));
+        }
+
+        Ok(Self { client })
+    }
+
+    /// Reads the current resource usage of the target.
+    async fn resource_usage(&mut self) -> Result<ResourceUsage, TopError> {
+        match self
+            .client
+            .request(ClientMessage::GetResourceUsage(GetResourceUsageRequest))
+            .await?
+        {
+            DaemonMessage::ResourceUsage(result) => result.map_err(TopError::Remote),
+            other => Err(AgentClientError::unexpected(other).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mirrord_protocol::resources::ProcessUsage;
+
+    use super::*;
+
+    fn sample(cpu_time_usec: u64, processes: &[(u32, u64)], taken_at: Instant) -> Sample {
+        Sample {
+            usage: ResourceUsage {
+                cpu_time_usec: Some(cpu_time_usec),
+                cpu_limit_millicores: Some(2000),
+                memory_bytes: Some(3 * 1024 * 1024),
+                memory_limit_bytes: None,
+                processes: processes
+                    .iter()
+                    .map(|(pid, cpu_time_usec)| ProcessUsage {
+                        pid: *pid,
+                        name: format!("proc-{pid}"),
+                        cpu_time_usec: *cpu_time_usec,
+                        memory_bytes: 1024,
+                    })
+                    .collect(),
+            },
+            taken_at,
+        }
+    }
+
+    /// Verifies that the CPU usage is computed from the difference between the samples, and the
+    /// busiest process goes first.
+    #[test]
+    fn report_from_samples() {
+        let start = Instant::now();
+        let previous = sample(1_000_000, &[(1, 500_000), (7, 500_000)], start);
+        let current = sample(
+            2_000_000,
+            &[(1, 600_000), (7, 1_300_000), (9, 100_000)],
+            start + Duration::from_secs(2),
+        );
+
+        let report = Report::new(&previous, &current).to_string();
+        let lines = report.lines().collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            [
+                "CPU: 0.50 cores / 2.00 cores, memory: 3.0 MiB (no limit)",
+                "     PID    CPU%      MEMORY  NAME",
+                "       7    40.0     1.0 KiB  proc-7",
+                "       1     5.0     1.0 KiB  proc-1",
+                "       9     5.0     1.0 KiB  proc-9",
+            ]
+        );
+    }
+}
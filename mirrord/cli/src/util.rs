@@ -66,8 +66,11 @@ unsafe fn redirect_fd_to_dev_null(fd: libc::c_int) {
 /// Create a new session for the proxy process, detaching from the original terminal.
 /// This makes the process not to receive signals from the "mirrord" process or it's parent
 /// terminal fixes some side effects such as <https://github.com/metalbear-co/mirrord/issues/1232>
+///
+/// With `keep_stderr`, stderr is left as it is, so that the process can still print to the
+/// terminal it inherited.
 #[cfg(not(target_os = "windows"))]
-pub(crate) unsafe fn detach_io() -> Result<(), nix::Error> {
+pub(crate) unsafe fn detach_io(keep_stderr: bool) -> Result<(), nix::Error> {
     unsafe {
         nix::unistd::setsid()?;
 
@@ -76,9 +79,12 @@ pub(crate) unsafe fn detach_io() -> Result<(), nix::Error> {
             // best effort
             let _ = std::io::stdout().lock().flush();
         }
-        for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO] {
             redirect_fd_to_dev_null(fd);
         }
+        if !keep_stderr {
+            redirect_fd_to_dev_null(libc::STDERR_FILENO);
+        }
         Ok(())
     }
 }
@@ -0,0 +1,263 @@
+//! Watching local files for `mirrord exec --watch`, see [`FileWatcher`].
+
+use std::{
+    collections::HashMap,
+    fs,
+    ops::Not,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use tokio::time;
+use wildmatch::WildMatch;
+
+/// Delay between the scans of the watched files.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// After a change, we wait until the files stop changing for this long, so that a burst of
+/// writes (e.g. a formatter or a `git checkout`) causes only one restart.
+const SETTLE_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Directories that are not scanned, unless a pattern starts in them: version control, build
+/// outputs, dependencies and caches. They are big, and change on every build or install.
+const IGNORED_DIRS: &[&str] = &[
+    ".git",
+    ".hg",
+    ".svn",
+    "target",
+    "node_modules",
+    "__pycache__",
+    ".venv",
+    "venv",
+    ".tox",
+    ".mypy_cache",
+    ".pytest_cache",
+    ".gradle",
+    ".next",
+    "dist",
+    "build",
+];
+
+/// A glob pattern given to `--watch`, e.g. `src/**` or `**/*.py`, matched with [`WildMatch`].
+///
+/// `*` matches any characters (including `/`), and `?` matches a single character. A pattern that
+/// matches a directory matches all files below it.
+#[derive(Debug)]
+struct WatchPattern {
+    /// Where to look for the matching files, the part of the pattern before the first wildcard.
+    root: PathBuf,
+    /// Match the watched files, by their path relative to the working directory.
+    globs: Vec<WildMatch>,
+}
+
+impl WatchPattern {
+    fn new(glob: &str) -> Self {
+        let glob = glob.trim_start_matches("./");
+
+        let root = glob
+            .split('/')
+            .take_while(|component| component.contains(['*', '?']).not())
+            .collect::<Vec<_>>()
+            .join("/");
+        let root = match root.as_str() {
+            "" if glob.starts_with('/') => PathBuf::from("/"),
+            "" => PathBuf::from("."),
+            root => PathBuf::from(root),
+        };
+
+        // `**/` also matches no directories at all.
+        let globs = std::iter::once(glob)
+            .chain(glob.strip_prefix("**/"))
+            .map(WildMatch::new)
+            .collect();
+
+        Self { root, globs }
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        let path = path.strip_prefix(".").unwrap_or(path);
+        path.ancestors()
+            .filter(|path| path.as_os_str().is_empty().not())
+            .any(|path| {
+                let path = path.to_string_lossy();
+                self.globs.iter().any(|glob| glob.matches(&path))
+            })
+    }
+}
+
+/// Modification times of the watched files.
+type Snapshot = HashMap<PathBuf, Option<SystemTime>>;
+
+/// Watches the files matching the `--watch` patterns, by periodically scanning them.
+///
+/// Polling does not need any OS specific APIs, and is cheap enough for the source directories
+/// that are usually watched.
+pub(crate) struct FileWatcher {
+    patterns: Arc<[WatchPattern]>,
+    snapshot: Snapshot,
+}
+
+impl FileWatcher {
+    /// Creates a new watcher, taking the current state of the files as the baseline.
+    pub(crate) async fn new(globs: &[String]) -> Self {
+        let patterns = globs
+            .iter()
+            .map(|glob| WatchPattern::new(glob))
+            .collect::<Arc<[_]>>();
+        let snapshot = scan(patterns.clone()).await;
+
+        Self { patterns, snapshot }
+    }
+
+    /// Number of the files currently watched.
+    pub(crate) fn watched_files(&self) -> usize {
+        self.snapshot.len()
+    }
+
+    /// Waits until any of the watched files is created, modified or removed, and returns its
+    /// path.
+    pub(crate) async fn changed(&mut self) -> PathBuf {
+        loop {
+            time::sleep(POLL_INTERVAL).await;
+
+            let current = scan(self.patterns.clone()).await;
+            let Some(changed) = first_difference(&self.snapshot, &current) else {
+                continue;
+            };
+
+            self.snapshot = current;
+            loop {
+                time::sleep(SETTLE_INTERVAL).await;
+                let current = scan(self.patterns.clone()).await;
+                if current == self.snapshot {
+                    break;
+                }
+                self.snapshot = current;
+            }
+
+            return changed;
+        }
+    }
+}
+
+/// Returns a file that was created, modified or removed between the snapshots.
+fn first_difference(previous: &Snapshot, current: &Snapshot) -> Option<PathBuf> {
+    current
+        .iter()
+        .find(|(path, modified)| previous.get(*path) != Some(*modified))
+        .or_else(|| {
+            previous
+                .iter()
+                .find(|(path, _)| current.contains_key(*path).not())
+        })
+        .map(|(path, _)| path.clone())
+}
+
+/// Reads the modification times of all files matching the patterns.
+///
+/// Files that can't be read (e.g. removed while we scan) are skipped.
+async fn scan(patterns: Arc<[WatchPattern]>) -> Snapshot {
+    tokio::task::spawn_blocking(move || {
+        let mut snapshot = Snapshot::new();
+
+        for pattern in patterns.iter() {
+            let mut paths = vec![pattern.root.clone()];
+            while let Some(path) = paths.pop() {
+                let Ok(metadata) = fs::metadata(&path) else {
+                    continue;
+                };
+
+                if metadata.is_dir() {
+                    let ignored = path != pattern.root
+                        && path.file_name().is_some_and(|name| {
+                            IGNORED_DIRS.iter().any(|ignored| name == *ignored)
+                        });
+                    if ignored {
+                        continue;
+                    }
+
+                    if let Ok(entries) = fs::read_dir(&path) {
+                        paths.extend(entries.filter_map(Result::ok).map(|entry| entry.path()));
+                    }
+                } else if pattern.matches(&path) {
+                    snapshot.insert(path, metadata.modified().ok());
+                }
+            }
+        }
+
+        snapshot
+    })
+    .await
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::Path, sync::Arc};
+
+    use rstest::rstest;
+
+    use super::{WatchPattern, scan};
+
+    #[rstest]
+    #[case("src/**", "src/main.rs", true)]
+    #[case("src/**", "src/api/handlers.rs", true)]
+    #[case("src/**", "tests/main.rs", false)]
+    #[case("./src/*.rs", "./src/main.rs", true)]
+    #[case("src/*.rs", "src/api/handlers.rs", true)]
+    #[case("src/*.rs", "tests/main.rs", false)]
+    #[case("**/*.py", "app.py", true)]
+    #[case("**/*.py", "app/views.py", true)]
+    #[case("**/*.py", "app/views.pyc", false)]
+    #[case("src", "src/main.rs", true)]
+    #[case("Cargo.toml", "Cargo.toml", true)]
+    #[case("Cargo.toml", "Cargo.tomlx", false)]
+    #[case("config/app.?ml", "config/app.yml", true)]
+    fn glob_matching(#[case] glob: &str, #[case] path: &str, #[case] matches: bool) {
+        assert_eq!(WatchPattern::new(glob).matches(Path::new(path)), matches);
+    }
+
+    #[rstest]
+    #[case("src/**", "src")]
+    #[case("./src/api/*.rs", "src/api")]
+    #[case("**/*.py", ".")]
+    #[case("/etc/app/*.conf", "/etc/app")]
+    fn glob_root(#[case] glob: &str, #[case] root: &str) {
+        assert_eq!(WatchPattern::new(glob).root, Path::new(root));
+    }
+
+    /// Verifies that build outputs and dependencies are not scanned, unless the pattern starts in
+    /// them.
+    #[tokio::test]
+    async fn ignored_dirs_are_not_scanned() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in [
+            "src/main.rs",
+            "target/debug/app",
+            "node_modules/lib/index.js",
+            ".git/HEAD",
+        ] {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        let scanned = |glob: &str| {
+            let pattern = WatchPattern::new(&format!("{}/{glob}", dir.path().display()));
+            scan(Arc::from([pattern]))
+        };
+
+        let snapshot = scanned("**").await;
+        assert_eq!(
+            snapshot.into_keys().collect::<Vec<_>>(),
+            [dir.path().join("src/main.rs")]
+        );
+
+        let snapshot = scanned("target/**").await;
+        assert_eq!(
+            snapshot.into_keys().collect::<Vec<_>>(),
+            [dir.path().join("target/debug/app")]
+        );
+    }
+}
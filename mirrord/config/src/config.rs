@@ -10,7 +10,10 @@ pub use context::ConfigContext;
 use thiserror::Error;
 
 pub use crate::env_key::EnvKey;
-use crate::feature::split_queues::QueueSplittingVerificationError;
+use crate::{
+    feature::split_queues::QueueSplittingVerificationError, profiles::ConfigProfileError,
+    variables::ConfigVariableError,
+};
 
 /// <!--${internal}-->
 /// Error that would be returned from [MirrordConfig::generate_config]
@@ -77,6 +80,25 @@ pub enum ConfigError {
         fail: Box<fancy_regex::Error>,
     },
 
+    /// The mirrord-agent does not have a capability required by the config.
+    #[error(
+        "`{feature}` is not supported by the mirrord-agent, which uses protocol version \
+        {agent_version}. Consider using a newer version of mirrord-agent, or disabling `{feature}` \
+        in the mirrord config."
+    )]
+    UnsupportedByAgent {
+        feature: &'static str,
+        agent_version: semver::Version,
+    },
+
+    /// When preparing the `EnvVarsRenamer`, a pattern may be invalid.
+    #[error("Invalid pattern `{pattern}: {value}` in `config.feature.env.rename`: {reason}")]
+    InvalidEnvRename {
+        pattern: String,
+        value: String,
+        reason: String,
+    },
+
     #[error("Decoding resolved config failed: {0}")]
     DecodeError(String),
 
@@ -96,6 +118,15 @@ pub enum FromFileError {
     ParseToml(#[from] toml::de::Error),
     ParseJson(#[from] serde_json::Error),
     ParseYaml(#[from] serde_yaml::Error),
+    ParseJson5(#[from] json5::Error),
+    Include {
+        path: PathBuf,
+        #[source]
+        error: Box<FromFileError>,
+    },
+    IncludeCycle(PathBuf),
+    Profile(#[from] ConfigProfileError),
+    Variable(#[from] ConfigVariableError),
 }
 
 impl From<tera::Error> for FromFileError {
@@ -110,7 +141,7 @@ impl fmt::Display for FromFileError {
             Self::InvalidExtension(None) => {
                 return f.write_str(
                     "the file has no extension, must have one of: \
-                    json, toml, yml, yaml",
+                    json, json5, toml, yml, yaml",
                 );
             }
             Self::InvalidExtension(Some(ext)) => {
@@ -118,9 +149,29 @@ impl fmt::Display for FromFileError {
                     f,
                     "the file has an invalid extension `{ext}`, \
                     must have one of: \
-                    json, toml, yml, yaml",
+                    json, json5, toml, yml, yaml",
                 );
             }
+            Self::Include { path, error } => {
+                return write!(
+                    f,
+                    "failed to load the included file `{}`: {error}",
+                    path.display()
+                );
+            }
+            Self::IncludeCycle(path) => {
+                return write!(
+                    f,
+                    "the included file `{}` is part of an `include` cycle",
+                    path.display()
+                );
+            }
+            Self::Profile(error) => {
+                return write!(f, "failed to apply the profile: {error}");
+            }
+            Self::Variable(error) => {
+                return write!(f, "failed to expand variables: {error}");
+            }
             Self::TeraRender(error) => {
                 f.write_str("failed to render Tera")?;
                 error.as_ref()
@@ -137,6 +188,17 @@ impl fmt::Display for FromFileError {
                 f.write_str("failed to parse Yaml")?;
                 error
             }
+            Self::ParseJson5(error) => {
+                f.write_str("failed to parse Json5")?;
+                if let json5::Error::Message {
+                    location: Some(location),
+                    ..
+                } = error
+                {
+                    write!(f, " at line {} column {}", location.line, location.column)?;
+                }
+                error
+            }
             Self::Read(error) => {
                 f.write_str("failed to read the file")?;
                 error
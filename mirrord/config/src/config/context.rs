@@ -5,6 +5,7 @@ use std::{
     env::VarError,
     ffi::{OsStr, OsString},
     ops::Not,
+    path::{Path, PathBuf},
 };
 
 /// Context for generating and verifying a [`MirrordConfig`](super::MirrordConfig).
@@ -30,6 +31,9 @@ pub struct ConfigContext {
 
     /// Warnings collected during config verification.
     warnings: Vec<String>,
+
+    /// Config files loaded when resolving the config.
+    loaded_files: Vec<PathBuf>,
 }
 
 impl ConfigContext {
@@ -146,4 +150,15 @@ impl ConfigContext {
     pub fn has_warnings(&self) -> bool {
         self.warnings.is_empty().not()
     }
+
+    /// Stores a path to a config file loaded when resolving the config.
+    pub fn add_loaded_file(&mut self, path: &Path) {
+        self.loaded_files.push(path.to_owned());
+    }
+
+    /// Returns paths of all config files previously stored with
+    /// [`ConfigContext::add_loaded_file`].
+    pub fn loaded_files(&self) -> &[PathBuf] {
+        &self.loaded_files
+    }
 }
@@ -0,0 +1,89 @@
+use std::{fmt, str::FromStr};
+
+use mirrord_analytics::CollectAnalytics;
+use mirrord_config_derive::MirrordConfig;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::source::MirrordConfigSource;
+
+/// Configuration for the connection between mirrord and the mirrord-agent.
+///
+/// Ignored when using the mirrord operator, which manages the connections with its agents.
+///
+/// ```json
+/// {
+///   "connection": {
+///     "transport": "quic"
+///   }
+/// }
+/// ```
+#[derive(MirrordConfig, Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+#[config(map_to = "ConnectionFileConfig", derive = "JsonSchema")]
+#[cfg_attr(test, config(derive = "PartialEq, Eq"))]
+pub struct ConnectionConfig {
+    /// ### connection.transport {#connection-transport}
+    ///
+    /// Transport used for the connections with the mirrord-agent.
+    ///
+    /// - `"tcp"` (default): connect through Kubernetes port forwarding.
+    /// - `"quic"`: connect directly to the agent pod's IP over QUIC. Requires the agent pod's IP
+    ///   to be reachable over UDP from your machine (e.g. through a VPN). QUIC connections recover
+    ///   from packet loss better than port forwarding, and survive changes of your machine's
+    ///   address (e.g. a VPN reconnect).
+    ///
+    /// Defaults to `"tcp"`.
+    #[config(env = "MIRRORD_AGENT_TRANSPORT", default)]
+    pub transport: AgentTransport,
+}
+
+/// Transport used for the connections with the mirrord-agent, see
+/// [`ConnectionConfig::transport`].
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentTransport {
+    /// Kubernetes port forwarding.
+    #[default]
+    Tcp,
+
+    /// Direct QUIC connection.
+    Quic,
+}
+
+impl AgentTransport {
+    pub fn is_quic(&self) -> bool {
+        matches!(self, Self::Quic)
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("could not parse AgentTransport from string, values must be tcp/quic")]
+pub struct AgentTransportParseError;
+
+impl FromStr for AgentTransport {
+    type Err = AgentTransportParseError;
+
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        match val {
+            "tcp" => Ok(Self::Tcp),
+            "quic" => Ok(Self::Quic),
+            _ => Err(AgentTransportParseError),
+        }
+    }
+}
+
+impl fmt::Display for AgentTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp => write!(f, "tcp"),
+            Self::Quic => write!(f, "quic"),
+        }
+    }
+}
+
+impl CollectAnalytics for &ConnectionConfig {
+    fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
+        analytics.add("quic", self.transport.is_quic());
+    }
+}
@@ -0,0 +1,71 @@
+//! Discovery of the project config in a `.mirrord` directory, used when no config file is given
+//! explicitly, see [`LayerConfig::resolve`](crate::LayerConfig::resolve).
+
+use std::path::{Path, PathBuf};
+
+/// Name of the project directory that holds the config files.
+pub const PROJECT_CONFIG_DIR: &str = ".mirrord";
+
+/// Names of the config files loaded from [`PROJECT_CONFIG_DIR`].
+///
+/// All files that exist are merged in this order, every file overriding the ones before it.
+/// `mirrord.json`, created by the IDE plugins, comes first.
+pub const PROJECT_CONFIG_FILES: [&str; 6] = [
+    "mirrord.json",
+    "config.toml",
+    "config.yaml",
+    "config.yml",
+    "config.json",
+    "config.json5",
+];
+
+/// Walks up from `start` to the first [`PROJECT_CONFIG_DIR`] that contains any of the
+/// [`PROJECT_CONFIG_FILES`], and returns paths to these files.
+///
+/// Directories without config files are skipped, e.g. `~/.mirrord`, which holds the
+/// credentials.
+pub fn discover_project_config(start: &Path) -> Vec<PathBuf> {
+    start
+        .ancestors()
+        .map(|directory| directory.join(PROJECT_CONFIG_DIR))
+        .filter(|directory| directory.is_dir())
+        .map(|directory| {
+            PROJECT_CONFIG_FILES
+                .iter()
+                .map(|file| directory.join(file))
+                .filter(|file| file.is_file())
+                .collect::<Vec<_>>()
+        })
+        .find(|files| !files.is_empty())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{PROJECT_CONFIG_DIR, discover_project_config};
+
+    #[test]
+    fn nearest_directory_with_config() {
+        let root = tempfile::tempdir().unwrap();
+        let project = root.path().join("project");
+        let nested = project.join("src").join("service");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(nested.join(PROJECT_CONFIG_DIR)).unwrap();
+        fs::create_dir_all(project.join(PROJECT_CONFIG_DIR)).unwrap();
+        fs::write(project.join(PROJECT_CONFIG_DIR).join("config.json"), "{}").unwrap();
+        fs::write(project.join(PROJECT_CONFIG_DIR).join("config.toml"), "").unwrap();
+        fs::write(project.join(PROJECT_CONFIG_DIR).join("mirrord.json"), "{}").unwrap();
+
+        assert_eq!(
+            discover_project_config(&nested),
+            vec![
+                project.join(PROJECT_CONFIG_DIR).join("mirrord.json"),
+                project.join(PROJECT_CONFIG_DIR).join("config.toml"),
+                project.join(PROJECT_CONFIG_DIR).join("config.json"),
+            ]
+        );
+        assert!(discover_project_config(root.path()).is_empty());
+    }
+}
@@ -11,6 +11,7 @@ use crate::{
 };
 
 pub mod mapper;
+pub mod rename;
 
 pub const MIRRORD_OVERRIDE_ENV_VARS_INCLUDE_ENV: &str = "MIRRORD_OVERRIDE_ENV_VARS_INCLUDE";
 pub const MIRRORD_OVERRIDE_ENV_VARS_EXCLUDE_ENV: &str = "MIRRORD_OVERRIDE_ENV_VARS_EXCLUDE";
@@ -141,6 +142,52 @@ pub struct EnvConfig {
     ///
     /// * `DATA_1234: common-value` => `DATA_1234: magic-value`
     pub mapping: Option<HashMap<String, String>>,
+
+    /// #### feature.env.rename {#feature-env-rename}
+    ///
+    /// Exposes remote environment variables under a different name in the local process, so
+    /// that they can coexist with local variables that must not be overridden.
+    ///
+    /// Keys are variable names, and may contain a single `*` wildcard. A `*` in the new name is
+    /// replaced with the part of the name that was matched by the wildcard. Only variables
+    /// fetched from the remote target are renamed, and renaming happens before
+    /// [`mapping`](#feature-env-mapping) and [`override`](#feature-env-override) are applied.
+    ///
+    /// Use it together with [`include`](#feature-env-include) to select which variables to fetch.
+    ///
+    /// Example:
+    /// ```json
+    /// {
+    ///   "include": "DB_*;PORT",
+    ///   "rename": {
+    ///     "DB_*": "REMOTE_DB_*",
+    ///     "PORT": "REMOTE_PORT"
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// Will fetch `DB_HOST` and `PORT` from the target, and expose them locally as
+    /// `REMOTE_DB_HOST` and `REMOTE_PORT`.
+    ///
+    /// If more than one key matches a variable, an exact name wins, and otherwise the key with
+    /// the longest non-wildcard part is used.
+    pub rename: Option<HashMap<String, String>>,
+
+    /// #### feature.env.jvm_limits {#feature-env-jvm_limits}
+    ///
+    /// Reads the target container's CPU and memory limits from its cgroups, and passes them to
+    /// the local JVM in `JAVA_TOOL_OPTIONS` (`-XX:ActiveProcessorCount` and `-XX:MaxRAM`), so
+    /// that it sizes its heap and thread pools like it would in the pod.
+    ///
+    /// Options already present in the remote `JAVA_TOOL_OPTIONS` take precedence. The limits are
+    /// also exported as `MIRRORD_CONTAINER_CPU_LIMIT` (in CPUs) and
+    /// `MIRRORD_CONTAINER_MEMORY_LIMIT` (in bytes).
+    ///
+    /// Ignored in targetless runs.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_ENV_JVM_LIMITS", default = false)]
+    pub jvm_limits: bool,
 }
 
 impl MirrordToggleableConfig for EnvFileConfig {
@@ -160,6 +207,8 @@ impl MirrordToggleableConfig for EnvFileConfig {
                 .source_value(context)
                 .transpose()?,
             mapping: None,
+            rename: None,
+            jvm_limits: false,
         })
     }
 }
@@ -202,6 +251,14 @@ impl CollectAnalytics for &EnvConfig {
                 .map(|v| v.len() as u32)
                 .unwrap_or_default(),
         );
+        analytics.add(
+            "env_rename_count",
+            self.rename
+                .as_ref()
+                .map(|v| v.len() as u32)
+                .unwrap_or_default(),
+        );
+        analytics.add("jvm_limits", self.jvm_limits);
     }
 }
 
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use tracing::Level;
+
+use crate::config::ConfigError;
+
+/// A single `feature.env.rename` entry, split around the `*` wildcard.
+#[derive(Debug)]
+struct RenameRule {
+    /// Part of the pattern before the `*` (or the whole pattern, if it has no wildcard).
+    prefix: String,
+    /// Part of the pattern after the `*`, [`None`] if the pattern has no wildcard.
+    suffix: Option<String>,
+    /// The new name, may contain a single `*` that is replaced with the matched part.
+    rename_to: String,
+}
+
+impl RenameRule {
+    fn new(pattern: String, rename_to: String) -> Result<Self, ConfigError> {
+        let invalid = |reason: &str| ConfigError::InvalidEnvRename {
+            pattern: pattern.clone(),
+            value: rename_to.clone(),
+            reason: reason.to_string(),
+        };
+
+        if pattern.is_empty() || rename_to.is_empty() {
+            return Err(invalid("names must not be empty"));
+        }
+
+        let wildcards = pattern.matches('*').count();
+        if wildcards > 1 || rename_to.matches('*').count() > 1 {
+            return Err(invalid("only a single `*` is allowed"));
+        }
+
+        if wildcards == 0 && rename_to.contains('*') {
+            return Err(invalid("`*` in the new name requires a `*` in the pattern"));
+        }
+
+        let (prefix, suffix) = match pattern.split_once('*') {
+            Some((prefix, suffix)) => (prefix.to_string(), Some(suffix.to_string())),
+            None => (pattern.clone(), None),
+        };
+
+        Ok(Self {
+            prefix,
+            suffix,
+            rename_to,
+        })
+    }
+
+    /// Returns the new name for `name`, or [`None`] if it doesn't match this rule.
+    fn apply(&self, name: &str) -> Option<String> {
+        match &self.suffix {
+            None => (name == self.prefix).then(|| self.rename_to.clone()),
+            Some(suffix) => {
+                let matched = name
+                    .strip_prefix(self.prefix.as_str())?
+                    .strip_suffix(suffix.as_str())?;
+                Some(self.rename_to.replacen('*', matched, 1))
+            }
+        }
+    }
+
+    /// Length of the literal part of the pattern, used to pick the most specific rule.
+    fn specificity(&self) -> (bool, usize) {
+        (
+            self.suffix.is_none(),
+            self.prefix.len() + self.suffix.as_ref().map(String::len).unwrap_or_default(),
+        )
+    }
+}
+
+/// Renames env vars found in `env_vars` that match the patterns specified in `rename`.
+///
+/// In other words: if we have an env var `DB_HOST=db.svc` in `env_vars`, and there's
+/// a pattern pair in `rename` `("DB_*", "REMOTE_DB_*")`, then the var is exposed as
+/// `REMOTE_DB_HOST=db.svc` instead.
+///
+/// When more than one pattern matches a name, an exact name wins over a wildcard pattern, and
+/// otherwise the pattern with the longest literal part is used.
+#[derive(Debug)]
+pub struct EnvVarsRenamer {
+    rules: Vec<RenameRule>,
+    env_vars: HashMap<String, String>,
+}
+
+impl EnvVarsRenamer {
+    /// Validates the patterns in `rename`.
+    ///
+    /// - `env_vars`: We take ownership of the env vars that were loaded somewhere else
+    /// (for example, from `fetch_env_vars`).
+    #[tracing::instrument(level = Level::TRACE, ret, err)]
+    pub fn new(
+        rename: HashMap<String, String>,
+        env_vars: HashMap<String, String>,
+    ) -> Result<Self, ConfigError> {
+        let mut rules: Vec<RenameRule> = rename
+            .into_iter()
+            .map(|(pattern, rename_to)| RenameRule::new(pattern, rename_to))
+            .try_collect()?;
+        rules.sort_by_key(|rule| std::cmp::Reverse(rule.specificity()));
+
+        Ok(EnvVarsRenamer { rules, env_vars })
+    }
+
+    /// Does the actual renaming of env vars explained in [`EnvVarsRenamer`].
+    ///
+    /// - Returns the `HashMap` of all the env vars that were passed to [`Self::new`], including
+    /// the ones that did not match any pattern (under their original names).
+    #[tracing::instrument(level = Level::TRACE, ret)]
+    pub fn renamed(self) -> HashMap<String, String> {
+        let Self { rules, env_vars } = self;
+
+        env_vars
+            .into_iter()
+            .map(|(name, value)| {
+                let name = rules
+                    .iter()
+                    .find_map(|rule| rule.apply(&name))
+                    .unwrap_or(name);
+                (name, value)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn env_vars() -> HashMap<String, String> {
+        [
+            ("DB_HOST".to_string(), "db.svc".to_string()),
+            ("DB_PORT".to_string(), "5432".to_string()),
+            ("DB_PASSWORD".to_string(), "hunter2".to_string()),
+            ("REDIS_URL".to_string(), "redis://redis.svc".to_string()),
+            ("LOG_LEVEL".to_string(), "info".to_string()),
+        ]
+        .into()
+    }
+
+    #[test]
+    fn prefix_mapping() {
+        let rename = [
+            ("DB_*".to_string(), "REMOTE_DB_*".to_string()),
+            ("DB_PASSWORD".to_string(), "REMOTE_SECRET".to_string()),
+            ("*_URL".to_string(), "*_REMOTE_URL".to_string()),
+        ]
+        .into();
+
+        let renamed = EnvVarsRenamer::new(rename, env_vars()).unwrap().renamed();
+
+        let expected: HashMap<String, String> = [
+            ("REMOTE_DB_HOST".to_string(), "db.svc".to_string()),
+            ("REMOTE_DB_PORT".to_string(), "5432".to_string()),
+            ("REMOTE_SECRET".to_string(), "hunter2".to_string()),
+            (
+                "REDIS_REMOTE_URL".to_string(),
+                "redis://redis.svc".to_string(),
+            ),
+            ("LOG_LEVEL".to_string(), "info".to_string()),
+        ]
+        .into();
+
+        assert_eq!(renamed, expected);
+    }
+
+    #[rstest]
+    #[case("DB_*_*", "REMOTE_*")]
+    #[case("DB_*", "REMOTE_*_*")]
+    #[case("DB_HOST", "REMOTE_*")]
+    #[case("", "REMOTE")]
+    #[case("DB_HOST", "")]
+    fn invalid_patterns(#[case] pattern: &str, #[case] rename_to: &str) {
+        let rename = [(pattern.to_string(), rename_to.to_string())].into();
+
+        assert!(EnvVarsRenamer::new(rename, HashMap::new()).is_err());
+    }
+}
@@ -91,7 +91,22 @@ impl MirrordConfig for FsUserConfig {
                     .transpose()?,
                 not_found: None,
                 mapping: None,
+                overrides: None,
+                path_translation: None,
+                materialize: None,
+                materialize_dir: None,
+                remote_cwd: None,
                 readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+                lock_timeout: LOCK_TIMEOUT_DEFAULT,
+                procfs: FromEnv::new("MIRRORD_FILE_PROCFS")
+                    .source_value(context)
+                    .transpose()?
+                    .unwrap_or_default(),
+                procfs_paths: None,
+                downgrade_o_direct: FromEnv::new("MIRRORD_FILE_DOWNGRADE_O_DIRECT")
+                    .source_value(context)
+                    .transpose()?
+                    .unwrap_or_default(),
             },
             FsUserConfig::Advanced(advanced) => advanced.generate_config(context)?,
         };
@@ -120,7 +135,16 @@ impl MirrordToggleableConfig for FsUserConfig {
             local,
             not_found: None,
             mapping: None,
+            overrides: None,
+            path_translation: None,
+            materialize: None,
+            materialize_dir: None,
+            remote_cwd: None,
             readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+            lock_timeout: LOCK_TIMEOUT_DEFAULT,
+            procfs: false,
+            procfs_paths: None,
+            downgrade_o_direct: false,
         })
     }
 }
@@ -137,6 +161,7 @@ mod tests {
         let expect = FsConfig {
             mode: FsModeConfig::Read,
             readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+            lock_timeout: LOCK_TIMEOUT_DEFAULT,
             ..Default::default()
         };
 
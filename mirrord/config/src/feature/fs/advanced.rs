@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::Not, path::PathBuf};
 
 use mirrord_analytics::{AnalyticValue, CollectAnalytics};
 use mirrord_config_derive::MirrordConfig;
@@ -14,10 +14,23 @@ use crate::{
 /// The default size in bytes used for buffering read-only remote files.
 /// See [`FsConfig::readonly_file_buffer`].
 pub const READONLY_FILE_BUFFER_DEFAULT: u64 = 128000;
+/// The default number of seconds we wait for a contended remote file lock.
+/// See [`FsConfig::lock_timeout`].
+pub const LOCK_TIMEOUT_DEFAULT: u64 = 30;
 /// Warn users if using a value of [`FsConfig::readonly_file_buffer`] larger than 1mb
 pub const READONLY_FILE_BUFFER_WARN_LIMIT: u64 = 1024 * 1024;
 /// Do not allow users to set a value of [`FsConfig::readonly_file_buffer`] larger than 15mb
 pub const READONLY_FILE_BUFFER_HARD_LIMIT: u64 = 15 * 1024 * 1024;
+/// Paths read from the target when [`FsConfig::procfs`] is enabled and
+/// [`FsConfig::procfs_paths`] is not set.
+pub const PROCFS_DEFAULT_PATHS: [&str; 6] = [
+    r"^/proc/(self|thread-self)/(cgroup|mountinfo)$",
+    r"^/proc/meminfo$",
+    r"^/proc/cpuinfo$",
+    r"^/proc/stat$",
+    r"^/sys/fs/cgroup(/|$)",
+    r"^/sys/devices/system/cpu(/|$)",
+];
 
 // TODO(alex): We could turn this derive macro (`MirrordConfig`) into an attribute version, which
 // would allow us to "capture" the `derive` statement, making it possible to implement the same for
@@ -43,6 +56,16 @@ pub const READONLY_FILE_BUFFER_HARD_LIMIT: u64 = 15 * 1024 * 1024;
 /// 4. `"not_found"` - List of patters that should never be read nor written. These files should be
 ///    treated as non-existent.
 /// 4. `"mapping"` - Map of patterns and their corresponding replacers. The replacement happens before any specific behavior as defined above or mode (uses [`Regex::replace`](https://docs.rs/regex/latest/regex/struct.Regex.html#method.replace))
+/// 5. `"overrides"` - Map of remote paths and local files that are accessed in their place. Checked
+///    right after `"mapping"`, before any other behavior.
+/// 6. `"path_translation"` - Map of local and remote path prefixes. Local paths are translated to
+///    remote ones before `"mapping"`, and remote paths are translated back to local ones when the
+///    operation is done locally.
+/// 7. `"materialize"` - List of patterns of remote files that are copied to a local shadow
+///    directory on first access, and accessed locally from then on. Checked right after
+///    `"overrides"`.
+/// 8. `"remote_cwd"` - List of patterns of relative paths that are resolved against the working
+///    directory of the target, instead of being accessed locally.
 ///
 /// The logic for choosing the behavior is as follows:
 ///
@@ -144,6 +167,103 @@ pub struct FsConfig {
     ///   `../dev`.
     pub mapping: Option<HashMap<String, String>>,
 
+    /// #### feature.fs.overrides {#feature-fs-overrides}
+    ///
+    /// Specify a map of remote file paths and local files that should be used in their place.
+    ///
+    /// Useful when you want the application to read your modified copy of a single file (e.g. a
+    /// config file), while everything else is still read from the remote.
+    ///
+    /// Example:
+    /// ```json
+    /// {
+    ///   "/app/config.yaml": "./config.dev.yaml"
+    /// }
+    /// ```
+    /// Will make the application open (and `stat`) the local `./config.dev.yaml` whenever it
+    /// accesses `/app/config.yaml`.
+    ///
+    /// - Keys are exact absolute paths, not patterns. They are checked after `mapping` is applied.
+    /// - Relative local paths are resolved against the application's working directory.
+    pub overrides: Option<HashMap<String, String>>,
+
+    /// #### feature.fs.path_translation {#feature-fs-path_translation}
+    ///
+    /// Specify a map of local and remote path prefixes, for when the local and remote
+    /// filesystems keep the same data in different places.
+    ///
+    /// Example:
+    /// ```json
+    /// {
+    ///   "./data": "/app/data"
+    /// }
+    /// ```
+    /// With this rule, opening `./data/users.db` opens `/app/data/users.db` in the target, and
+    /// an operation on `/app/data/users.db` that is done locally (e.g. because of `local` or
+    /// `read_only`) uses `./data/users.db`.
+    ///
+    /// - Prefixes match on whole path components, and the longest matching prefix wins.
+    /// - Relative local prefixes are resolved against the application's working directory when
+    ///   mirrord starts. Relative paths used by the application are resolved against its current
+    ///   working directory.
+    /// - Translation happens before `mapping`.
+    pub path_translation: Option<HashMap<String, String>>,
+
+    /// #### feature.fs.materialize {#feature-fs-materialize}
+    ///
+    /// Specify file path patterns of remote files that are copied to a local shadow directory
+    /// (`materialize_dir`) the first time they are accessed, and are then accessed locally.
+    ///
+    /// Useful for big trees that the application reads a lot but never changes in the cluster,
+    /// e.g. `/usr/share` or model files, which are then read at local speed after a one-time
+    /// download.
+    ///
+    /// - Writes go to the shadow copy only, they never reach the target.
+    /// - Only regular files are copied. Directories are still listed according to the other
+    ///   settings, so files created in the shadow directory don't show up in remote listings.
+    /// - The shadow copies are kept between runs. Delete them to download fresh versions.
+    /// - Checked right after `overrides`, before `read_write`, `read_only`, `local` and
+    ///   `not_found`.
+    ///
+    /// Example:
+    /// ```json
+    /// {
+    ///   "materialize": ["^/usr/share/", "^/models/.+\\.bin$"]
+    /// }
+    /// ```
+    pub materialize: Option<VecOrSingle<String>>,
+
+    /// #### feature.fs.materialize_dir {#feature-fs-materialize_dir}
+    ///
+    /// Local shadow directory for the files copied because of `materialize`. A remote file
+    /// `/usr/share/dict/words` is copied to `<materialize_dir>/usr/share/dict/words`.
+    ///
+    /// Use a different directory for each target whose files differ.
+    ///
+    /// Defaults to `mirrord-materialize` in the system's temporary directory.
+    pub materialize_dir: Option<String>,
+
+    /// #### feature.fs.remote_cwd {#feature-fs-remote_cwd}
+    ///
+    /// Specify patterns of relative paths that are resolved against the working directory of
+    /// the target (e.g. the container's `WORKDIR`), instead of being accessed locally.
+    ///
+    /// Relative paths are normally always accessed locally. With this option, an application that
+    /// opens `config/app.yaml` while the target runs in `/app` gets `/app/config/app.yaml` from
+    /// the target, and the path then goes through the other settings like any absolute path.
+    ///
+    /// - The working directory is fetched from the agent once, on the first matching path.
+    ///   Changing the local working directory (`chdir`) does not affect it.
+    /// - The patterns are matched against the relative path, after `path_translation`.
+    ///
+    /// Example:
+    /// ```json
+    /// {
+    ///   "remote_cwd": ["^config/", "^static/"]
+    /// }
+    /// ```
+    pub remote_cwd: Option<VecOrSingle<String>>,
+
     /// #### feature.fs.readonly_file_buffer {#feature-fs-readonly_file_buffer}
     ///
     /// Sets buffer size for read-only remote files in bytes. By default, the value is
@@ -152,8 +272,72 @@ pub struct FsConfig {
     /// Setting the value to 0 disables file buffering.
     /// Otherwise, read-only remote files will be read in chunks and buffered locally.
     /// This improves performance when the user application reads data in small portions.
+    ///
+    /// When a file is read sequentially, the next 16 buffers are prefetched in the background
+    /// (requires a recent mirrord-agent).
     #[config(default = READONLY_FILE_BUFFER_DEFAULT)]
     pub readonly_file_buffer: u64,
+
+    /// #### feature.fs.lock_timeout {#feature-fs-lock_timeout}
+    ///
+    /// Advisory locks (`flock`, and `fcntl` with `F_SETLK`/`F_SETLKW`) on remote files are taken
+    /// on the remote file, so they exclude processes in the cluster as well.
+    ///
+    /// The agent never blocks on a contended lock. When the application waits for the lock
+    /// (`F_SETLKW`, or `flock` without `LOCK_NB`), mirrord retries for up to this many seconds,
+    /// then fails the call with `EDEADLK`, so that a lock held in the cluster cannot hang the
+    /// application forever.
+    ///
+    /// Defaults to `30`.
+    #[config(default = LOCK_TIMEOUT_DEFAULT)]
+    pub lock_timeout: u64,
+
+    /// #### feature.fs.procfs {#feature-fs-procfs}
+    ///
+    /// Read a set of `/proc` and `/sys` paths from the target, so that libraries detecting
+    /// container limits (e.g. from `/proc/self/cgroup`, `/proc/meminfo` or `/sys/fs/cgroup`) see
+    /// the target's values instead of the local machine's.
+    ///
+    /// The paths are read-only, and take precedence over the
+    /// [paths read locally by default](https://github.com/metalbear-co/mirrord/tree/latest/mirrord/layer/src/file/filter/read_local_by_default.rs),
+    /// but not over `read_write`, `read_only`, `local` and `not_found`. `/proc/self` refers to the
+    /// target process.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_FILE_PROCFS", default = false)]
+    pub procfs: bool,
+
+    /// #### feature.fs.procfs_paths {#feature-fs-procfs_paths}
+    ///
+    /// Specify the path patterns read from the target when `procfs` is enabled.
+    ///
+    /// Defaults to:
+    ///
+    /// ```json
+    /// [
+    ///   "^/proc/(self|thread-self)/(cgroup|mountinfo)$",
+    ///   "^/proc/meminfo$",
+    ///   "^/proc/cpuinfo$",
+    ///   "^/proc/stat$",
+    ///   "^/sys/fs/cgroup(/|$)",
+    ///   "^/sys/devices/system/cpu(/|$)"
+    /// ]
+    /// ```
+    pub procfs_paths: Option<VecOrSingle<String>>,
+
+    /// #### feature.fs.downgrade_o_direct {#feature-fs-downgrade_o_direct}
+    ///
+    /// Remote files cannot be opened for direct I/O (`O_DIRECT`), so by default such opens fail
+    /// with `EINVAL`, the same way they do on local filesystems without direct I/O support.
+    /// Many databases handle this by falling back to buffered I/O.
+    ///
+    /// Set this to `true` to open these files with buffered I/O instead, with a warning in the
+    /// logs. Durability is still available with `fsync` and `fdatasync`, which are carried out
+    /// on the remote file.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_FILE_DOWNGRADE_O_DIRECT", default = false)]
+    pub downgrade_o_direct: bool,
 }
 
 impl MirrordToggleableConfig for AdvancedFsUserConfig {
@@ -176,7 +360,16 @@ impl MirrordToggleableConfig for AdvancedFsUserConfig {
             local,
             not_found: None,
             mapping: None,
+            overrides: None,
+            path_translation: None,
+            materialize: None,
+            materialize_dir: None,
+            remote_cwd: None,
             readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+            lock_timeout: LOCK_TIMEOUT_DEFAULT,
+            procfs: false,
+            procfs_paths: None,
+            downgrade_o_direct: false,
         })
     }
 }
@@ -194,6 +387,26 @@ impl FsConfig {
     pub fn is_active(&self) -> bool {
         !matches!(self.mode, FsModeConfig::Local)
     }
+
+    /// Returns the shadow directory for [`FsConfig::materialize`].
+    pub fn materialize_dir(&self) -> PathBuf {
+        self.materialize_dir
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::temp_dir().join("mirrord-materialize"))
+    }
+
+    /// Returns the path patterns read from the target because of [`FsConfig::procfs`].
+    pub fn procfs_patterns(&self) -> Vec<String> {
+        if self.procfs.not() {
+            return Vec::new();
+        }
+
+        match self.procfs_paths.as_deref() {
+            Some(paths) => paths.to_vec(),
+            None => PROCFS_DEFAULT_PATHS.map(String::from).to_vec(),
+        }
+    }
 }
 
 impl From<FsModeConfig> for AnalyticValue {
@@ -235,7 +448,38 @@ impl CollectAnalytics for &FsConfig {
                 .map(<[_]>::len)
                 .unwrap_or_default(),
         );
+        analytics.add(
+            "override_paths",
+            self.overrides
+                .as_ref()
+                .map(HashMap::len)
+                .unwrap_or_default(),
+        );
+        analytics.add(
+            "path_translation_rules",
+            self.path_translation
+                .as_ref()
+                .map(HashMap::len)
+                .unwrap_or_default(),
+        );
+        analytics.add(
+            "materialize_paths",
+            self.materialize
+                .as_deref()
+                .map(<[_]>::len)
+                .unwrap_or_default(),
+        );
+        analytics.add(
+            "remote_cwd_paths",
+            self.remote_cwd
+                .as_deref()
+                .map(<[_]>::len)
+                .unwrap_or_default(),
+        );
         analytics.add("readonly_file_buffer", self.readonly_file_buffer);
+        analytics.add("lock_timeout", self.lock_timeout);
+        analytics.add("procfs", self.procfs);
+        analytics.add("downgrade_o_direct", self.downgrade_o_direct);
     }
 }
 
@@ -251,6 +495,7 @@ mod tests {
         let expect = FsConfig {
             mode: FsModeConfig::Read,
             readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+            lock_timeout: LOCK_TIMEOUT_DEFAULT,
             ..Default::default()
         };
 
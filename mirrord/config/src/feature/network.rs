@@ -76,6 +76,19 @@ pub struct NetworkConfig {
     /// or connects to other services over IPv6.
     #[config(env = IPV6_ENV_VAR, default = false)]
     pub ipv6: bool,
+
+    /// #### feature.network.interfaces {#feature-network-interfaces}
+    ///
+    /// Present the network interfaces of the target to the application, instead of the local
+    /// ones.
+    ///
+    /// When enabled, `getifaddrs` returns the interfaces and addresses of the target pod (fetched
+    /// once from the agent). Useful for applications that bind to or advertise addresses
+    /// discovered from the interfaces, e.g. gossip protocols.
+    ///
+    /// Defaults to `false`.
+    #[config(default = false)]
+    pub interfaces: bool,
 }
 
 impl MirrordToggleableConfig for NetworkFileConfig {
@@ -90,6 +103,7 @@ impl MirrordToggleableConfig for NetworkFileConfig {
             dns: DnsFileConfig::disabled_config(context)?,
             outgoing: OutgoingFileConfig::disabled_config(context)?,
             ipv6,
+            interfaces: false,
         })
     }
 }
@@ -100,6 +114,7 @@ impl CollectAnalytics for &NetworkConfig {
         analytics.add("outgoing", &self.outgoing);
         analytics.add("dns", &self.dns);
         analytics.add("ipv6", self.ipv6);
+        analytics.add("interfaces", self.interfaces);
     }
 }
 
@@ -1,4 +1,4 @@
-use std::ops::Deref;
+use std::{collections::BTreeMap, net::IpAddr, ops::Deref};
 
 use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
@@ -79,6 +79,10 @@ pub enum DnsFilterConfig {
 ///
 /// Defaults to `true`.
 ///
+/// Names are resolved with the target's `/etc/resolv.conf`, so short Kubernetes service names
+/// like `payments` work just like in the target: names with fewer dots than `ndots` are first
+/// tried with each of the `search` domains (e.g. `payments.default.svc.cluster.local`).
+///
 /// Mind that:
 /// - DNS resolving can be done in multiple ways. Some frameworks use `getaddrinfo`/`gethostbyname`
 ///   functions, while others communicate directly with the DNS server at port `53` and perform a
@@ -99,10 +103,59 @@ pub struct DnsConfig {
     /// Unstable: the precise syntax of this config is subject to change.
     #[config(default, unstable)]
     pub filter: Option<DnsFilterConfig>,
+
+    /// ##### feature.network.dns.overrides {#feature-network-dns-overrides}
+    ///
+    /// Names that resolve to the given address, without asking the remote pod or the local
+    /// resolver.
+    ///
+    /// Keys are names, where `*` matches any sequence of characters (also across dots).
+    /// Matching is case insensitive. When many patterns match, the exact name wins, then the
+    /// longest pattern.
+    ///
+    /// Useful for making your app's calls to its own service stay local:
+    ///
+    /// ```json
+    /// {
+    ///   "overrides": {
+    ///     "my-svc": "127.0.0.1",
+    ///     "my-svc.*": "127.0.0.1"
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// Like the DNS filter, overrides work only with frameworks that use
+    /// `getaddrinfo`/`gethostbyname` functions, and are ignored when remote DNS is disabled.
+    #[config(default)]
+    pub overrides: Option<BTreeMap<String, IpAddr>>,
 }
 
 impl DnsConfig {
+    /// Returns the address from [`Self::overrides`] that the given name resolves to.
+    pub fn override_for(&self, name: &str) -> Option<IpAddr> {
+        let name = name.strip_suffix('.').unwrap_or(name);
+
+        self.overrides
+            .iter()
+            .flatten()
+            .filter(|(pattern, _)| pattern_matches(pattern, name))
+            .max_by_key(|(pattern, _)| (!pattern.contains('*'), pattern.len()))
+            .map(|(_, ip)| *ip)
+    }
+
     pub fn verify(&self, context: &mut ConfigContext) -> Result<(), ConfigError> {
+        if self
+            .overrides
+            .as_ref()
+            .is_some_and(|overrides| !overrides.is_empty())
+            && !self.enabled
+        {
+            context.add_warning(
+                "Remote DNS resolution is disabled, provided DNS overrides will be ignored"
+                    .to_string(),
+            );
+        }
+
         let filters = match &self.filter {
             Some(..) if !self.enabled => {
                 context.add_warning(
@@ -146,6 +199,32 @@ impl DnsConfig {
     }
 }
 
+/// Case insensitive match of the name against the pattern, where `*` matches any sequence of
+/// characters.
+fn pattern_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let name = name.to_ascii_lowercase();
+    let mut parts = pattern.split('*');
+
+    // Without `*` there is only one part, which must be the whole name.
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let Some(last) = parts.next_back() else {
+        return rest.is_empty();
+    };
+
+    for part in parts {
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
 impl MirrordToggleableConfig for DnsFileConfig {
     fn disabled_config(context: &mut ConfigContext) -> Result<Self::Generated, ConfigError> {
         Ok(DnsConfig {
@@ -168,5 +247,55 @@ impl CollectAnalytics for &DnsConfig {
                 DnsFilterConfig::Local(value) => analytics.add("dns_filter_local", value.len()),
             }
         }
+
+        if let Some(overrides) = self.overrides.as_ref() {
+            analytics.add("dns_overrides", overrides.len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("my-svc", "my-svc", true)]
+    #[case("my-svc", "MY-SVC", true)]
+    #[case("my-svc.*", "my-svc.default.svc.cluster.local", true)]
+    #[case("my-svc.*", "my-svc", false)]
+    #[case("*.svc.cluster.local", "my-svc.default.svc.cluster.local", true)]
+    #[case("my-*.default.*", "my-svc.default.svc", true)]
+    #[case("my-*.default.*", "my-svc.staging.svc", false)]
+    #[case("*-svc*svc", "my-svc", false)]
+    fn patterns(#[case] pattern: &str, #[case] name: &str, #[case] expected: bool) {
+        assert_eq!(pattern_matches(pattern, name), expected);
+    }
+
+    #[test]
+    fn most_specific_override_wins() {
+        let config = DnsConfig {
+            enabled: true,
+            filter: None,
+            overrides: Some(BTreeMap::from([
+                ("*".to_string(), "10.0.0.1".parse().unwrap()),
+                ("my-svc.*".to_string(), "10.0.0.2".parse().unwrap()),
+                ("my-svc.default".to_string(), "127.0.0.1".parse().unwrap()),
+            ])),
+        };
+
+        assert_eq!(
+            config.override_for("my-svc.default."),
+            Some("127.0.0.1".parse().unwrap())
+        );
+        assert_eq!(
+            config.override_for("my-svc.default.svc"),
+            Some("10.0.0.2".parse().unwrap())
+        );
+        assert_eq!(
+            config.override_for("other"),
+            Some("10.0.0.1".parse().unwrap())
+        );
     }
 }
@@ -1,11 +1,25 @@
-use std::{collections::HashSet, fmt, ops::Not, str::FromStr};
+use std::{
+    collections::HashSet,
+    fmt,
+    ops::{Not, RangeInclusive},
+    str::FromStr,
+};
 
+use backlog::BacklogConfig;
 use bimap::BiMap;
 use mirrord_analytics::{AnalyticValue, Analytics, CollectAnalytics};
+use postgres_filter::PostgresFilterConfig;
+use redis_filter::RedisFilterConfig;
+use replicas::ReplicasConfig;
+use request_limit::RequestLimitConfig;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize, de, ser, ser::SerializeSeq as _};
+use sni_filter::SniFilterConfig;
+use source_filter::SourceFilterConfig;
+use tee::TeeConfig;
 use thiserror::Error;
 use tls_delivery::LocalTlsDelivery;
+use wait_for_local_app::WaitForLocalAppConfig;
 
 use crate::{
     config::{
@@ -15,8 +29,17 @@ use crate::{
     util::{MirrordToggleableConfig, ToggleableConfig},
 };
 
+pub mod backlog;
 pub mod http_filter;
+pub mod postgres_filter;
+pub mod redis_filter;
+pub mod replicas;
+pub mod request_limit;
+pub mod sni_filter;
+pub mod source_filter;
+pub mod tee;
 pub mod tls_delivery;
+pub mod wait_for_local_app;
 
 use http_filter::*;
 
@@ -133,9 +156,21 @@ impl MirrordConfig for IncomingFileConfig {
                     .source_value(context)
                     .transpose()?
                     .unwrap_or_default(),
-                ports: advanced.ports.map(|ports| ports.into_iter().collect()),
+                ports: advanced.ports.map(resolve_ports).transpose()?,
+                follow_bind: advanced.follow_bind.unwrap_or_default(),
                 https_delivery: advanced.https_delivery,
                 tls_delivery: advanced.tls_delivery,
+                sni_filter: advanced.sni_filter,
+                postgres_filter: advanced.postgres_filter,
+                redis_filter: advanced.redis_filter,
+                source_filter: advanced.source_filter,
+                request_limit: advanced.request_limit,
+                replicas: advanced.replicas.unwrap_or_default(),
+                backlog: advanced.backlog,
+                metadata_headers: advanced.metadata_headers.unwrap_or_default(),
+                mirror_max_bytes_per_second: advanced.mirror_max_bytes_per_second,
+                wait_for_local_app: advanced.wait_for_local_app,
+                tee: advanced.tee,
             },
         };
 
@@ -306,8 +341,18 @@ pub struct IncomingAdvancedFileConfig {
     ///
     /// List of ports to mirror/steal traffic from. Other ports will remain local.
     ///
+    /// Besides single ports, accepts inclusive ranges of ports, e.g. `[80, "8000-8100"]`.
+    ///
+    /// Can also be given as `only_ports`.
+    ///
     /// Mutually exclusive with [`ignore_ports`](###ignore_ports).
-    pub ports: Option<Vec<u16>>,
+    #[serde(alias = "only_ports")]
+    pub ports: Option<Vec<PortSpec>>,
+
+    /// ### follow_bind
+    ///
+    /// Mirror/steal traffic on the port that the OS picks when the application binds port `0`.
+    pub follow_bind: Option<bool>,
 
     /// ### https_delivery
     ///
@@ -319,6 +364,145 @@ pub struct IncomingAdvancedFileConfig {
     /// (Operator Only): configures how mirrord delivers stolen TLS traffic
     /// to the local application.
     pub tls_delivery: Option<LocalTlsDelivery>,
+
+    /// ### sni_filter
+    ///
+    /// Steal whole TLS connections based on the server name (SNI) in their ClientHello.
+    ///
+    /// See [`sni_filter`](##sni_filter) for details.
+    pub sni_filter: Option<SniFilterConfig>,
+
+    /// ### postgres_filter
+    ///
+    /// Steal whole PostgreSQL connections based on the database or user name from their startup
+    /// message.
+    ///
+    /// See [`postgres_filter`](##postgres_filter) for details.
+    pub postgres_filter: Option<PostgresFilterConfig>,
+
+    /// ### redis_filter
+    ///
+    /// Steal Redis commands based on their name or key.
+    ///
+    /// See [`redis_filter`](##redis_filter) for details.
+    pub redis_filter: Option<RedisFilterConfig>,
+
+    /// ### source_filter
+    ///
+    /// Steal only the traffic made from the given source addresses.
+    ///
+    /// See [`source_filter`](##source_filter) for details.
+    pub source_filter: Option<SourceFilterConfig>,
+
+    /// ### request_limit
+    ///
+    /// Limits the number of stolen HTTP requests that the local application handles at the same
+    /// time.
+    ///
+    /// See [`request_limit`](##request_limit) for details.
+    pub request_limit: Option<RequestLimitConfig>,
+
+    /// ### replicas
+    ///
+    /// Distributes stolen traffic between the local application and its local replicas.
+    ///
+    /// See [`replicas`](##replicas) for details.
+    pub replicas: Option<Vec<ReplicasConfig>>,
+
+    /// ### backlog
+    ///
+    /// Limits the number of stolen connections that wait for the local application to accept
+    /// them.
+    ///
+    /// See [`backlog`](##backlog) for details.
+    pub backlog: Option<BacklogConfig>,
+
+    /// ### metadata_headers
+    ///
+    /// Adds `x-mirrord-connection-id`, `x-mirrord-request-id`, `x-mirrord-original-dst` and
+    /// `x-mirrord-correlation-id` headers to the HTTP requests delivered to the local
+    /// application.
+    pub metadata_headers: Option<bool>,
+
+    /// ### mirror_max_bytes_per_second
+    ///
+    /// Caps the mirrored traffic that the agent sends for each port.
+    ///
+    /// See [`mirror_max_bytes_per_second`](##mirror_max_bytes_per_second) for details.
+    pub mirror_max_bytes_per_second: Option<u64>,
+
+    /// ### wait_for_local_app
+    ///
+    /// Keeps retrying the delivery of stolen traffic while the local application refuses
+    /// connections.
+    ///
+    /// See [`wait_for_local_app`](##wait_for_local_app) for details.
+    pub wait_for_local_app: Option<WaitForLocalAppConfig>,
+
+    /// ### tee
+    ///
+    /// Appends the HTTP requests delivered to the local application to a capture file.
+    ///
+    /// See [`tee`](##tee) for details.
+    pub tee: Option<TeeConfig>,
+}
+
+/// An entry of [`IncomingAdvancedFileConfig::ports`].
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum PortSpec {
+    /// A single port, e.g. `8080`.
+    Port(u16),
+
+    /// An inclusive range of ports, e.g. `"8000-8100"`.
+    Range(String),
+}
+
+#[derive(Error, Debug)]
+#[error("could not parse a port range, expected `<start>-<end>` with `start <= end`")]
+pub struct PortRangeParseError;
+
+impl PortSpec {
+    /// Returns the ports described by this entry.
+    pub fn range(&self) -> Result<RangeInclusive<u16>, PortRangeParseError> {
+        match self {
+            Self::Port(port) => Ok(*port..=*port),
+            Self::Range(range) => {
+                let (start, end) = range.split_once('-').ok_or(PortRangeParseError)?;
+                let start: u16 = start.trim().parse().map_err(|_| PortRangeParseError)?;
+                let end: u16 = end.trim().parse().map_err(|_| PortRangeParseError)?;
+
+                if start > end {
+                    return Err(PortRangeParseError);
+                }
+
+                Ok(start..=end)
+            }
+        }
+    }
+}
+
+impl fmt::Display for PortSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Port(port) => write!(f, "{port}"),
+            Self::Range(range) => f.write_str(range),
+        }
+    }
+}
+
+/// Expands the port ranges from [`IncomingAdvancedFileConfig::ports`].
+fn resolve_ports(ports: Vec<PortSpec>) -> Result<HashSet<u16>> {
+    ports.iter().try_fold(HashSet::new(), |mut resolved, spec| {
+        let range = spec.range().map_err(|error| ConfigError::InvalidValue {
+            name: "feature.network.incoming.ports",
+            provided: spec.to_string(),
+            error: error.into(),
+        })?;
+        resolved.extend(range);
+
+        Ok(resolved)
+    })
 }
 
 fn serialize_bi_map<S>(map: &BiMap<u16, u16>, serializer: S) -> Result<S::Ok, S::Error>
@@ -482,10 +666,32 @@ pub struct IncomingConfig {
     /// and other ports will remain local. Otherwise, all ports are
     /// mirrored/stolen.
     ///
+    /// Ports can also be given as inclusive ranges, which is useful when the application picks
+    /// its port at runtime from a known range, e.g. `"ports": [80, "8000-8100"]`. Ranges are
+    /// expanded by mirrord, and only the ports that the application actually listens on are
+    /// mirrored/stolen.
+    ///
+    /// Can also be given as `only_ports`, e.g. `"only_ports": [80, 8080]`.
+    ///
     /// Mutually exclusive with
     /// [`feature.network.incoming.ignore_ports`](#feature-network-ignore_ports).
     pub ports: Option<HashSet<u16>>,
 
+    /// ##### feature.network.incoming.follow_bind {#feature-network-incoming-follow_bind}
+    ///
+    /// When the application binds a TCP socket to port `0` (letting the OS choose the port) and
+    /// then listens on it, mirror/steal traffic on the port that was actually chosen.
+    ///
+    /// Useful for applications that listen on an ephemeral port and advertise it, e.g. to a
+    /// service registry. The remote port is the same as the local one, unless changed with
+    /// [`feature.network.incoming.port_mapping`](#feature-network-incoming-port_mapping).
+    /// The port is still subject to
+    /// [`feature.network.incoming.ports`](#feature-network-incoming-ports) and
+    /// [`feature.network.incoming.ignore_ports`](#feature-network-incoming-ignore_ports).
+    ///
+    /// Defaults to `false`, which leaves such sockets local.
+    pub follow_bind: bool,
+
     /// ##### feature.network.incoming.https_delivery {#feature-network-incoming-https_delivery}
     ///
     /// DEPRECATED: use `tls_delivery` instead.
@@ -496,6 +702,88 @@ pub struct IncomingConfig {
     /// (Operator Only): configures how mirrord delivers stolen TLS traffic
     /// to the local application.
     pub tls_delivery: Option<LocalTlsDelivery>,
+
+    /// ##### feature.network.incoming.sni_filter {#feature-network-incoming-sni_filter}
+    pub sni_filter: Option<SniFilterConfig>,
+
+    /// ##### feature.network.incoming.postgres_filter {#feature-network-incoming-postgres_filter}
+    pub postgres_filter: Option<PostgresFilterConfig>,
+
+    /// ##### feature.network.incoming.redis_filter {#feature-network-incoming-redis_filter}
+    pub redis_filter: Option<RedisFilterConfig>,
+
+    /// ##### feature.network.incoming.source_filter {#feature-network-incoming-source_filter}
+    pub source_filter: Option<SourceFilterConfig>,
+
+    /// ##### feature.network.incoming.request_limit {#feature-network-incoming-request_limit}
+    pub request_limit: Option<RequestLimitConfig>,
+
+    /// ##### feature.network.incoming.replicas {#feature-network-incoming-replicas}
+    pub replicas: Vec<ReplicasConfig>,
+
+    /// ##### feature.network.incoming.backlog {#feature-network-incoming-backlog}
+    pub backlog: Option<BacklogConfig>,
+
+    /// ##### feature.network.incoming.metadata_headers {#feature-network-incoming-metadata_headers}
+    ///
+    /// Adds mirrord metadata headers to the HTTP requests delivered to the local application, so
+    /// that the application and its logs can be correlated with the agent-side records:
+    ///
+    /// - `x-mirrord-connection-id` - id of the remote connection;
+    /// - `x-mirrord-request-id` - id of the request within the connection;
+    /// - `x-mirrord-original-dst` - address the request was originally sent to, e.g.
+    ///   `10.0.0.12:8080` (omitted with older agents, which don't send it);
+    /// - `x-mirrord-correlation-id` - ULID generated by the agent for each stolen request, also
+    ///   present in the agent's logs and audit log (omitted for mirrored requests and with older
+    ///   agents).
+    ///
+    /// Applies to both mirrored and stolen requests. Defaults to `false`.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "steal",
+    ///         "metadata_headers": true
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub metadata_headers: bool,
+
+    /// ##### feature.network.incoming.mirror_max_bytes_per_second {#feature-network-incoming-mirror_max_bytes_per_second}
+    ///
+    /// Caps the mirrored traffic that the agent sends for each mirrored port, in bytes per
+    /// second, so that mirroring a busy port does not flood the local application.
+    ///
+    /// When a port goes over the limit, the agent cuts short the mirrored connection that went
+    /// over it, and does not mirror new connections to the port until the next second. The
+    /// dropped traffic is reported in the internal proxy logs, so that gaps in the mirrored data
+    /// can be explained.
+    ///
+    /// Only applies in `"mirror"` mode. Not set by default, which means no limit.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "mirror",
+    ///         "mirror_max_bytes_per_second": 1048576
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub mirror_max_bytes_per_second: Option<u64>,
+
+    /// ##### feature.network.incoming.wait_for_local_app {#feature-network-incoming-wait_for_local_app}
+    pub wait_for_local_app: Option<WaitForLocalAppConfig>,
+
+    /// ##### feature.network.incoming.tee {#feature-network-incoming-tee}
+    pub tee: Option<TeeConfig>,
 }
 
 impl IncomingConfig {
@@ -516,7 +804,21 @@ impl IncomingConfig {
             return false;
         }
 
-        if self.http_filter.is_filter_set() {
+        if self
+            .sni_filter
+            .as_ref()
+            .is_some_and(|filter| filter.filters_port(port))
+            || self
+                .postgres_filter
+                .as_ref()
+                .is_some_and(|filter| filter.filters_port(port))
+            || self
+                .redis_filter
+                .as_ref()
+                .is_some_and(|filter| filter.filters_port(port))
+        {
+            false
+        } else if self.http_filter.is_filter_set() {
             self.http_filter
                 .ports
                 .as_ref()
@@ -689,6 +991,57 @@ impl CollectAnalytics for &IncomingConfig {
         analytics.add("listen_ports_count", self.listen_ports.len());
         analytics.add("ignore_localhost", self.ignore_localhost);
         analytics.add("ignore_ports_count", self.ignore_ports.len());
+        analytics.add("follow_bind", self.follow_bind);
         analytics.add("http", &self.http_filter);
+        analytics.add("sni_filter", self.sni_filter.is_some());
+        analytics.add("postgres_filter", self.postgres_filter.is_some());
+        analytics.add("redis_filter", self.redis_filter.is_some());
+        analytics.add("source_filter", self.source_filter.is_some());
+        analytics.add(
+            "request_limit",
+            self.request_limit
+                .map(|limit| limit.max_in_flight)
+                .unwrap_or_default(),
+        );
+        analytics.add("replicas_count", self.replicas.len());
+        analytics.add("metadata_headers", self.metadata_headers);
+        analytics.add(
+            "mirror_max_bytes_per_second",
+            self.mirror_max_bytes_per_second.unwrap_or_default(),
+        );
+        analytics.add(
+            "backlog",
+            self.backlog
+                .map(|backlog| backlog.max_pending)
+                .unwrap_or_default(),
+        );
+        analytics.add(
+            "wait_for_local_app",
+            self.wait_for_local_app
+                .map(|wait| wait.timeout_secs)
+                .unwrap_or_default(),
+        );
+        analytics.add("tee", self.tee.is_some());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::PortSpec;
+
+    #[rstest]
+    #[case(PortSpec::Port(80), Some(80..=80))]
+    #[case(PortSpec::Range("8000-8100".into()), Some(8000..=8100))]
+    #[case(PortSpec::Range(" 8000 - 8000 ".into()), Some(8000..=8000))]
+    #[case(PortSpec::Range("8100-8000".into()), None)]
+    #[case(PortSpec::Range("8000".into()), None)]
+    #[case(PortSpec::Range("8000-70000".into()), None)]
+    fn port_spec_range(
+        #[case] spec: PortSpec,
+        #[case] expected: Option<std::ops::RangeInclusive<u16>>,
+    ) {
+        assert_eq!(spec.range().ok(), expected);
     }
 }
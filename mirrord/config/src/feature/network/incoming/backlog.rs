@@ -0,0 +1,53 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Limits the number of stolen connections that wait for the local application to accept them,
+/// like the backlog of a listening socket (only relevant when `incoming.mode` is `"steal"`).
+///
+/// Without the limit, when the local application is slow to accept, stolen connections pile up
+/// in mirrord and their remote clients wait indefinitely. For example, to keep at most 64
+/// connections waiting on each port, and let the original destination in the cluster handle the
+/// rest:
+///
+/// ```json
+/// {
+///   "max_pending": 64,
+///   "when_exceeded": "passthrough"
+/// }
+/// ```
+///
+/// Applies only to stolen TCP connections, stolen HTTP requests are limited with
+/// [`feature.network.incoming.request_limit`](#feature-network-incoming-request_limit).
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct BacklogConfig {
+    /// ##### feature.network.incoming.backlog.max_pending {#feature-network-incoming-backlog-max_pending}
+    ///
+    /// Maximum number of stolen connections on a single port that were not yet accepted by the
+    /// local application.
+    ///
+    /// Must be greater than 0.
+    pub max_pending: usize,
+
+    /// ##### feature.network.incoming.backlog.when_exceeded {#feature-network-incoming-backlog-when_exceeded}
+    ///
+    /// What to do with connections that exceed the limit.
+    ///
+    /// Defaults to `"reject"`.
+    #[serde(default)]
+    pub when_exceeded: BacklogAction,
+}
+
+/// What to do with stolen connections that exceed
+/// [`feature.network.incoming.backlog.max_pending`](#feature-network-incoming-backlog-max_pending).
+///
+/// - `"reject"`: The connections are reset, like when the backlog of a listening socket is full;
+/// - `"passthrough"`: The connections are not stolen, and reach their original destination in the
+///   cluster.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BacklogAction {
+    #[default]
+    Reject,
+    Passthrough,
+}
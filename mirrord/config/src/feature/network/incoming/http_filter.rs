@@ -1,10 +1,12 @@
 use std::{ops::Not, str::FromStr, sync::LazyLock};
 
+use fancy_regex::Expr;
 use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
 use mirrord_protocol::tcp::{
     Filter, HTTP_BODY_JSON_FILTER_VERSION, HTTP_COMPOSITE_FILTER_VERSION,
-    HTTP_METHOD_FILTER_VERSION, HttpBodyFilter, HttpFilter, HttpMethodFilter, JsonPathQuery,
+    HTTP_HEADER_PREFIX_FILTER_VERSION, HTTP_METHOD_FILTER_VERSION, HTTP_NOT_FILTER_VERSION,
+    HttpBodyFilter, HttpFilter, HttpMethodFilter, JsonPathQuery,
 };
 use schemars::JsonSchema;
 use semver::{Version, VersionReq};
@@ -87,6 +89,30 @@ use crate::{
 ///  ]
 /// }
 /// ```
+///
+/// For common filters, you can use a `preset` instead of writing a regex. For example, to steal
+/// only the requests whose `x-request-id` header (e.g. injected by the ingress) starts with
+/// `alice-`:
+/// ```json
+/// {
+///   "preset": {
+///     "name": "header-prefix",
+///     "header": "x-request-id",
+///     "prefix": "alice-"
+///   }
+/// }
+/// ```
+///
+/// If you want to steal everything **except** some requests, use `exclude`.
+/// For example, this filter steals all HTTP requests, apart from the ones that contain header
+/// `x-canary` with value `true`.
+/// ```json
+/// {
+///   "exclude": [
+///     { "header": "^x-canary: true$" }
+///   ]
+/// }
+/// ```
 #[derive(MirrordConfig, Default, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
 #[config(map_to = "HttpFilterFileConfig", derive = "JsonSchema")]
 #[cfg_attr(test, config(derive = "PartialEq, Eq"))]
@@ -127,6 +153,13 @@ pub struct HttpFilterConfig {
     /// Matches the request based on the contents of its body.
     pub body_filter: Option<BodyFilter>,
 
+    /// ##### feature.network.incoming.http_filter.preset {#feature-network-incoming-http_filter-preset}
+    ///
+    /// A common filter, given by its `name` and parameters, that does not require writing a
+    /// regex. See [`preset`](#feature-network-incoming-http_filter-preset-name) for the
+    /// available presets.
+    pub preset: Option<FilterPreset>,
+
     /// ##### feature.network.incoming.http_filter.all_of {#feature-network-incoming-http_filter-all_of}
     ///
     /// An array of HTTP filters.
@@ -175,16 +208,44 @@ pub struct HttpFilterConfig {
     /// absent, filtering will be done for all ports.
     #[config(env = "MIRRORD_HTTP_FILTER_PORTS")]
     pub ports: Option<VecOrSingle<u16>>,
+
+    /// ##### feature.network.incoming.http_filter.exclude {#feature-network-incoming-http_filter-exclude}
+    ///
+    /// An array of HTTP filters, in the same format as in `any_of`.
+    ///
+    /// Requests that match any of these filters are never stolen, even if they match the other
+    /// filters. Can be used alone, to steal every request apart from the excluded ones, or
+    /// together with any other filter.
+    ///
+    /// Cannot be an empty list.
+    ///
+    /// Example:
+    /// ```json
+    /// {
+    ///   "path_filter": "^/api/",
+    ///   "exclude": [
+    ///     { "header": "^x-canary: true$" },
+    ///     { "method": "options" }
+    ///   ]
+    /// }
+    /// ```
+    pub exclude: Option<Vec<InnerFilter>>,
 }
 
 impl HttpFilterConfig {
     pub fn is_filter_set(&self) -> bool {
+        self.has_include_filter() || self.exclude.is_some()
+    }
+
+    /// Whether any filter other than [`HttpFilterConfig::exclude`] is set.
+    fn has_include_filter(&self) -> bool {
         self.header_filter.is_some()
             || self.path_filter.is_some()
             || self.method_filter.is_some()
             || self.all_of.is_some()
             || self.any_of.is_some()
             || self.body_filter.is_some()
+            || self.preset.is_some()
     }
 
     pub fn ensure_usable_with(
@@ -192,7 +253,7 @@ impl HttpFilterConfig {
         agent_protocol_version: Option<Version>,
     ) -> Result<(), ConfigError> {
         #![allow(clippy::type_complexity)]
-        static REQUIREMENTS: [(fn(&HttpFilterConfig) -> bool, &LazyLock<VersionReq>, &str); 3] = [
+        static REQUIREMENTS: [(fn(&HttpFilterConfig) -> bool, &LazyLock<VersionReq>, &str); 5] = [
             (
                 HttpFilterConfig::is_composite,
                 &HTTP_COMPOSITE_FILTER_VERSION,
@@ -208,6 +269,16 @@ impl HttpFilterConfig {
                 &HTTP_BODY_JSON_FILTER_VERSION,
                 "JSON body filters",
             ),
+            (
+                HttpFilterConfig::has_exclude_filter,
+                &HTTP_NOT_FILTER_VERSION,
+                "'exclude' HTTP filters",
+            ),
+            (
+                HttpFilterConfig::has_header_prefix_preset,
+                &HTTP_HEADER_PREFIX_FILTER_VERSION,
+                "'header-prefix' HTTP filter presets",
+            ),
         ];
 
         for (validator, version, what) in REQUIREMENTS {
@@ -230,35 +301,94 @@ impl HttpFilterConfig {
     }
 
     fn is_composite(&self) -> bool {
-        self.all_of.is_some() || self.any_of.is_some()
+        self.all_of.is_some() || self.any_of.is_some() || self.exclude.is_some()
+    }
+
+    fn has_exclude_filter(&self) -> bool {
+        self.exclude.is_some()
     }
 
     fn has_method_filter(&self) -> bool {
         self.method_filter.is_some()
-            || self.all_of.as_ref().is_some_and(|composite| {
-                composite
-                    .iter()
-                    .any(|f| matches!(f, InnerFilter::Method { .. }))
-            })
-            || self.any_of.as_ref().is_some_and(|composite| {
-                composite
-                    .iter()
-                    .any(|f| matches!(f, InnerFilter::Method { .. }))
-            })
+            || [
+                self.all_of.as_ref(),
+                self.any_of.as_ref(),
+                self.exclude.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .any(|f| matches!(f, InnerFilter::Method { .. }))
     }
 
     fn has_json_body_filter(&self) -> bool {
         matches!(self.body_filter, Some(BodyFilter::Json { .. }))
-            || self.all_of.as_ref().is_some_and(|composite| {
-                composite
-                    .iter()
-                    .any(|f| matches!(f, InnerFilter::Body(BodyFilter::Json { .. })))
-            })
-            || self.any_of.as_ref().is_some_and(|composite| {
-                composite
-                    .iter()
-                    .any(|f| matches!(f, InnerFilter::Body(BodyFilter::Json { .. })))
-            })
+            || [
+                self.all_of.as_ref(),
+                self.any_of.as_ref(),
+                self.exclude.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .any(|f| matches!(f, InnerFilter::Body(BodyFilter::Json { .. })))
+    }
+
+    fn has_header_prefix_preset(&self) -> bool {
+        let is_header_prefix =
+            |preset: &FilterPreset| matches!(preset, FilterPreset::HeaderPrefix { .. });
+
+        self.preset.as_ref().is_some_and(is_header_prefix)
+            || [
+                self.all_of.as_ref(),
+                self.any_of.as_ref(),
+                self.exclude.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .any(|f| matches!(f, InnerFilter::Preset { preset } if is_header_prefix(preset)))
+    }
+
+    /// Verifies that none of the regexes can take exponential time to match, see
+    /// [`ensure_linear_matching`].
+    pub fn verify_regexes(&self) -> Result<(), ConfigError> {
+        let single = [
+            (
+                "feature.network.incoming.http_filter.header_filter",
+                self.header_filter.as_deref(),
+            ),
+            (
+                "feature.network.incoming.http_filter.path_filter",
+                self.path_filter.as_deref(),
+            ),
+            (
+                "feature.network.incoming.http_filter.body_filter.matches",
+                self.body_filter.as_ref().map(BodyFilter::regex),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, regex)| Some((name, regex?)));
+
+        let composite = [
+            ("feature.network.incoming.http_filter.all_of", &self.all_of),
+            ("feature.network.incoming.http_filter.any_of", &self.any_of),
+            (
+                "feature.network.incoming.http_filter.exclude",
+                &self.exclude,
+            ),
+        ]
+        .into_iter()
+        .flat_map(|(name, filters)| {
+            filters
+                .iter()
+                .flatten()
+                .filter_map(move |filter| Some((name, filter.regex()?)))
+        });
+
+        single
+            .chain(composite)
+            .try_for_each(|(name, regex)| verify_linear_matching(name, regex))
     }
 
     /// Returns the number of ports that get filtered.
@@ -279,15 +409,36 @@ impl HttpFilterConfig {
     /// Returns an error if a filter expression is invalid. Panics if no filter is set
     /// (call [`is_filter_set`](Self::is_filter_set) first).
     pub fn as_protocol_http_filter(&self) -> Result<HttpFilter, HttpFilterParseError> {
+        let Some(exclude) = self.exclude.as_deref() else {
+            return self.as_protocol_include_filter();
+        };
+
+        let exclude = HttpFilter::Not(Box::new(Self::make_composite_filter(false, exclude)?));
+
+        if self.has_include_filter() {
+            Ok(HttpFilter::Composite {
+                all: true,
+                filters: vec![self.as_protocol_include_filter()?, exclude],
+            })
+        } else {
+            Ok(exclude)
+        }
+    }
+
+    /// Converts all filters but [`HttpFilterConfig::exclude`] into the protocol-level
+    /// [`HttpFilter`].
+    fn as_protocol_include_filter(&self) -> Result<HttpFilter, HttpFilterParseError> {
         match self {
             HttpFilterConfig {
                 path_filter: Some(path),
                 header_filter: None,
                 method_filter: None,
                 body_filter: None,
+                preset: None,
                 all_of: None,
                 any_of: None,
                 ports: _,
+                exclude: _,
             } => Ok(HttpFilter::Path(Filter::new(path.into())?)),
 
             HttpFilterConfig {
@@ -295,9 +446,11 @@ impl HttpFilterConfig {
                 header_filter: Some(header),
                 method_filter: None,
                 body_filter: None,
+                preset: None,
                 all_of: None,
                 any_of: None,
                 ports: _,
+                exclude: _,
             } => Ok(HttpFilter::Header(Filter::new(header.into())?)),
 
             HttpFilterConfig {
@@ -305,9 +458,11 @@ impl HttpFilterConfig {
                 header_filter: None,
                 method_filter: Some(method),
                 body_filter: None,
+                preset: None,
                 all_of: None,
                 any_of: None,
                 ports: _,
+                exclude: _,
             } => Ok(HttpFilter::Method(HttpMethodFilter::from_str(method)?)),
 
             HttpFilterConfig {
@@ -315,9 +470,11 @@ impl HttpFilterConfig {
                 header_filter: None,
                 method_filter: None,
                 body_filter: Some(filter),
+                preset: None,
                 all_of: None,
                 any_of: None,
                 ports: _,
+                exclude: _,
             } => Ok(HttpFilter::Body(filter.as_protocol_http_body_filter()?)),
 
             HttpFilterConfig {
@@ -325,9 +482,11 @@ impl HttpFilterConfig {
                 header_filter: None,
                 method_filter: None,
                 body_filter: None,
+                preset: None,
                 all_of: Some(filters),
                 any_of: None,
                 ports: _,
+                exclude: _,
             } => Self::make_composite_filter(true, filters),
 
             HttpFilterConfig {
@@ -335,11 +494,25 @@ impl HttpFilterConfig {
                 header_filter: None,
                 method_filter: None,
                 body_filter: None,
+                preset: None,
                 all_of: None,
                 any_of: Some(filters),
                 ports: _,
+                exclude: _,
             } => Self::make_composite_filter(false, filters),
 
+            HttpFilterConfig {
+                path_filter: None,
+                header_filter: None,
+                method_filter: None,
+                body_filter: None,
+                preset: Some(preset),
+                all_of: None,
+                any_of: None,
+                ports: _,
+                exclude: _,
+            } => preset.as_protocol_http_filter(),
+
             _ => panic!("No HTTP filters specified, this should have been caught earlier"),
         }
     }
@@ -361,6 +534,7 @@ impl HttpFilterConfig {
                 InnerFilter::Body(body_filter) => Ok(HttpFilter::Body(
                     body_filter.as_protocol_http_body_filter()?,
                 )),
+                InnerFilter::Preset { preset } => preset.as_protocol_http_filter(),
             })
             .collect::<Result<Vec<_>, HttpFilterParseError>>()?;
 
@@ -404,6 +578,84 @@ pub enum InnerFilter {
     /// Matches the request based on the contents of its body. Currently only JSON body filtering is
     /// supported.
     Body(BodyFilter),
+
+    /// ##### feature.network.incoming.inner_filter.preset {#feature-network-incoming-inner-preset}
+    ///
+    /// A common filter that does not require writing a regex, same as
+    /// [`http_filter.preset`](#feature-network-incoming-http_filter-preset).
+    Preset {
+        preset: FilterPreset,
+    },
+}
+
+impl InnerFilter {
+    /// Returns the user-supplied regex of this filter, if it has one.
+    fn regex(&self) -> Option<&str> {
+        match self {
+            Self::Header { header } => Some(header),
+            Self::Path { path } => Some(path),
+            Self::Body(body_filter) => Some(body_filter.regex()),
+            Self::Method { .. } | Self::Preset { .. } => None,
+        }
+    }
+}
+
+/// ##### feature.network.incoming.http_filter.preset.name {#feature-network-incoming-http_filter-preset-name}
+///
+/// Common HTTP filters, compiled to matchers that do not require writing a regex. The
+/// parameters are matched literally.
+///
+/// - `"header-prefix"`: steals requests with the `header`, whose value starts with `prefix`
+///   (case-sensitive). For example, to steal the requests whose `x-request-id` (injected by the
+///   ingress) starts with `alice-`:
+///
+///   ```json
+///   { "name": "header-prefix", "header": "x-request-id", "prefix": "alice-" }
+///   ```
+///
+///   Matched in the agent without a regex, requires a recent mirrord-agent.
+///
+/// - `"header-equals"`: steals requests with the `header` set to `value` (case-insensitive).
+///
+///   ```json
+///   { "name": "header-equals", "header": "x-user", "value": "alice" }
+///   ```
+///
+/// - `"path-prefix"`: steals requests whose path starts with `prefix` (case-insensitive).
+///
+///   ```json
+///   { "name": "path-prefix", "prefix": "/api/v2/" }
+///   ```
+///
+/// Presets can also be used in `all_of`, `any_of` and `exclude`, e.g.
+/// `{ "preset": { "name": "path-prefix", "prefix": "/api/" } }`.
+#[derive(PartialEq, Eq, Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum FilterPreset {
+    HeaderPrefix { header: String, prefix: String },
+    HeaderEquals { header: String, value: String },
+    PathPrefix { prefix: String },
+}
+
+impl FilterPreset {
+    /// Converts this preset into the protocol-level [`HttpFilter`].
+    pub fn as_protocol_http_filter(&self) -> Result<HttpFilter, HttpFilterParseError> {
+        match self {
+            Self::HeaderPrefix { header, prefix } => Ok(HttpFilter::HeaderPrefix {
+                name: header.clone(),
+                prefix: prefix.clone(),
+            }),
+            Self::HeaderEquals { header, value } => Ok(HttpFilter::Header(Filter::new(format!(
+                "^{}: {}$",
+                fancy_regex::escape(header),
+                fancy_regex::escape(value)
+            ))?)),
+            Self::PathPrefix { prefix } => Ok(HttpFilter::Path(Filter::new(format!(
+                "^{}",
+                fancy_regex::escape(prefix)
+            ))?)),
+        }
+    }
 }
 
 /// Currently only JSON body filtering is supported.
@@ -507,6 +759,13 @@ pub enum BodyFilter {
 }
 
 impl BodyFilter {
+    /// Returns the regex of this filter.
+    fn regex(&self) -> &str {
+        match self {
+            BodyFilter::Json { matches, .. } => matches,
+        }
+    }
+
     /// Converts this config into the protocol-level [`HttpBodyFilter`].
     pub fn as_protocol_http_body_filter(&self) -> Result<HttpBodyFilter, Box<fancy_regex::Error>> {
         match self {
@@ -536,6 +795,8 @@ impl MirrordToggleableConfig for HttpFilterFileConfig {
         let any_of = None;
 
         let body_filter = None;
+        let preset = None;
+        let exclude = None;
 
         let ports = FromEnv::new("MIRRORD_HTTP_FILTER_PORTS")
             .source_value(context)
@@ -546,9 +807,11 @@ impl MirrordToggleableConfig for HttpFilterFileConfig {
             path_filter,
             method_filter,
             body_filter,
+            preset,
             all_of,
             any_of,
             ports,
+            exclude,
         })
     }
 }
@@ -557,6 +820,8 @@ impl CollectAnalytics for &HttpFilterConfig {
     fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
         analytics.add("header_filter", self.header_filter.is_some());
         analytics.add("path_filter", self.path_filter.is_some());
+        analytics.add("exclude_filter", self.exclude.is_some());
+        analytics.add("preset", self.preset.is_some());
         analytics.add("ports", self.count_filtered_ports());
     }
 }
@@ -570,3 +835,184 @@ pub enum HttpFilterParseError {
     #[error(transparent)]
     Method(#[from] strum::ParseError),
 }
+
+/// Error returned by [`ensure_linear_matching`].
+#[derive(Error, Debug)]
+pub enum UnsafeRegexError {
+    #[error(transparent)]
+    Regex(#[from] Box<fancy_regex::Error>),
+
+    #[error(
+        "the regex nests unbounded repetitions (e.g. `(a+)+`) and uses look-arounds or \
+        backreferences, which can take exponential time to match. Remove the nested repetition, \
+        or consider using a filter `preset`"
+    )]
+    Backtracking,
+}
+
+/// Rejects regexes that can take exponential time to match.
+///
+/// Regexes that use look-arounds, backreferences or atomic groups are matched by
+/// [`fancy_regex`] with backtracking. When such a regex also repeats a subexpression that
+/// contains an unbounded repetition (like `(a+)+` or `(.*,)*`), a crafted header or path can
+/// stall the agent. Other regexes are matched by the `regex` crate in linear time, and are always
+/// accepted.
+pub fn ensure_linear_matching(regex: &str) -> Result<(), UnsafeRegexError> {
+    /// Returns whether the expression has an unbounded repetition, and sets the flags.
+    fn visit(expr: &Expr, backtracks: &mut bool, nested: &mut bool) -> bool {
+        match expr {
+            Expr::Concat(children) | Expr::Alt(children) => {
+                children.iter().fold(false, |unbounded, child| {
+                    visit(child, backtracks, nested) || unbounded
+                })
+            }
+            Expr::Group(child) => visit(child, backtracks, nested),
+            Expr::LookAround(child, _) | Expr::AtomicGroup(child) => {
+                *backtracks = true;
+                visit(child, backtracks, nested)
+            }
+            Expr::Backref { .. } => {
+                *backtracks = true;
+                false
+            }
+            Expr::Repeat { child, hi, .. } => {
+                let inner_unbounded = visit(child, backtracks, nested);
+                let unbounded = *hi == usize::MAX;
+                *nested |= unbounded && inner_unbounded;
+                unbounded || inner_unbounded
+            }
+            _ => false,
+        }
+    }
+
+    let tree = Expr::parse_tree(regex).map_err(Box::new)?;
+    let (mut backtracks, mut nested) = (false, false);
+    visit(&tree.expr, &mut backtracks, &mut nested);
+
+    if backtracks && nested {
+        Err(UnsafeRegexError::Backtracking)
+    } else {
+        Ok(())
+    }
+}
+
+/// Runs [`ensure_linear_matching`] on the regex from the config field `name`.
+pub(crate) fn verify_linear_matching(name: &'static str, regex: &str) -> Result<(), ConfigError> {
+    ensure_linear_matching(regex).map_err(|error| ConfigError::InvalidValue {
+        name,
+        provided: regex.to_string(),
+        error: error.into(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::plain("^x-user: alice$")]
+    #[case::nested_without_lookaround(r"^host: (\w+\.)+example\.com$")]
+    #[case::lookaround_without_nesting("^User-Agent: (?!kube-probe)")]
+    #[case::backref_without_nesting(r"^x-pair: (\w+)-\1$")]
+    fn linear_regexes_are_accepted(#[case] regex: &str) {
+        ensure_linear_matching(regex).unwrap();
+    }
+
+    #[rstest]
+    #[case::nested_plus("^(?!health)(a+)+$")]
+    #[case::nested_star(r"^x-list: (?=.)(.*,)*end$")]
+    #[case::inside_lookaround("(?=(a*)*b)")]
+    fn backtracking_regexes_are_rejected(#[case] regex: &str) {
+        assert!(matches!(
+            ensure_linear_matching(regex),
+            Err(UnsafeRegexError::Backtracking)
+        ));
+    }
+
+    #[test]
+    fn presets_match_literally() {
+        let preset = FilterPreset::PathPrefix {
+            prefix: "/api/v1.0/".into(),
+        };
+        let HttpFilter::Path(filter) = preset.as_protocol_http_filter().unwrap() else {
+            panic!("`path-prefix` should compile to a path filter");
+        };
+        assert_eq!(filter.to_string(), r"^/api/v1\.0/");
+
+        let preset = FilterPreset::HeaderPrefix {
+            header: "x-request-id".into(),
+            prefix: "alice-".into(),
+        };
+        assert_eq!(
+            preset.as_protocol_http_filter().unwrap(),
+            HttpFilter::HeaderPrefix {
+                name: "x-request-id".into(),
+                prefix: "alice-".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn header_prefix_preset_requires_new_agent() {
+        let config = HttpFilterConfig {
+            any_of: Some(vec![InnerFilter::Preset {
+                preset: FilterPreset::HeaderPrefix {
+                    header: "x-request-id".into(),
+                    prefix: "alice-".into(),
+                },
+            }]),
+            ..Default::default()
+        };
+
+        config
+            .ensure_usable_with(Some("1.45.0".parse().unwrap()))
+            .unwrap_err();
+        config
+            .ensure_usable_with(Some(mirrord_protocol::VERSION.clone()))
+            .unwrap();
+    }
+
+    /// Verifies that the filters in `exclude` are checked against the agent version, and that
+    /// they are negated on top of the other filters.
+    #[test]
+    fn exclude_filters() {
+        let config = HttpFilterConfig {
+            path_filter: Some("^/api".into()),
+            exclude: Some(vec![
+                InnerFilter::Method {
+                    method: "OPTIONS".into(),
+                },
+                InnerFilter::Body(BodyFilter::Json {
+                    query: "$.dry_run".into(),
+                    matches: "^true$".into(),
+                }),
+            ]),
+            ..Default::default()
+        };
+
+        assert!(config.has_method_filter());
+        assert!(config.has_json_body_filter());
+        config
+            .ensure_usable_with(Some("1.30.0".parse().unwrap()))
+            .unwrap_err();
+        config
+            .ensure_usable_with(Some(mirrord_protocol::VERSION.clone()))
+            .unwrap();
+
+        let HttpFilter::Composite { all: true, filters } =
+            config.as_protocol_http_filter().unwrap()
+        else {
+            panic!("the included and excluded filters should be combined");
+        };
+        let [HttpFilter::Path(_), HttpFilter::Not(excluded)] = filters.as_slice() else {
+            panic!("the excluded filters should be negated: {filters:?}");
+        };
+        assert!(matches!(
+            excluded.as_ref(),
+            HttpFilter::Composite { all: false, filters }
+                if matches!(filters.as_slice(), [HttpFilter::Method(_), HttpFilter::Body(_)])
+        ));
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashSet;
+
+use mirrord_protocol::tcp::{Filter, POSTGRES_STEAL_VERSION, PostgresFilter};
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use super::http_filter::verify_linear_matching;
+use crate::config::ConfigError;
+
+/// Steal whole PostgreSQL connections based on the database or user name from their startup
+/// message (only relevant when `incoming.mode` is `"steal"`).
+///
+/// Connections matching all of the given regexes are stolen as raw TCP, other connections are
+/// passed through to their original destination. For example, to steal only connections to the
+/// `mydb_test` database:
+///
+/// ```json
+/// {
+///   "database": "^mydb_test$"
+/// }
+/// ```
+///
+/// Connections that request TLS or GSSAPI encryption (e.g. `sslmode=require`) cannot be inspected
+/// and are passed through.
+///
+/// MySQL is not supported, as the MySQL server sends its handshake before the client sends the
+/// user and database names.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PostgresFilterConfig {
+    /// ##### feature.network.incoming.postgres_filter.database {#feature-network-incoming-postgres_filter-database}
+    ///
+    /// Regex for the database name. When the client does not specify a database, the user name is
+    /// used instead, like in the PostgreSQL server.
+    ///
+    /// Supports regexes validated by the
+    /// [`fancy-regex`](https://docs.rs/fancy-regex/latest/fancy_regex/) crate.
+    pub database: Option<String>,
+
+    /// ##### feature.network.incoming.postgres_filter.user {#feature-network-incoming-postgres_filter-user}
+    ///
+    /// Regex for the user name.
+    ///
+    /// Supports regexes validated by the
+    /// [`fancy-regex`](https://docs.rs/fancy-regex/latest/fancy_regex/) crate.
+    pub user: Option<String>,
+
+    /// ##### feature.network.incoming.postgres_filter.ports {#feature-network-incoming-postgres_filter-ports}
+    ///
+    /// Activate the PostgreSQL filter only for these ports.
+    ///
+    /// Defaults to `[5432]`.
+    #[serde(default = "PostgresFilterConfig::default_ports")]
+    pub ports: Vec<u16>,
+}
+
+impl PostgresFilterConfig {
+    fn default_ports() -> Vec<u16> {
+        vec![5432]
+    }
+
+    /// <!--${internal}-->
+    /// Returns whether the PostgreSQL filter is active on the given port.
+    pub fn filters_port(&self, port: u16) -> bool {
+        self.ports.contains(&port)
+    }
+
+    /// <!--${internal}-->
+    /// Compiles the regexes into a [`PostgresFilter`] that can be sent to the agent.
+    pub fn as_protocol_filter(&self) -> Result<PostgresFilter, Box<fancy_regex::Error>> {
+        Ok(PostgresFilter {
+            database: self.database.clone().map(Filter::new).transpose()?,
+            user: self.user.clone().map(Filter::new).transpose()?,
+        })
+    }
+
+    /// <!--${internal}-->
+    /// Verifies that the agent's protocol version supports stealing based on the PostgreSQL
+    /// startup message.
+    pub fn ensure_usable_with(
+        &self,
+        agent_protocol_version: Option<&Version>,
+    ) -> Result<(), ConfigError> {
+        if agent_protocol_version.is_some_and(|version| POSTGRES_STEAL_VERSION.matches(version)) {
+            Ok(())
+        } else {
+            Err(ConfigError::Conflict(format!(
+                "Cannot use `feature.network.incoming.postgres_filter`, protocol version used by \
+                mirrord-agent must match {}. Consider using a newer version of mirrord-agent",
+                *POSTGRES_STEAL_VERSION
+            )))
+        }
+    }
+
+    /// <!--${internal}-->
+    /// Verifies that none of the regexes can take exponential time to match, see
+    /// [`ensure_linear_matching`](super::http_filter::ensure_linear_matching).
+    pub fn verify_regexes(&self) -> Result<(), ConfigError> {
+        [
+            (
+                "feature.network.incoming.postgres_filter.database",
+                self.database.as_deref(),
+            ),
+            (
+                "feature.network.incoming.postgres_filter.user",
+                self.user.as_deref(),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, regex)| Some((name, regex?)))
+        .try_for_each(|(name, regex)| verify_linear_matching(name, regex))
+    }
+
+    /// <!--${internal}-->
+    /// Returns the set of ports the PostgreSQL filter is active on.
+    pub fn port_set(&self) -> HashSet<u16> {
+        self.ports.iter().copied().collect()
+    }
+}
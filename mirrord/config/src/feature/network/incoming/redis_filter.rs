@@ -0,0 +1,118 @@
+use std::collections::HashSet;
+
+use mirrord_protocol::tcp::{Filter, REDIS_STEAL_VERSION, RedisFilter};
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use super::http_filter::verify_linear_matching;
+use crate::config::ConfigError;
+
+/// Steal Redis commands based on their name or key (only relevant when `incoming.mode` is
+/// `"steal"`).
+///
+/// Commands matching all of the given regexes are sent to the local application, other commands
+/// are sent to the original Redis server. Replies are returned to the client in order. For
+/// example, to handle only the keys prefixed with `session:` locally:
+///
+/// ```json
+/// {
+///   "key": "^session:"
+/// }
+/// ```
+///
+/// Commands are matched by their first key, which is the first argument after the command name.
+/// Connection level commands (e.g. `AUTH`, `SELECT`, `PING`), keyless commands (e.g. `KEYS`,
+/// `SCAN`, `FLUSHDB`), scripts and commands sent inside of a `MULTI` transaction are always sent
+/// to the original Redis server. After a `SUBSCRIBE` or `MONITOR` command, the rest of the
+/// connection is passed through.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RedisFilterConfig {
+    /// ##### feature.network.incoming.redis_filter.command {#feature-network-incoming-redis_filter-command}
+    ///
+    /// Case-insensitive regex for the command name, e.g. `"^(GET|SET)$"`.
+    ///
+    /// Supports regexes validated by the
+    /// [`fancy-regex`](https://docs.rs/fancy-regex/latest/fancy_regex/) crate.
+    pub command: Option<String>,
+
+    /// ##### feature.network.incoming.redis_filter.key {#feature-network-incoming-redis_filter-key}
+    ///
+    /// Regex for the first key of the command.
+    ///
+    /// Supports regexes validated by the
+    /// [`fancy-regex`](https://docs.rs/fancy-regex/latest/fancy_regex/) crate.
+    pub key: Option<String>,
+
+    /// ##### feature.network.incoming.redis_filter.ports {#feature-network-incoming-redis_filter-ports}
+    ///
+    /// Activate the Redis filter only for these ports.
+    ///
+    /// Defaults to `[6379]`.
+    #[serde(default = "RedisFilterConfig::default_ports")]
+    pub ports: Vec<u16>,
+}
+
+impl RedisFilterConfig {
+    fn default_ports() -> Vec<u16> {
+        vec![6379]
+    }
+
+    /// <!--${internal}-->
+    /// Returns whether the Redis filter is active on the given port.
+    pub fn filters_port(&self, port: u16) -> bool {
+        self.ports.contains(&port)
+    }
+
+    /// <!--${internal}-->
+    /// Compiles the regexes into a [`RedisFilter`] that can be sent to the agent.
+    pub fn as_protocol_filter(&self) -> Result<RedisFilter, Box<fancy_regex::Error>> {
+        Ok(RedisFilter {
+            command: self.command.clone().map(Filter::new).transpose()?,
+            key: self.key.clone().map(Filter::new).transpose()?,
+        })
+    }
+
+    /// <!--${internal}-->
+    /// Verifies that the agent's protocol version supports stealing Redis commands.
+    pub fn ensure_usable_with(
+        &self,
+        agent_protocol_version: Option<&Version>,
+    ) -> Result<(), ConfigError> {
+        if agent_protocol_version.is_some_and(|version| REDIS_STEAL_VERSION.matches(version)) {
+            Ok(())
+        } else {
+            Err(ConfigError::Conflict(format!(
+                "Cannot use `feature.network.incoming.redis_filter`, protocol version used by \
+                mirrord-agent must match {}. Consider using a newer version of mirrord-agent",
+                *REDIS_STEAL_VERSION
+            )))
+        }
+    }
+
+    /// <!--${internal}-->
+    /// Verifies that none of the regexes can take exponential time to match, see
+    /// [`ensure_linear_matching`](super::http_filter::ensure_linear_matching).
+    pub fn verify_regexes(&self) -> Result<(), ConfigError> {
+        [
+            (
+                "feature.network.incoming.redis_filter.command",
+                self.command.as_deref(),
+            ),
+            (
+                "feature.network.incoming.redis_filter.key",
+                self.key.as_deref(),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, regex)| Some((name, regex?)))
+        .try_for_each(|(name, regex)| verify_linear_matching(name, regex))
+    }
+
+    /// <!--${internal}-->
+    /// Returns the set of ports the Redis filter is active on.
+    pub fn port_set(&self) -> HashSet<u16> {
+        self.ports.iter().copied().collect()
+    }
+}
@@ -0,0 +1,36 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Simulates horizontal scaling of the local application (only relevant when `incoming.mode` is
+/// `"steal"`).
+///
+/// Stolen connections and requests on the remote `port` are distributed in a round-robin fashion
+/// between the application running with mirrord and its local replicas, listening on
+/// `local_ports`. The replicas are other instances of the application (e.g. running with
+/// different environment variables), started by you. This is useful for testing load balancing
+/// logic. For example, to spread the traffic from remote port `80` between the application and two
+/// replicas listening on ports `8081` and `8082`:
+///
+/// ```json
+/// [
+///   {
+///     "port": 80,
+///     "local_ports": [8081, 8082]
+///   }
+/// ]
+/// ```
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ReplicasConfig {
+    /// ##### feature.network.incoming.replicas.port {#feature-network-incoming-replicas-port}
+    ///
+    /// Remote port from which the traffic is distributed.
+    pub port: u16,
+
+    /// ##### feature.network.incoming.replicas.local_ports {#feature-network-incoming-replicas-local_ports}
+    ///
+    /// Local ports on which the replicas listen.
+    ///
+    /// Must not be empty.
+    pub local_ports: Vec<u16>,
+}
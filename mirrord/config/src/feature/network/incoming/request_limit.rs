@@ -0,0 +1,50 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Limits the number of stolen HTTP requests that the local application handles at the same
+/// time (only relevant when `incoming.mode` is `"steal"`).
+///
+/// Useful when a lot of requests match the HTTP filter at once, and the local application cannot
+/// keep up with them. For example, to let the application handle at most 16 requests at a time,
+/// and queue the rest:
+///
+/// ```json
+/// {
+///   "max_in_flight": 16,
+///   "when_exceeded": "queue"
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RequestLimitConfig {
+    /// ##### feature.network.incoming.request_limit.max_in_flight {#feature-network-incoming-request_limit-max_in_flight}
+    ///
+    /// Maximum number of stolen HTTP requests sent to the local application at the same time.
+    ///
+    /// Must be greater than 0.
+    pub max_in_flight: usize,
+
+    /// ##### feature.network.incoming.request_limit.when_exceeded {#feature-network-incoming-request_limit-when_exceeded}
+    ///
+    /// What to do with requests that exceed the limit.
+    ///
+    /// Defaults to `"queue"`.
+    #[serde(default)]
+    pub when_exceeded: RequestLimitAction,
+}
+
+/// What to do with stolen HTTP requests that exceed
+/// [`feature.network.incoming.request_limit.max_in_flight`](#
+/// feature-network-incoming-request_limit-max_in_flight).
+///
+/// - `"queue"`: The requests wait until the local application finishes handling some of the
+///   previous requests;
+/// - `"reject"`: The requests are not sent to the local application, and the remote clients receive
+///   a `503 Service Unavailable` response.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RequestLimitAction {
+    #[default]
+    Queue,
+    Reject,
+}
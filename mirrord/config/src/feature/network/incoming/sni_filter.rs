@@ -0,0 +1,95 @@
+use std::collections::HashSet;
+
+use mirrord_protocol::tcp::{Filter, SNI_STEAL_VERSION};
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use super::http_filter::verify_linear_matching;
+use crate::config::ConfigError;
+
+/// Steal whole TLS connections based on the server name (SNI) in their ClientHello, without
+/// decrypting them (only relevant when `incoming.mode` is `"steal"`).
+///
+/// Connections with a server name matching the regex are stolen as raw TCP, other connections
+/// are passed through to their original destination.
+///
+/// ```json
+/// {
+///   "server_name": "^api\\.example\\.com$",
+///   "ports": [443, 8443]
+/// }
+/// ```
+///
+/// On these ports, the SNI filter replaces the
+/// [`http_filter`](#feature-network-incoming-http-filter).
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SniFilterConfig {
+    /// ##### feature.network.incoming.sni_filter.server_name {#feature-network-incoming-sni_filter-server_name}
+    ///
+    /// Case-insensitive regex for the server name.
+    ///
+    /// Supports regexes validated by the
+    /// [`fancy-regex`](https://docs.rs/fancy-regex/latest/fancy_regex/) crate.
+    pub server_name: String,
+
+    /// ##### feature.network.incoming.sni_filter.ports {#feature-network-incoming-sni_filter-ports}
+    ///
+    /// Activate the SNI filter only for these ports.
+    ///
+    /// Defaults to `[443]`.
+    #[serde(default = "SniFilterConfig::default_ports")]
+    pub ports: Vec<u16>,
+}
+
+impl SniFilterConfig {
+    fn default_ports() -> Vec<u16> {
+        vec![443]
+    }
+
+    /// <!--${internal}-->
+    /// Returns whether the SNI filter is active on the given port.
+    pub fn filters_port(&self, port: u16) -> bool {
+        self.ports.contains(&port)
+    }
+
+    /// <!--${internal}-->
+    /// Compiles the server name regex into a [`Filter`] that can be sent to the agent.
+    pub fn as_protocol_filter(&self) -> Result<Filter, Box<fancy_regex::Error>> {
+        Filter::new(self.server_name.clone())
+    }
+
+    /// <!--${internal}-->
+    /// Verifies that the agent's protocol version supports stealing based on SNI.
+    pub fn ensure_usable_with(
+        &self,
+        agent_protocol_version: Option<&Version>,
+    ) -> Result<(), ConfigError> {
+        if agent_protocol_version.is_some_and(|version| SNI_STEAL_VERSION.matches(version)) {
+            Ok(())
+        } else {
+            Err(ConfigError::Conflict(format!(
+                "Cannot use `feature.network.incoming.sni_filter`, protocol version used by \
+                mirrord-agent must match {}. Consider using a newer version of mirrord-agent",
+                *SNI_STEAL_VERSION
+            )))
+        }
+    }
+
+    /// <!--${internal}-->
+    /// Verifies that the server name regex can't take exponential time to match, see
+    /// [`ensure_linear_matching`](super::http_filter::ensure_linear_matching).
+    pub fn verify_regexes(&self) -> Result<(), ConfigError> {
+        verify_linear_matching(
+            "feature.network.incoming.sni_filter.server_name",
+            &self.server_name,
+        )
+    }
+
+    /// <!--${internal}-->
+    /// Returns the set of ports the SNI filter is active on.
+    pub fn port_set(&self) -> HashSet<u16> {
+        self.ports.iter().copied().collect()
+    }
+}
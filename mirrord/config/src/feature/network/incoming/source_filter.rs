@@ -0,0 +1,96 @@
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use mirrord_protocol::tcp::{IpRange, STEAL_SOURCE_FILTER_VERSION, SourceFilter};
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::config::ConfigError;
+
+/// Steal only the connections (and HTTP requests) made from the given source addresses (only
+/// relevant when `incoming.mode` is `"steal"`).
+///
+/// Traffic from other addresses is passed through to its original destination, e.g. to keep
+/// health checks and traffic from other services going to the remote application, while the
+/// local application handles the requests from a test client:
+///
+/// ```json
+/// {
+///   "allow": ["10.8.0.0/16"],
+///   "deny": ["10.8.1.15"]
+/// }
+/// ```
+///
+/// The filter applies to new connections, connections that were already stolen are not affected.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SourceFilterConfig {
+    /// ##### feature.network.incoming.source_filter.allow {#feature-network-incoming-source_filter-allow}
+    ///
+    /// Steal only traffic from these addresses or CIDR ranges, e.g. `"10.8.0.0/16"`.
+    ///
+    /// Empty by default, which means traffic from all addresses (except the `deny`ed ones).
+    #[serde(default)]
+    pub allow: Vec<String>,
+
+    /// ##### feature.network.incoming.source_filter.deny {#feature-network-incoming-source_filter-deny}
+    ///
+    /// Never steal traffic from these addresses or CIDR ranges, even if they're `allow`ed.
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl SourceFilterConfig {
+    /// <!--${internal}-->
+    /// Parses the addresses into a [`SourceFilter`] that can be sent to the agent.
+    pub fn as_protocol_filter(&self) -> Result<SourceFilter, ConfigError> {
+        Ok(SourceFilter {
+            allow: parse_ranges("feature.network.incoming.source_filter.allow", &self.allow)?,
+            deny: parse_ranges("feature.network.incoming.source_filter.deny", &self.deny)?,
+        })
+    }
+
+    /// <!--${internal}-->
+    /// Verifies that the agent's protocol version supports stealing based on the source address.
+    pub fn ensure_usable_with(
+        &self,
+        agent_protocol_version: Option<&Version>,
+    ) -> Result<(), ConfigError> {
+        if agent_protocol_version
+            .is_some_and(|version| STEAL_SOURCE_FILTER_VERSION.matches(version))
+        {
+            Ok(())
+        } else {
+            Err(ConfigError::Conflict(format!(
+                "Cannot use `feature.network.incoming.source_filter`, protocol version used by \
+                mirrord-agent must match {}. Consider using a newer version of mirrord-agent",
+                *STEAL_SOURCE_FILTER_VERSION
+            )))
+        }
+    }
+}
+
+/// Parses CIDR ranges, or single addresses (as ranges with the full prefix length).
+fn parse_ranges(name: &'static str, ranges: &[String]) -> Result<Vec<IpRange>, ConfigError> {
+    ranges
+        .iter()
+        .map(|range| {
+            let net = match range.parse::<IpAddr>() {
+                Ok(addr) => IpNet::from(addr),
+                Err(..) => range
+                    .parse::<IpNet>()
+                    .map_err(|error| ConfigError::InvalidValue {
+                        name,
+                        provided: range.clone(),
+                        error: error.into(),
+                    })?,
+            };
+
+            Ok(IpRange {
+                addr: net.network(),
+                prefix_len: net.prefix_len(),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Appends every stolen or mirrored HTTP request delivered to the local application to a capture
+/// file, so that the session's traffic can be inspected or replayed later.
+///
+/// Each request is written as a single JSON line, with its headers and base64-encoded body.
+/// Bodies longer than `max_body_bytes` are truncated, which is marked with `"body_truncated":
+/// true`. For example, to capture the requests of a session with bodies up to 1MiB:
+///
+/// ```json
+/// {
+///   "path": "/tmp/mirrord-requests.jsonl",
+///   "max_body_bytes": 1048576
+/// }
+/// ```
+///
+/// The file is appended to, so it can hold the requests of multiple sessions.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TeeConfig {
+    /// ##### feature.network.incoming.tee.path {#feature-network-incoming-tee-path}
+    ///
+    /// Path to the capture file, created if it does not exist.
+    pub path: PathBuf,
+
+    /// ##### feature.network.incoming.tee.max_body_bytes {#feature-network-incoming-tee-max_body_bytes}
+    ///
+    /// Maximum number of body bytes captured for a single request.
+    ///
+    /// Defaults to `65536`.
+    #[serde(default = "TeeConfig::default_max_body_bytes")]
+    pub max_body_bytes: u64,
+}
+
+impl TeeConfig {
+    fn default_max_body_bytes() -> u64 {
+        64 * 1024
+    }
+}
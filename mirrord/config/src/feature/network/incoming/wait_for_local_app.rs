@@ -0,0 +1,44 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Keeps retrying the delivery of stolen traffic while the local application refuses
+/// connections, e.g. because it is still compiling or restarting (only relevant when
+/// `incoming.mode` is `"steal"`).
+///
+/// Without this, stolen requests and connections fail as soon as the local application refuses a
+/// few connection attempts. For example, to wait up to a minute for the application, with at most
+/// 32 requests and connections waiting for it at the same time:
+///
+/// ```json
+/// {
+///   "timeout_secs": 60,
+///   "max_waiting": 32
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct WaitForLocalAppConfig {
+    /// ##### feature.network.incoming.wait_for_local_app.timeout_secs {#feature-network-incoming-wait_for_local_app-timeout_secs}
+    ///
+    /// How long a stolen request or connection waits for the local application to start
+    /// accepting connections, before it fails.
+    ///
+    /// Defaults to `30`.
+    #[serde(default = "WaitForLocalAppConfig::default_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// ##### feature.network.incoming.wait_for_local_app.max_waiting {#feature-network-incoming-wait_for_local_app-max_waiting}
+    ///
+    /// Maximum number of stolen requests and connections that wait for the local application at
+    /// the same time. The ones above the limit fail right away, like without
+    /// `wait_for_local_app`.
+    ///
+    /// Not set by default, which means no limit.
+    pub max_waiting: Option<usize>,
+}
+
+impl WaitForLocalAppConfig {
+    fn default_timeout_secs() -> u64 {
+        30
+    }
+}
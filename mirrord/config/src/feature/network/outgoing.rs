@@ -1,11 +1,14 @@
-use std::ops::Deref;
+use std::{
+    net::{IpAddr, SocketAddr},
+    ops::{Deref, Not},
+};
 
 use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::filter::ProtocolAndAddressFilter;
+use super::filter::{ProtocolAndAddressFilter, ProtocolFilter};
 use crate::{
     config::{ConfigContext, ConfigError, from_env::FromEnv, source::MirrordConfigSource},
     util::{MirrordToggleableConfig, VecOrSingle},
@@ -146,6 +149,78 @@ pub struct OutgoingConfig {
     /// to happen locally on your machine.
     #[config(unstable, env = "MIRRORD_OUTGOING_REMOTE_UNIX_STREAMS")]
     pub unix_streams: Option<VecOrSingle<String>>,
+
+    /// ##### feature.network.outgoing.local_fallback {#feature.network.outgoing.local_fallback}
+    ///
+    /// When a TCP connection through the remote pod fails, connect from the local app instead.
+    ///
+    /// Only connection errors (e.g. refused or timed out connections) trigger the fallback.
+    /// Connections blocked by a policy are never made from the local app.
+    ///
+    /// Useful in hybrid setups, where some hosts exist only on your machine. Connections are still
+    /// tried through the remote pod first, unless they are sent from the local app by the
+    /// [`filter`](#feature.network.outgoing.filter).
+    ///
+    /// Has no effect with `experimental.non_blocking_tcp_connect`, as the remote connection
+    /// result is not known when the app connects.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_OUTGOING_LOCAL_FALLBACK", default = false)]
+    pub local_fallback: bool,
+
+    /// ##### feature.network.outgoing.local_fallback_ttl {#feature.network.outgoing.local_fallback_ttl}
+    ///
+    /// Number of seconds for which an address that failed to connect through the remote pod is
+    /// connected to from the local app directly, without trying the remote pod again.
+    ///
+    /// Only used with [`local_fallback`](#feature.network.outgoing.local_fallback).
+    ///
+    /// Defaults to `30`.
+    #[config(env = "MIRRORD_OUTGOING_LOCAL_FALLBACK_TTL", default = 30)]
+    pub local_fallback_ttl: u64,
+
+    /// ##### feature.network.outgoing.cluster_services {#feature.network.outgoing.cluster_services}
+    ///
+    /// Send TCP connections to Kubernetes Services (their cluster IPs and load balancer IPs, in
+    /// all namespaces you can list) through the remote pod, even when outgoing TCP traffic is
+    /// otherwise disabled or sent from the local app by a `remote` filter.
+    ///
+    /// mirrord lists the Services when the session starts, so this lets your app call other
+    /// services in the cluster, while the rest of its traffic stays local:
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "outgoing": {
+    ///         "tcp": false,
+    ///         "udp": false,
+    ///         "cluster_services": true
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// Has no effect with a `local` filter, where connections that are not filtered out already
+    /// go through the remote pod.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_OUTGOING_CLUSTER_SERVICES", default = false)]
+    pub cluster_services: bool,
+
+    /// ##### feature.network.outgoing.icmp {#feature.network.outgoing.icmp}
+    ///
+    /// Send ICMP echo requests (pings) from the remote pod, so that health-check utilities like
+    /// `ping` can reach the cluster IPs.
+    ///
+    /// Applies to `SOCK_DGRAM` + `IPPROTO_ICMP` sockets (unprivileged ping sockets) with IPv4
+    /// addresses, used with `sendto`/`sendmsg` and `recvfrom`/`recvmsg`. Each echo request waits
+    /// for its reply for up to a second.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_OUTGOING_ICMP", default = false)]
+    pub icmp: bool,
 }
 
 impl MirrordToggleableConfig for OutgoingFileConfig {
@@ -160,6 +235,9 @@ impl MirrordToggleableConfig for OutgoingFileConfig {
             unix_streams: FromEnv::new("MIRRORD_OUTGOING_REMOTE_UNIX_STREAMS")
                 .source_value(context)
                 .transpose()?,
+            cluster_services: FromEnv::new("MIRRORD_OUTGOING_CLUSTER_SERVICES")
+                .source_value(context)
+                .unwrap_or(Ok(false))?,
             ..Default::default()
         })
     }
@@ -170,6 +248,9 @@ impl CollectAnalytics for &OutgoingConfig {
         analytics.add("tcp", self.tcp);
         analytics.add("udp", self.udp);
         analytics.add("ignore_localhost", self.ignore_localhost);
+        analytics.add("local_fallback", self.local_fallback);
+        analytics.add("cluster_services", self.cluster_services);
+        analytics.add("icmp", self.icmp);
         analytics.add(
             "unix_streams",
             self.unix_streams
@@ -192,7 +273,18 @@ impl CollectAnalytics for &OutgoingConfig {
 }
 
 impl OutgoingConfig {
-    pub fn verify(&self, _: &mut ConfigContext) -> Result<(), ConfigError> {
+    pub fn verify(&self, context: &mut ConfigContext) -> Result<(), ConfigError> {
+        if self.cluster_services
+            && matches!(self.filter, Some(OutgoingFilterConfig::Local(..)))
+            && self.tcp.not()
+        {
+            context.add_warning(
+                "`feature.network.outgoing.cluster_services` has no effect with a `local` \
+                outgoing filter and outgoing TCP traffic disabled"
+                    .to_string(),
+            );
+        }
+
         let filters = match self.filter.as_ref() {
             None => return Ok(()),
             Some(OutgoingFilterConfig::Local(filters)) => filters.deref(),
@@ -213,12 +305,69 @@ impl OutgoingConfig {
 
         Ok(())
     }
+
+    /// Makes TCP connections to the given addresses (cluster IPs of Kubernetes Services) go
+    /// through the remote pod, see [`OutgoingConfig::cluster_services`].
+    ///
+    /// The addresses are added to the `remote` filter, which is created if needed. When outgoing
+    /// TCP traffic was disabled, it is enabled, and the user's filters are narrowed down to UDP, so
+    /// that the rest of TCP traffic still goes through the local app.
+    pub fn add_cluster_services<I>(&mut self, addresses: I)
+    where
+        I: IntoIterator<Item = IpAddr>,
+    {
+        let services = addresses
+            .into_iter()
+            .map(|address| format!("tcp://{}", SocketAddr::new(address, 0)));
+
+        match &mut self.filter {
+            // All TCP connections already go through the remote pod.
+            None if self.tcp => {}
+            // Connections that are not filtered out already go through the remote pod.
+            Some(OutgoingFilterConfig::Local(..)) => {}
+
+            None => {
+                let mut filters = services.collect::<Vec<_>>();
+                if self.udp {
+                    filters.push("udp://".to_string());
+                }
+
+                self.filter = Some(OutgoingFilterConfig::Remote(filters.into()));
+                self.tcp = true;
+            }
+
+            Some(OutgoingFilterConfig::Remote(filters)) => {
+                let tcp_enabled = self.tcp;
+                let filters_for_protocols = filters.iter().map(|filter| {
+                    let protocol = filter
+                        .parse::<ProtocolAndAddressFilter>()
+                        .map(|parsed| parsed.protocol);
+                    match protocol {
+                        Ok(ProtocolFilter::Any) if tcp_enabled.not() => {
+                            let address = filter
+                                .split_once("://")
+                                .map_or(filter.as_str(), |(_, address)| address);
+                            format!("udp://{address}")
+                        }
+                        _ => filter.clone(),
+                    }
+                });
+
+                *filters = filters_for_protocols
+                    .chain(services)
+                    .collect::<Vec<_>>()
+                    .into();
+                self.tcp = true;
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use rstest::rstest;
 
+    use super::{OutgoingConfig, OutgoingFilterConfig};
     use crate::{
         config::{ConfigContext, MirrordConfig},
         feature::network::OutgoingFileConfig,
@@ -270,4 +419,70 @@ mod tests {
         assert_eq!(outgoing.tcp, tcp.1);
         assert_eq!(outgoing.udp, udp.1);
     }
+
+    #[rstest]
+    #[case::enabled(true, true, None, None)]
+    #[case::disabled(
+        false,
+        false,
+        None,
+        Some(OutgoingFilterConfig::Remote(
+            vec!["tcp://10.96.0.1:0".to_string(), "tcp://[fd00::1]:0".to_string()].into()
+        ))
+    )]
+    #[case::udp_only(
+        false,
+        true,
+        None,
+        Some(OutgoingFilterConfig::Remote(
+            vec![
+                "tcp://10.96.0.1:0".to_string(),
+                "tcp://[fd00::1]:0".to_string(),
+                "udp://".to_string()
+            ]
+            .into()
+        ))
+    )]
+    #[case::remote_filter(
+        false,
+        true,
+        Some(OutgoingFilterConfig::Remote(
+            vec!["1.1.1.1:53".to_string(), "any://:7777".to_string(), "udp://:53".to_string()].into()
+        )),
+        Some(OutgoingFilterConfig::Remote(
+            vec![
+                "udp://1.1.1.1:53".to_string(),
+                "udp://:7777".to_string(),
+                "udp://:53".to_string(),
+                "tcp://10.96.0.1:0".to_string(),
+                "tcp://[fd00::1]:0".to_string()
+            ]
+            .into()
+        ))
+    )]
+    #[case::local_filter(
+        true,
+        true,
+        Some(OutgoingFilterConfig::Local(vec![":5432".to_string()].into())),
+        Some(OutgoingFilterConfig::Local(vec![":5432".to_string()].into()))
+    )]
+    fn add_cluster_services(
+        #[case] tcp: bool,
+        #[case] udp: bool,
+        #[case] filter: Option<OutgoingFilterConfig>,
+        #[case] expected: Option<OutgoingFilterConfig>,
+    ) {
+        let mut outgoing = OutgoingConfig {
+            tcp,
+            udp,
+            filter,
+            ..Default::default()
+        };
+
+        outgoing.add_cluster_services(["10.96.0.1".parse().unwrap(), "fd00::1".parse().unwrap()]);
+
+        assert!(outgoing.tcp);
+        assert_eq!(outgoing.udp, udp);
+        assert_eq!(outgoing.filter, expected);
+    }
 }
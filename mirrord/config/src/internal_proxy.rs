@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     config::source::MirrordConfigSource,
+    feature::network::incoming::PortSpec,
     logfile_path::{Intproxy, LogDestinationConfig},
 };
 
@@ -134,4 +135,25 @@ pub struct InternalProxyConfig {
     /// ```
     #[config(default = 60)]
     pub process_logging_interval: u64,
+
+    /// ### internal_proxy.listen_ports {#internal_proxy-listen_ports}
+    ///
+    /// Port (e.g. `40000`) or inclusive range of ports (e.g. `"40000-40100"`) the internal
+    /// proxy can listen on for the layer connections.
+    ///
+    /// Within the range, mirrord tries the ports in an order derived from the working directory
+    /// and the target, so that the same project gets the same port in consecutive runs. Ports
+    /// that are already in use (e.g. by your application) are skipped. When all of them are
+    /// taken, mirrord falls back to a port picked by the OS.
+    ///
+    /// Defaults to a port picked by the OS.
+    ///
+    /// ```json
+    /// {
+    ///   "internal_proxy": {
+    ///     "listen_ports": "40000-40100"
+    ///   }
+    /// }
+    /// ```
+    pub listen_ports: Option<PortSpec>,
 }
@@ -12,34 +12,50 @@
 pub mod agent;
 pub mod ci;
 pub mod config;
+pub mod connection;
 pub mod container;
+pub mod discovery;
 pub mod env_key;
 pub mod experimental;
 pub mod external_proxy;
 pub mod feature;
 pub mod internal_proxy;
+pub mod log_redaction;
 pub mod logfile_path;
+pub mod migration;
+pub mod profiles;
 pub mod retry;
 pub mod target;
 pub mod util;
+pub mod variables;
 
-use std::{collections::HashMap, ffi::OsStr, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
 
 use base64::prelude::*;
 use config::{ConfigContext, ConfigError, MirrordConfig};
 use experimental::ExperimentalConfig;
 use feature::{
-    env::mapper::EnvVarsRemapper,
+    env::{mapper::EnvVarsRemapper, rename::EnvVarsRenamer},
     network::{
-        incoming::http_filter::{BodyFilter, InnerFilter},
+        incoming::{
+            IncomingMode,
+            http_filter::{BodyFilter, InnerFilter},
+        },
         outgoing::OutgoingFilterConfig,
     },
 };
 use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
-use mirrord_protocol::tcp::JsonPathQuery;
+use mirrord_protocol::{
+    capabilities::AgentCapabilities,
+    tcp::{Filter, HTTP_BODY_PREVIEW_MAX_SIZE, JsonPathQuery},
+};
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use target::Target;
 use tera::Tera;
 use tracing::warn;
@@ -48,6 +64,7 @@ use crate::{
     agent::AgentConfig,
     ci::CiConfig,
     config::{FromFileError, source::MirrordConfigSource},
+    connection::ConnectionConfig,
     container::ContainerConfig,
     env_key::EnvKey,
     external_proxy::ExternalProxyConfig,
@@ -56,6 +73,8 @@ use crate::{
         fs::{READONLY_FILE_BUFFER_HARD_LIMIT, READONLY_FILE_BUFFER_WARN_LIMIT},
     },
     internal_proxy::InternalProxyConfig,
+    log_redaction::LogRedactionConfig,
+    profiles::{ConfigProfile, MIRRORD_CONFIG_PROFILE_ENV},
     retry::StartupRetryConfig,
     target::TargetConfig,
     util::VecOrSingle,
@@ -81,9 +100,18 @@ pub const MIRRORD_LAYER_WAIT_FOR_DEBUGGER: &str = "MIRRORD_LAYER_WAIT_FOR_DEBUGG
 /// Or if using VSCode Extension or JetBrains plugin, simply create a `.mirrord/mirrord.json` file
 /// or use the UI.
 ///
+/// Without `-f`, the CLI looks for a `.mirrord` directory in the current directory and its
+/// parents (like `git` does with `.git`), and loads `mirrord.json`, `config.toml`, `config.yaml`,
+/// `config.yml`, `config.json` and `config.json5` from the nearest one, merged in this order.
+///
 /// To help you get started, here are examples of a basic configuration file, and a complete
 /// configuration file containing all fields.
 ///
+/// String values can also reference environment variables with `${env:NAME}`, and the target
+/// with `${target.path}` and `${target.namespace}`. These are resolved after the `-t`/`-n`
+/// overrides are applied, so one shared config can filter on `x-user: ${env:USER}`.
+/// Use `$${` for a literal `${`.
+///
 /// ### Basic `config.json` {#root-basic}
 ///
 /// ```json
@@ -287,6 +315,64 @@ pub struct LayerConfig {
     /// ```
     pub profile: Option<String>,
 
+    /// ## profiles {#root-profiles}
+    ///
+    /// Named sets of overrides of this configuration, selected with
+    /// `mirrord exec -p <profile name>` (or the `MIRRORD_CONFIG_PROFILE` environment variable).
+    ///
+    /// A profile accepts the same fields as the top level of the configuration, and can build on
+    /// another profile with `extends`. When a profile is selected, the profiles in its `extends`
+    /// chain are applied first, and then the profile itself. Objects are merged field by field,
+    /// all other values (including arrays) replace the values from the base configuration.
+    ///
+    /// ```json
+    /// {
+    ///   "target": "deployment/payments",
+    ///   "profiles": {
+    ///     "readonly": {
+    ///       "feature": {
+    ///         "fs": "read",
+    ///         "network": { "outgoing": false }
+    ///       }
+    ///     },
+    ///     "debug-payments": {
+    ///       "extends": "readonly",
+    ///       "feature": {
+    ///         "network": { "incoming": "steal" },
+    ///         "env": { "override": { "RUST_LOG": "debug" } }
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// Not to be confused with [`profile`](#root-profile), which selects a mirrord profile
+    /// installed in the cluster.
+    pub profiles: Option<BTreeMap<String, ConfigProfile>>,
+
+    /// ## include {#root-include}
+    ///
+    /// Other config files to load before this one, e.g. a base config shared by the team.
+    ///
+    /// The files are merged in the order of the list, every file overriding the ones before it,
+    /// and this config file is merged last. Objects are merged field by field, all other values
+    /// (including arrays) are replaced. Relative paths are resolved from the directory of the
+    /// including file, and included files can have their own `include`.
+    ///
+    /// ```json
+    /// {
+    ///   "include": ["../base.yaml", "team.json5"],
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "http_filter": { "header_filter": "x-user: alice" }
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub include: Option<Vec<PathBuf>>,
+
     /// ## kubeconfig {#root-kubeconfig}
     ///
     /// Path to a kubeconfig file, if not specified, will use `KUBECONFIG`, or `~/.kube/config`, or
@@ -325,10 +411,18 @@ pub struct LayerConfig {
     #[config(nested)]
     pub agent: AgentConfig,
 
+    /// ## connection {#root-connection}
+    #[config(nested)]
+    pub connection: ConnectionConfig,
+
     /// ## container {#root-container}
     #[config(nested, unstable)]
     pub container: ContainerConfig,
 
+    /// ## log_redaction {#root-log_redaction}
+    #[config(nested)]
+    pub log_redaction: LogRedactionConfig,
+
     /// ## feature {#root-feature}
     #[config(nested)]
     pub feature: FeatureConfig,
@@ -354,6 +448,38 @@ pub struct LayerConfig {
     #[config(env = "MIRRORD_KUBE_CONTEXT")]
     pub kube_context: Option<String>,
 
+    /// ## kube_events {#root-kube_events}
+    ///
+    /// When running without the mirrord operator, emits Kubernetes events on the target pod when
+    /// a mirrord session starts and ends (with reasons `MirrordSessionStarted` and
+    /// `MirrordSessionEnded`), so that mirrord activity shows up in your existing event tooling,
+    /// e.g. `kubectl get events`.
+    ///
+    /// The events carry the local user and host names, and require only the permission to
+    /// create `events` in the target's namespace.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_KUBE_EVENTS", default = false)]
+    pub kube_events: bool,
+
+    /// ## remote_logs {#root-remote_logs}
+    ///
+    /// Streams the logs (stdout and stderr) of the target container into the session output,
+    /// prefixed with `[remote]`. With JSON progress (used by the IDE extensions), every line is
+    /// reported as a `RemoteLog` message.
+    ///
+    /// Streaming starts with the session, and can be paused and resumed at runtime by sending
+    /// `SIGUSR1` to the process printing the logs (its pid is shown when the streaming starts).
+    ///
+    /// Requires the permission to get `pods/log` in the target's namespace. Ignored when running
+    /// targetless.
+    ///
+    /// Can also be enabled with the `--remote-logs` flag.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_REMOTE_LOGS", default = false)]
+    pub remote_logs: bool,
+
     /// ## internal_proxy {#root-internal_proxy}
     #[config(nested)]
     pub internal_proxy: InternalProxyConfig,
@@ -496,16 +622,53 @@ impl LayerConfig {
     ///
     /// This function **does not** use [`LayerConfig::RESOLVED_CONFIG_ENV`] nor
     /// [`LayerConfig::decode`]. It resolves the config from scratch.
+    ///
+    /// When no config file is given in [`LayerConfig::FILE_PATH_ENV`], uses the project config
+    /// found with [`discovery::discover_project_config`], starting from the current directory.
+    /// Paths to the loaded files are available in [`ConfigContext::loaded_files`].
     pub fn resolve(context: &mut ConfigContext) -> Result<Self, ConfigError> {
         let mut config = if let Ok(path) = context.get_env(Self::FILE_PATH_ENV) {
             LayerFileConfig::from_path(path, context)?.generate_config(context)?
         } else {
-            LayerFileConfig::default().generate_config(context)?
+            let discovered = std::env::current_dir()
+                .map(|directory| discovery::discover_project_config(&directory))
+                .unwrap_or_default();
+
+            if discovered.is_empty() {
+                LayerFileConfig::default().generate_config(context)?
+            } else {
+                let paths = discovered.iter().map(PathBuf::as_path).collect::<Vec<_>>();
+                LayerFileConfig::from_paths(&paths, context)?.generate_config(context)?
+            }
         };
         config.apply_magic();
+        config.apply_target_cluster(context);
         Ok(config)
     }
 
+    /// Applies [`TargetConfig::kubeconfig`] and [`TargetConfig::kube_context`] to the root
+    /// [`LayerConfig::kubeconfig`] and [`LayerConfig::kube_context`], so that every Kubernetes
+    /// client of the session talks to the target's cluster.
+    ///
+    /// Only the root values from the config file are replaced, the ones from the environment (and
+    /// the CLI arguments, passed as environment variables) still take precedence.
+    ///
+    /// This is a partial implementation: the whole session runs against a single cluster. Targets
+    /// in several clusters within one session are not implemented.
+    fn apply_target_cluster(&mut self, context: &ConfigContext) {
+        if let Some(kubeconfig) = &self.target.kubeconfig
+            && context.get_env("MIRRORD_KUBECONFIG").is_err()
+        {
+            self.kubeconfig = Some(kubeconfig.clone());
+        }
+
+        if let Some(kube_context) = &self.target.kube_context
+            && context.get_env("MIRRORD_KUBE_CONTEXT").is_err()
+        {
+            self.kube_context = Some(kube_context.clone());
+        }
+    }
+
     /// Applies the presets in `feature.magic` to the config, modifying it in-place.
     fn apply_magic(&mut self) {
         if self.feature.magic.aws {
@@ -562,6 +725,7 @@ impl LayerConfig {
             http_filter.all_of.is_some(),
             http_filter.any_of.is_some(),
             http_filter.body_filter.is_some(),
+            http_filter.preset.is_some(),
         ]
         .into_iter()
         .filter(|used| *used)
@@ -572,16 +736,38 @@ impl LayerConfig {
             ))?
         }
 
-        if [http_filter.all_of.as_ref(), http_filter.any_of.as_ref()]
-            .into_iter()
-            .flatten()
-            .any(Vec::is_empty)
+        if [
+            http_filter.all_of.as_ref(),
+            http_filter.any_of.as_ref(),
+            http_filter.exclude.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(Vec::is_empty)
         {
             Err(ConfigError::Conflict(
                 "Composite HTTP filter cannot be empty".to_string(),
             ))?;
         }
 
+        http_filter.verify_regexes()?;
+
+        if let Some(ports) = &self.internal_proxy.listen_ports {
+            ports.range().map_err(|error| ConfigError::InvalidValue {
+                name: "internal_proxy.listen_ports",
+                provided: ports.to_string(),
+                error: error.into(),
+            })?;
+        }
+
+        if self.agent.max_body_buffer_size > HTTP_BODY_PREVIEW_MAX_SIZE {
+            Err(ConfigError::InvalidValue {
+                name: "agent.max_body_buffer_size",
+                provided: self.agent.max_body_buffer_size.to_string(),
+                error: format!("cannot exceed {HTTP_BODY_PREVIEW_MAX_SIZE} bytes").into(),
+            })?;
+        }
+
         let verify_body_filter = |filter: &BodyFilter| match filter {
             BodyFilter::Json { query, .. } => {
                 // Only need to verify `query` as `matches` is later
@@ -617,11 +803,20 @@ impl LayerConfig {
             }
         }
 
+        if let Some(exclude) = &http_filter.exclude {
+            for filter in exclude {
+                if let InnerFilter::Body(body) = filter {
+                    verify_body_filter(body)?
+                }
+            }
+        }
+
         if !self.feature.network.incoming.ignore_ports.is_empty()
             && self.feature.network.incoming.ports.is_some()
         {
             Err(ConfigError::Conflict(
-                "Cannot use both `incoming.ignore_ports` and `incoming.ports` at the same time"
+                "Cannot use both `incoming.ignore_ports` and `incoming.ports` (`only_ports`) \
+                at the same time"
                     .to_string(),
             ))?
         }
@@ -649,6 +844,244 @@ impl LayerConfig {
             (None, None) => {}
         }
 
+        if let Some(sni_filter) = &self.feature.network.incoming.sni_filter {
+            sni_filter
+                .as_protocol_filter()
+                .map_err(|error| ConfigError::InvalidValue {
+                    name: "feature.network.incoming.sni_filter.server_name",
+                    provided: sni_filter.server_name.clone(),
+                    error,
+                })?;
+            sni_filter.verify_regexes()?;
+
+            if let Some(port) = http_filter
+                .ports
+                .as_deref()
+                .unwrap_or_default()
+                .iter()
+                .find(|port| sni_filter.filters_port(**port))
+            {
+                Err(ConfigError::Conflict(format!(
+                    "Port {port} is present in both `feature.network.incoming.http_filter.ports` \
+                    and `feature.network.incoming.sni_filter.ports`"
+                )))?
+            }
+
+            if !self.feature.network.incoming.is_steal() {
+                context.add_warning(
+                    "`feature.network.incoming.sni_filter` is ignored when not stealing incoming traffic."
+                        .into(),
+                );
+            }
+        }
+
+        if let Some(postgres_filter) = &self.feature.network.incoming.postgres_filter {
+            let regexes = [
+                (
+                    "feature.network.incoming.postgres_filter.database",
+                    &postgres_filter.database,
+                ),
+                (
+                    "feature.network.incoming.postgres_filter.user",
+                    &postgres_filter.user,
+                ),
+            ];
+
+            if regexes.iter().all(|(_, regex)| regex.is_none()) {
+                Err(ConfigError::Conflict(
+                    "`feature.network.incoming.postgres_filter` requires `database` or `user`"
+                        .to_string(),
+                ))?
+            }
+
+            for (name, regex) in regexes {
+                if let Some(regex) = regex {
+                    Filter::new(regex.clone()).map_err(|error| ConfigError::InvalidValue {
+                        name,
+                        provided: regex.clone(),
+                        error,
+                    })?;
+                }
+            }
+            postgres_filter.verify_regexes()?;
+
+            let sni_ports = self
+                .feature
+                .network
+                .incoming
+                .sni_filter
+                .as_ref()
+                .map(|sni_filter| sni_filter.ports.as_slice())
+                .unwrap_or_default();
+            if let Some(port) = http_filter
+                .ports
+                .as_deref()
+                .unwrap_or_default()
+                .iter()
+                .chain(sni_ports)
+                .find(|port| postgres_filter.filters_port(**port))
+            {
+                Err(ConfigError::Conflict(format!(
+                    "Port {port} is present in both `feature.network.incoming.postgres_filter.ports` \
+                    and the ports of another incoming filter"
+                )))?
+            }
+
+            if !self.feature.network.incoming.is_steal() {
+                context.add_warning(
+                    "`feature.network.incoming.postgres_filter` is ignored when not stealing incoming traffic."
+                        .into(),
+                );
+            }
+        }
+
+        if let Some(redis_filter) = &self.feature.network.incoming.redis_filter {
+            let regexes = [
+                (
+                    "feature.network.incoming.redis_filter.command",
+                    &redis_filter.command,
+                ),
+                (
+                    "feature.network.incoming.redis_filter.key",
+                    &redis_filter.key,
+                ),
+            ];
+
+            if regexes.iter().all(|(_, regex)| regex.is_none()) {
+                Err(ConfigError::Conflict(
+                    "`feature.network.incoming.redis_filter` requires `command` or `key`"
+                        .to_string(),
+                ))?
+            }
+
+            for (name, regex) in regexes {
+                if let Some(regex) = regex {
+                    Filter::new(regex.clone()).map_err(|error| ConfigError::InvalidValue {
+                        name,
+                        provided: regex.clone(),
+                        error,
+                    })?;
+                }
+            }
+            redis_filter.verify_regexes()?;
+
+            let incoming = &self.feature.network.incoming;
+            let sni_ports = incoming
+                .sni_filter
+                .as_ref()
+                .map(|sni_filter| sni_filter.ports.as_slice())
+                .unwrap_or_default();
+            let postgres_ports = incoming
+                .postgres_filter
+                .as_ref()
+                .map(|postgres_filter| postgres_filter.ports.as_slice())
+                .unwrap_or_default();
+            if let Some(port) = http_filter
+                .ports
+                .as_deref()
+                .unwrap_or_default()
+                .iter()
+                .chain(sni_ports)
+                .chain(postgres_ports)
+                .find(|port| redis_filter.filters_port(**port))
+            {
+                Err(ConfigError::Conflict(format!(
+                    "Port {port} is present in both `feature.network.incoming.redis_filter.ports` \
+                    and the ports of another incoming filter"
+                )))?
+            }
+
+            if !incoming.is_steal() {
+                context.add_warning(
+                    "`feature.network.incoming.redis_filter` is ignored when not stealing incoming traffic."
+                        .into(),
+                );
+            }
+        }
+
+        if let Some(source_filter) = &self.feature.network.incoming.source_filter {
+            source_filter.as_protocol_filter()?;
+
+            if !self.feature.network.incoming.is_steal() {
+                context.add_warning(
+                    "`feature.network.incoming.source_filter` is ignored when not stealing incoming traffic."
+                        .into(),
+                );
+            }
+        }
+
+        if let Some(request_limit) = &self.feature.network.incoming.request_limit {
+            if request_limit.max_in_flight == 0 {
+                Err(ConfigError::InvalidValue {
+                    name: "feature.network.incoming.request_limit.max_in_flight",
+                    provided: request_limit.max_in_flight.to_string(),
+                    error: "must be greater than 0".into(),
+                })?
+            }
+
+            if !self.feature.network.incoming.is_steal() {
+                context.add_warning(
+                    "`feature.network.incoming.request_limit` is ignored when not stealing incoming traffic."
+                        .into(),
+                );
+            }
+        }
+
+        if let Some(backlog) = &self.feature.network.incoming.backlog {
+            if backlog.max_pending == 0 {
+                Err(ConfigError::InvalidValue {
+                    name: "feature.network.incoming.backlog.max_pending",
+                    provided: backlog.max_pending.to_string(),
+                    error: "must be greater than 0".into(),
+                })?
+            }
+
+            if !self.feature.network.incoming.is_steal() {
+                context.add_warning(
+                    "`feature.network.incoming.backlog` is ignored when not stealing incoming traffic."
+                        .into(),
+                );
+            }
+        }
+
+        if self.feature.network.incoming.wait_for_local_app.is_some()
+            && !self.feature.network.incoming.is_steal()
+        {
+            context.add_warning(
+                "`feature.network.incoming.wait_for_local_app` is ignored when not stealing incoming traffic."
+                    .into(),
+            );
+        }
+
+        let replicas = &self.feature.network.incoming.replicas;
+        if !replicas.is_empty() {
+            let mut ports = HashSet::new();
+            for replica in replicas {
+                if !ports.insert(replica.port) {
+                    Err(ConfigError::InvalidValue {
+                        name: "feature.network.incoming.replicas",
+                        provided: replica.port.to_string(),
+                        error: "each port can be given only once".into(),
+                    })?
+                }
+
+                if replica.local_ports.is_empty() {
+                    Err(ConfigError::InvalidValue {
+                        name: "feature.network.incoming.replicas.local_ports",
+                        provided: "[]".into(),
+                        error: "must not be empty".into(),
+                    })?
+                }
+            }
+
+            if !self.feature.network.incoming.is_steal() {
+                context.add_warning(
+                    "`feature.network.incoming.replicas` is ignored when not stealing incoming traffic."
+                        .into(),
+                );
+            }
+        }
+
         if !self.feature.copy_target.enabled
             && self
                 .target
@@ -687,6 +1120,14 @@ impl LayerConfig {
                         .into(),
                 );
             }
+
+            if self.feature.env.jvm_limits {
+                context.add_warning(
+                    "`feature.env.jvm_limits` is ignored in targetless runs, \
+                    as there is no target container to read the limits from."
+                        .into(),
+                );
+            }
         }
 
         if self.feature.copy_target.enabled {
@@ -766,8 +1207,25 @@ impl LayerConfig {
             EnvVarsRemapper::new(env_vars_mapping, HashMap::new())?;
         }
 
+        if let Some(env_vars_rename) = self.feature.env.rename.clone() {
+            EnvVarsRenamer::new(env_vars_rename, HashMap::new())?;
+        }
+
+        self.agent.verify(context)?;
+        self.log_redaction.verify(context)?;
         self.feature.network.dns.verify(context)?;
         self.feature.network.outgoing.verify(context)?;
+
+        if self.feature.network.outgoing.local_fallback
+            && self.experimental.non_blocking_tcp_connect
+        {
+            context.add_warning(
+                "`feature.network.outgoing.local_fallback` has no effect when \
+                `experimental.non_blocking_tcp_connect` is enabled."
+                    .into(),
+            );
+        }
+
         self.feature.split_queues.verify(context)?;
 
         if self.feature.fs.readonly_file_buffer > READONLY_FILE_BUFFER_HARD_LIMIT {
@@ -830,6 +1288,69 @@ impl LayerConfig {
 
         Ok(())
     }
+
+    /// Verifies that the agent, which settled on `agent_version`, supports all the features
+    /// enabled in this config.
+    ///
+    /// Meant to be called right after the protocol version handshake, so that the user gets a
+    /// precise error instead of a failure in the middle of the session.
+    pub fn ensure_supported_by(
+        &self,
+        capabilities: &AgentCapabilities,
+        agent_version: &semver::Version,
+    ) -> Result<(), ConfigError> {
+        let network = &self.feature.network;
+        let incoming = &network.incoming;
+        let http_filter = incoming.http_filter.is_filter_set();
+
+        let requirements = [
+            (
+                incoming.is_steal() && !capabilities.steal,
+                "feature.network.incoming.mode = \"steal\"",
+            ),
+            (
+                incoming.is_steal() && http_filter && !capabilities.http_filter,
+                "feature.network.incoming.http_filter",
+            ),
+            (
+                matches!(incoming.mode, IncomingMode::Mirror)
+                    && http_filter
+                    && !capabilities.mirror_http_filter,
+                "feature.network.incoming.http_filter (in mirror mode)",
+            ),
+            (
+                network.outgoing.tcp && !capabilities.outgoing_tcp,
+                "feature.network.outgoing.tcp",
+            ),
+            (
+                network.outgoing.udp && !capabilities.outgoing_udp,
+                "feature.network.outgoing.udp",
+            ),
+            (
+                network.outgoing.icmp && !capabilities.icmp_echo,
+                "feature.network.outgoing.icmp",
+            ),
+            (
+                network.dns.enabled && !capabilities.dns,
+                "feature.network.dns",
+            ),
+            (
+                self.feature.fs.is_active() && !capabilities.fs,
+                "feature.fs",
+            ),
+        ];
+
+        match requirements
+            .into_iter()
+            .find_map(|(unsupported, feature)| unsupported.then_some(feature))
+        {
+            Some(feature) => Err(ConfigError::UnsupportedByAgent {
+                feature,
+                agent_version: agent_version.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
 }
 
 impl CollectAnalytics for &LayerConfig {
@@ -839,11 +1360,15 @@ impl CollectAnalytics for &LayerConfig {
         };
         analytics.add("use_kubeconfig", self.kubeconfig.is_some());
         analytics.add("use_profile", self.profile.is_some());
+        analytics.add("kube_events", self.kube_events);
+        analytics.add("remote_logs", self.remote_logs);
         (&self.target).collect_analytics(analytics);
         (&self.agent).collect_analytics(analytics);
         (&self.feature).collect_analytics(analytics);
         (&self.experimental).collect_analytics(analytics);
         (&self.startup_retry).collect_analytics(analytics);
+        (&self.connection).collect_analytics(analytics);
+        analytics.add("log_redaction", &self.log_redaction);
     }
 }
 
@@ -872,31 +1397,178 @@ impl LayerFileConfig {
     where
         P: AsRef<Path>,
     {
+        Self::from_paths(&[path.as_ref()], context)
+    }
+
+    /// Parses a [`LayerFileConfig`] from multiple files, merged in the given order, every file
+    /// overriding the ones before it.
+    ///
+    /// The key is taken from the last file that defines it, see [`Self::from_path`].
+    pub fn from_paths(paths: &[&Path], context: &mut ConfigContext) -> Result<Self, FromFileError> {
         let key = context
             .get_env(env_key::MIRRORD_ENV_KEY)
             .ok()
-            .or_else(|| Self::extract_key_from_file(path.as_ref()))
+            .or_else(|| {
+                paths
+                    .iter()
+                    .rev()
+                    .find_map(|path| Self::extract_key_from_file(path))
+            })
             .unwrap_or_else(EnvKey::autogenerated_with_marker);
 
         context.override_env_mut(env_key::MIRRORD_ENV_KEY, &key);
 
-        let mut template_engine = Tera::default();
-        template_engine.add_template_file(path.as_ref(), Some("main"))?;
-
         let mut tera_context = tera::Context::new();
         tera_context.insert("key", &key);
 
-        let rendered = template_engine.render("main", &tera_context)?;
+        let profile = context.get_env(MIRRORD_CONFIG_PROFILE_ENV).ok();
+        let mut merged = serde_json::Value::Object(Default::default());
 
-        match path.as_ref().extension().and_then(OsStr::to_str) {
+        for path in paths {
+            context.add_loaded_file(path);
+
+            let rendered = Self::render(path, &tera_context)?;
+            let (config, value) = Self::parse_migrated(path, &rendered, context)?;
+
+            if let Some(config) = config
+                && paths.len() == 1
+                && config.include.is_none()
+                && profile.is_none()
+                && !variables::has_variables(&rendered)
+            {
+                return Ok(config);
+            }
+
+            let mut visited = path.canonicalize().into_iter().collect();
+            let value = Self::resolve_includes(path, value, &tera_context, context, &mut visited)?;
+            util::merge_json(&mut merged, value);
+        }
+
+        if let Some(profile) = profile {
+            merged = profiles::apply_profile(merged, &profile)?;
+        }
+        variables::expand_variables(&mut merged, context)?;
+
+        Ok(serde_json::from_value::<Self>(merged)?)
+    }
+
+    /// Renders the Tera template in the config file.
+    fn render(path: &Path, tera_context: &tera::Context) -> Result<String, FromFileError> {
+        let mut template_engine = Tera::default();
+        template_engine.add_template_file(path, Some("main"))?;
+
+        Ok(template_engine.render("main", tera_context)?)
+    }
+
+    /// Parses the rendered content of a config file, based on the file extension.
+    pub(crate) fn parse_rendered<T>(
+        rendered: &str,
+        extension: Option<&str>,
+    ) -> Result<T, FromFileError>
+    where
+        T: DeserializeOwned,
+    {
+        match extension {
             // No Extension? assume json
-            Some("json") | None => Ok(serde_json::from_str::<Self>(&rendered)?),
-            Some("toml") => Ok(toml::from_str::<Self>(&rendered)?),
-            Some("yaml" | "yml") => Ok(serde_yaml::from_str::<Self>(&rendered)?),
+            Some("json") | None => Ok(serde_json::from_str::<T>(rendered)?),
+            Some("json5") => Ok(json5::from_str::<T>(rendered)?),
+            Some("toml") => Ok(toml::from_str::<T>(rendered)?),
+            Some("yaml" | "yml") => Ok(serde_yaml::from_str::<T>(rendered)?),
             ext => Err(FromFileError::InvalidExtension(ext.map(String::from))),
         }
     }
 
+    /// Parses the rendered config file, migrating deprecated keys (see [`migration`]) with a
+    /// warning.
+    ///
+    /// Unless a migration was needed, also returns the file parsed into `Self`, which gives errors
+    /// with line and column of the invalid value.
+    fn parse_migrated(
+        path: &Path,
+        rendered: &str,
+        context: &mut ConfigContext,
+    ) -> Result<(Option<Self>, serde_json::Value), FromFileError> {
+        let extension = path.extension().and_then(OsStr::to_str);
+        let mut value = Self::parse_rendered::<serde_json::Value>(rendered, extension)?;
+
+        let migrations = migration::migrate(&mut value);
+        if migrations.is_empty() {
+            let config = Self::parse_rendered::<Self>(rendered, extension)?;
+            return Ok((Some(config), value));
+        }
+
+        for migration in migrations {
+            context.add_warning(format!(
+                "{migration} in `{}`, run `mirrord config migrate` to update the file",
+                path.display()
+            ));
+        }
+
+        Ok((None, value))
+    }
+
+    /// Loads the files from the `include` list of the config file at `path`, and merges `config`
+    /// on top of them.
+    ///
+    /// Included files are merged in the order of the list, so every file overrides the ones
+    /// before it. Paths are relative to the directory of the including file.
+    ///
+    /// `visited` holds the canonical paths of the files in the current include chain.
+    fn resolve_includes(
+        path: &Path,
+        config: serde_json::Value,
+        tera_context: &tera::Context,
+        context: &mut ConfigContext,
+        visited: &mut Vec<PathBuf>,
+    ) -> Result<serde_json::Value, FromFileError> {
+        let includes: Vec<PathBuf> = match config.get("include") {
+            Some(includes) => serde_json::from_value(includes.clone())?,
+            None => Default::default(),
+        };
+
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let mut merged = serde_json::Value::Object(Default::default());
+
+        for include in includes {
+            let include = directory.join(include);
+            let included =
+                Self::load_included(&include, tera_context, context, visited).map_err(|error| {
+                    FromFileError::Include {
+                        path: include.clone(),
+                        error: Box::new(error),
+                    }
+                })?;
+
+            util::merge_json(&mut merged, included);
+        }
+
+        util::merge_json(&mut merged, config);
+
+        Ok(merged)
+    }
+
+    /// Loads a file from the `include` list, see [`Self::resolve_includes`].
+    fn load_included(
+        path: &Path,
+        tera_context: &tera::Context,
+        context: &mut ConfigContext,
+        visited: &mut Vec<PathBuf>,
+    ) -> Result<serde_json::Value, FromFileError> {
+        let canonical = path.canonicalize()?;
+        if visited.contains(&canonical) {
+            return Err(FromFileError::IncludeCycle(path.to_owned()));
+        }
+
+        let rendered = Self::render(path, tera_context)?;
+        let (_, config) = Self::parse_migrated(path, &rendered, context)?;
+
+        visited.push(canonical);
+        let config = Self::resolve_includes(path, config, tera_context, context, visited);
+        visited.pop();
+
+        config
+    }
+
     /// Extracts just the `key` field from a config file without template rendering.
     ///
     /// This is used in the first pass of config loading to determine the key value
@@ -916,6 +1588,11 @@ impl LayerFileConfig {
                 .get("key")?
                 .as_str()
                 .map(String::from),
+            Some("json5") => json5::from_str::<serde_json::Value>(&content)
+                .ok()?
+                .get("key")?
+                .as_str()
+                .map(String::from),
             Some("toml") => toml::from_str::<toml::Value>(&content)
                 .ok()?
                 .get("key")?
@@ -1180,6 +1857,8 @@ mod tests {
                     container: None,
                 })),
                 namespace: Some("default".to_owned()),
+                kubeconfig: None,
+                kube_context: None,
             }),
             skip_processes: None,
             skip_extra_build_tools: None,
@@ -1223,6 +1902,18 @@ mod tests {
                             ports: None,
                             https_delivery: Default::default(),
                             tls_delivery: Default::default(),
+                            sni_filter: None,
+                            postgres_filter: None,
+                            redis_filter: None,
+                            source_filter: None,
+                            request_limit: None,
+                            replicas: None,
+                            backlog: None,
+                            metadata_headers: None,
+                            mirror_max_bytes_per_second: None,
+                            wait_for_local_app: None,
+                            tee: None,
+                            follow_bind: None,
                         }),
                     ))),
                     outgoing: Some(ToggleableConfig::Config(OutgoingFileConfig {
@@ -1242,14 +1933,20 @@ mod tests {
             container: None,
             operator: None,
             profile: None,
+            profiles: None,
+            include: None,
             sip_binaries: None,
             kube_context: None,
+            kube_events: None,
+            remote_logs: None,
             external_proxy: None,
             internal_proxy: None,
             use_proxy: None,
             experimental: None,
             skip_sip: None,
             startup_retry: None,
+            connection: None,
+            log_redaction: None,
             ci: None,
             traceparent: None,
             baggage: None,
@@ -1416,6 +2113,79 @@ mod tests {
         assert_eq!(decoded, resolved_config);
     }
 
+    /// Verifies that [`LayerConfig::ensure_supported_by`] names the first feature the agent does
+    /// not support.
+    #[test]
+    fn unsupported_by_agent() {
+        let mut cfg_context = ConfigContext::default();
+        let config = ConfigType::Json
+            .parse(
+                r#"
+        {
+            "feature": {
+                "network": {
+                    "incoming": {
+                        "mode": "mirror",
+                        "http_filter": { "header_filter": "x-intercept: me" }
+                    }
+                }
+            }
+        }"#,
+            )
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let version = "1.20.0".parse().unwrap();
+        let error = config
+            .ensure_supported_by(
+                &AgentCapabilities::from_protocol_version(&version),
+                &version,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ConfigError::UnsupportedByAgent {
+                feature: "feature.network.incoming.http_filter (in mirror mode)",
+                ..
+            }
+        ));
+
+        config
+            .ensure_supported_by(&AgentCapabilities::CURRENT, &mirrord_protocol::VERSION)
+            .unwrap();
+    }
+
+    /// Verifies that [`LayerConfig::verify`] rejects backtracking-prone regexes in the incoming
+    /// filters that the agent compiles with [`fancy_regex`].
+    #[rstest]
+    #[case::sni(
+        r#"{ "sni_filter": { "server_name": "^(?!x)(a+)+$" } }"#,
+        "feature.network.incoming.sni_filter.server_name"
+    )]
+    #[case::postgres(
+        r#"{ "postgres_filter": { "user": "^(?!x)(a+)+$" } }"#,
+        "feature.network.incoming.postgres_filter.user"
+    )]
+    #[case::redis(
+        r#"{ "redis_filter": { "key": "^(?!x)(a+)+$" } }"#,
+        "feature.network.incoming.redis_filter.key"
+    )]
+    fn backtracking_incoming_filters_are_rejected(#[case] incoming: &str, #[case] field: &str) {
+        let mut cfg_context = ConfigContext::default();
+        let config = ConfigType::Json
+            .parse(&format!(
+                r#"{{ "feature": {{ "network": {{ "incoming": {incoming} }} }} }}"#
+            ))
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let error = config.verify(&mut cfg_context).unwrap_err();
+        assert!(
+            matches!(&error, ConfigError::InvalidValue { name, .. } if *name == field),
+            "{error:?}"
+        );
+    }
+
     #[cfg(not(target_os = "windows"))]
     const USER_ENVVAR: &str = "USER";
 
@@ -1474,6 +2244,28 @@ mod tests {
         assert_eq!(config.key.analytics_len(), "only-cli-key".len());
     }
 
+    /// Verifies that the target's cluster replaces the root one from the config file, but not the
+    /// one given with the environment (or the CLI).
+    #[rstest]
+    #[case(None, "target-context")]
+    #[case(Some("cli-context"), "cli-context")]
+    fn target_cluster_priority(#[case] env_context: Option<&str>, #[case] expected: &str) {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file
+            .write_all(
+                br#"{"kube_context": "root-context", "target": {"kube_context": "target-context"}}"#,
+            )
+            .unwrap();
+
+        let mut ctx = ConfigContext::default()
+            .override_env(LayerConfig::FILE_PATH_ENV, temp_file.path())
+            .override_env_opt("MIRRORD_KUBE_CONTEXT", env_context)
+            .strict_env(true);
+        let config = LayerConfig::resolve(&mut ctx).unwrap();
+
+        assert_eq!(config.kube_context.as_deref(), Some(expected));
+    }
+
     #[test]
     fn test_template_rendering_with_key() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -1490,4 +2282,41 @@ mod tests {
 
         assert_eq!(pod_target.pod, "test-my-session");
     }
+
+    #[test]
+    fn test_include_merge_order() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("base.yaml"),
+            "operator: false\nkubeconfig: base\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("team.json5"),
+            "{\n  // shared by the team\n  kubeconfig: 'team',\n  accept_invalid_certificates: true,\n}",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("mirrord.json"),
+            r#"{"include": ["base.yaml", "team.json5"], "accept_invalid_certificates": false}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("loop.json"),
+            r#"{"include": ["loop.json"]}"#,
+        )
+        .unwrap();
+
+        let mut ctx = ConfigContext::default().strict_env(true);
+        let config = LayerFileConfig::from_path(dir.path().join("mirrord.json"), &mut ctx).unwrap();
+        assert_eq!(config.operator, Some(false));
+        assert_eq!(config.kubeconfig.as_deref(), Some("team"));
+        assert_eq!(config.accept_invalid_certificates, Some(false));
+
+        let error = LayerFileConfig::from_path(dir.path().join("loop.json"), &mut ctx).unwrap_err();
+        assert!(matches!(
+            error,
+            FromFileError::Include { error, .. } if matches!(*error, FromFileError::IncludeCycle(..))
+        ));
+    }
 }
@@ -0,0 +1,95 @@
+use mirrord_analytics::CollectAnalytics;
+use mirrord_config_derive::MirrordConfig;
+use mirrord_protocol::redact::{Redactor, RedactorError};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ConfigContext, ConfigError, source::MirrordConfigSource};
+
+/// Masking of secrets in mirrord logs of HTTP traffic, in the internal proxy and in the
+/// mirrord-agent.
+///
+/// Values of the `authorization`, `cookie` and `set-cookie` headers are always masked, unless
+/// redaction is disabled.
+///
+/// ```json
+/// {
+///   "log_redaction": {
+///     "headers": ["x-api-key"],
+///     "body_patterns": ["\"password\":\\s*\"[^\"]*\""]
+///   }
+/// }
+/// ```
+#[derive(MirrordConfig, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[config(map_to = "LogRedactionFileConfig", derive = "JsonSchema")]
+#[cfg_attr(test, config(derive = "PartialEq, Eq"))]
+pub struct LogRedactionConfig {
+    /// ### log_redaction.enabled {#log_redaction-enabled}
+    ///
+    /// Disabling this prints headers and bodies in full, including the default headers.
+    ///
+    /// Defaults to `true`.
+    #[config(env = "MIRRORD_LOG_REDACTION", default = true)]
+    pub enabled: bool,
+
+    /// ### log_redaction.headers {#log_redaction-headers}
+    ///
+    /// Names of headers whose values are masked, in addition to `authorization`, `cookie` and
+    /// `set-cookie`.
+    pub headers: Option<Vec<String>>,
+
+    /// ### log_redaction.body_patterns {#log_redaction-body_patterns}
+    ///
+    /// Regexes matched against HTTP bodies printed in the logs. Matches are masked.
+    pub body_patterns: Option<Vec<String>>,
+}
+
+impl LogRedactionConfig {
+    /// Builds the [`Redactor`] described by this config.
+    pub fn redactor(&self) -> Result<Redactor, RedactorError> {
+        if self.enabled {
+            Redactor::new(
+                self.headers.iter().flatten(),
+                self.body_patterns.iter().flatten(),
+            )
+        } else {
+            Ok(Redactor::disabled())
+        }
+    }
+
+    pub fn verify(&self, _: &mut ConfigContext) -> Result<(), ConfigError> {
+        self.redactor().map(|_| ()).map_err(|error| {
+            let (name, provided) = match &error {
+                RedactorError::InvalidHeaderName(header) => {
+                    ("log_redaction.headers", header.clone())
+                }
+                RedactorError::InvalidBodyPattern(pattern, _) => {
+                    ("log_redaction.body_patterns", pattern.clone())
+                }
+            };
+
+            ConfigError::InvalidValue {
+                name,
+                provided,
+                error: error.into(),
+            }
+        })
+    }
+}
+
+impl CollectAnalytics for &LogRedactionConfig {
+    fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
+        analytics.add("enabled", self.enabled);
+        analytics.add(
+            "headers",
+            self.headers.as_ref().map(Vec::len).unwrap_or_default(),
+        );
+        analytics.add(
+            "body_patterns",
+            self.body_patterns
+                .as_ref()
+                .map(Vec::len)
+                .unwrap_or_default(),
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Migration of deprecated keys in config files.
+//!
+//! When a config key is replaced with a new one, add a [`Migration`] to [`MIGRATIONS`]. Config
+//! files using the deprecated key are rewritten when loaded (with a warning), and can be updated
+//! on disk with `mirrord config migrate`.
+
+use std::{ffi::OsStr, fmt, io, path::Path};
+
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::{LayerFileConfig, config::FromFileError};
+
+/// Moves the value of a deprecated key to its replacement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    /// Dotted path of the deprecated key, e.g. `feature.network.incoming.https_delivery`.
+    pub deprecated: &'static str,
+
+    /// Dotted path of the replacement key.
+    pub replacement: &'static str,
+}
+
+/// All known migrations, applied in order.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        deprecated: "feature.network.incoming.http_header_filter",
+        replacement: "feature.network.incoming.http_filter.header_filter",
+    },
+    Migration {
+        deprecated: "feature.network.incoming.https_delivery",
+        replacement: "feature.network.incoming.tls_delivery",
+    },
+];
+
+impl fmt::Display for Migration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` is deprecated, use `{}` instead",
+            self.deprecated, self.replacement
+        )
+    }
+}
+
+impl Migration {
+    /// Moves the value of [`Self::deprecated`] to [`Self::replacement`].
+    ///
+    /// Does nothing if the deprecated key is not set, the replacement is already set, or the
+    /// replacement cannot be set because one of its parents is not an object (e.g. `incoming` is
+    /// set to `"mirror"`). In the last two cases, the deprecated key is left for the config
+    /// verification to report.
+    ///
+    /// Returns whether the config was changed.
+    pub fn apply(&self, config: &mut Value) -> bool {
+        let Some((parent, key)) = self.deprecated.rsplit_once('.') else {
+            return false;
+        };
+
+        let has_deprecated = config
+            .pointer(&to_pointer(parent))
+            .and_then(Value::as_object)
+            .is_some_and(|parent| parent.contains_key(key));
+        if !has_deprecated || !can_insert(config, self.replacement) {
+            return false;
+        }
+
+        let Some(value) = config
+            .pointer_mut(&to_pointer(parent))
+            .and_then(Value::as_object_mut)
+            .and_then(|parent| parent.remove(key))
+        else {
+            return false;
+        };
+
+        let mut current = config;
+        let mut segments = self.replacement.split('.').peekable();
+        while let Some(segment) = segments.next() {
+            let Some(object) = current.as_object_mut() else {
+                return false;
+            };
+
+            if segments.peek().is_none() {
+                object.insert(segment.to_owned(), value);
+                return true;
+            }
+
+            current = object
+                .entry(segment)
+                .or_insert_with(|| Value::Object(Map::new()));
+        }
+
+        false
+    }
+}
+
+/// Applies all [`MIGRATIONS`] to the given config.
+///
+/// Returns the migrations that changed the config.
+pub fn migrate(config: &mut Value) -> Vec<Migration> {
+    MIGRATIONS
+        .iter()
+        .filter(|migration| migration.apply(config))
+        .copied()
+        .collect()
+}
+
+/// Errors that can occur when migrating a config file with [`migrate_file`].
+#[derive(Error, Debug)]
+pub enum MigrateFileError {
+    #[error("failed to read the config file: {0}")]
+    Read(#[from] io::Error),
+
+    #[error(transparent)]
+    Parse(#[from] FromFileError),
+
+    #[error("failed to serialize the migrated config: {0}")]
+    Serialize(String),
+}
+
+/// Applies all [`MIGRATIONS`] to the config file at `path`.
+///
+/// Templates in the file are not rendered, and comments are not preserved.
+///
+/// Returns the applied migrations, and the migrated content of the file in the same format. The
+/// file itself is not modified.
+pub fn migrate_file(path: &Path) -> Result<(Vec<Migration>, String), MigrateFileError> {
+    let content = std::fs::read_to_string(path)?;
+    let extension = path.extension().and_then(OsStr::to_str);
+
+    let mut config = LayerFileConfig::parse_rendered::<Value>(&content, extension)?;
+    let migrations = migrate(&mut config);
+
+    let migrated = match extension {
+        Some("toml") => toml::to_string_pretty(&config).map_err(|error| error.to_string()),
+        Some("yaml" | "yml") => serde_yaml::to_string(&config).map_err(|error| error.to_string()),
+        // JSON is also valid JSON5.
+        _ => serde_json::to_string_pretty(&config).map_err(|error| error.to_string()),
+    }
+    .map_err(MigrateFileError::Serialize)?;
+
+    Ok((migrations, migrated))
+}
+
+/// Converts a dotted path to a JSON pointer.
+fn to_pointer(path: &str) -> String {
+    format!("/{}", path.replace('.', "/"))
+}
+
+/// Whether the given dotted path is not set yet, and all its existing parents are objects.
+fn can_insert(config: &Value, path: &str) -> bool {
+    let mut current = config;
+
+    for segment in path.split('.') {
+        let Some(object) = current.as_object() else {
+            return false;
+        };
+
+        match object.get(segment) {
+            Some(value) => current = value,
+            None => return true,
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{MIGRATIONS, migrate};
+
+    #[test]
+    fn moves_deprecated_keys() {
+        let mut config = json!({
+            "feature": {
+                "network": {
+                    "incoming": {
+                        "mode": "steal",
+                        "http_header_filter": "x-user: alice",
+                        "https_delivery": { "protocol": "tls" },
+                        "tls_delivery": { "protocol": "tcp" }
+                    }
+                }
+            }
+        });
+
+        assert_eq!(migrate(&mut config), vec![MIGRATIONS[0]]);
+        assert_eq!(
+            config,
+            json!({
+                "feature": {
+                    "network": {
+                        "incoming": {
+                            "mode": "steal",
+                            "http_filter": { "header_filter": "x-user: alice" },
+                            "https_delivery": { "protocol": "tls" },
+                            "tls_delivery": { "protocol": "tcp" }
+                        }
+                    }
+                }
+            })
+        );
+
+        let mut config = json!({ "feature": { "network": { "incoming": "mirror" } } });
+        assert!(migrate(&mut config).is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+//! Named profiles defined in the config file, see
+//! [`LayerConfig::profiles`](crate::LayerConfig::profiles).
+//!
+//! Not to be confused with [`LayerConfig::profile`](crate::LayerConfig::profile), which selects a
+//! mirrord profile installed in the cluster.
+
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::util::merge_json;
+
+/// Name of the environment variable that selects a profile from
+/// [`LayerConfig::profiles`](crate::LayerConfig::profiles).
+pub const MIRRORD_CONFIG_PROFILE_ENV: &str = "MIRRORD_CONFIG_PROFILE";
+
+/// A named set of overrides of the configuration, see
+/// [`LayerConfig::profiles`](crate::LayerConfig::profiles).
+///
+/// Besides `extends`, accepts the same fields as the top level of the configuration.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct ConfigProfile {
+    /// ### profiles.extends {#root-profiles-extends}
+    ///
+    /// Name of another profile, applied before this one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+
+    /// Overridden configuration fields.
+    #[serde(flatten)]
+    pub overrides: Map<String, Value>,
+}
+
+/// Errors that can occur when applying a profile from
+/// [`LayerConfig::profiles`](crate::LayerConfig::profiles).
+#[derive(Error, Debug)]
+pub enum ConfigProfileError {
+    #[error("profile `{0}` is not defined in the config file")]
+    NotFound(String),
+
+    #[error("profile `{0}` is part of an `extends` cycle")]
+    Cycle(String),
+
+    #[error("invalid `profiles`: {0}")]
+    Invalid(#[from] serde_json::Error),
+}
+
+/// Applies the profile with the given name to the `config` parsed from a config file.
+///
+/// Profiles from the `extends` chain are applied first, starting from the most basic one.
+/// Objects are merged field by field, all other values (including arrays) are replaced.
+pub fn apply_profile(mut config: Value, name: &str) -> Result<Value, ConfigProfileError> {
+    let profiles: BTreeMap<String, ConfigProfile> = match config.get("profiles") {
+        Some(profiles) => serde_json::from_value(profiles.clone())?,
+        None => Default::default(),
+    };
+
+    let mut chain: Vec<&str> = Vec::new();
+    let mut next = Some(name);
+    while let Some(name) = next {
+        if chain.contains(&name) {
+            return Err(ConfigProfileError::Cycle(name.to_owned()));
+        }
+
+        let profile = profiles
+            .get(name)
+            .ok_or_else(|| ConfigProfileError::NotFound(name.to_owned()))?;
+        chain.push(name);
+        next = profile.extends.as_deref();
+    }
+
+    for name in chain.into_iter().rev() {
+        merge_json(&mut config, Value::Object(profiles[name].overrides.clone()));
+    }
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{ConfigProfileError, apply_profile};
+
+    #[test]
+    fn extends_and_merges() {
+        let config = json!({
+            "target": "deploy/payments",
+            "feature": { "network": { "incoming": "mirror" }, "fs": "read" },
+            "profiles": {
+                "steal": { "feature": { "network": { "incoming": "steal" } } },
+                "debug-payments": {
+                    "extends": "steal",
+                    "feature": { "env": { "override": { "RUST_LOG": "debug" } } }
+                },
+                "loop": { "extends": "loop" }
+            }
+        });
+
+        let applied = apply_profile(config.clone(), "debug-payments").unwrap();
+        assert_eq!(applied["target"], "deploy/payments");
+        assert_eq!(applied["feature"]["fs"], "read");
+        assert_eq!(applied["feature"]["network"]["incoming"], "steal");
+        assert_eq!(applied["feature"]["env"]["override"]["RUST_LOG"], "debug");
+
+        assert!(matches!(
+            apply_profile(config.clone(), "loop"),
+            Err(ConfigProfileError::Cycle(..))
+        ));
+        assert!(matches!(
+            apply_profile(config, "readonly"),
+            Err(ConfigProfileError::NotFound(..))
+        ));
+    }
+}
@@ -49,6 +49,12 @@ pub enum TargetFileConfig {
         #[schemars(schema_with = "make_simple_target_custom_schema")]
         path: Option<Target>,
         namespace: Option<String>,
+        /// <!--${internal}-->
+        /// Path to the kubeconfig file of the target's cluster.
+        kubeconfig: Option<String>,
+        /// <!--${internal}-->
+        /// Context of the target's cluster in the kubeconfig file.
+        kube_context: Option<String>,
     },
 }
 
@@ -142,7 +148,28 @@ fn make_simple_target_custom_schema(r#gen: &mut SchemaGenerator) -> schemars::sc
 ///
 /// The setup above will result in a session without any target.
 /// Remote outgoing traffic and DNS will be done from the `bear-namespace` namespace.
-#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug)]
+///
+/// Setup with a target in another cluster:
+///
+/// ```json
+/// {
+///   "target": {
+///     "path": "deployment/bear-deployment",
+///     "kubeconfig": "~/.kube/staging-config",
+///     "kube_context": "staging-eu"
+///   }
+/// }
+/// ```
+///
+/// The setup above will result in a session targeting the `bear-deployment` deployment in the
+/// cluster of the `staging-eu` context from the `~/.kube/staging-config` file, regardless of the
+/// root [`kubeconfig`](#root-kubeconfig) and [`kube_context`](#root-kube_context) in the config
+/// file. The ones given with `MIRRORD_KUBECONFIG`, `MIRRORD_KUBE_CONTEXT` or the CLI arguments
+/// still take precedence.
+///
+/// The whole session runs against one cluster, a single session cannot use targets from several
+/// clusters.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, Default)]
 #[serde(deny_unknown_fields)]
 pub struct TargetConfig {
     /// ### target.path {#target-path}
@@ -180,6 +207,25 @@ pub struct TargetConfig {
     /// Defaults to the Kubernetes user's default namespace (defined in Kubernetes context).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub namespace: Option<String>,
+
+    /// ### target.kubeconfig {#target-kubeconfig}
+    ///
+    /// Path to the kubeconfig file of the cluster where the target lives.
+    ///
+    /// Overrides the root [`kubeconfig`](#root-kubeconfig) from the config file, so that a config
+    /// file can pick the cluster together with the target. `MIRRORD_KUBECONFIG` still takes
+    /// precedence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kubeconfig: Option<String>,
+
+    /// ### target.kube_context {#target-kube_context}
+    ///
+    /// Kube context of the cluster where the target lives.
+    ///
+    /// Overrides the root [`kube_context`](#root-kube_context) from the config file.
+    /// `MIRRORD_KUBE_CONTEXT` and the `--context` argument still take precedence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kube_context: Option<String>,
 }
 
 impl Default for TargetFileConfig {
@@ -214,15 +260,25 @@ impl MirrordConfig for TargetFileConfig {
     /// Generate the final config object, out of the configuration parsed from a configuration file,
     /// factoring in environment variables (which are also set by the front end - CLI/IDE-plugin).
     fn generate_config(self, context: &mut ConfigContext) -> Result<Self::Generated> {
-        let (path_from_conf_file, namespace_from_conf_file) = match self {
-            TargetFileConfig::Simple(path) => (path, None),
-            TargetFileConfig::Advanced { path, namespace } => (path, namespace),
+        let (path_from_conf_file, namespace_from_conf_file, kubeconfig, kube_context) = match self {
+            TargetFileConfig::Simple(path) => (path, None, None, None),
+            TargetFileConfig::Advanced {
+                path,
+                namespace,
+                kubeconfig,
+                kube_context,
+            } => (path, namespace, kubeconfig, kube_context),
         };
 
         // Env overrides configuration if both there.
         let path = Self::get_target_path_from_env(context)?.or(path_from_conf_file);
         let namespace = Self::get_target_namespace_from_env(context)?.or(namespace_from_conf_file);
-        Ok(TargetConfig { path, namespace })
+        Ok(TargetConfig {
+            path,
+            namespace,
+            kubeconfig,
+            kube_context,
+        })
     }
 }
 
@@ -590,6 +646,7 @@ bitflags::bitflags! {
         const STATEFUL_SET = 128;
         const SERVICE = 256;
         const REPLICA_SET = 512;
+        const CLUSTER = 1024;
     }
 }
 
@@ -599,6 +656,9 @@ impl CollectAnalytics for &TargetConfig {
         if self.namespace.is_some() {
             flags |= TargetAnalyticFlags::NAMESPACE;
         }
+        if self.kubeconfig.is_some() || self.kube_context.is_some() {
+            flags |= TargetAnalyticFlags::CLUSTER;
+        }
         if let Some(path) = &self.path {
             match path {
                 Target::Pod(target) => {
@@ -669,7 +729,9 @@ mod tests {
     #[case(None, None,
         TargetConfig {
             path: None,
-            namespace: None
+            namespace: None,
+            kubeconfig: None,
+            kube_context: None,
         }
     )] // Nothing specified - no target config (targetless mode).
     #[case(
@@ -677,7 +739,9 @@ mod tests {
         Some("ns"),
         TargetConfig{
             path: None,
-            namespace: Some("ns".to_string())
+            namespace: Some("ns".to_string()),
+            kubeconfig: None,
+            kube_context: None,
         }
     )] // Namespace without target - error.
     #[case(
@@ -685,7 +749,9 @@ mod tests {
         None,
         TargetConfig{
             path: Some(Target::Pod(PodTarget {pod: "foo".to_string(), container: None})),
-            namespace: None
+            namespace: None,
+            kubeconfig: None,
+            kube_context: None,
         }
     )] // Only pod specified
     #[case(
@@ -696,7 +762,9 @@ mod tests {
                 pod: "foo".to_string(),
                 container: Some("bar".to_string())
             })),
-            namespace: None
+            namespace: None,
+            kubeconfig: None,
+            kube_context: None,
         }
     )] // Pod and container specified.
     #[case(
@@ -704,7 +772,9 @@ mod tests {
         Some("baz"),
         TargetConfig{
             path: Some(Target::Pod(PodTarget {pod: "foo".to_string(), container: None})),
-            namespace: Some("baz".to_string())
+            namespace: Some("baz".to_string()),
+            kubeconfig: None,
+            kube_context: None,
         }
     )] // Pod and namespace specified.
     #[case(
@@ -715,7 +785,9 @@ mod tests {
                 rollout: "foo".to_string(),
                 container: None
             })),
-            namespace: None
+            namespace: None,
+            kubeconfig: None,
+            kube_context: None,
         }
     )] // Rollout specified.
     fn default(
@@ -741,7 +813,9 @@ mod tests {
         r#"{ "namespace": "my-test-namespace" }"#,
         TargetConfig {
             path: None,
-            namespace: Some("my-test-namespace".to_string())
+            namespace: Some("my-test-namespace".to_string()),
+            kubeconfig: None,
+            kube_context: None,
         }
     )]
     // simple variant of file config - path string, not an object.
@@ -749,7 +823,9 @@ mod tests {
         r#""pod/my-cool-pod""#,
         TargetConfig{
             path: Some(Target::Pod(PodTarget {pod: "my-cool-pod".to_string(), container: None})),
-            namespace: None
+            namespace: None,
+            kubeconfig: None,
+            kube_context: None,
         }
     )]
     // advanced variant of file config.
@@ -757,7 +833,9 @@ mod tests {
         r#"{ "path": "pod/my-cool-pod" }"#,
         TargetConfig{
             path: Some(Target::Pod(PodTarget {pod: "my-cool-pod".to_string(), container: None})),
-            namespace: None
+            namespace: None,
+            kubeconfig: None,
+            kube_context: None,
         }
     )]
     // advanced variant of file config, with object as path.
@@ -769,7 +847,23 @@ mod tests {
         }"#,
         TargetConfig{
             path: Some(Target::Pod(PodTarget {pod: "my-cool-pod".to_string(), container: None})),
-            namespace: None
+            namespace: None,
+            kubeconfig: None,
+            kube_context: None,
+        }
+    )]
+    // advanced variant of file config, with the cluster.
+    #[case(
+        r#"{
+            "path": "pod/my-cool-pod",
+            "kubeconfig": "~/.kube/other-config",
+            "kube_context": "other-cluster"
+        }"#,
+        TargetConfig{
+            path: Some(Target::Pod(PodTarget {pod: "my-cool-pod".to_string(), container: None})),
+            namespace: None,
+            kubeconfig: Some("~/.kube/other-config".to_string()),
+            kube_context: Some("other-cluster".to_string()),
         }
     )]
     fn parse_target_config_from_json(
@@ -2,6 +2,7 @@ use std::{collections::HashSet, fmt, hash::Hash, marker::PhantomData, ops::Deref
 
 use schemars::JsonSchema;
 use serde::{Deserialize, Deserializer, Serialize, de};
+use serde_json::Value;
 
 use crate::{
     LayerConfig,
@@ -291,6 +292,25 @@ where
     deserializer.deserialize_any(StringOrStruct(PhantomData))
 }
 
+/// Merges `overrides` into `base`, used for config profiles and included config files.
+///
+/// Objects are merged field by field, all other values (including arrays) are replaced.
+pub(crate) fn merge_json(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(base) => merge_json(base, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
 /// Convenience function to be used in mirrord-intproxy, mirrord-extproxy and mirrord-layer.
 ///
 /// Reads the resolved [`LayerConfig`] from the [`LayerConfig::RESOLVED_CONFIG_ENV`]
@@ -0,0 +1,211 @@
+//! Expansion of `${...}` variables in string values of the config file.
+//!
+//! Supported variables:
+//!
+//! - `${env:NAME}` - value of the environment variable `NAME`;
+//! - `${target.path}` - the target, e.g. `deployment/payments`;
+//! - `${target.namespace}` - namespace of the target.
+//!
+//! The target variables take the CLI and environment overrides into account.
+//!
+//! Use `$${` to put a literal `${` in a value.
+
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::config::ConfigContext;
+
+/// Errors that can occur when expanding variables in the config file.
+#[derive(Error, Debug)]
+pub enum ConfigVariableError {
+    #[error("environment variable `{0}` used in the config file is not set")]
+    EnvNotSet(String),
+
+    #[error("variable `{0}` used in the config file has no value")]
+    NotSet(&'static str),
+
+    #[error("unknown variable `{0}` used in the config file")]
+    Unknown(String),
+
+    #[error("unterminated variable in config value `{0}`")]
+    Unterminated(String),
+}
+
+/// Values of the built-in variables, taken from the config file and overrides.
+struct BuiltIns {
+    target_path: Option<String>,
+    target_namespace: Option<String>,
+}
+
+impl BuiltIns {
+    fn new(config: &Value, context: &ConfigContext) -> Self {
+        let target = config.get("target");
+        let (path, namespace) = match target {
+            Some(Value::String(path)) => (Some(path.as_str()), None),
+            Some(target) => (
+                target.get("path").and_then(Value::as_str),
+                target.get("namespace").and_then(Value::as_str),
+            ),
+            None => (None, None),
+        };
+
+        Self {
+            target_path: context
+                .get_env("MIRRORD_IMPERSONATED_TARGET")
+                .ok()
+                .or_else(|| path.map(ToOwned::to_owned)),
+            target_namespace: context
+                .get_env("MIRRORD_TARGET_NAMESPACE")
+                .ok()
+                .or_else(|| namespace.map(ToOwned::to_owned)),
+        }
+    }
+
+    fn resolve(&self, name: &str, context: &ConfigContext) -> Result<String, ConfigVariableError> {
+        if let Some(env) = name.strip_prefix("env:") {
+            return context
+                .get_env(env)
+                .map_err(|_| ConfigVariableError::EnvNotSet(env.to_owned()));
+        }
+
+        let (value, name) = match name {
+            "target.path" => (&self.target_path, "target.path"),
+            "target.namespace" => (&self.target_namespace, "target.namespace"),
+            unknown => return Err(ConfigVariableError::Unknown(unknown.to_owned())),
+        };
+
+        value.clone().ok_or(ConfigVariableError::NotSet(name))
+    }
+}
+
+/// Whether the given raw config file content may contain variables.
+pub fn has_variables(content: &str) -> bool {
+    content.contains("${")
+}
+
+/// Expands variables in all string values of the given config.
+pub fn expand_variables(
+    config: &mut Value,
+    context: &ConfigContext,
+) -> Result<(), ConfigVariableError> {
+    let builtins = BuiltIns::new(config, context);
+    expand_value(config, &builtins, context)
+}
+
+fn expand_value(
+    value: &mut Value,
+    builtins: &BuiltIns,
+    context: &ConfigContext,
+) -> Result<(), ConfigVariableError> {
+    match value {
+        Value::String(string) if string.contains("${") => {
+            *string = expand_string(string, builtins, context)?;
+        }
+        Value::Array(values) => {
+            for value in values {
+                expand_value(value, builtins, context)?;
+            }
+        }
+        Value::Object(values) => {
+            for value in values.values_mut() {
+                expand_value(value, builtins, context)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn expand_string(
+    string: &str,
+    builtins: &BuiltIns,
+    context: &ConfigContext,
+) -> Result<String, ConfigVariableError> {
+    let mut expanded = String::with_capacity(string.len());
+    let mut rest = string;
+
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            expanded.push_str(&rest[..start]);
+            expanded.push('{');
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        expanded.push_str(&rest[..start]);
+
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| ConfigVariableError::Unterminated(string.to_owned()))?;
+        let name = &rest[start + 2..start + end];
+        expanded.push_str(&builtins.resolve(name.trim(), context)?);
+        rest = &rest[start + end + 1..];
+    }
+
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{ConfigVariableError, expand_variables};
+    use crate::config::ConfigContext;
+
+    #[test]
+    fn expands_env_and_builtins() {
+        let context = ConfigContext::default()
+            .override_env("USER", "alice")
+            .strict_env(true);
+        let mut config = json!({
+            "target": { "path": "deployment/payments", "namespace": "staging" },
+            "feature": {
+                "network": {
+                    "incoming": {
+                        "http_filter": { "header_filter": "x-user: ${env:USER}" }
+                    }
+                },
+                "env": { "override": { "TARGET": "${target.path} in ${ target.namespace }" } }
+            },
+            "skip_processes": ["cost$${1}"]
+        });
+
+        expand_variables(&mut config, &context).unwrap();
+
+        assert_eq!(
+            config["feature"]["network"]["incoming"]["http_filter"]["header_filter"],
+            "x-user: alice"
+        );
+        assert_eq!(
+            config["feature"]["env"]["override"]["TARGET"],
+            "deployment/payments in staging"
+        );
+        assert_eq!(config["skip_processes"][0], "cost${1}");
+    }
+
+    #[test]
+    fn missing_values() {
+        let context = ConfigContext::default().strict_env(true);
+
+        let mut config = json!({ "key": "${env:NOT_SET}" });
+        assert!(matches!(
+            expand_variables(&mut config, &context),
+            Err(ConfigVariableError::EnvNotSet(..))
+        ));
+
+        let mut config = json!({ "key": "${target.namespace}" });
+        assert!(matches!(
+            expand_variables(&mut config, &context),
+            Err(ConfigVariableError::NotSet("target.namespace"))
+        ));
+
+        let mut config = json!({ "key": "${user}" });
+        assert!(matches!(
+            expand_variables(&mut config, &context),
+            Err(ConfigVariableError::Unknown(..))
+        ));
+    }
+}
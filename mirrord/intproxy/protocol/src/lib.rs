@@ -13,7 +13,11 @@ use mirrord_protocol::{
     FileRequest, FileResponse, GetEnvVarsRequest, Port, RemoteResult,
     dns::{GetAddrInfoRequestV2, GetAddrInfoResponse},
     file::*,
-    outgoing::SocketAddress,
+    interfaces::{GetInterfacesRequest, GetInterfacesResult},
+    outgoing::{
+        SocketAddress,
+        icmp::{IcmpEchoRequest, IcmpEchoResponse},
+    },
     tcp::{MirrorType, StealType},
 };
 
@@ -51,8 +55,20 @@ pub enum LayerToProxyMessage {
     Incoming(IncomingRequest),
     /// Fetch environment variables from the target.
     GetEnv(GetEnvVarsRequest),
+    /// Ping a remote host through the agent.
+    IcmpEcho(IcmpEchoRequest),
+    /// Fetch network interfaces of the target.
+    GetInterfaces(GetInterfacesRequest),
+    /// Something the user should know about, does not require a response.
+    Warning(LayerWarning),
 }
 
+/// A warning for the user, e.g. about traffic that the layer does not intercept.
+///
+/// Logged by the internal proxy, as the layer's own logs are filtered with the user's `RUST_LOG`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq)]
+pub struct LayerWarning(pub String);
+
 /// Layer process information
 #[derive(Encode, Decode, Debug, PartialEq, Eq)]
 pub struct ProcessInfo {
@@ -169,6 +185,9 @@ pub enum IncomingRequest {
     /// A request made by the layer when it accepts a connection on the socket that is listening
     /// for mirrored connections.
     ConnMetadata(ConnMetadataRequest),
+    /// A request to pause or resume the incoming traffic on a subscribed port, made by the CLI
+    /// (`mirrord pause`/`mirrord resume`).
+    PortPause(PortPause),
 }
 
 /// A request for additional metadata for accepted connection.
@@ -234,6 +253,18 @@ pub struct PortUnsubscribe {
     pub listening_on: SocketAddr,
 }
 
+/// A request to temporarily stop (or start again) receiving the incoming traffic on a port,
+/// without unsubscribing from it.
+///
+/// While the port is paused, the agent passes the traffic through to its original destination.
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortPause {
+    /// Port on the remote pod.
+    pub port: Port,
+    /// Whether the port should be paused or resumed.
+    pub paused: bool,
+}
+
 /// Messages sent by the internal proxy and handled by the layer.
 #[derive(Encode, Decode, Debug, PartialEq, Eq)]
 pub enum ProxyToLayerMessage {
@@ -250,6 +281,10 @@ pub enum ProxyToLayerMessage {
     Incoming(IncomingResponse),
     /// A response to layer's [`LayerToProxyMessage::GetEnv`].
     GetEnv(RemoteResult<HashMap<String, String>>),
+    /// A response to layer's [`LayerToProxyMessage::IcmpEcho`].
+    IcmpEcho(RemoteResult<IcmpEchoResponse>),
+    /// A response to layer's [`LayerToProxyMessage::GetInterfaces`].
+    GetInterfaces(GetInterfacesResult),
     /// Internal proxy encountered a fatal error.
     ProxyFailed(String),
 }
@@ -265,6 +300,8 @@ pub enum IncomingResponse {
     PortSubscribe(RemoteResult<()>),
     /// A response to layers' [`ConnMetadataRequest`].
     ConnMetadata(ConnMetadataResponse),
+    /// A response to [`PortPause`].
+    PortPause(RemoteResult<()>),
 }
 
 /// A response to layer's [`OutgoingRequest`].
@@ -547,6 +584,13 @@ impl_request!(
     res_path = ProxyToLayerMessage::Incoming => IncomingResponse::ConnMetadata,
 );
 
+impl_request!(
+    req = PortPause,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::Incoming => IncomingRequest::PortPause,
+    res_path = ProxyToLayerMessage::Incoming => IncomingResponse::PortPause,
+);
+
 impl_request!(
     req = GetEnvVarsRequest,
     res = RemoteResult<HashMap<String, String>>,
@@ -588,3 +632,40 @@ impl_request!(
     req_path = LayerToProxyMessage::File => FileRequest::Fchmod,
     res_path = ProxyToLayerMessage::File => FileResponse::Fchmod,
 );
+
+impl_request!(
+    req = FsyncRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Fsync,
+    res_path = ProxyToLayerMessage::File => FileResponse::Fsync,
+);
+
+impl_request!(
+    req = FileLockRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::FileLock,
+    res_path = ProxyToLayerMessage::File => FileResponse::FileLock,
+);
+
+impl_request!(
+    req = GetCwdRequest,
+    res = RemoteResult<GetCwdResponse>,
+    req_path = LayerToProxyMessage::File => FileRequest::GetCwd,
+    res_path = ProxyToLayerMessage::File => FileResponse::GetCwd,
+);
+
+impl_request!(
+    req = IcmpEchoRequest,
+    res = RemoteResult<IcmpEchoResponse>,
+    req_path = LayerToProxyMessage::IcmpEcho,
+    res_path = ProxyToLayerMessage::IcmpEcho,
+);
+
+impl_request!(
+    req = GetInterfacesRequest,
+    res = GetInterfacesResult,
+    req_path = LayerToProxyMessage::GetInterfaces,
+    res_path = ProxyToLayerMessage::GetInterfaces,
+);
+
+impl_request!(req = LayerWarning, req_path = LayerToProxyMessage::Warning,);
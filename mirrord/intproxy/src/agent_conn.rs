@@ -27,6 +27,8 @@ use strum::IntoDiscriminant;
 use strum_macros::EnumDiscriminants;
 use thiserror::Error;
 pub use tls::ConnectionTlsError;
+#[cfg(all(unix, feature = "fake-cluster"))]
+use tokio::net::UnixStream;
 use tokio::net::{TcpSocket, TcpStream};
 #[cfg(test)]
 use tokio::sync::mpsc;
@@ -77,6 +79,12 @@ pub enum AgentConnectInfo {
     ),
     /// Connect directly to the agent by name and port using k8s port forward.
     DirectKubernetes(AgentKubernetesConnectInfo),
+    /// Connect to an agent running as a local process, through a UNIX socket relayed to its
+    /// listener.
+    ///
+    /// Used by the fake cluster e2e tests, available only with the `fake-cluster` feature.
+    #[cfg(all(unix, feature = "fake-cluster"))]
+    LocalSocket(PathBuf),
     /// Use a dummy connection. The sender is used for
     /// sending the new dummy connection to the driver code.
     ///
@@ -91,6 +99,8 @@ impl fmt::Display for AgentConnectInfoDiscriminants {
             Self::ExternalProxy => "external proxy",
             Self::Operator => "operator",
             Self::DirectKubernetes => "agent",
+            #[cfg(all(unix, feature = "fake-cluster"))]
+            Self::LocalSocket => "local agent",
             #[cfg(test)]
             Self::Dummy => "dummy",
         };
@@ -207,6 +217,19 @@ impl AgentConnection {
                 (conn, ReconnectFlow::Break(kind))
             }
 
+            #[cfg(all(unix, feature = "fake-cluster"))]
+            AgentConnectInfo::LocalSocket(path) => {
+                let stream = UnixStream::connect(&path).await?;
+                let conn = Connection::from_stream(stream).await?;
+
+                let reconnect = ReconnectFlow::ConnectInfo {
+                    config: Box::new(config.clone()),
+                    connect_info: AgentConnectInfo::LocalSocket(path),
+                };
+
+                (conn, reconnect)
+            }
+
             #[cfg(test)]
             AgentConnectInfo::Dummy(sender) => {
                 let (conn, tx, rx) = Connection::dummy();
@@ -14,7 +14,7 @@ pub async fn create_connection(
         .map_err(AgentConnectionError::Kube)?;
 
     let stream = k8s_api
-        .create_connection_portforward(connect_info.clone())
+        .create_agent_connection(connect_info.clone())
         .await
         .map_err(AgentConnectionError::Kube)?;
 
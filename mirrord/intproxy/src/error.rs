@@ -58,6 +58,23 @@ pub(crate) enum ProxyRuntimeError {
     FilesProxy(#[from] FilesProxyError),
 }
 
+impl ProxyRuntimeError {
+    /// Message sent to the layers in [`ProxyToLayerMessage::ProxyFailed`].
+    ///
+    /// Errors caused by a [`ResponseError`] are displayed as `error[M0123]: <message>`, with a
+    /// link to the docs of the [`ErrorCode`](mirrord_protocol::ErrorCode).
+    ///
+    /// [`ProxyToLayerMessage::ProxyFailed`]: mirrord_intproxy_protocol::ProxyToLayerMessage::ProxyFailed
+    pub(crate) fn to_layer_message(&self) -> String {
+        match self {
+            Self::IncomingProxy(IncomingProxyError::SubscriptionFailed(error)) => {
+                error.code().wrap(self).to_string()
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
 /// This kind of error causes a total failure of the proxy, meaning that for these errors doesn't
 /// exist a failover strategy, so facing this error the proxy stops working.
 #[derive(Error, Debug)]
@@ -134,7 +134,7 @@ impl FailoverStrategy {
             ProxyMessage::NewLayer(new_layer) => {
                 self.any_connection_accepted = true;
                 let tx = self.background_tasks.register(
-                    LayerConnection::new(new_layer.stream, new_layer.id),
+                    LayerConnection::new(new_layer.stream, new_layer.id, &new_layer.process_info),
                     MainTaskId::LayerConnection(new_layer.id),
                     IntProxy::CHANNEL_SIZE,
                 );
@@ -159,7 +159,8 @@ impl FailoverStrategy {
     ) {
         match message {
             LayerToProxyMessage::File(FileRequest::Close(_) | FileRequest::CloseDir(_))
-            | LayerToProxyMessage::Incoming(IncomingRequest::PortUnsubscribe(_)) => {
+            | LayerToProxyMessage::Incoming(IncomingRequest::PortUnsubscribe(_))
+            | LayerToProxyMessage::Warning(_) => {
                 tracing::info!(message = ?message, "Proxy in failover mode, ignoring a message");
             }
             _ => self.send_error_to_layer(layer_id, message_id).await,
@@ -172,7 +173,7 @@ impl FailoverStrategy {
                 layer
                     .send(LocalMessage {
                         message_id,
-                        inner: ProxyToLayerMessage::ProxyFailed(self.fail_cause.to_string()),
+                        inner: ProxyToLayerMessage::ProxyFailed(self.fail_cause.to_layer_message()),
                     })
                     .await;
             }
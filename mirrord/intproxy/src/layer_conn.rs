@@ -1,7 +1,7 @@
 //! Implementation of `layer <-> proxy` connection through a [`TcpStream`].
 
 use mirrord_intproxy_protocol::{
-    LayerId, LayerToProxyMessage, LocalMessage, ProxyToLayerMessage,
+    LayerId, LayerToProxyMessage, LocalMessage, ProcessInfo, ProxyToLayerMessage,
     codec::{self, AsyncDecoder, AsyncEncoder, CodecError},
 };
 use tokio::net::{
@@ -14,6 +14,7 @@ use crate::{
     ProxyMessage,
     background_tasks::{BackgroundTask, MessageBus},
     main_tasks::FromLayer,
+    process_watch::ProcessWatch,
 };
 
 /// Handles logic of a single `layer <-> proxy` connection.
@@ -22,17 +23,22 @@ pub struct LayerConnection {
     layer_codec_tx: AsyncEncoder<LocalMessage<ProxyToLayerMessage>, OwnedWriteHalf>,
     layer_codec_rx: AsyncDecoder<LocalMessage<LayerToProxyMessage>, OwnedReadHalf>,
     layer_id: LayerId,
+    /// The connection is closed when the layer's process exits, even if the [`TcpStream`] is
+    /// still open in its forked children.
+    process_watch: ProcessWatch,
 }
 
 impl LayerConnection {
     /// Wraps a raw [`TcpStream`] to be used as a `layer <-> proxy` connection.
-    pub fn new(stream: TcpStream, layer_id: LayerId) -> Self {
+    pub fn new(stream: TcpStream, layer_id: LayerId, process_info: &ProcessInfo) -> Self {
+        let process_watch = ProcessWatch::new(process_info, &stream);
         let (layer_codec_tx, layer_codec_rx) = codec::make_async_framed(stream);
 
         Self {
             layer_codec_rx,
             layer_codec_tx,
             layer_id,
+            process_watch,
         }
     }
 
@@ -77,6 +83,11 @@ impl BackgroundTask for LayerConnection {
                         break Ok(());
                     },
                 },
+
+                _ = self.process_watch.exited() => {
+                    tracing::debug!("Layer process exited, exiting");
+                    break Ok(());
+                },
             }
         }
     }
@@ -4,6 +4,7 @@
 
 use std::{
     collections::{HashMap, HashSet, VecDeque},
+    io::{self, Write},
     ops::ControlFlow,
     time::Duration,
 };
@@ -14,13 +15,19 @@ use layer_conn::LayerConnection;
 use layer_initializer::LayerInitializer;
 use main_tasks::{FromLayer, LayerForked, MainTaskId, ProxyMessage, ToLayer};
 use mirrord_config::{
-    experimental::ExperimentalConfig, feature::network::incoming::tls_delivery::LocalTlsDelivery,
+    experimental::ExperimentalConfig,
+    feature::network::incoming::{
+        backlog::BacklogConfig, replicas::ReplicasConfig, request_limit::RequestLimitConfig,
+        tee::TeeConfig, tls_delivery::LocalTlsDelivery, wait_for_local_app::WaitForLocalAppConfig,
+    },
 };
 use mirrord_intproxy_protocol::{
-    IncomingRequest, LayerId, LayerToProxyMessage, LocalMessage, MessageId, ProcessInfo,
+    IncomingRequest, LayerId, LayerToProxyMessage, LayerWarning, LocalMessage, MessageId,
+    ProcessInfo,
 };
 use mirrord_protocol::{
-    CLIENT_READY_FOR_LOGS, ClientMessage, DaemonMessage, FileRequest, LogLevel,
+    AGENT_LOG_EVENTS_VERSION, CLIENT_READY_FOR_LOGS, ClientMessage, DaemonMessage, FileRequest,
+    LogLevel, tcp::SourceFilter,
 };
 use mirrord_protocol_io::{Client, TxHandle};
 use ping_pong::{PingPong, PingPongMessage};
@@ -53,6 +60,7 @@ mod layer_conn;
 mod layer_initializer;
 pub mod main_tasks;
 mod ping_pong;
+mod process_watch;
 pub mod proxies;
 mod remote_resources;
 mod request_queue;
@@ -102,6 +110,10 @@ pub struct IntProxy {
 
     /// Send handle for the agent connection
     agent_tx: TxHandle<Client>,
+
+    /// Whether we should ask the agent to forward its own logs, see
+    /// [`ClientMessage::ReadyForAgentLogs`].
+    forward_agent_logs: bool,
 }
 
 impl IntProxy {
@@ -118,12 +130,22 @@ impl IntProxy {
     /// Creates a new [`IntProxy`] using existing [`AgentConnection`].
     /// The returned instance will accept connections from the layers using the given
     /// [`TcpListener`].
+    #[allow(clippy::too_many_arguments)]
     pub fn new_with_connection(
         agent_conn: AgentConnection,
         listener: TcpListener,
         file_buffer_size: u64,
         https_delivery: LocalTlsDelivery,
+        request_limit: Option<RequestLimitConfig>,
+        replicas: Vec<ReplicasConfig>,
+        backlog: Option<BacklogConfig>,
+        metadata_headers: bool,
+        mirror_max_bytes_per_second: Option<u64>,
+        wait_for_local_app: Option<WaitForLocalAppConfig>,
+        source_filter: Option<SourceFilter>,
+        tee: Option<&TeeConfig>,
         process_logging_interval: Duration,
+        forward_agent_logs: bool,
         experimental: &ExperimentalConfig,
     ) -> Self {
         let mut background_tasks: BackgroundTasks<MainTaskId, ProxyMessage, ProxyRuntimeError> =
@@ -173,6 +195,14 @@ impl IntProxy {
             IncomingProxy::new(
                 Duration::from_millis(experimental.idle_local_http_connection_timeout),
                 https_delivery,
+                request_limit,
+                replicas,
+                backlog,
+                metadata_headers,
+                mirror_max_bytes_per_second,
+                wait_for_local_app,
+                source_filter,
+                tee,
             ),
             MainTaskId::IncomingProxy,
             Self::CHANNEL_SIZE,
@@ -218,6 +248,7 @@ impl IntProxy {
             connected_layers: HashMap::new(),
             process_logging_interval,
             agent_tx,
+            forward_agent_logs,
         }
     }
 
@@ -342,13 +373,13 @@ impl IntProxy {
             ProxyMessage::NewLayer(new_layer) => {
                 self.any_connection_accepted = true;
 
-                self.connected_layers
-                    .insert(new_layer.id, new_layer.process_info);
                 let tx = self.background_tasks.register(
-                    LayerConnection::new(new_layer.stream, new_layer.id),
+                    LayerConnection::new(new_layer.stream, new_layer.id, &new_layer.process_info),
                     MainTaskId::LayerConnection(new_layer.id),
                     Self::CHANNEL_SIZE,
                 );
+                self.connected_layers
+                    .insert(new_layer.id, new_layer.process_info);
                 self.task_txs.layers.insert(new_layer.id, tx);
 
                 if let Some(parent) = new_layer.parent_id {
@@ -537,6 +568,17 @@ impl IntProxy {
                     self.agent_tx.send(ClientMessage::ReadyForLogs).await;
                 }
 
+                if self.forward_agent_logs {
+                    if AGENT_LOG_EVENTS_VERSION.matches(&protocol_version) {
+                        self.agent_tx.send(ClientMessage::ReadyForAgentLogs).await;
+                    } else {
+                        tracing::warn!(
+                            %protocol_version,
+                            "Agent log forwarding was requested, but the agent does not support it"
+                        );
+                    }
+                }
+
                 self.task_txs
                     .files
                     .send(FilesProxyMessage::ProtocolVersion(protocol_version.clone()))
@@ -561,6 +603,35 @@ impl IntProxy {
                     .send(OutgoingProxyMessage::AgentProtocolVersion(protocol_version))
                     .await;
             }
+            DaemonMessage::DisabledFeatures(features) => {
+                tracing::warn!(
+                    ?features,
+                    "The mirrord agent runs in read-only mode, some features are disabled"
+                );
+
+                self.task_txs
+                    .incoming
+                    .send(IncomingProxyMessage::AgentDisabledFeatures(features))
+                    .await;
+            }
+            DaemonMessage::MandatoryHttpFilter(filter) => {
+                tracing::info!(
+                    %filter,
+                    "The mirrord agent requires an HTTP filter, it will be added to all steal \
+                    subscriptions"
+                );
+
+                self.task_txs
+                    .incoming
+                    .send(IncomingProxyMessage::AgentMandatoryHttpFilter(filter))
+                    .await;
+            }
+            DaemonMessage::SessionQuotas(quotas) => {
+                tracing::info!(?quotas, "The mirrord operator set quotas for this session");
+            }
+            DaemonMessage::Capabilities(capabilities) => {
+                tracing::info!(?capabilities, "The mirrord agent reported its capabilities");
+            }
             DaemonMessage::LogMessage(log) => match log.level {
                 LogLevel::Error => tracing::error!(
                     message = log.message,
@@ -575,15 +646,76 @@ impl IntProxy {
                     "Received a log message from the agent"
                 ),
             },
+            DaemonMessage::LogEvent(event) => {
+                let level = match event.level {
+                    LogLevel::Error => {
+                        tracing::error!(
+                            target: "mirrord::agent_logs",
+                            agent_target = event.target,
+                            "{}",
+                            event.message
+                        );
+                        "ERROR"
+                    }
+                    LogLevel::Warn => {
+                        tracing::warn!(
+                            target: "mirrord::agent_logs",
+                            agent_target = event.target,
+                            "{}",
+                            event.message
+                        );
+                        "WARN"
+                    }
+                    LogLevel::Info => {
+                        tracing::info!(
+                            target: "mirrord::agent_logs",
+                            agent_target = event.target,
+                            "{}",
+                            event.message
+                        );
+                        "INFO"
+                    }
+                };
+
+                // The agent sends these only when the user asked for them, and `mirrord exec`
+                // leaves our stderr attached to the user's terminal then. The user may be gone
+                // already, so failed writes are ignored.
+                let _ = writeln!(
+                    io::stderr(),
+                    "mirrord-agent {level} {}: {}",
+                    event.target,
+                    event.message
+                );
+            }
             DaemonMessage::GetEnvVarsResponse(res) => {
                 self.task_txs
                     .simple
                     .send(SimpleProxyMessage::GetEnvRes(res.map(Into::into)))
                     .await
             }
+            DaemonMessage::IcmpEcho(res) => {
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::IcmpEchoRes(res))
+                    .await
+            }
+            DaemonMessage::GetInterfaces(res) => {
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::GetInterfacesRes(res))
+                    .await
+            }
+            DaemonMessage::GoingDown(notice) => {
+                self.task_txs
+                    .incoming
+                    .send(IncomingProxyMessage::AgentGoingDown(notice))
+                    .await;
+            }
             message @ DaemonMessage::PauseTarget(_)
             | message @ DaemonMessage::Vpn(_)
-            | message @ DaemonMessage::ReverseDnsLookup(_) => {
+            | message @ DaemonMessage::ReverseDnsLookup(_)
+            | message @ DaemonMessage::ResourceUsage(_)
+            | message @ DaemonMessage::ListeningPorts(_) => {
                 Err(ProxyRuntimeError::UnexpectedAgentMessage(
                     UnexpectedAgentMessage(message.into()),
                 ))?;
@@ -634,6 +766,23 @@ impl IntProxy {
                     .send(SimpleProxyMessage::GetEnvReq(message_id, layer_id, req))
                     .await
             }
+            LayerToProxyMessage::IcmpEcho(req) => {
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::IcmpEchoReq(message_id, layer_id, req))
+                    .await
+            }
+            LayerToProxyMessage::GetInterfaces(req) => {
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::GetInterfacesReq(
+                        message_id, layer_id, req,
+                    ))
+                    .await
+            }
+            LayerToProxyMessage::Warning(LayerWarning(warning)) => {
+                tracing::warn!(?layer_id, warning, "Received a warning from the layer");
+            }
             other => Err(ProxyRuntimeError::UnexpectedLayerMessage(other))?,
         }
 
@@ -795,7 +944,16 @@ mod test {
             listener,
             4096,
             Default::default(),
+            None,
+            Default::default(),
+            None,
+            false,
+            None,
+            None,
+            None,
+            None,
             Duration::from_secs(60),
+            false,
             &ExperimentalFileConfig::default()
                 .generate_config(&mut Default::default())
                 .unwrap(),
@@ -913,7 +1071,16 @@ mod test {
             listener,
             4096,
             Default::default(),
+            None,
+            Default::default(),
+            None,
+            false,
+            None,
+            None,
+            None,
+            None,
             Duration::from_secs(60),
+            false,
             &ExperimentalFileConfig::default()
                 .generate_config(&mut Default::default())
                 .unwrap(),
@@ -1006,7 +1173,16 @@ mod test {
             listener,
             4096,
             Default::default(),
+            None,
+            Default::default(),
+            None,
+            false,
+            None,
+            None,
+            None,
+            None,
             Duration::from_secs(60),
+            false,
             &ExperimentalFileConfig::default()
                 .generate_config(&mut Default::default())
                 .unwrap(),
@@ -1077,7 +1253,16 @@ mod test {
             listener,
             4096,
             Default::default(),
+            None,
+            Default::default(),
+            None,
+            false,
+            None,
+            None,
+            None,
+            None,
             Duration::from_secs(60),
+            false,
             &ExperimentalFileConfig::default()
                 .generate_config(&mut Default::default())
                 .unwrap(),
@@ -0,0 +1,266 @@
+//! Tracking the lifetime of layer processes with `pidfd`s.
+//!
+//! A `layer <-> proxy` connection is not always closed when the layer's process is gone. The layer
+//! does not close its connection in forked children (it only forgets it, as the connection's
+//! mutexes might be in an invalid state after the fork), so the descriptor lives on in every child.
+//! Process-pool servers fork many long-lived workers, and their master process might exit or
+//! `exec` before them. Without tracking the process itself, the master's layer would hold its
+//! resources (e.g. port subscriptions) until the last worker exits.
+//!
+//! The process is identified by its end of the connection, not by its command line, which
+//! process-pool servers often change (e.g. `gunicorn: master [app]`). The connection is closed on
+//! `exec` (it is `CLOEXEC`), so a process that no longer holds it is considered gone too.
+//!
+//! Note that each forked child still makes its own connection (and the agent connection is shared
+//! through this proxy), there is no connection broker inside the layer.
+
+use std::time::Duration;
+
+use mirrord_intproxy_protocol::ProcessInfo;
+use tokio::net::TcpStream;
+
+/// How often we check if the layer process still holds its connection, see [`ProcessWatch`].
+const CONNECTION_CHECK_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Resolves when the process of a layer exits, or `exec`s.
+#[derive(Debug)]
+pub struct ProcessWatch {
+    #[cfg(target_os = "linux")]
+    process: Option<linux::LayerProcess>,
+}
+
+impl ProcessWatch {
+    /// Starts watching the process described by the given [`ProcessInfo`], that connected to
+    /// this proxy with the given [`TcpStream`].
+    ///
+    /// The watch never resolves if the process cannot be tracked, e.g. on platforms without
+    /// `pidfd`s, or when the layer runs in a different PID or network namespace than this proxy
+    /// (`mirrord container`).
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    pub fn new(process_info: &ProcessInfo, stream: &TcpStream) -> Self {
+        Self {
+            #[cfg(target_os = "linux")]
+            process: linux::LayerProcess::new(process_info.pid, stream)
+                .inspect_err(|error| {
+                    tracing::debug!(
+                        %error,
+                        pid = process_info.pid,
+                        "Failed to track the layer process with a pidfd",
+                    )
+                })
+                .ok()
+                .flatten(),
+        }
+    }
+
+    /// Resolves when the process exits, or no longer holds its connection.
+    pub async fn exited(&mut self) {
+        #[cfg(target_os = "linux")]
+        if let Some(process) = &mut self.process {
+            process.gone().await;
+            return;
+        }
+
+        std::future::pending().await
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        fs, io,
+        net::SocketAddr,
+        os::fd::{FromRawFd, OwnedFd},
+    };
+
+    use tokio::{
+        io::unix::AsyncFd,
+        net::TcpStream,
+        time::{Interval, MissedTickBehavior},
+    };
+
+    use super::CONNECTION_CHECK_INTERVAL;
+
+    /// A layer process tracked with a pidfd.
+    #[derive(Debug)]
+    pub(super) struct LayerProcess {
+        pid: i32,
+        /// Becomes readable when the process exits.
+        pidfd: AsyncFd<OwnedFd>,
+        /// Inode of the layer's end of the connection.
+        socket_inode: u64,
+        /// For checking if the process still holds the socket.
+        check_interval: Interval,
+    }
+
+    impl LayerProcess {
+        /// Opens a pidfd for the process, if it holds the layer's end of the `stream`.
+        pub(super) fn new(pid: i32, stream: &TcpStream) -> io::Result<Option<Self>> {
+            // SAFETY: `pidfd_open` takes no pointers.
+            let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: the descriptor was just opened and is owned by nobody else.
+            let pidfd = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+            // Verified after the pidfd is open, so that the pid cannot be reused in between.
+            let Some(socket_inode) = socket_inode(pid, stream.peer_addr()?, stream.local_addr()?)?
+            else {
+                return Ok(None);
+            };
+            if holds_socket(pid, socket_inode)? {
+                let mut check_interval = tokio::time::interval(CONNECTION_CHECK_INTERVAL);
+                check_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+                Ok(Some(Self {
+                    pid,
+                    pidfd: AsyncFd::new(pidfd)?,
+                    socket_inode,
+                    check_interval,
+                }))
+            } else {
+                Ok(None)
+            }
+        }
+
+        /// Resolves when the process exits, or no longer holds the socket (e.g. after `exec`).
+        pub(super) async fn gone(&mut self) {
+            loop {
+                tokio::select! {
+                    _ = self.pidfd.readable() => return,
+                    _ = self.check_interval.tick() => {
+                        if holds_socket(self.pid, self.socket_inode).unwrap_or(false) {
+                            continue;
+                        }
+
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Finds the inode of the TCP socket with the given addresses, in the network namespace of the
+    /// process.
+    ///
+    /// Only the ports are compared, the addresses in `/proc/net/tcp` are in the host byte order.
+    fn socket_inode(pid: i32, local: SocketAddr, remote: SocketAddr) -> io::Result<Option<u64>> {
+        let port = |address: &str| {
+            address
+                .rsplit_once(':')
+                .and_then(|(_, port)| u16::from_str_radix(port, 16).ok())
+        };
+
+        let ipv4 = fs::read_to_string(format!("/proc/{pid}/net/tcp"))?;
+        let ipv6 = fs::read_to_string(format!("/proc/{pid}/net/tcp6")).unwrap_or_default();
+
+        let inode = ipv4
+            .lines()
+            .chain(ipv6.lines())
+            .skip(1)
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .find(|fields| {
+                fields.get(1).and_then(|field| port(field)) == Some(local.port())
+                    && fields.get(2).and_then(|field| port(field)) == Some(remote.port())
+            })
+            .and_then(|fields| fields.get(9)?.parse().ok());
+
+        Ok(inode)
+    }
+
+    /// Checks if the process has a descriptor of the socket with the given inode.
+    fn holds_socket(pid: i32, inode: u64) -> io::Result<bool> {
+        let link = format!("socket:[{inode}]");
+
+        let holds = fs::read_dir(format!("/proc/{pid}/fd"))?
+            .filter_map(Result::ok)
+            .filter_map(|entry| fs::read_link(entry.path()).ok())
+            .any(|target| target.as_os_str() == link.as_str());
+
+        Ok(holds)
+    }
+
+    #[cfg(test)]
+    mod test {
+        use std::{
+            net::{Ipv4Addr, TcpListener},
+            os::fd::OwnedFd,
+            process::{Child, Command, Stdio},
+            time::Duration,
+        };
+
+        use mirrord_intproxy_protocol::ProcessInfo;
+        use tokio::net::TcpStream;
+
+        use crate::process_watch::ProcessWatch;
+
+        fn process_info(pid: u32) -> ProcessInfo {
+            ProcessInfo {
+                pid: pid as i32,
+                parent_pid: std::process::id() as i32,
+                // Process-pool servers rename their processes.
+                name: "gunicorn".into(),
+                cmdline: vec!["gunicorn: master [app]".into()],
+                loaded: true,
+            }
+        }
+
+        /// Returns the layer's end of a new connection, and the proxy's end.
+        fn connection() -> (std::net::TcpStream, TcpStream) {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+            let layer = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            let (proxy, _) = listener.accept().unwrap();
+            proxy.set_nonblocking(true).unwrap();
+
+            (layer, TcpStream::from_std(proxy).unwrap())
+        }
+
+        /// Spawns a process that holds the layer's end of the connection as its stdin.
+        fn spawn_layer(layer: std::net::TcpStream) -> Child {
+            Command::new("sleep")
+                .arg("30")
+                .stdin(Stdio::from(OwnedFd::from(layer)))
+                .spawn()
+                .unwrap()
+        }
+
+        #[tokio::test]
+        async fn resolves_on_exit() {
+            let (layer, proxy) = connection();
+            let mut child = spawn_layer(layer);
+            let mut watch = ProcessWatch::new(&process_info(child.id()), &proxy);
+            assert!(watch.process.is_some());
+
+            child.kill().unwrap();
+            tokio::time::timeout(Duration::from_secs(5), watch.exited())
+                .await
+                .unwrap();
+            child.wait().unwrap();
+        }
+
+        /// The socket is closed on `exec`, the layer of the new image makes a new connection.
+        #[tokio::test]
+        async fn resolves_when_connection_is_closed() {
+            let (layer, proxy) = connection();
+            let mut watch = ProcessWatch::new(&process_info(std::process::id()), &proxy);
+            assert!(watch.process.is_some());
+
+            drop(layer);
+            tokio::time::timeout(Duration::from_secs(10), watch.exited())
+                .await
+                .unwrap();
+        }
+
+        #[tokio::test]
+        async fn ignores_different_process() {
+            let (_layer, proxy) = connection();
+            let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+            let watch = ProcessWatch::new(&process_info(child.id()), &proxy);
+            assert!(watch.process.is_none());
+
+            child.kill().unwrap();
+            child.wait().unwrap();
+        }
+    }
+}
@@ -9,7 +9,9 @@ use std::{
 use mirrord_intproxy_protocol::{LayerId, MessageId, ProxyToLayerMessage};
 use mirrord_protocol::{
     ClientMessage, DaemonMessage, ErrorKindInternal, FileRequest, FileResponse, RemoteIOError,
-    ResponseError, file::*,
+    ResponseError,
+    cancel::{CANCEL_VERSION, CancelRequest},
+    file::*,
 };
 use semver::Version;
 use thiserror::Error;
@@ -62,6 +64,10 @@ impl From<AgentLostFileResponse> for ToLayer {
             FileResponse::Futimens(..) => FileResponse::Futimens(Err(error)),
             FileResponse::Fchown(..) => FileResponse::Fchown(Err(error)),
             FileResponse::Fchmod(..) => FileResponse::Fchmod(Err(error)),
+            FileResponse::Fsync(..) => FileResponse::Fsync(Err(error)),
+            FileResponse::FileLock(..) => FileResponse::FileLock(Err(error)),
+            FileResponse::GetCwd(..) => FileResponse::GetCwd(Err(error)),
+            FileResponse::ReadAhead(..) => FileResponse::ReadAhead(Err(error)),
         };
 
         debug_assert_eq!(
@@ -95,7 +101,7 @@ impl FileRequestExt for FileRequest {
         message_id: MessageId,
     ) -> Option<AgentLostFileResponse> {
         let response = match self {
-            Self::Close(..) | Self::CloseDir(..) => return None,
+            Self::Close(..) | Self::CloseDir(..) | Self::ReadAhead(..) => return None,
             Self::Access(..) => dummy_file_response!(Access),
             Self::FdOpenDir(..) => dummy_file_response!(OpenDir),
             Self::GetDEnts64(..) => dummy_file_response!(GetDEnts64),
@@ -124,6 +130,9 @@ impl FileRequestExt for FileRequest {
             Self::Futimens(..) => dummy_file_response!(Futimens),
             Self::Fchown(..) => dummy_file_response!(Fchown),
             Self::Fchmod(..) => dummy_file_response!(Fchmod),
+            Self::Fsync(..) => dummy_file_response!(Fsync),
+            Self::FileLock(..) => dummy_file_response!(FileLock),
+            Self::GetCwd(..) => dummy_file_response!(GetCwd),
         };
 
         Some(AgentLostFileResponse(layer_id, message_id, response))
@@ -164,6 +173,8 @@ struct BufferedFileData {
     /// but for buffered files we manage it here.
     /// It's simpler this way.
     fd_position: u64,
+    /// State of prefetching this file.
+    read_ahead: ReadAheadState,
 }
 
 impl BufferedFileData {
@@ -175,6 +186,78 @@ impl BufferedFileData {
         let end_before = start_from + amount as usize;
         self.buffer.get(start_from..end_before)
     }
+
+    /// Position in the file right after [`Self::buffer`].
+    fn buffer_end(&self) -> u64 {
+        self.buffer_position + self.buffer.len() as u64
+    }
+
+    /// Whether the data at `position` in the file is expected to come with the
+    /// [`FileRequest::ReadAhead`]s in progress.
+    fn will_receive(&self, position: u64) -> bool {
+        self.read_ahead.in_progress > 0
+            && (self.buffer_position..self.read_ahead.requested_until).contains(&position)
+    }
+
+    /// Attempts to serve a read of the user application from [`Self::buffer`].
+    ///
+    /// `start_from` is [`None`] for [`FileRequest::Read`], which reads from [`Self::fd_position`]
+    /// and advances it. When the buffer reaches the end of the file, the read can be served
+    /// partially.
+    fn serve_read(&mut self, amount: u64, start_from: Option<u64>) -> Option<FileResponse> {
+        let position = start_from.unwrap_or(self.fd_position);
+        let bytes = match self.read_from_buffer(amount, position) {
+            Some(bytes) => bytes,
+            None if self.read_ahead.eof => self
+                .buffer
+                .get(position.checked_sub(self.buffer_position)? as usize..)?,
+            None => return None,
+        }
+        .to_vec();
+
+        let response = ReadFileResponse {
+            read_amount: bytes.len() as u64,
+            bytes: bytes.into(),
+        };
+        self.read_ahead.last_read_end = position + response.read_amount;
+
+        let response = match start_from {
+            Some(..) => FileResponse::ReadLimited(Ok(response)),
+            None => {
+                self.fd_position += response.read_amount;
+                FileResponse::Read(Ok(response))
+            }
+        };
+
+        Some(response)
+    }
+
+    /// Appends a [`ReadAheadChunk`] to [`Self::buffer`]. If the buffer would exceed `budget` bytes,
+    /// drops the data that the user application already consumed.
+    ///
+    /// Chunks that do not continue the buffer (e.g. when it was replaced after a seek) are
+    /// ignored.
+    fn append_chunk(&mut self, chunk: ReadAheadChunk, budget: u64) {
+        let Some(overlap) = self.buffer_end().checked_sub(chunk.start_from) else {
+            return;
+        };
+        let Some(new_bytes) = chunk.bytes.get(overlap as usize..) else {
+            return;
+        };
+
+        if (self.buffer.len() + new_bytes.len()) as u64 > budget {
+            let consumed = self
+                .read_ahead
+                .last_read_end
+                .saturating_sub(self.buffer_position)
+                .min(self.buffer.len() as u64);
+            self.buffer.drain(..consumed as usize);
+            self.buffer_position += consumed;
+        }
+
+        self.buffer.extend_from_slice(new_bytes);
+        self.read_ahead.eof |= chunk.eof;
+    }
 }
 
 impl fmt::Debug for BufferedFileData {
@@ -183,10 +266,40 @@ impl fmt::Debug for BufferedFileData {
             .field("buffer_position", &self.buffer_position)
             .field("buffer_len", &self.buffer.len())
             .field("fd_position", &self.fd_position)
+            .field("read_ahead", &self.read_ahead)
             .finish()
     }
 }
 
+/// Prefetching of a buffered file, see the [`FilesProxy`] docs.
+#[derive(Debug, Default)]
+struct ReadAheadState {
+    /// Whether the user application reads the file sequentially.
+    ///
+    /// Set when the application reads right past [`BufferedFileData::buffer`], reset on seek.
+    sequential: bool,
+    /// End of the last read served to the user application.
+    last_read_end: u64,
+    /// Number of [`FileRequest::ReadAhead`]s in progress.
+    in_progress: usize,
+    /// End of the data requested with the [`FileRequest::ReadAhead`]s in progress.
+    requested_until: u64,
+    /// Whether [`BufferedFileData::buffer`] reaches the end of the file.
+    eof: bool,
+    /// Reads of the user application that wait for the [`FileRequest::ReadAhead`]s in progress.
+    waiting: VecDeque<WaitingRead>,
+}
+
+/// Read of the user application that waits for the data prefetched from the agent.
+#[derive(Debug)]
+struct WaitingRead {
+    message_id: MessageId,
+    layer_id: LayerId,
+    amount: u64,
+    /// [`None`] for [`FileRequest::Read`].
+    start_from: Option<u64>,
+}
+
 /// Locally cached data of a remote directory that is buffered.
 #[derive(Default)]
 struct BufferedDirData {
@@ -216,6 +329,8 @@ enum AdditionalRequestData {
         /// Read buffer size of the user application.
         /// The user requested reading this many bytes.
         requested_amount: u64,
+        /// Position of the read in the file.
+        position: u64,
         /// Whether we should update fd position in file
         /// (we store it locally).
         update_fd_position: bool,
@@ -227,6 +342,13 @@ enum AdditionalRequestData {
         fd: u64,
     },
 
+    /// Prefetch file that is buffered.
+    /// The agent sends multiple responses to this request.
+    ReadAhead {
+        /// File descriptor.
+        fd: u64,
+    },
+
     /// All other file ops.
     #[default]
     Other,
@@ -283,12 +405,14 @@ impl RouterFileOps {
             | FileRequest::StatFs(..)
             | FileRequest::StatFsV2(..)
             | FileRequest::Rename(..)
+            | FileRequest::GetCwd(..)
             | FileRequest::UnlinkAt(UnlinkAtRequest { dirfd: None, .. }) => {}
 
-            // These requests do not require any response from the agent.
-            // We need to remap the fd, but if the fd is invalid we simply drop them.
+            // These requests do not require any response from the agent (or no layer waits for
+            // the response). We need to remap the fd, but if the fd is invalid we simply drop them.
             FileRequest::Close(CloseFileRequest { fd: remote_fd })
-            | FileRequest::CloseDir(CloseDirRequest { remote_fd }) => {
+            | FileRequest::CloseDir(CloseDirRequest { remote_fd })
+            | FileRequest::ReadAhead(ReadAheadRequest { remote_fd, .. }) => {
                 if *remote_fd < self.current_fd_offset {
                     return Ok(None);
                 }
@@ -327,7 +451,9 @@ impl RouterFileOps {
             | FileRequest::Ftruncate(FtruncateRequest { fd: remote_fd, .. })
             | FileRequest::Futimens(FutimensRequest { fd: remote_fd, .. })
             | FileRequest::Fchown(FchownRequest { fd: remote_fd, .. })
-            | FileRequest::Fchmod(FchmodRequest { fd: remote_fd, .. }) => {
+            | FileRequest::Fchmod(FchmodRequest { fd: remote_fd, .. })
+            | FileRequest::Fsync(FsyncRequest { fd: remote_fd, .. })
+            | FileRequest::FileLock(FileLockRequest { fd: remote_fd, .. }) => {
                 if *remote_fd < self.current_fd_offset {
                     let error_response = request
                         .agent_lost_response(layer_id, message_id)
@@ -351,6 +477,10 @@ impl RouterFileOps {
     #[tracing::instrument(level = Level::TRACE, ret)]
     pub fn map_response(&mut self, mut response: FileResponse) -> FileResponse {
         match &mut response {
+            // Responses to the requests made by this proxy, we don't queue error responses for
+            // them.
+            FileResponse::ReadAhead(..) => return response,
+
             // These responses do not refer to any open remote fd.
             FileResponse::Access(..)
             | FileResponse::Read(..)
@@ -374,7 +504,10 @@ impl RouterFileOps {
             | FileResponse::Ftruncate(..)
             | FileResponse::Futimens(..)
             | FileResponse::Fchown(..)
-            | FileResponse::Fchmod(..) => {}
+            | FileResponse::Fchmod(..)
+            | FileResponse::Fsync(..)
+            | FileResponse::FileLock(..)
+            | FileResponse::GetCwd(..) => {}
 
             FileResponse::GetDEnts64(Ok(GetDEnts64Response { fd: remote_fd, .. }))
             | FileResponse::Open(Ok(OpenFileResponse { fd: remote_fd }))
@@ -425,6 +558,19 @@ impl RouterFileOps {
 ///    buffer. If it's not possible, we proceed as in point 1
 /// 4. To solve problems with descriptor offset, we only use [`FileRequest::ReadLimited`] to read
 ///    buffered files. Descriptor offset value is maintained in this proxy.
+///
+/// # Prefetching
+///
+/// When the user application reads a buffered file sequentially, it would still wait for the
+/// agent every `buffer_size` bytes. If the [`mirrord_protocol`] version allows for
+/// [`FileRequest::ReadAhead`], we prefetch such files instead.
+///
+/// 1. When the user requests a read that starts right after the local buffer, we consider the file
+///    to be read sequentially, and ask the agent to stream the next [`Self::READ_AHEAD_CHUNKS`]
+///    buffers. The read waits for the first chunk.
+/// 2. The chunks are appended to the local buffer, and serve the following reads.
+/// 3. When the user consumes half of the data we have or requested, we ask the agent for more. The
+///    data consumed by the user is dropped, so the buffer does not grow beyond the window.
 pub struct FilesProxy {
     /// [`mirrord_protocol`] version negotiated with the agent.
     /// Determines whether we can use some messages, like [`FileRequest::ReadDirBatch`] or
@@ -435,6 +581,9 @@ pub struct FilesProxy {
     /// If equal to 0, this proxy does not buffer files.
     file_buffer_size: u64,
 
+    /// How many bytes we prefetch at a time.
+    read_ahead_window: u64,
+
     /// Stores metadata of outstanding requests.
     request_queue: RequestQueue<AdditionalRequestData>,
 
@@ -455,6 +604,7 @@ impl fmt::Debug for FilesProxy {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("FilesProxy")
             .field("file_buffer_size", &self.file_buffer_size)
+            .field("read_ahead_window", &self.read_ahead_window)
             .field("buffer_readdir", &self.buffer_dirs())
             .field("buffered_files", &self.buffered_files)
             .field("buffered_dirs", &self.buffered_dirs)
@@ -470,6 +620,10 @@ impl FilesProxy {
     /// Relevant only if [`mirrord_protocol`] version allows for [`FileRequest::ReadDirBatch`].
     pub const READDIR_BATCH_SIZE: usize = 128;
 
+    /// How many buffers we prefetch at a time.
+    /// Relevant only if [`mirrord_protocol`] version allows for [`FileRequest::ReadAhead`].
+    pub const READ_AHEAD_CHUNKS: u64 = 16;
+
     /// Creates a new files proxy instance.
     /// Proxy can be used as a [`BackgroundTask`].
     ///
@@ -479,6 +633,9 @@ impl FilesProxy {
         Self {
             protocol_version: Default::default(),
             file_buffer_size,
+            read_ahead_window: file_buffer_size
+                .saturating_mul(Self::READ_AHEAD_CHUNKS)
+                .min(READ_AHEAD_MAX_WINDOW),
 
             request_queue: Default::default(),
 
@@ -504,6 +661,15 @@ impl FilesProxy {
         self.file_buffer_size > 0
     }
 
+    /// Returns whether [`mirrord_protocol`] version allows for prefetching buffered files.
+    fn read_ahead_enabled(&self) -> bool {
+        self.read_ahead_window > 0
+            && self
+                .protocol_version
+                .as_ref()
+                .is_some_and(|version| READ_AHEAD_VERSION.matches(version))
+    }
+
     #[tracing::instrument(level = Level::TRACE)]
     fn layer_forked(&mut self, forked: LayerForked) {
         self.remote_files.clone_all(forked.parent, forked.child);
@@ -513,7 +679,7 @@ impl FilesProxy {
     #[tracing::instrument(level = Level::TRACE, skip(message_bus))]
     async fn layer_closed(&mut self, closed: LayerClosed, message_bus: &mut MessageBus<Self>) {
         for fd in self.remote_files.remove_all(closed.id) {
-            self.buffered_files.remove(&fd);
+            self.remove_buffered_file(fd, message_bus).await;
             message_bus
                 .send_agent(ClientMessage::FileRequest(FileRequest::Close(
                     CloseFileRequest { fd },
@@ -579,6 +745,24 @@ impl FilesProxy {
             {
                 Err(FileResponse::Rename(Err(ResponseError::NotImplemented)))
             }
+            FileRequest::Fsync(..)
+                if protocol_version
+                    .is_none_or(|version: &Version| FSYNC_VERSION.matches(version).not()) =>
+            {
+                Err(FileResponse::Fsync(Err(ResponseError::NotImplemented)))
+            }
+            FileRequest::FileLock(..)
+                if protocol_version
+                    .is_none_or(|version: &Version| FILE_LOCK_VERSION.matches(version).not()) =>
+            {
+                Err(FileResponse::FileLock(Err(ResponseError::NotImplemented)))
+            }
+            FileRequest::GetCwd(..)
+                if protocol_version
+                    .is_none_or(|version: &Version| GET_CWD_VERSION.matches(version).not()) =>
+            {
+                Err(FileResponse::GetCwd(Err(ResponseError::NotImplemented)))
+            }
             _ => Ok(()),
         }
     }
@@ -614,7 +798,7 @@ impl FilesProxy {
             // Should trigger remote close only when the fd is closed in all layer instances.
             FileRequest::Close(close) => {
                 if self.remote_files.remove(layer_id, close.fd) {
-                    self.buffered_files.remove(&close.fd);
+                    self.remove_buffered_file(close.fd, message_bus).await;
                     message_bus
                         .send_agent(ClientMessage::FileRequest(FileRequest::Close(close)))
                         .await;
@@ -660,113 +844,46 @@ impl FilesProxy {
             }
 
             // Try to use local buffer if possible.
-            FileRequest::Read(read) => match self.buffered_files.get_mut(&read.remote_fd) {
-                // File is buffered.
-                Some(data) => {
-                    let from_buffer = data.read_from_buffer(read.buffer_size, data.fd_position);
-                    if let Some(from_buffer) = from_buffer {
-                        let bytes = from_buffer.to_vec();
-                        data.fd_position += read.buffer_size;
-                        message_bus
-                            .send(ToLayer {
-                                message_id,
-                                layer_id,
-                                message: ProxyToLayerMessage::File(FileResponse::Read(Ok(
-                                    ReadFileResponse {
-                                        bytes: bytes.into(),
-                                        read_amount: read.buffer_size,
-                                    },
-                                ))),
-                            })
-                            .await;
-                    } else {
-                        let additional_data = AdditionalRequestData::ReadBuffered {
-                            fd: read.remote_fd,
-                            requested_amount: read.buffer_size,
-                            update_fd_position: true,
-                        };
-                        self.request_queue.push_back_with_data(
-                            message_id,
-                            layer_id,
-                            additional_data,
-                        );
-                        message_bus
-                            .send_agent(ClientMessage::FileRequest(FileRequest::ReadLimited(
-                                ReadLimitedFileRequest {
-                                    remote_fd: read.remote_fd,
-                                    buffer_size: std::cmp::max(
-                                        read.buffer_size,
-                                        self.file_buffer_size,
-                                    ),
-                                    start_from: data.fd_position,
-                                },
-                            )))
-                            .await;
-                    }
-                }
+            FileRequest::Read(read) if self.buffered_files.contains_key(&read.remote_fd) => {
+                self.read_buffered(
+                    read.remote_fd,
+                    read.buffer_size,
+                    None,
+                    layer_id,
+                    message_id,
+                    message_bus,
+                )
+                .await;
+            }
 
-                // File is not buffered.
-                None => {
-                    self.request_queue.push_back(message_id, layer_id);
-                    message_bus
-                        .send_agent(ClientMessage::FileRequest(FileRequest::Read(read)))
-                        .await;
-                }
-            },
+            // File is not buffered.
+            FileRequest::Read(read) => {
+                self.request_queue.push_back(message_id, layer_id);
+                message_bus
+                    .send_agent(ClientMessage::FileRequest(FileRequest::Read(read)))
+                    .await;
+            }
 
             // Try to use local buffer if possible.
-            FileRequest::ReadLimited(read) => match self.buffered_files.get_mut(&read.remote_fd) {
-                // File is buffered.
-                Some(data) => {
-                    let from_buffer = data.read_from_buffer(read.buffer_size, read.start_from);
-                    if let Some(from_buffer) = from_buffer {
-                        let bytes = from_buffer.to_vec();
-                        message_bus
-                            .send(ToLayer {
-                                message_id,
-                                layer_id,
-                                message: ProxyToLayerMessage::File(FileResponse::ReadLimited(Ok(
-                                    ReadFileResponse {
-                                        bytes: bytes.into(),
-                                        read_amount: read.buffer_size,
-                                    },
-                                ))),
-                            })
-                            .await;
-                    } else {
-                        let additional_data = AdditionalRequestData::ReadBuffered {
-                            fd: read.remote_fd,
-                            requested_amount: read.buffer_size,
-                            update_fd_position: false,
-                        };
-                        self.request_queue.push_back_with_data(
-                            message_id,
-                            layer_id,
-                            additional_data,
-                        );
-                        message_bus
-                            .send_agent(ClientMessage::FileRequest(FileRequest::ReadLimited(
-                                ReadLimitedFileRequest {
-                                    remote_fd: read.remote_fd,
-                                    buffer_size: std::cmp::max(
-                                        read.buffer_size,
-                                        self.file_buffer_size,
-                                    ),
-                                    start_from: read.start_from,
-                                },
-                            )))
-                            .await;
-                    }
-                }
+            FileRequest::ReadLimited(read) if self.buffered_files.contains_key(&read.remote_fd) => {
+                self.read_buffered(
+                    read.remote_fd,
+                    read.buffer_size,
+                    Some(read.start_from),
+                    layer_id,
+                    message_id,
+                    message_bus,
+                )
+                .await;
+            }
 
-                // File is not buffered.
-                None => {
-                    self.request_queue.push_back(message_id, layer_id);
-                    message_bus
-                        .send_agent(ClientMessage::FileRequest(FileRequest::ReadLimited(read)))
-                        .await;
-                }
-            },
+            // File is not buffered.
+            FileRequest::ReadLimited(read) => {
+                self.request_queue.push_back(message_id, layer_id);
+                message_bus
+                    .send_agent(ClientMessage::FileRequest(FileRequest::ReadLimited(read)))
+                    .await;
+            }
 
             // Try to use local buffer if possible.
             FileRequest::ReadDir(read_dir) => match self.buffered_dirs.get_mut(&read_dir.remote_fd)
@@ -812,6 +929,11 @@ impl FilesProxy {
                 unreachable!("ReadDirBatch request is never sent from the layer");
             }
 
+            // Should only be sent from intproxy, not from the layer.
+            FileRequest::ReadAhead(..) => {
+                unreachable!("ReadAhead request is never sent from the layer");
+            }
+
             // May require storing additional data in the request queue.
             FileRequest::Seek(mut seek) => {
                 let additional_data =
@@ -952,6 +1074,7 @@ impl FilesProxy {
                 let AdditionalRequestData::ReadBuffered {
                     fd,
                     requested_amount,
+                    position,
                     update_fd_position,
                 } = additional_data
                 else {
@@ -992,7 +1115,9 @@ impl FilesProxy {
                 };
 
                 data.buffer = read.bytes.into_vec();
-                data.buffer_position = data.fd_position;
+                data.buffer_position = position;
+                data.read_ahead.eof = false;
+                data.read_ahead.last_read_end = position + read_amount;
                 let message = if update_fd_position {
                     // User originally sent `FileRequest::Read`.
                     data.fd_position += response.read_amount;
@@ -1063,6 +1188,7 @@ impl FilesProxy {
                     };
 
                     data.fd_position = seek.result_offset;
+                    data.read_ahead.sequential = false;
                 }
 
                 message_bus
@@ -1074,6 +1200,47 @@ impl FilesProxy {
                     .await;
             }
 
+            // Store prefetched data in `files_data`.
+            // The agent sends multiple responses to one `FileRequest::ReadAhead`.
+            FileResponse::ReadAhead(result) => {
+                let (message_id, layer_id, fd) = match self.request_queue.front() {
+                    Some((message_id, layer_id, AdditionalRequestData::ReadAhead { fd })) => {
+                        (message_id, layer_id, *fd)
+                    }
+                    _ => {
+                        return Err(UnexpectedAgentMessage(
+                            DaemonMessage::File(FileResponse::ReadAhead(result)).into(),
+                        )
+                        .into());
+                    }
+                };
+
+                let finished = matches!(result, Ok(ReadAheadChunk { last: false, .. })).not();
+                if finished {
+                    self.request_queue.pop_front();
+                }
+
+                let budget = self.read_ahead_window + self.file_buffer_size;
+                let Some(data) = self.buffered_files.get_mut(&fd) else {
+                    // File must have been closed from other thread in user application.
+                    return Ok(());
+                };
+
+                match result {
+                    Ok(chunk) => data.append_chunk(chunk, budget),
+                    Err(error) => {
+                        tracing::debug!(fd, ?error, "Failed to read ahead, stopping");
+                        data.read_ahead.sequential = false;
+                    }
+                }
+                if finished {
+                    data.read_ahead.in_progress = data.read_ahead.in_progress.saturating_sub(1);
+                }
+
+                self.serve_waiting_reads(fd, message_bus).await;
+                self.read_ahead(fd, layer_id, message_id, message_bus).await;
+            }
+
             // Store extra entries in `dirs_data`.
             FileResponse::ReadDirBatch(Ok(batch)) => {
                 let (message_id, layer_id) = self.request_queue.pop_front().ok_or_else(|| {
@@ -1146,6 +1313,206 @@ impl FilesProxy {
         Ok(())
     }
 
+    /// Handles [`FileRequest::Read`] (when `start_from` is [`None`]) or
+    /// [`FileRequest::ReadLimited`] on a buffered file.
+    async fn read_buffered(
+        &mut self,
+        fd: u64,
+        amount: u64,
+        start_from: Option<u64>,
+        layer_id: LayerId,
+        message_id: MessageId,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        let read_ahead_enabled = self.read_ahead_enabled();
+        let Some(data) = self.buffered_files.get_mut(&fd) else {
+            return;
+        };
+
+        // Reads that already wait must be served first.
+        if data.read_ahead.waiting.is_empty()
+            && let Some(response) = data.serve_read(amount, start_from)
+        {
+            message_bus
+                .send(ToLayer {
+                    message_id,
+                    layer_id,
+                    message: ProxyToLayerMessage::File(response),
+                })
+                .await;
+            self.read_ahead(fd, layer_id, message_id, message_bus).await;
+            return;
+        }
+
+        let position = start_from.unwrap_or(data.fd_position);
+        let read = WaitingRead {
+            message_id,
+            layer_id,
+            amount,
+            start_from,
+        };
+
+        if data.read_ahead.waiting.is_empty().not() || data.will_receive(position) {
+            data.read_ahead.waiting.push_back(read);
+            return;
+        }
+
+        // The user application consumed the whole buffer, and continues reading.
+        data.read_ahead.sequential = data.buffer.is_empty().not() && position == data.buffer_end();
+        if read_ahead_enabled && data.read_ahead.sequential {
+            data.read_ahead.last_read_end = position;
+            data.read_ahead.waiting.push_back(read);
+            self.read_ahead(fd, layer_id, message_id, message_bus).await;
+        } else {
+            self.fetch_read(fd, read, position, message_bus).await;
+        }
+    }
+
+    /// Fetches at least `buffer_size` bytes for the read from the agent, to replace the local
+    /// buffer of the file.
+    async fn fetch_read(
+        &mut self,
+        fd: u64,
+        read: WaitingRead,
+        position: u64,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        let additional_data = AdditionalRequestData::ReadBuffered {
+            fd,
+            requested_amount: read.amount,
+            position,
+            update_fd_position: read.start_from.is_none(),
+        };
+        self.request_queue
+            .push_back_with_data(read.message_id, read.layer_id, additional_data);
+        message_bus
+            .send_agent(ClientMessage::FileRequest(FileRequest::ReadLimited(
+                ReadLimitedFileRequest {
+                    remote_fd: fd,
+                    buffer_size: std::cmp::max(read.amount, self.file_buffer_size),
+                    start_from: position,
+                },
+            )))
+            .await;
+    }
+
+    /// Sends a [`FileRequest::ReadAhead`] for a sequentially read file, if the user application
+    /// consumed at least half of the data we have or requested.
+    async fn read_ahead(
+        &mut self,
+        fd: u64,
+        layer_id: LayerId,
+        message_id: MessageId,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        if self.read_ahead_enabled().not() {
+            return;
+        }
+
+        let Some(data) = self.buffered_files.get_mut(&fd) else {
+            return;
+        };
+        if data.read_ahead.sequential.not() || data.read_ahead.eof {
+            return;
+        }
+
+        let start_from = if data.read_ahead.in_progress > 0 {
+            data.read_ahead.requested_until
+        } else {
+            data.buffer_end()
+        };
+        if start_from.saturating_sub(data.read_ahead.last_read_end) > self.read_ahead_window / 2 {
+            return;
+        }
+
+        data.read_ahead.in_progress += 1;
+        data.read_ahead.requested_until = start_from + self.read_ahead_window;
+
+        self.request_queue.push_back_with_data(
+            message_id,
+            layer_id,
+            AdditionalRequestData::ReadAhead { fd },
+        );
+        message_bus
+            .send_agent(ClientMessage::FileRequest(FileRequest::ReadAhead(
+                ReadAheadRequest {
+                    remote_fd: fd,
+                    start_from,
+                    chunk_size: self.file_buffer_size,
+                    window: self.read_ahead_window,
+                },
+            )))
+            .await;
+    }
+
+    /// Serves the reads that wait for the prefetched data. When no more data is expected, the
+    /// remaining reads are sent to the agent.
+    async fn serve_waiting_reads(&mut self, fd: u64, message_bus: &mut MessageBus<Self>) {
+        loop {
+            let Some(data) = self.buffered_files.get_mut(&fd) else {
+                return;
+            };
+            let Some(read) = data.read_ahead.waiting.pop_front() else {
+                return;
+            };
+
+            if let Some(response) = data.serve_read(read.amount, read.start_from) {
+                message_bus
+                    .send(ToLayer {
+                        message_id: read.message_id,
+                        layer_id: read.layer_id,
+                        message: ProxyToLayerMessage::File(response),
+                    })
+                    .await;
+            } else if data.read_ahead.in_progress > 0 {
+                data.read_ahead.waiting.push_front(read);
+                return;
+            } else {
+                let position = read.start_from.unwrap_or(data.fd_position);
+                self.fetch_read(fd, read, position, message_bus).await;
+            }
+        }
+    }
+
+    /// Drops the local buffer of the file. Reads that wait for the prefetched data fail.
+    ///
+    /// If the file is still being prefetched, the [`FileRequest::ReadAhead`]s in progress are
+    /// cancelled, so that the agent does not send us the data that nobody reads.
+    async fn remove_buffered_file(&mut self, fd: u64, message_bus: &mut MessageBus<Self>) {
+        let Some(data) = self.buffered_files.remove(&fd) else {
+            return;
+        };
+
+        if data.read_ahead.in_progress > 0
+            && self
+                .protocol_version
+                .as_ref()
+                .is_some_and(|version| CANCEL_VERSION.matches(version))
+        {
+            message_bus
+                .send_agent(ClientMessage::Cancel(CancelRequest::ReadAhead {
+                    remote_fd: fd,
+                }))
+                .await;
+        }
+
+        for read in data.read_ahead.waiting {
+            let error = ResponseError::NotFound(fd);
+            let response = match read.start_from {
+                Some(..) => FileResponse::ReadLimited(Err(error)),
+                None => FileResponse::Read(Err(error)),
+            };
+
+            message_bus
+                .send(ToLayer {
+                    message_id: read.message_id,
+                    layer_id: read.layer_id,
+                    message: ProxyToLayerMessage::File(response),
+                })
+                .await;
+        }
+    }
+
     #[tracing::instrument(level = Level::INFO, skip(message_bus), ret)]
     async fn handle_reconnect(
         &mut self,
@@ -1246,11 +1613,13 @@ mod tests {
     use mirrord_intproxy_protocol::{LayerId, ProxyToLayerMessage};
     use mirrord_protocol::{
         ClientMessage, ErrorKindInternal, FileRequest, FileResponse, RemoteIOError, ResponseError,
+        cancel::CancelRequest,
         file::{
-            FdOpenDirRequest, OpenDirResponse, OpenFileRequest, OpenFileResponse,
-            OpenOptionsInternal, ReadDirBatchRequest, ReadDirBatchResponse, ReadDirRequest,
-            ReadDirResponse, ReadFileRequest, ReadFileResponse, ReadLimitedFileRequest,
-            SeekFileRequest, SeekFileResponse, SeekFromInternal,
+            CloseFileRequest, FdOpenDirRequest, OpenDirResponse, OpenFileRequest, OpenFileResponse,
+            OpenOptionsInternal, ReadAheadChunk, ReadAheadRequest, ReadDirBatchRequest,
+            ReadDirBatchResponse, ReadDirRequest, ReadDirResponse, ReadFileRequest,
+            ReadFileResponse, ReadLimitedFileRequest, SeekFileRequest, SeekFileResponse,
+            SeekFromInternal,
         },
     };
     use mirrord_protocol_io::{Client, Connection, ConnectionOutput};
@@ -1870,6 +2239,150 @@ mod tests {
         assert_eq!(update, ProxyToLayerMessage::File(seek_response),);
     }
 
+    #[tokio::test]
+    async fn reading_ahead_sequential_file() {
+        let (proxy, mut tasks, out) = setup_proxy(mirrord_protocol::VERSION.clone(), 4096).await;
+
+        let fd = open_file(&proxy, &mut tasks, &out, true).await;
+        let contents = std::iter::repeat(0_u8..=255).flatten();
+
+        let update = make_read_request(&proxy, &mut tasks, &out, fd, 4096, None)
+            .await
+            .unwrap_left();
+        assert_eq!(
+            update,
+            ClientMessage::FileRequest(FileRequest::ReadLimited(ReadLimitedFileRequest {
+                remote_fd: fd,
+                buffer_size: 4096,
+                start_from: 0,
+            })),
+        );
+        let data = contents.clone().take(4096).collect::<Vec<_>>();
+        respond_to_read_request(&proxy, &mut tasks, data, true).await;
+
+        // The buffer was consumed, so the next read waits for the prefetched data.
+        let update = make_read_request(&proxy, &mut tasks, &out, fd, 10, None)
+            .await
+            .unwrap_left();
+        assert_eq!(
+            update,
+            ClientMessage::FileRequest(FileRequest::ReadAhead(ReadAheadRequest {
+                remote_fd: fd,
+                start_from: 4096,
+                chunk_size: 4096,
+                window: 4096 * FilesProxy::READ_AHEAD_CHUNKS,
+            })),
+        );
+
+        let chunk = ReadAheadChunk {
+            start_from: 4096,
+            bytes: contents
+                .clone()
+                .skip(4096)
+                .take(4096)
+                .collect::<Vec<_>>()
+                .into(),
+            eof: false,
+            last: false,
+        };
+        proxy
+            .send(FilesProxyMessage::FileRes(FileResponse::ReadAhead(Ok(
+                chunk,
+            ))))
+            .await;
+        let update = tasks
+            .next()
+            .await
+            .unwrap()
+            .1
+            .unwrap_message()
+            .unwrap_proxy_to_layer_message();
+        assert_eq!(
+            update,
+            ProxyToLayerMessage::File(FileResponse::Read(Ok(ReadFileResponse {
+                bytes: contents
+                    .clone()
+                    .skip(4096)
+                    .take(10)
+                    .collect::<Vec<_>>()
+                    .into(),
+                read_amount: 10,
+            }))),
+        );
+
+        let chunk = ReadAheadChunk {
+            start_from: 8192,
+            bytes: contents
+                .clone()
+                .skip(8192)
+                .take(10)
+                .collect::<Vec<_>>()
+                .into(),
+            eof: true,
+            last: true,
+        };
+        proxy
+            .send(FilesProxyMessage::FileRes(FileResponse::ReadAhead(Ok(
+                chunk,
+            ))))
+            .await;
+
+        // The rest of the file is buffered, so the read is served partially.
+        let update = make_read_request(&proxy, &mut tasks, &out, fd, 8192, None)
+            .await
+            .unwrap_right()
+            .unwrap_proxy_to_layer_message();
+        assert_eq!(
+            update,
+            ProxyToLayerMessage::File(FileResponse::Read(Ok(ReadFileResponse {
+                bytes: contents.skip(4106).take(4096).collect::<Vec<_>>().into(),
+                read_amount: 4096,
+            }))),
+        );
+    }
+
+    /// Verifies that closing a file that is being prefetched cancels the prefetching.
+    #[tokio::test]
+    async fn cancelling_read_ahead_on_close() {
+        let (proxy, mut tasks, out) = setup_proxy(mirrord_protocol::VERSION.clone(), 4096).await;
+
+        let fd = open_file(&proxy, &mut tasks, &out, true).await;
+
+        make_read_request(&proxy, &mut tasks, &out, fd, 4096, None)
+            .await
+            .unwrap_left();
+        respond_to_read_request(&proxy, &mut tasks, vec![0; 4096], true).await;
+
+        let update = make_read_request(&proxy, &mut tasks, &out, fd, 10, None)
+            .await
+            .unwrap_left();
+        assert!(matches!(
+            update,
+            ClientMessage::FileRequest(FileRequest::ReadAhead(..))
+        ));
+
+        proxy
+            .send(FilesProxyMessage::FileReq(
+                rand::random(),
+                LayerId(0),
+                FileRequest::Close(CloseFileRequest { fd }),
+            ))
+            .await;
+
+        assert_eq!(
+            out.next().await,
+            Some(ClientMessage::Cancel(CancelRequest::ReadAhead {
+                remote_fd: fd
+            })),
+        );
+        assert_eq!(
+            out.next().await,
+            Some(ClientMessage::FileRequest(FileRequest::Close(
+                CloseFileRequest { fd }
+            ))),
+        );
+    }
+
     #[tokio::test]
     async fn reading_from_dir() {
         // relevant ticket: MBE-717: intproxy crashes when attempting to `cat` a remote dir
@@ -15,34 +15,51 @@ use std::{
     time::Duration,
 };
 
+use backlog::Backlogs;
 use bound_socket::BoundTcpSocket;
 use futures::future::Either;
 use http::{ClientStore, ResponseMode, StreamingBody};
-use http_gateway::HttpGatewayTask;
+use http_gateway::{HttpGatewayTask, InFlightSlot};
+use local_app_wait::LocalAppWait;
 use metadata_store::MetadataStore;
-use mirrord_config::feature::network::incoming::tls_delivery::LocalTlsDelivery;
+use mirrord_config::feature::network::incoming::{
+    backlog::BacklogConfig,
+    replicas::ReplicasConfig,
+    request_limit::{RequestLimitAction, RequestLimitConfig},
+    tee::TeeConfig,
+    tls_delivery::LocalTlsDelivery,
+    wait_for_local_app::WaitForLocalAppConfig,
+};
 use mirrord_intproxy_protocol::{
     ConnMetadataRequest, ConnMetadataResponse, IncomingRequest, IncomingResponse, LayerId,
     MessageId, PortSubscription, ProxyToLayerMessage,
 };
 use mirrord_protocol::{
-    ClientMessage, ConnectionId, RequestId, ResponseError,
+    ClientMessage, ConnectionId, DisabledFeatures, RequestId, ResponseError,
+    cancel::{CANCEL_VERSION, CancelRequest},
+    experimental::ExperimentalFeature,
+    redact::redactor,
+    shutdown::GoingDown,
     tcp::{
         ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestErrorV1, ChunkedRequestErrorV2,
-        DaemonTcp, HttpRequest, HttpRequestMetadata, IncomingTrafficTransportType,
-        InternalHttpBodyFrame, InternalHttpRequest, LayerTcp, LayerTcpSteal, NewTcpConnectionV1,
-        NewTcpConnectionV2,
+        DaemonTcp, HttpFilter, HttpRequest, HttpRequestMetadata, IncomingTrafficTransportType,
+        InternalHttpBodyFrame, InternalHttpRequest, LayerTcp, LayerTcpSteal, MIRROR_LIMITS_VERSION,
+        MirrorLimits, NewTcpConnectionV1, NewTcpConnectionV2, PortSubscribeWithLimits,
+        PortSubscribeWithSources, STEAL_SOURCE_FILTER_VERSION, SourceFilter, TCP_BACKLOG_VERSION,
+        TCP_SHUTDOWN_WRITE_VERSION, TcpBacklogFull, declare_trailers,
     },
 };
+use replicas::Replicas;
 use semver::Version;
 use tasks::{HttpGatewayId, HttpOut, InProxyTask, InProxyTaskError, InProxyTaskMessage};
 use tcp_proxy::{LocalTcpConnection, TcpProxyTask};
+use tee::RequestTee;
 use thiserror::Error;
 use tls::LocalTlsSetup;
-use tokio::sync::mpsc;
+use tokio::sync::{Semaphore, mpsc};
 use tracing::Level;
 
-use self::subscriptions::SubscriptionsManager;
+use self::{port_subscription_ext::PortSubscriptionExt, subscriptions::SubscriptionsManager};
 use crate::{
     ProxyMessage,
     background_tasks::{
@@ -51,14 +68,18 @@ use crate::{
     main_tasks::{ConnectionRefresh, LayerClosed, LayerForked, ToLayer},
 };
 
+mod backlog;
 mod bound_socket;
 pub mod http;
 mod http_gateway;
+mod local_app_wait;
 mod metadata_store;
 mod port_subscription_ext;
+mod replicas;
 mod subscriptions;
 pub mod tasks;
 mod tcp_proxy;
+mod tee;
 #[cfg(test)]
 mod tests;
 pub mod tls;
@@ -116,9 +137,22 @@ pub enum IncomingProxyMessage {
     AgentSteal(DaemonTcp),
     /// Agent responded to [`ClientMessage::SwitchProtocolVersion`].
     AgentProtocolVersion(semver::Version),
+    /// Agent disabled some features for this session.
+    AgentDisabledFeatures(DisabledFeatures),
+    /// Agent requires this HTTP filter in every steal subscription.
+    AgentMandatoryHttpFilter(HttpFilter),
+    /// Agent is shutting down, and waits for [`ClientMessage::GoingDownAck`].
+    AgentGoingDown(GoingDown),
     ConnectionRefresh(ConnectionRefresh),
 }
 
+/// [`RequestLimitConfig`] applied to stolen HTTP requests.
+struct RequestLimit {
+    /// Each running [`HttpGatewayTask`] holds one permit.
+    slots: Arc<Semaphore>,
+    when_exceeded: RequestLimitAction,
+}
+
 /// Handle to a running [`HttpGatewayTask`].
 struct HttpGatewayHandle {
     /// Only keeps the [`HttpGatewayTask`] alive.
@@ -205,15 +239,64 @@ pub struct IncomingProxy {
     protocol_version: Option<Version>,
 
     restore_subscriptions_on_protocol_version_switch: bool,
+
+    /// Whether the agent forbids stealing traffic, in which case steal subscriptions are turned
+    /// into mirror subscriptions.
+    steal_disabled: bool,
+
+    /// HTTP filter that the agent requires in every steal subscription, added to the
+    /// subscriptions of the layers.
+    mandatory_http_filter: Option<HttpFilter>,
+
+    /// Limits the number of stolen HTTP requests handled by the user application at the same
+    /// time.
+    request_limit: Option<RequestLimit>,
+
+    /// Distributes stolen connections and requests between the user application and its local
+    /// replicas.
+    replicas: Replicas,
+
+    /// Stolen connections not yet accepted by the user application.
+    backlogs: Backlogs,
+
+    /// Whether to add mirrord metadata headers to the HTTP requests, see
+    /// [`http::add_metadata_headers`].
+    metadata_headers: bool,
+
+    /// Limits of the mirror subscriptions, see [`Self::with_mirror_limits`].
+    mirror_limits: Option<MirrorLimits>,
+
+    /// Allows for delivering stolen traffic once the user application starts accepting
+    /// connections, instead of failing right away.
+    local_app_wait: Option<LocalAppWait>,
+
+    /// Source addresses of the stolen traffic, see [`Self::with_source_filter`].
+    source_filter: Option<SourceFilter>,
+
+    /// Appends the HTTP requests delivered to the user application to a capture file.
+    tee: Option<RequestTee>,
+
+    /// Whether the agent is shutting down, and we have not yet acknowledged it with
+    /// [`ClientMessage::GoingDownAck`], see [`Self::ack_going_down_when_drained`].
+    agent_going_down: bool,
 }
 
 impl IncomingProxy {
     /// Used when registering new tasks in the internal [`BackgroundTasks`] instance.
     const CHANNEL_SIZE: usize = 512;
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         idle_local_http_connection_timeout: Duration,
         https_delivery: LocalTlsDelivery,
+        request_limit: Option<RequestLimitConfig>,
+        replicas: Vec<ReplicasConfig>,
+        backlog: Option<BacklogConfig>,
+        metadata_headers: bool,
+        mirror_max_bytes_per_second: Option<u64>,
+        wait_for_local_app: Option<WaitForLocalAppConfig>,
+        source_filter: Option<SourceFilter>,
+        tee: Option<&TeeConfig>,
     ) -> Self {
         let tls_setup = LocalTlsSetup::from_config(https_delivery);
         Self {
@@ -230,13 +313,122 @@ impl IncomingProxy {
             tasks: None,
             protocol_version: None,
             restore_subscriptions_on_protocol_version_switch: false,
+            steal_disabled: false,
+            mandatory_http_filter: None,
+            request_limit: request_limit.map(|config| RequestLimit {
+                slots: Arc::new(Semaphore::new(config.max_in_flight)),
+                when_exceeded: config.when_exceeded,
+            }),
+            replicas: Replicas::new(replicas),
+            backlogs: Backlogs::new(backlog),
+            metadata_headers,
+            mirror_limits: mirror_max_bytes_per_second.map(|max_bytes_per_second| MirrorLimits {
+                max_bytes_per_second,
+            }),
+            local_app_wait: wait_for_local_app.map(LocalAppWait::new),
+            source_filter,
+            tee: tee.and_then(|config| {
+                RequestTee::new(config)
+                    .inspect_err(|error| {
+                        tracing::error!(
+                            %error,
+                            path = %config.path.display(),
+                            "Failed to open the capture file, HTTP requests will not be captured",
+                        )
+                    })
+                    .ok()
+            }),
+            agent_going_down: false,
+        }
+    }
+
+    /// Turns a mirror subscription into a [`LayerTcp::PortSubscribeWithLimits`], if
+    /// [`Self::mirror_limits`] are set and the agent supports them.
+    fn with_mirror_limits(&self, message: ClientMessage) -> ClientMessage {
+        let Some(limits) = self.mirror_limits else {
+            return message;
+        };
+
+        let (port, filter) = match message {
+            ClientMessage::Tcp(LayerTcp::PortSubscribe(port)) => (port, None),
+            ClientMessage::Tcp(LayerTcp::PortSubscribeFilteredHttp(port, filter)) => {
+                (port, Some(filter))
+            }
+            other => return other,
+        };
+
+        if self
+            .protocol_version
+            .as_ref()
+            .is_some_and(|version| MIRROR_LIMITS_VERSION.matches(version))
+            .not()
+        {
+            tracing::warn!(
+                protocol_version = ?self.protocol_version,
+                port,
+                "Negotiated mirrord-protocol version does not allow for limiting mirrored \
+                traffic. The limit will be ignored."
+            );
+
+            let message = match filter {
+                Some(filter) => LayerTcp::PortSubscribeFilteredHttp(port, filter),
+                None => LayerTcp::PortSubscribe(port),
+            };
+            return ClientMessage::Tcp(message);
+        }
+
+        ClientMessage::Tcp(LayerTcp::PortSubscribeWithLimits(PortSubscribeWithLimits {
+            port,
+            filter,
+            limits,
+        }))
+    }
+
+    /// Turns a steal subscription into a [`LayerTcpSteal::PortSubscribeWithSources`], if
+    /// [`Self::source_filter`] is set.
+    ///
+    /// The agent's support is verified by the CLI before the session starts, so we only log an
+    /// error if the negotiated version does not allow for it.
+    fn with_source_filter(&self, message: ClientMessage) -> ClientMessage {
+        let Some(sources) = self.source_filter.clone() else {
+            return message;
+        };
+
+        let ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(steal_type)) = message else {
+            return message;
+        };
+
+        if self
+            .protocol_version
+            .as_ref()
+            .is_some_and(|version| STEAL_SOURCE_FILTER_VERSION.matches(version))
+            .not()
+        {
+            tracing::error!(
+                protocol_version = ?self.protocol_version,
+                ?steal_type,
+                "Negotiated mirrord-protocol version does not allow for stealing based on the \
+                source address. Traffic from all addresses will be stolen."
+            );
+
+            return ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(steal_type));
         }
+
+        ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribeWithSources(
+            PortSubscribeWithSources {
+                steal_type,
+                sources,
+            },
+        ))
     }
 
     /// Starts a new [`HttpGatewayTask`] to handle the given request.
     ///
+    /// `metadata` is available only in [`ChunkedRequest::StartV2`].
+    ///
     /// If we don't have a [`PortSubscription`] for the port, the task is not started.
-    /// Instead, we respond immediately to the agent.
+    /// Instead, we respond immediately to the agent. The same happens when the
+    /// [`RequestLimit`] is exceeded and the request should be rejected.
     #[tracing::instrument(
         level = Level::DEBUG,
         skip(self, message_bus),
@@ -244,15 +436,31 @@ impl IncomingProxy {
     )]
     async fn start_http_gateway(
         &mut self,
-        request: HttpRequest<StreamingBody>,
+        mut request: HttpRequest<StreamingBody>,
+        metadata: Option<HttpRequestMetadata>,
         body_tx: Option<mpsc::Sender<InternalHttpBodyFrame>>,
         transport: IncomingTrafficTransportType,
         is_steal: bool,
         message_bus: &MessageBus<Self>,
     ) {
+        let correlation_id = metadata
+            .as_ref()
+            .and_then(HttpRequestMetadata::correlation_id);
+
+        if self.metadata_headers {
+            http::add_metadata_headers(
+                &mut request.internal_request.headers,
+                request.connection_id,
+                request.request_id,
+                metadata.as_ref().map(HttpRequestMetadata::destination),
+                correlation_id,
+            );
+        }
+
         tracing::info!(
-            full_headers = ?request.internal_request.headers,
+            full_headers = ?redactor().headers(&request.internal_request.headers),
             ?request,
+            correlation_id,
             is_steal,
             "Received an HTTP request from the agent",
         );
@@ -287,6 +495,40 @@ impl IncomingProxy {
             return;
         };
 
+        let slot = match self.request_limit.as_ref().filter(|_| is_steal) {
+            None => None,
+            Some(limit) => match limit.slots.clone().try_acquire_owned() {
+                Ok(permit) => Some(InFlightSlot::Taken(permit)),
+                Err(..) if limit.when_exceeded == RequestLimitAction::Queue => {
+                    tracing::debug!("Request limit exceeded, queueing the request");
+                    Some(InFlightSlot::Wait(limit.slots.clone()))
+                }
+                Err(..) => {
+                    tracing::warn!(
+                        connection_id = request.connection_id,
+                        request_id = request.request_id,
+                        port = request.port,
+                        "Request limit exceeded, rejecting the request"
+                    );
+
+                    let response = http::mirrord_unavailable_response(
+                        "too many requests in flight in the local application",
+                        request.version(),
+                        request.connection_id,
+                        request.request_id,
+                        request.port,
+                    );
+                    message_bus
+                        .send_agent(ClientMessage::TcpSteal(LayerTcpSteal::HttpResponse(
+                            response,
+                        )))
+                        .await;
+
+                    return;
+                }
+            },
+        };
+
         let connection_id = request.connection_id;
         let request_id = request.request_id;
         let id = HttpGatewayId {
@@ -295,9 +537,19 @@ impl IncomingProxy {
             port: request.port,
             version: request.version(),
         };
-        let server_addr = normalize_connection_address(subscription.listening_on);
+        let listening_on = if is_steal {
+            self.replicas
+                .next_address(request.port, subscription.listening_on)
+        } else {
+            subscription.listening_on
+        };
+        let server_addr = normalize_connection_address(listening_on);
         tracing::info!("Using server address {} for connection", server_addr);
 
+        if let Some(tee) = &self.tee {
+            tee.record(&request, is_steal);
+        }
+
         let tx = self.tasks.as_mut().unwrap().register(
             HttpGatewayTask::new(
                 request,
@@ -305,6 +557,8 @@ impl IncomingProxy {
                 is_steal.then_some(self.response_mode),
                 server_addr,
                 transport,
+                slot,
+                self.local_app_wait.clone().filter(|_| is_steal),
             ),
             if is_steal {
                 InProxyTask::StealHttpGateway(id)
@@ -323,7 +577,8 @@ impl IncomingProxy {
     /// Handles [`NewTcpConnectionV2`] message from the agent, starting a new [`TcpProxyTask`].
     ///
     /// If we don't have a [`PortSubscription`] for the port, the task is not started.
-    /// Instead, we respond immediately to the agent.
+    /// Instead, we respond immediately to the agent. The same happens when the backlog of stolen
+    /// connections on the port is full.
     #[tracing::instrument(level = Level::TRACE, skip(self, message_bus))]
     async fn handle_new_connection(
         &mut self,
@@ -368,14 +623,41 @@ impl IncomingProxy {
             return Ok(());
         };
 
-        let socket = BoundTcpSocket::bind_specified_or_localhost(subscription.listening_on.ip())
+        // The agent handles connections that overflow the backlog on its own, but some of them can
+        // still arrive before it learns that the backlog is full.
+        if is_steal && self.backlogs.is_full(destination_port) {
+            let overflowed = self.backlogs.overflow(destination_port);
+            tracing::debug!(
+                port = destination_port,
+                connection_id,
+                overflowed,
+                "Backlog of stolen connections is full, rejecting the connection.",
+            );
+
+            message_bus
+                .send_agent(ClientMessage::TcpSteal(
+                    LayerTcpSteal::ConnectionUnsubscribe(connection_id),
+                ))
+                .await;
+
+            return Ok(());
+        }
+
+        let listening_on = if is_steal {
+            self.replicas
+                .next_address(destination_port, subscription.listening_on)
+        } else {
+            subscription.listening_on
+        };
+
+        let socket = BoundTcpSocket::bind_specified_or_localhost(listening_on.ip())
             .map_err(IncomingProxyError::SocketSetupFailed)?;
 
-        let peer_address = normalize_connection_address(subscription.listening_on);
+        let peer_address = normalize_connection_address(listening_on);
 
         self.metadata_store.expect(
             ConnMetadataRequest {
-                listener_address: subscription.listening_on,
+                listener_address: listening_on,
                 peer_address: socket
                     .local_addr()
                     .map_err(IncomingProxyError::SocketSetupFailed)?,
@@ -392,6 +674,7 @@ impl IncomingProxy {
         } else {
             InProxyTask::MirrorTcpProxy(connection_id)
         };
+        let shutdown_write = self.supports_shutdown_write();
         let tx = self.tasks.as_mut().unwrap().register(
             TcpProxyTask::new(
                 connection_id,
@@ -400,8 +683,10 @@ impl IncomingProxy {
                     peer: peer_address,
                     transport,
                     tls_setup: self.tls_setup.clone(),
+                    wait: self.local_app_wait.clone().filter(|_| is_steal),
                 },
                 is_steal.not(),
+                shutdown_write,
             ),
             id,
             Self::CHANNEL_SIZE,
@@ -409,9 +694,32 @@ impl IncomingProxy {
 
         self.tcp_proxies.get_mut(is_steal).insert(connection_id, tx);
 
+        if is_steal {
+            if let Some(message) = self.backlogs.push(destination_port, connection_id) {
+                self.notify_backlog(message, message_bus).await;
+            }
+        }
+
         Ok(())
     }
 
+    /// Notifies the agent that the backlog of stolen connections on a port became full or is no
+    /// longer full.
+    ///
+    /// Does nothing if the agent does not support [`LayerTcpSteal::BacklogFull`], we reject the
+    /// overflowing connections ourselves anyway.
+    async fn notify_backlog(&self, message: TcpBacklogFull, message_bus: &MessageBus<Self>) {
+        let supported = self
+            .protocol_version
+            .as_ref()
+            .is_some_and(|version| TCP_BACKLOG_VERSION.matches(version));
+        if supported {
+            message_bus
+                .send_agent(ClientMessage::TcpSteal(LayerTcpSteal::BacklogFull(message)))
+                .await;
+        }
+    }
+
     /// Handles [`ChunkedRequest`] message from the agent.
     async fn handle_chunked_request(
         &mut self,
@@ -425,6 +733,7 @@ impl IncomingProxy {
                 let request = request.map_body(|frames| StreamingBody::new(body_rx, frames));
                 self.start_http_gateway(
                     request,
+                    None,
                     Some(body_tx),
                     IncomingTrafficTransportType::Tcp,
                     is_steal,
@@ -433,8 +742,18 @@ impl IncomingProxy {
                 .await;
             }
 
-            ChunkedRequest::StartV2(request) => {
+            ChunkedRequest::StartV2(mut request) => {
                 let (body, body_tx) = if request.request.body.is_last {
+                    // We have the whole body, so we can declare the trailers for HTTP/1 servers.
+                    declare_trailers(
+                        &mut request.request.headers,
+                        request
+                            .request
+                            .body
+                            .frames
+                            .iter()
+                            .filter_map(InternalHttpBodyFrame::trailers_ref),
+                    );
                     (StreamingBody::from(request.request.body.frames), None)
                 } else {
                     let (body_tx, body_rx) = mpsc::channel(128);
@@ -446,7 +765,8 @@ impl IncomingProxy {
 
                 let transport = request.transport;
 
-                let HttpRequestMetadata::V1 { destination, .. } = request.metadata;
+                let metadata = request.metadata;
+                let destination = metadata.destination();
                 let request = HttpRequest {
                     connection_id: request.connection_id,
                     request_id: request.request_id,
@@ -460,8 +780,15 @@ impl IncomingProxy {
                     port: destination.port(),
                 };
 
-                self.start_http_gateway(request, body_tx, transport, is_steal, message_bus)
-                    .await;
+                self.start_http_gateway(
+                    request,
+                    Some(metadata),
+                    body_tx,
+                    transport,
+                    is_steal,
+                    message_bus,
+                )
+                .await;
             }
 
             ChunkedRequest::Body(ChunkedRequestBodyV1 {
@@ -557,6 +884,39 @@ impl IncomingProxy {
         }
     }
 
+    /// Sends [`ClientMessage::GoingDownAck`] to the shutting down agent, once all stolen
+    /// connections and requests are finished.
+    async fn ack_going_down_when_drained(&mut self, message_bus: &mut MessageBus<Self>) {
+        if self.agent_going_down.not()
+            || self.tcp_proxies.steal.is_empty().not()
+            || self
+                .http_gateways
+                .steal
+                .values()
+                .any(|gateways| gateways.is_empty().not())
+        {
+            return;
+        }
+
+        tracing::info!("Finished the stolen traffic, acknowledging the agent shutdown");
+        message_bus.send_agent(ClientMessage::GoingDownAck).await;
+        self.agent_going_down = false;
+    }
+
+    /// Whether the agent supports [`ClientMessage::Cancel`].
+    fn supports_cancel(&self) -> bool {
+        self.protocol_version
+            .as_ref()
+            .is_some_and(|version| CANCEL_VERSION.matches(version))
+    }
+
+    /// Whether the agent supports [`LayerTcpSteal::ShutdownWrite`].
+    fn supports_shutdown_write(&self) -> bool {
+        self.protocol_version
+            .as_ref()
+            .is_some_and(|version| TCP_SHUTDOWN_WRITE_VERSION.matches(version))
+    }
+
     /// Handles all agent messages.
     async fn handle_agent_message(
         &mut self,
@@ -589,10 +949,18 @@ impl IncomingProxy {
                 }
             }
 
+            DaemonTcp::ShutdownWrite(shutdown) => {
+                // Empty data shuts down the writing half of the local connection.
+                if let Some(tx) = self.tcp_proxies.get(is_steal).get(&shutdown.connection_id) {
+                    tx.send(Vec::new()).await;
+                }
+            }
+
             DaemonTcp::HttpRequest(request) => {
                 self.start_http_gateway(
                     request.map_body(From::from),
                     None,
+                    None,
                     IncomingTrafficTransportType::Tcp,
                     is_steal,
                     message_bus,
@@ -600,10 +968,20 @@ impl IncomingProxy {
                 .await;
             }
 
-            DaemonTcp::HttpRequestFramed(request) => {
+            DaemonTcp::HttpRequestFramed(mut request) => {
+                declare_trailers(
+                    &mut request.internal_request.headers,
+                    request
+                        .internal_request
+                        .body
+                        .0
+                        .iter()
+                        .filter_map(InternalHttpBodyFrame::trailers_ref),
+                );
                 self.start_http_gateway(
                     request.map_body(From::from),
                     None,
+                    None,
                     IncomingTrafficTransportType::Tcp,
                     is_steal,
                     message_bus,
@@ -633,6 +1011,17 @@ impl IncomingProxy {
                     .await?;
             }
 
+            DaemonTcp::MirrorDropped(dropped) => {
+                tracing::warn!(
+                    port = dropped.port,
+                    connections = dropped.connections,
+                    bytes = dropped.bytes,
+                    "The mirrord agent dropped mirrored traffic that exceeded \
+                    `feature.network.incoming.mirror_max_bytes_per_second`, \
+                    the local application missed some of the remote traffic"
+                );
+            }
+
             DaemonTcp::SubscribeResult(result) => {
                 let msgs = self.subscriptions.agent_responded(result)?;
 
@@ -654,7 +1043,21 @@ impl IncomingProxy {
     ) -> Result<(), IncomingProxyError> {
         match message {
             IncomingProxyMessage::LayerRequest(message_id, layer_id, req) => match req {
-                IncomingRequest::PortSubscribe(subscribe) => {
+                IncomingRequest::PortSubscribe(mut subscribe) => {
+                    if self.steal_disabled
+                        && matches!(subscribe.subscription, PortSubscription::Steal(..))
+                    {
+                        tracing::warn!(
+                            ?subscribe,
+                            "The mirrord agent does not allow stealing traffic, \
+                            mirroring instead"
+                        );
+                        subscribe.subscription = subscribe.subscription.into_mirror();
+                    } else if let Some(filter) = &self.mandatory_http_filter {
+                        subscribe.subscription =
+                            subscribe.subscription.with_http_filter(filter.clone());
+                    }
+
                     let msg = self.subscriptions.layer_subscribed(
                         layer_id,
                         message_id,
@@ -663,7 +1066,10 @@ impl IncomingProxy {
                     );
                     match msg {
                         Some(Either::Left(m)) => message_bus.send(m).await,
-                        Some(Either::Right(m)) => message_bus.send_agent(m).await,
+                        Some(Either::Right(m)) => {
+                            let m = self.with_source_filter(self.with_mirror_limits(m));
+                            message_bus.send_agent(m).await
+                        }
                         None => (),
                     };
                 }
@@ -676,7 +1082,13 @@ impl IncomingProxy {
                     }
                 }
                 IncomingRequest::ConnMetadata(req) => {
-                    let res = self.metadata_store.get(req);
+                    let (res, connection_id) = self.metadata_store.get(req);
+
+                    // The user application accepted the connection.
+                    if let Some(message) = connection_id.and_then(|id| self.backlogs.remove(id)) {
+                        self.notify_backlog(message, message_bus).await;
+                    }
+
                     message_bus
                         .send(ToLayer {
                             message_id,
@@ -687,6 +1099,32 @@ impl IncomingProxy {
                         })
                         .await;
                 }
+                IncomingRequest::PortPause(pause) => {
+                    let supported = self.protocol_version.as_ref().is_some_and(|version| {
+                        ExperimentalFeature::PortPause.is_supported(version)
+                    });
+
+                    let result = match self.subscriptions.get(pause.port) {
+                        None => Err(ResponseError::NotFound(pause.port.into())),
+                        Some(..) if supported.not() => Err(ResponseError::NotImplemented),
+                        Some(subscribe) => {
+                            message_bus
+                                .send_agent(subscribe.subscription.wrap_agent_pause(pause.paused))
+                                .await;
+                            Ok(())
+                        }
+                    };
+
+                    message_bus
+                        .send(ToLayer {
+                            message_id,
+                            layer_id,
+                            message: ProxyToLayerMessage::Incoming(IncomingResponse::PortPause(
+                                result,
+                            )),
+                        })
+                        .await;
+                }
             },
 
             IncomingProxyMessage::AgentMirror(msg) => {
@@ -717,16 +1155,29 @@ impl IncomingProxy {
                     for subscription in self.subscriptions.iter_mut() {
                         tracing::info!(?subscription, "Resubscribing after connection refresh");
 
-                        message_bus
-                            .send_agent(
-                                subscription.resubscribe_message(self.protocol_version.as_ref()),
-                            )
-                            .await
+                        let message =
+                            subscription.resubscribe_message(self.protocol_version.as_ref());
+                        let message = self.with_source_filter(self.with_mirror_limits(message));
+                        message_bus.send_agent(message).await
                     }
                     self.restore_subscriptions_on_protocol_version_switch = false;
                 }
             }
 
+            IncomingProxyMessage::AgentDisabledFeatures(features) => {
+                self.steal_disabled = features.steal;
+            }
+
+            IncomingProxyMessage::AgentMandatoryHttpFilter(filter) => {
+                self.mandatory_http_filter = Some(filter);
+            }
+
+            IncomingProxyMessage::AgentGoingDown(notice) => {
+                tracing::warn!(%notice, "The mirrord agent is shutting down");
+                self.agent_going_down = true;
+                self.ack_going_down_when_drained(message_bus).await;
+            }
+
             IncomingProxyMessage::ConnectionRefresh(refresh) => {
                 match refresh {
                     ConnectionRefresh::Start => {
@@ -735,6 +1186,8 @@ impl IncomingProxy {
                         self.http_gateways.mirror.clear();
                         self.http_gateways.steal.clear();
                         self.tasks.as_mut().unwrap().clear();
+                        self.backlogs.clear();
+                        self.agent_going_down = false;
 
                         // Reset protocol version since we'll need another negotiation
                         // round for the new connection.
@@ -778,6 +1231,11 @@ impl IncomingProxy {
                 };
 
                 self.metadata_store.no_longer_expect(connection_id);
+                if is_steal {
+                    if let Some(message) = self.backlogs.remove(connection_id) {
+                        self.notify_backlog(message, message_bus).await;
+                    }
+                }
 
                 let send_close = self
                     .tcp_proxies
@@ -820,13 +1278,17 @@ impl IncomingProxy {
     ) {
         match update {
             TaskUpdate::Finished(result) => {
-                let respond_on_panic = self
+                let gateway = self
                     .http_gateways
                     .get_mut(is_steal)
                     .get_mut(&id.connection_id)
-                    .and_then(|gateways| gateways.remove(&id.request_id))
-                    .is_some()
-                    && is_steal;
+                    .and_then(|gateways| gateways.remove(&id.request_id));
+                let respond_on_panic = gateway.is_some() && is_steal;
+                // The agent is still sending us the request body, but nobody will read it.
+                let cancel = is_steal
+                    && gateway.is_some_and(|gateway| gateway.body_tx.is_some())
+                    && self.tcp_proxies.steal.contains_key(&id.connection_id).not()
+                    && self.supports_cancel();
 
                 match result {
                     Ok(()) => {}
@@ -856,6 +1318,15 @@ impl IncomingProxy {
                         }
                     }
                 }
+
+                if cancel {
+                    message_bus
+                        .send_agent(ClientMessage::Cancel(CancelRequest::HttpRequest {
+                            connection_id: id.connection_id,
+                            request_id: id.request_id,
+                        }))
+                        .await;
+                }
             }
 
             TaskUpdate::Message(InProxyTaskMessage::Http(message)) => {
@@ -871,11 +1342,13 @@ impl IncomingProxy {
 
                 match message {
                     HttpOut::Upgraded(on_upgrade) => {
+                        let shutdown_write = self.supports_shutdown_write();
                         let proxy = self.tasks.as_mut().unwrap().register(
                             TcpProxyTask::new(
                                 id.connection_id,
                                 LocalTcpConnection::AfterUpgrade(on_upgrade),
                                 is_steal.not(),
+                                shutdown_write,
                             ),
                             if is_steal {
                                 InProxyTask::StealTcpProxy(id.connection_id)
@@ -923,12 +1396,14 @@ impl BackgroundTask for IncomingProxy {
                     }
                     InProxyTask::StealTcpProxy(connection_id) => {
                         self.handle_tcp_proxy_update(connection_id, true, update, message_bus).await;
+                        self.ack_going_down_when_drained(message_bus).await;
                     }
                     InProxyTask::MirrorHttpGateway(id) => {
                         self.handle_http_gateway_update(id, false, update, message_bus).await;
                     }
                     InProxyTask::StealHttpGateway(id) => {
                         self.handle_http_gateway_update(id, true, update, message_bus).await;
+                        self.ack_going_down_when_drained(message_bus).await;
                     }
                 },
             }
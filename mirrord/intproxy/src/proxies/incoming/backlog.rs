@@ -0,0 +1,164 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Not,
+};
+
+use mirrord_config::feature::network::incoming::backlog::{BacklogAction, BacklogConfig};
+use mirrord_protocol::{
+    ConnectionId, Port,
+    tcp::{BacklogOverflow, TcpBacklogFull},
+};
+
+/// Tracks stolen connections that were not yet accepted by the user application, and enforces
+/// the [`BacklogConfig`].
+///
+/// A connection is pending from the moment we receive it from the agent, until the layer asks for
+/// its metadata (which happens when the user application accepts it), or until it is closed.
+#[derive(Default)]
+pub struct Backlogs {
+    config: Option<BacklogConfig>,
+    /// Pending connections, by the remote port.
+    pending: HashMap<Port, HashSet<ConnectionId>>,
+    /// Ports with full backlogs.
+    full: HashSet<Port>,
+    /// Number of connections that overflowed the backlog, by the remote port.
+    overflowed: HashMap<Port, u64>,
+}
+
+impl Backlogs {
+    pub fn new(config: Option<BacklogConfig>) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the backlog on the given port is full, and new connections should be rejected.
+    pub fn is_full(&self, port: Port) -> bool {
+        let Some(config) = self.config else {
+            return false;
+        };
+
+        self.pending
+            .get(&port)
+            .is_some_and(|pending| pending.len() >= config.max_pending)
+    }
+
+    /// Records a connection that was rejected because the backlog on its port was full.
+    ///
+    /// Returns the total number of connections that overflowed the backlog on this port.
+    pub fn overflow(&mut self, port: Port) -> u64 {
+        let overflowed = self.overflowed.entry(port).or_default();
+        *overflowed += 1;
+        *overflowed
+    }
+
+    /// Adds a new pending connection.
+    ///
+    /// If this fills the backlog, returns a message that notifies the agent.
+    pub fn push(&mut self, port: Port, connection_id: ConnectionId) -> Option<TcpBacklogFull> {
+        let config = self.config?;
+
+        self.pending.entry(port).or_default().insert(connection_id);
+        if self.is_full(port).not() || self.full.insert(port).not() {
+            return None;
+        }
+
+        tracing::warn!(
+            port,
+            max_pending = config.max_pending,
+            overflowed = self.overflowed.get(&port).copied().unwrap_or_default(),
+            "Backlog of stolen connections is full, the local application does not accept them fast enough",
+        );
+
+        Some(TcpBacklogFull {
+            port,
+            full: true,
+            overflow: Self::overflow_action(config),
+        })
+    }
+
+    /// Removes the connection from its backlog, because it was accepted by the user application or
+    /// closed.
+    ///
+    /// If the backlog is no longer full, returns a message that notifies the agent.
+    pub fn remove(&mut self, connection_id: ConnectionId) -> Option<TcpBacklogFull> {
+        let config = self.config?;
+
+        let (&port, pending) = self
+            .pending
+            .iter_mut()
+            .find(|(_, pending)| pending.contains(&connection_id))?;
+        pending.remove(&connection_id);
+        if pending.is_empty() {
+            self.pending.remove(&port);
+        }
+
+        if self.is_full(port) || self.full.remove(&port).not() {
+            return None;
+        }
+
+        Some(TcpBacklogFull {
+            port,
+            full: false,
+            overflow: Self::overflow_action(config),
+        })
+    }
+
+    /// Forgets all pending connections, used when the connection to the agent is refreshed.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.full.clear();
+    }
+
+    fn overflow_action(config: BacklogConfig) -> BacklogOverflow {
+        match config.when_exceeded {
+            BacklogAction::Reject => BacklogOverflow::Reject,
+            BacklogAction::Passthrough => BacklogOverflow::PassThrough,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Not;
+
+    use mirrord_config::feature::network::incoming::backlog::{BacklogAction, BacklogConfig};
+    use mirrord_protocol::tcp::{BacklogOverflow, TcpBacklogFull};
+
+    use super::Backlogs;
+
+    #[test]
+    fn fills_and_drains() {
+        let mut backlogs = Backlogs::new(Some(BacklogConfig {
+            max_pending: 2,
+            when_exceeded: BacklogAction::Passthrough,
+        }));
+
+        assert_eq!(backlogs.push(80, 0), None);
+        assert_eq!(backlogs.push(8080, 1), None);
+        assert!(backlogs.is_full(80).not());
+        assert_eq!(
+            backlogs.push(80, 2),
+            Some(TcpBacklogFull {
+                port: 80,
+                full: true,
+                overflow: BacklogOverflow::PassThrough,
+            })
+        );
+        assert!(backlogs.is_full(80));
+        assert_eq!(backlogs.overflow(80), 1);
+
+        assert_eq!(backlogs.remove(1), None);
+        assert_eq!(
+            backlogs.remove(0),
+            Some(TcpBacklogFull {
+                port: 80,
+                full: false,
+                overflow: BacklogOverflow::PassThrough,
+            })
+        );
+        assert_eq!(backlogs.remove(0), None);
+        assert!(backlogs.is_full(80).not());
+    }
+}
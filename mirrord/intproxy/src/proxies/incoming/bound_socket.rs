@@ -28,6 +28,24 @@ impl BoundTcpSocket {
         Ok(Self(socket))
     }
 
+    /// Opens a new TCP socket and binds it to the given address.
+    ///
+    /// Used to make another connection attempt from the address of a socket that failed to
+    /// connect, so that the [`MetadataStore`](super::metadata_store::MetadataStore) entry for this
+    /// address stays valid.
+    #[tracing::instrument(level = Level::TRACE, ret, err)]
+    pub fn bind_exact(addr: SocketAddr) -> io::Result<Self> {
+        let socket = match addr {
+            SocketAddr::V4(..) => TcpSocket::new_v4()?,
+            SocketAddr::V6(..) => TcpSocket::new_v6()?,
+        };
+
+        socket.set_reuseaddr(true)?;
+        socket.bind(addr)?;
+
+        Ok(Self(socket))
+    }
+
     /// Returns the address to which this socket is bound.
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.0.local_addr()
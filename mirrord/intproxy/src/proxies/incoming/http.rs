@@ -1,9 +1,10 @@
 use std::{fmt, io, net::SocketAddr, ops::Not};
 
 use hyper::{
-    Request, Response, StatusCode, Version,
+    HeaderMap, Request, Response, StatusCode, Version,
     body::Incoming,
     client::conn::{http1, http2},
+    header::{self, HeaderName, HeaderValue},
 };
 use hyper_util::rt::{TokioExecutor, TokioIo};
 use mirrord_protocol::{
@@ -22,7 +23,7 @@ pub use client_store::ClientStore;
 pub use response_mode::ResponseMode;
 pub use streaming_body::StreamingBody;
 
-use super::tls::LocalTlsSetupError;
+use super::{local_app_wait::is_refused, tls::LocalTlsSetupError};
 
 /// An HTTP client used to pass requests to the user application.
 pub struct LocalHttpClient {
@@ -121,6 +122,12 @@ impl LocalHttpError {
             .not(),
         }
     }
+
+    /// Checks if the user application refused the connection, e.g. because it is not listening
+    /// yet.
+    pub fn is_connection_refused(&self) -> bool {
+        matches!(self, Self::ConnectTcpFailed(error) if is_refused(error))
+    }
 }
 
 /// Produces a mirrord-specific [`StatusCode::BAD_GATEWAY`] response.
@@ -130,6 +137,42 @@ pub fn mirrord_error_response<M: fmt::Display>(
     connection_id: ConnectionId,
     request_id: RequestId,
     port: Port,
+) -> HttpResponse<Payload> {
+    mirrord_response(
+        StatusCode::BAD_GATEWAY,
+        message,
+        version,
+        connection_id,
+        request_id,
+        port,
+    )
+}
+
+/// Produces a mirrord-specific [`StatusCode::SERVICE_UNAVAILABLE`] response.
+pub fn mirrord_unavailable_response<M: fmt::Display>(
+    message: M,
+    version: Version,
+    connection_id: ConnectionId,
+    request_id: RequestId,
+    port: Port,
+) -> HttpResponse<Payload> {
+    mirrord_response(
+        StatusCode::SERVICE_UNAVAILABLE,
+        message,
+        version,
+        connection_id,
+        request_id,
+        port,
+    )
+}
+
+fn mirrord_response<M: fmt::Display>(
+    status: StatusCode,
+    message: M,
+    version: Version,
+    connection_id: ConnectionId,
+    request_id: RequestId,
+    port: Port,
 ) -> HttpResponse<Payload> {
     let body = format!(
         "mirrord-intproxy v{}: {message}\n",
@@ -142,7 +185,7 @@ pub fn mirrord_error_response<M: fmt::Display>(
         port,
         request_id,
         internal_response: InternalHttpResponse {
-            status: StatusCode::BAD_GATEWAY,
+            status,
             version,
             headers: Default::default(),
             body,
@@ -150,6 +193,98 @@ pub fn mirrord_error_response<M: fmt::Display>(
     }
 }
 
+/// Header with the id of the remote connection, see [`add_metadata_headers`].
+pub const CONNECTION_ID_HEADER: HeaderName = HeaderName::from_static("x-mirrord-connection-id");
+
+/// Header with the id of the request within the remote connection, see
+/// [`add_metadata_headers`].
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-mirrord-request-id");
+
+/// Header with the original destination of the request, see [`add_metadata_headers`].
+pub const ORIGINAL_DST_HEADER: HeaderName = HeaderName::from_static("x-mirrord-original-dst");
+
+/// Header with the agent-generated id of the request, see [`add_metadata_headers`].
+///
+/// Unlike [`REQUEST_ID_HEADER`], this id is unique across the whole session, and matches the id
+/// in the agent audit log and in the intproxy logs.
+pub const CORRELATION_ID_HEADER: HeaderName = HeaderName::from_static("x-mirrord-correlation-id");
+
+/// Adds mirrord metadata headers to a request delivered to the user application, so that it can
+/// be correlated with the agent-side records.
+///
+/// Enabled with `feature.network.incoming.metadata_headers`. Headers with the same names sent by
+/// the remote client are replaced.
+pub fn add_metadata_headers(
+    headers: &mut HeaderMap,
+    connection_id: ConnectionId,
+    request_id: RequestId,
+    original_destination: Option<SocketAddr>,
+    correlation_id: Option<&str>,
+) {
+    headers.insert(CONNECTION_ID_HEADER, HeaderValue::from(connection_id));
+    headers.insert(REQUEST_ID_HEADER, HeaderValue::from(request_id));
+
+    match correlation_id.map(HeaderValue::try_from) {
+        Some(Ok(value)) => {
+            headers.insert(CORRELATION_ID_HEADER, value);
+        }
+        _ => {
+            headers.remove(CORRELATION_ID_HEADER);
+        }
+    }
+
+    match original_destination.map(|address| HeaderValue::try_from(address.to_string())) {
+        Some(Ok(value)) => {
+            headers.insert(ORIGINAL_DST_HEADER, value);
+        }
+        _ => {
+            headers.remove(ORIGINAL_DST_HEADER);
+        }
+    }
+}
+
+/// Removes the `expect: 100-continue` header, leaving any other expectations in place.
+fn strip_expect_continue(headers: &mut HeaderMap) {
+    let expects_continue = headers
+        .get(header::EXPECT)
+        .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"100-continue"));
+
+    if expects_continue {
+        headers.remove(header::EXPECT);
+    }
+}
+
+/// Checks whether an HTTP connection remains open after an exchange with the given request and
+/// response headers.
+///
+/// HTTP/1.1 connections are persistent unless either side sends `connection: close`. HTTP/1.0
+/// connections are closed after the response, unless both sides send `connection: keep-alive`.
+pub fn keeps_connection_alive(
+    request_version: Version,
+    request_headers: &HeaderMap,
+    response_version: Version,
+    response_headers: &HeaderMap,
+) -> bool {
+    let has_token = |headers: &HeaderMap, token: &str| {
+        headers
+            .get_all(header::CONNECTION)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|value| value.trim().eq_ignore_ascii_case(token))
+    };
+
+    match (request_version, response_version) {
+        (Version::HTTP_2, _) | (_, Version::HTTP_2) => true,
+        (Version::HTTP_10, _) | (_, Version::HTTP_10) => {
+            has_token(request_headers, "keep-alive") && has_token(response_headers, "keep-alive")
+        }
+        _ => {
+            has_token(request_headers, "close").not() && has_token(response_headers, "close").not()
+        }
+    }
+}
+
 /// Holds either [`http1::SendRequest`] or [`http2::SendRequest`] and exposes a unified interface.
 enum HttpSender {
     V1(http1::SendRequest<StreamingBody>),
@@ -211,6 +346,13 @@ impl HttpSender {
         &mut self,
         request: HttpRequest<StreamingBody>,
     ) -> Result<Response<Incoming>, LocalHttpError> {
+        let mut hyper_request: Request<_> = request.internal_request.into();
+
+        // The agent already sent `100 Continue` to the remote client (or will, once we read the
+        // body), and we stream the body to the application without waiting for its interim
+        // response anyway.
+        strip_expect_continue(hyper_request.headers_mut());
+
         match self {
             Self::V1(sender) => {
                 // Solves a "connection was not ready" client error.
@@ -218,13 +360,11 @@ impl HttpSender {
                 sender.ready().await.map_err(LocalHttpError::SendFailed)?;
 
                 sender
-                    .send_request(request.internal_request.into())
+                    .send_request(hyper_request)
                     .await
                     .map_err(LocalHttpError::SendFailed)
             }
             Self::V2(sender) => {
-                let mut hyper_request: Request<_> = request.internal_request.into();
-
                 // fixes https://github.com/metalbear-co/mirrord/issues/2497
                 // inspired by https://github.com/linkerd/linkerd2-proxy/blob/c5d9f1c1e7b7dddd9d75c0d1a0dca68188f38f34/linkerd/proxy/http/src/h2.rs#L175
                 if hyper_request.uri().authority().is_none()
@@ -251,3 +391,119 @@ impl HttpSender {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use hyper::{HeaderMap, Version, header};
+    use rstest::rstest;
+
+    use super::{
+        CONNECTION_ID_HEADER, CORRELATION_ID_HEADER, ORIGINAL_DST_HEADER, REQUEST_ID_HEADER,
+        add_metadata_headers, keeps_connection_alive, strip_expect_continue,
+    };
+
+    #[rstest]
+    #[case::http11(Version::HTTP_11, None, Version::HTTP_11, None, true)]
+    #[case::http11_close(Version::HTTP_11, None, Version::HTTP_11, Some("close"), false)]
+    #[case::http11_close_list(
+        Version::HTTP_11,
+        Some("Upgrade, Close"),
+        Version::HTTP_11,
+        None,
+        false
+    )]
+    #[case::http10(Version::HTTP_10, None, Version::HTTP_10, None, false)]
+    #[case::http10_response_11(Version::HTTP_10, None, Version::HTTP_11, None, false)]
+    #[case::http10_keep_alive(
+        Version::HTTP_10,
+        Some("keep-alive"),
+        Version::HTTP_10,
+        Some("Keep-Alive"),
+        true
+    )]
+    #[case::http10_keep_alive_request_only(
+        Version::HTTP_10,
+        Some("keep-alive"),
+        Version::HTTP_11,
+        None,
+        false
+    )]
+    #[case::http2(Version::HTTP_2, None, Version::HTTP_2, None, true)]
+    #[test]
+    fn connection_persistence(
+        #[case] request_version: Version,
+        #[case] request_connection: Option<&'static str>,
+        #[case] response_version: Version,
+        #[case] response_connection: Option<&'static str>,
+        #[case] expected: bool,
+    ) {
+        let headers = |connection: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(connection) = connection {
+                headers.insert(header::CONNECTION, connection.parse().unwrap());
+            }
+            headers
+        };
+
+        assert_eq!(
+            keeps_connection_alive(
+                request_version,
+                &headers(request_connection),
+                response_version,
+                &headers(response_connection),
+            ),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::continue_lowercase(Some("100-continue"), None)]
+    #[case::continue_mixed_case(Some("100-Continue"), None)]
+    #[case::other_expectation(Some("something-else"), Some("something-else"))]
+    #[case::no_expectation(None, None)]
+    #[test]
+    fn expect_continue_stripped(
+        #[case] expect: Option<&'static str>,
+        #[case] expected: Option<&'static str>,
+    ) {
+        let mut headers = HeaderMap::new();
+        if let Some(expect) = expect {
+            headers.insert(header::EXPECT, expect.parse().unwrap());
+        }
+
+        strip_expect_continue(&mut headers);
+
+        assert_eq!(
+            headers
+                .get(header::EXPECT)
+                .map(|value| value.to_str().unwrap()),
+            expected
+        );
+    }
+
+    #[test]
+    fn metadata_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(ORIGINAL_DST_HEADER, "spoofed".parse().unwrap());
+
+        add_metadata_headers(
+            &mut headers,
+            7,
+            3,
+            Some("10.0.0.12:8080".parse().unwrap()),
+            Some("01J9ZQ4Y8R6V3N2K5M7P0T1W2X"),
+        );
+        assert_eq!(headers.get(CONNECTION_ID_HEADER).unwrap(), "7");
+        assert_eq!(headers.get(REQUEST_ID_HEADER).unwrap(), "3");
+        assert_eq!(headers.get(ORIGINAL_DST_HEADER).unwrap(), "10.0.0.12:8080");
+        assert_eq!(
+            headers.get(CORRELATION_ID_HEADER).unwrap(),
+            "01J9ZQ4Y8R6V3N2K5M7P0T1W2X"
+        );
+
+        add_metadata_headers(&mut headers, 7, 4, None, None);
+        assert_eq!(headers.get(REQUEST_ID_HEADER).unwrap(), "4");
+        assert!(headers.get(ORIGINAL_DST_HEADER).is_none());
+        assert!(headers.get(CORRELATION_ID_HEADER).is_none());
+    }
+}
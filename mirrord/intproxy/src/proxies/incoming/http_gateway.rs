@@ -5,6 +5,7 @@ use std::{
     fmt,
     net::SocketAddr,
     ops::ControlFlow,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
@@ -14,17 +15,23 @@ use mirrord_protocol::{
     ClientMessage, Payload,
     batched_body::BatchedBody,
     tcp::{
-        ChunkedRequestBodyV1, ChunkedRequestErrorV1, ChunkedResponse, HttpRequest, HttpResponse,
-        IncomingTrafficTransportType, InternalHttpBody, InternalHttpBodyFrame,
-        InternalHttpResponse, LayerTcpSteal,
+        ChunkedResponse, HttpRequest, HttpResponse, IncomingTrafficTransportType, InternalHttpBody,
+        InternalHttpBodyFrame, InternalHttpResponse, LayerTcpSteal, is_event_stream,
     },
 };
-use tokio::time;
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time,
+};
 use tokio_retry::strategy::ExponentialBackoff;
 use tracing::Level;
 
 use super::{
-    http::{ClientStore, LocalHttpError, ResponseMode, StreamingBody, mirrord_error_response},
+    http::{
+        ClientStore, LocalHttpError, ResponseMode, StreamingBody, keeps_connection_alive,
+        mirrord_error_response,
+    },
+    local_app_wait::{LocalAppWait, Waiting},
     tasks::{HttpOut, InProxyTaskMessage},
 };
 use crate::background_tasks::{BackgroundTask, MessageBus};
@@ -47,6 +54,24 @@ pub struct HttpGatewayTask {
     server_addr: SocketAddr,
     /// How to transport the HTTP request to the server.
     transport: IncomingTrafficTransportType,
+    /// Slot in the limit of requests handled by the user application at the same time.
+    ///
+    /// [`None`] if there is no limit.
+    slot: Option<InFlightSlot>,
+    /// Allows for waiting until the user application accepts connections.
+    ///
+    /// [`None`] if we should give up after the regular retries.
+    local_app_wait: Option<LocalAppWait>,
+}
+
+/// Slot of a [`HttpGatewayTask`] in the limit of stolen requests handled by the user application
+/// at the same time.
+#[derive(Debug)]
+pub enum InFlightSlot {
+    /// The slot is already taken, and freed when the task finishes.
+    Taken(OwnedSemaphorePermit),
+    /// The task must wait for a free slot before sending the request.
+    Wait(Arc<Semaphore>),
 }
 
 impl fmt::Debug for HttpGatewayTask {
@@ -56,6 +81,8 @@ impl fmt::Debug for HttpGatewayTask {
             .field("response_mode", &self.response_mode)
             .field("server_addr", &self.server_addr)
             .field("transport", &self.transport)
+            .field("slot", &self.slot)
+            .field("local_app_wait", &self.local_app_wait)
             .finish()
     }
 }
@@ -68,6 +95,8 @@ impl HttpGatewayTask {
         response_mode: Option<ResponseMode>,
         server_addr: SocketAddr,
         transport: IncomingTrafficTransportType,
+        slot: Option<InFlightSlot>,
+        local_app_wait: Option<LocalAppWait>,
     ) -> Self {
         Self {
             request,
@@ -75,6 +104,8 @@ impl HttpGatewayTask {
             response_mode,
             server_addr,
             transport,
+            slot,
+            local_app_wait,
         }
     }
 
@@ -111,17 +142,10 @@ impl HttpGatewayTask {
                 ?ready_frames,
                 "All response body frames were instantly ready, sending full response"
             );
-            let response = HttpResponse {
-                port: self.request.port,
-                connection_id: self.request.connection_id,
-                request_id: self.request.request_id,
-                internal_response: InternalHttpResponse {
-                    status: parts.status,
-                    version: parts.version,
-                    headers: parts.headers,
-                    body: InternalHttpBody(ready_frames),
-                },
-            };
+            let response = HttpResponse::for_request(
+                &self.request,
+                InternalHttpResponse::from_parts(parts, InternalHttpBody(ready_frames)),
+            );
             message_bus
                 .send_agent(ClientMessage::TcpSteal(LayerTcpSteal::HttpResponseFramed(
                     response,
@@ -142,21 +166,14 @@ impl HttpGatewayTask {
             but response body may not be finished yet"
         );
 
-        let response = HttpResponse {
-            port: self.request.port,
-            connection_id: self.request.connection_id,
-            request_id: self.request.request_id,
-            internal_response: InternalHttpResponse {
-                status: parts.status,
-                version: parts.version,
-                headers: parts.headers,
-                body: ready_frames,
-            },
-        };
+        let response = ChunkedResponse::start(
+            &self.request,
+            InternalHttpResponse::from_parts(parts, ready_frames),
+        );
 
         message_bus
             .send_agent(ClientMessage::TcpSteal(LayerTcpSteal::HttpResponseChunked(
-                ChunkedResponse::Start(response),
+                response,
             )))
             .await;
 
@@ -179,12 +196,7 @@ impl HttpGatewayTask {
 
                     message_bus
                         .send_agent(ClientMessage::TcpSteal(LayerTcpSteal::HttpResponseChunked(
-                            ChunkedResponse::Body(ChunkedRequestBodyV1 {
-                                frames,
-                                is_last,
-                                connection_id: self.request.connection_id,
-                                request_id: self.request.request_id,
-                            }),
+                            ChunkedResponse::body(&self.request, frames, is_last),
                         )))
                         .await;
 
@@ -205,10 +217,7 @@ impl HttpGatewayTask {
 
                     message_bus
                         .send_agent(ClientMessage::TcpSteal(LayerTcpSteal::HttpResponseChunked(
-                            ChunkedResponse::Error(ChunkedRequestErrorV1 {
-                                connection_id: self.request.connection_id,
-                                request_id: self.request.request_id,
-                            }),
+                            ChunkedResponse::error(&self.request),
                         )))
                         .await;
 
@@ -242,16 +251,39 @@ impl HttpGatewayTask {
             hyper::upgrade::on(&mut response)
         });
         let (parts, mut body) = response.into_parts();
+        let keep_alive = keeps_connection_alive(
+            self.request.version(),
+            &self.request.internal_request.headers,
+            parts.version,
+            &parts.headers,
+        );
+
+        if matches!(
+            self.response_mode,
+            Some(ResponseMode::Basic | ResponseMode::Framed)
+        ) && is_event_stream(&parts.headers)
+        {
+            tracing::warn!(
+                "The agent does not support streamed HTTP responses, server-sent events will be \
+                delivered only when the local application ends the stream. Update the agent to \
+                stream them as they are produced."
+            );
+        }
 
         let flow = match self.response_mode {
             Some(ResponseMode::Basic) => {
                 let start = Instant::now();
-                let body: Vec<u8> = body
+                let collected = body
                     .collect()
                     .await
-                    .map_err(LocalHttpError::ReadBodyFailed)?
-                    .to_bytes()
-                    .into();
+                    .map_err(LocalHttpError::ReadBodyFailed)?;
+                if let Some(trailers) = collected.trailers() {
+                    tracing::warn!(
+                        trailers = trailers.len(),
+                        "Dropping HTTP response trailers, the agent does not support them",
+                    );
+                }
+                let body: Vec<u8> = collected.to_bytes().into();
                 let body = Payload::from(body);
                 tracing::debug!(
                     body_len = body.len(),
@@ -259,17 +291,10 @@ impl HttpGatewayTask {
                     "Collected the whole response body",
                 );
 
-                let response = HttpResponse {
-                    port: self.request.port,
-                    connection_id: self.request.connection_id,
-                    request_id: self.request.request_id,
-                    internal_response: InternalHttpResponse {
-                        status: parts.status,
-                        version: parts.version,
-                        headers: parts.headers,
-                        body,
-                    },
-                };
+                let response = HttpResponse::for_request(
+                    &self.request,
+                    InternalHttpResponse::from_parts(parts, body),
+                );
                 message_bus
                     .send_agent(ClientMessage::TcpSteal(LayerTcpSteal::HttpResponse(
                         response,
@@ -289,17 +314,10 @@ impl HttpGatewayTask {
                     "Collected the whole response body",
                 );
 
-                let response = HttpResponse {
-                    port: self.request.port,
-                    connection_id: self.request.connection_id,
-                    request_id: self.request.request_id,
-                    internal_response: InternalHttpResponse {
-                        status: parts.status,
-                        version: parts.version,
-                        headers: parts.headers,
-                        body,
-                    },
-                };
+                let response = HttpResponse::for_request(
+                    &self.request,
+                    InternalHttpResponse::from_parts(parts, body),
+                );
                 message_bus
                     .send_agent(ClientMessage::TcpSteal(LayerTcpSteal::HttpResponseFramed(
                         response,
@@ -334,10 +352,16 @@ impl HttpGatewayTask {
             Some(on_upgrade) => {
                 message_bus.send(HttpOut::Upgraded(on_upgrade)).await;
             }
-            _ => {
+            _ if keep_alive => {
                 // If there was no upgrade and no error, the client can be reused.
                 self.client_store.push_idle(client);
             }
+            _ => {
+                tracing::debug!(
+                    ?client,
+                    "Local connection does not persist after the response, dropping the client"
+                );
+            }
         }
 
         Ok(())
@@ -363,6 +387,27 @@ impl BackgroundTask for HttpGatewayTask {
 
         let closed_token = message_bus.closed_token().clone();
 
+        // Held until the request is handled.
+        let _permit = match self.slot.take() {
+            None => None,
+            Some(InFlightSlot::Taken(permit)) => Some(permit),
+            Some(InFlightSlot::Wait(semaphore)) => {
+                tracing::debug!("Waiting for a free slot in the request limit");
+
+                match closed_token
+                    .run_until_cancelled(semaphore.acquire_owned())
+                    .await
+                {
+                    Some(Ok(permit)) => Some(permit),
+                    // The semaphore is never closed.
+                    Some(Err(..)) | None => return Ok(()),
+                }
+            }
+        };
+
+        // Set when the user application first refuses the connection.
+        let mut waiting: Option<Waiting> = None;
+
         let mut attempt = 0;
         let error = loop {
             attempt += 1;
@@ -381,6 +426,31 @@ impl BackgroundTask for HttpGatewayTask {
             match send_result {
                 None | Some(Ok(())) => return Ok(()),
                 Some(Err(error)) => {
+                    if error.is_connection_refused()
+                        && let Some(wait) = &self.local_app_wait
+                    {
+                        if waiting.is_none() {
+                            waiting = wait.start();
+                            if waiting.is_some() {
+                                tracing::info!(
+                                    server_addr = %self.server_addr,
+                                    "Local application refused the connection, waiting for it",
+                                );
+                            }
+                        }
+
+                        if let Some(waiting) = &waiting {
+                            match closed_token
+                                .run_until_cancelled(waiting.next_attempt())
+                                .await
+                            {
+                                Some(true) => continue,
+                                Some(false) => {}
+                                None => return Ok(()),
+                            }
+                        }
+                    }
+
                     let backoff = error.can_retry().then(|| backoffs.next()).flatten();
 
                     let Some(backoff) = backoff else {
@@ -449,7 +519,7 @@ mod test {
     use bytes::Bytes;
     use http_body_util::{Empty, StreamBody};
     use hyper::{
-        Method, Request, Response, StatusCode, Version,
+        HeaderMap, Method, Request, Response, StatusCode, Version,
         body::{Frame, Incoming},
         header::{self, CONNECTION, HeaderValue, UPGRADE},
         server::conn::http1,
@@ -681,6 +751,8 @@ mod test {
                 } else {
                     IncomingTrafficTransportType::Tcp
                 },
+                None,
+                None,
             );
             tasks.register(gateway, 0, 8)
         };
@@ -733,6 +805,7 @@ mod test {
                 update.0,
                 LocalTcpConnection::AfterUpgrade(on_upgrade),
                 is_steal.not(),
+                true,
             ),
             1,
             8,
@@ -843,6 +916,8 @@ mod test {
                 response_mode,
                 addr,
                 IncomingTrafficTransportType::Tcp,
+                None,
+                None,
             ),
             (),
             8,
@@ -1004,6 +1079,8 @@ mod test {
                 Some(ResponseMode::Basic),
                 addr,
                 IncomingTrafficTransportType::Tcp,
+                None,
+                None,
             ),
             (),
             8,
@@ -1033,6 +1110,113 @@ mod test {
         conn_task.await.unwrap();
     }
 
+    /// Verifies that [`HttpGatewayTask`] passes the response trailers to the agent.
+    #[rstest]
+    #[case::framed(ResponseMode::Framed)]
+    #[case::chunked(ResponseMode::Chunked)]
+    #[tokio::test]
+    async fn preserves_response_trailers(#[case] response_mode: ResponseMode) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let conn_task = tokio::spawn(async move {
+            let service = service_fn(|_req: Request<Incoming>| async move {
+                let mut trailers = HeaderMap::new();
+                trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                let frames = [
+                    Frame::data(Bytes::from_static(b"hello")),
+                    Frame::trailers(trailers),
+                ];
+                let body = StreamBody::new(futures::stream::iter(frames.map(Ok::<_, Infallible>)));
+
+                let mut response = Response::new(body);
+                response
+                    .headers_mut()
+                    .insert(header::TRAILER, HeaderValue::from_static("grpc-status"));
+
+                Ok::<_, Infallible>(response)
+            });
+
+            let (connection, _) = listener.accept().await.unwrap();
+            http1::Builder::new()
+                .serve_connection(TokioIo::new(connection), service)
+                .await
+                .unwrap()
+        });
+
+        let mut request = HttpRequest {
+            connection_id: 0,
+            request_id: 0,
+            port: 80,
+            internal_request: InternalHttpRequest {
+                method: Method::GET,
+                uri: "/".parse().unwrap(),
+                headers: Default::default(),
+                version: Version::HTTP_11,
+                body: StreamingBody::from(Payload::from(Vec::<u8>::new())),
+            },
+        };
+        // hyper sends HTTP/1 trailers only to clients that accept them.
+        request
+            .internal_request
+            .headers
+            .insert(header::TE, HeaderValue::from_static("trailers"));
+
+        let (connection, _, proxy_rx) = Connection::dummy();
+
+        let mut tasks: BackgroundTasks<(), InProxyTaskMessage, Infallible> =
+            BackgroundTasks::new(connection.tx_handle());
+
+        let _gateway = tasks.register(
+            HttpGatewayTask::new(
+                request,
+                ClientStore::new_with_timeout(Duration::from_secs(1), Default::default()),
+                Some(response_mode),
+                addr,
+                IncomingTrafficTransportType::Tcp,
+                None,
+                None,
+            ),
+            (),
+            8,
+        );
+
+        let mut frames = vec![];
+        loop {
+            match proxy_rx.next().await.unwrap() {
+                ClientMessage::TcpSteal(LayerTcpSteal::HttpResponseFramed(response)) => {
+                    frames.extend(response.internal_response.body.0);
+                    break;
+                }
+                ClientMessage::TcpSteal(LayerTcpSteal::HttpResponseChunked(
+                    ChunkedResponse::Start(response),
+                )) => frames.extend(response.internal_response.body),
+                ClientMessage::TcpSteal(LayerTcpSteal::HttpResponseChunked(
+                    ChunkedResponse::Body(body),
+                )) => {
+                    frames.extend(body.frames);
+                    if body.is_last {
+                        break;
+                    }
+                }
+                other => panic!("unexpected task message: {other:?}"),
+            }
+        }
+
+        let trailers = frames
+            .iter()
+            .find_map(InternalHttpBodyFrame::trailers_ref)
+            .expect("response trailers were dropped");
+        assert_eq!(trailers.get("grpc-status").unwrap(), "0");
+
+        match tasks.next().await.unwrap().1 {
+            TaskUpdate::Finished(Ok(())) => {}
+            other => panic!("unexpected task update: {other:?}"),
+        }
+
+        conn_task.await.unwrap();
+    }
+
     /// Verifies that [`HttpGateway`] reuses already established HTTP connections.
     #[tokio::test]
     async fn reuses_client_connections() {
@@ -1084,6 +1268,8 @@ mod test {
                 Some(ResponseMode::Basic),
                 addr,
                 IncomingTrafficTransportType::Tcp,
+                None,
+                None,
             ),
             0,
             8,
@@ -1095,6 +1281,8 @@ mod test {
                 Some(ResponseMode::Basic),
                 addr,
                 IncomingTrafficTransportType::Tcp,
+                None,
+                None,
             ),
             1,
             8,
@@ -0,0 +1,92 @@
+//! Waiting for the user application to start accepting connections, see [`LocalAppWait`].
+
+use std::{io, sync::Arc, time::Duration};
+
+use mirrord_config::feature::network::incoming::wait_for_local_app::WaitForLocalAppConfig;
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::{self, Instant},
+};
+
+/// Delay between the connection attempts.
+const RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Shared between all tasks that deliver stolen traffic to the user application.
+///
+/// When the application refuses a connection (e.g. because it is still compiling), the tasks
+/// [`LocalAppWait::start`] waiting for it, and keep trying to connect until the timeout elapses.
+#[derive(Clone, Debug)]
+pub struct LocalAppWait {
+    timeout: Duration,
+    /// Limits the number of tasks that wait at the same time.
+    slots: Arc<Semaphore>,
+}
+
+impl LocalAppWait {
+    pub fn new(config: WaitForLocalAppConfig) -> Self {
+        Self {
+            timeout: Duration::from_secs(config.timeout_secs),
+            slots: Arc::new(Semaphore::new(
+                config.max_waiting.unwrap_or(Semaphore::MAX_PERMITS),
+            )),
+        }
+    }
+
+    /// Starts waiting for the application.
+    ///
+    /// Returns [`None`] if too many tasks are already waiting.
+    pub fn start(&self) -> Option<Waiting> {
+        let permit = self.slots.clone().try_acquire_owned().ok()?;
+
+        Some(Waiting {
+            deadline: Instant::now() + self.timeout,
+            _permit: permit,
+        })
+    }
+}
+
+/// A task waiting for the application, see [`LocalAppWait::start`].
+#[derive(Debug)]
+pub struct Waiting {
+    deadline: Instant,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Waiting {
+    /// Sleeps until the next connection attempt.
+    ///
+    /// Returns `false` when the timeout has elapsed, and the task should give up.
+    pub async fn next_attempt(&self) -> bool {
+        if Instant::now() + RETRY_INTERVAL > self.deadline {
+            return false;
+        }
+
+        time::sleep(RETRY_INTERVAL).await;
+        true
+    }
+}
+
+/// Whether the connection failed because the application is not listening (yet).
+pub fn is_refused(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::ConnectionRefused
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn limits_waiting_tasks() {
+        let wait = LocalAppWait::new(WaitForLocalAppConfig {
+            timeout_secs: 0,
+            max_waiting: Some(1),
+        });
+
+        let waiting = wait.start().unwrap();
+        assert!(wait.start().is_none());
+        assert!(!waiting.next_attempt().await);
+
+        std::mem::drop(waiting);
+        assert!(wait.start().is_some());
+    }
+}
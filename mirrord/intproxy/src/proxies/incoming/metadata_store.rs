@@ -8,7 +8,7 @@ use mirrord_protocol::ConnectionId;
 /// Allows for extracting the original socket addresses of peers of a remote connection.
 #[derive(Default)]
 pub struct MetadataStore {
-    prepared_responses: HashMap<ConnMetadataRequest, ConnMetadataResponse>,
+    prepared_responses: HashMap<ConnMetadataRequest, (ConnectionId, ConnMetadataResponse)>,
     expected_requests: HashMap<ConnectionId, ConnMetadataRequest>,
 }
 
@@ -16,13 +16,25 @@ impl MetadataStore {
     /// Retrieves remote addresses for the given pair of local addresses.
     ///
     /// If the mapping is not found, returns the local addresses unchanged.
-    pub fn get(&mut self, req: ConnMetadataRequest) -> ConnMetadataResponse {
-        self.prepared_responses
-            .remove(&req)
-            .unwrap_or_else(|| ConnMetadataResponse {
-                remote_source: req.peer_address,
-                local_address: req.listener_address.ip(),
-            })
+    ///
+    /// Also returns the id of the remote connection, if the mapping was found.
+    pub fn get(
+        &mut self,
+        req: ConnMetadataRequest,
+    ) -> (ConnMetadataResponse, Option<ConnectionId>) {
+        match self.prepared_responses.remove(&req) {
+            Some((connection, res)) => {
+                self.expected_requests.remove(&connection);
+                (res, Some(connection))
+            }
+            None => (
+                ConnMetadataResponse {
+                    remote_source: req.peer_address,
+                    local_address: req.listener_address.ip(),
+                },
+                None,
+            ),
+        }
     }
 
     /// Adds a new `req`->`res` mapping to this struct.
@@ -35,7 +47,7 @@ impl MetadataStore {
         res: ConnMetadataResponse,
     ) {
         self.expected_requests.insert(connection, req.clone());
-        self.prepared_responses.insert(req, res);
+        self.prepared_responses.insert(req, (connection, res));
     }
 
     /// Clears mapping related to the remote connection with the given id.
@@ -3,7 +3,7 @@
 use mirrord_intproxy_protocol::PortSubscription;
 use mirrord_protocol::{
     ClientMessage, Port,
-    tcp::{LayerTcp, LayerTcpSteal, MIRROR_HTTP_FILTER_VERSION, MirrorType, StealType},
+    tcp::{HttpFilter, LayerTcp, LayerTcpSteal, MIRROR_HTTP_FILTER_VERSION, MirrorType, StealType},
 };
 
 /// Retrieves subscribed port from the given [`StealType`].
@@ -12,6 +12,9 @@ fn get_port(steal_type: &StealType) -> Port {
         StealType::All(port) => *port,
         StealType::FilteredHttp(port, _) => *port,
         StealType::FilteredHttpEx(port, _) => *port,
+        StealType::FilteredSni(port, _) => *port,
+        StealType::FilteredPostgres(port, _) => *port,
+        StealType::FilteredRedis(port, _) => *port,
     }
 }
 
@@ -26,6 +29,20 @@ pub trait PortSubscriptionExt {
 
     /// Returns an unsubscribe request to be sent to the agent.
     fn wrap_agent_unsubscribe(&self) -> ClientMessage;
+
+    /// Returns a pause (or resume) request to be sent to the agent.
+    fn wrap_agent_pause(&self, paused: bool) -> ClientMessage;
+
+    /// Turns a steal subscription into the closest mirror subscription, used when the agent does
+    /// not allow stealing.
+    ///
+    /// HTTP filters are kept, other filters match whole connections and cannot be used when
+    /// mirroring, so all traffic is mirrored instead.
+    fn into_mirror(self) -> Self;
+
+    /// Adds the HTTP filter required by the agent to a steal subscription, see
+    /// [`StealType::with_http_filter`].
+    fn with_http_filter(self, required: HttpFilter) -> Self;
 }
 
 impl PortSubscriptionExt for PortSubscription {
@@ -82,4 +99,40 @@ impl PortSubscriptionExt for PortSubscription {
             }
         }
     }
+
+    /// [`LayerTcp::PortPause`], [`LayerTcp::PortResume`], [`LayerTcpSteal::PortPause`] or
+    /// [`LayerTcpSteal::PortResume`].
+    fn wrap_agent_pause(&self, paused: bool) -> ClientMessage {
+        let port = self.port();
+
+        match (self, paused) {
+            (Self::Mirror(..), true) => ClientMessage::Tcp(LayerTcp::PortPause(port)),
+            (Self::Mirror(..), false) => ClientMessage::Tcp(LayerTcp::PortResume(port)),
+            (Self::Steal(..), true) => ClientMessage::TcpSteal(LayerTcpSteal::PortPause(port)),
+            (Self::Steal(..), false) => ClientMessage::TcpSteal(LayerTcpSteal::PortResume(port)),
+        }
+    }
+
+    fn into_mirror(self) -> Self {
+        let Self::Steal(steal_type) = self else {
+            return self;
+        };
+
+        let mirror_type = match steal_type {
+            StealType::FilteredHttp(port, filter) => {
+                MirrorType::FilteredHttp(port, HttpFilter::Header(filter))
+            }
+            StealType::FilteredHttpEx(port, filter) => MirrorType::FilteredHttp(port, filter),
+            other => MirrorType::All(get_port(&other)),
+        };
+
+        Self::Mirror(mirror_type)
+    }
+
+    fn with_http_filter(self, required: HttpFilter) -> Self {
+        match self {
+            Self::Steal(steal_type) => Self::Steal(steal_type.with_http_filter(required)),
+            mirror @ Self::Mirror(..) => mirror,
+        }
+    }
 }
@@ -0,0 +1,69 @@
+use std::{collections::HashMap, net::SocketAddr};
+
+use mirrord_config::feature::network::incoming::replicas::ReplicasConfig;
+use mirrord_protocol::Port;
+
+/// Distributes stolen connections and requests between the user application and its local
+/// replicas, as configured with [`ReplicasConfig`]s.
+#[derive(Default)]
+pub struct Replicas {
+    /// Maps remote ports to the local ports of the replicas, and the index of the next target.
+    ///
+    /// Index `0` is the user application, the following indices are the replicas.
+    ports: HashMap<Port, (Vec<u16>, usize)>,
+}
+
+impl Replicas {
+    pub fn new(config: Vec<ReplicasConfig>) -> Self {
+        let ports = config
+            .into_iter()
+            .map(|replicas| (replicas.port, (replicas.local_ports, 0)))
+            .collect();
+
+        Self { ports }
+    }
+
+    /// Returns the address that should receive the next stolen connection or request on the
+    /// given remote port.
+    ///
+    /// `listening_on` is the address of the user application's listener. The replicas are
+    /// expected to listen on the same IP.
+    pub fn next_address(&mut self, port: Port, listening_on: SocketAddr) -> SocketAddr {
+        let Some((local_ports, next)) = self.ports.get_mut(&port) else {
+            return listening_on;
+        };
+
+        let current = *next;
+        *next = (current + 1) % (local_ports.len() + 1);
+
+        match current.checked_sub(1) {
+            None => listening_on,
+            Some(replica) => SocketAddr::new(listening_on.ip(), local_ports[replica]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+
+    use mirrord_config::feature::network::incoming::replicas::ReplicasConfig;
+
+    use super::Replicas;
+
+    #[test]
+    fn round_robin() {
+        let mut replicas = Replicas::new(vec![ReplicasConfig {
+            port: 80,
+            local_ports: vec![8081, 8082],
+        }]);
+        let listening_on: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+
+        let ports = std::iter::repeat_with(|| replicas.next_address(80, listening_on).port())
+            .take(4)
+            .collect::<Vec<_>>();
+        assert_eq!(ports, [8080, 8081, 8082, 8080]);
+
+        assert_eq!(replicas.next_address(81, listening_on), listening_on);
+    }
+}
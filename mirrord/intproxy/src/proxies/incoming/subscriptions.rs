@@ -122,15 +122,15 @@ impl Subscription {
         Ok(responses)
     }
 
-    /// Removed a source from this subscription.
-    /// If this source is the last one, returns [`Err`] with a message to be sent to the agent.
+    /// Removes all sources with the given `listening_on` address from this subscription.
+    ///
+    /// There can be many of them, e.g. when multiple processes listen on the same address with
+    /// `SO_REUSEPORT`.
+    ///
+    /// If no sources are left, returns [`Err`] with a message to be sent to the agent.
     fn remove_source(mut self, listening_on: SocketAddr) -> Result<Self, Box<ClientMessage>> {
-        let queue_size = self.queued_sources.len();
         self.queued_sources
             .retain(|source| source.request.listening_on != listening_on);
-        if queue_size != self.queued_sources.len() {
-            return Ok(self);
-        }
 
         if self.active_source.request.listening_on != listening_on {
             return Ok(self);
@@ -283,6 +283,9 @@ impl SubscriptionsManager {
                 }
                 | ResponseError::ForbiddenWithReason {
                     ref blocked_action, ..
+                }
+                | ResponseError::QuotaExceeded {
+                    ref blocked_action, ..
                 }),
             ) => {
                 tracing::warn!(%response_error, "Port subscribe blocked by policy");
@@ -545,4 +548,69 @@ mod test {
             .unwrap();
         assert!(responses.is_empty(), "{responses:?}");
     }
+
+    /// Multiple processes listening on the same address with `SO_REUSEPORT` share the
+    /// subscription, which is removed only when the last of them unsubscribes.
+    #[test]
+    fn with_reuse_port_listeners() {
+        let listening_on = "127.0.0.1:1111".parse().unwrap();
+
+        let mut manager = SubscriptionsManager::default();
+
+        let response = manager.layer_subscribed(
+            LayerId(0),
+            0,
+            PortSubscribe {
+                listening_on,
+                subscription: PortSubscription::Mirror(MirrorType::All(80)),
+            },
+            None,
+        );
+        assert!(
+            matches!(
+                response,
+                Some(Either::Right(ClientMessage::Tcp(LayerTcp::PortSubscribe(
+                    80
+                ))))
+            ),
+            "{response:?}"
+        );
+
+        let response = manager.layer_subscribed(
+            LayerId(1),
+            0,
+            PortSubscribe {
+                listening_on,
+                subscription: PortSubscription::Mirror(MirrorType::All(80)),
+            },
+            None,
+        );
+        assert!(response.is_none(), "{response:?}");
+
+        let responses = manager.agent_responded(Ok(80)).unwrap();
+        assert_eq!(responses.len(), 2, "{responses:?}");
+
+        let response = manager.layer_unsubscribed(
+            LayerId(0),
+            PortUnsubscribe {
+                port: 80,
+                listening_on,
+            },
+        );
+        assert!(response.is_none(), "{response:?}");
+        assert_eq!(manager.get(80).unwrap().listening_on, listening_on);
+
+        let response = manager.layer_unsubscribed(
+            LayerId(1),
+            PortUnsubscribe {
+                port: 80,
+                listening_on,
+            },
+        );
+        assert!(matches!(
+            response,
+            Some(ClientMessage::Tcp(LayerTcp::PortUnsubscribe(80)))
+        ));
+        assert!(manager.get(80).is_none());
+    }
 }
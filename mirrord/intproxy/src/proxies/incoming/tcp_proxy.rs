@@ -1,16 +1,23 @@
-use std::{io::ErrorKind, net::SocketAddr, ops::Not, sync::Arc, time::Duration};
+use std::{
+    io::{self, ErrorKind},
+    net::SocketAddr,
+    ops::Not,
+    sync::Arc,
+    time::Duration,
+};
 
 use bytes::BytesMut;
 use hyper::upgrade::OnUpgrade;
 use hyper_util::rt::TokioIo;
 use mirrord_protocol::{
     ClientMessage, ConnectionId,
-    tcp::{IncomingTrafficTransportType, LayerTcpSteal, TcpData},
+    tcp::{IncomingTrafficTransportType, LayerTcpSteal, TcpData, TcpShutdownWrite},
 };
 use mirrord_tls_util::MaybeTls;
 use rustls::pki_types::ServerName;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
     time,
 };
 use tokio_rustls::TlsStream;
@@ -18,6 +25,7 @@ use tracing::Level;
 
 use super::{
     bound_socket::BoundTcpSocket,
+    local_app_wait::{LocalAppWait, is_refused},
     tasks::{InProxyTaskError, InProxyTaskMessage},
     tls::LocalTlsSetup,
 };
@@ -33,6 +41,8 @@ pub enum LocalTcpConnection {
         peer: SocketAddr,
         transport: IncomingTrafficTransportType,
         tls_setup: Option<Arc<LocalTlsSetup>>,
+        /// Allows for waiting until the user application accepts connections.
+        wait: Option<LocalAppWait>,
     },
     /// Upgraded HTTP connection from a previously stolen HTTP request.
     AfterUpgrade(OnUpgrade),
@@ -47,8 +57,9 @@ impl LocalTcpConnection {
                 peer,
                 transport,
                 tls_setup,
+                wait,
             } => {
-                let stream = socket.connect(peer).await?;
+                let stream = Self::connect_waiting(socket, peer, wait).await?;
                 let stream = match (transport, tls_setup) {
                     (IncomingTrafficTransportType::Tcp, ..) => MaybeTls::NoTls(stream),
                     (.., None) => MaybeTls::NoTls(stream),
@@ -87,6 +98,42 @@ impl LocalTcpConnection {
             }
         }
     }
+
+    /// Connects the given socket to the peer.
+    ///
+    /// If `wait` is given and the user application refuses the connection, keeps trying to
+    /// connect from the same local address until the application starts accepting connections.
+    async fn connect_waiting(
+        socket: BoundTcpSocket,
+        peer: SocketAddr,
+        wait: Option<LocalAppWait>,
+    ) -> io::Result<TcpStream> {
+        let Some(wait) = wait else {
+            return socket.connect(peer).await;
+        };
+
+        let local_addr = socket.local_addr()?;
+        let error = match socket.connect(peer).await {
+            Err(error) if is_refused(&error) => error,
+            result => return result,
+        };
+
+        let Some(waiting) = wait.start() else {
+            return Err(error);
+        };
+        tracing::info!(%peer, "Local application refused the connection, waiting for it");
+
+        loop {
+            if waiting.next_attempt().await.not() {
+                return Err(error);
+            }
+
+            match BoundTcpSocket::bind_exact(local_addr)?.connect(peer).await {
+                Err(error) if is_refused(&error) => continue,
+                result => return result,
+            }
+        }
+    }
 }
 
 /// [`BackgroundTask`] of [`IncomingProxy`](super::IncomingProxy) that handles a remote
@@ -111,6 +158,10 @@ pub struct TcpProxyTask {
     /// `true`, the task will silently discard all outbound traffic
     /// from the application.
     mirror: bool,
+
+    /// Whether the agent supports [`LayerTcpSteal::ShutdownWrite`]. When `false`, shutdown of the
+    /// user application's writing half is reported with an empty [`LayerTcpSteal::Data`].
+    shutdown_write: bool,
 }
 
 impl TcpProxyTask {
@@ -123,11 +174,19 @@ impl TcpProxyTask {
     /// * This task will talk with the user application using the given [`LocalTcpConnection`].
     /// * If `discard_data` is set, this task will silently discard all data coming from the user
     ///   application.
-    pub fn new(connection_id: ConnectionId, connection: LocalTcpConnection, mirror: bool) -> Self {
+    /// * If `shutdown_write` is set, this task will use [`LayerTcpSteal::ShutdownWrite`] to notify
+    ///   the agent about the user application shutting down its side of the connection.
+    pub fn new(
+        connection_id: ConnectionId,
+        connection: LocalTcpConnection,
+        mirror: bool,
+        shutdown_write: bool,
+    ) -> Self {
         Self {
             connection_id,
             connection: Some(connection),
             mirror,
+            shutdown_write,
         }
     }
 }
@@ -148,7 +207,21 @@ impl BackgroundTask for TcpProxyTask {
             .take()
             .expect("task should have a valid connection before run");
 
-        let (mut stream, read_buf) = connection.connect().await?;
+        // In steal mode, we may wait a while for the user application, see
+        // [`LocalAppWait`]. Give up when the remote connection is closed.
+        let (mut stream, read_buf) = if self.mirror {
+            connection.connect().await?
+        } else {
+            match message_bus
+                .closed_token()
+                .clone()
+                .run_until_cancelled(connection.connect())
+                .await
+            {
+                Some(result) => result?,
+                None => return Ok(()),
+            }
+        };
 
         if self.mirror.not() && read_buf.is_empty().not() {
             // We don't send empty data,
@@ -191,12 +264,17 @@ impl BackgroundTask for TcpProxyTask {
                         }
 
                         if !self.mirror {
-                            let msg =
-                                ClientMessage::TcpSteal(LayerTcpSteal::Data(TcpData {
+                            let msg = if reading_closed && self.shutdown_write {
+                                LayerTcpSteal::ShutdownWrite(TcpShutdownWrite {
+                                    connection_id: self.connection_id,
+                                })
+                            } else {
+                                LayerTcpSteal::Data(TcpData {
                                     connection_id: self.connection_id,
                                     bytes: buf.clone().into(),
-                                }));
-                            message_bus.send_agent(msg).await;
+                                })
+                            };
+                            message_bus.send_agent(ClientMessage::TcpSteal(msg)).await;
                         }
 
                         buf.clear();
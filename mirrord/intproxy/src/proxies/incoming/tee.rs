@@ -0,0 +1,175 @@
+//! Appending the HTTP requests delivered to the user application to a capture file, see
+//! [`RequestTee`].
+
+use std::{
+    fs::OpenOptions,
+    io,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use http_body_util::BodyExt;
+use mirrord_config::feature::network::incoming::tee::TeeConfig;
+use mirrord_protocol::{ConnectionId, Port, RequestId, tcp::HttpRequest};
+use serde::Serialize;
+use tokio::{fs::File, io::AsyncWriteExt, sync::Mutex};
+
+use super::http::StreamingBody;
+
+/// A single line of the capture file.
+#[derive(Serialize)]
+struct CapturedRequest {
+    /// When the request was received from the agent, in milliseconds since the Unix epoch.
+    timestamp_ms: u64,
+    /// `steal` or `mirror`.
+    mode: &'static str,
+    connection_id: ConnectionId,
+    request_id: RequestId,
+    port: Port,
+    method: String,
+    uri: String,
+    /// E.g. `HTTP/1.1`.
+    version: String,
+    /// In the order they were received, values that are not valid UTF-8 are converted lossily.
+    headers: Vec<(String, String)>,
+    /// Base64-encoded, at most [`TeeConfig::max_body_bytes`] of the body.
+    body: String,
+    body_truncated: bool,
+}
+
+/// Appends the HTTP requests delivered to the user application to the capture file from
+/// [`TeeConfig`], as JSON lines.
+///
+/// The body of each request is captured in a separate task, as the frames arrive from the agent.
+/// The [`StreamingBody`] is cloned for this, so delivery to the user application is not delayed.
+#[derive(Clone)]
+pub struct RequestTee {
+    file: Arc<Mutex<File>>,
+    max_body_bytes: u64,
+}
+
+impl RequestTee {
+    /// Opens the capture file for appending, creating it if it does not exist.
+    pub fn new(config: &TeeConfig) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)?;
+
+        Ok(Self {
+            file: Arc::new(Mutex::new(File::from_std(file))),
+            max_body_bytes: config.max_body_bytes,
+        })
+    }
+
+    /// Captures the request in the background.
+    pub fn record(&self, request: &HttpRequest<StreamingBody>, is_steal: bool) {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let internal = &request.internal_request;
+        let mut captured = CapturedRequest {
+            timestamp_ms,
+            mode: if is_steal { "steal" } else { "mirror" },
+            connection_id: request.connection_id,
+            request_id: request.request_id,
+            port: request.port,
+            method: internal.method.to_string(),
+            uri: internal.uri.to_string(),
+            version: format!("{:?}", internal.version),
+            headers: internal
+                .headers
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.to_string(),
+                        String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                    )
+                })
+                .collect(),
+            body: Default::default(),
+            body_truncated: false,
+        };
+
+        let body = internal.body.clone();
+        let tee = self.clone();
+        tokio::spawn(async move {
+            let (body, truncated) = collect_body(body, tee.max_body_bytes).await;
+            captured.body = STANDARD.encode(body);
+            captured.body_truncated = truncated;
+
+            if let Err(error) = tee.append(&captured).await {
+                tracing::error!(
+                    %error,
+                    connection_id = captured.connection_id,
+                    request_id = captured.request_id,
+                    "Failed to append an HTTP request to the capture file",
+                );
+            }
+        });
+    }
+
+    async fn append(&self, captured: &CapturedRequest) -> io::Result<()> {
+        let mut line = serde_json::to_vec(captured)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().await;
+        file.write_all(&line).await?;
+        file.flush().await
+    }
+}
+
+/// Reads at most `max_bytes` of the body.
+///
+/// Returns the bytes and whether the body was longer.
+async fn collect_body(mut body: StreamingBody, max_bytes: u64) -> (Vec<u8>, bool) {
+    let mut collected = Vec::new();
+
+    while let Some(Ok(frame)) = body.frame().await {
+        let Ok(data) = frame.into_data() else {
+            continue;
+        };
+
+        let remaining = (max_bytes as usize).saturating_sub(collected.len());
+        if data.len() > remaining {
+            collected.extend_from_slice(data.get(..remaining).unwrap_or_default());
+            return (collected, true);
+        }
+
+        collected.extend_from_slice(&data);
+    }
+
+    (collected, false)
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use mirrord_protocol::tcp::InternalHttpBodyFrame;
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(16, b"hello world".as_slice(), false)]
+    #[case(11, b"hello world".as_slice(), false)]
+    #[case(7, b"hello w".as_slice(), true)]
+    #[tokio::test]
+    async fn body_truncated_at_limit(
+        #[case] max_bytes: u64,
+        #[case] expected: &[u8],
+        #[case] truncated: bool,
+    ) {
+        let body = StreamingBody::from(vec![
+            InternalHttpBodyFrame::Data(Bytes::from_static(b"hello ").into()),
+            InternalHttpBodyFrame::Data(Bytes::from_static(b"world").into()),
+        ]);
+
+        assert_eq!(
+            collect_body(body, max_bytes).await,
+            (expected.to_vec(), truncated)
+        );
+    }
+}
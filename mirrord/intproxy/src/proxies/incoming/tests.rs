@@ -4,11 +4,14 @@ use bytes::Bytes;
 use futures::FutureExt;
 use http_body_util::{StreamBody, combinators::BoxBody};
 use hyper::{
-    Method, Request, Response, Version,
+    Method, Request, Response, StatusCode, Version,
     body::{Frame, Incoming},
     service::Service,
 };
 use hyper_util::rt::TokioIo;
+use mirrord_config::feature::network::incoming::request_limit::{
+    RequestLimitAction, RequestLimitConfig,
+};
 use mirrord_intproxy_protocol::{
     IncomingRequest, IncomingResponse, LayerId, PortSubscribe, PortSubscription,
     ProxyToLayerMessage,
@@ -18,8 +21,8 @@ use mirrord_protocol::{
     tcp::{
         ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, ChunkedResponse, DaemonTcp,
         HttpFilter, HttpMethodFilter, HttpRequestMetadata, IncomingTrafficTransportType,
-        InternalHttpBodyFrame, InternalHttpBodyNew, InternalHttpRequest, LayerTcpSteal, StealType,
-        TcpClose,
+        InternalHttpBodyFrame, InternalHttpBodyNew, InternalHttpRequest, LayerTcp, LayerTcpSteal,
+        MirrorLimits, MirrorType, PortSubscribeWithLimits, StealType, TcpClose,
     },
 };
 use mirrord_protocol_io::Connection;
@@ -62,7 +65,18 @@ async fn http_request_terminates_on_remote_close(#[case] steal_type: StealType)
     let local_addr = local_listener.local_addr().unwrap();
 
     let (conn, _, out) = Connection::dummy();
-    let proxy = IncomingProxy::new(Duration::from_secs(3), Default::default());
+    let proxy = IncomingProxy::new(
+        Duration::from_secs(3),
+        Default::default(),
+        None,
+        Default::default(),
+        None,
+        false,
+        None,
+        None,
+        None,
+        None,
+    );
     let mut background_tasks: BackgroundTasks<(), ProxyMessage, IncomingProxyError> =
         BackgroundTasks::new(conn.tx_handle());
 
@@ -187,3 +201,169 @@ async fn http_request_terminates_on_remote_close(#[case] steal_type: StealType)
         panic!("{error}");
     }
 }
+
+/// Verifies that [`IncomingProxy`] responds with [`StatusCode::SERVICE_UNAVAILABLE`] to stolen
+/// HTTP requests that exceed the [`RequestLimitConfig`] in the reject mode.
+#[tokio::test]
+async fn http_request_rejected_over_limit() {
+    let local_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let local_addr = local_listener.local_addr().unwrap();
+
+    let (conn, _, out) = Connection::dummy();
+    let proxy = IncomingProxy::new(
+        Duration::from_secs(3),
+        Default::default(),
+        Some(RequestLimitConfig {
+            max_in_flight: 1,
+            when_exceeded: RequestLimitAction::Reject,
+        }),
+        Default::default(),
+        None,
+        false,
+        None,
+        None,
+        None,
+        None,
+    );
+    let mut background_tasks: BackgroundTasks<(), ProxyMessage, IncomingProxyError> =
+        BackgroundTasks::new(conn.tx_handle());
+
+    let proxy = background_tasks.register(proxy, (), 8);
+
+    proxy
+        .send(IncomingProxyMessage::AgentProtocolVersion(
+            mirrord_protocol::VERSION.clone(),
+        ))
+        .await;
+
+    proxy
+        .send(IncomingProxyMessage::LayerRequest(
+            0,
+            LayerId(0),
+            IncomingRequest::PortSubscribe(PortSubscribe {
+                listening_on: local_addr,
+                subscription: PortSubscription::Steal(StealType::All(80)),
+            }),
+        ))
+        .await;
+    assert_eq!(
+        out.next().await.unwrap(),
+        ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(StealType::All(80))),
+    );
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(
+            DaemonTcp::SubscribeResult(Ok(80)),
+        ))
+        .await;
+    background_tasks.next().await.unwrap().1.unwrap_message();
+
+    // Local server that never finishes its responses, so the first request keeps its slot.
+    tokio::spawn(async move {
+        let (conn, _) = local_listener.accept().await.unwrap();
+        let _ = hyper::server::conn::http1::Builder::new()
+            .serve_connection(TokioIo::new(conn), SseService)
+            .await;
+    });
+
+    for request_id in 0..2 {
+        proxy
+            .send(IncomingProxyMessage::AgentSteal(
+                DaemonTcp::HttpRequestChunked(ChunkedRequest::StartV2(ChunkedRequestStartV2 {
+                    connection_id: 0,
+                    request_id,
+                    metadata: HttpRequestMetadata::V1 {
+                        source: "127.0.0.1:55555".parse().unwrap(),
+                        destination: "127.0.0.1:80".parse().unwrap(),
+                    },
+                    transport: IncomingTrafficTransportType::Tcp,
+                    request: InternalHttpRequest {
+                        method: Method::GET,
+                        uri: "http://127.0.0.1:80/hello/there".parse().unwrap(),
+                        version: Version::HTTP_11,
+                        headers: Default::default(),
+                        body: InternalHttpBodyNew {
+                            frames: Default::default(),
+                            is_last: true,
+                        },
+                    },
+                })),
+            ))
+            .await;
+    }
+
+    let response = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match out.next().await.unwrap() {
+                ClientMessage::TcpSteal(LayerTcpSteal::HttpResponse(response)) => break response,
+                ClientMessage::TcpSteal(LayerTcpSteal::HttpResponseChunked(..)) => {}
+                other => panic!("unexpected message: {other:?}"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(response.request_id, 1);
+    assert_eq!(
+        response.internal_response.status,
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+}
+
+/// Verifies that [`IncomingProxy`] sends mirror subscriptions with
+/// [`LayerTcp::PortSubscribeWithLimits`] when the mirrored traffic is limited, and falls back to
+/// a regular subscription when the agent does not support limits.
+#[rstest]
+#[case::supported(mirrord_protocol::VERSION.clone(), true)]
+#[case::unsupported("1.39.0".parse().unwrap(), false)]
+#[tokio::test]
+async fn mirror_subscription_with_limits(
+    #[case] protocol_version: semver::Version,
+    #[case] with_limits: bool,
+) {
+    let (conn, _, out) = Connection::dummy();
+    let proxy = IncomingProxy::new(
+        Duration::from_secs(3),
+        Default::default(),
+        None,
+        Default::default(),
+        None,
+        false,
+        Some(1024),
+        None,
+        None,
+        None,
+    );
+    let mut background_tasks: BackgroundTasks<(), ProxyMessage, IncomingProxyError> =
+        BackgroundTasks::new(conn.tx_handle());
+
+    let proxy = background_tasks.register(proxy, (), 8);
+
+    proxy
+        .send(IncomingProxyMessage::AgentProtocolVersion(protocol_version))
+        .await;
+
+    proxy
+        .send(IncomingProxyMessage::LayerRequest(
+            0,
+            LayerId(0),
+            IncomingRequest::PortSubscribe(PortSubscribe {
+                listening_on: "127.0.0.1:8080".parse().unwrap(),
+                subscription: PortSubscription::Mirror(MirrorType::All(80)),
+            }),
+        ))
+        .await;
+
+    let expected = if with_limits {
+        LayerTcp::PortSubscribeWithLimits(PortSubscribeWithLimits {
+            port: 80,
+            filter: None,
+            limits: MirrorLimits {
+                max_bytes_per_second: 1024,
+            },
+        })
+    } else {
+        LayerTcp::PortSubscribe(80)
+    };
+    assert_eq!(out.next().await.unwrap(), ClientMessage::Tcp(expected));
+}
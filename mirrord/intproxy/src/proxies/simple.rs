@@ -8,6 +8,8 @@ use mirrord_protocol::{
     ClientMessage, DaemonMessage, DnsLookupError, GetEnvVarsRequest, RemoteResult,
     ResolveErrorKindInternal, ResponseError,
     dns::{ADDRINFO_V2_VERSION, AddressFamily, GetAddrInfoRequestV2, GetAddrInfoResponse},
+    interfaces::{GET_INTERFACES_VERSION, GetInterfacesRequest, GetInterfacesResult},
+    outgoing::icmp::{ICMP_ECHO_VERSION, IcmpEchoRequest, IcmpEchoResult},
 };
 use semver::Version;
 use thiserror::Error;
@@ -27,6 +29,10 @@ pub enum SimpleProxyMessage {
     AddrInfoRes(GetAddrInfoResponse),
     GetEnvReq(MessageId, LayerId, GetEnvVarsRequest),
     GetEnvRes(RemoteResult<HashMap<String, String>>),
+    IcmpEchoReq(MessageId, LayerId, IcmpEchoRequest),
+    IcmpEchoRes(IcmpEchoResult),
+    GetInterfacesReq(MessageId, LayerId, GetInterfacesRequest),
+    GetInterfacesRes(GetInterfacesResult),
     /// Protocol version was negotiated with the agent.
     ProtocolVersion(Version),
     ConnectionRefresh(ConnectionRefresh),
@@ -49,6 +55,8 @@ pub enum SimpleProxyError {
 pub enum AgentLostSimpleResponseKind {
     AddrInfo,
     GetEnv,
+    IcmpEcho,
+    GetInterfaces,
 }
 
 /// Lightweight (no allocations) [`ProxyMessage`] to be returned when connection with the
@@ -63,6 +71,18 @@ impl AgentLostSimpleResponse {
     pub fn get_env(layer_id: LayerId, message_id: MessageId) -> Self {
         AgentLostSimpleResponse(AgentLostSimpleResponseKind::GetEnv, layer_id, message_id)
     }
+
+    pub fn icmp_echo(layer_id: LayerId, message_id: MessageId) -> Self {
+        AgentLostSimpleResponse(AgentLostSimpleResponseKind::IcmpEcho, layer_id, message_id)
+    }
+
+    pub fn get_interfaces(layer_id: LayerId, message_id: MessageId) -> Self {
+        AgentLostSimpleResponse(
+            AgentLostSimpleResponseKind::GetInterfaces,
+            layer_id,
+            message_id,
+        )
+    }
 }
 
 impl From<AgentLostSimpleResponse> for ToLayer {
@@ -75,6 +95,10 @@ impl From<AgentLostSimpleResponse> for ToLayer {
                 ProxyToLayerMessage::GetAddrInfo(GetAddrInfoResponse(Err(error)))
             }
             AgentLostSimpleResponseKind::GetEnv => ProxyToLayerMessage::GetEnv(Err(error)),
+            AgentLostSimpleResponseKind::IcmpEcho => ProxyToLayerMessage::IcmpEcho(Err(error)),
+            AgentLostSimpleResponseKind::GetInterfaces => {
+                ProxyToLayerMessage::GetInterfaces(Err(error))
+            }
         };
 
         ToLayer {
@@ -92,6 +116,10 @@ pub struct SimpleProxy {
     addr_info_reqs: RequestQueue,
     /// For [`GetEnvVarsRequest`]s.
     get_env_reqs: RequestQueue,
+    /// For [`IcmpEchoRequest`]s.
+    icmp_echo_reqs: RequestQueue,
+    /// For [`GetInterfacesRequest`]s.
+    get_interfaces_reqs: RequestQueue,
     /// [`mirrord_protocol`] version negotiated with the agent.
     /// Determines whether we can use `GetAddrInfoRequestV2`.
     protocol_version: Option<Version>,
@@ -104,6 +132,8 @@ impl SimpleProxy {
         Self {
             addr_info_reqs: Default::default(),
             get_env_reqs: Default::default(),
+            icmp_echo_reqs: Default::default(),
+            get_interfaces_reqs: Default::default(),
             protocol_version: Default::default(),
             dns_permission_error_fatal,
         }
@@ -120,6 +150,20 @@ impl SimpleProxy {
             .is_some_and(|version| ADDRINFO_V2_VERSION.matches(version))
     }
 
+    /// Returns whether [`mirrord_protocol`] version allows for [`IcmpEchoRequest`]s.
+    fn icmp_echo(&self) -> bool {
+        self.protocol_version
+            .as_ref()
+            .is_some_and(|version| ICMP_ECHO_VERSION.matches(version))
+    }
+
+    /// Returns whether [`mirrord_protocol`] version allows for [`GetInterfacesRequest`]s.
+    fn get_interfaces(&self) -> bool {
+        self.protocol_version
+            .as_ref()
+            .is_some_and(|version| GET_INTERFACES_VERSION.matches(version))
+    }
+
     #[tracing::instrument(level = Level::INFO, skip_all)]
     async fn handle_connection_refresh(
         &mut self,
@@ -152,6 +196,30 @@ impl SimpleProxy {
                         .await;
                 }
 
+                tracing::debug!(
+                    num_responses = self.icmp_echo_reqs.len(),
+                    "Flushing error responses to IcmpEchoRequests"
+                );
+                while let Some((message_id, layer_id)) = self.icmp_echo_reqs.pop_front() {
+                    message_bus
+                        .send(ToLayer::from(AgentLostSimpleResponse::icmp_echo(
+                            layer_id, message_id,
+                        )))
+                        .await;
+                }
+
+                tracing::debug!(
+                    num_responses = self.get_interfaces_reqs.len(),
+                    "Flushing error responses to GetInterfacesRequests"
+                );
+                while let Some((message_id, layer_id)) = self.get_interfaces_reqs.pop_front() {
+                    message_bus
+                        .send(ToLayer::from(AgentLostSimpleResponse::get_interfaces(
+                            layer_id, message_id,
+                        )))
+                        .await;
+                }
+
                 // Reset protocol version since we'll need another negotiation
                 // round for the new connection.
                 self.protocol_version = None;
@@ -235,6 +303,66 @@ impl BackgroundTask for SimpleProxy {
                         })
                         .await
                 }
+                SimpleProxyMessage::IcmpEchoReq(message_id, layer_id, _) if !self.icmp_echo() => {
+                    message_bus
+                        .send(ToLayer {
+                            message_id,
+                            message: ProxyToLayerMessage::IcmpEcho(Err(
+                                ResponseError::NotImplemented,
+                            )),
+                            layer_id,
+                        })
+                        .await
+                }
+                SimpleProxyMessage::IcmpEchoReq(message_id, layer_id, req) => {
+                    self.icmp_echo_reqs.push_back(message_id, layer_id);
+                    message_bus.send_agent(ClientMessage::IcmpEcho(req)).await;
+                }
+                SimpleProxyMessage::IcmpEchoRes(res) => {
+                    let (message_id, layer_id) =
+                        self.icmp_echo_reqs.pop_front().ok_or_else(|| {
+                            UnexpectedAgentMessage(DaemonMessage::IcmpEcho(res.clone()).into())
+                        })?;
+                    message_bus
+                        .send(ToLayer {
+                            message_id,
+                            message: ProxyToLayerMessage::IcmpEcho(res),
+                            layer_id,
+                        })
+                        .await
+                }
+                SimpleProxyMessage::GetInterfacesReq(message_id, layer_id, _)
+                    if !self.get_interfaces() =>
+                {
+                    message_bus
+                        .send(ToLayer {
+                            message_id,
+                            message: ProxyToLayerMessage::GetInterfaces(Err(
+                                ResponseError::NotImplemented,
+                            )),
+                            layer_id,
+                        })
+                        .await
+                }
+                SimpleProxyMessage::GetInterfacesReq(message_id, layer_id, req) => {
+                    self.get_interfaces_reqs.push_back(message_id, layer_id);
+                    message_bus
+                        .send_agent(ClientMessage::GetInterfaces(req))
+                        .await;
+                }
+                SimpleProxyMessage::GetInterfacesRes(res) => {
+                    let (message_id, layer_id) =
+                        self.get_interfaces_reqs.pop_front().ok_or_else(|| {
+                            UnexpectedAgentMessage(DaemonMessage::GetInterfaces(res.clone()).into())
+                        })?;
+                    message_bus
+                        .send(ToLayer {
+                            message_id,
+                            message: ProxyToLayerMessage::GetInterfaces(res),
+                            layer_id,
+                        })
+                        .await
+                }
                 SimpleProxyMessage::ProtocolVersion(version) => self.set_protocol_version(version),
                 SimpleProxyMessage::ConnectionRefresh(new_agent_tx) => {
                     self.handle_connection_refresh(message_bus, new_agent_tx)
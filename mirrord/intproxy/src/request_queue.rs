@@ -74,6 +74,12 @@ impl<T: fmt::Debug> RequestQueue<T> {
         Some((message_id, layer_id, data))
     }
 
+    /// Returns the request at the front of this queue, without removing it.
+    pub fn front(&self) -> Option<(MessageId, LayerId, &T)> {
+        let (message_id, layer_id, data) = self.inner.front()?;
+        Some((*message_id, *layer_id, data))
+    }
+
     pub fn len(&self) -> usize {
         self.inner.len()
     }
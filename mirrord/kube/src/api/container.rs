@@ -1,7 +1,9 @@
 use std::{collections::HashSet, net::IpAddr, sync::LazyLock, time::Duration};
 
 use k8s_openapi::api::core::v1::{ContainerStatus, Pod};
-use mirrord_agent_env::{mesh::MeshVendor, steal_tls::StealPortTlsConfig};
+use mirrord_agent_env::{
+    mesh::MeshVendor, redaction::AgentLogRedaction, steal_tls::StealPortTlsConfig,
+};
 use mirrord_config::agent::AgentConfig;
 use mirrord_progress::Progress;
 use rand::distr::{Alphanumeric, SampleString};
@@ -11,6 +13,7 @@ use crate::{api::kubernetes::AgentKubernetesConnectInfo, error::Result};
 pub mod ephemeral;
 pub mod job;
 pub mod pod;
+pub mod pull_secret;
 pub mod targeted;
 pub mod targetless;
 pub mod util;
@@ -47,6 +50,13 @@ pub struct ContainerConfig {
     pub steal_tls_config: Vec<StealPortTlsConfig>,
     /// How long the agent should keep running after all client connections have been closed.
     pub idle_ttl: Duration,
+    /// Value for [`QUIC`](mirrord_agent_env::envs::QUIC) set in the agent container.
+    pub quic: bool,
+    /// Value for [`LOG_REDACTION`](mirrord_agent_env::envs::LOG_REDACTION) set in the agent
+    /// container.
+    pub log_redaction: Option<AgentLogRedaction>,
+    /// Value for [`PROCFS`](mirrord_agent_env::envs::PROCFS) set in the agent container.
+    pub procfs: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -68,6 +78,13 @@ pub struct ContainerParams {
     pub steal_tls_config: Vec<StealPortTlsConfig>,
     /// How long the agent should keep running after all client connections have been closed.
     pub idle_ttl: Duration,
+    /// Value for [`QUIC`](mirrord_agent_env::envs::QUIC) set in the agent container.
+    pub quic: bool,
+    /// Value for [`LOG_REDACTION`](mirrord_agent_env::envs::LOG_REDACTION) set in the agent
+    /// container.
+    pub log_redaction: Option<AgentLogRedaction>,
+    /// Value for [`PROCFS`](mirrord_agent_env::envs::PROCFS) set in the agent container.
+    pub procfs: bool,
 }
 
 impl From<ContainerConfig> for ContainerParams {
@@ -93,6 +110,9 @@ impl From<ContainerConfig> for ContainerParams {
             support_ipv6: value.support_ipv6,
             steal_tls_config: value.steal_tls_config,
             idle_ttl: value.idle_ttl,
+            quic: value.quic,
+            log_redaction: value.log_redaction,
+            procfs: value.procfs,
         }
     }
 }
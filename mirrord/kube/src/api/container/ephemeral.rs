@@ -9,7 +9,7 @@ use kube::{
 };
 use mirrord_agent_env::{envs, mesh::MeshVendor};
 use mirrord_config::agent::AgentConfig;
-use mirrord_progress::Progress;
+use mirrord_progress::{ExecPhase, Progress};
 use tokio::pin;
 use tracing::debug;
 
@@ -128,6 +128,7 @@ where
         pod_name = &runtime_data.pod_name,
     )));
 
+    progress.phase(ExecPhase::WaitingForAgent);
     let mut container_progress = progress.subtask("waiting for container to be ready...");
 
     let stream = watcher(pod_api.clone(), watcher_config).applied_objects();
@@ -142,9 +143,9 @@ where
         }
     }
 
-    let version =
+    let agent_ready =
         wait_for_agent_startup(&pod_api, &runtime_data.pod_name, params.name.clone()).await?;
-    match version.as_ref() {
+    match agent_ready.version.as_ref() {
         Some(version) if version != env!("CARGO_PKG_VERSION") => {
             let message = format!(
                 "Agent version {version} does not match the local mirrord version {}. This may lead to unexpected errors.",
@@ -162,6 +163,8 @@ where
         pod_name: runtime_data.pod_name.to_string(),
         pod_namespace: runtime_data.pod_namespace.clone(),
         agent_port: params.port,
+        quic_cert: agent_ready.quic_cert,
+        target_pod: None,
     })
 }
 
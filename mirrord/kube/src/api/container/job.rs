@@ -7,19 +7,20 @@ use std::{
 use futures::StreamExt;
 use k8s_openapi::api::{
     batch::v1::{Job, JobSpec},
-    core::v1::{Pod, PodStatus, PodTemplateSpec},
+    core::v1::{ContainerStatus, Pod, PodStatus, PodTemplateSpec},
 };
 use kube::{
     Api, Client, ResourceExt,
-    api::{ObjectMeta, PostParams},
+    api::{DeleteParams, ObjectMeta, PostParams},
     runtime::{
         WatchStreamExt,
         watcher::{self, Event, watcher},
     },
 };
 use mirrord_config::agent::AgentConfig;
-use mirrord_progress::Progress;
+use mirrord_progress::{ExecPhase, Progress};
 use tokio::{pin, time::interval};
+use tracing::warn;
 
 use crate::{
     api::{
@@ -85,6 +86,7 @@ where
         "agent pod {pod_namespace}/{pod_name} created"
     )));
 
+    progress.phase(ExecPhase::WaitingForAgent);
     let mut pod_progress = progress.subtask("waiting for pod to be ready...");
 
     let initialization_start = Instant::now();
@@ -124,6 +126,21 @@ where
                             continue;
                         };
 
+                        if let Some(reason) = image_pull_failure(agent_status) {
+                            pod_progress.failure(Some("failed to pull the agent image"));
+                            // The pod would keep retrying the pull until the job is cleaned up.
+                            if let Err(error) = job_api
+                                .delete(&params.name, &DeleteParams::background())
+                                .await
+                            {
+                                warn!(%error, "Failed to delete the agent job");
+                            }
+                            return Err(KubeApiError::AgentImagePullFailed {
+                                image: agent.image().to_owned(),
+                                reason,
+                            });
+                        }
+
                         // Ref: https://kubernetes.io/docs/concepts/workloads/pods/pod-lifecycle/#pod-phase
                         match phase.as_str() {
                             "Running" if agent_status.ready => break,
@@ -189,8 +206,9 @@ where
         }
     }
 
-    let version = wait_for_agent_startup(&pod_api, pod_name, "mirrord-agent".to_string()).await?;
-    match version.as_ref() {
+    let agent_ready =
+        wait_for_agent_startup(&pod_api, pod_name, "mirrord-agent".to_string()).await?;
+    match agent_ready.version.as_ref() {
         Some(version) if version != env!("CARGO_PKG_VERSION") => {
             let message = format!(
                 "Agent version {version} does not match the local mirrord version {}. This may lead to unexpected errors.",
@@ -207,9 +225,28 @@ where
         pod_name: pod_name.to_owned(),
         pod_namespace: pod_namespace.to_owned(),
         agent_port: params.port,
+        quic_cert: agent_ready.quic_cert,
+        target_pod: None,
     })
 }
 
+/// Container waiting reasons that mean the image cannot be pulled.
+const IMAGE_PULL_FAILURE_REASONS: &[&str] =
+    &["ErrImagePull", "ImagePullBackOff", "InvalidImageName"];
+
+/// Returns the reason why the agent container's image cannot be pulled, if that's why it's
+/// waiting.
+fn image_pull_failure(status: &ContainerStatus) -> Option<String> {
+    let waiting = status.state.as_ref()?.waiting.as_ref()?;
+    let reason = waiting.reason.as_deref()?;
+    IMAGE_PULL_FAILURE_REASONS
+        .contains(&reason)
+        .then(|| match &waiting.message {
+            Some(message) => format!("{reason} ({message})"),
+            None => reason.to_owned(),
+        })
+}
+
 /// Tries finding mirrord-agent's container in `status.container_statuses` and returns a string
 /// representing it's state.
 fn find_agent_container_state(status: &Option<PodStatus>) -> String {
@@ -380,6 +417,9 @@ mod test {
             support_ipv6,
             steal_tls_config: Default::default(),
             idle_ttl: Default::default(),
+            quic: false,
+            log_redaction: None,
+            procfs: false,
         };
 
         let update = JobVariant::new(&agent, &params).as_update();
@@ -472,6 +512,9 @@ mod test {
             support_ipv6,
             steal_tls_config: Default::default(),
             idle_ttl: Default::default(),
+            quic: false,
+            log_redaction: None,
+            procfs: false,
         };
 
         let update = JobTargetedVariant::new(
@@ -3,8 +3,8 @@ use std::collections::BTreeMap;
 use k8s_openapi::{
     DeepMerge,
     api::core::v1::{
-        Capabilities, Container, HostPathVolumeSource, LocalObjectReference, Pod, PodSpec,
-        SecurityContext, Volume, VolumeMount,
+        Capabilities, Container, HostPathVolumeSource, Pod, PodSpec, SecurityContext, Volume,
+        VolumeMount,
     },
 };
 use kube::api::ObjectMeta;
@@ -15,6 +15,7 @@ use super::util::agent_env;
 use crate::api::{
     container::{
         ContainerParams, ContainerVariant,
+        pull_secret::agent_image_pull_secrets,
         util::{DEFAULT_TOLERATIONS, base_command_line, get_capabilities},
     },
     runtime::RuntimeData,
@@ -85,14 +86,7 @@ impl ContainerVariant for PodVariant<'_> {
         });
 
         let env = agent_env(agent, params);
-        let image_pull_secrets = agent.image_pull_secrets.as_ref().map(|secrets| {
-            secrets
-                .iter()
-                .map(|secret| LocalObjectReference {
-                    name: secret.name.to_string(),
-                })
-                .collect()
-        });
+        let image_pull_secrets = agent_image_pull_secrets(agent);
         let node_selector = agent
             .node_selector
             .clone()
@@ -122,6 +116,7 @@ impl ContainerVariant for PodVariant<'_> {
                 image_pull_secrets,
                 tolerations: agent.tolerations.clone(),
                 node_selector: Some(node_selector),
+                affinity: agent.affinity.clone(),
                 service_account_name: agent.service_account.clone(),
                 containers: vec![Container {
                     name: "mirrord-agent".to_string(),
@@ -266,6 +261,12 @@ impl ContainerVariant for PodTargetedVariant<'_> {
         let mut pod = self.inner.as_update();
         pod.merge_from(update);
 
+        // The targeted agent is pinned to the target's node, affinity rules could only prevent it
+        // from starting.
+        if let Some(spec) = pod.spec.as_mut() {
+            spec.affinity = None;
+        }
+
         pod
     }
 }
@@ -299,6 +300,9 @@ mod test {
             support_ipv6: false,
             steal_tls_config: Default::default(),
             idle_ttl: Default::default(),
+            quic: false,
+            log_redaction: None,
+            procfs: false,
         };
 
         let update = PodVariant::new(&agent, &params).as_update();
@@ -331,4 +335,61 @@ mod test {
         );
         Ok(())
     }
+
+    /// Verifies that the affinity rules are applied only to the targetless agent.
+    #[test]
+    fn agent_affinity() -> Result<(), Box<dyn std::error::Error>> {
+        let mut config_context = ConfigContext::default();
+        let mut agent = AgentFileConfig::default().generate_config(&mut config_context)?;
+        agent.affinity = Some(serde_json::from_value(serde_json::json!({
+            "nodeAffinity": {
+                "requiredDuringSchedulingIgnoredDuringExecution": {
+                    "nodeSelectorTerms": [
+                        {
+                            "matchExpressions": [
+                                { "key": "pool", "operator": "In", "values": ["debug"] }
+                            ]
+                        }
+                    ]
+                }
+            }
+        }))?);
+        let params = ContainerParams {
+            name: "foobar".to_string(),
+            port: 3000,
+            gid: 13,
+            tls_cert: None,
+            pod_ips: None,
+            support_ipv6: false,
+            steal_tls_config: Default::default(),
+            idle_ttl: Default::default(),
+            quic: false,
+            log_redaction: None,
+            procfs: false,
+        };
+
+        let update = PodVariant::new(&agent, &params).as_update();
+        assert_eq!(update.spec.unwrap().affinity, agent.affinity);
+
+        let update = PodTargetedVariant::new(
+            &agent,
+            &params,
+            &RuntimeData {
+                mesh: None,
+                pod_name: "some-pod".to_string(),
+                pod_ips: vec![],
+                pod_namespace: "default".to_string(),
+                node_name: "some-node".to_string(),
+                container_id: "container".to_string(),
+                container_runtime: ContainerRuntime::Docker,
+                container_name: "some-container".to_string(),
+                guessed_container: false,
+                share_process_namespace: false,
+                containers_probe_ports: vec![],
+            },
+        )
+        .as_update();
+        assert!(update.spec.unwrap().affinity.is_none());
+        Ok(())
+    }
 }
@@ -0,0 +1,165 @@
+use std::{collections::BTreeMap, ops::Not};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use k8s_openapi::{
+    ByteString,
+    api::core::v1::{LocalObjectReference, Secret},
+};
+use kube::{
+    Client,
+    api::{ObjectMeta, Patch, PatchParams},
+};
+use mirrord_config::agent::{AgentConfig, AgentImagePullCredentials};
+
+use crate::{
+    api::kubernetes::get_k8s_resource_api,
+    error::{KubeApiError, Result},
+};
+
+/// Name of the secret created from
+/// [`AgentConfig::image_pull_credentials`](mirrord_config::agent::AgentConfig::image_pull_credentials).
+pub const AGENT_PULL_SECRET_NAME: &str = "mirrord-agent-pull-secret";
+
+/// Field manager used when applying the secret.
+const FIELD_MANAGER: &str = "mirrord";
+
+/// Creates or updates the [`AGENT_PULL_SECRET_NAME`] secret in the agent's namespace, if
+/// [`AgentConfig::image_pull_credentials`] are set.
+#[tracing::instrument(level = "trace", skip_all, err)]
+pub async fn apply_agent_pull_secret(client: &Client, agent: &AgentConfig) -> Result<()> {
+    let Some(credentials) = agent.image_pull_credentials.as_ref() else {
+        return Ok(());
+    };
+
+    let password = std::env::var(&credentials.password_env)
+        .map_err(|_| KubeApiError::MissingRegistryPassword(credentials.password_env.clone()))?;
+
+    let secret = pull_secret(credentials, agent.image(), &password);
+
+    get_k8s_resource_api::<Secret>(client, agent.namespace.as_deref())
+        .patch(
+            AGENT_PULL_SECRET_NAME,
+            &PatchParams::apply(FIELD_MANAGER).force(),
+            &Patch::Apply(secret),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Returns the image pull secrets for the agent pod, including the [`AGENT_PULL_SECRET_NAME`]
+/// secret if [`AgentConfig::image_pull_credentials`] are set.
+pub fn agent_image_pull_secrets(agent: &AgentConfig) -> Option<Vec<LocalObjectReference>> {
+    let configured = agent
+        .image_pull_secrets
+        .iter()
+        .flatten()
+        .map(|secret| secret.name.clone());
+    let created = agent
+        .image_pull_credentials
+        .as_ref()
+        .map(|_| AGENT_PULL_SECRET_NAME.to_string());
+
+    let secrets = configured
+        .chain(created)
+        .map(|name| LocalObjectReference { name })
+        .collect::<Vec<_>>();
+
+    (agent.image_pull_secrets.is_some() || secrets.is_empty().not()).then_some(secrets)
+}
+
+/// Builds a `kubernetes.io/dockerconfigjson` secret from the credentials.
+fn pull_secret(credentials: &AgentImagePullCredentials, image: &str, password: &str) -> Secret {
+    let auth = STANDARD.encode(format!("{}:{password}", credentials.username));
+    let docker_config = serde_json::json!({
+        "auths": {
+            credentials.server_for(image): {
+                "username": credentials.username,
+                "password": password,
+                "auth": auth,
+            }
+        }
+    });
+
+    Secret {
+        metadata: ObjectMeta {
+            name: Some(AGENT_PULL_SECRET_NAME.to_string()),
+            labels: Some(BTreeMap::from([("app".to_string(), "mirrord".to_string())])),
+            ..Default::default()
+        },
+        type_: Some("kubernetes.io/dockerconfigjson".to_string()),
+        data: Some(BTreeMap::from([(
+            ".dockerconfigjson".to_string(),
+            ByteString(docker_config.to_string().into_bytes()),
+        )])),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mirrord_config::{
+        agent::{AgentFileConfig, AgentPullSecret},
+        config::{ConfigContext, MirrordConfig},
+    };
+
+    use super::*;
+
+    fn credentials() -> AgentImagePullCredentials {
+        AgentImagePullCredentials {
+            server: None,
+            username: "mirrord".to_string(),
+            password_env: "REGISTRY_TOKEN".to_string(),
+        }
+    }
+
+    #[test]
+    fn docker_config() {
+        let secret = pull_secret(
+            &credentials(),
+            "registry.internal:5000/mirrord:latest",
+            "hunter2",
+        );
+
+        let data = secret.data.unwrap();
+        let docker_config: serde_json::Value =
+            serde_json::from_slice(&data.get(".dockerconfigjson").unwrap().0).unwrap();
+
+        assert_eq!(
+            docker_config,
+            serde_json::json!({
+                "auths": {
+                    "registry.internal:5000": {
+                        "username": "mirrord",
+                        "password": "hunter2",
+                        "auth": "bWlycm9yZDpodW50ZXIy",
+                    }
+                }
+            })
+        );
+        assert_eq!(
+            secret.type_.as_deref(),
+            Some("kubernetes.io/dockerconfigjson")
+        );
+    }
+
+    #[test]
+    fn pull_secrets_include_created_secret() -> Result<(), Box<dyn std::error::Error>> {
+        let mut agent = AgentFileConfig::default().generate_config(&mut ConfigContext::default())?;
+        assert_eq!(agent_image_pull_secrets(&agent), None);
+
+        agent.image_pull_secrets = Some(vec![AgentPullSecret {
+            name: "existing".to_string(),
+        }]);
+        agent.image_pull_credentials = Some(credentials());
+
+        let names = agent_image_pull_secrets(&agent)
+            .unwrap()
+            .into_iter()
+            .map(|secret| secret.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["existing", AGENT_PULL_SECRET_NAME]);
+
+        Ok(())
+    }
+}
@@ -3,15 +3,19 @@ use std::{ops::Not, sync::LazyLock};
 use futures::{AsyncBufReadExt, TryStreamExt};
 use k8s_openapi::api::core::v1::{EnvVar, Pod, Toleration};
 use kube::{Api, api::LogParams};
-use mirrord_agent_env::envs;
-use mirrord_config::agent::{AgentConfig, LinuxCapability};
+use mirrord_agent_env::{envs, redaction::AgentLogRedaction};
+use mirrord_config::{
+    agent::{AgentConfig, LinuxCapability},
+    log_redaction::LogRedactionConfig,
+};
 use regex::Regex;
 use tracing::warn;
 
 use crate::{api::container::ContainerParams, error::Result};
 
 static AGENT_READY_REGEX: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new("agent ready( - version (\\S+))?").expect("failed to create regex")
+    Regex::new("agent ready( - version (\\S+))?( - quic certificate (\\S+))?")
+        .expect("failed to create regex")
 });
 
 pub(super) static DEFAULT_TOLERATIONS: LazyLock<Vec<Toleration>> = LazyLock::new(|| {
@@ -92,9 +96,79 @@ pub(super) fn agent_env(agent: &AgentConfig, params: &ContainerParams) -> Vec<En
         env.push(envs::CLEAN_IPTABLES_ON_START.as_k8s_spec(&clean));
     }
 
+    if agent.audit_enabled() {
+        env.push(envs::AUDIT_LOG.as_k8s_spec(&true));
+    }
+
+    if let Some(webhook) = agent.audit_webhook.as_ref() {
+        env.push(envs::AUDIT_WEBHOOK.as_k8s_spec(webhook));
+    }
+
+    if agent.read_only {
+        env.push(envs::READ_ONLY.as_k8s_spec(&true));
+    }
+
+    if let Some(keepalive) = agent.tcp_keepalive {
+        env.push(envs::TCP_KEEPALIVE.as_k8s_spec(&keepalive));
+    }
+
+    if let Some(paths) = agent
+        .probe_paths
+        .as_ref()
+        .filter(|paths| paths.is_empty().not())
+    {
+        env.push(envs::PROBE_PATHS.as_k8s_spec(paths));
+    }
+
+    if let Some(ttl) = agent.passthrough_cache.ttl.filter(|ttl| *ttl > 0) {
+        env.push(envs::PASSTHROUGH_CACHE_TTL.as_k8s_spec(&ttl));
+        env.push(
+            envs::PASSTHROUGH_CACHE_MAX_ENTRIES.as_k8s_spec(&agent.passthrough_cache.max_entries),
+        );
+        if let Some(headers) = &agent.passthrough_cache.headers {
+            env.push(envs::PASSTHROUGH_CACHE_HEADERS.as_k8s_spec(headers));
+        }
+    }
+
+    if let Some(dir) = &agent.capture_stolen.dir {
+        env.push(envs::CAPTURE_STOLEN_DIR.as_k8s_spec(dir));
+        env.push(
+            envs::CAPTURE_STOLEN_MAX_CONNECTION_BYTES
+                .as_k8s_spec(&agent.capture_stolen.max_connection_bytes),
+        );
+        env.push(
+            envs::CAPTURE_STOLEN_MAX_TOTAL_BYTES.as_k8s_spec(&agent.capture_stolen.max_total_bytes),
+        );
+    }
+
+    if params.quic {
+        env.push(envs::QUIC.as_k8s_spec(&true));
+    }
+
+    if let Some(log_redaction) = &params.log_redaction {
+        env.push(envs::LOG_REDACTION.as_k8s_spec(log_redaction));
+    }
+
+    if params.procfs {
+        env.push(envs::PROCFS.as_k8s_spec(&true));
+    }
+
     env
 }
 
+/// Prepares the [`AgentLogRedaction`] for the given config.
+///
+/// Returns [`None`] when the config does not change the agent's default redaction.
+pub fn agent_log_redaction(config: &LogRedactionConfig) -> Option<AgentLogRedaction> {
+    let redaction = AgentLogRedaction {
+        disabled: config.enabled.not(),
+        headers: config.headers.clone().unwrap_or_default(),
+        body_patterns: config.body_patterns.clone().unwrap_or_default(),
+    };
+
+    (redaction != AgentLogRedaction::default()).then_some(redaction)
+}
+
 pub(super) fn base_command_line(agent: &AgentConfig, params: &ContainerParams) -> Vec<String> {
     let mut command_line = vec![
         "./mirrord-agent".to_owned(),
@@ -114,16 +188,26 @@ pub(super) fn base_command_line(agent: &AgentConfig, params: &ContainerParams) -
     command_line
 }
 
+/// What the agent reports in its "agent ready" message, see [`wait_for_agent_startup`].
+#[derive(Debug, Default)]
+pub(super) struct AgentReady {
+    /// Version of the agent.
+    pub(super) version: Option<String>,
+    /// Base64-encoded DER of the certificate that the agent generated for its QUIC endpoint, see
+    /// [`AgentKubernetesConnectInfo::quic_cert`](crate::api::kubernetes::AgentKubernetesConnectInfo::quic_cert).
+    pub(super) quic_cert: Option<String>,
+}
+
 /**
  * Wait until the agent prints the "agent ready" message.
- * Return agent version extracted from the message (if found).
+ * Return agent version and QUIC certificate extracted from the message (if found).
  */
 #[tracing::instrument(level = "trace", skip(pod_api), ret)]
 pub(super) async fn wait_for_agent_startup(
     pod_api: &Api<Pod>,
     pod_name: &str,
     container_name: String,
-) -> Result<Option<String>> {
+) -> Result<AgentReady> {
     let log_params = LogParams {
         follow: true,
         container: Some(container_name),
@@ -138,12 +222,14 @@ pub(super) async fn wait_for_agent_startup(
             continue;
         };
 
-        let version = captures.get(2).map(|m| m.as_str().to_string());
-        return Ok(version);
+        return Ok(AgentReady {
+            version: captures.get(2).map(|m| m.as_str().to_string()),
+            quic_cert: captures.get(4).map(|m| m.as_str().to_string()),
+        });
     }
 
     warn!("Agent did not print 'agent ready' message");
-    Ok(None)
+    Ok(AgentReady::default())
 }
 
 #[cfg(test)]
@@ -153,11 +239,21 @@ mod test {
     use super::*;
 
     #[rstest]
-    #[case("agent ready", None)]
-    #[case("agent ready - version 3.56.0", Some("3.56.0"))]
-    fn agent_version_regex(#[case] agent_message: &str, #[case] version: Option<&str>) {
+    #[case("agent ready", None, None)]
+    #[case("agent ready - version 3.56.0", Some("3.56.0"), None)]
+    #[case(
+        "agent ready - version 3.56.0 - quic certificate MIIBcDCCARag==",
+        Some("3.56.0"),
+        Some("MIIBcDCCARag==")
+    )]
+    fn agent_version_regex(
+        #[case] agent_message: &str,
+        #[case] version: Option<&str>,
+        #[case] quic_cert: Option<&str>,
+    ) {
         let captures = AGENT_READY_REGEX.captures(agent_message).unwrap();
 
         assert_eq!(captures.get(2).map(|c| c.as_str()), version);
+        assert_eq!(captures.get(4).map(|c| c.as_str()), quic_cert);
     }
 }
@@ -28,17 +28,23 @@ use crate::{
             ContainerApi, ContainerParams,
             ephemeral::EphemeralTargetedVariant,
             job::{JobTargetedVariant, JobVariant},
+            pull_secret::apply_agent_pull_secret,
             targeted::Targeted,
             targetless::Targetless,
         },
+        kubernetes::events::SessionEventTarget,
         runtime::{RuntimeData, RuntimeDataProvider},
     },
     error::{KubeApiError, Result},
     retry::RetryKube,
 };
 
+pub mod events;
+pub mod logs;
 #[cfg(feature = "portforward")]
 pub mod portforwarder;
+#[cfg(feature = "quic")]
+pub mod quic;
 pub mod rollout;
 pub mod seeker;
 
@@ -180,6 +186,38 @@ impl KubernetesAPI {
         Ok(stream)
     }
 
+    /// Connects to the agent directly over QUIC, trusting only the agent's certificate from
+    /// [`AgentKubernetesConnectInfo::quic_cert`].
+    #[cfg(feature = "quic")]
+    pub async fn create_connection_quic(
+        &self,
+        connect_info: &AgentKubernetesConnectInfo,
+        cert: &str,
+    ) -> Result<Box<dyn UnpinStream>> {
+        let stream = tokio::time::timeout(
+            std::time::Duration::from_secs(self.agent.startup_timeout),
+            quic::connect(&self.client, connect_info, cert),
+        )
+        .await
+        .map_err(|_| KubeApiError::AgentReadyTimeout)??;
+
+        Ok(Box::new(stream))
+    }
+
+    /// Connects to the agent, over QUIC if the agent accepts QUIC connections (see
+    /// [`AgentKubernetesConnectInfo::quic_cert`]), otherwise with kube's
+    /// [`kube::Api::portforward`].
+    #[cfg(feature = "quic")]
+    pub async fn create_agent_connection(
+        &self,
+        connect_info: AgentKubernetesConnectInfo,
+    ) -> Result<Box<dyn UnpinStream>> {
+        match connect_info.quic_cert.as_deref() {
+            Some(cert) => self.create_connection_quic(&connect_info, cert).await,
+            None => self.create_connection_portforward(connect_info).await,
+        }
+    }
+
     /// Prepares params to create an agent.
     ///
     /// Unless targetless, fetches [`RuntimeData`] for the given target and fills
@@ -270,36 +308,90 @@ impl KubernetesAPI {
             }
         }
 
+        if self.agent.ephemeral.not() {
+            apply_agent_pull_secret(&self.client, &self.agent).await?;
+        }
+
         info!(?params, "Spawning new agent");
 
-        let agent_connect_info = match (runtime_data, self.agent.ephemeral) {
+        let target_pod = runtime_data
+            .as_ref()
+            .map(|runtime_data| SessionEventTarget {
+                pod_name: runtime_data.pod_name.clone(),
+                pod_namespace: runtime_data.pod_namespace.clone(),
+            });
+
+        let result = self
+            .spawn_agent(&self.agent, &params, runtime_data.as_ref(), progress)
+            .await;
+        let fallback_image = self
+            .agent
+            .image_fallback_tag
+            .as_deref()
+            .map(|tag| self.agent.image.with_tag(tag))
+            .filter(|image| *image != self.agent.image);
+
+        let mut agent_connect_info = match (result, fallback_image) {
+            (Err(KubeApiError::AgentImagePullFailed { image, reason }), Some(fallback_image)) => {
+                progress.warning(&format!(
+                    "failed to pull the agent image `{image}` ({reason}), \
+                    falling back to `{}` from `agent.image_fallback_tag`",
+                    fallback_image.0,
+                ));
+
+                let agent = AgentConfig {
+                    image: fallback_image,
+                    ..self.agent.clone()
+                };
+                self.spawn_agent(&agent, &params, runtime_data.as_ref(), progress)
+                    .await?
+            }
+            (result, _) => result?,
+        };
+
+        agent_connect_info.target_pod = target_pod;
+
+        info!(?agent_connect_info, "Created agent pod");
+
+        Ok(agent_connect_info)
+    }
+
+    /// Spawns the agent described by the given [`AgentConfig`], as a job or an ephemeral
+    /// container.
+    async fn spawn_agent<P>(
+        &self,
+        agent: &AgentConfig,
+        params: &ContainerParams,
+        runtime_data: Option<&RuntimeData>,
+        progress: &mut P,
+    ) -> Result<AgentKubernetesConnectInfo, KubeApiError>
+    where
+        P: Progress,
+    {
+        match (runtime_data, agent.ephemeral) {
             (None, false) => {
-                let variant = JobVariant::new(&self.agent, &params);
+                let variant = JobVariant::new(agent, params);
 
                 Targetless::new(&self.client, &variant)
                     .create_agent(progress)
-                    .await?
+                    .await
             }
             (Some(runtime_data), false) => {
-                let variant = JobTargetedVariant::new(&self.agent, &params, &runtime_data);
+                let variant = JobTargetedVariant::new(agent, params, runtime_data);
 
-                Targeted::new(&self.client, &runtime_data, &variant)
+                Targeted::new(&self.client, runtime_data, &variant)
                     .create_agent(progress)
-                    .await?
+                    .await
             }
             (Some(runtime_data), true) => {
-                let variant = EphemeralTargetedVariant::new(&self.agent, &params, &runtime_data);
+                let variant = EphemeralTargetedVariant::new(agent, params, runtime_data);
 
-                Targeted::new(&self.client, &runtime_data, &variant)
+                Targeted::new(&self.client, runtime_data, &variant)
                     .create_agent(progress)
-                    .await?
+                    .await
             }
-            (None, true) => return Err(KubeApiError::MissingRuntimeData),
-        };
-
-        info!(?agent_connect_info, "Created agent pod");
-
-        Ok(agent_connect_info)
+            (None, true) => Err(KubeApiError::MissingRuntimeData),
+        }
     }
 }
 
@@ -328,6 +420,18 @@ pub struct AgentKubernetesConnectInfo {
     pub pod_namespace: String,
     /// Port on which the agent accepts connections.
     pub agent_port: u16,
+    /// Base64-encoded DER of the certificate of the agent's QUIC endpoint.
+    ///
+    /// The agent generates its QUIC identity, and reports the certificate in its `agent ready`
+    /// message. Set when the agent accepts QUIC connections on the UDP [`Self::agent_port`], in
+    /// which case clients should connect over QUIC.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quic_cert: Option<String>,
+    /// Pod targeted by the agent, on which we emit session events.
+    ///
+    /// [`None`] when targetless.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_pod: Option<SessionEventTarget>,
 }
 
 #[tracing::instrument(level = Level::TRACE, skip(kubeconfig), ret, err)]
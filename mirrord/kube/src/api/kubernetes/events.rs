@@ -0,0 +1,113 @@
+//! Kubernetes [`Event`]s that make mirrord sessions visible on the target pods, see
+//! [`LayerConfig::kube_events`](mirrord_config::LayerConfig::kube_events).
+
+use std::collections::BTreeMap;
+
+use k8s_openapi::{
+    api::core::v1::{Event, EventSource, ObjectReference},
+    apimachinery::pkg::apis::meta::v1::Time,
+    chrono::Utc,
+};
+use kube::{
+    Api, Client,
+    api::{ObjectMeta, PostParams},
+};
+use serde::{Deserialize, Serialize};
+use tracing::Level;
+
+use crate::error::Result;
+
+/// Name of the component reported in the [`Event`]s.
+const COMPONENT: &str = "mirrord";
+
+/// Annotation with the name of the local user that started the session.
+const USER_ANNOTATION: &str = "mirrord.metalbear.co/user";
+
+/// Annotation with the name of the local machine that started the session.
+const HOST_ANNOTATION: &str = "mirrord.metalbear.co/host";
+
+/// Why the [`Event`] was emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEventReason {
+    Started,
+    Ended,
+}
+
+impl SessionEventReason {
+    /// Value of [`Event::reason`].
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Started => "MirrordSessionStarted",
+            Self::Ended => "MirrordSessionEnded",
+        }
+    }
+
+    fn verb(self) -> &'static str {
+        match self {
+            Self::Started => "started",
+            Self::Ended => "ended",
+        }
+    }
+}
+
+/// Pod on which we emit the session [`Event`]s.
+///
+/// Passed from the CLI to the internal proxy in
+/// [`AgentKubernetesConnectInfo::target_pod`](super::AgentKubernetesConnectInfo::target_pod), so
+/// that the latter can emit [`SessionEventReason::Ended`] when the session is over.
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
+pub struct SessionEventTarget {
+    pub pod_name: String,
+    pub pod_namespace: String,
+}
+
+impl SessionEventTarget {
+    /// Creates a new [`Event`] on the target pod.
+    #[tracing::instrument(level = Level::DEBUG, skip(client), err)]
+    pub async fn emit(&self, client: &Client, reason: SessionEventReason) -> Result<()> {
+        let user = whoami::username();
+        let host = whoami::fallible::hostname().unwrap_or_else(|_| "unknown".into());
+        let now = Time(Utc::now());
+
+        let event = Event {
+            metadata: ObjectMeta {
+                generate_name: Some(format!("{}.mirrord-", self.pod_name)),
+                namespace: Some(self.pod_namespace.clone()),
+                annotations: Some(BTreeMap::from([
+                    (USER_ANNOTATION.to_string(), user.clone()),
+                    (HOST_ANNOTATION.to_string(), host.clone()),
+                ])),
+                ..Default::default()
+            },
+            involved_object: ObjectReference {
+                api_version: Some("v1".into()),
+                kind: Some("Pod".into()),
+                name: Some(self.pod_name.clone()),
+                namespace: Some(self.pod_namespace.clone()),
+                ..Default::default()
+            },
+            reason: Some(reason.as_str().into()),
+            message: Some(format!(
+                "mirrord session {} by {user} on {host}",
+                reason.verb()
+            )),
+            type_: Some("Normal".into()),
+            source: Some(EventSource {
+                component: Some(COMPONENT.into()),
+                host: None,
+            }),
+            reporting_component: Some(COMPONENT.into()),
+            reporting_instance: Some(host),
+            first_timestamp: Some(now.clone()),
+            last_timestamp: Some(now),
+            count: Some(1),
+            ..Default::default()
+        };
+
+        Api::<Event>::namespaced(client.clone(), &self.pod_namespace)
+            .create(&PostParams::default(), &event)
+            .await?;
+
+        Ok(())
+    }
+}
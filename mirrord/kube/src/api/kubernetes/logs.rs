@@ -0,0 +1,52 @@
+//! Streaming the logs of the target container into the local session output, see
+//! [`LayerConfig::remote_logs`](mirrord_config::LayerConfig::remote_logs).
+
+use futures::{AsyncBufReadExt, StreamExt, TryStreamExt, stream::BoxStream};
+use k8s_openapi::api::core::v1::Pod;
+use kube::{Api, Client, api::LogParams};
+use tracing::Level;
+
+use crate::{api::runtime::RuntimeData, error::Result};
+
+/// Container from which we stream the logs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteLogsSource {
+    pub pod_name: String,
+    pub pod_namespace: String,
+    pub container_name: String,
+}
+
+impl From<&RuntimeData> for RemoteLogsSource {
+    fn from(runtime_data: &RuntimeData) -> Self {
+        Self {
+            pod_name: runtime_data.pod_name.clone(),
+            pod_namespace: runtime_data.pod_namespace.clone(),
+            container_name: runtime_data.container_name.clone(),
+        }
+    }
+}
+
+impl RemoteLogsSource {
+    /// Follows the logs of the container (both stdout and stderr, as they're merged by the
+    /// runtime), starting from now.
+    ///
+    /// The returned stream ends when the container exits, or when the API server closes the
+    /// connection.
+    #[tracing::instrument(level = Level::DEBUG, skip(client), err)]
+    pub async fn follow(&self, client: &Client) -> Result<BoxStream<'static, Result<String>>> {
+        let params = LogParams {
+            container: Some(self.container_name.clone()),
+            follow: true,
+            tail_lines: Some(0),
+            ..Default::default()
+        };
+
+        let lines = Api::<Pod>::namespaced(client.clone(), &self.pod_namespace)
+            .log_stream(&self.pod_name, &params)
+            .await?
+            .lines()
+            .map_err(From::from);
+
+        Ok(lines.boxed())
+    }
+}
@@ -0,0 +1,100 @@
+//! Direct QUIC connections with the agent, see
+//! [`ConnectionConfig::transport`](mirrord_config::connection::ConnectionConfig::transport).
+//!
+//! Unlike port forwarding, this requires the agent pod's IP to be reachable over UDP.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use base64::{Engine, engine::general_purpose};
+use k8s_openapi::api::core::v1::Pod;
+use kube::{Api, Client};
+use quinn::{ClientConfig, Endpoint, IdleTimeout, RecvStream, SendStream, TransportConfig, VarInt};
+use rustls::{RootCertStore, pki_types::CertificateDer};
+use tokio::io::Join;
+
+use crate::{
+    api::kubernetes::AgentKubernetesConnectInfo,
+    error::{KubeApiError, Result},
+};
+
+/// Name in the agent's QUIC certificate, generated by the agent.
+const AGENT_SERVER_NAME: &str = "mirrord-agent";
+
+/// How often we ping the agent, so that an idle connection is not dropped and a change of our
+/// address is noticed quickly.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long we wait for any packet from the agent before we consider the connection lost.
+///
+/// Generous, so that the connection survives short network outages (e.g. a VPN reconnect).
+const MAX_IDLE_TIMEOUT_MS: u32 = 60_000;
+
+/// A bidirectional QUIC stream with the agent.
+///
+/// The stream keeps the QUIC connection alive.
+pub type QuicStream = Join<RecvStream, SendStream>;
+
+/// Makes a QUIC connection with the agent, trusting only the given certificate (base64-encoded
+/// DER), reported by the agent.
+///
+/// Opens a single bidirectional stream, which carries the `mirrord-protocol` messages.
+pub(super) async fn connect(
+    client: &Client,
+    connect_info: &AgentKubernetesConnectInfo,
+    cert: &str,
+) -> Result<QuicStream> {
+    let pod_api: Api<Pod> = Api::namespaced(client.clone(), &connect_info.pod_namespace);
+    let pod = pod_api.get(&connect_info.pod_name).await?;
+    let pod_ip = pod
+        .status
+        .as_ref()
+        .and_then(|status| status.pod_ip.as_deref())
+        .ok_or_else(|| KubeApiError::missing_field(&pod, ".status.podIP"))?
+        .parse::<IpAddr>()
+        .map_err(|error| KubeApiError::invalid_value(&pod, ".status.podIP", error))?;
+
+    let cert = general_purpose::STANDARD
+        .decode(cert)
+        .map_err(|error| KubeApiError::QuicConnectionError(error.into()))?;
+    let mut roots = RootCertStore::empty();
+    roots
+        .add(CertificateDer::from(cert))
+        .map_err(|error| KubeApiError::QuicConnectionError(error.into()))?;
+
+    let mut transport = TransportConfig::default();
+    transport
+        .keep_alive_interval(Some(KEEP_ALIVE_INTERVAL))
+        .max_idle_timeout(Some(IdleTimeout::from(VarInt::from_u32(
+            MAX_IDLE_TIMEOUT_MS,
+        ))));
+    let mut client_config = ClientConfig::with_root_certificates(Arc::new(roots))
+        .map_err(|error| KubeApiError::QuicConnectionError(error.into()))?;
+    client_config.transport_config(Arc::new(transport));
+
+    // Bound to an unspecified address, so that the connection migrates when our address changes.
+    let bind_address = match pod_ip {
+        IpAddr::V4(..) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+        IpAddr::V6(..) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+    };
+    let mut endpoint = Endpoint::client(bind_address)?;
+    endpoint.set_default_client_config(client_config);
+
+    let agent_address = SocketAddr::new(pod_ip, connect_info.agent_port);
+    tracing::trace!(%agent_address, "Connecting to the agent over QUIC");
+
+    let connection = endpoint
+        .connect(agent_address, AGENT_SERVER_NAME)
+        .map_err(|error| KubeApiError::QuicConnectionError(error.into()))?
+        .await
+        .map_err(|error| KubeApiError::QuicConnectionError(error.into()))?;
+    let (send, recv) = connection
+        .open_bi()
+        .await
+        .map_err(|error| KubeApiError::QuicConnectionError(error.into()))?;
+
+    Ok(tokio::io::join(recv, send))
+}
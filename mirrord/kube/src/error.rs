@@ -81,9 +81,21 @@ pub enum KubeApiError {
     #[error("Failed to wait for the agent pod to start: {0}")]
     AgentPodStartError(String),
 
+    /// The agent image could not be pulled, e.g. because the registry does not have it.
+    #[error("Failed to pull the agent image `{image}`: {reason}")]
+    AgentImagePullFailed { image: String, reason: String },
+
     /// Spawned agent pod was deleted during startup.
     #[error("Agent pod was unexpectedly deleted")]
     AgentPodDeleted,
+
+    /// The environment variable from `agent.image_pull_credentials.password_env` is not set.
+    #[error("Environment variable `{0}` with the agent image registry password is not set")]
+    MissingRegistryPassword(String),
+
+    /// Setting up a QUIC connection with the agent failed.
+    #[error("QUIC connection to agent failed: {0}")]
+    QuicConnectionError(#[source] Box<dyn std::error::Error + Send + Sync>),
 }
 
 impl KubeApiError {
@@ -467,9 +467,12 @@ fn get_platform_errno(fail: HookError) -> i32 {
             ResponseError::PortAlreadyStolen(_port) => libc::EINVAL,
             ResponseError::NotImplemented => libc::EINVAL,
             ResponseError::StripPrefix(_) => libc::EINVAL,
-            err @ (ResponseError::Forbidden { .. } | ResponseError::ForbiddenWithReason { .. }) => {
+            err @ (ResponseError::Forbidden { .. }
+            | ResponseError::ForbiddenWithReason { .. }
+            | ResponseError::QuotaExceeded { .. }) => {
                 graceful_exit!(
-                    "Stopping mirrord run. Please adjust your mirrord configuration.\n{err}"
+                    "Stopping mirrord run. Please adjust your mirrord configuration.\n{}",
+                    err.with_code()
                 );
             }
         },
@@ -550,9 +553,12 @@ fn get_platform_errno(fail: HookError) -> u32 {
             ResponseError::PortAlreadyStolen(_port) => WSAEINVAL,
             ResponseError::NotImplemented => WSAEINVAL,
             ResponseError::StripPrefix(_) => WSAEINVAL,
-            err @ (ResponseError::Forbidden { .. } | ResponseError::ForbiddenWithReason { .. }) => {
+            err @ (ResponseError::Forbidden { .. }
+            | ResponseError::ForbiddenWithReason { .. }
+            | ResponseError::QuotaExceeded { .. }) => {
                 graceful_exit!(
-                    "Stopping mirrord run. Please adjust your mirrord configuration.\n{err}"
+                    "Stopping mirrord run. Please adjust your mirrord configuration.\n{}",
+                    err.with_code()
                 );
             }
         },
@@ -635,6 +641,9 @@ impl From<HookError> for i64 {
                     Please report it to us on https://github.com/metalbear-co/mirrord/issues/new?assignees=&labels=bug&projects=&template=bug_report.yml"
                 );
             }
+            HookError::ResponseError(ref response_fail) => {
+                error!(code = %response_fail.code(), "Error occured in Layer >> {fail:?}")
+            }
             _ => error!("Error occured in Layer >> {fail:?}"),
         };
 
@@ -80,6 +80,13 @@ pub struct FileFilter {
     pub read_write: RegexSet,
     pub local: RegexSet,
     pub not_found: RegexSet,
+    /// Paths copied to a local shadow directory because of `feature.fs.materialize`.
+    pub materialize: RegexSet,
+    /// Relative paths resolved against the working directory of the target because of
+    /// `feature.fs.remote_cwd`.
+    pub remote_cwd: RegexSet,
+    /// Paths read from the target because of `feature.fs.procfs`, empty when it's disabled.
+    pub procfs: RegexSet,
     pub default_local: RegexSet,
     pub default_remote_ro: RegexSet,
     pub default_not_found: RegexSet,
@@ -101,12 +108,17 @@ impl FileFilter {
     /// If not, it does the default behavior set by user (default is read only remote).
     #[mirrord_layer_macro::instrument(level = "trace")]
     pub fn new(fs_config: FsConfig) -> Self {
+        let procfs = RegexSet::new(fs_config.procfs_patterns())
+            .expect("building procfs path regex set failed");
+
         let FsConfig {
             read_write,
             read_only,
             local,
             mode,
             not_found,
+            materialize,
+            remote_cwd,
             ..
         } = fs_config;
 
@@ -117,6 +129,10 @@ impl FileFilter {
         let local = Self::make_regex_set(local).expect("building local path regex set failed");
         let not_found =
             Self::make_regex_set(not_found).expect("building not-found regex set failed");
+        let materialize =
+            Self::make_regex_set(materialize).expect("building materialize regex set failed");
+        let remote_cwd =
+            Self::make_regex_set(remote_cwd).expect("building remote cwd regex set failed");
 
         let default_local = generate_local_set();
         let default_remote_ro = generate_remote_ro_set();
@@ -127,6 +143,9 @@ impl FileFilter {
             read_write,
             local,
             not_found,
+            materialize,
+            remote_cwd,
+            procfs,
             default_local,
             default_remote_ro,
             default_not_found,
@@ -150,6 +169,8 @@ impl FileFilter {
                     Some(FileMode::Local(false))
                 } else if self.default_not_found.is_match(path) {
                     Some(FileMode::NotFound(true))
+                } else if self.procfs.is_match(path) {
+                    Some(FileMode::ReadOnly(true))
                 } else if self.default_remote_ro.is_match(path) {
                     Some(FileMode::ReadOnly(true))
                 } else if self.default_local.is_match(path) {
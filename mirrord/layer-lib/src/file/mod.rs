@@ -1,5 +1,7 @@
 pub mod filter;
 pub mod mapper;
+pub mod overrides;
+pub mod translation;
 #[cfg(unix)]
 pub mod unix;
 #[cfg(windows)]
@@ -0,0 +1,62 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// Local files that are accessed in place of remote paths, see
+/// [`FsConfig::overrides`](mirrord_config::feature::fs::FsConfig::overrides).
+#[derive(Debug, Default)]
+pub struct FileOverrides {
+    overrides: HashMap<PathBuf, PathBuf>,
+}
+
+impl FileOverrides {
+    /// Relative local paths in the given `overrides` are resolved against `base_dir`.
+    pub fn new(overrides: HashMap<String, String>, base_dir: &Path) -> Self {
+        let overrides = overrides
+            .into_iter()
+            .map(|(remote, local)| (PathBuf::from(remote), base_dir.join(local)))
+            .collect();
+
+        Self { overrides }
+    }
+
+    /// Returns the local file that should be accessed instead of the given remote `path`.
+    pub fn local_path(&self, path: &Path) -> Option<&Path> {
+        self.overrides.get(path).map(PathBuf::as_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("/app/config.yaml", Some("/home/dev/app/config.dev.yaml"))]
+    #[case("/app/secrets.yaml", Some("/etc/secrets.yaml"))]
+    #[case("/app/config.yaml.bak", None)]
+    #[case("/app", None)]
+    fn local_path(#[case] path: &str, #[case] expected: Option<&str>) {
+        let overrides = FileOverrides::new(
+            [
+                (
+                    "/app/config.yaml".to_string(),
+                    "config.dev.yaml".to_string(),
+                ),
+                (
+                    "/app/secrets.yaml".to_string(),
+                    "/etc/secrets.yaml".to_string(),
+                ),
+            ]
+            .into(),
+            Path::new("/home/dev/app"),
+        );
+
+        assert_eq!(
+            overrides.local_path(Path::new(path)),
+            expected.map(Path::new)
+        );
+    }
+}
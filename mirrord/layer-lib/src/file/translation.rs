@@ -0,0 +1,156 @@
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+
+/// Bidirectional translation between local and remote paths, see
+/// [`FsConfig::path_translation`](mirrord_config::feature::fs::FsConfig::path_translation).
+///
+/// Rules are matched on whole path components, and the rule with the longest matching prefix
+/// wins.
+#[derive(Debug, Default)]
+pub struct PathTranslator {
+    /// Pairs of local and remote prefixes, both absolute and normalized.
+    rules: Vec<(PathBuf, PathBuf)>,
+}
+
+impl PathTranslator {
+    /// Relative local prefixes in the given `rules` are resolved against `base_dir`.
+    pub fn new(rules: HashMap<String, String>, base_dir: &Path) -> Self {
+        let rules = rules
+            .into_iter()
+            .map(|(local, remote)| {
+                (
+                    normalize(&base_dir.join(local)),
+                    normalize(Path::new(&remote)),
+                )
+            })
+            .collect();
+
+        Self { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Translates a local `path` into the remote one, if any rule matches.
+    ///
+    /// A relative `path` is resolved against `cwd` first.
+    pub fn to_remote(&self, path: &Path, cwd: &Path) -> Option<PathBuf> {
+        let path = normalize(&cwd.join(path));
+
+        Self::translate(
+            self.rules
+                .iter()
+                .map(|(local, remote)| (local.as_path(), remote.as_path())),
+            &path,
+        )
+    }
+
+    /// Translates a remote `path` into the local one, if any rule matches.
+    ///
+    /// Relative paths are never translated, as they have no meaning on the remote.
+    pub fn to_local(&self, path: &Path) -> Option<PathBuf> {
+        if path.is_relative() {
+            return None;
+        }
+
+        Self::translate(
+            self.rules
+                .iter()
+                .map(|(local, remote)| (remote.as_path(), local.as_path())),
+            &normalize(path),
+        )
+    }
+
+    /// Replaces the longest matching `from` prefix of the `path` with its `to` counterpart.
+    fn translate<'a, I>(rules: I, path: &Path) -> Option<PathBuf>
+    where
+        I: Iterator<Item = (&'a Path, &'a Path)>,
+    {
+        rules
+            .filter_map(|(from, to)| {
+                let rest = path.strip_prefix(from).ok()?;
+                Some((from.components().count(), to, rest))
+            })
+            .max_by_key(|(prefix_len, ..)| *prefix_len)
+            .map(|(_, to, rest)| {
+                if rest.as_os_str().is_empty() {
+                    to.to_path_buf()
+                } else {
+                    to.join(rest)
+                }
+            })
+    }
+}
+
+/// Lexically removes `.` and `..` components from the given path, without touching the
+/// filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn translator() -> PathTranslator {
+        PathTranslator::new(
+            [
+                ("./data".to_string(), "/app/data".to_string()),
+                ("data/cache".to_string(), "/var/cache/app".to_string()),
+                ("/opt/local".to_string(), "/opt/remote".to_string()),
+            ]
+            .into(),
+            Path::new("/home/dev/project"),
+        )
+    }
+
+    #[rstest]
+    #[case(
+        "/home/dev/project/data/users.db",
+        "/home/dev",
+        Some("/app/data/users.db")
+    )]
+    #[case("data/users.db", "/home/dev/project", Some("/app/data/users.db"))]
+    #[case("./data", "/home/dev/project", Some("/app/data"))]
+    #[case("../project/data/x", "/home/dev/project/src", Some("/app/data/x"))]
+    #[case("project/data/cache/x", "/home/dev", Some("/var/cache/app/x"))]
+    #[case("/opt/local/bin", "/", Some("/opt/remote/bin"))]
+    #[case("data-old/x", "/home/dev/project", None)]
+    #[case("data/x", "/home/dev", None)]
+    fn to_remote(#[case] path: &str, #[case] cwd: &str, #[case] expected: Option<&str>) {
+        assert_eq!(
+            translator().to_remote(Path::new(path), Path::new(cwd)),
+            expected.map(PathBuf::from)
+        );
+    }
+
+    #[rstest]
+    #[case("/app/data/users.db", Some("/home/dev/project/data/users.db"))]
+    #[case("/var/cache/app/x", Some("/home/dev/project/data/cache/x"))]
+    #[case("/app/data/../data/x", Some("/home/dev/project/data/x"))]
+    #[case("/app/database", None)]
+    #[case("app/data/x", None)]
+    fn to_local(#[case] path: &str, #[case] expected: Option<&str>) {
+        assert_eq!(
+            translator().to_local(Path::new(path)),
+            expected.map(PathBuf::from)
+        );
+    }
+}
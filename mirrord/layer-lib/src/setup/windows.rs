@@ -16,7 +16,7 @@ use mirrord_config::{
 use mirrord_intproxy_protocol::PortSubscription;
 use mirrord_protocol::{
     Port,
-    tcp::{HttpFilter, MirrorType, StealType},
+    tcp::{Filter, HttpFilter, MirrorType, PostgresFilter, RedisFilter, StealType},
 };
 
 use crate::{
@@ -193,10 +193,40 @@ pub struct HttpSettings {
     pub ports: Option<HashSet<Port>>,
 }
 
+/// Settings for stealing TLS connections based on their SNI.
+#[derive(Debug)]
+pub struct SniSettings {
+    /// The server name filter to use.
+    pub filter: Filter,
+    /// Ports to filter SNI on.
+    pub ports: HashSet<Port>,
+}
+
+/// Settings for stealing PostgreSQL connections based on their startup message.
+#[derive(Debug)]
+pub struct PostgresSettings {
+    /// The database and user filter to use.
+    pub filter: PostgresFilter,
+    /// Ports to filter PostgreSQL connections on.
+    pub ports: HashSet<Port>,
+}
+
+/// Settings for stealing Redis commands.
+#[derive(Debug)]
+pub struct RedisSettings {
+    /// The command and key filter to use.
+    pub filter: RedisFilter,
+    /// Ports to filter Redis commands on.
+    pub ports: HashSet<Port>,
+}
+
 #[derive(Debug)]
 pub struct IncomingMode {
     pub steal: bool,
     pub http_settings: Option<HttpSettings>,
+    pub sni_settings: Option<SniSettings>,
+    pub postgres_settings: Option<PostgresSettings>,
+    pub redis_settings: Option<RedisSettings>,
 }
 
 impl IncomingMode {
@@ -221,18 +251,76 @@ impl IncomingMode {
             HttpSettings { filter, ports }
         });
 
+        let sni_settings = config.sni_filter.as_ref().map(|sni_filter| SniSettings {
+            filter: sni_filter
+                .as_protocol_filter()
+                .expect("invalid SNI filter expression"),
+            ports: sni_filter.port_set(),
+        });
+
+        let postgres_settings =
+            config
+                .postgres_filter
+                .as_ref()
+                .map(|postgres_filter| PostgresSettings {
+                    filter: postgres_filter
+                        .as_protocol_filter()
+                        .expect("invalid PostgreSQL filter expression"),
+                    ports: postgres_filter.port_set(),
+                });
+
+        let redis_settings = config
+            .redis_filter
+            .as_ref()
+            .map(|redis_filter| RedisSettings {
+                filter: redis_filter
+                    .as_protocol_filter()
+                    .expect("invalid Redis filter expression"),
+                ports: redis_filter.port_set(),
+            });
+
         Self {
             steal: config.is_steal(),
             http_settings,
+            sni_settings,
+            postgres_settings,
+            redis_settings,
         }
     }
 
     /// Returns [`PortSubscription`] request to be used for the given port.
     pub fn subscription(&self, port: Port) -> PortSubscription {
         if self.steal {
-            let steal_type = match &self.http_settings {
-                None => StealType::All(port),
-                Some(settings) => {
+            let sni_filter = self
+                .sni_settings
+                .as_ref()
+                .filter(|settings| settings.ports.contains(&port));
+
+            let postgres_filter = self
+                .postgres_settings
+                .as_ref()
+                .filter(|settings| settings.ports.contains(&port));
+
+            let redis_filter = self
+                .redis_settings
+                .as_ref()
+                .filter(|settings| settings.ports.contains(&port));
+
+            let steal_type = match (
+                sni_filter,
+                postgres_filter,
+                redis_filter,
+                &self.http_settings,
+            ) {
+                (Some(settings), ..) => StealType::FilteredSni(port, settings.filter.clone()),
+                (None, Some(settings), ..) => {
+                    StealType::FilteredPostgres(port, settings.filter.clone())
+                }
+                (None, None, Some(settings), _) => {
+                    StealType::FilteredRedis(port, settings.filter.clone())
+                }
+                (None, None, None, None) => StealType::All(port),
+                (None, None, None, Some(settings)) => {
                     if settings
                         .ports
                         .as_ref()
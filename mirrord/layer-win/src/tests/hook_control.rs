@@ -13,6 +13,7 @@ mod tests {
             outgoing: Default::default(),
             dns: Default::default(),
             ipv6: Default::default(),
+            interfaces: Default::default(),
         };
 
         // Configure all network features as disabled
@@ -52,6 +53,7 @@ mod tests {
             outgoing: Default::default(),
             dns: Default::default(),
             ipv6: Default::default(),
+            interfaces: Default::default(),
         };
         network_all_off.incoming.mode = IncomingMode::Off;
         network_all_off.outgoing.tcp = false;
@@ -0,0 +1,42 @@
+/*
+ * C ABI of mirrord-layer, for starting the layer explicitly from inside the process (e.g. when
+ * the layer is loaded with `dlopen` instead of `LD_PRELOAD`/`DYLD_INSERT_LIBRARIES`).
+ *
+ * See `src/embed.rs` for details.
+ */
+
+#ifndef MIRRORD_LAYER_H
+#define MIRRORD_LAYER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define MIRRORD_LAYER_STATUS_RUNNING 0
+#define MIRRORD_LAYER_STATUS_STOPPED 1
+#define MIRRORD_LAYER_STATUS_INVALID -1
+
+typedef struct MirrordLayerHandle mirrord_layer_handle_t;
+
+/*
+ * Starts the layer with the given fully resolved config (JSON).
+ * The internal proxy address is taken from `MIRRORD_LAYER_INTPROXY_ADDR`.
+ *
+ * Returns NULL on failure, the reason is printed to stderr.
+ */
+mirrord_layer_handle_t *mirrord_layer_start(const char *config_json);
+
+/* Returns one of the MIRRORD_LAYER_STATUS_* values. */
+int mirrord_layer_status(const mirrord_layer_handle_t *handle);
+
+/*
+ * Stops the layer: the hooks call the original functions from now on.
+ * The layer cannot be started again in this process.
+ */
+void mirrord_layer_stop(mirrord_layer_handle_t *handle);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MIRRORD_LAYER_H */
@@ -109,6 +109,10 @@ pub fn hook_fn(
 }
 
 /// Same as above but calls the original function if detour guard is active.
+///
+/// The body of the annotated function is run with `crate::detour::contain_hook_panic`, so that a
+/// panic in the layer calls the original function (if the body did not call it already) instead of
+/// aborting the process.
 #[proc_macro_attribute]
 pub fn hook_guard_fn(
     _args: proc_macro::TokenStream,
@@ -190,9 +194,15 @@ pub fn hook_guard_fn(
                 if __bypass.is_none() {
                     return #static_name (#fn_arg_names);
                 }
+
+                match crate::detour::contain_hook_panic(#ident_string, &#static_name, || {
+                    #(#statements)*
+                }) {
+                    Some(result) => result,
+                    None => #static_name (#fn_arg_names),
+                }
             ))
             .unwrap();
-        modified_function.block.stmts.extend(statements);
 
         let output = quote! {
             #[allow(non_camel_case_types)]
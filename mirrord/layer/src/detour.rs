@@ -10,12 +10,24 @@ use core::{
     ops::{FromResidual, Residual, Try},
 };
 use std::{
-    cell::RefCell, ffi::CString, net::SocketAddr, ops::Deref, os::unix::prelude::*, path::PathBuf,
-    sync::OnceLock,
+    any::Any,
+    cell::{Cell, RefCell},
+    ffi::CString,
+    net::SocketAddr,
+    ops::{Deref, Not},
+    os::unix::prelude::*,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    ptr,
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
 };
 
 #[cfg(target_os = "macos")]
 use libc::c_char;
+use nix::errno::Errno;
 
 use crate::error::HookError;
 
@@ -43,6 +55,152 @@ thread_local!(
     static DETOUR_BYPASS: RefCell<bool> = const { RefCell::new(false) }
 );
 
+thread_local!(
+    /// The original function of the hook currently running in [`contain_hook_panic`], and whether
+    /// it was already called.
+    ///
+    /// Updated in the [`Deref`] implementation of [`HookFn`].
+    static RUNNING_HOOK: Cell<RunningHook> = const { Cell::new(RunningHook::NONE) }
+);
+
+/// Value of [`RUNNING_HOOK`].
+#[derive(Clone, Copy)]
+struct RunningHook {
+    /// Address of the [`HookFn`] holding the original function.
+    original: *const (),
+    called: bool,
+}
+
+impl RunningHook {
+    const NONE: Self = Self {
+        original: ptr::null(),
+        called: false,
+    };
+}
+
+/// Set when one of the hooks panics, see [`contain_panic`].
+///
+/// When set, [`DetourGuard::new`] always fails, so the hooks call the original functions for the
+/// rest of the process' life.
+static HOOK_PANICKED: AtomicBool = AtomicBool::new(false);
+
+/// Runs the layer logic of the hook `hook`, containing its panic.
+///
+/// Without this, a panic unwinding out of an `extern "C"` hook aborts the user application. When
+/// `logic` panics, we report it to the user (only the first time), and turn all the hooks into
+/// plain calls to the original functions, as the layer's state may be broken.
+///
+/// Returns [`None`] if `logic` panicked, in which case the hook should fall back to the original
+/// function, unless it was already called.
+///
+/// Hooks that call the original function outside of `logic` use this directly, the rest go through
+/// [`contain_hook_panic`].
+pub(crate) fn contain_panic<R, F: FnOnce() -> R>(hook: &'static str, logic: F) -> Option<R> {
+    let payload = match panic::catch_unwind(AssertUnwindSafe(logic)) {
+        Ok(result) => return Some(result),
+        Err(payload) => payload,
+    };
+
+    if HOOK_PANICKED.swap(true, Ordering::Relaxed).not() {
+        let message = panic_message(payload.as_ref());
+        tracing::error!(
+            hook,
+            message,
+            "mirrord layer hook panicked, bypassing all hooks"
+        );
+        eprintln!(
+            "mirrord layer hook `{hook}` panicked with `{message}`. mirrord is disabled for the \
+            rest of this process, which now runs as if it was started without mirrord. \
+            Please report this issue."
+        );
+    }
+
+    None
+}
+
+/// [`contain_panic`] for a hook that may call its `original` function as a part of its logic.
+///
+/// Returns [`None`] only if `logic` panicked before calling `original`, in which case the hook
+/// should call it. If `logic` panicked after, calling `original` again would repeat its side
+/// effects (e.g. close an fd that was already reused by another thread, or fork again), so the
+/// call fails with [`Errno::EIO`] instead.
+///
+/// Used by the [`hook_guard_fn`](mirrord_layer_macro::hook_guard_fn) macro.
+pub(crate) fn contain_hook_panic<T, R, F>(
+    hook: &'static str,
+    original: &'static HookFn<T>,
+    logic: F,
+) -> Option<R>
+where
+    R: HookFailure,
+    F: FnOnce() -> R,
+{
+    let previous = RUNNING_HOOK.replace(RunningHook {
+        original: ptr::from_ref(original).cast(),
+        called: false,
+    });
+    let result = contain_panic(hook, logic);
+    let running = RUNNING_HOOK.replace(previous);
+
+    match result {
+        None if running.called => {
+            Errno::set_raw(libc::EIO);
+            Some(R::failure())
+        }
+        result => result,
+    }
+}
+
+/// Return value of a hook that signals a failure, see [`contain_hook_panic`].
+pub(crate) trait HookFailure {
+    fn failure() -> Self;
+}
+
+macro_rules! impl_hook_failure {
+    ($value:expr => $($ty:ty),+) => {
+        $(
+            impl HookFailure for $ty {
+                fn failure() -> Self {
+                    $value
+                }
+            }
+        )+
+    };
+}
+
+impl_hook_failure!(-1 => i32, i64, isize);
+// Hooks returning pointers as `usize`, e.g. `opendir`.
+impl_hook_failure!(0 => usize);
+impl_hook_failure!(false => bool);
+impl_hook_failure!(() => ());
+
+impl<T> HookFailure for *mut T {
+    fn failure() -> Self {
+        ptr::null_mut()
+    }
+}
+
+impl<T> HookFailure for *const T {
+    fn failure() -> Self {
+        ptr::null()
+    }
+}
+
+impl<T> HookFailure for Option<T> {
+    fn failure() -> Self {
+        None
+    }
+}
+
+/// Extracts the message from a panic payload returned from [`panic::catch_unwind`].
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
 /// Sets [`DETOUR_BYPASS`] to `false`.
 ///
 /// Prefer relying on the [`Drop`] implementation of [`DetourGuard`] instead.
@@ -66,7 +224,15 @@ pub(crate) struct DetourGuard;
 
 impl DetourGuard {
     /// Create a new DetourGuard if it's not already enabled.
+    ///
+    /// Always fails after the layer was stopped with
+    /// [`mirrord_layer_stop`](crate::embed::mirrord_layer_stop), or after one of the hooks
+    /// panicked (see [`contain_panic`]).
     pub(crate) fn new() -> Option<Self> {
+        if crate::embed::is_stopped() || HOOK_PANICKED.load(Ordering::Relaxed) {
+            return None;
+        }
+
         DETOUR_BYPASS.with(|enabled| {
             if let Ok(bypass) = enabled.try_borrow()
                 && *bypass
@@ -101,6 +267,16 @@ impl<T> Deref for HookFn<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
+        RUNNING_HOOK.with(|running| {
+            let hook = running.get();
+            if ptr::eq(hook.original, ptr::from_ref(self).cast()) {
+                running.set(RunningHook {
+                    called: true,
+                    ..hook
+                });
+            }
+        });
+
         self.0.get().unwrap()
     }
 }
@@ -131,6 +307,9 @@ pub(crate) enum Bypass {
     /// Either an invalid socket domain, or one that we don't handle.
     Domain(i32),
 
+    /// SCTP socket, which we don't support, so its traffic stays local.
+    Sctp,
+
     /// Unix socket to address that was not configured to be connected remotely.
     UnixSocket(Option<String>),
 
@@ -230,6 +409,10 @@ pub(crate) enum Bypass {
     /// Invalid argument value
     #[cfg(target_os = "macos")]
     InvalidArgValue,
+
+    /// Socket option (level and name) that is not emulated for managed sockets.
+    #[cfg(target_os = "linux")]
+    NotEmulatedSockopt(libc::c_int, libc::c_int),
 }
 
 impl Bypass {
@@ -480,3 +663,46 @@ impl<T> OnceLockExt<T> for OnceLock<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use nix::errno::Errno;
+
+    use super::{HookFn, contain_hook_panic};
+
+    static FN_HOOKED: HookFn<fn() -> i32> = HookFn::default_const();
+    static FN_OTHER: HookFn<fn() -> i32> = HookFn::default_const();
+
+    /// Verifies that the original function is called after a panic only if the hook did not call
+    /// it already.
+    #[test]
+    fn original_not_called_twice() {
+        let _ = FN_HOOKED.set(|| 0);
+        let _ = FN_OTHER.set(|| 0);
+
+        assert_eq!(contain_hook_panic("hooked", &FN_HOOKED, || 7), Some(7));
+
+        let result = contain_hook_panic("hooked", &FN_HOOKED, || -> i32 { panic!("before") });
+        assert_eq!(result, None);
+
+        let result = contain_hook_panic("hooked", &FN_HOOKED, || -> i32 {
+            FN_OTHER();
+            panic!("after another original")
+        });
+        assert_eq!(result, None);
+
+        let result = contain_hook_panic("hooked", &FN_HOOKED, || -> i32 {
+            FN_HOOKED();
+            panic!("after the original")
+        });
+        assert_eq!(result, Some(-1));
+        assert_eq!(Errno::last(), Errno::EIO);
+
+        let result = contain_hook_panic("hooked", &FN_HOOKED, || -> i32 {
+            FN_HOOKED();
+            contain_hook_panic("other", &FN_OTHER, || -> i32 { panic!("nested") });
+            panic!("after the original and a nested hook")
+        });
+        assert_eq!(result, Some(-1));
+    }
+}
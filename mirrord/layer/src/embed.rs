@@ -0,0 +1,194 @@
+//! Stable C ABI for starting the layer explicitly, from inside the process.
+//!
+//! Meant for runtimes that can't use `LD_PRELOAD`/`DYLD_INSERT_LIBRARIES` (custom launchers, test
+//! frameworks). Such a runtime loads the layer with `dlopen`, and calls [`mirrord_layer_start`]
+//! before running the user code. When the layer is loaded this way (it's not in [`PRELOAD_ENV`]),
+//! the library constructor does not initialize it.
+//!
+//! The declarations are available in `include/mirrord_layer.h`:
+//!
+//! ```c
+//! mirrord_layer_handle_t *mirrord_layer_start(const char *config_json);
+//! int mirrord_layer_status(const mirrord_layer_handle_t *handle);
+//! void mirrord_layer_stop(mirrord_layer_handle_t *handle);
+//! ```
+//!
+//! The layer still connects to the internal proxy given in
+//! [`MIRRORD_LAYER_INTPROXY_ADDR`](mirrord_config::MIRRORD_LAYER_INTPROXY_ADDR), exactly like
+//! when it's preloaded.
+
+use std::{
+    ffi::{CStr, OsStr},
+    fs,
+    ops::Not,
+    os::unix::ffi::OsStrExt,
+    panic,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use libc::{c_char, c_int};
+use mirrord_config::LayerConfig;
+
+use crate::{SETUP, error::LayerError, layer_pre_initialization};
+
+/// Returned from [`mirrord_layer_status`] when the layer intercepts the process' calls.
+pub const MIRRORD_LAYER_STATUS_RUNNING: c_int = 0;
+
+/// Returned from [`mirrord_layer_status`] after [`mirrord_layer_stop`].
+pub const MIRRORD_LAYER_STATUS_STOPPED: c_int = 1;
+
+/// Returned from [`mirrord_layer_status`] when the handle is invalid.
+pub const MIRRORD_LAYER_STATUS_INVALID: c_int = -1;
+
+/// Environment variable with the libraries preloaded into the process.
+#[cfg(target_os = "macos")]
+pub(crate) const PRELOAD_ENV: &str = "DYLD_INSERT_LIBRARIES";
+
+/// Environment variable with the libraries preloaded into the process.
+#[cfg(not(target_os = "macos"))]
+pub(crate) const PRELOAD_ENV: &str = "LD_PRELOAD";
+
+/// Whether this library was loaded with [`PRELOAD_ENV`], rather than with `dlopen`.
+///
+/// Compares the path of this library (from `dladdr`) with the preloaded ones. When the path
+/// can't be found, the layer is assumed to be preloaded.
+pub(crate) fn is_preloaded() -> bool {
+    let Some(preloaded) = std::env::var_os(PRELOAD_ENV) else {
+        return false;
+    };
+
+    // SAFETY: `Dl_info` is plain data, and `dladdr` only writes to it.
+    let mut info = unsafe { std::mem::zeroed::<libc::Dl_info>() };
+    let found = unsafe { libc::dladdr(is_preloaded as *const libc::c_void, &mut info) };
+    if found == 0 || info.dli_fname.is_null() {
+        return true;
+    }
+
+    // SAFETY: `dli_fname` is a null-terminated string, valid while this library is loaded.
+    let layer = Path::new(OsStr::from_bytes(
+        unsafe { CStr::from_ptr(info.dli_fname) }.to_bytes(),
+    ));
+    let layer = fs::canonicalize(layer).unwrap_or_else(|_| layer.to_path_buf());
+
+    preloaded
+        .as_bytes()
+        .split(|byte| *byte == b':' || *byte == b' ')
+        .filter(|entry| entry.is_empty().not())
+        .map(|entry| Path::new(OsStr::from_bytes(entry)))
+        .any(|entry| {
+            // Entries without a slash are searched for in the library paths.
+            if entry.components().count() == 1 {
+                entry.file_name() == layer.file_name()
+            } else {
+                fs::canonicalize(entry).is_ok_and(|entry| entry == layer)
+            }
+        })
+}
+
+/// Set in [`mirrord_layer_stop`].
+///
+/// When set, [`DetourGuard::new`](crate::detour::DetourGuard::new) always fails, so the hooks
+/// call the original functions.
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the layer was stopped with [`mirrord_layer_stop`].
+pub(crate) fn is_stopped() -> bool {
+    STOPPED.load(Ordering::Relaxed)
+}
+
+/// Handle to the layer started with [`mirrord_layer_start`], opaque to the C code.
+///
+/// The layer can be started only once in a process, so the only valid handle is [`HANDLE`].
+#[derive(Debug)]
+pub struct MirrordLayerHandle {
+    _private: (),
+}
+
+static HANDLE: MirrordLayerHandle = MirrordLayerHandle { _private: () };
+
+/// Starts the layer in the current process, with the given resolved config.
+///
+/// `config_json` is the fully resolved [`LayerConfig`], serialized to JSON (the decoded value of
+/// [`LayerConfig::RESOLVED_CONFIG_ENV`]).
+///
+/// Returns null if the layer could not be started (the reason is printed to stderr), e.g. when
+/// it's already running in this process, or when the config skips this process. The process
+/// should not rely on mirrord after a failed start, as the hooks may already be installed.
+///
+/// # Safety
+///
+/// `config_json` must be a valid pointer to a null-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mirrord_layer_start(
+    config_json: *const c_char,
+) -> *mut MirrordLayerHandle {
+    if config_json.is_null() {
+        eprintln!("mirrord layer start failed: config is null");
+        return std::ptr::null_mut();
+    }
+
+    if SETUP.get().is_some() {
+        eprintln!("mirrord layer start failed: the layer is already running in this process");
+        return std::ptr::null_mut();
+    }
+
+    let config_json = unsafe { CStr::from_ptr(config_json) };
+    let config = match serde_json::from_slice::<LayerConfig>(config_json.to_bytes()) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("mirrord layer start failed: invalid config: {error}");
+            return std::ptr::null_mut();
+        }
+    };
+
+    let result = panic::catch_unwind(|| layer_pre_initialization(config));
+    match result {
+        Ok(Ok(())) if SETUP.get().is_some() => std::ptr::from_ref(&HANDLE).cast_mut(),
+        Ok(Ok(())) => {
+            eprintln!("mirrord layer start failed: this process is skipped in the config");
+            std::ptr::null_mut()
+        }
+        Ok(Err(LayerError::NoProcessFound)) => {
+            eprintln!("mirrord layer start failed: could not inspect the current process");
+            std::ptr::null_mut()
+        }
+        Ok(Err(error)) => {
+            eprintln!("mirrord layer start failed with {error:?}");
+            std::ptr::null_mut()
+        }
+        Err(..) => {
+            eprintln!("mirrord layer start panicked");
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Returns the status of the layer started with [`mirrord_layer_start`]: one of
+/// [`MIRRORD_LAYER_STATUS_RUNNING`], [`MIRRORD_LAYER_STATUS_STOPPED`],
+/// [`MIRRORD_LAYER_STATUS_INVALID`].
+#[unsafe(no_mangle)]
+pub extern "C" fn mirrord_layer_status(handle: *const MirrordLayerHandle) -> c_int {
+    if std::ptr::eq(handle, &HANDLE).not() {
+        MIRRORD_LAYER_STATUS_INVALID
+    } else if is_stopped() {
+        MIRRORD_LAYER_STATUS_STOPPED
+    } else {
+        MIRRORD_LAYER_STATUS_RUNNING
+    }
+}
+
+/// Stops the layer started with [`mirrord_layer_start`].
+///
+/// The hooks cannot be removed, but from now on they call the original functions. The layer
+/// cannot be started again in this process. The handle remains valid for
+/// [`mirrord_layer_status`].
+#[unsafe(no_mangle)]
+pub extern "C" fn mirrord_layer_stop(handle: *mut MirrordLayerHandle) {
+    if std::ptr::eq(handle, &HANDLE).not() {
+        return;
+    }
+
+    STOPPED.store(true, Ordering::Relaxed);
+    tracing::info!("mirrord-layer stopped through the C ABI");
+}
@@ -118,6 +118,16 @@ pub(crate) enum HookError {
 
     #[error("mirrord-layer: Failed encoding value with `{0}`!")]
     BincodeEncode(#[from] bincode::error::EncodeError),
+
+    /// When the user's application tries to open a remote file with `O_DIRECT`, and
+    /// `fs.downgrade_o_direct` is not set.
+    #[error("mirrord-layer: Remote file `{0}` cannot be opened for direct I/O")]
+    DirectIoUnsupported(String),
+
+    /// When the user's application waits for a lock on a remote file for longer than
+    /// `fs.lock_timeout`.
+    #[error("mirrord-layer: Timed out waiting for a lock on remote file `{0}`")]
+    FileLockTimeout(String),
 }
 
 /// Errors internal to mirrord-layer.
@@ -234,7 +244,7 @@ impl From<HookError> for i64 {
                     err => format!("Proxy error, connectivity issue or a bug: {err}"),
                 };
                 graceful_exit!(
-                    r"{reason}.
+                    r"{reason}
                     Please report it to us on https://github.com/metalbear-co/mirrord/issues/new?assignees=&labels=bug&projects=&template=bug_report.yml
                     You can find the `mirrord-intproxy` logs in {}.",
                     crate::setup()
@@ -244,6 +254,9 @@ impl From<HookError> for i64 {
                         .display()
                 );
             }
+            HookError::ResponseError(ref response_fail) => {
+                error!(code = %response_fail.code(), "Error occured in Layer >> {fail:?}")
+            }
             _ => error!("Error occured in Layer >> {fail:?}"),
         };
 
@@ -287,9 +300,11 @@ impl From<HookError> for i64 {
                 ResponseError::NotImplemented => libc::EINVAL,
                 ResponseError::StripPrefix(_) => libc::EINVAL,
                 err @ (ResponseError::Forbidden { .. }
-                | ResponseError::ForbiddenWithReason { .. }) => {
+                | ResponseError::ForbiddenWithReason { .. }
+                | ResponseError::QuotaExceeded { .. }) => {
                     graceful_exit!(
-                        "Stopping mirrord run. Please adjust your mirrord configuration.\n{err}"
+                        "Stopping mirrord run. Please adjust your mirrord configuration.\n{}",
+                        err.with_code()
                     );
                     libc::EINVAL
                 }
@@ -312,6 +327,8 @@ impl From<HookError> for i64 {
             #[cfg(target_os = "linux")]
             HookError::EmptyPath => libc::ENOENT,
             HookError::InvalidBindAddressForDomain => libc::EINVAL,
+            HookError::DirectIoUnsupported(_) => libc::EINVAL,
+            HookError::FileLockTimeout(_) => libc::EDEADLK,
         };
 
         Errno::set_raw(libc_error);
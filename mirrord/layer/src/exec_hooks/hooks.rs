@@ -8,6 +8,8 @@ use mirrord_layer_macro::hook_guard_fn;
 use super::*;
 #[cfg(not(target_os = "macos"))]
 use crate::common::CheckedInto;
+#[cfg(not(target_os = "macos"))]
+use crate::detour::contain_panic;
 #[cfg(target_os = "macos")]
 use crate::exec_utils::*;
 use crate::{
@@ -69,8 +71,8 @@ unsafe fn environ() -> *const *const c_char {
 unsafe extern "C" fn execv_detour(path: *const c_char, argv: *const *const c_char) -> c_int {
     unsafe {
         let envp = environ();
-        match prepare_execve_envp(envp.checked_into()) {
-            Detour::Success(envp) => FN_EXECVE(path, argv, envp.leak()),
+        match contain_panic("execv_detour", || prepare_execve_envp(envp.checked_into())) {
+            Some(Detour::Success(envp)) => FN_EXECVE(path, argv, envp.leak()),
             _ => FN_EXECVE(path, argv, envp),
         }
     }
@@ -87,8 +89,8 @@ pub(crate) unsafe extern "C" fn execve_detour(
     envp: *const *const c_char,
 ) -> c_int {
     unsafe {
-        match prepare_execve_envp(envp.checked_into()) {
-            Detour::Success(envp) => FN_EXECVE(path, argv, envp.leak()),
+        match contain_panic("execve_detour", || prepare_execve_envp(envp.checked_into())) {
+            Some(Detour::Success(envp)) => FN_EXECVE(path, argv, envp.leak()),
             _ => FN_EXECVE(path, argv, envp),
         }
     }
@@ -326,17 +326,21 @@ pub(crate) unsafe extern "C" fn dlopen_detour(
     mode: c_int,
 ) -> *const c_void {
     unsafe {
-        // we hold the guard manually for tracing/internal code
-        let guard = crate::detour::DetourGuard::new();
-        let detour: Detour<PathBuf> = raw_path.checked_into();
-        let raw_path = if let Bypass(FileOperationInMirrordBinTempDir(ptr)) = detour {
-            trace!("dlopen called with a path inside our patch dir, switching with fixed pointer.");
-            ptr
-        } else {
-            trace!("dlopen called on path {detour:?}.");
-            raw_path
-        };
-        drop(guard);
+        let raw_path = crate::detour::contain_panic("dlopen_detour", || {
+            // we hold the guard manually for tracing/internal code
+            let _guard = crate::detour::DetourGuard::new();
+            let detour: Detour<PathBuf> = raw_path.checked_into();
+            if let Bypass(FileOperationInMirrordBinTempDir(ptr)) = detour {
+                trace!(
+                    "dlopen called with a path inside our patch dir, switching with fixed pointer."
+                );
+                ptr
+            } else {
+                trace!("dlopen called on path {detour:?}.");
+                raw_path
+            }
+        })
+        .unwrap_or(raw_path);
         // call dlopen guardless
         FN_DLOPEN(raw_path, mode)
     }
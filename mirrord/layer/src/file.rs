@@ -46,6 +46,20 @@ pub(crate) trait OpenOptionsInternalExt {
     fn from_mode(mode: String) -> Self;
 }
 
+/// Whether the given `open` flags request direct I/O (`O_DIRECT`).
+pub(crate) fn is_direct_io(flags: c_int) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        flags & libc::O_DIRECT != 0
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = flags;
+        false
+    }
+}
+
 impl OpenOptionsInternalExt for OpenOptionsInternal {
     fn from_flags(flags: c_int) -> Self {
         OpenOptionsInternal {
@@ -23,8 +23,8 @@ use mirrord_layer_macro::{hook_fn, hook_guard_fn};
 #[cfg(target_os = "linux")]
 use mirrord_protocol::ResponseError::{NotDirectory, NotFound};
 use mirrord_protocol::file::{
-    FsMetadataInternalV2, MetadataInternal, ReadFileResponse, ReadLinkFileResponse, Timespec,
-    WriteFileResponse,
+    FileLockKind, FileLockRange, FileLockWhence, FsMetadataInternalV2, MetadataInternal,
+    ReadFileResponse, ReadLinkFileResponse, Timespec, WriteFileResponse,
 };
 use nix::errno::Errno;
 use num_traits::Bounded;
@@ -32,13 +32,13 @@ use tracing::trace;
 #[cfg(target_os = "linux")]
 use tracing::{error, info, warn};
 
-use super::{OpenOptionsInternalExt, open_dirs, ops::*};
+use super::{OpenOptionsInternalExt, is_direct_io, open_dirs, ops::*};
 #[cfg(target_os = "linux")]
 use crate::error::HookError::ResponseError;
 use crate::{
     close_layer_fd,
     common::CheckedInto,
-    detour::{Bypass, Detour, DetourGuard},
+    detour::{Bypass, Detour, DetourGuard, contain_hook_panic, contain_panic},
     error::HookError,
     file::{
         open_dirs::OPEN_DIRS,
@@ -82,7 +82,7 @@ unsafe fn open_logic(raw_path: *const c_char, open_flags: c_int, _mode: c_int) -
 
     trace!("path {:#?} | open_options {:#?}", path, open_options);
 
-    open(path, open_options)
+    open(path, open_options, is_direct_io(open_flags))
 }
 
 /// Hook for `libc::open`.
@@ -99,13 +99,16 @@ pub(super) unsafe extern "C" fn open_detour(
         let mode: c_int = args.arg();
         let guard = DetourGuard::new();
         if guard.is_none() {
-            FN_OPEN(raw_path, open_flags, mode)
-        } else {
+            return FN_OPEN(raw_path, open_flags, mode);
+        }
+
+        contain_hook_panic("open_detour", &FN_OPEN, || {
             open_logic(raw_path, open_flags, mode).unwrap_or_bypass_with(|bypass| {
                 let raw_path = update_ptr_from_bypass(raw_path, &bypass);
                 FN_OPEN(raw_path, open_flags, mode)
             })
-        }
+        })
+        .unwrap_or_else(|| FN_OPEN(raw_path, open_flags, mode))
     }
 }
 
@@ -123,13 +126,16 @@ pub(super) unsafe extern "C" fn open64_detour(
         let mode: c_int = args.arg();
         let guard = DetourGuard::new();
         if guard.is_none() {
-            FN_OPEN64(raw_path, open_flags, mode)
-        } else {
+            return FN_OPEN64(raw_path, open_flags, mode);
+        }
+
+        contain_hook_panic("open64_detour", &FN_OPEN64, || {
             open_logic(raw_path, open_flags, mode).unwrap_or_bypass_with(|bypass| {
                 let raw_path = update_ptr_from_bypass(raw_path, &bypass);
                 FN_OPEN64(raw_path, open_flags, mode)
             })
-        }
+        })
+        .unwrap_or_else(|| FN_OPEN64(raw_path, open_flags, mode))
     }
 }
 
@@ -144,13 +150,16 @@ pub(super) unsafe extern "C" fn open_nocancel_detour(
         let mode: c_int = args.arg();
         let guard = DetourGuard::new();
         if guard.is_none() {
-            FN_OPEN_NOCANCEL(raw_path, open_flags, mode)
-        } else {
+            return FN_OPEN_NOCANCEL(raw_path, open_flags, mode);
+        }
+
+        contain_hook_panic("open_nocancel_detour", &FN_OPEN_NOCANCEL, || {
             open_logic(raw_path, open_flags, mode).unwrap_or_bypass_with(|bypass| {
                 let raw_path = update_ptr_from_bypass(raw_path, &bypass);
                 FN_OPEN_NOCANCEL(raw_path, open_flags, mode)
             })
-        }
+        })
+        .unwrap_or_else(|| FN_OPEN_NOCANCEL(raw_path, open_flags, mode))
     }
 }
 
@@ -237,12 +246,18 @@ pub(super) unsafe extern "C" fn sendfile_detour(
             return -1;
         };
 
-        sendfile_impl(fd, s, Some(offset), *count as usize)
-            .map(|(written, _)| {
-                *count = written as off_t;
-                0
-            })
-            .unwrap_or(-1)
+        contain_panic("sendfile_detour", || {
+            sendfile_impl(fd, s, Some(offset), *count as usize)
+        })
+        .map(|result| {
+            result
+                .map(|(written, _)| {
+                    *count = written as off_t;
+                    0
+                })
+                .unwrap_or(-1)
+        })
+        .unwrap_or_else(|| FN_SENDFILE(fd, s, offset, len, _hdtr, _flags))
     }
 }
 
@@ -262,14 +277,20 @@ pub(super) unsafe extern "C" fn sendfile_detour(
             Some(*offset)
         };
 
-        sendfile_impl(in_fd, out_fd, offset_val, count)
-            .map(|(written, new_offset)| {
-                if !offset.is_null() {
-                    *offset = new_offset;
-                }
-                written
-            })
-            .unwrap_or(-1)
+        contain_panic("sendfile_detour", || {
+            sendfile_impl(in_fd, out_fd, offset_val, count)
+        })
+        .map(|result| {
+            result
+                .map(|(written, new_offset)| {
+                    if !offset.is_null() {
+                        *offset = new_offset;
+                    }
+                    written
+                })
+                .unwrap_or(-1)
+        })
+        .unwrap_or_else(|| FN_SENDFILE(out_fd, in_fd, offset, count))
     }
 }
 
@@ -329,6 +350,67 @@ pub(super) unsafe extern "C" fn fchmod_detour(fd: c_int, mode: mode_t) -> c_int
         .unwrap_or_bypass_with(|_| unsafe { FN_FCHMOD(fd, mode) })
 }
 
+/// Hook for [`libc::flock`].
+#[hook_guard_fn]
+pub(super) unsafe extern "C" fn flock_detour(fd: c_int, operation: c_int) -> c_int {
+    flock(fd, operation)
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|_| unsafe { FN_FLOCK(fd, operation) })
+}
+
+/// Handles the record lock commands of [`libc::fcntl`] on remote files.
+///
+/// Called from the `fcntl` hooks, before the original function. Returns [`None`] if the call
+/// should go to the original function, i.e. when `cmd` is not a lock command, or the file is not
+/// remote.
+///
+/// `F_GETLK` is not handled, so it always reports the local file as unlocked.
+///
+/// # Safety
+///
+/// `arg` must be a valid pointer to [`libc::flock`] when `cmd` is a lock command.
+pub(crate) unsafe fn fcntl_lock_logic(fd: RawFd, cmd: c_int, arg: usize) -> Option<c_int> {
+    let wait = match cmd {
+        libc::F_SETLK => false,
+        libc::F_SETLKW => true,
+        #[cfg(target_os = "linux")]
+        libc::F_OFD_SETLK => false,
+        #[cfg(target_os = "linux")]
+        libc::F_OFD_SETLKW => true,
+        _ => return None,
+    };
+
+    let _guard = DetourGuard::new()?;
+
+    let lock = unsafe { (arg as *const libc::flock).as_ref() }?;
+
+    let kind = match c_int::from(lock.l_type) {
+        libc::F_RDLCK => FileLockKind::Shared,
+        libc::F_WRLCK => FileLockKind::Exclusive,
+        libc::F_UNLCK => FileLockKind::Unlock,
+        _ => return None,
+    };
+
+    let whence = match c_int::from(lock.l_whence) {
+        libc::SEEK_SET => FileLockWhence::Start,
+        libc::SEEK_CUR => FileLockWhence::Current,
+        libc::SEEK_END => FileLockWhence::End,
+        _ => return None,
+    };
+
+    let range = FileLockRange {
+        whence,
+        start: lock.l_start,
+        len: lock.l_len,
+    };
+
+    match file_lock(fd, kind, Some(range), wait) {
+        Detour::Success(()) => Some(0),
+        Detour::Bypass(_) => None,
+        Detour::Error(error) => Some(error.into()),
+    }
+}
+
 /// see below, to have nice code we also implement it for other archs.
 #[cfg(not(all(target_os = "macos", target_arch = "aarch64")))]
 unsafe fn opendir_bypass(raw_filename: *const c_char) -> usize {
@@ -492,15 +574,24 @@ pub(crate) unsafe extern "C" fn openat_detour(
 
         let guard = DetourGuard::new();
         if guard.is_none() {
-            FN_OPENAT(fd, raw_path, open_flags, mode)
-        } else {
+            return FN_OPENAT(fd, raw_path, open_flags, mode);
+        }
+
+        contain_hook_panic("openat_detour", &FN_OPENAT, || {
             let open_options = OpenOptionsInternalExt::from_flags(open_flags);
 
-            openat(fd, raw_path.checked_into(), open_options).unwrap_or_bypass_with(|bypass| {
+            openat(
+                fd,
+                raw_path.checked_into(),
+                open_options,
+                is_direct_io(open_flags),
+            )
+            .unwrap_or_bypass_with(|bypass| {
                 let raw_path = update_ptr_from_bypass(raw_path, &bypass);
                 FN_OPENAT(fd, raw_path, open_flags, mode)
             })
-        }
+        })
+        .unwrap_or_else(|| FN_OPENAT(fd, raw_path, open_flags, mode))
     }
 }
 
@@ -518,7 +609,13 @@ pub(crate) unsafe extern "C" fn openat64_detour(
     unsafe {
         let open_options = OpenOptionsInternalExt::from_flags(open_flags);
 
-        openat(fd, raw_path.checked_into(), open_options).unwrap_or_bypass_with(|bypass| {
+        openat(
+            fd,
+            raw_path.checked_into(),
+            open_options,
+            is_direct_io(open_flags),
+        )
+        .unwrap_or_bypass_with(|bypass| {
             let raw_path = update_ptr_from_bypass(raw_path, &bypass);
             FN_OPENAT64(fd, raw_path, open_flags)
         })
@@ -534,7 +631,13 @@ pub(crate) unsafe extern "C" fn openat_nocancel_detour(
     unsafe {
         let open_options = OpenOptionsInternalExt::from_flags(open_flags);
 
-        openat(fd, raw_path.checked_into(), open_options).unwrap_or_bypass_with(|bypass| {
+        openat(
+            fd,
+            raw_path.checked_into(),
+            open_options,
+            is_direct_io(open_flags),
+        )
+        .unwrap_or_bypass_with(|bypass| {
             let raw_path = update_ptr_from_bypass(raw_path, &bypass);
             FN_OPENAT_NOCANCEL(fd, raw_path, open_flags)
         })
@@ -876,19 +979,19 @@ pub(crate) unsafe extern "C" fn faccessat_detour(
 /// Hook for `libc::fsync`.
 #[hook_guard_fn]
 pub(crate) unsafe extern "C" fn fsync_detour(fd: RawFd) -> c_int {
-    unsafe { fsync(fd).unwrap_or_bypass_with(|_| FN_FSYNC(fd)) }
+    unsafe { fsync(fd, false).unwrap_or_bypass_with(|_| FN_FSYNC(fd)) }
 }
 
 /// Hook for `fsync$NOCANCEL`.
 #[hook_guard_fn]
 pub(crate) unsafe extern "C" fn fsync_nocancel_detour(fd: RawFd) -> c_int {
-    unsafe { fsync(fd).unwrap_or_bypass_with(|_| FN_FSYNC_NOCANCEL(fd)) }
+    unsafe { fsync(fd, false).unwrap_or_bypass_with(|_| FN_FSYNC_NOCANCEL(fd)) }
 }
 
 /// Hook for `libc::fdatasync`.
 #[hook_guard_fn]
 pub(crate) unsafe extern "C" fn fdatasync_detour(fd: RawFd) -> c_int {
-    unsafe { fsync(fd).unwrap_or_bypass_with(|_| FN_FDATASYNC(fd)) }
+    unsafe { fsync(fd, true).unwrap_or_bypass_with(|_| FN_FDATASYNC(fd)) }
 }
 
 /// Tries to convert input to type O, if it fails it returns the max value of O.
@@ -1947,5 +2050,7 @@ pub(crate) unsafe fn enable_file_hooks(hook_manager: &mut HookManager, state: &L
         replace!(hook_manager, "fchown", fchown_detour, FnFchown, FN_FCHOWN);
 
         replace!(hook_manager, "fchmod", fchmod_detour, FnFchmod, FN_FCHMOD);
+
+        replace!(hook_manager, "flock", flock_detour, FnFlock, FN_FLOCK);
     }
 }
@@ -3,14 +3,16 @@
 //! When operating on the paths provided from the user application, remember to verify/remap them.
 //! Canonical order of operations can be found in [`common_path_check`].
 
-#[cfg(target_os = "linux")]
-use std::time::Duration;
 use std::{
     env,
     ffi::CString,
-    io::SeekFrom,
-    os::unix::io::RawFd,
+    fs::{File, Permissions},
+    io::{SeekFrom, Write},
+    ops::Not,
+    os::unix::{fs::PermissionsExt, io::RawFd},
     path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
 };
 
 use libc::{AT_FDCWD, c_int, iovec};
@@ -19,10 +21,11 @@ use libc::{c_char, statx, statx_timestamp};
 use mirrord_config::feature::fs::FsModeConfig;
 use mirrord_layer_lib::file::filter::FileFilter;
 use mirrord_protocol::{
-    Payload, ResponseError,
+    ErrorKindInternal, Payload, ResponseError,
     file::{
-        FchmodRequest, FchownRequest, FtruncateRequest, FutimensRequest, MakeDirAtRequest,
-        MakeDirRequest, OpenFileRequest, OpenFileResponse, OpenOptionsInternal, ReadFileResponse,
+        FchmodRequest, FchownRequest, FileLockKind, FileLockRange, FileLockRequest, FsyncRequest,
+        FtruncateRequest, FutimensRequest, GetCwdRequest, MakeDirAtRequest, MakeDirRequest,
+        OpenFileRequest, OpenFileResponse, OpenOptionsInternal, ReadFileResponse,
         ReadLinkFileRequest, ReadLinkFileResponse, RemoveDirRequest, RenameRequest,
         SeekFileResponse, StatFsRequestV2, Timespec, UnlinkAtRequest, UnlinkRequest,
         WriteFileResponse, XstatFsRequestV2, XstatFsResponseV2, XstatResponse,
@@ -39,13 +42,19 @@ use super::{hooks::FN_OPEN, open_dirs::OPEN_DIRS, *};
 use crate::common::CheckedInto;
 use crate::{
     common,
-    detour::{Bypass, Detour},
+    detour::{Bypass, Detour, OnceLockExt},
     error::{HookError, HookResult as Result},
 };
 
 /// 1 Megabyte. Large read requests can lead to timeouts.
 const MAX_READ_SIZE: u64 = 1024 * 1024;
 
+/// Initial delay between attempts to take a contended remote file lock, see [`file_lock`].
+const FILE_LOCK_MIN_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Maximal delay between attempts to take a contended remote file lock, see [`file_lock`].
+const FILE_LOCK_MAX_BACKOFF: Duration = Duration::from_millis(500);
+
 /// Convenience extension for verifying that a [`Path`] is not relative.
 trait PathExt {
     /// If this [`Path`] is relative and is not present in the `fs.not_found` filters, returns a
@@ -90,6 +99,7 @@ pub fn ensure_remote(file_filter: &FileFilter, path: &Path, write: bool) -> Deto
         _ if file_filter.default_not_found.is_match(text) => {
             Detour::Error(HookError::FileNotFound(text.to_string()))
         }
+        _ if file_filter.procfs.is_match(text) && !write => Detour::Success(()),
         _ if file_filter.default_remote_ro.is_match(text) && !write => Detour::Success(()),
         _ if file_filter.default_local.is_match(text) => Detour::Bypass(Bypass::ignored_file(text)),
         FsModeConfig::LocalWithOverrides => Detour::Bypass(Bypass::ignored_file(text)),
@@ -99,19 +109,208 @@ pub fn ensure_remote(file_filter: &FileFilter, path: &Path, write: bool) -> Deto
     }
 }
 
+/// Bypasses with the local file from `fs.overrides`, if there is one for the given path.
+fn ensure_not_overridden(path: &Path) -> Detour<()> {
+    match crate::setup().file_overrides().local_path(path) {
+        Some(local_path) => Detour::Bypass(Bypass::ignored_file(
+            local_path.as_os_str().as_encoded_bytes(),
+        )),
+        None => Detour::Success(()),
+    }
+}
+
+/// Bypasses with the local shadow copy from `fs.materialize`, if the given path should be
+/// materialized.
+///
+/// Regular files are downloaded to the shadow directory the first time they're accessed.
+/// Remote directories are not materialized, they go through the other checks of
+/// [`common_path_check`].
+fn ensure_not_materialized(path: &Path) -> Detour<()> {
+    let setup = crate::setup();
+    if setup
+        .file_filter()
+        .materialize
+        .is_match(path.to_str().unwrap_or_default())
+        .not()
+    {
+        return Detour::Success(());
+    }
+
+    let shadow_path = setup
+        .materialize_dir()
+        .join(path.strip_prefix("/").unwrap_or(path));
+
+    if shadow_path.symlink_metadata().is_err() && materialize(path, &shadow_path)?.not() {
+        return Detour::Success(());
+    }
+
+    Detour::Bypass(Bypass::ignored_file(
+        shadow_path.as_os_str().as_encoded_bytes(),
+    ))
+}
+
+/// Downloads the remote file at `path` to `shadow_path`.
+///
+/// When the remote file does not exist, only creates the parent directories of `shadow_path`, so
+/// that the application can create the file in the shadow directory.
+///
+/// Returns `false` if the remote path is not a regular file (e.g. a directory).
+fn materialize(path: &Path, shadow_path: &Path) -> Detour<bool> {
+    let Some(parent) = shadow_path.parent() else {
+        return Detour::Success(false);
+    };
+
+    let xstat = XstatRequest {
+        path: Some(path.to_path_buf()),
+        fd: None,
+        follow_symlink: true,
+    };
+    let metadata = match common::make_proxy_request_with_response(xstat)? {
+        Ok(XstatResponse { metadata }) => metadata,
+        Err(ResponseError::RemoteIO(error)) if error.kind == ErrorKindInternal::NotFound => {
+            std::fs::create_dir_all(parent)?;
+            return Detour::Success(true);
+        }
+        Err(error) => return Detour::Error(error.into()),
+    };
+
+    if metadata.mode & libc::S_IFMT as u32 != libc::S_IFREG as u32 {
+        return Detour::Success(false);
+    }
+
+    std::fs::create_dir_all(parent)?;
+
+    // Download to a temporary file first, so that concurrent accesses never see a partial copy.
+    let temp_path = parent.join(format!(
+        ".mirrord-{}",
+        Alphanumeric.sample_string(&mut rand::rng(), 16)
+    ));
+
+    let OpenFileResponse { fd } = RemoteFile::remote_open(
+        path.to_path_buf(),
+        OpenOptionsInternal {
+            read: true,
+            ..Default::default()
+        },
+    )?;
+
+    let downloaded = download(fd, &temp_path).and_then(|()| {
+        std::fs::set_permissions(&temp_path, Permissions::from_mode(metadata.mode & 0o7777))?;
+        std::fs::rename(&temp_path, shadow_path)?;
+        Detour::Success(())
+    });
+
+    let _ = RemoteFile::remote_close(fd).inspect_err(|fail| {
+        trace!("Leaking remote file fd (should be harmless) due to {fail:#?}!")
+    });
+
+    if matches!(downloaded, Detour::Success(())).not() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    downloaded?;
+
+    trace!(
+        ?path,
+        ?shadow_path,
+        size = metadata.size,
+        "Materialized remote file"
+    );
+
+    Detour::Success(true)
+}
+
+/// Reads the whole remote file into a new local file at `local_path`.
+fn download(fd: u64, local_path: &Path) -> Detour<()> {
+    let mut file = File::create(local_path)?;
+
+    loop {
+        let ReadFileResponse { bytes, read_amount } = RemoteFile::remote_read(fd, MAX_READ_SIZE)?;
+        if read_amount == 0 {
+            return Detour::Success(());
+        }
+
+        file.write_all(&bytes)?;
+    }
+}
+
+/// Working directory of the target, fetched on the first path that matches `fs.remote_cwd`.
+static REMOTE_CWD: OnceLock<PathBuf> = OnceLock::new();
+
+/// Resolves a relative path against the working directory of the target, if it matches
+/// `fs.remote_cwd`.
+///
+/// Other paths are returned as they are.
+fn resolve_in_remote_cwd(path: PathBuf) -> Detour<PathBuf> {
+    if path.is_absolute()
+        || crate::setup()
+            .file_filter()
+            .remote_cwd
+            .is_match(path.to_str().unwrap_or_default())
+            .not()
+    {
+        return Detour::Success(path);
+    }
+
+    let cwd =
+        REMOTE_CWD.get_or_detour_init(|| {
+            match common::make_proxy_request_with_response(GetCwdRequest)? {
+                Ok(response) => Detour::Success(response.path),
+                Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+                Err(fail) => Detour::Error(fail.into()),
+            }
+        })?;
+
+    Detour::Success(cwd.join(path))
+}
+
+/// Translates a local path into the remote one according to `fs.path_translation`.
+///
+/// Relative paths are resolved against the current working directory.
+fn translate_to_remote(path: PathBuf) -> PathBuf {
+    let translator = crate::setup().path_translator();
+    if translator.is_empty() {
+        return path;
+    }
+
+    let cwd = env::current_dir().unwrap_or_default();
+    translator.to_remote(&path, &cwd).unwrap_or(path)
+}
+
+/// [`ensure_remote`], but when the path should be accessed locally, bypasses with the local path
+/// from `fs.path_translation` (if there is one).
+fn ensure_remote_or_translated(path: &Path, write: bool) -> Detour<()> {
+    match ensure_remote(crate::setup().file_filter(), path, write) {
+        Detour::Bypass(bypass) => match crate::setup().path_translator().to_local(path) {
+            Some(local_path) => Detour::Bypass(Bypass::ignored_file(
+                local_path.as_os_str().as_encoded_bytes(),
+            )),
+            None => Detour::Bypass(bypass),
+        },
+        other => other,
+    }
+}
+
 /// Performs standard verification of paths accessed by the user application.
 ///
 /// Operations in order:
-/// 1. Bypass if the path is not relative and not present in the `fs.not_found` filters.
-/// 2. Remap the file according to the config.
-/// 3. Bypass if the new path should be accessed locally.
+/// 1. Translate the path to the remote one according to `fs.path_translation`.
+/// 2. Resolve the path against the target's working directory if it's present in `fs.remote_cwd`.
+/// 3. Bypass if the path is not relative and not present in the `fs.not_found` filters.
+/// 4. Remap the file according to the config.
+/// 5. Bypass with the local file if the new path is present in `fs.overrides`.
+/// 6. Bypass with the local shadow copy if the new path is present in `fs.materialize`.
+/// 7. Bypass if the new path should be accessed locally, translating it back to the local path.
 ///
 /// Returns the remapped path.
 fn common_path_check(path: PathBuf, write: bool) -> Detour<PathBuf> {
+    let path = translate_to_remote(path);
+    let path = resolve_in_remote_cwd(path)?;
     path.ensure_not_relative_or_not_found()?;
 
     let path = crate::setup().file_remapper().change_path(path);
-    ensure_remote(crate::setup().file_filter(), &path, write)?;
+    ensure_not_overridden(&path)?;
+    ensure_not_materialized(&path)?;
+    ensure_remote_or_translated(&path, write)?;
     Detour::Success(path)
 }
 
@@ -253,8 +452,15 @@ fn close_remote_file_on_failure(fd: u64) -> Result<()> {
 /// _local_ and _remote_ file association, plus **inserting** it into the storage for
 /// [`OPEN_FILES`].
 #[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
-pub(crate) fn open(path: Detour<PathBuf>, open_options: OpenOptionsInternal) -> Detour<RawFd> {
+pub(crate) fn open(
+    path: Detour<PathBuf>,
+    open_options: OpenOptionsInternal,
+    direct_io: bool,
+) -> Detour<RawFd> {
     let path = common_path_check(path?, open_options.is_write())?;
+    if direct_io {
+        ensure_direct_io_allowed(&path)?;
+    }
 
     let OpenFileResponse { fd: remote_fd } = RemoteFile::remote_open(path.clone(), open_options)
         .or_else(|fail| match fail {
@@ -276,6 +482,22 @@ pub(crate) fn open(path: Detour<PathBuf>, open_options: OpenOptionsInternal) ->
     Detour::Success(local_file_fd)
 }
 
+/// Remote files have no local page cache to bypass, so we can't honor `O_DIRECT` for them.
+///
+/// Fails like filesystems without direct I/O support do, unless `fs.downgrade_o_direct` is set,
+/// in which case the file is opened with buffered I/O.
+fn ensure_direct_io_allowed(path: &Path) -> Detour<()> {
+    if crate::setup().fs_config().downgrade_o_direct {
+        tracing::warn!(
+            path = %path.display(),
+            "Opening a remote file with buffered I/O instead of O_DIRECT"
+        );
+        Detour::Success(())
+    } else {
+        Detour::Error(HookError::DirectIoUnsupported(path.display().to_string()))
+    }
+}
+
 /// creates a directory stream for the `remote_fd` in the agent
 #[mirrord_layer_macro::instrument(level = "trace", ret)]
 pub(crate) fn fdopendir(fd: RawFd) -> Detour<usize> {
@@ -307,18 +529,23 @@ pub(crate) fn openat(
     fd: RawFd,
     path: Detour<PathBuf>,
     open_options: OpenOptionsInternal,
+    direct_io: bool,
 ) -> Detour<RawFd> {
     let path = path?;
 
     // `openat` behaves the same as `open` when the path is absolute. When called with AT_FDCWD, the
     // call is propagated to `open`.
     if path.is_absolute() || fd == AT_FDCWD {
-        return open(Detour::Success(path), open_options);
+        return open(Detour::Success(path), open_options, direct_io);
+    }
+
+    let remote_fd = get_remote_fd(fd)?;
+    if direct_io {
+        ensure_direct_io_allowed(&path)?;
     }
 
     // Relative path requires special handling, we must identify the relative part
     // (relative to what).
-    let remote_fd = get_remote_fd(fd)?;
 
     let requesting_file = OpenRelativeFileRequest {
         relative_fd: remote_fd,
@@ -463,6 +690,7 @@ pub(crate) fn unlinkat(dirfd: RawFd, path: Detour<PathBuf>, flags: u32) -> Detou
     let mut path = path?;
 
     if dirfd == AT_FDCWD {
+        path = resolve_in_remote_cwd(path)?;
         path.ensure_not_relative_or_not_found()?;
     }
 
@@ -570,12 +798,89 @@ pub(crate) fn access(path: Detour<PathBuf>, mode: c_int) -> Detour<c_int> {
     Detour::Success(0)
 }
 
-/// Original function _flushes_ data from `fd` to disk, but we don't really do any of this
-/// for our managed fds, so we just return `0` which means success.
+/// Flushes the remote file to disk in the agent, with `data_only` for `fdatasync`.
+///
+/// Agents that don't support [`FsyncRequest`] are not asked, and we just return `0` which means
+/// success.
 #[mirrord_layer_macro::instrument(level = "trace", ret)]
-pub(crate) fn fsync(fd: RawFd) -> Detour<c_int> {
-    get_remote_fd(fd)?;
-    Detour::Success(0)
+pub(crate) fn fsync(fd: RawFd, data_only: bool) -> Detour<c_int> {
+    let fd = get_remote_fd(fd)?;
+
+    match common::make_proxy_request_with_response(FsyncRequest { fd, data_only })? {
+        Ok(()) | Err(ResponseError::NotImplemented) => Detour::Success(0),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+/// Places or removes an advisory lock on the remote file, see [`FileLockRequest`].
+///
+/// The agent never blocks on a contended lock, so when `wait` is set, we retry with a backoff
+/// for up to `fs.lock_timeout` seconds, and then fail with [`HookError::FileLockTimeout`].
+///
+/// Agents that don't support [`FileLockRequest`] are not asked, and the lock is taken on the
+/// local fd, as before.
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(crate) fn file_lock(
+    fd: RawFd,
+    kind: FileLockKind,
+    range: Option<FileLockRange>,
+    wait: bool,
+) -> Detour<()> {
+    let (remote_fd, path) = {
+        let files = OPEN_FILES.lock()?;
+        let file = files.get(&fd).ok_or(Bypass::LocalFdNotFound(fd))?;
+        (file.fd, file.path.clone())
+    };
+
+    let timeout = Duration::from_secs(crate::setup().fs_config().lock_timeout);
+    let started = std::time::Instant::now();
+    let mut backoff = FILE_LOCK_MIN_BACKOFF;
+
+    loop {
+        let request = FileLockRequest {
+            fd: remote_fd,
+            kind,
+            range,
+        };
+
+        match common::make_proxy_request_with_response(request)? {
+            Ok(()) => return Detour::Success(()),
+            Err(ResponseError::NotImplemented) => return Detour::Bypass(Bypass::NotImplemented),
+            Err(ResponseError::RemoteIO(error)) if error.kind == ErrorKindInternal::WouldBlock => {
+                if wait.not() {
+                    tracing::warn!(path, "Remote file lock is held by another process");
+                    return Detour::Error(ResponseError::RemoteIO(error).into());
+                }
+
+                if started.elapsed() >= timeout {
+                    tracing::warn!(
+                        path,
+                        ?timeout,
+                        "Timed out waiting for a remote file lock held by another process"
+                    );
+                    return Detour::Error(HookError::FileLockTimeout(path));
+                }
+
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(FILE_LOCK_MAX_BACKOFF);
+            }
+            Err(fail) => return Detour::Error(fail.into()),
+        }
+    }
+}
+
+/// Handles `flock` on a remote file, see [`file_lock`].
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(crate) fn flock(fd: RawFd, operation: c_int) -> Detour<()> {
+    let kind = match operation & !libc::LOCK_NB {
+        libc::LOCK_SH => FileLockKind::Shared,
+        libc::LOCK_EX => FileLockKind::Exclusive,
+        libc::LOCK_UN => FileLockKind::Unlock,
+        // Let the original call fail with `EINVAL`.
+        _ => return Detour::Bypass(Bypass::NotImplemented),
+    };
+
+    file_lock(fd, kind, None, operation & libc::LOCK_NB == 0)
 }
 
 /// General stat function that can be used for lstat, fstat, stat and fstatat.
@@ -603,7 +908,8 @@ pub(crate) fn xstat(
                     None
                 } else if path.is_absolute() {
                     path = crate::setup().file_remapper().change_path(path);
-                    ensure_remote(crate::setup().file_filter(), &path, true)?;
+                    ensure_not_overridden(&path)?;
+                    ensure_remote_or_translated(&path, true)?;
                     None
                 } else {
                     Some(get_remote_fd(fd)?)
@@ -1157,7 +1463,7 @@ mod test {
         #[case] write: bool,
         #[case] expected: DetourKind,
     ) {
-        use mirrord_config::feature::fs::READONLY_FILE_BUFFER_DEFAULT;
+        use mirrord_config::feature::fs::{LOCK_TIMEOUT_DEFAULT, READONLY_FILE_BUFFER_DEFAULT};
 
         let read_write = Some(VecOrSingle::Multiple(vec![
             r"/pain/read_write.*\.a".to_string(),
@@ -1174,7 +1480,16 @@ mod test {
             not_found,
             mode,
             mapping: None,
+            overrides: None,
+            path_translation: None,
+            materialize: None,
+            materialize_dir: None,
+            remote_cwd: None,
             readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+            lock_timeout: LOCK_TIMEOUT_DEFAULT,
+            procfs: false,
+            procfs_paths: None,
+            downgrade_o_direct: false,
         };
 
         let file_filter = FileFilter::new(fs_config);
@@ -1207,11 +1522,12 @@ mod test {
         #[case] write: bool,
         #[case] expected: DetourKind,
     ) {
-        use mirrord_config::feature::fs::READONLY_FILE_BUFFER_DEFAULT;
+        use mirrord_config::feature::fs::{LOCK_TIMEOUT_DEFAULT, READONLY_FILE_BUFFER_DEFAULT};
 
         let fs_config = FsConfig {
             mode,
             readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+            lock_timeout: LOCK_TIMEOUT_DEFAULT,
             ..Default::default()
         };
 
@@ -1223,6 +1539,30 @@ mod test {
         assert_eq!(res.kind(), expected);
     }
 
+    #[rstest]
+    #[case(true, "/proc/self/cgroup", false, DetourKind::Success)]
+    #[case(true, "/sys/fs/cgroup/memory.max", false, DetourKind::Success)]
+    #[case(true, "/sys/fs/cgroup/memory.max", true, DetourKind::Bypass)]
+    #[case(true, "/proc/self/environ", false, DetourKind::Bypass)]
+    #[case(false, "/proc/self/cgroup", false, DetourKind::Bypass)]
+    fn procfs_set(
+        #[case] procfs: bool,
+        #[case] path: &str,
+        #[case] write: bool,
+        #[case] expected: DetourKind,
+    ) {
+        let fs_config = FsConfig {
+            mode: FsModeConfig::Read,
+            procfs,
+            ..Default::default()
+        };
+
+        let file_filter = FileFilter::new(fs_config);
+
+        let res = ensure_remote(&file_filter, Path::new(path), write);
+        assert_eq!(res.kind(), expected);
+    }
+
     /// Sanity test for empty [`RegexSet`] behaviour.
     #[test]
     fn empty_regex_set() {
@@ -2,7 +2,11 @@ use std::arch::naked_asm;
 
 use tracing::trace;
 
-use crate::{HookManager, go::c_abi_syscall6_handler, macros::hook_symbol};
+use crate::{
+    HookManager,
+    go::{c_abi_syscall6_handler, raw_vfork_handler},
+    macros::hook_symbol,
+};
 
 type VoidFn = unsafe extern "C" fn() -> ();
 static mut FN_ASMCGOCALL: Option<VoidFn> = None;
@@ -81,6 +85,39 @@ unsafe extern "C" fn go_syscall_new_detour() {
     )
 }
 
+/// Detour for `syscall.rawVforkSyscall.abi0` function.
+///
+/// Arguments are on the stack, starting at the original sp+8 (trap, a1, a2, a3), followed by the
+/// results (r1, err).
+#[unsafe(naked)]
+unsafe extern "C" fn raw_vfork_detour() {
+    naked_asm!(
+        // save fp and lr, sp stays 16 bytes aligned
+        "stp x29, x30, [sp, -0x10]!",
+        "ldr x0, [sp, 0x20]",
+        "ldr x1, [sp, 0x28]",
+        "ldr x2, [sp, 0x30]",
+        "ldr x3, [sp, 0x18]",
+        "bl {vfork_handler}",
+        "ldp x29, x30, [sp], 0x10",
+        // check return code
+        "cmn x0, 0xfff",
+        "b.cc 2f",
+        // syscall fail flow
+        "mov x4, -0x1",
+        "str x4, [sp, 0x28]",
+        "neg x0, x0",
+        "str x0, [sp, 0x30]",
+        "ret",
+        // syscall success
+        "2:",
+        "str x0, [sp, 0x28]",
+        "str xzr, [sp, 0x30]",
+        "ret",
+        vfork_handler = sym raw_vfork_handler,
+    )
+}
+
 /// Hooks for when hooking a go binary between 1.19 and 1.23
 fn post_go1_19(hook_manager: &mut HookManager, module_name: Option<&str>) {
     if let Some(module_name) = module_name {
@@ -177,7 +214,14 @@ pub(crate) fn enable_hooks(hook_manager: &mut HookManager) {
         post_go1_19(hook_manager, None);
     } else {
         trace!("found version < 1.19, arm64 not supported - not hooking");
+        return;
     }
+
+    hook_symbol!(
+        hook_manager,
+        "syscall.rawVforkSyscall.abi0",
+        raw_vfork_detour
+    );
 }
 
 /// Same as [`enable_hooks`], but hook symbols found in the given `module_name`.
@@ -194,5 +238,13 @@ pub(crate) fn enable_hooks_in_loaded_module(hook_manager: &mut HookManager, modu
         post_go1_19(hook_manager, Some(module_name.as_str()));
     } else {
         trace!("found version < 1.19, arm64 not supported - not hooking");
+        return;
     }
+
+    hook_symbol!(
+        hook_manager,
+        module_name.as_str(),
+        "syscall.rawVforkSyscall.abi0",
+        raw_vfork_detour
+    );
 }
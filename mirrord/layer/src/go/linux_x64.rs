@@ -4,7 +4,8 @@ use nix::errno::Errno;
 use tracing::trace;
 
 use crate::{
-    close_detour, file::hooks::*, hooks::HookManager, macros::hook_symbol, socket::hooks::*,
+    close_detour, detour::contain_panic, file::hooks::*, hooks::HookManager, macros::hook_symbol,
+    socket::hooks::*,
 };
 /*
  * Reference for which syscalls are managed by the handlers:
@@ -12,7 +13,9 @@ use crate::{
  * SYS_read, SYS_write, SYS_lseek, SYS_faccessat: Syscall
  *
  * SYS_socket, SYS_bind, SYS_listen, SYS_accept, SYS_close: Syscall
+ * SYS_dup, SYS_dup2, SYS_dup3, SYS_fcntl: Syscall, Syscall6
  * SYS_accept4: Syscall6
+ * SYS_getpeername, SYS_getsockname: RawSyscall, Syscall6
  *
  * SYS_getdents64: Syscall on go 1.18, Syscall6 on go 1.19.
  */
@@ -313,13 +316,23 @@ unsafe extern "C" fn c_abi_syscall_handler(
             "c_abi_syscall_handler: syscall={} param1={} param2={} param3={}",
             syscall, param1, param2, param3
         );
-        let syscall_result = match syscall {
+        let syscall_result = contain_panic("c_abi_syscall_handler", || match syscall {
             libc::SYS_socket => socket_detour(param1 as _, param2 as _, param3 as _) as i64,
             libc::SYS_bind => bind_detour(param1 as _, param2 as _, param3 as _) as i64,
             libc::SYS_listen => listen_detour(param1 as _, param2 as _) as i64,
             libc::SYS_connect => connect_detour(param1 as _, param2 as _, param3 as _) as i64,
             libc::SYS_accept => accept_detour(param1 as _, param2 as _, param3 as _) as i64,
+            libc::SYS_getpeername => {
+                getpeername_detour(param1 as _, param2 as _, param3 as _) as i64
+            }
+            libc::SYS_getsockname => {
+                getsockname_detour(param1 as _, param2 as _, param3 as _) as i64
+            }
             libc::SYS_close => close_detour(param1 as _) as i64,
+            libc::SYS_dup => dup_detour(param1 as _) as i64,
+            libc::SYS_dup2 => dup2_detour(param1 as _, param2 as _) as i64,
+            libc::SYS_dup3 => dup3_detour(param1 as _, param2 as _, param3 as _) as i64,
+            libc::SYS_fcntl => fcntl_detour(param1 as _, param2 as _, param3 as usize) as i64,
 
             _ if crate::setup().fs_config().is_active() => match syscall {
                 libc::SYS_read => read_detour(param1 as _, param2 as _, param3 as _) as i64,
@@ -386,7 +399,8 @@ unsafe extern "C" fn c_abi_syscall_handler(
                 });
                 result
             }
-        };
+        })
+        .unwrap_or_else(|| super::raw_syscall(syscall, [param1, param2, param3, 0, 0, 0]));
 
         if syscall_result.is_negative() {
             // Might not be an exact mapping, but it should be good enough.
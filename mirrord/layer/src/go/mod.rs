@@ -8,7 +8,9 @@ use frida_gum::NativePointer;
 use nix::errno::Errno;
 use tracing::trace;
 
-use crate::{close_detour, file::hooks::*, hooks::HookManager, socket::hooks::*};
+use crate::{
+    close_detour, detour::contain_panic, file::hooks::*, hooks::HookManager, socket::hooks::*,
+};
 
 #[cfg_attr(
     all(target_os = "linux", target_arch = "x86_64"),
@@ -37,7 +39,7 @@ unsafe extern "C" fn c_abi_syscall6_handler(
             "c_abi_syscall6_handler: syscall={} param1={} param2={} param3={} param4={} param5={} param6={}",
             syscall, param1, param2, param3, param4, param5, param6
         );
-        let syscall_result = match syscall {
+        let syscall_result = contain_panic("c_abi_syscall6_handler", || match syscall {
             libc::SYS_accept4 => {
                 accept4_detour(param1 as _, param2 as _, param3 as _, param4 as _) as i64
             }
@@ -47,6 +49,17 @@ unsafe extern "C" fn c_abi_syscall6_handler(
             libc::SYS_accept => accept_detour(param1 as _, param2 as _, param3 as _) as i64,
             libc::SYS_close => close_detour(param1 as _) as i64,
             libc::SYS_connect => connect_detour(param1 as _, param2 as _, param3 as _) as i64,
+            libc::SYS_getpeername => {
+                getpeername_detour(param1 as _, param2 as _, param3 as _) as i64
+            }
+            libc::SYS_getsockname => {
+                getsockname_detour(param1 as _, param2 as _, param3 as _) as i64
+            }
+            libc::SYS_dup => dup_detour(param1 as _) as i64,
+            #[cfg(target_arch = "x86_64")]
+            libc::SYS_dup2 => dup2_detour(param1 as _, param2 as _) as i64,
+            libc::SYS_dup3 => dup3_detour(param1 as _, param2 as _, param3 as _) as i64,
+            libc::SYS_fcntl => fcntl_detour(param1 as _, param2 as _, param3 as usize) as i64,
 
             _ if crate::setup().fs_config().is_active() => {
                 match syscall {
@@ -171,7 +184,8 @@ unsafe extern "C" fn c_abi_syscall6_handler(
                 });
                 result
             }
-        };
+        })
+        .unwrap_or_else(|| raw_syscall(syscall, [param1, param2, param3, param4, param5, param6]));
 
         if syscall_result.is_negative() {
             // Might not be an exact mapping, but it should be good enough.
@@ -182,6 +196,32 @@ unsafe extern "C" fn c_abi_syscall6_handler(
     }
 }
 
+/// Makes the `syscall` without going through our detours, setting [`Errno`] on failure.
+///
+/// Used by the syscall handlers when their logic panics, see [`contain_panic`].
+unsafe fn raw_syscall(syscall: i64, params: [i64; 6]) -> i64 {
+    let [param1, param2, param3, param4, param5, param6] = params;
+    let (Ok(result) | Err(result)) = unsafe {
+        syscalls::syscall!(
+            syscalls::Sysno::from(syscall as i32),
+            param1,
+            param2,
+            param3,
+            param4,
+            param5,
+            param6
+        )
+    }
+    .map(|success| success as i64)
+    .map_err(|fail| {
+        let raw_errno = fail.into_raw();
+        Errno::set_raw(raw_errno);
+
+        -(raw_errno as i64)
+    });
+    result
+}
+
 /// Handler for `rawVforkSyscall` calls.
 ///
 /// Removes the [`libc::CLONE_VM`] flag from the clone flags.
@@ -1,4 +1,4 @@
-use std::{ptr::null_mut, sync::LazyLock};
+use std::{ops::Not, ptr::null_mut, sync::LazyLock};
 
 use frida_gum::{Gum, Module, NativePointer, Process, interceptor::Interceptor};
 use tracing::trace;
@@ -7,10 +7,36 @@ use crate::{LayerError, Result};
 
 static GUM: LazyLock<Gum> = LazyLock::new(Gum::obtain);
 
+/// Prefixes of the names of musl's libc module.
+///
+/// musl's libc is also its dynamic loader, so the module is named e.g. `ld-musl-x86_64.so.1`, or
+/// `libc.musl-x86_64.so.1` on Alpine, where the latter links to the former.
+const MUSL_MODULE_PREFIXES: [&str; 2] = ["ld-musl-", "libc.musl-"];
+
+/// glibc internals that we hook, and that musl does not export.
+///
+/// We don't even look for them when the process uses musl.
+const GLIBC_ONLY_SYMBOLS: [&str; 6] = [
+    "__close",
+    "__close_nocancel",
+    "__xstat",
+    "__xstat64",
+    "__lxstat",
+    "__lxstat64",
+];
+
+fn is_musl_module(module_name: &str) -> bool {
+    MUSL_MODULE_PREFIXES
+        .iter()
+        .any(|prefix| module_name.starts_with(prefix))
+}
+
 /// Struct for managing the hooks using Frida.
 pub(crate) struct HookManager<'a> {
     interceptor: Interceptor,
     modules: Vec<Module>,
+    /// Whether the process uses musl instead of glibc, e.g. an app built on Alpine.
+    musl: bool,
     // process is need for Linux build and having different struct between OS feels over kill
     #[allow(dead_code)]
     process: Process<'a>,
@@ -18,6 +44,8 @@ pub(crate) struct HookManager<'a> {
 
 impl<'a> HookManager<'a> {
     /// Hook the first function exported from a lib that is in modules and is hooked succesfully
+    ///
+    /// musl's libc module is considered a lib as well, see [`MUSL_MODULE_PREFIXES`].
     pub(crate) fn hook_any_lib_export(
         &mut self,
         symbol: &str,
@@ -27,7 +55,9 @@ impl<'a> HookManager<'a> {
         for module in &self.modules {
             // In this case we only want libs, no "main binaries"
             let module_name = module.name();
-            if !module_name.starts_with(filter.unwrap_or("lib")) {
+            let is_lib = module_name.starts_with(filter.unwrap_or("lib"))
+                || (matches!(filter, None | Some("libc")) && is_musl_module(&module_name));
+            if is_lib.not() {
                 continue;
             }
 
@@ -56,6 +86,11 @@ impl<'a> HookManager<'a> {
         symbol: &str,
         detour: *mut libc::c_void,
     ) -> Result<NativePointer> {
+        if self.musl && GLIBC_ONLY_SYMBOLS.contains(&symbol) {
+            trace!("{symbol:?} is not exported by musl, skipping");
+            return Err(LayerError::NoExportName(symbol.to_string()));
+        }
+
         // First try to hook the default exported one, if it fails, fallback to first lib that
         // provides it.
         let function = Module::find_global_export_by_name(symbol);
@@ -145,9 +180,15 @@ impl<'a> Default for HookManager<'a> {
         interceptor.begin_transaction();
         let process = Process::obtain(&GUM);
         let modules = process.enumerate_modules();
+        let musl = modules.iter().any(|module| is_musl_module(&module.name()));
+        if musl {
+            trace!("process uses musl");
+        }
+
         Self {
             interceptor,
             modules,
+            musl,
             process,
         }
     }
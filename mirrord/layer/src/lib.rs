@@ -78,6 +78,7 @@ use std::{
     fs::File,
     io::Read,
     net::SocketAddr,
+    ops::Not,
     os::unix::process::parent_id,
     panic,
     sync::{Arc, OnceLock},
@@ -99,7 +100,11 @@ use load::ExecuteArgs;
 use mirrord_config::feature::fs::FsConfig;
 use mirrord_config::{
     LayerConfig, MIRRORD_LAYER_INTPROXY_ADDR,
-    feature::{env::mapper::EnvVarsRemapper, fs::FsModeConfig, network::incoming::IncomingMode},
+    feature::{
+        env::{mapper::EnvVarsRemapper, rename::EnvVarsRenamer},
+        fs::FsModeConfig,
+        network::incoming::IncomingMode,
+    },
 };
 use mirrord_intproxy_protocol::NewSessionRequest;
 use mirrord_layer_lib::logging;
@@ -113,7 +118,7 @@ use socket::SOCKETS;
 use crate::{
     common::make_proxy_request_with_response,
     debugger_ports::DebuggerPorts,
-    detour::DetourGuard,
+    detour::{DetourGuard, contain_panic},
     load::LoadType,
     socket::{hooks::MANAGED_ADDRINFO, ops::REMOTE_DNS_REVERSE_MAPPING},
 };
@@ -129,7 +134,6 @@ mod integration_tests_deps {
     use futures as _;
     use mirrord_intproxy as _;
     use mirrord_test_utils as _;
-    use serde_json as _;
     use tempfile as _;
     use test_cdylib as _;
     use tokio as _;
@@ -139,6 +143,7 @@ mod integration_tests_deps {
 mod common;
 mod debugger_ports;
 mod detour;
+mod embed;
 mod error;
 mod exec_hooks;
 #[cfg(target_os = "macos")]
@@ -205,8 +210,8 @@ static EXECUTABLE_PATH: OnceLock<String> = OnceLock::new();
 /// Can be configured in the [`LayerConfig`].
 static PROXY_CONNECTION_TIMEOUT: OnceLock<Duration> = OnceLock::new();
 
-/// Loads mirrord configuration and does some patching (SIP, dotnet, etc)
-fn layer_pre_initialization() -> Result<(), LayerError> {
+/// Does some patching (SIP, dotnet, etc) and starts the layer with the given configuration.
+fn layer_pre_initialization(config: LayerConfig) -> Result<(), LayerError> {
     // we don't care about value, just that this env exists
     let dont_start = std::env::var(FAILSAFE_ENV).is_ok();
     if dont_start {
@@ -219,8 +224,6 @@ fn layer_pre_initialization() -> Result<(), LayerError> {
         std::env::current_exe().map(|arg| arg.to_string_lossy().into_owned())
     })?;
 
-    let config = mirrord_config::util::read_resolved_config()?;
-
     #[cfg(target_os = "macos")]
     let patch_binaries = config
         .sip_binaries
@@ -325,20 +328,28 @@ fn load_only_layer_start(config: &LayerConfig) {
 
 /// The one true start of mirrord-layer.
 ///
-/// Calls [`layer_pre_initialization`], which runs mirrord-layer.
+/// Loads mirrord configuration and calls [`layer_pre_initialization`], which runs mirrord-layer.
+///
+/// Does nothing when the layer was loaded with `dlopen` (see [`embed::is_preloaded`]), as it's
+/// started later with [`embed::mirrord_layer_start`].
 #[ctor]
 fn mirrord_layer_entry_point() {
-    if cfg!(test) {
+    if cfg!(test) || embed::is_preloaded().not() {
         return;
     }
 
-    let res = panic::catch_unwind(|| match layer_pre_initialization() {
-        Err(LayerError::NoProcessFound) => {}
-        Err(e) => {
-            eprintln!("mirrord layer setup failed with {e:?}");
-            std::process::exit(-1)
+    let res = panic::catch_unwind(|| {
+        match mirrord_config::util::read_resolved_config()
+            .map_err(LayerError::from)
+            .and_then(layer_pre_initialization)
+        {
+            Err(LayerError::NoProcessFound) => {}
+            Err(e) => {
+                eprintln!("mirrord layer setup failed with {e:?}");
+                std::process::exit(-1)
+            }
+            Ok(()) => {}
         }
-        Ok(()) => {}
     });
 
     if res.is_err() {
@@ -523,6 +534,12 @@ fn fetch_env_vars() -> HashMap<String, String> {
         Default::default()
     };
 
+    if let Some(rename) = setup().env_config().rename.clone() {
+        env_vars = EnvVarsRenamer::new(rename, env_vars)
+            .expect("Invalid rename pattern, this should've been caught when verifying config!")
+            .renamed();
+    }
+
     if let Some(file) = &setup().env_config().env_file {
         let envs_from_file = dotenvy::from_path_iter(file)
             .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
@@ -552,7 +569,7 @@ fn sip_only_layer_start(
     patch_binaries: Vec<String>,
     skip_patch_binaries: Vec<String>,
 ) {
-    use mirrord_config::feature::fs::READONLY_FILE_BUFFER_DEFAULT;
+    use mirrord_config::feature::fs::{LOCK_TIMEOUT_DEFAULT, READONLY_FILE_BUFFER_DEFAULT};
 
     load_only_layer_start(&config);
 
@@ -572,7 +589,16 @@ fn sip_only_layer_start(
         local: None,
         not_found: None,
         mapping: None,
+        overrides: None,
+        path_translation: None,
+        materialize: None,
+        materialize_dir: None,
+        remote_cwd: None,
         readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+        lock_timeout: LOCK_TIMEOUT_DEFAULT,
+        procfs: false,
+        procfs_paths: None,
+        downgrade_o_direct: false,
     };
     let debugger_ports = DebuggerPorts::from_env();
     let layer_setup = LayerSetup::new(config, debugger_ports, true);
@@ -655,6 +681,7 @@ fn enable_hooks(state: &LayerSetup) {
         socket::hooks::enable_socket_hooks(
             &mut hook_manager,
             enabled_remote_dns,
+            state.layer_config().feature.network.interfaces,
             state.experimental(),
         )
     };
@@ -717,8 +744,10 @@ fn enable_hooks(state: &LayerSetup) {
 /// **DON'T ADD LOGS HERE SINCE CALLER MIGHT CLOSE STDOUT/STDERR CAUSING THIS TO CRASH**
 #[mirrord_layer_macro::instrument(level = "trace", fields(pid = std::process::id()))]
 pub(crate) fn close_layer_fd(fd: c_int) {
-    // Remove from sockets.
-    match SOCKETS.lock().expect("SOCKETS lock failed").remove(&fd) {
+    // Remove from sockets. The lock is released before closing the socket, which looks for other
+    // listeners in [`SOCKETS`].
+    let removed = SOCKETS.lock().expect("SOCKETS lock failed").remove(&fd);
+    match removed {
         Some(socket) => {
             // Closed file is a socket, so if it's already bound to a port - notify agent to stop
             // mirroring/stealing that port.
@@ -765,7 +794,8 @@ pub(crate) fn close_layer_fd(fd: c_int) {
 pub(crate) unsafe extern "C" fn close_detour(fd: c_int) -> c_int {
     unsafe {
         let res = FN_CLOSE(fd);
-        close_layer_fd(fd);
+        // The fd is already closed, so a panic here must not call the original function again.
+        contain_panic("close_detour", || close_layer_fd(fd));
         res
     }
 }
@@ -790,42 +820,45 @@ pub(crate) unsafe extern "C" fn fork_detour() -> pid_t {
         let res = FN_FORK();
 
         match res.cmp(&0) {
+            // The process is already forked, so a panic here must not call the original function
+            // again.
             Ordering::Equal => {
-                tracing::debug!("Child process initializing layer.");
-                #[allow(static_mut_refs)]
-                let parent_connection = match PROXY_CONNECTION.take() {
-                    Some(conn) => conn,
-                    None => {
-                        tracing::debug!("Skipping new inptroxy connection (trace only)");
-                        return res;
-                    }
-                };
-
-                let new_connection = ProxyConnection::new(
-                    parent_connection.proxy_addr(),
-                    NewSessionRequest {
-                        parent_layer: Some(parent_connection.layer_id()),
-                        process_info: EXECUTABLE_ARGS
+                contain_panic("fork_detour", || {
+                    tracing::debug!("Child process initializing layer.");
+                    #[allow(static_mut_refs)]
+                    let parent_connection = match PROXY_CONNECTION.take() {
+                        Some(conn) => conn,
+                        None => {
+                            tracing::debug!("Skipping new inptroxy connection (trace only)");
+                            return;
+                        }
+                    };
+
+                    let new_connection = ProxyConnection::new(
+                        parent_connection.proxy_addr(),
+                        NewSessionRequest {
+                            parent_layer: Some(parent_connection.layer_id()),
+                            process_info: EXECUTABLE_ARGS
+                                .get()
+                                .expect("should always be set in layer constructor")
+                                .to_process_info(setup().layer_config()),
+                        },
+                        PROXY_CONNECTION_TIMEOUT
                             .get()
-                            .expect("should always be set in layer constructor")
-                            .to_process_info(setup().layer_config()),
-                    },
-                    PROXY_CONNECTION_TIMEOUT
-                        .get()
-                        .copied()
-                        .expect("PROXY_CONNECTION_TIMEOUT should be set by now!"),
-                )
-                .expect("failed to establish proxy connection for child");
-                #[allow(static_mut_refs)]
-                PROXY_CONNECTION
-                    .set(new_connection)
-                    .expect("Failed setting PROXY_CONNECTION in child fork");
-                // in macOS (and tbh sounds logical) we can't just drop the old connection in the
-                // child, as it needs to access a mutex with invalid state, so we
-                // need to forget it. better implementation would be to somehow
-                // close the underlying connections but side effect should be
-                // trivial
-                std::mem::forget(parent_connection);
+                            .copied()
+                            .expect("PROXY_CONNECTION_TIMEOUT should be set by now!"),
+                    )
+                    .expect("failed to establish proxy connection for child");
+                    #[allow(static_mut_refs)]
+                    PROXY_CONNECTION
+                        .set(new_connection)
+                        .expect("Failed setting PROXY_CONNECTION in child fork");
+                    // in macOS (and tbh sounds logical) we can't just drop the old connection in
+                    // the child, as it needs to access a mutex with invalid state, so we need to
+                    // forget it. better implementation would be to somehow close the underlying
+                    // connections but side effect should be trivial
+                    std::mem::forget(parent_connection);
+                });
             }
             Ordering::Greater => tracing::debug!("Child process id is {res}."),
             Ordering::Less => tracing::debug!("fork failed"),
@@ -944,7 +977,8 @@ pub(crate) unsafe extern "C" fn vfork_detour() -> pid_t {
     fork_result
 }
 
-/// No need to guard because we call another detour which will do the guard for us.
+/// No need to guard because we call another detour which will do the guard for us, and contain
+/// its panics.
 ///
 /// ## Hook
 ///
@@ -999,18 +1033,21 @@ pub(crate) unsafe extern "C" fn dlopen_detour(
     let handle = unsafe { FN_DLOPEN(raw_path, mode) };
     let _guard = DetourGuard::new();
 
-    let mut hook_manager = HookManager::default();
-    let path_str = unsafe {
-        std::ffi::CStr::from_ptr(raw_path)
+    // The library is already loaded, so a panic here must not call the original function again.
+    contain_panic("dlopen_detour", || {
+        let mut hook_manager = HookManager::default();
+        let path_str = unsafe {
+            std::ffi::CStr::from_ptr(raw_path)
+                .to_string_lossy()
+                .into_owned()
+        };
+        let filename = std::path::Path::new(&path_str)
+            .file_name()
+            .expect("cannot get the filename of the dynamic library")
             .to_string_lossy()
-            .into_owned()
-    };
-    let filename = std::path::Path::new(&path_str)
-        .file_name()
-        .expect("cannot get the filename of the dynamic library")
-        .to_string_lossy()
-        .into_owned();
-    go_hooks::enable_hooks_in_loaded_module(&mut hook_manager, filename);
+            .into_owned();
+        go_hooks::enable_hooks_in_loaded_module(&mut hook_manager, filename);
+    });
 
     handle
 }
@@ -1,4 +1,9 @@
-use std::{collections::HashSet, net::SocketAddr, ops::Not};
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    ops::Not,
+    path::{Path, PathBuf},
+};
 
 use mirrord_config::{
     LayerConfig, MIRRORD_LAYER_INTPROXY_ADDR,
@@ -11,10 +16,12 @@ use mirrord_config::{
     target::Target,
 };
 use mirrord_intproxy_protocol::PortSubscription;
-use mirrord_layer_lib::file::{filter::FileFilter, mapper::FileRemapper};
+use mirrord_layer_lib::file::{
+    filter::FileFilter, mapper::FileRemapper, overrides::FileOverrides, translation::PathTranslator,
+};
 use mirrord_protocol::{
     Port,
-    tcp::{HttpFilter, MirrorType, StealType},
+    tcp::{Filter, HttpFilter, MirrorType, PostgresFilter, RedisFilter, StealType},
 };
 use regex::RegexSet;
 
@@ -31,6 +38,10 @@ pub struct LayerSetup {
     config: LayerConfig,
     file_filter: FileFilter,
     file_remapper: FileRemapper,
+    file_overrides: FileOverrides,
+    path_translator: PathTranslator,
+    /// Shadow directory for `fs.materialize`.
+    materialize_dir: PathBuf,
     debugger_ports: DebuggerPorts,
     remote_unix_streams: RegexSet,
     outgoing_selector: OutgoingSelector,
@@ -52,6 +63,22 @@ impl LayerSetup {
         let file_filter = FileFilter::new(config.feature.fs.clone());
         let file_remapper =
             FileRemapper::new(config.feature.fs.mapping.clone().unwrap_or_default());
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let file_overrides = FileOverrides::new(
+            config.feature.fs.overrides.clone().unwrap_or_default(),
+            &cwd,
+        );
+        let path_translator = PathTranslator::new(
+            config
+                .feature
+                .fs
+                .path_translation
+                .clone()
+                .unwrap_or_default(),
+            &cwd,
+        );
+
+        let materialize_dir = config.feature.fs.materialize_dir();
 
         let remote_unix_streams = config
             .feature
@@ -84,6 +111,9 @@ impl LayerSetup {
             config,
             file_filter,
             file_remapper,
+            file_overrides,
+            path_translator,
+            materialize_dir,
             debugger_ports,
             remote_unix_streams,
             outgoing_selector,
@@ -116,6 +146,18 @@ impl LayerSetup {
         &self.file_remapper
     }
 
+    pub fn file_overrides(&self) -> &FileOverrides {
+        &self.file_overrides
+    }
+
+    pub fn path_translator(&self) -> &PathTranslator {
+        &self.path_translator
+    }
+
+    pub fn materialize_dir(&self) -> &Path {
+        &self.materialize_dir
+    }
+
     pub fn incoming_config(&self) -> &IncomingConfig {
         &self.config.feature.network.incoming
     }
@@ -198,10 +240,40 @@ pub struct HttpSettings {
     pub ports: Option<HashSet<Port>>,
 }
 
+/// Settings for stealing TLS connections based on their SNI.
+#[derive(Debug)]
+pub struct SniSettings {
+    /// The server name filter to use.
+    pub filter: Filter,
+    /// Ports to filter SNI on.
+    pub ports: HashSet<Port>,
+}
+
+/// Settings for stealing PostgreSQL connections based on their startup message.
+#[derive(Debug)]
+pub struct PostgresSettings {
+    /// The database and user filter to use.
+    pub filter: PostgresFilter,
+    /// Ports to filter PostgreSQL connections on.
+    pub ports: HashSet<Port>,
+}
+
+/// Settings for stealing Redis commands.
+#[derive(Debug)]
+pub struct RedisSettings {
+    /// The command and key filter to use.
+    pub filter: RedisFilter,
+    /// Ports to filter Redis commands on.
+    pub ports: HashSet<Port>,
+}
+
 #[derive(Debug)]
 pub struct IncomingMode {
     pub steal: bool,
     pub http_settings: Option<HttpSettings>,
+    pub sni_settings: Option<SniSettings>,
+    pub postgres_settings: Option<PostgresSettings>,
+    pub redis_settings: Option<RedisSettings>,
 }
 
 impl IncomingMode {
@@ -226,18 +298,76 @@ impl IncomingMode {
             HttpSettings { filter, ports }
         });
 
+        let sni_settings = config.sni_filter.as_ref().map(|sni_filter| SniSettings {
+            filter: sni_filter
+                .as_protocol_filter()
+                .expect("invalid SNI filter expression"),
+            ports: sni_filter.port_set(),
+        });
+
+        let postgres_settings =
+            config
+                .postgres_filter
+                .as_ref()
+                .map(|postgres_filter| PostgresSettings {
+                    filter: postgres_filter
+                        .as_protocol_filter()
+                        .expect("invalid PostgreSQL filter expression"),
+                    ports: postgres_filter.port_set(),
+                });
+
+        let redis_settings = config
+            .redis_filter
+            .as_ref()
+            .map(|redis_filter| RedisSettings {
+                filter: redis_filter
+                    .as_protocol_filter()
+                    .expect("invalid Redis filter expression"),
+                ports: redis_filter.port_set(),
+            });
+
         Self {
             steal: config.is_steal(),
             http_settings,
+            sni_settings,
+            postgres_settings,
+            redis_settings,
         }
     }
 
     /// Returns [`PortSubscription`] request to be used for the given port.
     pub fn subscription(&self, port: Port) -> PortSubscription {
         if self.steal {
-            let steal_type = match &self.http_settings {
-                None => StealType::All(port),
-                Some(settings) => {
+            let sni_filter = self
+                .sni_settings
+                .as_ref()
+                .filter(|settings| settings.ports.contains(&port));
+
+            let postgres_filter = self
+                .postgres_settings
+                .as_ref()
+                .filter(|settings| settings.ports.contains(&port));
+
+            let redis_filter = self
+                .redis_settings
+                .as_ref()
+                .filter(|settings| settings.ports.contains(&port));
+
+            let steal_type = match (
+                sni_filter,
+                postgres_filter,
+                redis_filter,
+                &self.http_settings,
+            ) {
+                (Some(settings), ..) => StealType::FilteredSni(port, settings.filter.clone()),
+                (None, Some(settings), ..) => {
+                    StealType::FilteredPostgres(port, settings.filter.clone())
+                }
+                (None, None, Some(settings), _) => {
+                    StealType::FilteredRedis(port, settings.filter.clone())
+                }
+                (None, None, None, None) => StealType::All(port),
+                (None, None, None, Some(settings)) => {
                     if settings
                         .ports
                         .as_ref()
@@ -1,8 +1,8 @@
 //! We implement each hook function in a safe function as much as possible, having the unsafe do the
 //! absolute minimum
 use std::{
-    collections::{HashMap, HashSet},
-    net::{SocketAddr, ToSocketAddrs},
+    collections::{HashMap, HashSet, VecDeque},
+    net::{Ipv4Addr, SocketAddr, ToSocketAddrs},
     os::unix::io::RawFd,
     str::FromStr,
     sync::{Arc, LazyLock},
@@ -137,6 +137,23 @@ pub struct Connected {
     connection_id: Option<u128>,
 }
 
+/// A destination of an unconnected UDP socket, to which we send the datagrams through the agent.
+///
+/// Each destination gets its own interceptor socket in the internal proxy (and its own socket in
+/// the agent), so the responses can be told apart by the interceptor address they come from. See
+/// [`ops::send_to`] and [`ops::recv_from`].
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+pub struct DatagramRoute {
+    /// The address the user sends the datagrams to, and expects the responses from.
+    remote_address: SocketAddr,
+
+    /// The address of the interceptor socket, where we really send the datagrams.
+    layer_address: SocketAddr,
+
+    /// Unique ID of the outgoing connection that backs this route.
+    connection_id: u128,
+}
+
 /// Represents a [`SocketState`] where the user made a [`libc::bind`] call, and we intercepted it.
 ///
 /// ## Details
@@ -219,6 +236,22 @@ pub(crate) struct UserSocket {
     protocol: c_int,
     pub state: SocketState,
     pub(crate) kind: SocketKind,
+    /// Values of the socket options that we emulate instead of setting them on the local socket.
+    pub(crate) sockopts: EmulatedSockopts,
+    /// Destinations of an unconnected UDP socket that go through the agent.
+    pub(crate) datagram_routes: Vec<DatagramRoute>,
+    /// Sources of the ICMP echo replies delivered to an ICMP socket, in order, see
+    /// [`ops::icmp_echo`].
+    pub(crate) icmp_replies: VecDeque<Ipv4Addr>,
+}
+
+/// Values of the socket options emulated with [`ops::setsockopt`], returned from
+/// [`ops::getsockopt`].
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub(crate) struct EmulatedSockopts {
+    /// `SO_BINDTODEVICE`, names an interface of the target, which usually does not exist locally.
+    #[cfg(target_os = "linux")]
+    pub(crate) bind_to_device: Option<Vec<u8>>,
 }
 
 impl UserSocket {
@@ -235,9 +268,30 @@ impl UserSocket {
             protocol,
             state,
             kind,
+            sockopts: Default::default(),
+            datagram_routes: Default::default(),
+            icmp_replies: Default::default(),
         }
     }
 
+    /// Whether this is an ICMP socket, replaced with a local UDP socket in [`ops::socket`].
+    pub(crate) fn is_icmp(&self) -> bool {
+        self.domain == libc::AF_INET && self.protocol == libc::IPPROTO_ICMP
+    }
+
+    /// Whether this is a TCP socket listening on the given local `address`, subscribed to the
+    /// given `port`.
+    pub(crate) fn is_listening_on(&self, port: u16, address: SocketAddr) -> bool {
+        matches!(
+            self,
+            Self {
+                state: SocketState::Listening(bound),
+                kind: SocketKind::Tcp(..),
+                ..
+            } if bound.requested_address.port() == port && bound.address == address
+        )
+    }
+
     /// Inform internal proxy about closing a listening port.
     ///
     /// **Important**
@@ -252,10 +306,21 @@ impl UserSocket {
                 kind: SocketKind::Tcp(..),
                 ..
             } => {
-                let _ = common::make_proxy_request_no_response(PortUnsubscribe {
-                    port: bound.requested_address.port(),
-                    listening_on: bound.address,
+                // Other `SO_REUSEPORT` sockets may still be listening on the same address, using
+                // the same subscription.
+                let port = bound.requested_address.port();
+                let shared_listener = SOCKETS.lock().is_ok_and(|sockets| {
+                    sockets
+                        .values()
+                        .any(|other| other.is_listening_on(port, bound.address))
                 });
+
+                if !shared_listener {
+                    let _ = common::make_proxy_request_no_response(PortUnsubscribe {
+                        port,
+                        listening_on: bound.address,
+                    });
+                }
             }
             Self {
                 state:
@@ -271,6 +336,12 @@ impl UserSocket {
             }
             _ => {}
         }
+
+        for route in &self.datagram_routes {
+            let _ = common::make_proxy_request_no_response(OutgoingConnCloseRequest {
+                conn_id: route.connection_id,
+            });
+        }
     }
 }
 
@@ -12,7 +12,12 @@ use nix::errno::Errno;
 #[cfg(target_os = "macos")]
 use super::apple_dnsinfo::*;
 use super::ops::*;
-use crate::{detour::DetourGuard, hooks::HookManager, mutex::Mutex, replace};
+use crate::{
+    detour::{DetourGuard, contain_panic},
+    hooks::HookManager,
+    mutex::Mutex,
+    replace,
+};
 
 /// Here we keep addr infos that we allocated so we'll know when to use the original
 /// freeaddrinfo function and when to use our implementation
@@ -77,7 +82,7 @@ pub(super) unsafe extern "C" fn connect_nocancel_detour(
 }
 
 #[hook_guard_fn]
-pub(super) unsafe extern "C" fn getpeername_detour(
+pub(crate) unsafe extern "C" fn getpeername_detour(
     sockfd: RawFd,
     address: *mut sockaddr,
     address_len: *mut socklen_t,
@@ -100,6 +105,38 @@ pub(crate) unsafe extern "C" fn getsockname_detour(
     }
 }
 
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn setsockopt_detour(
+    sockfd: RawFd,
+    level: c_int,
+    option_name: c_int,
+    option_value: *const c_void,
+    option_len: socklen_t,
+) -> c_int {
+    unsafe {
+        setsockopt(sockfd, level, option_name, option_value, option_len).unwrap_or_bypass_with(
+            |_| FN_SETSOCKOPT(sockfd, level, option_name, option_value, option_len),
+        )
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn getsockopt_detour(
+    sockfd: RawFd,
+    level: c_int,
+    option_name: c_int,
+    option_value: *mut c_void,
+    option_len: *mut socklen_t,
+) -> c_int {
+    unsafe {
+        getsockopt(sockfd, level, option_name, option_value, option_len).unwrap_or_bypass_with(
+            |_| FN_GETSOCKOPT(sockfd, level, option_name, option_value, option_len),
+        )
+    }
+}
+
 /// Hook for `libc::gethostname`.
 ///
 /// Reads remote hostname bytes into `raw_name`, will rais EINVAL errno and return -1 if hostname
@@ -217,6 +254,10 @@ pub(super) unsafe extern "C" fn accept_nocancel_detour(
 pub(crate) unsafe extern "C" fn fcntl_detour(fd: c_int, cmd: c_int, mut arg: ...) -> c_int {
     unsafe {
         let arg = arg.arg::<usize>();
+        if let Some(result) = crate::file::hooks::fcntl_lock_logic(fd, cmd, arg) {
+            return result;
+        }
+
         let fcntl_result = FN_FCNTL(fd, cmd, arg);
         let guard = DetourGuard::new();
         if guard.is_none() {
@@ -224,13 +265,14 @@ pub(crate) unsafe extern "C" fn fcntl_detour(fd: c_int, cmd: c_int, mut arg: ...
         }
 
         if fcntl_result == -1 {
-            fcntl_result
-        } else {
-            match fcntl(fd, cmd, fcntl_result) {
-                Ok(()) => fcntl_result,
-                Err(e) => e.into(),
-            }
+            return fcntl_result;
         }
+
+        contain_panic("fcntl_detour", || match fcntl(fd, cmd, fcntl_result) {
+            Ok(()) => fcntl_result,
+            Err(e) => e.into(),
+        })
+        .unwrap_or(fcntl_result)
     }
 }
 
@@ -242,6 +284,10 @@ pub(crate) unsafe extern "C" fn fcntl_nocancel_detour(
 ) -> c_int {
     unsafe {
         let arg = arg.arg::<usize>();
+        if let Some(result) = crate::file::hooks::fcntl_lock_logic(fd, cmd, arg) {
+            return result;
+        }
+
         let fcntl_result = FN_FCNTL_NOCANCEL(fd, cmd, arg);
         let guard = DetourGuard::new();
         if guard.is_none() {
@@ -249,18 +295,21 @@ pub(crate) unsafe extern "C" fn fcntl_nocancel_detour(
         }
 
         if fcntl_result == -1 {
-            fcntl_result
-        } else {
+            return fcntl_result;
+        }
+
+        contain_panic("fcntl_nocancel_detour", || {
             match fcntl(fd, cmd, fcntl_result) {
                 Ok(()) => fcntl_result,
                 Err(e) => e.into(),
             }
-        }
+        })
+        .unwrap_or(fcntl_result)
     }
 }
 
 #[hook_guard_fn]
-pub(super) unsafe extern "C" fn dup_detour(fd: c_int) -> c_int {
+pub(crate) unsafe extern "C" fn dup_detour(fd: c_int) -> c_int {
     unsafe {
         let dup_result = FN_DUP(fd);
 
@@ -276,7 +325,7 @@ pub(super) unsafe extern "C" fn dup_detour(fd: c_int) -> c_int {
 }
 
 #[hook_guard_fn]
-pub(super) unsafe extern "C" fn dup2_detour(oldfd: c_int, newfd: c_int) -> c_int {
+pub(crate) unsafe extern "C" fn dup2_detour(oldfd: c_int, newfd: c_int) -> c_int {
     unsafe {
         if oldfd == newfd {
             return newfd;
@@ -297,7 +346,7 @@ pub(super) unsafe extern "C" fn dup2_detour(oldfd: c_int, newfd: c_int) -> c_int
 
 #[cfg(target_os = "linux")]
 #[hook_guard_fn]
-pub(super) unsafe extern "C" fn dup3_detour(oldfd: c_int, newfd: c_int, flags: c_int) -> c_int {
+pub(crate) unsafe extern "C" fn dup3_detour(oldfd: c_int, newfd: c_int, flags: c_int) -> c_int {
     unsafe {
         let dup3_result = FN_DUP3(oldfd, newfd, flags);
 
@@ -647,6 +696,7 @@ unsafe extern "C-unwind" fn CFNetworkCopySystemProxySettings_detour()
 pub(crate) unsafe fn enable_socket_hooks(
     hook_manager: &mut HookManager,
     enabled_remote_dns: bool,
+    enabled_remote_interfaces: bool,
     experimental: &ExperimentalConfig,
 ) {
     unsafe {
@@ -777,6 +827,21 @@ pub(crate) unsafe fn enable_socket_hooks(
             );
 
             replace!(hook_manager, "dup3", dup3_detour, FnDup3, FN_DUP3);
+
+            replace!(
+                hook_manager,
+                "setsockopt",
+                setsockopt_detour,
+                FnSetsockopt,
+                FN_SETSOCKOPT
+            );
+            replace!(
+                hook_manager,
+                "getsockopt",
+                getsockopt_detour,
+                FnGetsockopt,
+                FN_GETSOCKOPT
+            );
         }
 
         replace!(hook_manager, "accept", accept_detour, FnAccept, FN_ACCEPT);
@@ -840,7 +905,7 @@ pub(crate) unsafe fn enable_socket_hooks(
             }
         }
 
-        if experimental.hide_ipv6_interfaces {
+        if enabled_remote_interfaces || experimental.hide_ipv6_interfaces {
             replace!(
                 hook_manager,
                 "getifaddrs",
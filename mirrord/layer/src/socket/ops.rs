@@ -11,7 +11,8 @@ use std::{
     },
     path::PathBuf,
     ptr::{self, copy_nonoverlapping},
-    sync::{Arc, OnceLock},
+    sync::{Arc, Once, OnceLock},
+    time::{Duration, Instant},
 };
 
 use libc::{AF_UNIX, c_int, c_void, hostent, sockaddr, socklen_t};
@@ -19,13 +20,16 @@ use libc::{AF_UNIX, c_int, c_void, hostent, sockaddr, socklen_t};
 use libc::{SAE_ASSOCID_ANY, c_uint, iovec, sa_endpoints_t, sae_associd_t, sae_connid_t, size_t};
 use mirrord_config::feature::network::incoming::{IncomingConfig, IncomingMode};
 use mirrord_intproxy_protocol::{
-    ConnMetadataRequest, ConnMetadataResponse, NetProtocol, OutgoingConnMetadataRequest,
-    OutgoingConnectRequest, OutgoingConnectResponse, PortSubscribe,
+    ConnMetadataRequest, ConnMetadataResponse, LayerWarning, NetProtocol,
+    OutgoingConnMetadataRequest, OutgoingConnectRequest, OutgoingConnectResponse, PortSubscribe,
 };
 use mirrord_layer_lib::graceful_exit;
 use mirrord_protocol::{
+    ErrorKindInternal, RemoteError, RemoteIOError, ResponseError,
     dns::{AddressFamily, GetAddrInfoRequestV2, LookupRecord, SockType},
     file::{OpenFileResponse, OpenOptionsInternal, ReadFileResponse},
+    interfaces::{GetInterfacesRequest, InterfaceAddress},
+    outgoing::icmp::{IcmpEchoRequest, IcmpEchoResponse},
 };
 use nix::{
     errno::Errno,
@@ -40,7 +44,8 @@ use tracing::{error, trace};
 use super::apple_dnsinfo::*;
 use super::{hooks::*, *};
 use crate::{
-    common::make_proxy_request_with_response,
+    close_layer_fd,
+    common::{make_proxy_request_no_response, make_proxy_request_with_response},
     detour::{Detour, OnceLockExt, OptionDetourExt, OptionExt},
     error::HookError,
     file::{self, OPEN_FILES},
@@ -55,6 +60,18 @@ use crate::{
 pub(crate) static REMOTE_DNS_REVERSE_MAPPING: LazyLock<Mutex<HashMap<IpAddr, String>>> =
     LazyLock::new(|| Mutex::new(HashMap::new()));
 
+/// Remote addresses that recently failed to connect through the agent, with the time of the
+/// failure.
+///
+/// Expired entries are removed when a new failure is recorded.
+///
+/// Used by [`connect_outgoing`] when [`OutgoingConfig::local_fallback`] is enabled, to connect to
+/// these addresses from the local app without asking the agent again.
+///
+/// [`OutgoingConfig::local_fallback`]: mirrord_config::feature::network::outgoing::OutgoingConfig::local_fallback
+static LOCAL_FALLBACK_CACHE: LazyLock<Mutex<HashMap<SocketAddr, Instant>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
 /// Hostname initialized from the agent with [`gethostname`].
 pub(crate) static HOSTNAME: OnceLock<CString> = OnceLock::new();
 
@@ -123,6 +140,10 @@ impl From<ConnectResult> for i32 {
 /// Create the socket, add it to SOCKETS if successful and matching protocol and domain (Tcpv4/v6)
 #[mirrord_layer_macro::instrument(level = Level::TRACE, fields(pid = std::process::id()), ret)]
 pub(super) fn socket(domain: c_int, type_: c_int, protocol: c_int) -> Detour<RawFd> {
+    if protocol == libc::IPPROTO_SCTP {
+        return sctp_socket();
+    }
+
     let socket_kind = type_.try_into()?;
 
     if !((domain == libc::AF_INET) || (domain == libc::AF_INET6) || (domain == libc::AF_UNIX)) {
@@ -131,6 +152,10 @@ pub(super) fn socket(domain: c_int, type_: c_int, protocol: c_int) -> Detour<Raw
         Ok(())
     }?;
 
+    if domain == libc::AF_INET && protocol == libc::IPPROTO_ICMP {
+        return icmp_socket(type_, socket_kind);
+    }
+
     if domain == libc::AF_INET6 && crate::setup().layer_config().feature.network.ipv6.not() {
         return Detour::Error(HookError::SocketUnsuportedIpv6);
     }
@@ -150,6 +175,61 @@ pub(super) fn socket(domain: c_int, type_: c_int, protocol: c_int) -> Detour<Raw
     Detour::Success(socket_fd)
 }
 
+/// SCTP sockets are not intercepted at all, so none of the other hooks touch them.
+///
+/// Without this, `SOCK_STREAM` SCTP sockets would be treated as TCP, and the application could
+/// hang waiting for traffic that never comes.
+///
+/// The user is warned once per process, with a [`LayerWarning`] logged by the internal proxy.
+fn sctp_socket() -> Detour<RawFd> {
+    static WARNED: Once = Once::new();
+    WARNED.call_once(|| {
+        let warning = LayerWarning(
+            "SCTP not supported, traffic is local. \
+            The application's SCTP sockets are not intercepted by mirrord."
+                .into(),
+        );
+        if let Err(error) = make_proxy_request_no_response(warning) {
+            tracing::error!(%error, "Failed to send the SCTP warning to the internal proxy");
+        }
+    });
+
+    Detour::Bypass(Bypass::Sctp)
+}
+
+/// Replaces an ICMP socket (`SOCK_DGRAM` + `IPPROTO_ICMP`) with a UDP socket bound to localhost,
+/// when [`OutgoingConfig::icmp`](mirrord_config::feature::network::outgoing::OutgoingConfig::icmp)
+/// is enabled.
+///
+/// The echo requests sent on this socket go through the agent, see [`icmp_echo`].
+fn icmp_socket(type_: c_int, socket_kind: SocketKind) -> Detour<RawFd> {
+    if crate::setup().outgoing_config().icmp.not() {
+        return Detour::Bypass(Bypass::DisabledOutgoing);
+    }
+
+    let socket_fd = unsafe { FN_SOCKET(libc::AF_INET, type_, 0) };
+    if socket_fd == -1 {
+        return Detour::Error(io::Error::last_os_error().into());
+    }
+
+    let address = SockaddrStorage::from(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)));
+    if let Err(error) = nix::sys::socket::bind(socket_fd, &address) {
+        let _ = nix::unistd::close(socket_fd);
+        return Detour::Error(io::Error::from(error).into());
+    }
+
+    let new_socket = UserSocket::new(
+        libc::AF_INET,
+        type_,
+        libc::IPPROTO_ICMP,
+        Default::default(),
+        socket_kind,
+    );
+    SOCKETS.lock()?.insert(socket_fd, Arc::new(new_socket));
+
+    Detour::Success(socket_fd)
+}
+
 /// Tries to bind the given socket to the requested address, with fallbacks.
 ///
 /// Tried addresses, in order:
@@ -235,6 +315,12 @@ fn is_ignored_tcp_port(addr: &SocketAddr, config: &IncomingConfig) -> bool {
     is_ignored_port(addr) || have_whitelist_and_port_is_not_whitelisted
 }
 
+/// Whether the local socket has `SO_REUSEPORT` set.
+fn is_reuse_port(sockfd: RawFd) -> bool {
+    let fd = unsafe { BorrowedFd::borrow_raw(sockfd) };
+    nix::sys::socket::getsockopt(&fd, sockopt::ReusePort).unwrap_or(false)
+}
+
 /// If the socket is not found in [`SOCKETS`], bypass.
 /// Otherwise, if it's not an ignored port, bind (possibly with a fallback to random port) and
 /// update socket state in [`SOCKETS`]. If it's an ignored port, remove the socket from [`SOCKETS`].
@@ -276,14 +362,18 @@ pub(super) fn bind(
     // bound, as we bind to a different address, but if we don't check for this then we're
     // changing normal socket behavior (see issue #1123).
     // We check that port isn't 0 because if it's port 0 it can't really conflict.
+    // Sockets that all have `SO_REUSEPORT` set are allowed to share the address (e.g. worker
+    // threads of nginx or Envoy, each with its own listener).
+    let reuse_port = is_reuse_port(sockfd);
     if requested_address.port() != 0
         && SOCKETS
             .lock()?
             .iter()
-            .any(|(_, socket)| match &socket.state {
+            .any(|(fd, socket)| match &socket.state {
                 SocketState::Initialized | SocketState::Connected(_) => false,
                 SocketState::Bound { bound, .. } | SocketState::Listening(bound) => {
                     bound.requested_address == requested_address
+                        && (reuse_port && is_reuse_port(*fd)).not()
                 }
             })
     {
@@ -295,14 +385,21 @@ pub(super) fn bind(
         .get_by_left(&requested_address.port())
         .copied();
 
+    let is_tcp = matches!(socket.kind, SocketKind::Tcp(_));
+
     // we don't use `is_localhost` here since unspecified means to listen
     // on all IPs.
-    let will_not_trigger_subscription = (incoming_config.ignore_localhost
-        && requested_address.ip().is_loopback())
-        || ((matches!(socket.kind, SocketKind::Tcp(_)))
-            && is_ignored_tcp_port(&requested_address, incoming_config)
-            || crate::setup().is_debugger_port(&requested_address)
-            || incoming_config.ignore_ports.contains(&requested_port));
+    let is_ignored = |address: &SocketAddr| {
+        (incoming_config.ignore_localhost && address.ip().is_loopback())
+            || (is_tcp && is_ignored_tcp_port(address, incoming_config)
+                || crate::setup().is_debugger_port(address)
+                || incoming_config.ignore_ports.contains(&address.port()))
+    };
+
+    // With `follow_bind`, a TCP socket bound to port 0 is subscribed on the port chosen by the OS,
+    // so whether it's ignored can only be checked after the bind.
+    let follows_bind = is_tcp && requested_port == 0 && incoming_config.follow_bind;
+    let will_not_trigger_subscription = follows_bind.not() && is_ignored(&requested_address);
 
     if will_not_trigger_subscription && listen_port.is_none() {
         return Detour::Bypass(Bypass::IgnoredInIncoming(requested_address));
@@ -364,6 +461,13 @@ pub(super) fn bind(
         return Detour::Bypass(Bypass::AddressConversion);
     };
 
+    let (requested_address, will_not_trigger_subscription) = if follows_bind {
+        let requested_address = SocketAddr::new(requested_address.ip(), address.port());
+        (requested_address, is_ignored(&requested_address))
+    } else {
+        (requested_address, will_not_trigger_subscription)
+    };
+
     Arc::get_mut(&mut socket).unwrap().state = SocketState::Bound {
         bound: Bound {
             requested_address,
@@ -427,10 +531,26 @@ pub(super) fn listen(sockfd: RawFd, backlog: c_int) -> Detour<i32> {
                 .copied()
                 .unwrap_or_else(|| requested_address.port());
 
-            common::make_proxy_request_with_response(PortSubscribe {
-                listening_on: address,
-                subscription: setup.incoming_mode().subscription(mapped_port),
-            })??;
+            // With `SO_REUSEPORT`, other sockets may be already listening on the same address,
+            // and share the subscription (the local kernel distributes the connections between
+            // them).
+            let shared_listener = SOCKETS
+                .lock()?
+                .values()
+                .any(|other| other.is_listening_on(requested_address.port(), address));
+
+            if shared_listener {
+                tracing::debug!(
+                    port = requested_address.port(),
+                    %address,
+                    "listen -> sharing the port subscription with another listener"
+                );
+            } else {
+                common::make_proxy_request_with_response(PortSubscribe {
+                    listening_on: address,
+                    subscription: setup.incoming_mode().subscription(mapped_port),
+                })??;
+            }
 
             // this log message is expected by some E2E tests
             tracing::debug!("daemon subscribed port {}", requested_address.port());
@@ -557,29 +677,7 @@ fn connect_outgoing<const CALL_CONNECT: bool>(
         } = response;
 
         if let SocketAddress::Ip(interceptor_addr) = &mut layer_address {
-            // Our socket can be bound to any local interface,
-            // so the interceptor listens on an unspecified IP address, e.g. 0.0.0.0
-            // We need to fill the exact IP here.
-            match &user_socket_info.state {
-                SocketState::Bound {
-                    bound: Bound { address, .. },
-                    ..
-                } => {
-                    if interceptor_addr.ip().is_unspecified() {
-                        if interceptor_addr.is_ipv4() {
-                            interceptor_addr.set_ip(Ipv4Addr::LOCALHOST.into())
-                        } else {
-                            interceptor_addr.set_ip(Ipv6Addr::LOCALHOST.into())
-                        }
-                    } else {
-                        interceptor_addr.set_ip(address.ip());
-                    }
-                }
-                _ if interceptor_addr.is_ipv4() => {
-                    interceptor_addr.set_ip(Ipv4Addr::LOCALHOST.into())
-                }
-                _ => interceptor_addr.set_ip(Ipv6Addr::LOCALHOST.into()),
-            }
+            set_interceptor_ip(interceptor_addr, &user_socket_info.state);
         }
 
         // Connect to the socket prepared by the internal proxy.
@@ -660,8 +758,42 @@ fn connect_outgoing<const CALL_CONNECT: bool>(
             .get_connection_through(remote_address.as_socket()?, protocol)?
         {
             ConnectionThrough::Remote(addr) => {
-                let connect_result = remote_connection(SockAddr::from(addr))?;
-                Detour::Success(connect_result)
+                let outgoing = crate::setup().outgoing_config();
+                if outgoing.local_fallback.not() || protocol != NetProtocol::Stream {
+                    let connect_result = remote_connection(SockAddr::from(addr))?;
+                    return Detour::Success(connect_result);
+                }
+
+                let ttl = Duration::from_secs(outgoing.local_fallback_ttl);
+                let recently_failed = LOCAL_FALLBACK_CACHE
+                    .lock()?
+                    .get(&addr)
+                    .is_some_and(|failed_at| failed_at.elapsed() < ttl);
+                if recently_failed {
+                    return connect_locally_after_fallback(sockfd, addr);
+                }
+
+                match remote_connection(SockAddr::from(addr)) {
+                    // Other errors (e.g. a policy forbidding the connection) must not be bypassed
+                    // by connecting from the local app.
+                    Detour::Error(HookError::ResponseError(
+                        error @ (ResponseError::RemoteIO(..)
+                        | ResponseError::Remote(RemoteError::ConnectTimedOut(..))),
+                    )) => {
+                        tracing::info!(
+                            %addr,
+                            %error,
+                            "Outgoing connection through the remote pod failed, connecting from the local app",
+                        );
+                        let mut cache = LOCAL_FALLBACK_CACHE.lock()?;
+                        cache.retain(|_, failed_at| failed_at.elapsed() < ttl);
+                        cache.insert(addr, Instant::now());
+                        drop(cache);
+
+                        connect_locally_after_fallback(sockfd, addr)
+                    }
+                    result => result,
+                }
             }
             ConnectionThrough::Local(addr) => {
                 let rawish_local_addr = SockAddr::from(addr);
@@ -676,6 +808,43 @@ fn connect_outgoing<const CALL_CONNECT: bool>(
     }
 }
 
+/// Our socket can be bound to any local interface, so the interceptor listens on an unspecified IP
+/// address, e.g. 0.0.0.0. We need to fill the exact IP here.
+fn set_interceptor_ip(interceptor_addr: &mut SocketAddr, state: &SocketState) {
+    match state {
+        SocketState::Bound {
+            bound: Bound { address, .. },
+            ..
+        } => {
+            if interceptor_addr.ip().is_unspecified() {
+                if interceptor_addr.is_ipv4() {
+                    interceptor_addr.set_ip(Ipv4Addr::LOCALHOST.into())
+                } else {
+                    interceptor_addr.set_ip(Ipv6Addr::LOCALHOST.into())
+                }
+            } else {
+                interceptor_addr.set_ip(address.ip());
+            }
+        }
+        _ if interceptor_addr.is_ipv4() => interceptor_addr.set_ip(Ipv4Addr::LOCALHOST.into()),
+        _ => interceptor_addr.set_ip(Ipv6Addr::LOCALHOST.into()),
+    }
+}
+
+/// Connects the socket from the local app, after the connection through the remote pod failed.
+///
+/// The `address` may have been resolved remotely, so it's translated with
+/// [`OutgoingSelector::get_local_address_to_connect`] first.
+fn connect_locally_after_fallback(sockfd: RawFd, address: SocketAddr) -> Detour<ConnectResult> {
+    let local_address = SockAddr::from(OutgoingSelector::get_local_address_to_connect(address)?);
+
+    let connect_result = ConnectResult::from(unsafe {
+        FN_CONNECT(sockfd, local_address.as_ptr(), local_address.len())
+    });
+
+    Detour::Success(connect_result)
+}
+
 /// Iterate through sockets, if any of them has the requested port that the application is now
 /// trying to connect to - then don't forward this connection to the agent, and instead of
 /// connecting to the requested address, connect to the actual address where the application
@@ -974,6 +1143,143 @@ pub(super) fn getsockname(
     fill_address(address, address_len, local_address)
 }
 
+/// Socket options emulated for managed sockets, see [`setsockopt`] and [`getsockopt`].
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy)]
+enum EmulatedSockopt {
+    /// `SO_BINDTODEVICE`.
+    BindToDevice,
+
+    /// `SO_ORIGINAL_DST` and `IP6T_SO_ORIGINAL_DST`, read-only.
+    OriginalDst,
+}
+
+#[cfg(target_os = "linux")]
+impl EmulatedSockopt {
+    fn from_raw(level: c_int, name: c_int) -> Detour<Self> {
+        match (level, name) {
+            (libc::SOL_SOCKET, libc::SO_BINDTODEVICE) => Detour::Success(Self::BindToDevice),
+            (libc::SOL_IP, libc::SO_ORIGINAL_DST)
+            | (libc::SOL_IPV6, libc::IP6T_SO_ORIGINAL_DST) => Detour::Success(Self::OriginalDst),
+            _ => Detour::Bypass(Bypass::NotEmulatedSockopt(level, name)),
+        }
+    }
+}
+
+/// Emulates setting a socket option on a managed socket, where the local socket would contradict
+/// the impersonation.
+///
+/// - `SO_BINDTODEVICE` names an interface of the target, so we only store it in
+///   [`EmulatedSockopts`], and return it from [`getsockopt`]. The traffic is sent from the agent
+///   anyway.
+///
+/// Other options (e.g. `IP_TOS`) are set on the local socket.
+#[cfg(target_os = "linux")]
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret, skip(value))]
+pub(super) fn setsockopt(
+    sockfd: RawFd,
+    level: c_int,
+    name: c_int,
+    value: *const c_void,
+    value_len: socklen_t,
+) -> Detour<i32> {
+    let option = EmulatedSockopt::from_raw(level, name)?;
+
+    let mut sockets = SOCKETS.lock()?;
+    let socket = sockets
+        .get_mut(&sockfd)
+        .bypass(Bypass::LocalFdNotFound(sockfd))?;
+
+    match option {
+        EmulatedSockopt::BindToDevice => {
+            let device = if value.is_null() || value_len == 0 {
+                Vec::new()
+            } else {
+                let value =
+                    unsafe { std::slice::from_raw_parts(value.cast::<u8>(), value_len as usize) };
+                // The name does not have to be null terminated.
+                value
+                    .split(|byte| *byte == 0)
+                    .next()
+                    .unwrap_or_default()
+                    .to_vec()
+            };
+
+            // An empty name removes the binding.
+            Arc::make_mut(socket).sockopts.bind_to_device =
+                device.is_empty().not().then_some(device);
+
+            Detour::Success(0)
+        }
+
+        EmulatedSockopt::OriginalDst => Detour::Bypass(Bypass::NotEmulatedSockopt(level, name)),
+    }
+}
+
+/// Emulates reading a socket option of a managed socket, returning values consistent with the
+/// target.
+///
+/// - `SO_BINDTODEVICE` returns the interface set with [`setsockopt`];
+/// - `SO_ORIGINAL_DST` (and `IP6T_SO_ORIGINAL_DST`) on an accepted incoming connection returns the
+///   target's address that the connection was made to, as if the application was running behind the
+///   target's NAT redirect.
+///
+/// Other options are read from the local socket.
+#[cfg(target_os = "linux")]
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret, skip(value, value_len))]
+pub(super) fn getsockopt(
+    sockfd: RawFd,
+    level: c_int,
+    name: c_int,
+    value: *mut c_void,
+    value_len: *mut socklen_t,
+) -> Detour<i32> {
+    let option = EmulatedSockopt::from_raw(level, name)?;
+
+    let socket = SOCKETS
+        .lock()?
+        .get(&sockfd)
+        .bypass(Bypass::LocalFdNotFound(sockfd))?
+        .clone();
+
+    if value.is_null() || value_len.is_null() {
+        return Detour::Error(HookError::NullPointer);
+    }
+
+    match option {
+        EmulatedSockopt::BindToDevice => {
+            let Some(device) = socket.sockopts.bind_to_device.as_ref() else {
+                return Detour::Bypass(Bypass::NotEmulatedSockopt(level, name));
+            };
+
+            let mut device = device.clone();
+            device.push(0);
+
+            unsafe {
+                let len = std::cmp::min(*value_len as usize, device.len());
+                copy_nonoverlapping(device.as_ptr(), value.cast::<u8>(), len);
+                *value_len = len as socklen_t;
+            }
+
+            Detour::Success(0)
+        }
+
+        EmulatedSockopt::OriginalDst => match &socket.state {
+            SocketState::Connected(Connected {
+                local_address: Some(local_address),
+                connection_id: None,
+                layer_address: None,
+                ..
+            }) => fill_address(
+                value.cast(),
+                value_len,
+                map_socketaddress_ipv64(socket.domain, local_address.clone()).try_into()?,
+            ),
+            _ => Detour::Bypass(Bypass::InvalidState(sockfd)),
+        },
+    }
+}
+
 /// When the fd is "ours", we accept and use [`ConnMetadataRequest`] to retrieve peer address from
 /// the internal proxy.
 #[mirrord_layer_macro::instrument(level = "trace", ret, skip(address, address_len))]
@@ -1049,20 +1355,22 @@ pub(super) fn fcntl(orig_fd: c_int, cmd: c_int, fcntl_fd: i32) -> Result<(), Hoo
 ///
 /// - `SWITCH_MAP`:
 ///
-/// Indicates that we're switching the `fd` from [`SOCKETS`] to [`OPEN_FILES`] (or vice-versa).
+/// Indicates that `dup_fd` might have been open before the call, and was silently closed by it
+/// (`dup2`, `dup3`). Whatever we had keyed by `dup_fd` is released with [`close_layer_fd`], so
+/// that the `fd` does not mix its old and new identity, e.g. when it's switched from [`SOCKETS`]
+/// to [`OPEN_FILES`] (or vice-versa).
 ///
 /// We need this to properly handle some cases in [`fcntl`], [`dup2_detour`], and [`dup3_detour`].
 /// Extra relevant for node on macos.
 #[mirrord_layer_macro::instrument(level = "trace", ret)]
 pub(super) fn dup<const SWITCH_MAP: bool>(fd: c_int, dup_fd: i32) -> Result<(), HookError> {
+    if SWITCH_MAP {
+        close_layer_fd(dup_fd);
+    }
+
     let mut sockets = SOCKETS.lock()?;
     if let Some(socket) = sockets.get(&fd).cloned() {
         sockets.insert(dup_fd as RawFd, socket);
-
-        if SWITCH_MAP {
-            OPEN_FILES.lock()?.remove(&dup_fd);
-        }
-
         return Ok(());
     }
 
@@ -1070,10 +1378,6 @@ pub(super) fn dup<const SWITCH_MAP: bool>(fd: c_int, dup_fd: i32) -> Result<(),
     if let Some(file) = open_files.get(&fd) {
         let cloned_file = file.clone();
         open_files.insert(dup_fd as RawFd, cloned_file);
-
-        if SWITCH_MAP {
-            sockets.remove(&dup_fd);
-        }
     }
 
     Ok(())
@@ -1173,7 +1477,10 @@ pub(super) fn getaddrinfo(
         .unwrap_or(0);
 
     let setup = crate::setup();
-    setup.dns_selector().check_query(&node, service)?;
+    let dns_override = setup.layer_config().feature.network.dns.override_for(&node);
+    if dns_override.is_none() {
+        setup.dns_selector().check_query(&node, service)?;
+    }
     let ipv6_enabled = setup.layer_config().feature.network.ipv6;
 
     let raw_hints = raw_hints
@@ -1190,7 +1497,9 @@ pub(super) fn getaddrinfo(
 
     // Some apps (gRPC on Python) use `::` to listen on all interfaces, and usually that just means
     // resolve on unspecified. So we just return that in IPv4, if IPv6 support is disabled.
-    let resolved_addr = if ipv6_enabled.not() && (node == "::") {
+    let resolved_addr = if let Some(ip) = dns_override {
+        vec![(node.clone(), ip)]
+    } else if ipv6_enabled.not() && (node == "::") {
         // name is "" because that's what happens in real flow.
         vec![("".to_string(), IpAddr::V4(Ipv4Addr::UNSPECIFIED))]
     } else {
@@ -1299,9 +1608,14 @@ pub(super) fn gethostbyname(raw_name: Option<&CStr>) -> Detour<*mut hostent> {
         })?
         .into();
 
-    crate::setup().dns_selector().check_query(&name, 0)?;
-
-    let hosts_and_ips = remote_getaddrinfo(name.clone(), 0, 0, 0, 0, 0)?;
+    let setup = crate::setup();
+    let hosts_and_ips = match setup.layer_config().feature.network.dns.override_for(&name) {
+        Some(ip) => vec![(name.clone(), ip)],
+        None => {
+            setup.dns_selector().check_query(&name, 0)?;
+            remote_getaddrinfo(name.clone(), 0, 0, 0, 0, 0)?
+        }
+    };
 
     // We could `unwrap` here, as this would have failed on the previous conversion.
     let host_name = CString::new(name)?;
@@ -1425,6 +1739,12 @@ pub(super) fn read_remote_resolv_conf() -> Detour<Vec<u8>> {
 /// When the socket is in a [`Connected`] state, we call [`fill_address`] with its `remote_address`,
 /// instead of letting whatever came in `raw_source` through.
 ///
+/// When the packet came from the interceptor of one of the socket's [`DatagramRoute`]s, we call
+/// [`fill_address`] with the `remote_address` of that route.
+///
+/// For ICMP sockets, we call [`fill_address`] with the source of the oldest echo reply delivered
+/// by [`icmp_echo`].
+///
 /// See [`send_to`] for more information.
 #[mirrord_layer_macro::instrument(level = "trace", ret, skip(raw_source, source_length))]
 pub(super) fn recv_from(
@@ -1433,6 +1753,21 @@ pub(super) fn recv_from(
     raw_source: *mut sockaddr,
     source_length: *mut socklen_t,
 ) -> Detour<isize> {
+    let icmp_reply_source = SOCKETS
+        .lock()?
+        .get_mut(&sockfd)
+        .filter(|socket| socket.is_icmp())
+        .and_then(|socket| Arc::make_mut(socket).icmp_replies.pop_front());
+    if let Some(source) = icmp_reply_source {
+        fill_address(
+            raw_source,
+            source_length,
+            SockAddr::from(SocketAddr::from((source, 0))),
+        )?;
+        Errno::set_raw(0);
+        return Detour::Success(recv_from_result);
+    }
+
     SOCKETS
         .lock()?
         .get(&sockfd)
@@ -1440,6 +1775,22 @@ pub(super) fn recv_from(
             SocketState::Connected(Connected { remote_address, .. }) => {
                 Some(remote_address.clone())
             }
+            SocketState::Bound { .. } | SocketState::Initialized | SocketState::Listening(_)
+                if socket.datagram_routes.is_empty().not()
+                    && raw_source.is_null().not()
+                    && source_length.is_null().not() =>
+            {
+                let Detour::Success(source) =
+                    SocketAddr::try_from_raw(raw_source, unsafe { *source_length })
+                else {
+                    return None;
+                };
+                socket
+                    .datagram_routes
+                    .iter()
+                    .find(|route| route.layer_address == source)
+                    .map(|route| SocketAddress::Ip(route.remote_address))
+            }
             SocketState::Bound { .. } | SocketState::Initialized | SocketState::Listening(_) => {
                 None
             }
@@ -1451,7 +1802,11 @@ pub(super) fn recv_from(
     Detour::Success(recv_from_result)
 }
 
-/// Helps manually resolving DNS on port `53` with UDP, see [`send_to`] and [`sendmsg`].
+/// Finds the real destination of a datagram that is NOT sent to port `53`, see [`send_to`] and
+/// [`sendmsg`].
+///
+/// If the `destination` is one of our sockets, returns its real address. Otherwise, the datagram
+/// may be sent through the agent, see [`route_datagram`].
 #[mirrord_layer_macro::instrument(level = "trace", ret)]
 fn send_dns_patch(
     sockfd: RawFd,
@@ -1463,7 +1818,7 @@ fn send_dns_patch(
     sockets.insert(sockfd, user_socket_info);
 
     // Sending a packet on port NOT 53.
-    let destination = sockets
+    let local_destination = sockets
         .iter()
         .filter(|(_, socket)| socket.kind.is_udp())
         // Is the `destination` one of our sockets? If so, then we grab the actual address,
@@ -1500,9 +1855,186 @@ fn send_dns_patch(
                 }
             }
             SocketState::Listening(_) | SocketState::Initialized => None,
-        })?;
+        });
+
+    if let Some(local_destination) = local_destination {
+        return Detour::Success(SockAddr::from(local_destination));
+    }
+
+    drop(sockets);
+    route_datagram(sockfd, destination)
+}
+
+/// Sends the datagrams of an unconnected UDP socket through the agent, when outgoing UDP traffic is
+/// enabled and the outgoing filter selects the remote pod for the `destination`.
+///
+/// Returns the address of the interceptor socket for the `destination` (see [`DatagramRoute`]),
+/// requesting a new one from the internal proxy for the first datagram. Every destination gets its
+/// own connection in the agent, so the responses are never mixed up, and [`recv_from`] can tell
+/// the user where they came from.
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+fn route_datagram(sockfd: RawFd, destination: SocketAddr) -> Detour<SockAddr> {
+    let ip = destination.ip();
+    if ip.is_loopback() || ip.is_unspecified() {
+        return Detour::Success(SockAddr::from(destination));
+    }
+
+    if is_ignored_port(&destination) || crate::setup().is_debugger_port(&destination) {
+        return Detour::Bypass(Bypass::IgnoredInIncoming(destination));
+    }
+
+    if crate::setup().outgoing_config().udp.not() {
+        return Detour::Bypass(Bypass::DisabledOutgoing);
+    }
+
+    let user_socket = SOCKETS
+        .lock()?
+        .get(&sockfd)
+        .cloned()
+        .ok_or(Bypass::LocalFdNotFound(sockfd))?;
+    if !matches!(
+        user_socket.state,
+        SocketState::Initialized | SocketState::Bound { .. }
+    ) {
+        return Detour::Bypass(Bypass::InvalidState(sockfd));
+    }
+
+    if let Some(route) = user_socket
+        .datagram_routes
+        .iter()
+        .find(|route| route.remote_address == destination)
+    {
+        return Detour::Success(SockAddr::from(route.layer_address));
+    }
+
+    let remote_address = match crate::setup()
+        .outgoing_selector()
+        .get_connection_through(destination, NetProtocol::Datagrams)?
+    {
+        ConnectionThrough::Local(address) => return Detour::Success(SockAddr::from(address)),
+        ConnectionThrough::Remote(address) => address,
+    };
+
+    let OutgoingConnectResponse {
+        connection_id,
+        layer_address,
+        ..
+    } = common::make_proxy_request_with_response(OutgoingConnectRequest {
+        remote_address: SocketAddress::Ip(remote_address),
+        protocol: NetProtocol::Datagrams,
+    })??;
+    let SocketAddress::Ip(mut layer_address) = layer_address else {
+        return Detour::Bypass(Bypass::AddressConversion);
+    };
+    set_interceptor_ip(&mut layer_address, &user_socket.state);
+
+    if let Some(socket) = SOCKETS.lock()?.get_mut(&sockfd) {
+        Arc::make_mut(socket).datagram_routes.push(DatagramRoute {
+            remote_address: destination,
+            layer_address,
+            connection_id,
+        });
+    }
+
+    Detour::Success(SockAddr::from(layer_address))
+}
+
+/// ICMP message type of an echo request.
+const ICMP_ECHO_REQUEST: u8 = 8;
+
+/// Whether `sockfd` is one of our ICMP sockets, see [`icmp_socket`].
+fn is_icmp_socket(sockfd: RawFd) -> Detour<bool> {
+    Detour::Success(
+        SOCKETS
+            .lock()?
+            .get(&sockfd)
+            .is_some_and(|socket| socket.is_icmp()),
+    )
+}
+
+/// Copies the message scattered in the [`libc::iovec`]s of a [`libc::msghdr`].
+// `msg_iovlen` is not a `usize` on every platform.
+#[allow(clippy::unnecessary_cast)]
+fn gather_message(message_header: &libc::msghdr) -> Vec<u8> {
+    if message_header.msg_iov.is_null() {
+        return Vec::new();
+    }
+
+    let iovecs = unsafe {
+        std::slice::from_raw_parts(message_header.msg_iov, message_header.msg_iovlen as usize)
+    };
+
+    iovecs
+        .iter()
+        .filter(|iovec| iovec.iov_base.is_null().not())
+        .flat_map(|iovec| unsafe {
+            std::slice::from_raw_parts(iovec.iov_base.cast::<u8>(), iovec.iov_len)
+        })
+        .copied()
+        .collect()
+}
+
+/// How long the agent waits for the reply to an ICMP echo request, see [`icmp_echo`].
+const ICMP_ECHO_TIMEOUT: Duration = Duration::from_secs(1);
 
-    Detour::Success(SockAddr::from(destination))
+/// Sends the ICMP echo request `message` of an ICMP socket (see [`icmp_socket`]) through the
+/// agent, and delivers the echo reply by sending it from the socket to itself.
+///
+/// Blocks until the reply arrives, or for up to [`ICMP_ECHO_TIMEOUT`]. When there is no reply, the
+/// request is lost, like it would be on the network. [`recv_from`] tells the user where the reply
+/// came from.
+#[mirrord_layer_macro::instrument(level = "trace", ret, skip(message))]
+fn icmp_echo(sockfd: RawFd, message: &[u8], destination: Ipv4Addr) -> Detour<isize> {
+    let field = |range: std::ops::Range<usize>| {
+        message
+            .get(range)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u16::from_be_bytes)
+    };
+    let (Some(&ICMP_ECHO_REQUEST), Some(identifier), Some(sequence)) =
+        (message.first(), field(4..6), field(6..8))
+    else {
+        return Detour::Error(io::Error::from(io::ErrorKind::InvalidInput).into());
+    };
+
+    let response = make_proxy_request_with_response(IcmpEchoRequest {
+        destination,
+        identifier,
+        sequence,
+        payload: message.get(8..).unwrap_or_default().to_vec().into(),
+        timeout_ms: ICMP_ECHO_TIMEOUT.as_millis().try_into()?,
+    })?;
+
+    let IcmpEchoResponse { source, packet } = match response {
+        Ok(response) => response,
+        Err(ResponseError::RemoteIO(RemoteIOError {
+            kind: ErrorKindInternal::TimedOut,
+            ..
+        })) => return Detour::Success(message.len().try_into()?),
+        Err(error) => return Detour::Error(error.into()),
+    };
+
+    let local_address =
+        nix::sys::socket::getsockname::<SockaddrStorage>(sockfd).map_err(io::Error::from)?;
+    if let Some(socket) = SOCKETS.lock()?.get_mut(&sockfd) {
+        Arc::make_mut(socket).icmp_replies.push_back(source);
+    }
+
+    let sent = unsafe {
+        FN_SEND_TO(
+            sockfd,
+            packet.as_ptr().cast(),
+            packet.len(),
+            0,
+            local_address.as_ptr(),
+            local_address.len(),
+        )
+    };
+    if sent == -1 {
+        return Detour::Error(io::Error::last_os_error().into());
+    }
+
+    Detour::Success(message.len().try_into()?)
 }
 
 /// ## DNS resolution on port `53`
@@ -1521,6 +2053,9 @@ fn send_dns_patch(
 /// If we find `destination` as the `requested_address` of one of our [`Bound`] sockets, then we
 /// [`libc::sendto`] to the bound `address`. A similar logic applies to a [`Connected`] socket.
 ///
+/// Otherwise, with outgoing UDP traffic enabled, we [`libc::sendto`] to the interceptor of the
+/// socket's [`DatagramRoute`] for `destination`, see [`route_datagram`].
+///
 /// ## Destination is `0.0.0.0:{not 53}`
 ///
 /// No special care is taken here, sending a packet to this address behaves the same with or without
@@ -1543,6 +2078,17 @@ pub(super) fn send_to(
     let destination = SockAddr::try_from_raw(raw_destination, destination_length)?;
     trace!("destination {:?}", destination.as_socket());
 
+    if let Some(destination) = destination.as_socket_ipv4()
+        && is_icmp_socket(sockfd)?
+    {
+        let message = if raw_message.is_null() {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(raw_message.cast::<u8>(), message_length) }
+        };
+        return icmp_echo(sockfd, message, *destination.ip());
+    }
+
     let user_socket_info = SOCKETS
         .lock()?
         .remove(&sockfd)
@@ -1635,6 +2181,13 @@ pub(super) fn sendmsg(
 
     trace!("destination {:?}", destination.as_socket());
 
+    if let Some(destination) = destination.as_socket_ipv4()
+        && is_icmp_socket(sockfd)?
+    {
+        let message = gather_message(unsafe { &*raw_message_header });
+        return icmp_echo(sockfd, &message, *destination.ip());
+    }
+
     // send_dns_patch acquires lock, so don't hold it
     let user_socket_info = SOCKETS
         .lock()?
@@ -1845,9 +2398,20 @@ pub(super) fn remote_dns_configuration_copy() -> Detour<*mut dns_config_t> {
     Detour::Success(config)
 }
 
+/// Interfaces of the target, fetched from the agent on the first [`getifaddrs`] call when
+/// `feature.network.interfaces` is enabled.
+static REMOTE_INTERFACES: OnceLock<Vec<InterfaceAddress>> = OnceLock::new();
+
 /// Calls [`libc::getifaddrs`] and removes IPv6 addresses from the list.
+///
+/// When `feature.network.interfaces` is enabled, returns the interfaces of the target instead,
+/// see [`remote_getifaddrs`].
 #[mirrord_layer_macro::instrument(level = Level::TRACE, ret, err)]
 pub(super) fn getifaddrs() -> HookResult<*mut libc::ifaddrs> {
+    if crate::setup().layer_config().feature.network.interfaces {
+        return remote_getifaddrs();
+    }
+
     let mut original_head = std::ptr::null_mut();
     let result: i32 = unsafe { FN_GETIFADDRS(&mut original_head) };
     if result != 0 {
@@ -1926,3 +2490,97 @@ pub(super) fn getifaddrs() -> HookResult<*mut libc::ifaddrs> {
 
     Ok(new_list_start)
 }
+
+/// Builds the [`libc::ifaddrs`] list from the interfaces of the target.
+///
+/// IPv6 addresses are removed from the list when `experimental.hide_ipv6_interfaces` is enabled.
+fn remote_getifaddrs() -> HookResult<*mut libc::ifaddrs> {
+    let interfaces = REMOTE_INTERFACES.get_or_try_init(|| {
+        make_proxy_request_with_response(GetInterfacesRequest)?.map_err(HookError::from)
+    })?;
+
+    let hide_ipv6 = crate::setup().experimental().hide_ipv6_interfaces;
+    let interfaces = interfaces
+        .iter()
+        .filter(|interface| (hide_ipv6 && interface.address.is_ipv6()).not())
+        .collect::<Vec<_>>();
+
+    if interfaces.is_empty() {
+        return Ok(ptr::null_mut());
+    }
+
+    // Everything is placed in a single allocation (entries, then their addresses, then their
+    // names), so that the user can release the list with `freeifaddrs`, like the list allocated
+    // by libc.
+    let entries_size = mem::size_of::<libc::ifaddrs>() * interfaces.len();
+    let addresses_size = mem::size_of::<libc::sockaddr_storage>() * 3 * interfaces.len();
+    let names_size = interfaces
+        .iter()
+        .map(|interface| interface.name.len() + 1)
+        .sum::<usize>();
+
+    // Safety: We assume `libc::malloc` is the same allocator as the user's system.
+    let list_start = unsafe { libc::malloc(entries_size + addresses_size + names_size) } as *mut u8;
+    if list_start.is_null() {
+        Err(io::Error::from(io::ErrorKind::OutOfMemory))?;
+    }
+
+    // Safety: all writes stay within the allocation, which is sized for every entry having
+    // 3 addresses.
+    unsafe {
+        let entries = list_start as *mut libc::ifaddrs;
+        let mut next_address = list_start.add(entries_size) as *mut libc::sockaddr_storage;
+        let mut next_name = list_start.add(entries_size + addresses_size) as *mut libc::c_char;
+
+        let mut write_address = |ip: Option<IpAddr>| -> *mut sockaddr {
+            let Some(ip) = ip else {
+                return ptr::null_mut();
+            };
+
+            let address = SockAddr::from(SocketAddr::new(ip, 0));
+            let slot = next_address;
+            copy_nonoverlapping(
+                address.as_ptr() as *const u8,
+                slot as *mut u8,
+                address.len() as usize,
+            );
+            next_address = next_address.add(1);
+
+            slot as *mut sockaddr
+        };
+
+        for (index, interface) in interfaces.iter().enumerate() {
+            let name = next_name;
+            copy_nonoverlapping(
+                interface.name.as_ptr() as *const libc::c_char,
+                name,
+                interface.name.len(),
+            );
+            *name.add(interface.name.len()) = 0;
+            next_name = name.add(interface.name.len() + 1);
+
+            let mut entry: libc::ifaddrs = mem::zeroed();
+            entry.ifa_next = if index + 1 < interfaces.len() {
+                entries.add(index + 1)
+            } else {
+                ptr::null_mut()
+            };
+            entry.ifa_name = name;
+            entry.ifa_flags = interface.flags;
+            entry.ifa_addr = write_address(Some(interface.address));
+            entry.ifa_netmask = write_address(interface.netmask);
+            #[cfg(target_os = "linux")]
+            {
+                entry.ifa_ifu = write_address(interface.broadcast_or_destination);
+            }
+            #[cfg(target_os = "macos")]
+            {
+                entry.ifa_dstaddr = write_address(interface.broadcast_or_destination);
+            }
+
+            entries.add(index).write(entry);
+        }
+    }
+
+    Ok(list_start as *mut libc::ifaddrs)
+}
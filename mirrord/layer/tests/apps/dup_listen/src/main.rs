@@ -1,5 +1,6 @@
 #[cfg(target_family = "unix")]
 use std::{
+    fs::File,
     io::Read,
     net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener},
     os::fd::AsRawFd,
@@ -24,6 +25,14 @@ fn main() {
     let mut buf = String::new();
     stream.read_to_string(&mut buf).unwrap();
     assert_eq!(buf, "hello there");
+
+    // Replace the listener's descriptor with another file, which silently closes the listener.
+    let dev_null = File::open("/dev/null").unwrap();
+    nix::unistd::dup2(dev_null.as_raw_fd(), fd).unwrap();
+    // The descriptor now belongs to `/dev/null`, so we can't let the listener close it.
+    std::mem::forget(listener);
+    // Test code waits for this message.
+    println!("Listener descriptor replaced");
 }
 
 #[cfg(not(target_family = "unix"))]
@@ -0,0 +1,57 @@
+#include <assert.h>
+#include <dlfcn.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+
+#include "../../../include/mirrord_layer.h"
+
+typedef mirrord_layer_handle_t *(*start_fn)(const char *);
+typedef int (*status_fn)(const mirrord_layer_handle_t *);
+typedef void (*stop_fn)(mirrord_layer_handle_t *);
+
+/// Test the C ABI of the layer (`src/embed.rs`).
+///
+/// Loads the layer with `dlopen` (the path is in `MIRRORD_TEST_LAYER_PATH`), starts it with the
+/// config from `MIRRORD_TEST_LAYER_CONFIG`, and makes a remote `readlink` call (the test
+/// answers it as if `ln -s /gatos/rajado.txt /gatos/tigrado.txt` was run in the target).
+/// After the layer is stopped, the same call is made locally.
+int main() {
+  char *layer_path = getenv("MIRRORD_TEST_LAYER_PATH");
+  char *config = getenv("MIRRORD_TEST_LAYER_CONFIG");
+  assert(layer_path != NULL);
+  assert(config != NULL);
+
+  void *layer = dlopen(layer_path, RTLD_NOW);
+  if (layer == NULL) {
+    fprintf(stderr, "dlopen failed: %s\n", dlerror());
+    return 1;
+  }
+
+  start_fn start = (start_fn)dlsym(layer, "mirrord_layer_start");
+  status_fn status = (status_fn)dlsym(layer, "mirrord_layer_status");
+  stop_fn stop = (stop_fn)dlsym(layer, "mirrord_layer_stop");
+  assert(start != NULL && status != NULL && stop != NULL);
+
+  mirrord_layer_handle_t *handle = start(config);
+  assert(handle != NULL);
+  assert(status(handle) == MIRRORD_LAYER_STATUS_RUNNING);
+
+  char buffer[30];
+  char *path = "/gatos/tigrado.txt";
+  ssize_t amount_read = readlink(path, buffer, sizeof(buffer) - 1);
+  assert(amount_read >= 0);
+  buffer[amount_read] = '\0';
+  printf("'%s' -> '%s'\n", path, buffer);
+  assert(strcmp("/gatos/rajado.txt", buffer) == 0);
+
+  stop(handle);
+  assert(status(handle) == MIRRORD_LAYER_STATUS_STOPPED);
+  assert(status(NULL) == MIRRORD_LAYER_STATUS_INVALID);
+
+  // The file does not exist locally.
+  assert(readlink(path, buffer, sizeof(buffer) - 1) == -1);
+
+  return 0;
+}
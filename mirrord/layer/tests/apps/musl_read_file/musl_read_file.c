@@ -0,0 +1,23 @@
+#include <fcntl.h>
+#include <stdio.h>
+#include <unistd.h>
+
+// Built with `musl-gcc` by `scripts/build_c_apps.sh`, so that it is linked against musl, like the
+// apps built on Alpine.
+int main() {
+    char buffer[64] = {0};
+
+    int fd = open("/app/test.txt", O_RDONLY);
+    if (fd < 0) {
+        return 1;
+    }
+
+    ssize_t read_amount = read(fd, buffer, sizeof(buffer) - 1);
+    close(fd);
+    if (read_amount <= 0) {
+        return 1;
+    }
+
+    printf("%s", buffer);
+    return 0;
+}
@@ -3,9 +3,11 @@
 use std::{
     env,
     io::{Read, Write},
+    mem::MaybeUninit,
     net::{SocketAddr, TcpStream, UdpSocket},
 };
 
+use socket2::{Domain, Socket, Type};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     task::JoinSet,
@@ -13,8 +15,19 @@ use tokio::{
 
 const MESSAGE: &[u8] = "FOO BAR HAM".as_bytes();
 
+#[derive(Clone, Copy)]
+enum Protocol {
+    Tcp,
+    /// UDP with a `connect`ed socket.
+    Udp,
+    /// UDP with an unconnected socket, using `sendto` and `recvfrom`.
+    UdpSendTo,
+    /// ICMP echo (ping) with a `SOCK_DGRAM` socket, using `sendto` and `recvfrom`.
+    Icmp,
+}
+
 struct Args {
-    tcp: bool,
+    protocol: Protocol,
     expected_local_addr: SocketAddr,
     peers: Vec<SocketAddr>,
     non_blocking: bool,
@@ -23,9 +36,11 @@ struct Args {
 fn parse_args() -> Option<Args> {
     let args = env::args().collect::<Vec<_>>();
 
-    let tcp = match args.get(1)?.as_str() {
-        "--tcp" => true,
-        "--udp" => false,
+    let protocol = match args.get(1)?.as_str() {
+        "--tcp" => Protocol::Tcp,
+        "--udp" => Protocol::Udp,
+        "--udp-sendto" => Protocol::UdpSendTo,
+        "--icmp" => Protocol::Icmp,
         _ => None?,
     };
     let expected_local_addr = args.get(2)?.parse::<SocketAddr>().ok()?;
@@ -42,7 +57,7 @@ fn parse_args() -> Option<Args> {
     };
 
     Some(Args {
-        tcp,
+        protocol,
         expected_local_addr,
         peers,
         non_blocking,
@@ -107,6 +122,69 @@ fn test_udp(socket: SocketAddr, peer: SocketAddr) {
     }
 }
 
+/// Sends to all peers from a single unconnected socket, and expects each peer to send the same data
+/// back.
+fn test_udp_send_to(peers: Vec<SocketAddr>) {
+    let udp_socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+
+    for peer in peers {
+        let sent = udp_socket.send_to(MESSAGE, peer).unwrap();
+        if sent != MESSAGE.len() {
+            panic!("Partial send: {sent} bytes.");
+        }
+
+        let mut response = [0; MESSAGE.len()];
+        let (res_len, remote) = udp_socket.recv_from(&mut response).unwrap();
+        if res_len != MESSAGE.len() || response != MESSAGE {
+            panic!(
+                "Invalid response received: {:?}.",
+                response
+                    .get(..res_len)
+                    .expect("returned response length out of bounds")
+            );
+        }
+        if remote != peer {
+            panic!("Invalid peer address from recv: {remote}.");
+        }
+    }
+}
+
+/// Pings all peers (ignoring the ports) from a single ICMP socket, and expects an echo reply with
+/// the same data from each of them.
+fn test_icmp(peers: Vec<SocketAddr>) {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(socket2::Protocol::ICMPV4)).unwrap();
+
+    for (sequence, peer) in (1..).zip(peers) {
+        let peer = SocketAddr::new(peer.ip(), 0);
+
+        let mut request = vec![8, 0, 0, 0, 0, 1, 0, sequence];
+        request.extend_from_slice(MESSAGE);
+        let sent = socket.send_to(&request, &peer.into()).unwrap();
+        if sent != request.len() {
+            panic!("Partial send: {sent} bytes.");
+        }
+
+        let mut buffer = [MaybeUninit::new(0); 64];
+        let (reply_len, source) = socket.recv_from(&mut buffer).unwrap();
+        let reply = buffer
+            .get(..reply_len)
+            .expect("returned reply length out of bounds")
+            .iter()
+            .map(|byte| unsafe { byte.assume_init() })
+            .collect::<Vec<_>>();
+
+        if reply.first() != Some(&0)
+            || reply.get(6..8) != Some([0, sequence].as_slice())
+            || reply.get(8..) != Some(MESSAGE)
+        {
+            panic!("Invalid echo reply received: {reply:?}.");
+        }
+        if source.as_socket() != Some(peer) {
+            panic!("Invalid source address from recv: {source:?}.");
+        }
+    }
+}
+
 async fn test_tcp_non_blocking(socket: SocketAddr, peers: Vec<SocketAddr>) {
     let mut tasks = JoinSet::new();
 
@@ -142,13 +220,13 @@ async fn test_tcp_non_blocking(socket: SocketAddr, peers: Vec<SocketAddr>) {
 fn main() {
     let Some(args) = parse_args() else {
         panic!(
-            "USAGE: {} --tcp/--udp <local socket> <peer sockets> [--non-blocking]",
+            "USAGE: {} --tcp/--udp/--udp-sendto/--icmp <local socket> <peer sockets> [--non-blocking]",
             env::args().next().unwrap()
         );
     };
 
-    match (args.tcp, args.non_blocking) {
-        (true, true) => {
+    match (args.protocol, args.non_blocking) {
+        (Protocol::Tcp, true) => {
             // The runtime **must** be single-threaded, otherwise this app will not verify what it's
             // supposed to verify. See the corresponding integration test for reference.
             let runtime = tokio::runtime::Builder::new_current_thread()
@@ -157,18 +235,20 @@ fn main() {
                 .unwrap();
             runtime.block_on(test_tcp_non_blocking(args.expected_local_addr, args.peers));
         }
-        (true, false) => {
+        (Protocol::Tcp, false) => {
             args.peers
                 .into_iter()
                 .for_each(|peer| test_tcp(args.expected_local_addr, peer));
         }
-        (false, true) => {
-            panic!("--non-blocking flag is not supported with --udp")
+        (Protocol::Udp | Protocol::UdpSendTo | Protocol::Icmp, true) => {
+            panic!("--non-blocking flag is only supported with --tcp")
         }
-        (false, false) => {
+        (Protocol::Udp, false) => {
             args.peers
                 .into_iter()
                 .for_each(|peer| test_udp(args.expected_local_addr, peer));
         }
+        (Protocol::UdpSendTo, false) => test_udp_send_to(args.peers),
+        (Protocol::Icmp, false) => test_icmp(args.peers),
     }
 }
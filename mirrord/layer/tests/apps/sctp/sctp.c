@@ -0,0 +1,30 @@
+#include <assert.h>
+#include <netinet/in.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+/// Test that SCTP sockets are not intercepted.
+///
+/// The sockets are created locally (this fails if the kernel has no SCTP support, which is
+/// fine). The layer warns the internal proxy about the first one, and keeps working for other
+/// calls: the `readlink` is answered by the test.
+int main() {
+  for (int i = 0; i < 2; i++) {
+    int sctp = socket(AF_INET, SOCK_STREAM, IPPROTO_SCTP);
+    if (sctp >= 0) {
+      close(sctp);
+    }
+  }
+
+  char buffer[30];
+  ssize_t amount_read =
+      readlink("/gatos/tigrado.txt", buffer, sizeof(buffer) - 1);
+  assert(amount_read >= 0);
+  buffer[amount_read] = '\0';
+  assert(strcmp("/gatos/rajado.txt", buffer) == 0);
+
+  printf("test sctp: SUCCESS\n");
+  return 0;
+}
@@ -0,0 +1,67 @@
+#include <arpa/inet.h>
+#include <assert.h>
+#include <net/if.h>
+#include <netinet/in.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+// From `linux/netfilter_ipv4.h`.
+#ifndef SO_ORIGINAL_DST
+#define SO_ORIGINAL_DST 80
+#endif
+
+/// Test the socket options emulated by the layer on managed sockets.
+///
+/// `SO_BINDTODEVICE` names an interface that does not exist locally, so it would fail on the
+/// local socket. `SO_ORIGINAL_DST` on the accepted connection returns the target's address
+/// (`1.1.1.1:80`, sent by the test), as if the app was behind the target's NAT redirect. Other
+/// options are set on the local socket.
+int main() {
+  int listener = socket(AF_INET, SOCK_STREAM, 0);
+  assert(listener >= 0);
+
+  const char *device = "eth-target";
+  assert(setsockopt(listener, SOL_SOCKET, SO_BINDTODEVICE, device,
+                    strlen(device)) == 0);
+
+  char bound_device[IFNAMSIZ] = {0};
+  socklen_t bound_device_len = sizeof(bound_device);
+  assert(getsockopt(listener, SOL_SOCKET, SO_BINDTODEVICE, bound_device,
+                    &bound_device_len) == 0);
+  assert(strcmp(device, bound_device) == 0);
+
+  int reuse = 1;
+  assert(setsockopt(listener, SOL_SOCKET, SO_REUSEADDR, &reuse,
+                    sizeof(reuse)) == 0);
+  int value = 0;
+  socklen_t value_len = sizeof(value);
+  assert(getsockopt(listener, SOL_SOCKET, SO_REUSEADDR, &value, &value_len) ==
+         0);
+  assert(value == 1);
+
+  struct sockaddr_in address = {0};
+  address.sin_family = AF_INET;
+  address.sin_addr.s_addr = htonl(INADDR_ANY);
+  address.sin_port = htons(80);
+  assert(bind(listener, (struct sockaddr *)&address, sizeof(address)) == 0);
+  assert(listen(listener, 8) == 0);
+
+  int connection = accept(listener, NULL, NULL);
+  assert(connection >= 0);
+
+  struct sockaddr_in original_dst = {0};
+  socklen_t original_dst_len = sizeof(original_dst);
+  assert(getsockopt(connection, IPPROTO_IP, SO_ORIGINAL_DST, &original_dst,
+                    &original_dst_len) == 0);
+  assert(original_dst.sin_family == AF_INET);
+  assert(ntohs(original_dst.sin_port) == 80);
+  assert(strcmp(inet_ntoa(original_dst.sin_addr), "1.1.1.1") == 0);
+
+  close(connection);
+  close(listener);
+
+  printf("test sockopts: SUCCESS\n");
+  return 0;
+}
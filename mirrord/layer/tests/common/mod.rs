@@ -150,7 +150,16 @@ impl TestIntProxy {
                 listener,
                 0,
                 Default::default(),
+                None,
+                Default::default(),
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
                 Duration::from_secs(60),
+                false,
                 &experimental_config,
             );
             intproxy
@@ -957,6 +966,8 @@ pub enum Application {
     GoFAccessAt(GoVersion),
     GoSelfOpen(GoVersion),
     RustOutgoingUdp,
+    RustOutgoingUdpSendTo,
+    RustOutgoingIcmp,
     RustOutgoingTcp {
         non_blocking: bool,
     },
@@ -1010,6 +1021,12 @@ pub enum Application {
     DupListen,
     /// Rust app that listens on a socket twice
     DoubleListen,
+    /// C app that loads the layer with `dlopen`, and starts it with the C ABI.
+    Embed,
+    /// C app that sets and reads the socket options emulated by the layer.
+    Sockopts,
+    /// C app that creates SCTP sockets.
+    Sctp,
 }
 
 impl Application {
@@ -1042,6 +1059,9 @@ impl Application {
             Application::PythonFastApiHTTP | Application::PythonIssue864 => String::from("uvicorn"),
             Application::Fork => String::from("tests/apps/fork/out.c_test_app"),
             Application::ReadLink => String::from("tests/apps/readlink/out.c_test_app"),
+            Application::Embed => String::from("tests/apps/embed/out.c_test_app"),
+            Application::Sockopts => String::from("tests/apps/sockopts/out.c_test_app"),
+            Application::Sctp => String::from("tests/apps/sctp/out.c_test_app"),
             Application::StatfsFstatfs => String::from("tests/apps/statfs_fstatfs/out.c_test_app"),
             Application::MkdirRmdir => String::from("tests/apps/mkdir_rmdir/out.c_test_app"),
             Application::Realpath => String::from("tests/apps/realpath/out.c_test_app"),
@@ -1093,7 +1113,10 @@ impl Application {
             Application::RustIssue1458PortNot53 => {
                 String::from("tests/apps/issue1458portnot53/target/issue1458portnot53")
             }
-            Application::RustOutgoingUdp | Application::RustOutgoingTcp { .. } => format!(
+            Application::RustOutgoingUdp
+            | Application::RustOutgoingUdpSendTo
+            | Application::RustOutgoingIcmp
+            | Application::RustOutgoingTcp { .. } => format!(
                 "{}/{}",
                 env!("CARGO_MANIFEST_DIR"),
                 "../../target/debug/outgoing",
@@ -1297,6 +1320,9 @@ impl Application {
             | Application::GoFAccessAt(..)
             | Application::Fork
             | Application::ReadLink
+            | Application::Embed
+            | Application::Sockopts
+            | Application::Sctp
             | Application::StatfsFstatfs
             | Application::MkdirRmdir
             | Application::Realpath
@@ -1333,6 +1359,16 @@ impl Application {
                 .into_iter()
                 .map(Into::into)
                 .collect(),
+            Application::RustOutgoingUdpSendTo => {
+                ["--udp-sendto", RUST_OUTGOING_LOCAL, RUST_OUTGOING_PEERS]
+                    .into_iter()
+                    .map(Into::into)
+                    .collect()
+            }
+            Application::RustOutgoingIcmp => ["--icmp", RUST_OUTGOING_LOCAL, RUST_OUTGOING_PEERS]
+                .into_iter()
+                .map(Into::into)
+                .collect(),
             Application::RustOutgoingTcp {
                 non_blocking: false,
             } => ["--tcp", RUST_OUTGOING_LOCAL, RUST_OUTGOING_PEERS]
@@ -1371,7 +1407,8 @@ impl Application {
             | Application::NodeHTTP
             | Application::RustIssue1054
             | Application::PythonFlaskHTTP
-            | Application::DupListen => 80,
+            | Application::DupListen
+            | Application::Sockopts => 80,
             // mapped from 9999 in `configs/port_mapping.json`
             Application::PythonFastApiHTTP | Application::PythonIssue864 => 1234,
             Application::RustIssue1123 => 41222,
@@ -1389,6 +1426,8 @@ impl Application {
             | Application::BashShebang
             | Application::Fork
             | Application::ReadLink
+            | Application::Embed
+            | Application::Sctp
             | Application::StatfsFstatfs
             | Application::MkdirRmdir
             | Application::Realpath
@@ -1401,6 +1440,8 @@ impl Application {
             | Application::GoSelfOpen(..)
             | Application::GoDir(..)
             | Application::RustOutgoingUdp
+            | Application::RustOutgoingUdpSendTo
+            | Application::RustOutgoingIcmp
             | Application::RustOutgoingTcp { .. }
             | Application::RustIssue1458
             | Application::RustIssue1458PortNot53
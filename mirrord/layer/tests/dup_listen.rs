@@ -39,8 +39,23 @@ async fn dup_listen(dylib_path: &Path) {
         .await
         .unwrap_err();
 
-    // Verify that the subscription works fine - test app waits for this data before exiting.
+    // Verify that the subscription works fine - test app waits for this data before replacing
+    // the listener's descriptor.
     intproxy.send_connection_then_data("hello there", 80).await;
+
+    // Replacing the descriptor with `dup2` closes the listener, which should trigger port
+    // unsubscribe.
+    test_process
+        .wait_for_line_stdout(Duration::from_secs(5), "Listener descriptor replaced")
+        .await;
+    loop {
+        match intproxy.recv().await {
+            ClientMessage::Tcp(LayerTcp::PortUnsubscribe(80)) => break,
+            ClientMessage::Tcp(LayerTcp::ConnectionUnsubscribe(..)) => {}
+            other => panic!("unexpected message: {other:?}"),
+        }
+    }
+
     test_process.wait_assert_success().await;
     test_process.assert_no_error_in_stderr().await;
     test_process.assert_no_error_in_stdout().await;
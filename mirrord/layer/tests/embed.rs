@@ -0,0 +1,41 @@
+#![cfg(target_family = "unix")]
+#![cfg(target_os = "linux")]
+#![feature(assert_matches)]
+use std::{path::Path, time::Duration};
+
+use mirrord_config::LayerConfig;
+use rstest::rstest;
+use tokio::net::TcpListener;
+
+mod common;
+pub use common::*;
+
+/// Verifies that the layer loaded with `dlopen` does nothing until it's started with
+/// `mirrord_layer_start`, and that the calls are local again after `mirrord_layer_stop`.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn embed(dylib_path: &Path) {
+    let application = Application::Embed;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let mut env = get_env(dylib_path, listener.local_addr().unwrap(), vec![], None);
+    env.remove("LD_PRELOAD");
+    let config = env.remove(LayerConfig::RESOLVED_CONFIG_ENV).unwrap();
+    let config = serde_json::to_string(&LayerConfig::decode(&config).unwrap()).unwrap();
+    env.insert("MIRRORD_TEST_LAYER_CONFIG".to_string(), config);
+    env.insert(
+        "MIRRORD_TEST_LAYER_PATH".to_string(),
+        dylib_path.to_str().unwrap().to_string(),
+    );
+
+    let mut test_process = application.get_test_process(env).await;
+    let mut intproxy = TestIntProxy::new(listener, None).await;
+
+    intproxy.expect_read_link("/gatos/tigrado.txt").await;
+
+    assert_eq!(intproxy.try_recv().await, None);
+
+    test_process.wait_assert_success().await;
+    test_process.assert_no_error_in_stderr().await;
+}
@@ -0,0 +1,55 @@
+#![cfg(target_os = "linux")]
+use std::{path::PathBuf, time::Duration};
+
+use rstest::rstest;
+
+mod common;
+pub use common::*;
+
+/// Path to the layer built for musl, e.g. with
+/// `RUSTFLAGS="-C target-feature=-crt-static" cargo build -p mirrord-layer --target
+/// x86_64-unknown-linux-musl`.
+fn musl_dylib_path() -> PathBuf {
+    let path = std::env::var("MIRRORD_TEST_MUSL_LIB")
+        .expect("MIRRORD_TEST_MUSL_LIB should point to the layer built for musl");
+    let path = PathBuf::from(path);
+    assert!(path.exists());
+    path
+}
+
+/// Verify that the layer built for musl hooks the file operations of an app linked against musl,
+/// like the apps built on Alpine.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+#[ignore = "requires the layer built for musl in MIRRORD_TEST_MUSL_LIB, and musl-gcc"]
+async fn musl_read_file() {
+    let _tracing = init_tracing();
+
+    let application = Application::DynamicApp(
+        format!(
+            "{}/tests/apps/musl_read_file/out.musl_test_app",
+            env!("CARGO_MANIFEST_DIR")
+        ),
+        vec![],
+    );
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(
+            &musl_dylib_path(),
+            vec![("MIRRORD_FILE_MODE", "read")],
+            None,
+        )
+        .await;
+
+    let fd = 1;
+    intproxy
+        .expect_file_open_for_reading("/app/test.txt", fd)
+        .await;
+    intproxy
+        .expect_single_file_read("hello from musl\n", fd)
+        .await;
+    intproxy.expect_file_close(fd).await;
+
+    test_process.wait_assert_success().await;
+    test_process.assert_stdout_contains("hello from musl").await;
+}
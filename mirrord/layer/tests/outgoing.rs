@@ -12,6 +12,7 @@ use mirrord_protocol::{
     ClientMessage, DaemonMessage,
     outgoing::{
         DaemonRead, LayerConnectV2, LayerWrite, SocketAddress,
+        icmp::{IcmpEchoRequest, IcmpEchoResponse},
         tcp::{DaemonTcpOutgoing, LayerTcpOutgoing},
         udp::{DaemonUdpOutgoing, LayerUdpOutgoing},
     },
@@ -89,6 +90,111 @@ async fn outgoing_udp(dylib_path: &Path) {
     test_process.wait_assert_success().await;
 }
 
+/// Test outgoing UDP without `connect`.
+/// Application, from a single socket bound to `0.0.0.0:0`, for each remote peer in
+/// [`RUST_OUTGOING_PEERS`]:
+/// 1. Sends some data with `sendto`
+/// 2. Expects the peer to send the same data back, and `recvfrom` to report the peer's address
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(15))]
+async fn outgoing_udp_sendto(dylib_path: &Path) {
+    let (mut test_process, mut intproxy) = Application::RustOutgoingUdpSendTo
+        .start_process_with_layer(dylib_path, vec![], None)
+        .await;
+
+    let peers = RUST_OUTGOING_PEERS
+        .split(',')
+        .map(|s| s.parse::<SocketAddr>().unwrap())
+        .collect::<Vec<_>>();
+
+    for (connection_id, peer) in peers.into_iter().enumerate() {
+        let connection_id = connection_id as u64;
+
+        // Every destination gets its own connection.
+        let (uid, addr) = intproxy.recv_udp_connect().await;
+        assert_eq!(addr, peer);
+        intproxy
+            .send_udp_connect_ok(
+                uid,
+                connection_id,
+                addr,
+                RUST_OUTGOING_LOCAL.parse().unwrap(),
+            )
+            .await;
+
+        let msg = intproxy.recv().await;
+        let ClientMessage::UdpOutgoing(LayerUdpOutgoing::Write(LayerWrite {
+            connection_id: response_connection_id,
+            bytes,
+        })) = msg
+        else {
+            panic!("Invalid message received from layer: {msg:?}");
+        };
+
+        assert_eq!(response_connection_id, connection_id);
+
+        intproxy
+            .send(DaemonMessage::UdpOutgoing(DaemonUdpOutgoing::Read(Ok(
+                DaemonRead {
+                    connection_id,
+                    bytes,
+                },
+            ))))
+            .await;
+    }
+
+    test_process.wait_assert_success().await;
+}
+
+/// Test ICMP echo through the agent.
+/// Application, from a single `SOCK_DGRAM` ICMP socket, for each remote peer in
+/// [`RUST_OUTGOING_PEERS`]:
+/// 1. Sends an echo request with `sendto`
+/// 2. Expects an echo reply with the same data, and `recvfrom` to report the peer's address
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(15))]
+async fn outgoing_icmp(dylib_path: &Path) {
+    let (mut test_process, mut intproxy) = Application::RustOutgoingIcmp
+        .start_process_with_layer(dylib_path, vec![("MIRRORD_OUTGOING_ICMP", "true")], None)
+        .await;
+
+    let peers = RUST_OUTGOING_PEERS
+        .split(',')
+        .map(|s| s.parse::<SocketAddr>().unwrap())
+        .collect::<Vec<_>>();
+
+    for peer in peers {
+        let msg = intproxy.recv().await;
+        let ClientMessage::IcmpEcho(IcmpEchoRequest {
+            destination,
+            identifier,
+            sequence,
+            payload,
+            ..
+        }) = msg
+        else {
+            panic!("Invalid message received from layer: {msg:?}");
+        };
+        assert_eq!(SocketAddr::new(destination.into(), peer.port()), peer);
+
+        let mut packet = vec![0, 0, 0, 0];
+        packet.extend_from_slice(&identifier.to_be_bytes());
+        packet.extend_from_slice(&sequence.to_be_bytes());
+        packet.extend_from_slice(&payload);
+
+        intproxy
+            .send(DaemonMessage::IcmpEcho(Ok(IcmpEchoResponse {
+                source: destination,
+                packet: packet.into(),
+            })))
+            .await;
+    }
+
+    test_process.wait_assert_success().await;
+}
+
 /// Test outgoing TCP.
 /// Application, for each remote peer in [`RUST_OUTGOING_PEERS`]:
 /// 1. Opens a TCP port at [`RUST_OUTGOING_LOCAL`]
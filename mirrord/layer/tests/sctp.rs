@@ -0,0 +1,32 @@
+#![cfg(target_family = "unix")]
+#![cfg(target_os = "linux")]
+#![feature(assert_matches)]
+use std::{path::Path, time::Duration};
+
+use rstest::rstest;
+
+mod common;
+pub use common::*;
+
+/// Verifies that SCTP sockets are not intercepted, and that the warning sent to the internal
+/// proxy does not break the session.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn sctp(dylib_path: &Path) {
+    let application = Application::Sctp;
+
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(dylib_path, Default::default(), None)
+        .await;
+
+    // The internal proxy handles the warning itself, the agent gets only the `readlink`.
+    intproxy.expect_read_link("/gatos/tigrado.txt").await;
+    assert_eq!(intproxy.try_recv().await, None);
+
+    test_process.wait_assert_success().await;
+    test_process
+        .assert_stdout_contains("test sctp: SUCCESS")
+        .await;
+    test_process.assert_no_error_in_stderr().await;
+}
@@ -0,0 +1,30 @@
+#![cfg(target_family = "unix")]
+#![cfg(target_os = "linux")]
+#![feature(assert_matches)]
+use std::{path::Path, time::Duration};
+
+use rstest::rstest;
+
+mod common;
+pub use common::*;
+
+/// Verifies that `SO_BINDTODEVICE` and `SO_ORIGINAL_DST` are emulated on managed sockets, and
+/// that the other socket options are set on the local socket.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn sockopts(dylib_path: &Path) {
+    let application = Application::Sockopts;
+
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer_and_port(dylib_path, Default::default(), None)
+        .await;
+
+    intproxy.send_connection_then_data("hello", 80).await;
+
+    test_process.wait_assert_success().await;
+    test_process
+        .assert_stdout_contains("test sockopts: SUCCESS")
+        .await;
+    test_process.assert_no_error_in_stderr().await;
+}
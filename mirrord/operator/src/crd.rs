@@ -113,6 +113,7 @@ impl TryFrom<TargetCrd> for TargetConfig {
         Ok(TargetConfig {
             path: Some(Target::try_from(crd.spec.target)?),
             namespace: crd.metadata.namespace,
+            ..Default::default()
         })
     }
 }
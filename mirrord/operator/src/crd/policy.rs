@@ -318,6 +318,14 @@ pub struct HttpFilterPolicy {
     /// When the user requests an `any_of` HTTP filter, all nested header filters must match this
     /// regex. At least one nested header filter is required.
     pub header_filter: Option<String>,
+
+    /// Header filter (regex) that is automatically added to every steal subscription.
+    ///
+    /// Unlike [`HttpFilterPolicy::header_filter`], the user does not have to specify it: the
+    /// operator passes it to the mirrord-agent, which informs the clients, and the clients combine
+    /// it with the user's own filter (`all_of`). The agent rejects steal subscriptions that do not
+    /// include it.
+    pub inject_header_filter: Option<String>,
 }
 
 #[test]
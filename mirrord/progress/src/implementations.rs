@@ -1,10 +1,10 @@
-use std::{collections::HashSet, time::Duration};
+use std::{collections::HashSet, net::SocketAddr, time::Duration};
 
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use serde::Serialize;
 use serde_json::Value;
 
-use crate::Progress;
+use crate::{ExecPhase, Progress};
 
 #[derive(Debug)]
 pub struct JsonProgress {
@@ -80,6 +80,30 @@ impl Progress for JsonProgress {
         message.print();
     }
 
+    fn phase(&self, phase: ExecPhase) {
+        let message = ProgressMessage::Phase {
+            phase,
+            percentage: phase.percentage(),
+        };
+        message.print();
+    }
+
+    fn remote_log(&self, container: &str, line: &str) {
+        let message = ProgressMessage::RemoteLog {
+            container: container.to_string(),
+            line: line.to_string(),
+        };
+        message.print();
+    }
+
+    fn listening(&self, name: &str, address: SocketAddr) {
+        let message = ProgressMessage::Listening {
+            name: name.to_string(),
+            address,
+        };
+        message.print();
+    }
+
     fn ide(&self, value: serde_json::Value) {
         if std::env::var("MIRRORD_PROGRESS_SUPPORT_IDE")
             .ok()
@@ -144,6 +168,14 @@ impl Progress for SimpleProgress {
         println!("{msg}");
     }
 
+    fn phase(&self, phase: ExecPhase) {
+        println!("[{:>3}%] {}", phase.percentage(), phase.description());
+    }
+
+    fn remote_log(&self, _: &str, line: &str) {
+        println!("[remote] {line}");
+    }
+
     fn print(&self, text: &str) {
         println!("{text}");
     }
@@ -244,6 +276,14 @@ impl Progress for SpinnerProgress {
         self.progress.set_message(formatted_message);
     }
 
+    fn remote_log(&self, _: &str, line: &str) {
+        self.print(&format!("[remote] {line}"));
+    }
+
+    fn update(&self, msg: &str) {
+        self.progress.set_message(msg.to_string());
+    }
+
     fn print(&self, msg: &str) {
         let _ = self.root_progress.println(msg);
     }
@@ -351,6 +391,21 @@ enum ProgressMessage {
     Info {
         message: String,
     },
+    /// The flow entered the next phase, see [`ExecPhase`].
+    Phase {
+        phase: ExecPhase,
+        percentage: u8,
+    },
+    /// A log line of the target container.
+    RemoteLog {
+        container: String,
+        line: String,
+    },
+    /// A local listener of the session got its address, see [`Progress::listening`].
+    Listening {
+        name: String,
+        address: SocketAddr,
+    },
     /// Messages that are passed to the IDE and shown to the user in notification boxes.
     IdeMessage {
         /// It's a generic json [`Value`].
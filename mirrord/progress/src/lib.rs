@@ -1,11 +1,15 @@
 #![deny(unused_crate_dependencies)]
 
+use std::net::SocketAddr;
+
 #[cfg(feature = "implementations")]
 pub mod implementations;
 pub mod messages;
+pub mod phase;
 
 #[cfg(feature = "implementations")]
 pub use implementations::*;
+pub use phase::ExecPhase;
 
 /// The environment variable name that is used
 /// to determine the mode of progress reporting
@@ -39,6 +43,27 @@ pub trait Progress: Sized + Send + Sync {
     /// You may use this to pass additional context to the IDE through the `value` object.
     fn ide(&self, _: serde_json::Value) {}
 
+    /// When the flow enters the next [`ExecPhase`].
+    ///
+    /// Only reported by the JSON and simple progress, the spinner already shows the subtasks of
+    /// each phase.
+    fn phase(&self, _: ExecPhase) {}
+
+    /// When you want to show a log line of the target container (the `remote_logs` config).
+    ///
+    /// `container` is the name of the container that produced the line.
+    fn remote_log(&self, _container: &str, _line: &str) {}
+
+    /// When a local listener of the session got its address, e.g. the internal proxy.
+    ///
+    /// `name` identifies the listener, so that IDEs can find the port they need.
+    fn listening(&self, _name: &str, _address: SocketAddr) {}
+
+    /// When you want to replace the text of the current task, e.g. to show how far it got.
+    ///
+    /// Only for `SpinnerProgress`.
+    fn update(&self, _: &str) {}
+
     /// When you want to print a message, cli only.
     fn print(&self, _: &str) {}
 
@@ -0,0 +1,87 @@
+//! Typed phases of the `mirrord exec` flow, reported with
+//! [`Progress::phase`](crate::Progress::phase).
+//!
+//! Unlike the free-form task messages, phases have a stable identifier and an estimated completion
+//! percentage, so that the IDE extensions can render a progress bar instead of parsing the text.
+
+/// A phase of preparing the session for the user application, in the order in which they happen.
+///
+/// Not every flow goes through every phase, e.g. [`ExecPhase::CreatingAgent`] is skipped when the
+/// session is started with the mirrord operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "implementations", derive(serde::Serialize))]
+#[cfg_attr(feature = "implementations", serde(rename_all = "snake_case"))]
+pub enum ExecPhase {
+    /// Looking for the mirrord operator in the cluster.
+    CheckingOperator,
+    /// Starting the session with the mirrord operator.
+    StartingSession,
+    /// Creating the mirrord-agent pod or container.
+    CreatingAgent,
+    /// Waiting for the mirrord-agent to become ready.
+    WaitingForAgent,
+    /// Establishing the connection with the mirrord-agent.
+    ConnectingToAgent,
+    /// Fetching the remote environment variables.
+    FetchingEnvironment,
+    /// Spawning the internal proxy.
+    StartingInternalProxy,
+    /// The user application is about to be started.
+    Ready,
+}
+
+impl ExecPhase {
+    /// Estimated completion of the whole flow when this phase starts.
+    pub fn percentage(self) -> u8 {
+        match self {
+            Self::CheckingOperator => 0,
+            Self::StartingSession => 20,
+            Self::CreatingAgent => 20,
+            Self::WaitingForAgent => 35,
+            Self::ConnectingToAgent => 65,
+            Self::FetchingEnvironment => 75,
+            Self::StartingInternalProxy => 85,
+            Self::Ready => 100,
+        }
+    }
+
+    /// Human-readable description of the phase.
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::CheckingOperator => "checking operator",
+            Self::StartingSession => "starting operator session",
+            Self::CreatingAgent => "creating agent",
+            Self::WaitingForAgent => "waiting for agent",
+            Self::ConnectingToAgent => "connecting to agent",
+            Self::FetchingEnvironment => "fetching remote environment",
+            Self::StartingInternalProxy => "starting internal proxy",
+            Self::Ready => "ready",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ExecPhase;
+
+    #[test]
+    fn percentage_follows_phase_order() {
+        let phases = [
+            ExecPhase::CheckingOperator,
+            ExecPhase::StartingSession,
+            ExecPhase::CreatingAgent,
+            ExecPhase::WaitingForAgent,
+            ExecPhase::ConnectingToAgent,
+            ExecPhase::FetchingEnvironment,
+            ExecPhase::StartingInternalProxy,
+            ExecPhase::Ready,
+        ];
+
+        assert!(phases.is_sorted());
+        assert!(
+            phases
+                .windows(2)
+                .all(|pair| pair[0].percentage() <= pair[1].percentage())
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! Throughput of the [`ProtocolCodec`] on the messages that dominate the traffic
+//! between the agent and the clients: raw TCP data and stolen HTTP requests and responses.
+
+use std::{
+    hint::black_box,
+    io,
+    net::{Ipv4Addr, SocketAddr},
+};
+
+use actix_codec::{Decoder, Encoder};
+use bytes::BytesMut;
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use hyper::{
+    Method, StatusCode, Version,
+    header::{HeaderMap, HeaderName, HeaderValue},
+};
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, Payload, ProtocolCodec,
+    tcp::{
+        ChunkedRequest, ChunkedRequestStartV2, DaemonTcp, HttpRequestMetadata, HttpResponse,
+        IncomingTrafficTransportType, InternalHttpBody, InternalHttpBodyFrame, InternalHttpBodyNew,
+        InternalHttpRequest, InternalHttpResponse, LayerTcpSteal, TcpData,
+    },
+};
+
+/// Sizes of the [`TcpData`] payloads.
+const DATA_SIZES: [usize; 3] = [64, 4 * 1024, 64 * 1024];
+
+/// Size of the HTTP bodies.
+const BODY_SIZE: usize = 2 * 1024;
+
+fn headers(entries: &[(&'static str, &'static str)]) -> HeaderMap {
+    entries
+        .iter()
+        .map(|&(name, value)| {
+            (
+                HeaderName::from_static(name),
+                HeaderValue::from_static(value),
+            )
+        })
+        .collect()
+}
+
+fn tcp_data(size: usize) -> DaemonMessage {
+    DaemonMessage::TcpSteal(DaemonTcp::Data(TcpData {
+        connection_id: 1,
+        bytes: Payload::from(vec![7_u8; size]),
+    }))
+}
+
+fn http_request() -> DaemonMessage {
+    let request = InternalHttpRequest {
+        method: Method::POST,
+        uri: "/api/v2/orders/8812/items?expand=product&limit=50"
+            .parse()
+            .unwrap(),
+        headers: headers(&[
+            ("host", "api.example.com"),
+            (
+                "user-agent",
+                "Mozilla/5.0 (X11; Linux x86_64) Chrome/126.0.0.0",
+            ),
+            ("accept", "application/json, text/plain, */*"),
+            ("accept-encoding", "gzip, deflate, br"),
+            (
+                "authorization",
+                "Bearer eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiIxIn0.c2ln",
+            ),
+            ("content-type", "application/json"),
+            ("cookie", "session=4f3c2a1b; theme=dark"),
+            ("x-forwarded-for", "203.0.113.7, 10.0.12.4"),
+            ("x-request-id", "7b0c5bde-3b1a-4d2e-9f7a-52a4c6f1e0d9"),
+            (
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            ),
+            ("x-user", "alice"),
+        ]),
+        version: Version::HTTP_11,
+        body: InternalHttpBodyNew {
+            frames: vec![InternalHttpBodyFrame::Data(Payload::from(vec![
+                b'{';
+                BODY_SIZE
+            ]))],
+            is_last: true,
+        },
+    };
+
+    DaemonMessage::TcpSteal(DaemonTcp::HttpRequestChunked(ChunkedRequest::StartV2(
+        ChunkedRequestStartV2 {
+            connection_id: 1,
+            request_id: 0,
+            request,
+            metadata: HttpRequestMetadata::V1 {
+                source: SocketAddr::from((Ipv4Addr::new(10, 0, 12, 4), 51234)),
+                destination: SocketAddr::from((Ipv4Addr::new(10, 0, 3, 17), 8080)),
+            },
+            transport: IncomingTrafficTransportType::Tcp,
+        },
+    )))
+}
+
+fn http_response() -> ClientMessage {
+    ClientMessage::TcpSteal(LayerTcpSteal::HttpResponseFramed(HttpResponse {
+        port: 8080,
+        connection_id: 1,
+        request_id: 0,
+        internal_response: InternalHttpResponse {
+            status: StatusCode::OK,
+            version: Version::HTTP_11,
+            headers: headers(&[
+                ("content-type", "application/json"),
+                ("cache-control", "no-store"),
+                ("date", "Fri, 16 Oct 2026 12:00:00 GMT"),
+                ("x-request-id", "7b0c5bde-3b1a-4d2e-9f7a-52a4c6f1e0d9"),
+            ]),
+            body: InternalHttpBody(
+                [InternalHttpBodyFrame::Data(Payload::from(vec![
+                    b'}';
+                    BODY_SIZE
+                ]))]
+                .into(),
+            ),
+        },
+    }))
+}
+
+/// Benchmarks encoding of the given messages with `ProtocolCodec<I, O>`, and decoding with
+/// `ProtocolCodec<O, I>` on the other side.
+fn bench_messages<I, O>(c: &mut Criterion, group: &str, messages: Vec<(String, O)>)
+where
+    O: Clone,
+    ProtocolCodec<I, O>: Encoder<O, Error = io::Error>,
+    ProtocolCodec<O, I>: Decoder<Item = O, Error = io::Error>,
+{
+    let mut group = c.benchmark_group(group);
+
+    for (name, message) in messages {
+        let mut encoded = BytesMut::new();
+        ProtocolCodec::<I, O>::default()
+            .encode(message.clone(), &mut encoded)
+            .unwrap();
+        group.throughput(Throughput::Bytes(encoded.len() as u64));
+
+        group.bench_function(format!("{name}/encode"), |b| {
+            let mut codec = ProtocolCodec::<I, O>::default();
+            let mut buffer = BytesMut::new();
+            b.iter_batched(
+                || message.clone(),
+                |message| {
+                    buffer.clear();
+                    codec.encode(message, &mut buffer).unwrap();
+                    black_box(&buffer);
+                },
+                BatchSize::SmallInput,
+            )
+        });
+
+        group.bench_function(format!("{name}/decode"), |b| {
+            let mut codec = ProtocolCodec::<O, I>::default();
+            b.iter_batched(
+                || encoded.clone(),
+                |mut encoded| codec.decode(&mut encoded).unwrap().unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+fn tcp(c: &mut Criterion) {
+    let messages = DATA_SIZES
+        .into_iter()
+        .map(|size| (format!("data_{size}"), tcp_data(size)))
+        .collect();
+    bench_messages::<ClientMessage, _>(c, "codec_tcp", messages);
+}
+
+fn http(c: &mut Criterion) {
+    bench_messages::<ClientMessage, _>(
+        c,
+        "codec_http_request",
+        vec![("chunked".into(), http_request())],
+    );
+    bench_messages::<DaemonMessage, _>(
+        c,
+        "codec_http_response",
+        vec![("framed".into(), http_response())],
+    );
+}
+
+criterion_group!(codec, tcp, http);
+criterion_main!(codec);
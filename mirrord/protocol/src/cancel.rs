@@ -0,0 +1,39 @@
+//! Cancellation of the client's in-flight requests, see [`CancelRequest`].
+
+use std::sync::LazyLock;
+
+use bincode::{Decode, Encode};
+use semver::VersionReq;
+
+use crate::{ConnectionId, RequestId};
+
+/// Minimal mirrord-protocol version that allows for [`ClientMessage::Cancel`].
+///
+/// [`ClientMessage::Cancel`]: crate::ClientMessage::Cancel
+pub static CANCEL_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.50.0".parse().expect("Bad Identifier"));
+
+/// Identifies an in-flight request that the client is no longer interested in.
+///
+/// Sent when the user application gives up on the request, so that the agent can stop working on
+/// it and free its resources, instead of completing it for nobody. Cancelling a request that is
+/// already finished (or unknown to the agent) has no effect.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub enum CancelRequest {
+    /// [`FileRequest::ReadAhead`] of the given file.
+    ///
+    /// The agent stops reading the file, and ends the series of
+    /// [`ReadAheadChunk`](crate::file::ReadAheadChunk)s early, with an empty chunk marked as
+    /// [`last`](crate::file::ReadAheadChunk::last).
+    ///
+    /// [`FileRequest::ReadAhead`]: crate::FileRequest::ReadAhead
+    ReadAhead { remote_fd: u64 },
+    /// Stolen HTTP request.
+    ///
+    /// The agent stops sending the request body to the client, and drops the request. If the
+    /// response was not yet sent in whole, the connection with the HTTP client is closed.
+    HttpRequest {
+        connection_id: ConnectionId,
+        request_id: RequestId,
+    },
+}
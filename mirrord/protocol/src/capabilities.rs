@@ -0,0 +1,95 @@
+//! Features that the agent supports, sent to the client so that it can tell the user about
+//! unsupported configurations before the session starts.
+
+use std::sync::LazyLock;
+
+use bincode::{Decode, Encode};
+use semver::{Version, VersionReq};
+
+use crate::{outgoing::icmp::ICMP_ECHO_VERSION, tcp::MIRROR_HTTP_FILTER_VERSION};
+
+/// Minimal mirrord-protocol version that allows [`DaemonMessage::Capabilities`].
+///
+/// [`DaemonMessage::Capabilities`]: crate::DaemonMessage::Capabilities
+pub static AGENT_CAPABILITIES_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.39.0".parse().expect("Bad Identifier"));
+
+/// Features supported by the agent.
+///
+/// Sent to the client right after
+/// [`DaemonMessage::SwitchProtocolVersionResponse`](crate::DaemonMessage::SwitchProtocolVersionResponse),
+/// before any of its requests is handled.
+///
+/// This describes what the agent build can do. Features that are disabled at runtime (e.g.
+/// because the agent runs in read-only mode) are reported separately, with
+/// [`DisabledFeatures`](crate::DisabledFeatures).
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct AgentCapabilities {
+    /// Incoming traffic can be stolen.
+    pub steal: bool,
+    /// Stolen traffic can be filtered with an HTTP filter.
+    pub http_filter: bool,
+    /// Mirrored traffic can be filtered with an HTTP filter.
+    pub mirror_http_filter: bool,
+    /// Outgoing TCP connections can be made from the target.
+    pub outgoing_tcp: bool,
+    /// Outgoing UDP traffic can be sent from the target.
+    pub outgoing_udp: bool,
+    /// DNS queries can be resolved in the target.
+    pub dns: bool,
+    /// Files can be accessed in the target.
+    pub fs: bool,
+    /// ICMP echo requests can be sent from the target.
+    pub icmp_echo: bool,
+    /// The target can be paused.
+    pub pause: bool,
+}
+
+impl AgentCapabilities {
+    /// Capabilities of the agent built from this version of the protocol.
+    pub const CURRENT: Self = Self {
+        steal: true,
+        http_filter: true,
+        mirror_http_filter: true,
+        outgoing_tcp: true,
+        outgoing_udp: true,
+        dns: true,
+        fs: true,
+        icmp_echo: true,
+        pause: false,
+    };
+
+    /// Capabilities of an agent that does not send [`DaemonMessage::Capabilities`], inferred from
+    /// the protocol version it settled on.
+    ///
+    /// [`DaemonMessage::Capabilities`]: crate::DaemonMessage::Capabilities
+    pub fn from_protocol_version(version: &Version) -> Self {
+        if AGENT_CAPABILITIES_VERSION.matches(version) {
+            return Self::CURRENT;
+        }
+
+        Self {
+            mirror_http_filter: MIRROR_HTTP_FILTER_VERSION.matches(version),
+            icmp_echo: ICMP_ECHO_VERSION.matches(version),
+            ..Self::CURRENT
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Not;
+
+    use super::*;
+
+    #[test]
+    fn legacy_capabilities() {
+        let legacy = AgentCapabilities::from_protocol_version(&"1.2.0".parse().unwrap());
+        assert!(legacy.steal);
+        assert!(legacy.mirror_http_filter.not());
+        assert!(legacy.icmp_echo.not());
+
+        let current = AgentCapabilities::from_protocol_version(&crate::VERSION);
+        assert_eq!(current, AgentCapabilities::CURRENT);
+    }
+}
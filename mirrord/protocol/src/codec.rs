@@ -21,16 +21,24 @@ use semver::VersionReq;
 
 use crate::{
     ResponseError,
+    cancel::CancelRequest,
+    capabilities::AgentCapabilities,
     dns::{
         GetAddrInfoRequest, GetAddrInfoRequestV2, GetAddrInfoResponse, ReverseDnsLookupRequest,
         ReverseDnsLookupResponse,
     },
     file::*,
+    interfaces::{GetInterfacesRequest, GetInterfacesResult},
+    listening_ports::{GetListeningPortsRequest, GetListeningPortsResult},
     outgoing::{
+        icmp::{IcmpEchoRequest, IcmpEchoResult},
         tcp::{DaemonTcpOutgoing, LayerTcpOutgoing},
         udp::{DaemonUdpOutgoing, LayerUdpOutgoing},
     },
-    tcp::{DaemonTcp, LayerTcp, LayerTcpSteal},
+    quota::SessionQuotas,
+    resources::{GetResourceUsageRequest, GetResourceUsageResult},
+    shutdown::GoingDown,
+    tcp::{DaemonTcp, HttpFilter, LayerTcp, LayerTcpSteal},
     vpn::{ClientVpn, ServerVpn},
 };
 
@@ -68,6 +76,57 @@ impl LogMessage {
     }
 }
 
+/// Minimal mirrord-protocol version that allows [`ClientMessage::ReadyForAgentLogs`] and
+/// [`DaemonMessage::LogEvent`].
+pub static AGENT_LOG_EVENTS_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.26.0".parse().expect("Bad Identifier"));
+
+/// A log event that was emitted by the agent itself, forwarded to the client so users don't
+/// have to dig through the agent pod logs.
+///
+/// Unlike [`LogMessage`], these are not meant to be shown to the user by default. The agent sends
+/// them only after [`ClientMessage::ReadyForAgentLogs`], e.g. with `--agent-logs`, and the
+/// internal proxy prints them to stderr and stores them in its log file.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct LogEvent {
+    pub level: LogLevel,
+    /// The `tracing` target of the event, e.g. `mirrord_agent::steal::connections`.
+    pub target: String,
+    pub message: String,
+}
+
+/// Minimal mirrord-protocol version that allows [`DaemonMessage::DisabledFeatures`].
+pub static DISABLED_FEATURES_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.30.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`DaemonMessage::MandatoryHttpFilter`].
+pub static MANDATORY_HTTP_FILTER_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.32.0".parse().expect("Bad Identifier"));
+
+/// Features that the agent refuses to serve regardless of the client's config, e.g. because an
+/// admin put it in read-only mode.
+///
+/// Requests for the disabled features fail with errors that the layer already handles, e.g.
+/// [`ResponseError::OpenLocal`](crate::ResponseError::OpenLocal) for files opened for writing.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct DisabledFeatures {
+    /// Files cannot be modified.
+    pub fs_write: bool,
+    /// Incoming traffic cannot be stolen, only mirrored.
+    pub steal: bool,
+    /// Outgoing connections cannot be made from the target.
+    pub outgoing: bool,
+}
+
+impl DisabledFeatures {
+    /// All of the features that modify the target or its traffic.
+    pub const READ_ONLY: Self = Self {
+        fs_write: true,
+        steal: true,
+        outgoing: true,
+    };
+}
+
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub struct GetEnvVarsRequest {
     pub env_vars_filter: HashSet<String>,
@@ -116,6 +175,18 @@ pub enum FileRequest {
     Futimens(FutimensRequest),
     Fchown(FchownRequest),
     Fchmod(FchmodRequest),
+    /// Only supported since [`FSYNC_VERSION`](crate::file::FSYNC_VERSION).
+    Fsync(FsyncRequest),
+    /// Only supported since [`FILE_LOCK_VERSION`](crate::file::FILE_LOCK_VERSION).
+    FileLock(FileLockRequest),
+    /// Only supported since [`GET_CWD_VERSION`](crate::file::GET_CWD_VERSION).
+    GetCwd(GetCwdRequest),
+
+    /// Prefetching of sequentially read files.
+    ///
+    /// Like [`FileRequest::ReadDirBatch`], this one is intproxy only.
+    /// Only supported since [`READ_AHEAD_VERSION`](crate::file::READ_AHEAD_VERSION).
+    ReadAhead(ReadAheadRequest),
 }
 
 /// Minimal mirrord-protocol version that allows `ClientMessage::ReadyForLogs` message.
@@ -126,10 +197,10 @@ pub static CLIENT_READY_FOR_LOGS: LazyLock<VersionReq> =
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub enum ClientMessage {
     Close,
-    /// TCP sniffer message.
+    /// TCP mirror message.
     ///
     /// These are the messages used by the `mirror` feature, and handled by the
-    /// `TcpSnifferApi` in the agent.
+    /// `TcpMirrorApi` in the agent.
     Tcp(LayerTcp),
 
     /// TCP stealer message.
@@ -166,6 +237,39 @@ pub enum ClientMessage {
     ///
     /// Sent by the operator when enforcing hostname-based outgoing network policies.
     ReverseDnsLookup(ReverseDnsLookupRequest),
+    /// Asks the agent to start forwarding its own logs as [`DaemonMessage::LogEvent`]s.
+    ///
+    /// Supported from [`AGENT_LOG_EVENTS_VERSION`].
+    ReadyForAgentLogs,
+    /// Asks the agent to ping the given address, see [`IcmpEchoRequest`].
+    ///
+    /// Supported from [`ICMP_ECHO_VERSION`](crate::outgoing::icmp::ICMP_ECHO_VERSION).
+    IcmpEcho(IcmpEchoRequest),
+    /// Asks the agent for the network interfaces of the target, see [`GetInterfacesRequest`].
+    ///
+    /// Supported from
+    /// [`GET_INTERFACES_VERSION`](crate::interfaces::GET_INTERFACES_VERSION).
+    GetInterfaces(GetInterfacesRequest),
+    /// Asks the agent for the resource usage of the target, see [`GetResourceUsageRequest`].
+    ///
+    /// Supported from
+    /// [`RESOURCE_USAGE_VERSION`](crate::resources::RESOURCE_USAGE_VERSION).
+    GetResourceUsage(GetResourceUsageRequest),
+    /// Acknowledges [`DaemonMessage::GoingDown`], sent when the client finished handling its
+    /// in-flight stolen traffic.
+    ///
+    /// Supported from [`GOING_DOWN_VERSION`](crate::shutdown::GOING_DOWN_VERSION).
+    GoingDownAck,
+    /// Cancels an in-flight request, see [`CancelRequest`].
+    ///
+    /// Supported from [`CANCEL_VERSION`](crate::cancel::CANCEL_VERSION).
+    Cancel(CancelRequest),
+    /// Asks the agent for the ports the target is listening on, see
+    /// [`GetListeningPortsRequest`].
+    ///
+    /// Supported from
+    /// [`LISTENING_PORTS_VERSION`](crate::listening_ports::LISTENING_PORTS_VERSION).
+    GetListeningPorts(GetListeningPortsRequest),
 }
 
 /// Type alias for `Result`s that should be returned from mirrord-agent to mirrord-layer.
@@ -196,6 +300,11 @@ pub enum FileResponse {
     Futimens(RemoteResult<()>),
     Fchown(RemoteResult<()>),
     Fchmod(RemoteResult<()>),
+    Fsync(RemoteResult<()>),
+    FileLock(RemoteResult<()>),
+    GetCwd(RemoteResult<GetCwdResponse>),
+    /// One of the responses to a [`FileRequest::ReadAhead`].
+    ReadAhead(RemoteResult<ReadAheadChunk>),
 }
 
 /// `-agent` --> `-layer` messages.
@@ -230,6 +339,58 @@ pub enum DaemonMessage {
     ///
     /// Sent by the agent in response to [`ClientMessage::ReverseDnsLookup`].
     ReverseDnsLookup(RemoteResult<ReverseDnsLookupResponse>),
+    /// Log event emitted by the agent, sent only after [`ClientMessage::ReadyForAgentLogs`].
+    LogEvent(LogEvent),
+    /// Features force-disabled in the agent, sent right after
+    /// [`DaemonMessage::SwitchProtocolVersionResponse`].
+    ///
+    /// Supported from [`DISABLED_FEATURES_VERSION`].
+    DisabledFeatures(DisabledFeatures),
+    /// HTTP filter that must be a part of every steal subscription, e.g. because a policy allows
+    /// the user to steal only the requests meant for them. Sent right after
+    /// [`DaemonMessage::SwitchProtocolVersionResponse`].
+    ///
+    /// The client should combine it with its own filters, see
+    /// [`StealType::with_http_filter`](crate::tcp::StealType::with_http_filter).
+    /// Steal subscriptions that do not include it are rejected with
+    /// [`ResponseError::ForbiddenWithReason`].
+    ///
+    /// Supported from [`MANDATORY_HTTP_FILTER_VERSION`].
+    MandatoryHttpFilter(HttpFilter),
+    /// Response to [`ClientMessage::IcmpEcho`].
+    ///
+    /// Supported from [`ICMP_ECHO_VERSION`](crate::outgoing::icmp::ICMP_ECHO_VERSION).
+    IcmpEcho(IcmpEchoResult),
+    /// Quotas of this session, sent right after
+    /// [`DaemonMessage::SwitchProtocolVersionResponse`] when the operator set any.
+    ///
+    /// Supported from [`SESSION_QUOTAS_VERSION`](crate::quota::SESSION_QUOTAS_VERSION).
+    SessionQuotas(SessionQuotas),
+    /// Features supported by the agent, sent right after
+    /// [`DaemonMessage::SwitchProtocolVersionResponse`].
+    ///
+    /// Supported from
+    /// [`AGENT_CAPABILITIES_VERSION`](crate::capabilities::AGENT_CAPABILITIES_VERSION).
+    Capabilities(AgentCapabilities),
+    /// Response to [`ClientMessage::GetInterfaces`].
+    ///
+    /// Supported from
+    /// [`GET_INTERFACES_VERSION`](crate::interfaces::GET_INTERFACES_VERSION).
+    GetInterfaces(GetInterfacesResult),
+    /// Response to [`ClientMessage::GetResourceUsage`].
+    ///
+    /// Supported from
+    /// [`RESOURCE_USAGE_VERSION`](crate::resources::RESOURCE_USAGE_VERSION).
+    ResourceUsage(GetResourceUsageResult),
+    /// The agent is about to exit, see [`GoingDown`].
+    ///
+    /// Supported from [`GOING_DOWN_VERSION`](crate::shutdown::GOING_DOWN_VERSION).
+    GoingDown(GoingDown),
+    /// Response to [`ClientMessage::GetListeningPorts`].
+    ///
+    /// Supported from
+    /// [`LISTENING_PORTS_VERSION`](crate::listening_ports::LISTENING_PORTS_VERSION).
+    ListeningPorts(GetListeningPortsResult),
 }
 
 #[derive(Encode, Decode, PartialEq, Eq, Clone, From, Into, Deref)]
@@ -13,6 +13,7 @@ use thiserror::Error;
 use crate::{
     Port,
     outgoing::SocketAddress,
+    quota::QuotaExceeded,
     tcp::{Filter, HttpFilter, StealType},
 };
 
@@ -69,6 +70,43 @@ pub enum ResponseError {
         policy_name: Option<String>,
         reason: String,
     },
+
+    /// Supported from [`SESSION_QUOTAS_VERSION`](crate::quota::SESSION_QUOTAS_VERSION).
+    #[error("{blocked_action} is not allowed, {quota}")]
+    QuotaExceeded {
+        blocked_action: BlockedAction,
+        quota: QuotaExceeded,
+    },
+}
+
+impl ResponseError {
+    /// Returns the stable [`ErrorCode`] that identifies this error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::IdsExhausted(_) => ErrorCode::IdsExhausted,
+            Self::NotFound(_) => ErrorCode::NotFound,
+            Self::NotDirectory(_) => ErrorCode::NotDirectory,
+            Self::NotFile(_) => ErrorCode::NotFile,
+            Self::RemoteIO(_) => ErrorCode::RemoteIO,
+            Self::DnsLookup(_) => ErrorCode::DnsLookup,
+            Self::Remote(remote) => remote.code(),
+            Self::PortAlreadyStolen(_) => ErrorCode::PortAlreadyStolen,
+            Self::NotImplemented => ErrorCode::NotImplemented,
+            Self::Forbidden { .. } | Self::ForbiddenWithReason { .. } => ErrorCode::Forbidden,
+            Self::StripPrefix(_) => ErrorCode::StripPrefix,
+            Self::OpenLocal => ErrorCode::OpenLocal,
+            Self::QuotaExceeded { .. } => ErrorCode::QuotaExceeded,
+        }
+    }
+
+    /// Wraps this error in a [`CodedError`], which displays it as `error[M0123]: <message>`,
+    /// followed by a link to the documentation of the code.
+    ///
+    /// Only for the top-level render of the error, errors that wrap a [`ResponseError`] display
+    /// it with its plain message.
+    pub fn with_code(&self) -> CodedError<'_, Self> {
+        self.code().wrap(self)
+    }
 }
 
 impl From<StripPrefixError> for ResponseError {
@@ -124,6 +162,24 @@ impl fmt::Display for BlockedAction {
                     "Stealing traffic from port {port} with http request filter: {filter}"
                 )
             }
+            BlockedAction::Steal(StealType::FilteredSni(port, filter)) => {
+                write!(
+                    f,
+                    "Stealing traffic from port {port} with TLS server name filter: {filter}"
+                )
+            }
+            BlockedAction::Steal(StealType::FilteredPostgres(port, filter)) => {
+                write!(
+                    f,
+                    "Stealing traffic from port {port} with PostgreSQL filter: {filter}"
+                )
+            }
+            BlockedAction::Steal(StealType::FilteredRedis(port, filter)) => {
+                write!(
+                    f,
+                    "Stealing traffic from port {port} with Redis filter: {filter}"
+                )
+            }
             BlockedAction::Mirror(port) => {
                 write!(f, "Mirroring traffic from port {port}")
             }
@@ -160,12 +216,116 @@ pub enum RemoteError {
     BadHttpFilterExRegex(HttpFilter, String),
 }
 
+impl RemoteError {
+    /// Returns the stable [`ErrorCode`] that identifies this error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::NameserverNotFound => ErrorCode::NameserverNotFound,
+            Self::AddressParsing(_) => ErrorCode::AddressParsing,
+            Self::InvalidAddress(_) => ErrorCode::InvalidAddress,
+            Self::ConnectTimedOut(_) => ErrorCode::ConnectTimedOut,
+            Self::BadHttpFilterRegex(..) | Self::BadHttpFilterExRegex(..) => {
+                ErrorCode::BadHttpFilterRegex
+            }
+        }
+    }
+}
+
 impl From<AddrParseError> for RemoteError {
     fn from(fail: AddrParseError) -> Self {
         Self::AddressParsing(fail.to_string())
     }
 }
 
+/// Base URL of the documentation page that describes every [`ErrorCode`], `ERROR_CODES.md` in
+/// this crate.
+pub const ERROR_CODES_DOCS_URL: &str =
+    "https://github.com/metalbear-co/mirrord/blob/latest/mirrord/protocol/ERROR_CODES.md";
+
+/// Stable identifiers of errors produced by the mirrord components.
+///
+/// Unlike the messages of [`ResponseError`] and [`RemoteError`], which may change between
+/// releases, these codes are a stable contract that IDE plugins and other tooling can react to.
+///
+/// The codes are **not** sent over the wire, they are derived locally from the errors with
+/// [`ResponseError::code`] and [`RemoteError::code`], so adding a new code does not affect
+/// protocol compatibility. Existing codes must never be renumbered or reused.
+///
+/// Codes are grouped by ranges:
+/// - `M00xx` - [`ResponseError`] variants;
+/// - `M01xx` - [`RemoteError`] variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(u16)]
+pub enum ErrorCode {
+    IdsExhausted = 1,
+    NotFound = 2,
+    NotDirectory = 3,
+    NotFile = 4,
+    RemoteIO = 5,
+    DnsLookup = 6,
+    PortAlreadyStolen = 7,
+    NotImplemented = 8,
+    Forbidden = 9,
+    StripPrefix = 10,
+    OpenLocal = 11,
+    QuotaExceeded = 12,
+
+    NameserverNotFound = 101,
+    AddressParsing = 102,
+    InvalidAddress = 103,
+    ConnectTimedOut = 104,
+    BadHttpFilterRegex = 105,
+}
+
+impl ErrorCode {
+    /// Numeric value of this code, e.g. `105` for `M0105`.
+    pub const fn as_u16(self) -> u16 {
+        self as u16
+    }
+
+    /// Link to the documentation of this code.
+    pub fn docs_url(self) -> String {
+        format!("{ERROR_CODES_DOCS_URL}#{}", self.to_string().to_lowercase())
+    }
+
+    /// Wraps `error` in a [`CodedError`] with this code.
+    ///
+    /// For errors that wrap an error with this code, e.g. a [`ResponseError`], so that the code
+    /// is displayed in front of their whole message.
+    pub fn wrap<E>(self, error: &E) -> CodedError<'_, E> {
+        CodedError { code: self, error }
+    }
+}
+
+/// Displays as `M0123`.
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "M{:04}", self.as_u16())
+    }
+}
+
+/// Displays the wrapped error as `error[M0123]: <message>`, followed by a link to the
+/// documentation of the [`ErrorCode`].
+///
+/// Created with [`ResponseError::with_code`] or [`ErrorCode::wrap`].
+#[derive(Debug)]
+pub struct CodedError<'a, E> {
+    pub code: ErrorCode,
+    pub error: &'a E,
+}
+
+impl<E: fmt::Display> fmt::Display for CodedError<'_, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "error[{}]: {}\n  see: {}",
+            self.code,
+            self.error,
+            self.code.docs_url()
+        )
+    }
+}
+
 /// Our internal version of Rust's `std::io::Error` that can be passed between mirrord-layer and
 /// mirrord-agent.
 ///
@@ -341,3 +501,58 @@ impl From<io::ErrorKind> for ErrorKindInternal {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_code_format() {
+        let error = ResponseError::PortAlreadyStolen(80);
+        assert_eq!(error.code().to_string(), "M0007");
+
+        let error = ResponseError::Remote(RemoteError::NameserverNotFound);
+        assert_eq!(error.code().to_string(), "M0101");
+        assert_eq!(
+            error.code().docs_url(),
+            format!("{ERROR_CODES_DOCS_URL}#m0101")
+        );
+
+        let rendered = error.with_code().to_string();
+        assert!(
+            rendered.starts_with("error[M0101]: Remote operation failed with"),
+            "{rendered}"
+        );
+    }
+
+    /// Every code needs a section in `ERROR_CODES.md`, which [`ErrorCode::docs_url`] links to.
+    #[test]
+    fn error_codes_are_documented() {
+        let docs = include_str!("../ERROR_CODES.md");
+
+        for code in [
+            ErrorCode::IdsExhausted,
+            ErrorCode::NotFound,
+            ErrorCode::NotDirectory,
+            ErrorCode::NotFile,
+            ErrorCode::RemoteIO,
+            ErrorCode::DnsLookup,
+            ErrorCode::PortAlreadyStolen,
+            ErrorCode::NotImplemented,
+            ErrorCode::Forbidden,
+            ErrorCode::StripPrefix,
+            ErrorCode::OpenLocal,
+            ErrorCode::QuotaExceeded,
+            ErrorCode::NameserverNotFound,
+            ErrorCode::AddressParsing,
+            ErrorCode::InvalidAddress,
+            ErrorCode::ConnectTimedOut,
+            ErrorCode::BadHttpFilterRegex,
+        ] {
+            assert!(
+                docs.contains(&format!("\n## {code}\n")),
+                "{code} is not documented"
+            );
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Message families that are still experimental, see [`ExperimentalFeature`].
+
+use std::{ops::Not, sync::LazyLock};
+
+use semver::{Version, VersionReq};
+
+use crate::tcp::{HTTP_CORRELATION_ID_VERSION, PORT_PAUSE_VERSION};
+
+/// Matches no version, used for the experimental families when this crate is built without the
+/// `experimental` feature.
+static NEVER: LazyLock<VersionReq> = LazyLock::new(|| "<0.0.0".parse().expect("Bad Identifier"));
+
+/// Families of messages whose shape or semantics can still change between minor versions of the
+/// protocol.
+///
+/// Like the stable message families, each of them is gated by a minimal protocol version of the
+/// peer. Additionally, they're enabled only when this crate is built with the `experimental`
+/// feature. Without it, [`ExperimentalFeature::version_req`] matches no version, so tooling that
+/// depends only on the stable protocol never sends these messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ExperimentalFeature {
+    /// [`LayerTcp::PortPause`](crate::tcp::LayerTcp::PortPause) and
+    /// [`LayerTcpSteal::PortPause`](crate::tcp::LayerTcpSteal::PortPause), with their `Resume`
+    /// counterparts.
+    PortPause,
+    /// [`HttpRequestMetadata::V2`](crate::tcp::HttpRequestMetadata::V2).
+    HttpCorrelationId,
+}
+
+impl ExperimentalFeature {
+    /// Whether this crate was built with the `experimental` feature.
+    pub const ENABLED: bool = cfg!(feature = "experimental");
+
+    /// Minimal protocol version of the peer that allows for using this feature.
+    ///
+    /// Matches no version when [`ExperimentalFeature::ENABLED`] is `false`.
+    pub fn version_req(self) -> &'static VersionReq {
+        if Self::ENABLED.not() {
+            return &NEVER;
+        }
+
+        match self {
+            Self::PortPause => &PORT_PAUSE_VERSION,
+            Self::HttpCorrelationId => &HTTP_CORRELATION_ID_VERSION,
+        }
+    }
+
+    /// Whether this feature can be used with a peer using the given protocol version.
+    pub fn is_supported(self, version: &Version) -> bool {
+        self.version_req().matches(version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gated_by_version_and_cargo_feature() {
+        let old = "1.42.0".parse().unwrap();
+        assert!(ExperimentalFeature::PortPause.is_supported(&old).not());
+        assert_eq!(
+            ExperimentalFeature::PortPause.is_supported(&crate::VERSION),
+            ExperimentalFeature::ENABLED
+        );
+    }
+}
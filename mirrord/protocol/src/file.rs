@@ -49,6 +49,22 @@ pub static RENAME_VERSION: LazyLock<VersionReq> =
 pub static COPYFILE_VERSION: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.24.0".parse().expect("Bad Identifier"));
 
+/// Minimal mirrord-protocol version that allows [`FsyncRequest`].
+pub static FSYNC_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.34.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`FileLockRequest`].
+pub static FILE_LOCK_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.35.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`GetCwdRequest`].
+pub static GET_CWD_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.42.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`ReadAheadRequest`].
+pub static READ_AHEAD_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.47.0".parse().expect("Bad Identifier"));
+
 /// Internal version of Metadata across operating system (macOS, Linux)
 /// Only mutual attributes
 #[derive(Encode, Decode, Debug, PartialEq, Clone, Copy, Eq, Default)]
@@ -390,6 +406,48 @@ impl fmt::Debug for ReadFileResponse {
     }
 }
 
+/// Asks the agent to read `window` bytes of the file, starting from `start_from`, without waiting
+/// for the reads of the user application.
+///
+/// The agent responds with a series of [`ReadAheadChunk`]s of at most `chunk_size` bytes each,
+/// sent one after another. The series ends with a chunk marked as [`ReadAheadChunk::last`], or
+/// with an error.
+///
+/// The agent may read less than `window` bytes, e.g. when the window exceeds its own limit.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct ReadAheadRequest {
+    pub remote_fd: u64,
+    pub start_from: u64,
+    pub chunk_size: u64,
+    pub window: u64,
+}
+
+/// The agent reads at most this many bytes in response to a single [`ReadAheadRequest`].
+pub const READ_AHEAD_MAX_WINDOW: u64 = 16 * 1024 * 1024;
+
+/// Part of the agent's response to a [`ReadAheadRequest`].
+#[derive(Encode, Decode, PartialEq, Eq, Clone)]
+pub struct ReadAheadChunk {
+    /// Position of [`Self::bytes`] in the file.
+    pub start_from: u64,
+    pub bytes: Payload,
+    /// The agent reached the end of the file.
+    pub eof: bool,
+    /// This is the last chunk sent for the [`ReadAheadRequest`].
+    pub last: bool,
+}
+
+impl fmt::Debug for ReadAheadChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadAheadChunk")
+            .field("start_from", &self.start_from)
+            .field("bytes (length)", &self.bytes.len())
+            .field("eof", &self.eof)
+            .field("last", &self.last)
+            .finish()
+    }
+}
+
 /// The contents of the symbolic link.
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub struct ReadLinkFileResponse {
@@ -687,3 +745,65 @@ pub struct FchmodRequest {
     pub fd: u64,
     pub mode: u32,
 }
+
+/// Flushes the remote file to its storage device, see `fsync(2)`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct FsyncRequest {
+    pub fd: u64,
+    /// When set, only the data (and metadata required to read it back) is flushed, like
+    /// `fdatasync(2)` does.
+    pub data_only: bool,
+}
+
+/// Kind of an advisory lock requested with [`FileLockRequest`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FileLockKind {
+    Shared,
+    Exclusive,
+    Unlock,
+}
+
+/// Where the [`FileLockRange::start`] is relative to.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FileLockWhence {
+    /// Beginning of the file (`SEEK_SET`).
+    Start,
+    /// Current offset of the remote file (`SEEK_CUR`).
+    Current,
+    /// End of the file (`SEEK_END`).
+    End,
+}
+
+/// Byte range of a record lock, see `fcntl(2)`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FileLockRange {
+    pub whence: FileLockWhence,
+    pub start: i64,
+    /// `0` means until the end of the file, however large it grows.
+    pub len: i64,
+}
+
+/// Places or removes an advisory lock on the remote file.
+///
+/// The agent never blocks on a contended lock, it responds with a
+/// [`ResponseError::RemoteIO`](crate::ResponseError::RemoteIO) of kind
+/// [`ErrorKindInternal::WouldBlock`](crate::ErrorKindInternal::WouldBlock) instead. Clients that
+/// want to wait for the lock should retry.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct FileLockRequest {
+    pub fd: u64,
+    pub kind: FileLockKind,
+    /// When set, a record lock on the given range is requested (`fcntl(2)`). Otherwise, the whole
+    /// file is locked (`flock(2)`).
+    pub range: Option<FileLockRange>,
+}
+
+/// Asks the agent for the current working directory of the target process.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct GetCwdRequest;
+
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct GetCwdResponse {
+    /// Working directory, as seen in the target's filesystem.
+    pub path: PathBuf,
+}
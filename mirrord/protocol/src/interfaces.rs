@@ -0,0 +1,45 @@
+//! Network interfaces of the target, used to present the pod's networking to the user application
+//! (e.g. from `getifaddrs`).
+
+use std::{net::IpAddr, sync::LazyLock};
+
+use bincode::{Decode, Encode};
+use semver::VersionReq;
+
+use crate::RemoteResult;
+
+/// Minimal mirrord-protocol version that allows for [`ClientMessage::GetInterfaces`] and
+/// [`DaemonMessage::GetInterfaces`].
+///
+/// [`ClientMessage::GetInterfaces`]: crate::ClientMessage::GetInterfaces
+/// [`DaemonMessage::GetInterfaces`]: crate::DaemonMessage::GetInterfaces
+pub static GET_INTERFACES_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.41.0".parse().expect("Bad Identifier"));
+
+/// Asks the agent for the addresses assigned to the network interfaces of the target.
+///
+/// The agent answers these requests in order, with [`DaemonMessage::GetInterfaces`].
+///
+/// [`DaemonMessage::GetInterfaces`]: crate::DaemonMessage::GetInterfaces
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct GetInterfacesRequest;
+
+/// An IP address assigned to a network interface of the target.
+///
+/// Corresponds to a single `ifaddrs` entry returned from `getifaddrs`. Entries that don't hold an
+/// IP address (e.g. `AF_PACKET` link entries) are not reported.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct InterfaceAddress {
+    /// Name of the interface, e.g. `eth0`.
+    pub name: String,
+    /// `IFF_*` flags of the interface.
+    pub flags: u32,
+    pub address: IpAddr,
+    pub netmask: Option<IpAddr>,
+    /// Broadcast address of the interface, or the destination address when the interface is
+    /// point-to-point (`IFF_POINTOPOINT`).
+    pub broadcast_or_destination: Option<IpAddr>,
+}
+
+/// Result of a [`GetInterfacesRequest`].
+pub type GetInterfacesResult = RemoteResult<Vec<InterfaceAddress>>;
@@ -30,6 +30,16 @@
 //! If you're not sure whether your change is breaking,
 //! you can check it manually with a unit test.
 //!
+//! # Experimental messages
+//!
+//! Some message families are still experimental, see [`experimental::ExperimentalFeature`].
+//! They're used only when this crate is built with the `experimental` feature, so that external
+//! tooling can depend on the stable part of the protocol.
+//!
+//! Prefer the typed constructors (e.g. [`tcp::HttpResponse::for_request`] and
+//! [`tcp::ChunkedResponse::body`]) over building the messages by hand, they keep the ids of the
+//! related messages consistent.
+//!
 //! # Versioning
 //!
 //! The version of the protocol is stored in the [`VERSION`] static variable
@@ -62,14 +72,23 @@
 #![cfg_attr(target_os = "windows", feature(windows_by_handle))]
 
 pub mod batched_body;
+pub mod cancel;
+pub mod capabilities;
 pub mod codec;
 pub mod dns;
 pub mod error;
+pub mod experimental;
 pub mod file;
+pub mod interfaces;
+pub mod listening_ports;
 pub mod outgoing;
 #[deprecated = "pause feature was removed"]
 pub mod pause;
 pub mod payload;
+pub mod quota;
+pub mod redact;
+pub mod resources;
+pub mod shutdown;
 pub mod tcp;
 pub mod uid;
 pub mod vpn;
@@ -94,6 +113,15 @@ pub static VERSION: LazyLock<semver::Version> = LazyLock::new(|| {
         .expect("Bad version parsing")
 });
 
+/// Protocol versions of the agent that this crate can talk to.
+///
+/// Features introduced in later versions are gated by their own minimal versions, so that older
+/// agents are still usable. Agents below this range (or from another major version) predate the
+/// gating, and clients should refuse them right after
+/// [`DaemonMessage::SwitchProtocolVersionResponse`].
+pub static COMPATIBLE_AGENT_VERSIONS: LazyLock<VersionReq> =
+    LazyLock::new(|| "^1.3.0".parse().expect("Bad Identifier"));
+
 /// Minimal protocol version that allows for sending [`DaemonMessage::OperatorPing`].
 pub static MIRRORD_OPERATOR_LATENCY_PING_PONG: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.19.8".parse().expect("Bad Identifier"));
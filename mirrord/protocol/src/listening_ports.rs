@@ -0,0 +1,39 @@
+//! TCP ports the target is listening on, used by the IDE plugins to pre-fill the ports to steal,
+//! and to warn when a configured port is not open in the target.
+
+use std::{net::IpAddr, sync::LazyLock};
+
+use bincode::{Decode, Encode};
+use semver::VersionReq;
+
+use crate::RemoteResult;
+
+/// Minimal mirrord-protocol version that allows for [`ClientMessage::GetListeningPorts`] and
+/// [`DaemonMessage::ListeningPorts`].
+///
+/// [`ClientMessage::GetListeningPorts`]: crate::ClientMessage::GetListeningPorts
+/// [`DaemonMessage::ListeningPorts`]: crate::DaemonMessage::ListeningPorts
+pub static LISTENING_PORTS_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.51.0".parse().expect("Bad Identifier"));
+
+/// Asks the agent for the TCP sockets listening in the target's network namespace.
+///
+/// The agent answers these requests in order, with [`DaemonMessage::ListeningPorts`].
+///
+/// [`DaemonMessage::ListeningPorts`]: crate::DaemonMessage::ListeningPorts
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct GetListeningPortsRequest;
+
+/// A TCP socket listening in the target's network namespace.
+///
+/// All containers of a pod share the network namespace, so these are the sockets of the whole
+/// pod.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord)]
+pub struct ListeningPort {
+    /// Address the socket is bound to, e.g. `0.0.0.0` or `127.0.0.1`.
+    pub address: IpAddr,
+    pub port: u16,
+}
+
+/// Result of a [`GetListeningPortsRequest`].
+pub type GetListeningPortsResult = RemoteResult<Vec<ListeningPort>>;
@@ -0,0 +1,47 @@
+use std::net::Ipv4Addr;
+
+use super::*;
+
+/// Minimal mirrord-protocol version that allows for [`ClientMessage::IcmpEcho`] and
+/// [`DaemonMessage::IcmpEcho`].
+///
+/// [`ClientMessage::IcmpEcho`]: crate::ClientMessage::IcmpEcho
+/// [`DaemonMessage::IcmpEcho`]: crate::DaemonMessage::IcmpEcho
+pub static ICMP_ECHO_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.37.0".parse().expect("Bad Identifier"));
+
+/// Asks the agent to send an ICMP echo request (ping) to the `destination`, and wait for the echo
+/// reply.
+///
+/// The agent answers these requests in order, with [`DaemonMessage::IcmpEcho`].
+///
+/// [`DaemonMessage::IcmpEcho`]: crate::DaemonMessage::IcmpEcho
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct IcmpEchoRequest {
+    pub destination: Ipv4Addr,
+    /// Identifier field of the echo request.
+    pub identifier: u16,
+    /// Sequence number field of the echo request.
+    pub sequence: u16,
+    /// Data of the echo request, returned in the echo reply.
+    pub payload: Payload,
+    /// How long the agent should wait for the echo reply, in milliseconds.
+    pub timeout_ms: u32,
+}
+
+/// Echo reply received by the agent for an [`IcmpEchoRequest`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct IcmpEchoResponse {
+    /// Address that sent the echo reply.
+    pub source: Ipv4Addr,
+    /// The whole ICMP message (header and data), without the IP header.
+    ///
+    /// This is what the user would read from a local `SOCK_DGRAM` + `IPPROTO_ICMP` socket.
+    pub packet: Payload,
+}
+
+/// Result of an [`IcmpEchoRequest`].
+///
+/// Fails with [`std::io::ErrorKind::TimedOut`] when no reply arrives within
+/// [`IcmpEchoRequest::timeout_ms`].
+pub type IcmpEchoResult = RemoteResult<IcmpEchoResponse>;
@@ -0,0 +1,85 @@
+//! Per-user quotas, set by the operator and enforced by the agent.
+
+use std::sync::LazyLock;
+
+use bincode::{Decode, Encode};
+use semver::VersionReq;
+use thiserror::Error;
+
+/// Minimal mirrord-protocol version that allows [`DaemonMessage::SessionQuotas`] and
+/// [`ResponseError::QuotaExceeded`].
+///
+/// [`DaemonMessage::SessionQuotas`]: crate::DaemonMessage::SessionQuotas
+/// [`ResponseError::QuotaExceeded`]: crate::ResponseError::QuotaExceeded
+pub static SESSION_QUOTAS_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.38.0".parse().expect("Bad Identifier"));
+
+/// Limits that apply to a mirrord session, set by the operator from the quota of the user that
+/// started it.
+///
+/// Sent to the client right after
+/// [`DaemonMessage::SwitchProtocolVersionResponse`](crate::DaemonMessage::SwitchProtocolVersionResponse),
+/// before any of its requests is handled.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct SessionQuotas {
+    /// How many clients one agent serves at the same time.
+    ///
+    /// Sessions usually get an agent of their own, so this does not limit how many sessions the
+    /// user runs across agents. That is up to the operator.
+    pub max_clients: Option<u32>,
+    /// How many ports a session can steal at the same time.
+    pub max_stolen_ports: Option<u32>,
+    /// How long a session can run, in seconds.
+    pub max_session_duration: Option<u64>,
+}
+
+impl SessionQuotas {
+    /// Returns whether no quota is set.
+    pub fn is_empty(&self) -> bool {
+        self.max_clients.is_none()
+            && self.max_stolen_ports.is_none()
+            && self.max_session_duration.is_none()
+    }
+}
+
+/// Quota from [`SessionQuotas`] that the session would exceed.
+///
+/// The messages are shown to the user as they are, so they should say what to do next.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy, Error)]
+pub enum QuotaExceeded {
+    #[error(
+        "the mirrord agent already serves {limit} clients, which is the most your quota allows. \
+        Stop one of the sessions that use the same agent and try again."
+    )]
+    AgentClients { limit: u32 },
+
+    #[error(
+        "your quota allows stealing at most {limit} ports in one session. Close the listening \
+        socket on one of the other stolen ports, or narrow down `feature.network.incoming.ports`."
+    )]
+    StolenPorts { limit: u32 },
+
+    #[error(
+        "your session reached the maximum duration of {limit}s that your quota allows. \
+        Start a new session to continue."
+    )]
+    SessionDuration { limit: u64 },
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Not;
+
+    use super::*;
+
+    #[test]
+    fn empty_quotas() {
+        assert!(SessionQuotas::default().is_empty());
+
+        let quotas = SessionQuotas {
+            max_stolen_ports: Some(2),
+            ..Default::default()
+        };
+        assert!(quotas.is_empty().not());
+    }
+}
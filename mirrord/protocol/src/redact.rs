@@ -0,0 +1,282 @@
+//! Masking secrets in logs of HTTP traffic.
+//!
+//! Whenever mirrord components log HTTP headers or bodies, they format them with the global
+//! [`Redactor`] returned from [`redactor`]. Unless configured otherwise with
+//! [`Redactor::install`], values of [`DEFAULT_REDACTED_HEADERS`] are masked.
+
+use std::{borrow::Cow, collections::HashSet, fmt, sync::OnceLock};
+
+use fancy_regex::Regex;
+use hyper::http::{HeaderMap, HeaderName};
+use thiserror::Error;
+
+/// Replaces the redacted parts of the logs.
+pub const REDACTED: &str = "<redacted>";
+
+/// Headers that are always redacted, unless redaction is disabled with [`Redactor::disabled`].
+pub const DEFAULT_REDACTED_HEADERS: [&str; 3] = ["authorization", "cookie", "set-cookie"];
+
+static REDACTOR: OnceLock<Redactor> = OnceLock::new();
+
+/// Returns the global [`Redactor`].
+///
+/// If none was installed with [`Redactor::install`], installs the [`Redactor::default`].
+pub fn redactor() -> &'static Redactor {
+    REDACTOR.get_or_init(Redactor::default)
+}
+
+/// Errors that can occur when creating a [`Redactor`].
+#[derive(Debug, Error)]
+pub enum RedactorError {
+    #[error("invalid header name `{0}`")]
+    InvalidHeaderName(String),
+
+    #[error("invalid body pattern `{0}`: {1}")]
+    InvalidBodyPattern(String, Box<fancy_regex::Error>),
+}
+
+/// Masks configured headers and body patterns when formatting HTTP traffic for logs.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    headers: HashSet<HeaderName>,
+    body_patterns: Vec<Regex>,
+}
+
+impl Default for Redactor {
+    /// Redacts only [`DEFAULT_REDACTED_HEADERS`].
+    fn default() -> Self {
+        Self {
+            headers: DEFAULT_REDACTED_HEADERS
+                .into_iter()
+                .map(HeaderName::from_static)
+                .collect(),
+            body_patterns: Default::default(),
+        }
+    }
+}
+
+impl Redactor {
+    /// Creates a redactor that masks the given headers (in addition to
+    /// [`DEFAULT_REDACTED_HEADERS`]), and all matches of the given regexes in bodies.
+    pub fn new<H, P>(headers: H, body_patterns: P) -> Result<Self, RedactorError>
+    where
+        H: IntoIterator,
+        H::Item: AsRef<str>,
+        P: IntoIterator,
+        P::Item: AsRef<str>,
+    {
+        let mut redactor = Self::default();
+
+        for header in headers {
+            let header = header.as_ref();
+            let name = HeaderName::try_from(header)
+                .map_err(|_| RedactorError::InvalidHeaderName(header.to_owned()))?;
+            redactor.headers.insert(name);
+        }
+
+        for pattern in body_patterns {
+            let pattern = pattern.as_ref();
+            let regex = Regex::new(pattern).map_err(|error| {
+                RedactorError::InvalidBodyPattern(pattern.to_owned(), error.into())
+            })?;
+            redactor.body_patterns.push(regex);
+        }
+
+        Ok(redactor)
+    }
+
+    /// Creates a redactor that does not mask anything.
+    pub fn disabled() -> Self {
+        Self {
+            headers: Default::default(),
+            body_patterns: Default::default(),
+        }
+    }
+
+    /// Makes this the global redactor, returned from [`redactor`].
+    ///
+    /// Fails if the global redactor is already set.
+    pub fn install(self) -> Result<(), Self> {
+        REDACTOR.set(self)
+    }
+
+    /// Whether values of the given header are masked.
+    pub fn is_redacted(&self, header: &HeaderName) -> bool {
+        self.headers.contains(header)
+    }
+
+    /// Formats the given headers with values of the redacted ones masked.
+    pub fn headers<'a>(&'a self, headers: &'a HeaderMap) -> RedactedHeaders<'a> {
+        RedactedHeaders {
+            redactor: self,
+            headers,
+        }
+    }
+
+    /// Formats a `name: value` header line, masking the value if the header is redacted.
+    pub fn header_line<'a>(&'a self, line: &'a str) -> RedactedHeaderLine<'a> {
+        RedactedHeaderLine {
+            redactor: self,
+            line,
+        }
+    }
+
+    /// Formats the given body as lossy UTF-8, with matches of the body patterns masked.
+    pub fn body<'a>(&'a self, body: &'a [u8]) -> RedactedBody<'a> {
+        RedactedBody {
+            redactor: self,
+            body,
+        }
+    }
+
+    fn redact_body<'a>(&self, body: &'a [u8]) -> Cow<'a, str> {
+        let mut body = String::from_utf8_lossy(body);
+
+        for pattern in &self.body_patterns {
+            let replaced = match pattern.replace_all(&body, REDACTED) {
+                Cow::Owned(replaced) => Some(replaced),
+                Cow::Borrowed(..) => None,
+            };
+
+            if let Some(replaced) = replaced {
+                body = Cow::Owned(replaced);
+            }
+        }
+
+        body
+    }
+}
+
+/// Formatting of [`HeaderMap`] with values of the redacted headers masked, see
+/// [`Redactor::headers`].
+pub struct RedactedHeaders<'a> {
+    redactor: &'a Redactor,
+    headers: &'a HeaderMap,
+}
+
+impl fmt::Debug for RedactedHeaders<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.headers.iter().map(|(name, value)| {
+                let value: &dyn fmt::Debug = if self.redactor.is_redacted(name) {
+                    &Masked
+                } else {
+                    value
+                };
+
+                (name, value)
+            }))
+            .finish()
+    }
+}
+
+/// Formatting of a `name: value` header line, see [`Redactor::header_line`].
+pub struct RedactedHeaderLine<'a> {
+    redactor: &'a Redactor,
+    line: &'a str,
+}
+
+impl fmt::Display for RedactedHeaderLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redacted = self.line.split_once(':').and_then(|(name, _)| {
+            HeaderName::try_from(name.trim())
+                .ok()
+                .filter(|name| self.redactor.is_redacted(name))
+                .map(|_| name)
+        });
+
+        match redacted {
+            Some(name) => write!(f, "{name}: {REDACTED}"),
+            None => f.write_str(self.line),
+        }
+    }
+}
+
+impl fmt::Debug for RedactedHeaderLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{self}\"")
+    }
+}
+
+/// Formatting of an HTTP body, see [`Redactor::body`].
+pub struct RedactedBody<'a> {
+    redactor: &'a Redactor,
+    body: &'a [u8],
+}
+
+impl fmt::Display for RedactedBody<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.redactor.redact_body(self.body))
+    }
+}
+
+impl fmt::Debug for RedactedBody<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.redactor.redact_body(self.body), f)
+    }
+}
+
+/// Debug-formats as [`REDACTED`].
+struct Masked;
+
+impl fmt::Debug for Masked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::http::{HeaderMap, HeaderValue, header};
+
+    use super::Redactor;
+
+    #[test]
+    fn default_headers_redacted() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer secret"),
+        );
+        headers.insert(header::HOST, HeaderValue::from_static("example.com"));
+
+        let formatted = format!("{:?}", Redactor::default().headers(&headers));
+        assert!(formatted.contains("\"authorization\": <redacted>"));
+        assert!(formatted.contains("\"host\": \"example.com\""));
+        assert!(!formatted.contains("secret"));
+    }
+
+    #[test]
+    fn configured_headers_and_body_patterns() {
+        let redactor = Redactor::new(["x-api-key"], [r#""password":\s*"[^"]*""#]).unwrap();
+
+        assert_eq!(
+            redactor.header_line("X-Api-Key: 1234").to_string(),
+            "X-Api-Key: <redacted>"
+        );
+        assert_eq!(
+            redactor.header_line("cookie: session=1").to_string(),
+            "cookie: <redacted>"
+        );
+        assert_eq!(
+            redactor.header_line("accept: */*").to_string(),
+            "accept: */*"
+        );
+        assert_eq!(
+            redactor
+                .body(br#"{"user": "me", "password": "hunter2"}"#)
+                .to_string(),
+            r#"{"user": "me", <redacted>}"#
+        );
+    }
+
+    #[test]
+    fn disabled_redacts_nothing() {
+        let redactor = Redactor::disabled();
+
+        assert_eq!(
+            redactor.header_line("authorization: Basic abc").to_string(),
+            "authorization: Basic abc"
+        );
+    }
+}
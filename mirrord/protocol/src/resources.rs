@@ -0,0 +1,61 @@
+//! Resource usage of the target container, used to compare the load of the remote pod with a local
+//! replica of the application (e.g. in `mirrord top`).
+
+use std::sync::LazyLock;
+
+use bincode::{Decode, Encode};
+use semver::VersionReq;
+
+use crate::RemoteResult;
+
+/// Minimal mirrord-protocol version that allows for [`ClientMessage::GetResourceUsage`] and
+/// [`DaemonMessage::ResourceUsage`].
+///
+/// [`ClientMessage::GetResourceUsage`]: crate::ClientMessage::GetResourceUsage
+/// [`DaemonMessage::ResourceUsage`]: crate::DaemonMessage::ResourceUsage
+pub static RESOURCE_USAGE_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.48.0".parse().expect("Bad Identifier"));
+
+/// Asks the agent for the current resource usage and limits of the target container.
+///
+/// The agent answers these requests in order, with [`DaemonMessage::ResourceUsage`].
+///
+/// [`DaemonMessage::ResourceUsage`]: crate::DaemonMessage::ResourceUsage
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct GetResourceUsageRequest;
+
+/// Resource usage and limits of the target container, read from its cgroup.
+///
+/// The values the agent could not read (e.g. because of an unsupported cgroup setup) are
+/// [`None`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Default)]
+pub struct ResourceUsage {
+    /// CPU time used by the container since it started, in microseconds.
+    ///
+    /// The client can compute the CPU usage from two consecutive samples.
+    pub cpu_time_usec: Option<u64>,
+    /// CPU quota of the container, in millicores.
+    pub cpu_limit_millicores: Option<u64>,
+    /// Memory used by the container, in bytes.
+    pub memory_bytes: Option<u64>,
+    /// Memory limit of the container, in bytes.
+    pub memory_limit_bytes: Option<u64>,
+    /// Processes running in the container.
+    pub processes: Vec<ProcessUsage>,
+}
+
+/// Resource usage of a single process in the target container.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct ProcessUsage {
+    /// Process id, as seen by the agent.
+    pub pid: u32,
+    /// Name of the executable, e.g. `node`.
+    pub name: String,
+    /// CPU time used by the process since it started (user and system), in microseconds.
+    pub cpu_time_usec: u64,
+    /// Resident set size of the process, in bytes.
+    pub memory_bytes: u64,
+}
+
+/// Result of a [`GetResourceUsageRequest`].
+pub type GetResourceUsageResult = RemoteResult<ResourceUsage>;
@@ -0,0 +1,45 @@
+//! Graceful shutdown of the agent, see [`GoingDown`].
+
+use std::{fmt, sync::LazyLock};
+
+use bincode::{Decode, Encode};
+use semver::VersionReq;
+
+/// Minimal mirrord-protocol version that allows for [`DaemonMessage::GoingDown`] and
+/// [`ClientMessage::GoingDownAck`].
+///
+/// [`DaemonMessage::GoingDown`]: crate::DaemonMessage::GoingDown
+/// [`ClientMessage::GoingDownAck`]: crate::ClientMessage::GoingDownAck
+pub static GOING_DOWN_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.49.0".parse().expect("Bad Identifier"));
+
+/// Sent by the agent when it is about to exit, e.g. because its pod is being deleted, or the
+/// target container exited.
+///
+/// From this moment, the agent no longer steals new connections and requests for the client
+/// (they're passed through to their original destination), but it still delivers the traffic of
+/// the connections and requests that are already in progress.
+///
+/// The client should finish handling its in-flight stolen traffic and reply with
+/// [`ClientMessage::GoingDownAck`]. The agent closes the connection when it gets the ack and all
+/// stolen traffic of the client is done, or when the deadline elapses, whichever comes first.
+///
+/// [`ClientMessage::GoingDownAck`]: crate::ClientMessage::GoingDownAck
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct GoingDown {
+    /// Human readable reason of the shutdown, e.g. `agent pod is being deleted`.
+    pub reason: String,
+    /// How long the agent waits for the client before closing the connection, in milliseconds
+    /// since this message was sent.
+    pub deadline_ms: u64,
+}
+
+impl fmt::Display for GoingDown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "mirrord-agent is shutting down ({}), in-flight traffic has {}ms to finish",
+            self.reason, self.deadline_ms
+        )
+    }
+}
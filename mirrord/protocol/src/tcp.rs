@@ -4,6 +4,7 @@ use std::{
     convert::Infallible,
     fmt,
     net::{IpAddr, SocketAddr},
+    ops::Not,
     pin::Pin,
     sync::LazyLock,
     task::{Context, Poll},
@@ -15,6 +16,8 @@ use http_body_util::BodyExt;
 use hyper::{
     HeaderMap, Method, Request, Response, StatusCode, Uri, Version,
     body::{Body, Frame},
+    header::{CONTENT_TYPE, HeaderName, HeaderValue, TRAILER},
+    http::response,
 };
 use mirrord_macros::protocol_break;
 use semver::VersionReq;
@@ -63,13 +66,83 @@ pub struct TcpClose {
     pub connection_id: ConnectionId,
 }
 
+/// The sender of this message will not write any more data to the connection with the given
+/// [`ConnectionId`], but it can still read from it (TCP half-close).
+///
+/// Older peers use an empty [`TcpData`] for the same purpose, see [`TCP_SHUTDOWN_WRITE_VERSION`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct TcpShutdownWrite {
+    pub connection_id: ConnectionId,
+}
+
+/// The client cannot take any more stolen connections on the given [`Port`] for now, because too
+/// many of them were not yet accepted by the user application.
+///
+/// While the backlog is full, the agent handles new connections to this port that would be stolen
+/// by the client according to the [`BacklogOverflow`] policy. The client sends another message
+/// with `full: false` when the backlog is no longer full.
+///
+/// Supported from [`TCP_BACKLOG_VERSION`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct TcpBacklogFull {
+    pub port: Port,
+    pub full: bool,
+    pub overflow: BacklogOverflow,
+}
+
+/// What the agent does with connections that overflow the client's backlog, see
+/// [`TcpBacklogFull`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BacklogOverflow {
+    /// The connection is closed.
+    Reject,
+    /// The connection is passed through to its original destination.
+    PassThrough,
+}
+
+/// Limits that the agent enforces on a mirrored port, see [`LayerTcp::PortSubscribeWithLimits`].
+///
+/// Supported from [`MIRROR_LIMITS_VERSION`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MirrorLimits {
+    /// How many bytes of mirrored traffic on the port the agent sends to the client per second.
+    ///
+    /// When the limit is exceeded, the agent closes the mirrored connection that exceeded it, and
+    /// does not mirror new connections to the port until the next second.
+    pub max_bytes_per_second: u64,
+}
+
+/// Same as [`LayerTcp::PortSubscribe`] or [`LayerTcp::PortSubscribeFilteredHttp`], but the
+/// mirrored traffic is capped with [`MirrorLimits`].
+///
+/// Supported from [`MIRROR_LIMITS_VERSION`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct PortSubscribeWithLimits {
+    pub port: Port,
+    pub filter: Option<HttpFilter>,
+    pub limits: MirrorLimits,
+}
+
+/// Mirrored traffic on the given [`Port`] that the agent dropped because of the [`MirrorLimits`]
+/// of the subscription, since the last report.
+///
+/// Supported from [`MIRROR_LIMITS_VERSION`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct MirrorDropped {
+    pub port: Port,
+    /// Connections (or HTTP requests) that were not mirrored, or were cut short.
+    pub connections: u64,
+    /// Bytes of data that were not mirrored.
+    pub bytes: u64,
+}
+
 /// Messages related to Tcp handler from client.
 ///
 /// Part of the `mirror` feature.
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub enum LayerTcp {
-    /// User is interested in mirroring traffic on this `Port`, so add it to the list of
-    /// ports that the sniffer is filtering.
+    /// User is interested in mirroring traffic on this `Port`, so the agent starts redirecting
+    /// connections made to it.
     PortSubscribe(Port),
 
     /// User is not interested in the connection with `ConnectionId` anymore.
@@ -77,16 +150,35 @@ pub enum LayerTcp {
     /// This means that their app has closed the connection they were `listen`ning on.
     ///
     /// There is no `ConnectionSubscribe` counter-part of this variant, the subscription
-    /// happens when the sniffer receives an (agent) internal `SniffedConnection`.
+    /// happens when the agent redirects a new connection made to the subscribed port.
     ConnectionUnsubscribe(ConnectionId),
 
-    /// Removes this `Port` from the sniffer's filter, the traffic won't be cloned to mirrord
-    /// anymore.
+    /// Stops mirroring this `Port`, the traffic won't be cloned to mirrord anymore.
     PortUnsubscribe(Port),
 
-    /// User is interested in mirroring traffic on this `Port`, so add it to the list of
-    /// ports that the sniffer is filtering.
+    /// Same as [`LayerTcp::PortSubscribe`], but only HTTP requests matching the [`HttpFilter`]
+    /// are mirrored.
     PortSubscribeFilteredHttp(Port, HttpFilter),
+
+    /// Same as [`LayerTcp::PortSubscribe`] or [`LayerTcp::PortSubscribeFilteredHttp`], but the
+    /// agent caps the mirrored traffic and reports what it dropped with
+    /// [`DaemonTcp::MirrorDropped`].
+    ///
+    /// Supported from [`MIRROR_LIMITS_VERSION`].
+    PortSubscribeWithLimits(PortSubscribeWithLimits),
+
+    /// Stops mirroring new connections made to this `Port`, without unsubscribing.
+    ///
+    /// Connections that are already mirrored are not affected. New connections are not mirrored
+    /// until [`LayerTcp::PortResume`].
+    ///
+    /// Supported from [`PORT_PAUSE_VERSION`].
+    PortPause(Port),
+
+    /// Resumes mirroring new connections made to this `Port`, see [`LayerTcp::PortPause`].
+    ///
+    /// Supported from [`PORT_PAUSE_VERSION`].
+    PortResume(Port),
 }
 
 /// Messages related to Tcp handler from server.
@@ -105,6 +197,14 @@ pub enum DaemonTcp {
     HttpRequestFramed(HttpRequest<InternalHttpBody>),
     HttpRequestChunked(ChunkedRequest),
     NewConnectionV2(NewTcpConnectionV2),
+    /// The remote peer of a stolen connection shut down its writing half.
+    ///
+    /// Supported from [`TCP_SHUTDOWN_WRITE_VERSION`].
+    ShutdownWrite(TcpShutdownWrite),
+    /// Mirrored traffic was dropped because of [`MirrorLimits`].
+    ///
+    /// Supported from [`MIRROR_LIMITS_VERSION`].
+    MirrorDropped(MirrorDropped),
 }
 
 /// Contents of a chunked message from server.
@@ -164,12 +264,46 @@ pub enum IncomingTrafficTransportType {
     },
 }
 
+/// Not exhaustive, new versions of the metadata can be added in the future. Use the accessor
+/// methods instead of matching on the variants.
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
 pub enum HttpRequestMetadata {
     V1 {
         source: SocketAddr,
         destination: SocketAddr,
     },
+    /// Supported from [`HTTP_CORRELATION_ID_VERSION`].
+    V2 {
+        source: SocketAddr,
+        destination: SocketAddr,
+        /// Unique id of the stolen request (ULID), generated by the agent.
+        ///
+        /// The same id appears in the agent audit log and in the client logs, so that a request
+        /// can be traced across all components.
+        correlation_id: String,
+    },
+}
+
+impl HttpRequestMetadata {
+    pub fn source(&self) -> SocketAddr {
+        match self {
+            Self::V1 { source, .. } | Self::V2 { source, .. } => *source,
+        }
+    }
+
+    pub fn destination(&self) -> SocketAddr {
+        match self {
+            Self::V1 { destination, .. } | Self::V2 { destination, .. } => *destination,
+        }
+    }
+
+    pub fn correlation_id(&self) -> Option<&str> {
+        match self {
+            Self::V1 { .. } => None,
+            Self::V2 { correlation_id, .. } => Some(correlation_id),
+        }
+    }
 }
 
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
@@ -202,6 +336,12 @@ impl Filter {
 
         Ok(Self(filter_str))
     }
+
+    /// Compiles the regex of an [`HttpFilter::Header`], matched against the [`header_lines`] of
+    /// a request, ignoring case.
+    pub fn header_regex(&self) -> Result<fancy_regex::Regex, fancy_regex::Error> {
+        fancy_regex::Regex::new(&format!("(?i){}", self.0))
+    }
 }
 
 impl Display for Filter {
@@ -325,6 +465,17 @@ pub enum HttpMethodFilter {
     Other(String),
 }
 
+/// Upper limit for the number of request body bytes that [`HttpBodyFilter`]s are matched against.
+///
+/// The agent does not buffer bodies past its configured limit, which cannot exceed this value.
+/// When the body is larger, the filters see only its head (a preview), and the rest of the body is
+/// streamed to its destination as it arrives.
+///
+/// A [`HttpBodyFilter::Json`] fails to parse a truncated preview, so it never matches a body
+/// larger than the limit. Filters that match the raw bytes of the body (e.g. a regex) can match
+/// the preview.
+pub const HTTP_BODY_PREVIEW_MAX_SIZE: u32 = 1024 * 1024;
+
 /// Filter based on the contents of the body.
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, strum_macros::Display)]
 pub enum HttpBodyFilter {
@@ -353,6 +504,19 @@ pub enum HttpFilter {
 
     /// Filter by body
     Body(HttpBodyFilter),
+
+    /// Matches when the inner filter does not match.
+    ///
+    /// Supported from [`HTTP_NOT_FILTER_VERSION`].
+    Not(Box<HttpFilter>),
+
+    /// Matches when the request has the header `name` (case-insensitive), with a value that
+    /// starts with `prefix` (case-sensitive).
+    ///
+    /// Unlike [`HttpFilter::Header`], matched without a regex.
+    ///
+    /// Supported from [`HTTP_HEADER_PREFIX_FILTER_VERSION`].
+    HeaderPrefix { name: String, prefix: String },
 }
 
 impl Display for HttpFilter {
@@ -390,10 +554,48 @@ impl Display for HttpFilter {
                 }
             },
             HttpFilter::Body(filter) => write!(f, "body={filter}"),
+            HttpFilter::Not(filter) => write!(f, "not ({filter})"),
+            HttpFilter::HeaderPrefix { name, prefix } => {
+                write!(f, "header-prefix={name}: {prefix}")
+            }
         }
     }
 }
 
+/// Formats the headers of a request like `name: value`, which is what [`HttpFilter::Header`]
+/// regexes are matched against.
+///
+/// HTTP/2 requests carry the host in the `:authority` pseudo-header, and HTTP/1.0 clients might
+/// send an absolute URI without the `host` header, so then the host is taken from the `uri`.
+/// Values that are not valid UTF-8 are skipped.
+pub fn header_lines(headers: &HeaderMap, uri: &Uri) -> Vec<String> {
+    let mut lines = headers
+        .iter()
+        .filter_map(|(name, value)| Some(format!("{name}: {}", value.to_str().ok()?)))
+        .collect::<Vec<_>>();
+
+    if headers.contains_key(hyper::header::HOST).not()
+        && let Some(authority) = uri.authority()
+    {
+        lines.push(format!("{}: {authority}", hyper::header::HOST));
+    }
+
+    lines
+}
+
+/// Returns the values of the `name` header that start with `prefix`, i.e. the ones that make the
+/// request match [`HttpFilter::HeaderPrefix`].
+pub fn header_values_with_prefix<'a>(
+    headers: &'a HeaderMap,
+    name: &HeaderName,
+    prefix: &'a str,
+) -> impl Iterator<Item = &'a HeaderValue> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter(move |value| value.as_bytes().starts_with(prefix.as_bytes()))
+}
+
 /// Describes the stealing subscription to a port:
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 #[protocol_break(2)]
@@ -405,15 +607,199 @@ pub enum StealType {
     FilteredHttp(Port, Filter),
     /// Steal HTTP traffic matching a given filter - supporting more than once kind of filter
     FilteredHttpEx(Port, HttpFilter),
+    /// Steal whole TLS connections whose ClientHello carries a server name (SNI) matching the
+    /// given regex. The connections are not decrypted by the agent.
+    ///
+    /// Requires [`SNI_STEAL_VERSION`].
+    FilteredSni(Port, Filter),
+    /// Steal whole PostgreSQL connections whose startup message matches the given filter.
+    ///
+    /// Requires [`POSTGRES_STEAL_VERSION`].
+    FilteredPostgres(Port, PostgresFilter),
+    /// Steal Redis commands matching the given filter. Other commands on the same connection are
+    /// sent to the original destination.
+    ///
+    /// Requires [`REDIS_STEAL_VERSION`].
+    FilteredRedis(Port, RedisFilter),
+}
+
+/// Filter for PostgreSQL connections, matched against the parameters of the startup message.
+///
+/// A connection matches when all of the given regexes match.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct PostgresFilter {
+    /// Regex for the name of the database.
+    pub database: Option<Filter>,
+    /// Regex for the name of the user.
+    pub user: Option<Filter>,
+}
+
+impl fmt::Display for PostgresFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.database, &self.user) {
+            (Some(database), Some(user)) => write!(f, "database={database}, user={user}"),
+            (Some(database), None) => write!(f, "database={database}"),
+            (None, Some(user)) => write!(f, "user={user}"),
+            (None, None) => f.write_str("any"),
+        }
+    }
+}
+
+/// Filter for Redis commands, matched against the command name and its first key.
+///
+/// A command matches when all of the given regexes match.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct RedisFilter {
+    /// Regex for the name of the command.
+    pub command: Option<Filter>,
+    /// Regex for the first key of the command.
+    pub key: Option<Filter>,
+}
+
+impl fmt::Display for RedisFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.command, &self.key) {
+            (Some(command), Some(key)) => write!(f, "command={command}, key={key}"),
+            (Some(command), None) => write!(f, "command={command}"),
+            (None, Some(key)) => write!(f, "key={key}"),
+            (None, None) => f.write_str("any"),
+        }
+    }
+}
+
+impl HttpFilter {
+    /// Whether this filter matches only the requests that are matched by the `required` filter,
+    /// i.e. it is the `required` filter, or an `all` composite that includes it.
+    pub fn includes(&self, required: &HttpFilter) -> bool {
+        match self {
+            filter if filter == required => true,
+            HttpFilter::Composite { all: true, filters } => {
+                filters.iter().any(|filter| filter.includes(required))
+            }
+            _ => false,
+        }
+    }
 }
 
 impl StealType {
     pub fn get_port(&self) -> Port {
         let (StealType::All(port)
         | StealType::FilteredHttpEx(port, ..)
-        | StealType::FilteredHttp(port, ..)) = self;
+        | StealType::FilteredHttp(port, ..)
+        | StealType::FilteredSni(port, ..)
+        | StealType::FilteredPostgres(port, ..)
+        | StealType::FilteredRedis(port, ..)) = self;
         *port
     }
+
+    /// Whether this subscription steals only the HTTP requests that match the `required` filter.
+    pub fn includes_http_filter(&self, required: &HttpFilter) -> bool {
+        match self {
+            StealType::FilteredHttp(_, filter) => {
+                HttpFilter::Header(filter.clone()).includes(required)
+            }
+            StealType::FilteredHttpEx(_, filter) => filter.includes(required),
+            StealType::All(..)
+            | StealType::FilteredSni(..)
+            | StealType::FilteredPostgres(..)
+            | StealType::FilteredRedis(..) => false,
+        }
+    }
+
+    /// Narrows this subscription down to the HTTP requests that match the `required` filter, see
+    /// [`DaemonMessage::MandatoryHttpFilter`](crate::DaemonMessage::MandatoryHttpFilter).
+    ///
+    /// Subscriptions that do not steal HTTP traffic are returned unchanged.
+    pub fn with_http_filter(self, required: HttpFilter) -> Self {
+        if self.includes_http_filter(&required) {
+            return self;
+        }
+
+        match self {
+            StealType::All(port) => StealType::FilteredHttpEx(port, required),
+            StealType::FilteredHttp(port, filter) => StealType::FilteredHttpEx(
+                port,
+                HttpFilter::Composite {
+                    all: true,
+                    filters: vec![required, HttpFilter::Header(filter)],
+                },
+            ),
+            StealType::FilteredHttpEx(port, filter) => StealType::FilteredHttpEx(
+                port,
+                HttpFilter::Composite {
+                    all: true,
+                    filters: vec![required, filter],
+                },
+            ),
+            other @ (StealType::FilteredSni(..)
+            | StealType::FilteredPostgres(..)
+            | StealType::FilteredRedis(..)) => other,
+        }
+    }
+}
+
+/// A range of IP addresses, written as `addr/prefix_len` (e.g. `10.0.0.0/8`).
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct IpRange {
+    pub addr: IpAddr,
+    pub prefix_len: u8,
+}
+
+impl IpRange {
+    /// Whether the given address is in this range.
+    ///
+    /// IPv4-mapped IPv6 addresses are matched as IPv4 addresses.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr.to_canonical(), ip.to_canonical()) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix_len.min(32)));
+                let mask = mask.unwrap_or_default();
+                u32::from(range) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix_len.min(128)));
+                let mask = mask.unwrap_or_default();
+                u128::from(range) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Restricts a steal subscription to the connections made from the given source addresses, see
+/// [`LayerTcpSteal::PortSubscribeWithSources`].
+///
+/// A connection matches when its source address is not in any of the `deny` ranges, and is in
+/// one of the `allow` ranges (or `allow` is empty).
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Default)]
+pub struct SourceFilter {
+    pub allow: Vec<IpRange>,
+    pub deny: Vec<IpRange>,
+}
+
+impl SourceFilter {
+    /// Whether connections from the given address should be stolen.
+    pub fn matches(&self, source: IpAddr) -> bool {
+        self.deny.iter().any(|range| range.contains(source)).not()
+            && (self.allow.is_empty() || self.allow.iter().any(|range| range.contains(source)))
+    }
+}
+
+/// Same as [`LayerTcpSteal::PortSubscribe`], but only the connections (and HTTP requests) made
+/// from the addresses matching the [`SourceFilter`] are stolen. Other traffic is passed through
+/// to its original destination.
+///
+/// Supported from [`STEAL_SOURCE_FILTER_VERSION`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct PortSubscribeWithSources {
+    pub steal_type: StealType,
+    pub sources: SourceFilter,
 }
 
 /// Describes the mirroring subscription to a port
@@ -471,6 +857,46 @@ pub enum LayerTcpSteal {
     HttpResponse(HttpResponse<Payload>),
     HttpResponseFramed(HttpResponse<InternalHttpBody>),
     HttpResponseChunked(ChunkedResponse),
+    /// The user application shut down the writing half of a stolen connection.
+    ///
+    /// Supported from [`TCP_SHUTDOWN_WRITE_VERSION`].
+    ShutdownWrite(TcpShutdownWrite),
+    /// The client's backlog of stolen connections on a port became full or is no longer full.
+    ///
+    /// Supported from [`TCP_BACKLOG_VERSION`].
+    BacklogFull(TcpBacklogFull),
+
+    /// Stops stealing new connections (and HTTP requests) made to this `Port` on behalf of this
+    /// client, without unsubscribing.
+    ///
+    /// Traffic that is already stolen is not affected. Traffic that would be stolen by this client
+    /// is passed through to its original destination until [`LayerTcpSteal::PortResume`].
+    ///
+    /// Supported from [`PORT_PAUSE_VERSION`].
+    PortPause(Port),
+
+    /// Resumes stealing traffic on this `Port`, see [`LayerTcpSteal::PortPause`].
+    ///
+    /// Supported from [`PORT_PAUSE_VERSION`].
+    PortResume(Port),
+
+    /// Same as [`LayerTcpSteal::PortSubscribe`], but restricted to the given source addresses.
+    ///
+    /// Supported from [`STEAL_SOURCE_FILTER_VERSION`].
+    PortSubscribeWithSources(PortSubscribeWithSources),
+}
+
+impl LayerTcpSteal {
+    /// Returns the [`StealType`] of a subscription message, with or without source addresses.
+    pub fn steal_type(&self) -> Option<&StealType> {
+        match self {
+            Self::PortSubscribe(steal_type)
+            | Self::PortSubscribeWithSources(PortSubscribeWithSources { steal_type, .. }) => {
+                Some(steal_type)
+            }
+            _ => None,
+        }
+    }
 }
 
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
@@ -480,6 +906,40 @@ pub enum ChunkedResponse {
     Error(ChunkedRequestErrorV1),
 }
 
+impl ChunkedResponse {
+    /// Starts the response to the given `request`, with the body frames that are already
+    /// available.
+    pub fn start<R>(
+        request: &HttpRequest<R>,
+        response: InternalHttpResponse<Vec<InternalHttpBodyFrame>>,
+    ) -> Self {
+        Self::Start(HttpResponse::for_request(request, response))
+    }
+
+    /// Next frames of the response body, [`ChunkedResponse::start`] must be sent first.
+    pub fn body<R>(
+        request: &HttpRequest<R>,
+        frames: Vec<InternalHttpBodyFrame>,
+        is_last: bool,
+    ) -> Self {
+        Self::Body(ChunkedRequestBodyV1 {
+            frames,
+            is_last,
+            connection_id: request.connection_id,
+            request_id: request.request_id,
+        })
+    }
+
+    /// Aborts the response after [`ChunkedResponse::start`], e.g. when reading the response body
+    /// failed.
+    pub fn error<R>(request: &HttpRequest<R>) -> Self {
+        Self::Error(ChunkedRequestErrorV1 {
+            connection_id: request.connection_id,
+            request_id: request.request_id,
+        })
+    }
+}
+
 /// (De-)Serializable HTTP request.
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct InternalHttpRequest<B> {
@@ -601,6 +1061,56 @@ pub static MIRROR_HTTP_FILTER_VERSION: LazyLock<VersionReq> =
 pub static HTTP_BODY_JSON_FILTER_VERSION: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.23.0".parse().expect("Bad Identifier"));
 
+/// Minimal mirrord-protocol version that allows stealing TLS connections based on their SNI
+/// ([`StealType::FilteredSni`]).
+pub static SNI_STEAL_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.27.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows stealing PostgreSQL connections based on their
+/// startup message ([`StealType::FilteredPostgres`]).
+pub static POSTGRES_STEAL_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.28.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows stealing Redis commands
+/// ([`StealType::FilteredRedis`]).
+pub static REDIS_STEAL_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.29.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows negated HTTP filters ([`HttpFilter::Not`]).
+pub static HTTP_NOT_FILTER_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.31.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`DaemonTcp::ShutdownWrite`] and
+/// [`LayerTcpSteal::ShutdownWrite`].
+pub static TCP_SHUTDOWN_WRITE_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.33.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`LayerTcpSteal::BacklogFull`].
+pub static TCP_BACKLOG_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.36.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`LayerTcp::PortSubscribeWithLimits`] and
+/// [`DaemonTcp::MirrorDropped`].
+pub static MIRROR_LIMITS_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.40.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`LayerTcp::PortPause`],
+/// [`LayerTcp::PortResume`], [`LayerTcpSteal::PortPause`] and [`LayerTcpSteal::PortResume`].
+pub static PORT_PAUSE_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.43.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`HttpRequestMetadata::V2`].
+pub static HTTP_CORRELATION_ID_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.44.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`LayerTcpSteal::PortSubscribeWithSources`].
+pub static STEAL_SOURCE_FILTER_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.45.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`HttpFilter::HeaderPrefix`].
+pub static HTTP_HEADER_PREFIX_FILTER_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.46.0".parse().expect("Bad Identifier"));
+
 /// Protocol break - on version 2, please add source port, dest/src IP to the message
 /// so we can avoid losing this information.
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
@@ -657,6 +1167,16 @@ pub struct InternalHttpResponse<Body> {
 }
 
 impl<B> InternalHttpResponse<B> {
+    /// Creates a response from the [`hyper`] response head and the given `body`.
+    pub fn from_parts(parts: response::Parts, body: B) -> Self {
+        Self {
+            status: parts.status,
+            version: parts.version,
+            headers: parts.headers,
+            body,
+        }
+    }
+
     pub fn map_body<T, F>(self, cb: F) -> InternalHttpResponse<T>
     where
         F: FnOnce(B) -> T,
@@ -695,6 +1215,13 @@ impl<B> From<InternalHttpResponse<B>> for Response<B> {
     }
 }
 
+impl<B> From<Response<B>> for InternalHttpResponse<B> {
+    fn from(value: Response<B>) -> Self {
+        let (parts, body) = value.into_parts();
+        Self::from_parts(parts, body)
+    }
+}
+
 impl<B: fmt::Debug> fmt::Debug for InternalHttpResponse<B> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("InternalHttpResponse")
@@ -765,6 +1292,15 @@ impl From<Frame<Bytes>> for InternalHttpBodyFrame {
     }
 }
 
+impl InternalHttpBodyFrame {
+    pub fn trailers_ref(&self) -> Option<&HeaderMap> {
+        match self {
+            Self::Data(..) => None,
+            Self::Trailers(trailers) => Some(trailers),
+        }
+    }
+}
+
 impl fmt::Debug for InternalHttpBodyFrame {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -779,6 +1315,57 @@ impl fmt::Debug for InternalHttpBodyFrame {
     }
 }
 
+/// Declares the fields of the given trailers in the `Trailer` header, keeping the fields that are
+/// already declared.
+///
+/// HTTP/1 encoders (hyper included) send only the declared trailer fields, so this should be called
+/// whenever we know the trailers before sending the head of the message.
+pub fn declare_trailers<'a, I>(headers: &mut HeaderMap, trailers: I)
+where
+    I: IntoIterator<Item = &'a HeaderMap>,
+{
+    let declared = headers
+        .get_all(TRAILER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::try_from(name.trim()).ok())
+        .collect::<Vec<_>>();
+
+    let mut missing: Vec<&HeaderName> = Vec::new();
+    for trailers in trailers {
+        for name in trailers.keys() {
+            if !declared.contains(name) && !missing.contains(&name) {
+                missing.push(name);
+            }
+        }
+    }
+
+    if missing.is_empty() {
+        return;
+    }
+
+    let missing = missing
+        .iter()
+        .map(|name| name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if let Ok(value) = HeaderValue::try_from(missing) {
+        headers.append(TRAILER, value);
+    }
+}
+
+/// Whether the response is a stream of server-sent events (`Content-Type: text/event-stream`).
+///
+/// Such responses are streamed frame by frame, both by the agent and the internal proxy.
+pub fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("text/event-stream"))
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
 pub struct InternalHttpBodyNew {
     pub frames: Vec<InternalHttpBodyFrame>,
@@ -798,6 +1385,22 @@ pub struct HttpResponse<B> {
 }
 
 impl<B> HttpResponse<B> {
+    /// Creates the response to the given `request`.
+    ///
+    /// Copies the ids and the port of the request, so that the agent can match the response with
+    /// the request.
+    pub fn for_request<R>(
+        request: &HttpRequest<R>,
+        internal_response: InternalHttpResponse<B>,
+    ) -> Self {
+        Self {
+            port: request.port,
+            connection_id: request.connection_id,
+            request_id: request.request_id,
+            internal_response,
+        }
+    }
+
     pub fn map_body<T, F>(self, cb: F) -> HttpResponse<T>
     where
         F: FnOnce(B) -> T,
@@ -810,3 +1413,115 @@ impl<B> HttpResponse<B> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::ops::Not;
+
+    use hyper::{
+        HeaderMap,
+        header::{HeaderValue, TRAILER},
+    };
+
+    use super::{
+        ChunkedResponse, HttpRequest, HttpResponse, InternalHttpBodyFrame, InternalHttpRequest,
+        InternalHttpResponse, IpRange, SourceFilter, declare_trailers,
+    };
+
+    #[test]
+    fn source_filter() {
+        let range = |addr: &str, prefix_len| IpRange {
+            addr: addr.parse().unwrap(),
+            prefix_len,
+        };
+        let filter = SourceFilter {
+            allow: vec![range("10.0.0.0", 8), range("fd00::", 8)],
+            deny: vec![range("10.1.0.0", 16)],
+        };
+
+        assert!(filter.matches("10.2.3.4".parse().unwrap()));
+        assert!(filter.matches("::ffff:10.2.3.4".parse().unwrap()));
+        assert!(filter.matches("fd12::1".parse().unwrap()));
+        assert!(filter.matches("10.1.3.4".parse().unwrap()).not());
+        assert!(filter.matches("192.168.0.1".parse().unwrap()).not());
+        assert!(SourceFilter::default().matches("192.168.0.1".parse().unwrap()));
+        assert!(range("0.0.0.0", 0).contains("1.2.3.4".parse().unwrap()));
+    }
+
+    /// Verifies that the response messages get the ids of the request they respond to.
+    #[test]
+    fn responses_match_request() {
+        let request = HttpRequest {
+            internal_request: InternalHttpRequest {
+                method: Default::default(),
+                uri: Default::default(),
+                headers: Default::default(),
+                version: Default::default(),
+                body: (),
+            },
+            connection_id: 7,
+            request_id: 3,
+            port: 8080,
+        };
+
+        let response = HttpResponse::for_request(
+            &request,
+            InternalHttpResponse::from(hyper::Response::new(Vec::<u8>::new())),
+        );
+        assert_eq!(
+            (response.connection_id, response.request_id, response.port),
+            (7, 3, 8080)
+        );
+
+        let ChunkedResponse::Body(body) = ChunkedResponse::body(&request, vec![], true) else {
+            panic!("expected a body message");
+        };
+        assert_eq!((body.connection_id, body.request_id), (7, 3));
+
+        let ChunkedResponse::Error(error) = ChunkedResponse::error(&request) else {
+            panic!("expected an error message");
+        };
+        assert_eq!((error.connection_id, error.request_id), (7, 3));
+    }
+
+    #[test]
+    fn declares_missing_trailers() {
+        let trailers = |names: &[&'static str]| {
+            let mut trailers = HeaderMap::new();
+            for name in names {
+                trailers.insert(*name, HeaderValue::from_static("0"));
+            }
+            InternalHttpBodyFrame::Trailers(trailers)
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(TRAILER, HeaderValue::from_static("Grpc-Status"));
+
+        let frames = [
+            InternalHttpBodyFrame::Data(b"hello".as_slice().into()),
+            trailers(&["grpc-status", "grpc-message"]),
+            trailers(&["grpc-message", "x-checksum"]),
+        ];
+        declare_trailers(
+            &mut headers,
+            frames
+                .iter()
+                .filter_map(InternalHttpBodyFrame::trailers_ref),
+        );
+
+        let declared = headers
+            .get_all(TRAILER)
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(declared, ["Grpc-Status", "grpc-message, x-checksum"]);
+
+        declare_trailers(
+            &mut headers,
+            frames
+                .iter()
+                .filter_map(InternalHttpBodyFrame::trailers_ref),
+        );
+        assert_eq!(headers.get_all(TRAILER).iter().count(), 2);
+    }
+}
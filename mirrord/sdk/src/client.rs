@@ -0,0 +1,194 @@
+//! Request-response client for short-lived sessions, like the ones of `mirrord cp`, `mirrord top`
+//! and `mirrord ls-ports`.
+
+use mirrord_protocol::{ClientMessage, DaemonMessage, LogLevel, LogMessage};
+use mirrord_protocol_io::{Client, Connection};
+use semver::Version;
+use thiserror::Error;
+
+/// Errors that can occur when talking to the agent with an [`AgentClient`].
+#[derive(Debug, Error)]
+pub enum AgentClientError {
+    #[error("agent connection was closed: {}", .0.as_deref().unwrap_or("<no close message>"))]
+    AgentConnClosed(Option<String>),
+
+    #[error("received an unexpected message from the agent: {0:?}")]
+    UnexpectedAgentMessage(
+        /// Boxed due to large size difference.
+        Box<DaemonMessage>,
+    ),
+}
+
+impl AgentClientError {
+    /// Creates [`AgentClientError::UnexpectedAgentMessage`].
+    pub fn unexpected(message: DaemonMessage) -> Self {
+        Self::UnexpectedAgentMessage(Box::new(message))
+    }
+}
+
+/// Sends requests to the agent one by one, and waits for their responses.
+///
+/// Handles the messages that the agent can send at any time, like
+/// [`DaemonMessage::OperatorPing`] or [`DaemonMessage::GoingDown`], so that the caller only sees
+/// the responses.
+#[derive(Debug)]
+pub struct AgentClient {
+    connection: Connection<Client>,
+    protocol_version: Version,
+}
+
+impl AgentClient {
+    /// Negotiates the [`mirrord_protocol`] version on the given [`Connection`].
+    pub async fn new(connection: Connection<Client>) -> Result<Self, AgentClientError> {
+        let mut client = Self {
+            connection,
+            protocol_version: mirrord_protocol::VERSION.clone(),
+        };
+
+        let response = client
+            .request(ClientMessage::SwitchProtocolVersion(
+                mirrord_protocol::VERSION.clone(),
+            ))
+            .await?;
+        let DaemonMessage::SwitchProtocolVersionResponse(version) = response else {
+            return Err(AgentClientError::unexpected(response));
+        };
+
+        tracing::debug!(%version, "Established mirrord-protocol version");
+        client.protocol_version = version;
+
+        Ok(client)
+    }
+
+    /// [`mirrord_protocol`] version negotiated with the agent.
+    pub fn protocol_version(&self) -> &Version {
+        &self.protocol_version
+    }
+
+    /// Sends a message to the agent, without waiting for a response.
+    pub async fn send(&self, message: ClientMessage) {
+        self.connection.send(message).await;
+    }
+
+    /// Sends a request to the agent, and waits for the next message that is not handled by
+    /// [`AgentClient::recv`].
+    pub async fn request(
+        &mut self,
+        message: ClientMessage,
+    ) -> Result<DaemonMessage, AgentClientError> {
+        self.send(message).await;
+        self.recv().await
+    }
+
+    /// Waits for the next message from the agent, handling the ones that can come at any time.
+    pub async fn recv(&mut self) -> Result<DaemonMessage, AgentClientError> {
+        loop {
+            let message = self
+                .connection
+                .recv()
+                .await
+                .ok_or(AgentClientError::AgentConnClosed(None))?;
+
+            match message {
+                DaemonMessage::OperatorPing(id) => {
+                    self.connection.send(ClientMessage::OperatorPong(id)).await;
+                }
+                DaemonMessage::Close(message) => {
+                    return Err(AgentClientError::AgentConnClosed(Some(message)));
+                }
+                DaemonMessage::GoingDown(notice) => {
+                    tracing::warn!(%notice, "Agent is shutting down");
+                    self.connection.send(ClientMessage::GoingDownAck).await;
+                }
+                DaemonMessage::Pong => {}
+                DaemonMessage::LogMessage(LogMessage { level, message }) => match level {
+                    LogLevel::Error => tracing::error!("Received log: {message}"),
+                    LogLevel::Warn => tracing::warn!("Received log: {message}"),
+                    LogLevel::Info => tracing::info!("Received log: {message}"),
+                },
+                DaemonMessage::LogEvent(event) => {
+                    tracing::debug!(?event, "Agent forwarded its log");
+                }
+                DaemonMessage::DisabledFeatures(features) => {
+                    tracing::debug!(?features, "Agent has some features disabled");
+                }
+                DaemonMessage::MandatoryHttpFilter(filter) => {
+                    tracing::debug!(%filter, "Agent requires an HTTP filter");
+                }
+                DaemonMessage::SessionQuotas(quotas) => {
+                    tracing::debug!(?quotas, "Agent enforces session quotas");
+                }
+                DaemonMessage::Capabilities(capabilities) => {
+                    tracing::debug!(?capabilities, "Agent reported its capabilities");
+                }
+                other => return Ok(other),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mirrord_protocol::listening_ports::{GetListeningPortsRequest, ListeningPort};
+
+    use super::*;
+
+    /// Verifies that the messages the agent can send at any time are handled, and only the
+    /// responses reach the caller.
+    #[tokio::test]
+    async fn request_skips_unsolicited_messages() {
+        let (connection, agent_tx, client_rx) = Connection::<Client>::dummy();
+
+        agent_tx
+            .send(DaemonMessage::SwitchProtocolVersionResponse(
+                "1.2.3".parse().unwrap(),
+            ))
+            .await
+            .unwrap();
+        let mut client = AgentClient::new(connection).await.unwrap();
+        assert_eq!(
+            client.protocol_version(),
+            &"1.2.3".parse::<Version>().unwrap()
+        );
+        assert!(matches!(
+            client_rx.next().await,
+            Some(ClientMessage::SwitchProtocolVersion(..))
+        ));
+
+        agent_tx.send(DaemonMessage::OperatorPing(7)).await.unwrap();
+        agent_tx
+            .send(DaemonMessage::LogMessage(LogMessage {
+                message: "hello".into(),
+                level: LogLevel::Warn,
+            }))
+            .await
+            .unwrap();
+        agent_tx
+            .send(DaemonMessage::ListeningPorts(Ok(vec![ListeningPort {
+                address: [0, 0, 0, 0].into(),
+                port: 80,
+            }])))
+            .await
+            .unwrap();
+
+        let response = client
+            .request(ClientMessage::GetListeningPorts(GetListeningPortsRequest))
+            .await
+            .unwrap();
+        assert!(matches!(response, DaemonMessage::ListeningPorts(Ok(ports)) if ports.len() == 1));
+        assert!(matches!(
+            client_rx.next().await,
+            Some(ClientMessage::GetListeningPorts(..))
+        ));
+        assert_eq!(client_rx.next().await, Some(ClientMessage::OperatorPong(7)));
+
+        agent_tx
+            .send(DaemonMessage::Close("bye".into()))
+            .await
+            .unwrap();
+        assert!(matches!(
+            client.recv().await,
+            Err(AgentClientError::AgentConnClosed(Some(message))) if message == "bye"
+        ));
+    }
+}
@@ -0,0 +1,443 @@
+#![deny(unused_crate_dependencies)]
+
+//! # mirrord-sdk
+//!
+//! Drives mirrord sessions from Rust code, without shelling out to the `mirrord` binary.
+//!
+//! This is the same orchestration that the mirrord CLI uses for `mirrord exec` and friends:
+//!
+//! 1. Resolving the target, either through the mirrord operator or directly with the Kubernetes
+//!    API;
+//! 2. Spawning the mirrord-agent (or asking the operator for a session);
+//! 3. Opening a `mirrord-protocol` connection with the agent.
+//!
+//! Progress is reported through the [`Progress`] trait, so the caller decides how to present it.
+//! Use [`NullProgress`](mirrord_progress::NullProgress) to ignore it, and
+//! [`NullReporter`](mirrord_analytics::NullReporter) to skip analytics.
+//!
+//! ```rust,ignore
+//! let mut config = LayerConfig::resolve(&mut ConfigContext::default())?;
+//!
+//! let session = mirrord_sdk::connect(
+//!     &mut config,
+//!     &mut NullProgress,
+//!     &mut NullReporter::default(),
+//!     SessionOptions::default(),
+//! )
+//! .await?;
+//!
+//! session.connection.send(ClientMessage::Ping).await;
+//! ```
+//!
+//! The returned [`AgentSession::connect_info`] can be passed to an
+//! [`IntProxy`](mirrord_intproxy::IntProxy) in another process, the way the CLI does it.
+//!
+//! With the `fake-cluster` feature, when `TEST_AGENT_SOCKET_ENV` is set, [`connect`] skips the
+//! cluster altogether and connects to an agent that already listens behind the given UNIX socket.
+//! This is how the fake cluster e2e tests run the agent as a local process.
+//!
+//! For short request-response sessions, wrap the connection in a [`client::AgentClient`], which
+//! negotiates the protocol version and handles the messages the agent can send at any time.
+
+#[cfg(all(unix, feature = "fake-cluster"))]
+use std::path::PathBuf;
+use std::time::Duration;
+
+use mirrord_analytics::Reporter;
+use mirrord_auth::credentials::CiApiKey;
+use mirrord_config::{
+    LayerConfig,
+    target::{Target, TargetDisplay},
+};
+pub use mirrord_intproxy::agent_conn::AgentConnectInfo;
+use mirrord_kube::{
+    api::{
+        container::{ContainerConfig, util::agent_log_redaction},
+        kubernetes::{KubernetesAPI, events::SessionEventReason},
+    },
+    error::KubeApiError,
+    resolved::ResolvedTarget,
+};
+use mirrord_operator::{
+    client::{OperatorApi, OperatorSessionConnection, error::OperatorApiError},
+    crd::{NewOperatorFeature, session::SessionCiInfo},
+};
+use mirrord_progress::{ExecPhase, Progress};
+use mirrord_protocol_io::{Client, Connection, ProtocolError};
+use thiserror::Error;
+use tracing::Level;
+
+pub mod client;
+
+/// Errors that can occur when starting a mirrord session.
+#[derive(Debug, Error)]
+pub enum SdkError {
+    #[error("mirrord operator was explicitly enabled, but it's not installed in the cluster")]
+    OperatorNotInstalled,
+
+    #[error(transparent)]
+    Operator(#[from] OperatorApiError),
+
+    #[error("failed to resolve the target with the mirrord operator: {0}")]
+    OperatorTargetResolution(KubeApiError),
+
+    #[error("mirrord for CI requires an API key when running with the mirrord operator")]
+    MissingCiApiKey,
+
+    #[error("{0} requires the mirrord operator")]
+    FeatureRequiresOperator(String),
+
+    #[error("failed to create the mirrord-agent: {0}")]
+    CreateAgent(KubeApiError),
+
+    #[error("failed to connect to the mirrord-agent: {0}")]
+    AgentConnection(KubeApiError),
+
+    #[error("failed to set up the connection with the mirrord-agent: {0}")]
+    Protocol(#[from] ProtocolError),
+
+    #[cfg(all(unix, feature = "fake-cluster"))]
+    #[error("failed to connect to the local mirrord-agent socket `{}`: {1}", .0.display())]
+    LocalAgent(PathBuf, std::io::Error),
+}
+
+pub type SdkResult<T, E = SdkError> = Result<T, E>;
+
+/// Test-only connector: path to a UNIX socket that leads to an already running agent.
+///
+/// When set, [`connect`] does not talk to the cluster at all, see the `fake_cluster` e2e tests.
+///
+/// Available only with the `fake-cluster` feature, which release builds don't enable.
+#[cfg(all(unix, feature = "fake-cluster"))]
+pub const TEST_AGENT_SOCKET_ENV: &str = "MIRRORD_TEST_AGENT_SOCKET";
+
+/// mirrord for CI parameters of a session, see [`SessionOptions::ci`].
+#[derive(Debug)]
+pub struct CiOptions<'a> {
+    /// Used as the operator credentials, required when the session uses the operator.
+    pub api_key: Option<&'a CiApiKey>,
+
+    /// Reported to the operator with the session.
+    pub info: SessionCiInfo,
+}
+
+/// Additional parameters of a session, not coming from the [`LayerConfig`].
+#[derive(Debug, Default)]
+pub struct SessionOptions<'a> {
+    /// Name of the user's git branch, reported to the operator.
+    pub branch_name: Option<String>,
+
+    /// Set when the session is started by mirrord for CI.
+    pub ci: Option<CiOptions<'a>>,
+}
+
+/// A started mirrord session.
+#[derive(Debug)]
+pub struct AgentSession {
+    /// How to make more connections to the agent, e.g. from the internal proxy.
+    pub connect_info: AgentConnectInfo,
+
+    /// Established connection with the agent.
+    pub connection: Connection<Client>,
+}
+
+impl AgentSession {
+    /// Whether the session was started through the mirrord operator.
+    pub fn uses_operator(&self) -> bool {
+        matches!(self.connect_info, AgentConnectInfo::Operator(..))
+    }
+}
+
+/// Starts a new mirrord session.
+///
+/// 1. If mirrord-operator is explicitly enabled in the given [`LayerConfig`], makes a connection
+///    with the target using the mirrord-operator.
+/// 2. If mirrord-operator is explicitly disabled in the given [`LayerConfig`], creates a
+///    mirrord-agent and runs session without the mirrord-operator.
+/// 3. Otherwise, attempts to use the mirrord-operator and falls back to OSS flow in case
+///    mirrord-operator is not found or its license is invalid.
+///
+/// The [`LayerConfig`] may be adjusted to the target, e.g. with the ports exposed by the target.
+///
+/// With the `fake-cluster` feature, if `TEST_AGENT_SOCKET_ENV` is set, connects to the agent
+/// behind that socket instead.
+#[tracing::instrument(level = Level::TRACE, skip_all, err)]
+pub async fn connect<P: Progress, R: Reporter>(
+    config: &mut LayerConfig,
+    progress: &mut P,
+    analytics: &mut R,
+    options: SessionOptions<'_>,
+) -> SdkResult<AgentSession> {
+    #[cfg(all(unix, feature = "fake-cluster"))]
+    if let Some(socket) = std::env::var_os(TEST_AGENT_SOCKET_ENV) {
+        return connect_to_local_agent(PathBuf::from(socket), progress).await;
+    }
+
+    if let Some(connection) =
+        try_connect_using_operator(config, progress, analytics, options).await?
+    {
+        return Ok(AgentSession {
+            connect_info: AgentConnectInfo::Operator(connection.session),
+            connection: connection.conn,
+        });
+    }
+
+    ensure_supported_without_operator(config)?;
+
+    progress.phase(ExecPhase::CreatingAgent);
+    let k8s_api = KubernetesAPI::create(config, progress)
+        .await
+        .map_err(SdkError::CreateAgent)?;
+
+    k8s_api
+        .detect_openshift(progress)
+        .await
+        .inspect_err(|fail| tracing::debug!(?fail, "Failed to detect OpenShift!"))
+        .ok();
+
+    let agent_container_config = ContainerConfig {
+        support_ipv6: config.feature.network.ipv6,
+        quic: config.connection.transport.is_quic(),
+        log_redaction: agent_log_redaction(&config.log_redaction),
+        procfs: config.feature.fs.procfs,
+        ..Default::default()
+    };
+    let agent_connect_info = tokio::time::timeout(
+        Duration::from_secs(config.agent.startup_timeout),
+        k8s_api.create_agent(
+            progress,
+            &config.target,
+            Some(&mut config.feature.network),
+            agent_container_config,
+        ),
+    )
+    .await
+    .unwrap_or(Err(KubeApiError::AgentReadyTimeout))
+    .map_err(SdkError::CreateAgent)?;
+
+    if config.kube_events
+        && let Some(target_pod) = &agent_connect_info.target_pod
+        && let Err(error) = target_pod
+            .emit(k8s_api.client(), SessionEventReason::Started)
+            .await
+    {
+        progress.warning(&format!(
+            "Failed to emit a Kubernetes event on the target pod: {error}"
+        ));
+    }
+
+    progress.phase(ExecPhase::ConnectingToAgent);
+    let connection = Connection::<Client>::from_stream(
+        k8s_api
+            .create_agent_connection(agent_connect_info.clone())
+            .await
+            .map_err(SdkError::AgentConnection)?,
+    )
+    .await?;
+
+    Ok(AgentSession {
+        connect_info: AgentConnectInfo::DirectKubernetes(agent_connect_info),
+        connection,
+    })
+}
+
+/// Connects to an agent that runs as a local process, see [`TEST_AGENT_SOCKET_ENV`].
+#[cfg(all(unix, feature = "fake-cluster"))]
+async fn connect_to_local_agent<P: Progress>(
+    socket: PathBuf,
+    progress: &mut P,
+) -> SdkResult<AgentSession> {
+    progress.warning(&format!(
+        "{TEST_AGENT_SOCKET_ENV} is set, connecting to the local mirrord-agent at {}",
+        socket.display()
+    ));
+
+    progress.phase(ExecPhase::ConnectingToAgent);
+    let stream = tokio::net::UnixStream::connect(&socket)
+        .await
+        .map_err(|error| SdkError::LocalAgent(socket.clone(), error))?;
+    let connection = Connection::<Client>::from_stream(stream).await?;
+
+    Ok(AgentSession {
+        connect_info: AgentConnectInfo::LocalSocket(socket),
+        connection,
+    })
+}
+
+/// Checks that the [`LayerConfig`] does not use features that require the mirrord operator.
+pub fn ensure_supported_without_operator(config: &LayerConfig) -> SdkResult<()> {
+    if let Some(target) = config.target.path.as_ref()
+        && Target::requires_operator(target)
+    {
+        return Err(SdkError::FeatureRequiresOperator(format!(
+            "target type {}",
+            target.type_()
+        )));
+    }
+
+    if config.feature.copy_target.enabled {
+        return Err(SdkError::FeatureRequiresOperator("copy_target".into()));
+    }
+
+    Ok(())
+}
+
+/// 1. If mirrord-operator is explicitly enabled in the given [`LayerConfig`], makes a connection
+///    with the target using the mirrord-operator.
+/// 2. If mirrord-operator is explicitly disabled in the given [`LayerConfig`], returns [`None`].
+/// 3. Otherwise, attempts to use the mirrord-operator and returns [`None`] in case mirrord-operator
+///    is not found or its license is invalid.
+async fn try_connect_using_operator<P, R>(
+    layer_config: &mut LayerConfig,
+    progress: &P,
+    analytics: &mut R,
+    SessionOptions { branch_name, ci }: SessionOptions<'_>,
+) -> SdkResult<Option<OperatorSessionConnection>>
+where
+    P: Progress,
+    R: Reporter,
+{
+    progress.phase(ExecPhase::CheckingOperator);
+    let mut operator_subtask = progress.subtask("checking operator");
+    if layer_config.operator == Some(false) {
+        operator_subtask.success(Some("operator disabled"));
+        return Ok(None);
+    }
+
+    let api = match OperatorApi::try_new(layer_config, analytics, progress).await? {
+        Some(api) => api,
+        None if layer_config.operator == Some(true) => {
+            return Err(SdkError::OperatorNotInstalled);
+        }
+        None => {
+            operator_subtask.success(Some("operator not found"));
+            return Ok(None);
+        }
+    };
+
+    let mut license_subtask = operator_subtask.subtask("checking license");
+    match api.check_license_validity(&license_subtask) {
+        Ok(()) => license_subtask.success(Some("operator license valid")),
+        Err(error) => {
+            license_subtask.failure(Some("operator license expired"));
+
+            if layer_config.operator == Some(true) {
+                return Err(error.into());
+            } else {
+                operator_subtask.failure(Some("proceeding without operator"));
+                return Ok(None);
+            }
+        }
+    }
+
+    let mut user_cert_subtask = operator_subtask.subtask("preparing user credentials");
+    let (api, session_ci_info) = match ci {
+        Some(CiOptions { api_key, info }) => {
+            let api = api
+                .with_ci_api_key(
+                    analytics,
+                    progress,
+                    layer_config,
+                    api_key.ok_or(SdkError::MissingCiApiKey)?,
+                )
+                .await;
+            (api, Some(info))
+        }
+        None => {
+            let api = api
+                .with_client_certificate(analytics, progress, layer_config)
+                .await;
+            (api, None)
+        }
+    };
+    let api = api.into_certified()?;
+
+    user_cert_subtask.success(Some("user credentials prepared"));
+
+    let is_multi_cluster = api
+        .operator()
+        .spec
+        .supported_features()
+        .contains(&NewOperatorFeature::MultiClusterPrimary);
+
+    progress.phase(ExecPhase::StartingSession);
+    let mut session_subtask = operator_subtask.subtask("starting session");
+    let connection = if is_multi_cluster {
+        // Multi-cluster: we connect to Primary, which routes to the workload cluster
+        // where the target is resolved and the session is created
+        let target_config = layer_config
+            .target
+            .path
+            .clone()
+            .unwrap_or(Target::Targetless);
+
+        api.connect_in_multi_cluster_session(
+            &target_config,
+            layer_config,
+            &session_subtask,
+            branch_name,
+            session_ci_info,
+        )
+        .await?
+    } else {
+        // Single-cluster: we resolve target of the connected cluster
+        let target = ResolvedTarget::new(
+            api.client(),
+            &layer_config
+                .target
+                .path
+                .clone()
+                .unwrap_or(Target::Targetless),
+            layer_config.target.namespace.as_deref(),
+        )
+        .await
+        .map_err(SdkError::OperatorTargetResolution)?;
+
+        api.connect_in_new_session(
+            target,
+            layer_config,
+            &session_subtask,
+            branch_name,
+            session_ci_info,
+        )
+        .await?
+    };
+    session_subtask.success(Some("session started"));
+
+    operator_subtask.success(Some("using operator"));
+
+    Ok(Some(connection))
+}
+
+#[cfg(test)]
+mod tests {
+    use mirrord_config::{
+        LayerFileConfig,
+        config::{ConfigContext, MirrordConfig},
+        target::{Target, TargetFileConfig, pod::PodTarget, service::ServiceTarget},
+    };
+    use rstest::rstest;
+
+    use crate::ensure_supported_without_operator;
+
+    /// Ensure that operator-only target types are disallowed when [`crate::connect`] fails to
+    /// establish a connection with the operator.
+    #[rstest]
+    #[case(Target::Pod(PodTarget{pod: "my-pet-pod".into(),container: None}))]
+    #[case(Target::Service(
+        ServiceTarget{service: "service-for-world-domination".into(),container: None}
+    ))]
+    fn deny_non_oss_targets_without_operator(#[case] target: Target) {
+        let allowed = !target.requires_operator();
+
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let config = LayerFileConfig {
+            target: Some(TargetFileConfig::Simple(Some(target))),
+            ..Default::default()
+        }
+        .generate_config(&mut cfg_context)
+        .unwrap();
+
+        assert_eq!(ensure_supported_without_operator(&config).is_ok(), allowed)
+    }
+}
@@ -20,3 +20,14 @@ do
     echo "$out_file"
     gcc "$source_file" -o "$out_file"
 done
+
+# Apps linked against musl, like the apps built on Alpine. Skipped if musl-gcc is not installed.
+if command -v musl-gcc > /dev/null
+then
+  for source_file in $(find . -path "*/apps/musl_*/*.c" -not -path "./target/*")
+  do
+      out_file="$(dirname "$source_file")/out.musl_test_app"
+      echo "$out_file"
+      musl-gcc "$source_file" -o "$out_file"
+  done
+fi
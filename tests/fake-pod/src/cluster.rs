@@ -0,0 +1,210 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    ops::Not,
+    path::PathBuf,
+    process::ExitCode,
+    time::Duration,
+};
+
+use clap::Args;
+use tokio::{
+    net::{TcpStream, UnixListener, UnixStream},
+    process::{Child, Command},
+};
+
+use crate::{
+    command,
+    pod::{POD_INTERFACE, POD_IP},
+};
+
+/// Printed to stdout when the application and the agent are listening, and their sockets are
+/// ready for the host.
+pub const READY_LINE: &str = "fake pod ready";
+
+/// Address of the cluster side of the veth pair.
+const CLUSTER_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+/// Name of the cluster side of the veth pair.
+const CLUSTER_INTERFACE: &str = "veth-pod";
+
+#[derive(Args, Debug)]
+pub struct ClusterArgs {
+    /// Directory where the UNIX sockets are created, `agent.sock` for the agent and `<port>.sock`
+    /// for every exposed port.
+    #[arg(long)]
+    dir: PathBuf,
+
+    /// Path to the mirrord-agent binary.
+    #[arg(long)]
+    agent: PathBuf,
+
+    /// Port of the agent listener, inside the pod.
+    #[arg(long, default_value_t = 61337)]
+    agent_port: u16,
+
+    /// Port of the application to expose through `<port>.sock`.
+    #[arg(long = "expose")]
+    ports: Vec<u16>,
+
+    /// `<name>=<content>` of a file visible only in the cluster, in `<dir>/remote`.
+    #[arg(long = "remote-file", value_parser = parse_remote_file)]
+    remote_files: Vec<(String, String)>,
+
+    /// The target application.
+    #[arg(trailing_var_arg = true, required = true)]
+    app: Vec<String>,
+}
+
+fn parse_remote_file(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(name, content)| (name.to_string(), content.to_string()))
+        .ok_or_else(|| format!("expected `<name>=<content>`, got `{value}`"))
+}
+
+/// Starts the pod, and relays the connections from the host until the pod exits.
+pub async fn run(args: ClusterArgs) -> io::Result<ExitCode> {
+    // Mounted before the pod starts, so that its mount namespace gets a copy.
+    if args.remote_files.is_empty().not() {
+        let remote = args.dir.join("remote");
+        tokio::fs::create_dir_all(&remote).await?;
+        command(
+            "mount",
+            &["-t", "tmpfs", "tmpfs", &remote.to_string_lossy()],
+        )
+        .await?;
+
+        for (name, content) in &args.remote_files {
+            tokio::fs::write(remote.join(name), content).await?;
+        }
+    }
+
+    let mut pod = Command::new("unshare")
+        .args(["--net", "--pid", "--fork", "--mount-proc", "--kill-child"])
+        .arg(std::env::current_exe()?)
+        .arg("pod")
+        .arg("--agent")
+        .arg(&args.agent)
+        .args(["--agent-port", &args.agent_port.to_string(), "--"])
+        .args(&args.app)
+        .kill_on_drop(true)
+        .spawn()?;
+
+    // `unshare` moves itself to the new network namespace before forking the pod.
+    let pod_pid = pod
+        .id()
+        .ok_or_else(|| io::Error::other("pod exited right away"))?;
+    wait_for_network_namespace(pod_pid, &mut pod).await?;
+
+    command(
+        "ip",
+        &[
+            "link",
+            "add",
+            CLUSTER_INTERFACE,
+            "type",
+            "veth",
+            "peer",
+            "name",
+            POD_INTERFACE,
+            "netns",
+            &pod_pid.to_string(),
+        ],
+    )
+    .await?;
+    command(
+        "ip",
+        &[
+            "addr",
+            "add",
+            &format!("{CLUSTER_IP}/24"),
+            "dev",
+            CLUSTER_INTERFACE,
+        ],
+    )
+    .await?;
+    command("ip", &["link", "set", CLUSTER_INTERFACE, "up"]).await?;
+
+    let exposed = std::iter::once(("agent".to_string(), args.agent_port))
+        .chain(args.ports.iter().map(|port| (port.to_string(), *port)));
+    for (name, port) in exposed {
+        let address = SocketAddr::new(POD_IP, port);
+        wait_for_listener(address, &mut pod).await?;
+
+        let listener = UnixListener::bind(args.dir.join(format!("{name}.sock")))?;
+        tokio::spawn(relay(listener, address));
+    }
+
+    println!("{READY_LINE}");
+
+    let status = pod.wait().await?;
+    eprintln!("fake pod exited with {status}");
+
+    Ok(if status.success() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Fails if the pod exited.
+fn check_pod(pod: &mut Child) -> io::Result<()> {
+    match pod.try_wait()? {
+        Some(status) => Err(io::Error::other(format!(
+            "pod exited with {status} during the setup"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Waits until the process with the given pid leaves our network namespace.
+async fn wait_for_network_namespace(pid: u32, pod: &mut Child) -> io::Result<()> {
+    let ours = tokio::fs::read_link("/proc/self/ns/net").await?;
+
+    loop {
+        if tokio::fs::read_link(format!("/proc/{pid}/ns/net")).await? != ours {
+            return Ok(());
+        }
+
+        check_pod(pod)?;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// Waits until something listens on the given address in the pod.
+async fn wait_for_listener(address: SocketAddr, pod: &mut Child) -> io::Result<()> {
+    loop {
+        if TcpStream::connect(address).await.is_ok() {
+            return Ok(());
+        }
+
+        check_pod(pod)?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Relays every connection accepted on the UNIX socket to the given address in the pod.
+async fn relay(listener: UnixListener, address: SocketAddr) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(error) => {
+                eprintln!("fake pod failed to accept on {address} socket: {error}");
+                return;
+            }
+        };
+
+        tokio::spawn(async move {
+            if let Err(error) = relay_connection(stream, address).await {
+                eprintln!("fake pod relay to {address} failed: {error}");
+            }
+        });
+    }
+}
+
+async fn relay_connection(mut stream: UnixStream, address: SocketAddr) -> io::Result<()> {
+    let mut target = TcpStream::connect(address).await?;
+    tokio::io::copy_bidirectional(&mut stream, &mut target).await?;
+    Ok(())
+}
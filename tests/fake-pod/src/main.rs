@@ -0,0 +1,77 @@
+//! A "cluster" with a single "pod", for the fake cluster e2e tests, see
+//! `tests/src/fake_cluster.rs`.
+//!
+//! Meant to be started in fresh namespaces, e.g.
+//! `unshare --user --map-root-user --net --pid --mount --fork --mount-proc mirrord-fake-pod cluster
+//! ...`. The cluster starts the pod in nested network, PID and mount namespaces, and connects the
+//! two network namespaces with a veth pair, so that the traffic reaches the pod through its `eth0`,
+//! like in a real cluster.
+//!
+//! The pod runs the target application and the mirrord-agent (in the ephemeral mode, so that it
+//! uses the pod's environment and root). The cluster exposes their ports to the host through UNIX
+//! sockets, as the namespaces are not reachable otherwise.
+
+#[cfg(target_os = "linux")]
+mod cluster;
+#[cfg(target_os = "linux")]
+mod pod;
+
+#[cfg(target_os = "linux")]
+use std::{io, process::Stdio};
+
+#[cfg(target_os = "linux")]
+use clap::{Parser, Subcommand};
+
+#[cfg(target_os = "linux")]
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(subcommand)]
+    mode: Mode,
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Subcommand, Debug)]
+enum Mode {
+    /// Sets up the cluster network, starts the pod, and exposes its ports.
+    Cluster(cluster::ClusterArgs),
+    /// Runs inside the pod namespaces, started by the cluster.
+    Pod(pod::PodArgs),
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> std::process::ExitCode {
+    let result = match Args::parse().mode {
+        Mode::Cluster(args) => cluster::run(args).await,
+        Mode::Pod(args) => pod::run(args).await,
+    };
+
+    result.unwrap_or_else(|error| {
+        eprintln!("fake pod failed: {error}");
+        std::process::ExitCode::FAILURE
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    panic!("mirrord-fake-pod is only supported on Linux");
+}
+
+/// Runs a setup command to completion, failing if it fails.
+#[cfg(target_os = "linux")]
+async fn command(program: &str, args: &[&str]) -> io::Result<()> {
+    let status = tokio::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .status()
+        .await?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "`{program} {}` exited with {status}",
+            args.join(" ")
+        )))
+    }
+}
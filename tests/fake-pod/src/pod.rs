@@ -0,0 +1,89 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr},
+    path::PathBuf,
+    process::ExitCode,
+    time::Duration,
+};
+
+use clap::Args;
+use tokio::process::Command;
+
+use crate::command;
+
+/// Address of the pod, on the [`POD_INTERFACE`] moved here by the cluster.
+pub const POD_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+/// Name of the pod side of the veth pair.
+pub const POD_INTERFACE: &str = "eth0";
+
+#[derive(Args, Debug)]
+pub struct PodArgs {
+    /// Path to the mirrord-agent binary.
+    #[arg(long)]
+    pub agent: PathBuf,
+
+    /// Port of the agent listener.
+    #[arg(long)]
+    pub agent_port: u16,
+
+    /// The target application.
+    #[arg(trailing_var_arg = true, required = true)]
+    pub app: Vec<String>,
+}
+
+/// Configures the pod network, and runs the application and the agent until one of them exits.
+pub async fn run(args: PodArgs) -> io::Result<ExitCode> {
+    wait_for_interface().await?;
+    command(
+        "ip",
+        &["addr", "add", &format!("{POD_IP}/24"), "dev", POD_INTERFACE],
+    )
+    .await?;
+    command("ip", &["link", "set", POD_INTERFACE, "up"]).await?;
+    command("ip", &["link", "set", "lo", "up"]).await?;
+
+    let (program, app_args) = args
+        .app
+        .split_first()
+        .expect("clap requires the application");
+    let mut app = Command::new(program)
+        .args(app_args)
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let mut agent = Command::new(&args.agent)
+        .args(["-l", &args.agent_port.to_string(), "-t", "180", "ephemeral"])
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let (name, status) = tokio::select! {
+        status = app.wait() => ("application", status?),
+        status = agent.wait() => ("agent", status?),
+    };
+    eprintln!("fake pod {name} exited with {status}");
+
+    Ok(if status.success() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Waits until the cluster moves [`POD_INTERFACE`] into our network namespace.
+async fn wait_for_interface() -> io::Result<()> {
+    for _ in 0..100 {
+        if command("ip", &["link", "show", POD_INTERFACE])
+            .await
+            .is_ok()
+        {
+            return Ok(());
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    Err(io::Error::other(format!(
+        "{POD_INTERFACE} did not appear in the pod"
+    )))
+}
@@ -0,0 +1,286 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use hyper::{HeaderMap, Method, StatusCode, Uri, Version, header::HeaderValue};
+use mirrord_protocol::{
+    ClientMessage, ConnectionId, DaemonMessage, Payload, RequestId,
+    tcp::{
+        ChunkedRequest, ChunkedRequestStartV2, ChunkedResponse, DaemonTcp, HttpRequestMetadata,
+        IncomingTrafficTransportType, InternalHttpBodyFrame, InternalHttpBodyNew,
+        InternalHttpRequest, LayerTcpSteal, TcpClose,
+    },
+};
+use mirrord_protocol_io::{Client, ConnectionOutput};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    error::{SoakError, SoakResult},
+    server::{REQUEST_ID_HEADER, Server},
+};
+
+/// Remote port of the stolen connections.
+pub const PORT: u16 = 80;
+
+/// A response collected from the internal proxy.
+#[derive(Debug, Default)]
+struct CollectedResponse {
+    status: Option<StatusCode>,
+    body: Vec<u8>,
+}
+
+impl CollectedResponse {
+    fn extend(&mut self, frames: impl IntoIterator<Item = InternalHttpBodyFrame>) {
+        for frame in frames {
+            if let InternalHttpBodyFrame::Data(data) = frame {
+                self.body.extend_from_slice(&data);
+            }
+        }
+    }
+}
+
+/// A request waiting for the response from the internal proxy.
+struct PendingResponse {
+    response: CollectedResponse,
+    tx: oneshot::Sender<CollectedResponse>,
+}
+
+type PendingResponses = Arc<Mutex<HashMap<(ConnectionId, RequestId), PendingResponse>>>;
+
+/// Plays the role of the agent for the internal proxy: answers its requests, and steals HTTP
+/// requests with [`FakeAgent::connection`].
+#[derive(Clone)]
+pub struct FakeAgent {
+    to_proxy: mpsc::Sender<DaemonMessage>,
+    pending: PendingResponses,
+    timeout: Duration,
+    body: Bytes,
+}
+
+impl FakeAgent {
+    /// Starts handling the messages from the internal proxy in the background.
+    pub fn start(
+        to_proxy: mpsc::Sender<DaemonMessage>,
+        from_proxy: ConnectionOutput<Client>,
+        timeout: Duration,
+        body_size: usize,
+    ) -> Self {
+        let agent = Self {
+            to_proxy,
+            pending: Default::default(),
+            timeout,
+            body: Bytes::from(vec![b'x'; body_size]),
+        };
+
+        tokio::spawn(agent.clone().run(from_proxy));
+
+        agent
+    }
+
+    async fn run(self, from_proxy: ConnectionOutput<Client>) {
+        while let Some(message) = from_proxy.next().await {
+            let response = match message {
+                ClientMessage::Ping => Some(DaemonMessage::Pong),
+                ClientMessage::SwitchProtocolVersion(..) => Some(
+                    DaemonMessage::SwitchProtocolVersionResponse(mirrord_protocol::VERSION.clone()),
+                ),
+                ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(steal_type)) => {
+                    tracing::debug!(?steal_type, "Port subscribed");
+                    Some(DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Ok(
+                        PORT,
+                    ))))
+                }
+                ClientMessage::TcpSteal(steal) => {
+                    self.handle_steal(steal);
+                    None
+                }
+                other => {
+                    tracing::trace!(message = ?other, "Ignoring a message from the internal proxy");
+                    None
+                }
+            };
+
+            if let Some(response) = response
+                && self.to_proxy.send(response).await.is_err()
+            {
+                break;
+            }
+        }
+    }
+
+    fn handle_steal(&self, message: LayerTcpSteal) {
+        let mut pending = self
+            .pending
+            .lock()
+            .expect("pending responses mutex poisoned");
+
+        let (key, finished) = match message {
+            LayerTcpSteal::HttpResponse(response) => {
+                let key = (response.connection_id, response.request_id);
+                if let Some(entry) = pending.get_mut(&key) {
+                    entry.response.status = Some(response.internal_response.status);
+                    entry
+                        .response
+                        .body
+                        .extend_from_slice(&response.internal_response.body);
+                }
+                (key, true)
+            }
+            LayerTcpSteal::HttpResponseFramed(response) => {
+                let key = (response.connection_id, response.request_id);
+                if let Some(entry) = pending.get_mut(&key) {
+                    entry.response.status = Some(response.internal_response.status);
+                    entry.response.extend(response.internal_response.body.0);
+                }
+                (key, true)
+            }
+            LayerTcpSteal::HttpResponseChunked(ChunkedResponse::Start(response)) => {
+                let key = (response.connection_id, response.request_id);
+                if let Some(entry) = pending.get_mut(&key) {
+                    entry.response.status = Some(response.internal_response.status);
+                    entry.response.extend(response.internal_response.body);
+                }
+                (key, false)
+            }
+            LayerTcpSteal::HttpResponseChunked(ChunkedResponse::Body(body)) => {
+                let key = (body.connection_id, body.request_id);
+                if let Some(entry) = pending.get_mut(&key) {
+                    entry.response.extend(body.frames);
+                }
+                (key, body.is_last)
+            }
+            LayerTcpSteal::HttpResponseChunked(ChunkedResponse::Error(error)) => {
+                // Dropping the sender fails the request.
+                pending.remove(&(error.connection_id, error.request_id));
+                return;
+            }
+            other => {
+                tracing::trace!(message = ?other, "Ignoring a steal message from the internal proxy");
+                return;
+            }
+        };
+
+        if finished && let Some(entry) = pending.remove(&key) {
+            let _ = entry.tx.send(entry.response);
+        }
+    }
+
+    /// Steals a connection with the given id, sends `requests` HTTP requests through it one after
+    /// another, checks the responses, and closes the connection.
+    pub async fn connection(
+        &self,
+        connection_id: ConnectionId,
+        requests: u16,
+        filter_header: (&'static str, String),
+    ) -> SoakResult<()> {
+        let result = self.requests(connection_id, requests, filter_header).await;
+
+        self.pending
+            .lock()
+            .expect("pending responses mutex poisoned")
+            .retain(|(id, _), _| *id != connection_id);
+        self.send(DaemonMessage::TcpSteal(DaemonTcp::Close(TcpClose {
+            connection_id,
+        })))
+        .await?;
+
+        result
+    }
+
+    async fn requests(
+        &self,
+        connection_id: ConnectionId,
+        requests: u16,
+        (filter_name, filter_value): (&'static str, String),
+    ) -> SoakResult<()> {
+        for request_id in 0..requests {
+            let request_tag = format!("{connection_id}-{request_id}");
+
+            let mut headers = HeaderMap::new();
+            headers.insert("host", HeaderValue::from_static("soak.mirrord.local"));
+            headers.insert("content-length", HeaderValue::from(self.body.len()));
+            headers.insert(
+                REQUEST_ID_HEADER,
+                HeaderValue::from_str(&request_tag).expect("request tag is a valid header"),
+            );
+            headers.insert(
+                filter_name,
+                HeaderValue::from_str(&filter_value).expect("filter value is a valid header"),
+            );
+
+            let (tx, rx) = oneshot::channel();
+            self.pending
+                .lock()
+                .expect("pending responses mutex poisoned")
+                .insert(
+                    (connection_id, request_id),
+                    PendingResponse {
+                        response: Default::default(),
+                        tx,
+                    },
+                );
+
+            self.send(DaemonMessage::TcpSteal(DaemonTcp::HttpRequestChunked(
+                ChunkedRequest::StartV2(ChunkedRequestStartV2 {
+                    connection_id,
+                    request_id,
+                    request: InternalHttpRequest {
+                        method: Method::POST,
+                        uri: Uri::from_static("/soak"),
+                        headers,
+                        version: Version::HTTP_11,
+                        body: InternalHttpBodyNew {
+                            frames: vec![InternalHttpBodyFrame::Data(Payload(self.body.clone()))],
+                            is_last: true,
+                        },
+                    },
+                    metadata: HttpRequestMetadata::V1 {
+                        source: SocketAddr::from(([10, 0, 0, 1], 40000)),
+                        destination: SocketAddr::from(([10, 0, 0, 2], PORT)),
+                    },
+                    transport: IncomingTrafficTransportType::Tcp,
+                }),
+            )))
+            .await?;
+
+            let response = tokio::time::timeout(self.timeout, rx)
+                .await
+                .map_err(|_| SoakError::ResponseTimeout {
+                    connection_id,
+                    request_id,
+                    timeout: self.timeout,
+                })?
+                .map_err(|_| SoakError::InvalidResponse {
+                    connection_id,
+                    request_id,
+                    reason: "internal proxy failed the request".into(),
+                })?;
+
+            let expected = Server::response_body(&request_tag, self.body.len());
+            if response.status != Some(StatusCode::OK) || response.body != expected.as_bytes() {
+                return Err(SoakError::InvalidResponse {
+                    connection_id,
+                    request_id,
+                    reason: format!(
+                        "expected 200 with body {expected:?}, got {:?} with body {:?}",
+                        response.status,
+                        String::from_utf8_lossy(&response.body),
+                    ),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn send(&self, message: DaemonMessage) -> SoakResult<()> {
+        self.to_proxy
+            .send(message)
+            .await
+            .map_err(|_| SoakError::ProxyExited)
+    }
+}
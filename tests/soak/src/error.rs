@@ -0,0 +1,63 @@
+use std::{io, time::Duration};
+
+use mirrord_config::config::ConfigError;
+use mirrord_intproxy::error::ProxyStartupError;
+use mirrord_intproxy_protocol::codec::CodecError;
+use thiserror::Error;
+use tokio::task::JoinError;
+
+use crate::monitor::Usage;
+
+/// Reasons for failing the soak test.
+#[derive(Error, Debug)]
+pub enum SoakError {
+    #[error("io failed: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("failed to generate the config: {0}")]
+    Config(#[from] ConfigError),
+
+    #[error("driver task failed: {0}")]
+    Task(#[from] JoinError),
+
+    #[error("layer connection failed: {0}")]
+    Codec(#[from] CodecError),
+
+    #[error("internal proxy failed to start: {0}")]
+    ProxyStartup(#[from] ProxyStartupError),
+
+    #[error("internal proxy exited")]
+    ProxyExited,
+
+    #[error("internal proxy closed the layer connection")]
+    LayerConnectionClosed,
+
+    #[error("unexpected message from the internal proxy: {0}")]
+    UnexpectedMessage(String),
+
+    #[error("port subscription failed: {0}")]
+    SubscribeFailed(String),
+
+    #[error("no response to request {request_id} on connection {connection_id} within {timeout:?}")]
+    ResponseTimeout {
+        connection_id: u64,
+        request_id: u16,
+        timeout: Duration,
+    },
+
+    #[error("invalid response to request {request_id} on connection {connection_id}: {reason}")]
+    InvalidResponse {
+        connection_id: u64,
+        request_id: u16,
+        reason: String,
+    },
+
+    #[error("resource leak detected: {current} after {elapsed:?}, baseline was {baseline}")]
+    Leak {
+        baseline: Usage,
+        current: Usage,
+        elapsed: Duration,
+    },
+}
+
+pub type SoakResult<T> = Result<T, SoakError>;
@@ -0,0 +1,152 @@
+use std::net::SocketAddr;
+
+use mirrord_intproxy_protocol::{
+    IncomingRequest, IncomingResponse, LayerToProxyMessage, LocalMessage, MessageId,
+    NewSessionRequest, PortSubscribe, PortSubscription, PortUnsubscribe, ProcessInfo,
+    ProxyToLayerMessage,
+    codec::{AsyncDecoder, AsyncEncoder},
+};
+use mirrord_protocol::tcp::{Filter, HttpFilter, StealType};
+use tokio::net::{
+    TcpStream,
+    tcp::{OwnedReadHalf, OwnedWriteHalf},
+};
+
+use crate::{
+    agent::PORT,
+    error::{SoakError, SoakResult},
+};
+
+/// Plays the role of the layer for the internal proxy: a session of the user application that
+/// steals [`PORT`] with an HTTP filter.
+pub struct LayerSession {
+    tx: AsyncEncoder<LocalMessage<LayerToProxyMessage>, OwnedWriteHalf>,
+    rx: AsyncDecoder<LocalMessage<ProxyToLayerMessage>, OwnedReadHalf>,
+    next_message_id: MessageId,
+    /// Address of the user application.
+    listening_on: SocketAddr,
+}
+
+impl LayerSession {
+    /// Starts a new session with the internal proxy.
+    ///
+    /// `generation` is used to make the process info of every session unique, as after a fork or
+    /// a restart of the user application.
+    pub async fn connect(
+        proxy_address: SocketAddr,
+        listening_on: SocketAddr,
+        generation: u32,
+    ) -> SoakResult<Self> {
+        let stream = TcpStream::connect(proxy_address).await?;
+        let (tx, rx) = mirrord_intproxy_protocol::codec::make_async_framed::<
+            LocalMessage<LayerToProxyMessage>,
+            LocalMessage<ProxyToLayerMessage>,
+        >(stream);
+
+        let pid = std::process::id() as i32;
+        let mut session = Self {
+            tx,
+            rx,
+            next_message_id: 0,
+            listening_on,
+        };
+
+        let response = session
+            .request(LayerToProxyMessage::NewSession(NewSessionRequest {
+                process_info: ProcessInfo {
+                    pid: pid.wrapping_add_unsigned(generation),
+                    parent_pid: pid,
+                    name: "mirrord-soak".into(),
+                    cmdline: vec!["mirrord-soak".into()],
+                    loaded: true,
+                },
+                parent_layer: None,
+            }))
+            .await?;
+
+        match response {
+            ProxyToLayerMessage::NewSession(..) => Ok(session),
+            other => Err(SoakError::UnexpectedMessage(format!("{other:?}"))),
+        }
+    }
+
+    /// The [`Filter`] header set by [`Self::subscribe`] for the given generation.
+    pub fn filter_header(generation: u32) -> (&'static str, String) {
+        ("x-soak-generation", generation.to_string())
+    }
+
+    /// Subscribes to [`PORT`] with a header filter for the given generation.
+    pub async fn subscribe(&mut self, generation: u32) -> SoakResult<()> {
+        let (name, value) = Self::filter_header(generation);
+        let filter = Filter::new(format!("{name}: {value}"))
+            .map_err(|error| SoakError::SubscribeFailed(error.to_string()))?;
+
+        let response = self
+            .request(LayerToProxyMessage::Incoming(
+                IncomingRequest::PortSubscribe(PortSubscribe {
+                    listening_on: self.listening_on,
+                    subscription: PortSubscription::Steal(StealType::FilteredHttpEx(
+                        PORT,
+                        HttpFilter::Header(filter),
+                    )),
+                }),
+            ))
+            .await?;
+
+        match response {
+            ProxyToLayerMessage::Incoming(IncomingResponse::PortSubscribe(Ok(()))) => Ok(()),
+            ProxyToLayerMessage::Incoming(IncomingResponse::PortSubscribe(Err(error))) => {
+                Err(SoakError::SubscribeFailed(error.to_string()))
+            }
+            other => Err(SoakError::UnexpectedMessage(format!("{other:?}"))),
+        }
+    }
+
+    /// Removes the subscription to [`PORT`], as if the user application closed its listener.
+    pub async fn unsubscribe(&mut self) -> SoakResult<()> {
+        // The internal proxy does not respond to this request.
+        self.send(LayerToProxyMessage::Incoming(
+            IncomingRequest::PortUnsubscribe(PortUnsubscribe {
+                port: PORT,
+                listening_on: self.listening_on,
+            }),
+        ))
+        .await?;
+
+        Ok(())
+    }
+
+    async fn send(&mut self, message: LayerToProxyMessage) -> SoakResult<MessageId> {
+        let message_id = self.next_message_id;
+        self.next_message_id += 1;
+
+        self.tx
+            .send(&LocalMessage {
+                message_id,
+                inner: message,
+            })
+            .await?;
+        self.tx.flush().await?;
+
+        Ok(message_id)
+    }
+
+    /// Sends the request, and waits for the response with the same [`MessageId`].
+    async fn request(&mut self, message: LayerToProxyMessage) -> SoakResult<ProxyToLayerMessage> {
+        let message_id = self.send(message).await?;
+
+        loop {
+            let response = self
+                .rx
+                .receive()
+                .await?
+                .ok_or(SoakError::LayerConnectionClosed)?;
+
+            if response.message_id == message_id {
+                break Ok(response.inner);
+            }
+
+            tracing::debug!(?response, "Ignoring a message from the internal proxy");
+        }
+    }
+}
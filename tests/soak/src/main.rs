@@ -0,0 +1,266 @@
+//! Soak test for long-running steal sessions.
+//!
+//! Runs a real [`IntProxy`] in this process, between a fake agent ([`FakeAgent`]) and a fake
+//! layer ([`LayerSession`]), and steals HTTP traffic into a synthetic user application
+//! ([`Server`]) for hours. Along the steady traffic, it injects bursts of connections, HTTP filter
+//! updates and layer reconnects, and periodically checks that the number of open file descriptors
+//! and the resident memory of the process do not grow (see [`Monitor`]).
+//!
+//! Linux only, as the resource usage is read from `/proc/self`. See the README for usage.
+
+use std::{
+    net::SocketAddr,
+    process::ExitCode,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use clap::Parser;
+use mirrord_config::{config::MirrordConfig, experimental::ExperimentalFileConfig};
+use mirrord_intproxy::{
+    IntProxy,
+    agent_conn::{AgentConnectInfoDiscriminants, AgentConnection, ReconnectFlow},
+};
+use mirrord_protocol_io::Connection;
+use tokio::{net::TcpListener, time::Instant};
+use tracing_subscriber::EnvFilter;
+
+use crate::{
+    agent::FakeAgent,
+    error::{SoakError, SoakResult},
+    layer::LayerSession,
+    monitor::{Limits, Monitor},
+    server::Server,
+};
+
+mod agent;
+mod error;
+mod layer;
+mod monitor;
+mod server;
+
+#[derive(Parser, Debug)]
+#[command(about = "Soak test for long-running steal sessions")]
+struct Args {
+    /// How long to run the test, in minutes.
+    #[arg(long, default_value_t = 240)]
+    duration: u64,
+
+    /// How long to wait before taking the baseline resource usage, in minutes.
+    #[arg(long, default_value_t = 10)]
+    warmup: u64,
+
+    /// How often to sample the resource usage, in seconds.
+    #[arg(long, default_value_t = 60)]
+    sample_interval: u64,
+
+    /// Number of concurrent stolen connections in every round.
+    #[arg(long, default_value_t = 16)]
+    connections: usize,
+
+    /// Number of HTTP requests sent through every stolen connection.
+    #[arg(long, default_value_t = 8)]
+    requests_per_connection: u16,
+
+    /// Size of the HTTP request bodies, in bytes.
+    #[arg(long, default_value_t = 4096)]
+    body_size: usize,
+
+    /// Number of concurrent stolen connections in a burst.
+    #[arg(long, default_value_t = 256)]
+    burst_connections: usize,
+
+    /// Run a burst every this many rounds.
+    #[arg(long, default_value_t = 10)]
+    burst_every: u64,
+
+    /// Update the HTTP filter every this many rounds.
+    #[arg(long, default_value_t = 25)]
+    filter_update_every: u64,
+
+    /// Reconnect the layer every this many rounds.
+    #[arg(long, default_value_t = 50)]
+    reconnect_every: u64,
+
+    /// How long to wait for a response, in seconds.
+    #[arg(long, default_value_t = 30)]
+    request_timeout: u64,
+
+    /// Allowed growth of the number of open file descriptors over the baseline.
+    #[arg(long, default_value_t = 32)]
+    max_fd_growth: usize,
+
+    /// Allowed growth of the resident memory over the baseline, in MiB.
+    #[arg(long, default_value_t = 128)]
+    max_rss_growth: u64,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| EnvFilter::new("warn,mirrord_soak=info")),
+        )
+        .init();
+
+    let args = Args::parse();
+
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            tracing::error!(%error, "Soak test failed");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(args: Args) -> SoakResult<()> {
+    let server_address = Server::start().await?;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let proxy_address = listener.local_addr()?;
+
+    let (connection, to_proxy, from_proxy) = Connection::dummy();
+    let agent_conn = AgentConnection {
+        connection,
+        reconnect: ReconnectFlow::Break(AgentConnectInfoDiscriminants::ExternalProxy),
+    };
+    let experimental = ExperimentalFileConfig::default().generate_config(&mut Default::default())?;
+    let proxy = IntProxy::new_with_connection(
+        agent_conn,
+        listener,
+        4096,
+        Default::default(),
+        None,
+        Default::default(),
+        None,
+        Duration::from_secs(60),
+        false,
+        &experimental,
+    );
+    let mut proxy = tokio::spawn(proxy.run(Duration::from_secs(60), Duration::from_secs(60)));
+
+    let agent = FakeAgent::start(
+        to_proxy,
+        from_proxy,
+        Duration::from_secs(args.request_timeout),
+        args.body_size,
+    );
+
+    tokio::select! {
+        result = soak(&args, &agent, proxy_address, server_address) => result,
+        result = &mut proxy => match result {
+            Ok(Err(error)) => Err(error.into()),
+            Ok(Ok(())) | Err(..) => Err(SoakError::ProxyExited),
+        },
+    }
+}
+
+async fn soak(
+    args: &Args,
+    agent: &FakeAgent,
+    proxy_address: SocketAddr,
+    server_address: SocketAddr,
+) -> SoakResult<()> {
+    let deadline = Instant::now() + Duration::from_secs(args.duration * 60);
+    let sample_interval = Duration::from_secs(args.sample_interval);
+    let mut monitor = Monitor::new(
+        Duration::from_secs(args.warmup * 60),
+        Limits {
+            max_fd_growth: args.max_fd_growth,
+            max_rss_growth: args.max_rss_growth * 1024 * 1024,
+        },
+    );
+
+    let connection_ids = AtomicU64::new(0);
+    let mut generation = 0;
+    let mut layer = LayerSession::connect(proxy_address, server_address, generation).await?;
+    layer.subscribe(generation).await?;
+
+    let mut round = 0;
+    let mut last_sample = Instant::now();
+    monitor.sample()?;
+
+    while Instant::now() < deadline {
+        round += 1;
+
+        steal(
+            agent,
+            &connection_ids,
+            args.connections,
+            args.requests_per_connection,
+            generation,
+        )
+        .await?;
+
+        if round % args.burst_every == 0 {
+            tracing::debug!(round, "Injecting a burst");
+            steal(
+                agent,
+                &connection_ids,
+                args.burst_connections,
+                1,
+                generation,
+            )
+            .await?;
+        }
+
+        if round % args.filter_update_every == 0 {
+            generation += 1;
+            tracing::debug!(round, generation, "Updating the HTTP filter");
+            layer.unsubscribe().await?;
+            layer.subscribe(generation).await?;
+        }
+
+        if round % args.reconnect_every == 0 {
+            generation += 1;
+            tracing::debug!(round, generation, "Reconnecting the layer");
+            drop(layer);
+            layer = LayerSession::connect(proxy_address, server_address, generation).await?;
+            layer.subscribe(generation).await?;
+        }
+
+        if last_sample.elapsed() >= sample_interval {
+            last_sample = Instant::now();
+            monitor.sample()?;
+        }
+    }
+
+    tracing::info!(
+        rounds = round,
+        connections = connection_ids.load(Ordering::Relaxed),
+        peak = ?monitor.peak(),
+        "Soak test finished",
+    );
+
+    Ok(())
+}
+
+/// Steals `connections` concurrent connections, each with `requests` HTTP requests.
+async fn steal(
+    agent: &FakeAgent,
+    connection_ids: &AtomicU64,
+    connections: usize,
+    requests: u16,
+    generation: u32,
+) -> SoakResult<()> {
+    let tasks = (0..connections)
+        .map(|_| {
+            let connection_id = connection_ids.fetch_add(1, Ordering::Relaxed);
+            let agent = agent.clone();
+            let filter_header = LayerSession::filter_header(generation);
+            tokio::spawn(async move {
+                agent
+                    .connection(connection_id, requests, filter_header)
+                    .await
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for task in tasks {
+        task.await??;
+    }
+
+    Ok(())
+}
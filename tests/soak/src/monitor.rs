@@ -0,0 +1,174 @@
+use std::{fmt, fs, io, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::error::{SoakError, SoakResult};
+
+/// Resources used by this process, read from `/proc/self`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    /// Number of open file descriptors.
+    pub fds: usize,
+    /// Resident set size, in bytes.
+    pub rss: u64,
+}
+
+impl Usage {
+    pub fn read() -> io::Result<Self> {
+        let fds = fs::read_dir("/proc/self/fd")?.count();
+
+        let status = fs::read_to_string("/proc/self/status")?;
+        let rss = status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))
+            .and_then(|value| value.trim().strip_suffix("kB"))
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .ok_or_else(|| io::Error::other("missing VmRSS in /proc/self/status"))?;
+
+        Ok(Self {
+            fds,
+            rss: rss * 1024,
+        })
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} fds, {} MiB RSS", self.fds, self.rss / (1024 * 1024))
+    }
+}
+
+/// How much the [`Usage`] may grow over the baseline before it's considered a leak.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_fd_growth: usize,
+    pub max_rss_growth: u64,
+}
+
+impl Limits {
+    fn exceeded(&self, baseline: Usage, current: Usage) -> bool {
+        current.fds > baseline.fds + self.max_fd_growth
+            || current.rss > baseline.rss + self.max_rss_growth
+    }
+}
+
+/// Samples the [`Usage`] of this process, and compares it with a baseline taken after the warmup.
+///
+/// The warmup gives the buffers, connection pools and allocator arenas time to reach their steady
+/// size, so that only the growth that continues for the whole session is reported.
+pub struct Monitor {
+    started_at: Instant,
+    warmup: Duration,
+    limits: Limits,
+    baseline: Option<Usage>,
+    peak: Option<Usage>,
+}
+
+impl Monitor {
+    pub fn new(warmup: Duration, limits: Limits) -> Self {
+        Self {
+            started_at: Instant::now(),
+            warmup,
+            limits,
+            baseline: None,
+            peak: None,
+        }
+    }
+
+    /// Takes a sample and fails if it exceeds the [`Limits`].
+    pub fn sample(&mut self) -> SoakResult<Usage> {
+        let usage = Usage::read()?;
+        self.check(usage)?;
+        Ok(usage)
+    }
+
+    fn check(&mut self, usage: Usage) -> SoakResult<()> {
+        let elapsed = self.started_at.elapsed();
+
+        self.peak = Some(match self.peak {
+            Some(peak) => Usage {
+                fds: peak.fds.max(usage.fds),
+                rss: peak.rss.max(usage.rss),
+            },
+            None => usage,
+        });
+
+        let Some(baseline) = self.baseline else {
+            if elapsed >= self.warmup {
+                tracing::info!(%usage, ?elapsed, "Warmup finished, baseline taken");
+                self.baseline = Some(usage);
+            } else {
+                tracing::info!(%usage, ?elapsed, "Warming up");
+            }
+
+            return Ok(());
+        };
+
+        tracing::info!(%usage, %baseline, ?elapsed, "Resource usage sampled");
+
+        if self.limits.exceeded(baseline, usage) {
+            return Err(SoakError::Leak {
+                baseline,
+                current: usage,
+                elapsed,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Highest [`Usage`] seen so far.
+    pub fn peak(&self) -> Option<Usage> {
+        self.peak
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{Limits, Monitor, Usage};
+    use crate::error::SoakError;
+
+    const LIMITS: Limits = Limits {
+        max_fd_growth: 4,
+        max_rss_growth: 1024,
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn baseline_after_warmup() {
+        let mut monitor = Monitor::new(Duration::from_secs(60), LIMITS);
+
+        // Growth during the warmup is ignored.
+        monitor.check(Usage { fds: 10, rss: 0 }).unwrap();
+        monitor
+            .check(Usage {
+                fds: 100,
+                rss: 10_000,
+            })
+            .unwrap();
+
+        tokio::time::advance(Duration::from_secs(60)).await;
+        monitor.check(Usage { fds: 20, rss: 4096 }).unwrap();
+        monitor.check(Usage { fds: 24, rss: 5120 }).unwrap();
+
+        let error = monitor.check(Usage { fds: 25, rss: 4096 }).unwrap_err();
+        assert!(matches!(
+            error,
+            SoakError::Leak { baseline, current, .. }
+                if baseline == Usage { fds: 20, rss: 4096 } && current.fds == 25
+        ));
+
+        assert!(matches!(
+            monitor.check(Usage { fds: 20, rss: 5121 }),
+            Err(SoakError::Leak { .. })
+        ));
+        assert_eq!(
+            monitor.peak(),
+            Some(Usage {
+                fds: 100,
+                rss: 10_000
+            })
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use std::{convert::Infallible, net::SocketAddr};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    Request, Response, StatusCode, body::Incoming, server::conn::http1, service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+
+/// Header that carries the id of the request, echoed back by the [`Server`].
+pub const REQUEST_ID_HEADER: &str = "x-soak-request";
+
+/// The synthetic user application, which receives the stolen requests from the internal proxy.
+///
+/// Responds to every request with the value of the [`REQUEST_ID_HEADER`] and the size of the
+/// request body, so that the driver can check that the responses are not mixed up.
+pub struct Server;
+
+impl Server {
+    /// Starts the server in the background, returning its address.
+    pub async fn start() -> std::io::Result<SocketAddr> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+
+        tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(error) => {
+                        tracing::error!(%error, "Server failed to accept a connection");
+                        continue;
+                    }
+                };
+
+                tokio::spawn(async move {
+                    if let Err(error) = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service_fn(Self::handle))
+                        .await
+                    {
+                        tracing::debug!(%error, "Server connection failed");
+                    }
+                });
+            }
+        });
+
+        Ok(address)
+    }
+
+    /// The expected response body for the given request.
+    pub fn response_body(request_id: &str, body_size: usize) -> String {
+        format!("{request_id}:{body_size}")
+    }
+
+    async fn handle(request: Request<Incoming>) -> Result<Response<Full<Bytes>>, Infallible> {
+        let request_id = request
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string);
+
+        let Some(request_id) = request_id else {
+            return Ok(Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Full::default())
+                .expect("response is valid"));
+        };
+
+        let body_size = match request.into_body().collect().await {
+            Ok(body) => body.to_bytes().len(),
+            Err(error) => {
+                tracing::warn!(%error, "Server failed to read a request body");
+                return Ok(Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(Full::default())
+                    .expect("response is valid"));
+            }
+        };
+
+        Ok(Response::new(Full::new(Bytes::from(Self::response_body(
+            &request_id,
+            body_size,
+        )))))
+    }
+}
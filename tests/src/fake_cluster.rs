@@ -0,0 +1,217 @@
+#![cfg(test)]
+
+/// Tests of the steal, mirror, env and fs flows that run the agent as a local process, see
+/// [`FakeCluster`].
+mod fake_cluster_tests {
+    use std::time::Duration;
+
+    use rstest::*;
+
+    use crate::utils::{
+        access_log::{AccessLog, Marker},
+        application::Application,
+        fake_cluster::FakeCluster,
+        traffic_fixture::{self, assert_not_received, assert_received, TrafficFixture},
+    };
+
+    /// Target application of the traffic tests, responds with a directory listing.
+    fn remote_http_server() -> Vec<String> {
+        ["python3", "-m", "http.server", "80"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    /// Target application of the tests that don't need traffic.
+    fn remote_idle() -> Vec<String> {
+        ["sleep", "infinity"].map(String::from).to_vec()
+    }
+
+    #[rstest]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[timeout(Duration::from_secs(120))]
+    async fn fake_cluster_steal_http() {
+        let cluster = FakeCluster::start(remote_http_server(), &[80], vec![], vec![]).await;
+
+        let mut process = cluster
+            .run_exec(Application::NodeHTTP.get_cmd(), Some(vec!["--steal"]), None)
+            .await;
+        process
+            .wait_for_line(Duration::from_secs(40), "daemon subscribed")
+            .await;
+
+        let response = cluster.send_http_request(80, "GET").await;
+        // The local application responds with the request method.
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.contains("GET"), "{response}");
+        assert!(!response.contains("Directory listing"), "{response}");
+        process
+            .wait_for_line_stdout(Duration::from_secs(10), "GET: Request completed")
+            .await;
+    }
+
+    #[rstest]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[timeout(Duration::from_secs(120))]
+    async fn fake_cluster_mirror_http() {
+        let cluster = FakeCluster::start(remote_http_server(), &[80], vec![], vec![]).await;
+
+        let mut process = cluster
+            .run_exec(Application::NodeHTTP.get_cmd(), None, None)
+            .await;
+        process
+            .wait_for_line(Duration::from_secs(40), "daemon subscribed")
+            .await;
+
+        // The remote application still responds, the local one gets a copy of the request.
+        let response = cluster.send_http_request(80, "GET").await;
+        assert!(response.starts_with("HTTP/1.0 200"), "{response}");
+        assert!(response.contains("Directory listing"), "{response}");
+        process
+            .wait_for_line_stdout(Duration::from_secs(10), "GET: Request completed")
+            .await;
+    }
+
+    /// Steals TCP and WebSocket traffic between two `traffic-fixture` instances.
+    #[rstest]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[timeout(Duration::from_secs(120))]
+    async fn fake_cluster_steal_fixtures(
+        #[values(
+            TrafficFixture::Http,
+            TrafficFixture::TcpEcho,
+            TrafficFixture::Websocket
+        )]
+        fixture: TrafficFixture,
+    ) {
+        let cluster =
+            FakeCluster::start(fixture.command("remote", 80), &[80], vec![], vec![]).await;
+
+        let process = cluster
+            .run_exec(fixture.command("local", 80), Some(vec!["--steal"]), None)
+            .await;
+        process
+            .wait_for_line(Duration::from_secs(40), "daemon subscribed")
+            .await;
+
+        let stream = cluster.connect(80).await;
+        let payload = match fixture {
+            TrafficFixture::Http => {
+                traffic_fixture::http_request(stream, "/", "stolen")
+                    .await
+                    .assert_served_by("local", "stolen");
+                "GET / marker=stolen"
+            }
+            TrafficFixture::TcpEcho => {
+                traffic_fixture::tcp_echo(stream, "stolen").await;
+                "stolen"
+            }
+            TrafficFixture::Websocket => {
+                traffic_fixture::websocket_echo(stream, "stolen").await;
+                "stolen"
+            }
+            TrafficFixture::UdpEcho => unreachable!("UDP is not stolen"),
+        };
+
+        assert_received(&process, "local", fixture, payload).await;
+        assert_not_received(cluster.pod(), "remote", fixture, payload).await;
+    }
+
+    /// Steals only the HTTP requests that match the header filter.
+    #[rstest]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[timeout(Duration::from_secs(120))]
+    async fn fake_cluster_steal_http_filter() {
+        let fixture = TrafficFixture::Http;
+        let cluster =
+            FakeCluster::start(fixture.command("remote", 80), &[80], vec![], vec![]).await;
+
+        let process = cluster
+            .run_exec(
+                fixture.command("local", 80),
+                Some(vec!["--steal"]),
+                Some(vec![(
+                    "MIRRORD_HTTP_HEADER_FILTER",
+                    "x-mirrord-test-marker: stolen-.*",
+                )]),
+            )
+            .await;
+        process
+            .wait_for_line(Duration::from_secs(40), "daemon subscribed")
+            .await;
+
+        let access_log = AccessLog::new(&process, cluster.logs());
+        for name in ["stolen", "passed", "stolen", "passed"] {
+            let marker = Marker::new();
+            let tag = format!("{name}-{marker}");
+            let response =
+                traffic_fixture::http_request(cluster.connect(80).await, "/", &tag).await;
+
+            if name == "stolen" {
+                response.assert_served_by("local", &tag);
+                access_log.assert_stolen(&marker).await;
+            } else {
+                response.assert_served_by("remote", &tag);
+                access_log.assert_passed_through(&marker).await;
+            }
+        }
+    }
+
+    #[rstest]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[timeout(Duration::from_secs(120))]
+    async fn fake_cluster_remote_env() {
+        let cluster = FakeCluster::start(
+            remote_idle(),
+            &[],
+            vec![("MIRRORD_FAKE_CLUSTER_ENV", "from-the-pod")],
+            vec![],
+        )
+        .await;
+
+        let mut process = cluster
+            .run_exec(
+                ["printenv", "MIRRORD_FAKE_CLUSTER_ENV"]
+                    .map(String::from)
+                    .to_vec(),
+                None,
+                None,
+            )
+            .await;
+
+        process.wait_assert_success().await;
+        process.assert_stdout_contains("from-the-pod").await;
+    }
+
+    #[rstest]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[timeout(Duration::from_secs(120))]
+    async fn fake_cluster_remote_file() {
+        let cluster = FakeCluster::start(
+            remote_idle(),
+            &[],
+            vec![],
+            vec![("hello.txt", "hello from the pod")],
+        )
+        .await;
+
+        // The file exists only in the mount namespace of the pod.
+        let remote_dir = cluster.remote_dir();
+        let path = remote_dir.join("hello.txt");
+        assert!(!path.exists());
+
+        let read_only_pattern = format!("^{}/.*", remote_dir.display());
+        let mut process = cluster
+            .run_exec(
+                vec!["cat".to_string(), path.to_string_lossy().into_owned()],
+                None,
+                Some(vec![(
+                    "MIRRORD_FILE_READ_ONLY_PATTERN",
+                    read_only_pattern.as_str(),
+                )]),
+            )
+            .await;
+
+        process.wait_assert_success().await;
+        process.assert_stdout_contains("hello from the pod").await;
+    }
+}
@@ -7,6 +7,8 @@ mod cleanup;
 #[cfg(feature = "cli")]
 mod cli;
 mod env;
+#[cfg(feature = "fake-cluster")]
+mod fake_cluster;
 mod file_ops;
 mod http;
 #[cfg(any(feature = "cli", feature = "operator"))]
@@ -235,9 +235,10 @@ mod traffic_tests {
         assert!(res.success());
     }
 
-    /// Currently, mirrord only intercepts and forwards outgoing udp traffic if the application
-    /// binds a non-0 port and calls `connect`. This test runs with mirrord a node app that does
-    /// that and verifies that mirrord intercepts and forwards the outgoing udp message.
+    /// mirrord intercepts and forwards outgoing udp traffic both from connected sockets and from
+    /// unconnected sockets that use `sendto`. This test runs with mirrord a node app that binds a
+    /// non-0 port and calls `connect`, and verifies that mirrord intercepts and forwards the
+    /// outgoing udp message.
     #[cfg_attr(not(feature = "job"), ignore)]
     #[rstest]
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
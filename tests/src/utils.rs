@@ -11,14 +11,18 @@ use reqwest::{RequestBuilder, StatusCode};
 use rstest::*;
 use serde_json::{json, Value};
 
+pub mod access_log;
 pub mod application;
 pub mod cluster_resource;
+#[cfg(feature = "fake-cluster")]
+pub mod fake_cluster;
 pub mod ipv6;
 pub mod kube_service;
 pub mod port_forwarder;
 pub mod resource_guard;
 pub mod run_command;
 pub mod services;
+pub mod traffic_fixture;
 
 #[cfg(target_os = "windows")]
 pub mod windows;
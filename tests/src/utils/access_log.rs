@@ -0,0 +1,188 @@
+//! Checks whether a request was served by the local app or by the remote pod.
+//!
+//! Every request gets a unique [`Marker`], sent both in the query and in the
+//! [`MARKER_HEADER`](super::traffic_fixture::MARKER_HEADER), so it shows up in the access logs of
+//! most servers. [`AccessLog`] then looks for the marker in the output of both apps, instead of
+//! counting lines or sleeping until the traffic settles.
+
+use std::{fmt, time::Duration};
+
+use k8s_openapi::api::core::v1::Pod;
+use kube::{api::LogParams, Api, Client};
+use mirrord_test_utils::TestProcess;
+use reqwest::{header::HeaderMap, Method, Response, Url};
+
+use super::{
+    kube_service::KubeService, random_string, traffic_fixture::MARKER_HEADER, CONTAINER_NAME,
+};
+
+/// Query parameter with the [`Marker`] of a request.
+pub const MARKER_QUERY: &str = "mirrord-marker";
+
+/// How often [`AccessLog`] scrapes the outputs.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Unique tag of a single request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker(String);
+
+impl Marker {
+    pub fn new() -> Self {
+        Self(format!("marker-{}", random_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The given URL with this marker in the query.
+    pub fn url(&self, url: &str) -> Url {
+        let mut url = Url::parse(url).unwrap();
+        url.query_pairs_mut().append_pair(MARKER_QUERY, &self.0);
+        url
+    }
+
+    /// Sends a request with this marker, and the given headers.
+    pub async fn send(
+        &self,
+        client: &reqwest::Client,
+        method: Method,
+        url: &str,
+        mut headers: HeaderMap,
+    ) -> Response {
+        headers.insert(MARKER_HEADER, self.0.parse().unwrap());
+
+        client
+            .request(method, self.url(url))
+            .headers(headers)
+            .send()
+            .await
+            .unwrap()
+    }
+}
+
+impl Default for Marker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for Marker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Output of the remote app.
+pub enum RemoteLogs<'a> {
+    /// Logs of the test container in a pod.
+    Pod { api: Api<Pod>, pod_name: String },
+    /// Output of a local process, e.g. a [`FakeCluster`](super::fake_cluster::FakeCluster) pod.
+    Process(&'a TestProcess),
+}
+
+impl RemoteLogs<'_> {
+    /// Logs of the pod that backs the given service.
+    pub fn pod(client: Client, service: &KubeService) -> Self {
+        Self::Pod {
+            api: Api::namespaced(client, &service.namespace),
+            pod_name: service.pod_name.clone(),
+        }
+    }
+
+    async fn contents(&self) -> String {
+        match self {
+            Self::Pod { api, pod_name } => api
+                .logs(
+                    pod_name,
+                    &LogParams {
+                        container: Some(CONTAINER_NAME.to_string()),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap_or_else(|error| panic!("failed to read logs of pod {pod_name}: {error}")),
+            Self::Process(process) => process_output(process).await,
+        }
+    }
+}
+
+async fn process_output(process: &TestProcess) -> String {
+    let mut output = process.get_stdout().await;
+    output.push_str(&process.get_stderr().await);
+    output
+}
+
+/// The outputs of the local app (run with mirrord) and of the remote app.
+pub struct AccessLog<'a> {
+    local: &'a TestProcess,
+    remote: RemoteLogs<'a>,
+    timeout: Duration,
+}
+
+impl<'a> AccessLog<'a> {
+    pub fn new(local: &'a TestProcess, remote: RemoteLogs<'a>) -> Self {
+        Self {
+            local,
+            remote,
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// How long to wait for the marker to show up, 30 seconds by default.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Asserts that the request was stolen: served by the local app, and not by the remote one.
+    pub async fn assert_stolen(&self, marker: &Marker) {
+        self.wait_for(marker, true, false).await;
+    }
+
+    /// Asserts that the request was passed through to the remote app, and not seen by the local
+    /// one.
+    pub async fn assert_passed_through(&self, marker: &Marker) {
+        self.wait_for(marker, false, true).await;
+    }
+
+    /// Asserts that the request was mirrored: served by the remote app, and seen by the local one.
+    pub async fn assert_mirrored(&self, marker: &Marker) {
+        self.wait_for(marker, true, true).await;
+    }
+
+    /// Waits until the marker shows up in the expected outputs, and fails if it shows up in an
+    /// unexpected one.
+    ///
+    /// The app that served the request logs it before the response is sent, so call this after
+    /// the response is received.
+    async fn wait_for(&self, marker: &Marker, expect_local: bool, expect_remote: bool) {
+        let started = std::time::Instant::now();
+
+        loop {
+            let local = process_output(self.local).await.contains(marker.as_str());
+            let remote = self.remote.contents().await.contains(marker.as_str());
+
+            assert!(
+                expect_local || !local,
+                "request {marker} should not reach the local app"
+            );
+            assert!(
+                expect_remote || !remote,
+                "request {marker} should not reach the remote app"
+            );
+
+            if local == expect_local && remote == expect_remote {
+                return;
+            }
+
+            assert!(
+                started.elapsed() < self.timeout,
+                "request {marker} did not reach the {} app within {:?}",
+                if local { "remote" } else { "local" },
+                self.timeout
+            );
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Harness for the e2e tests that run without Kubernetes, see [`FakeCluster`].
+
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use mirrord_test_utils::TestProcess;
+use tempfile::TempDir;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+};
+
+use super::{access_log::RemoteLogs, run_command::run_mirrord};
+
+/// Env var read by `mirrord_sdk::connect` (built with the `fake-cluster` feature), makes the CLI
+/// connect to the agent through the given UNIX socket instead of the cluster.
+const TEST_AGENT_SOCKET_ENV: &str = "MIRRORD_TEST_AGENT_SOCKET";
+
+/// Printed by the `mirrord-fake-pod` when its sockets are ready.
+const FAKE_POD_READY_LINE: &str = "fake pod ready";
+
+/// A single "pod", running the target application and the mirrord-agent as local processes.
+///
+/// `mirrord-fake-pod` runs the pod in its own network, PID and mount namespaces, nested in the
+/// "cluster" namespaces created with `unshare`, and connects the two with a veth pair. This way the
+/// agent can steal and mirror the traffic that arrives at the pod, and read the pod's environment
+/// and files, like it would from an ephemeral container in a real cluster. The ports are exposed
+/// to the test through UNIX sockets, and the CLI reaches the agent through the test-only
+/// connector.
+///
+/// Requires `unshare`, `ip`, `mount` and `iptables` on the host, and unprivileged user namespaces.
+pub struct FakeCluster {
+    /// Holds the sockets and the remote files.
+    dir: TempDir,
+    pod: TestProcess,
+}
+
+impl FakeCluster {
+    /// Starts the given application in a fake pod, exposing the given ports.
+    ///
+    /// `env` is set for the application, so it is visible as the remote environment.
+    /// `remote_files` are `(name, content)` of the files created in [`FakeCluster::remote_dir`],
+    /// which exist only inside the pod.
+    pub async fn start(
+        app: Vec<String>,
+        ports: &[u16],
+        env: Vec<(&str, &str)>,
+        remote_files: Vec<(&str, &str)>,
+    ) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut args = [
+            "--user",
+            "--map-root-user",
+            "--net",
+            "--pid",
+            "--mount",
+            "--fork",
+            "--mount-proc",
+            "--kill-child",
+            env!("CARGO_BIN_FILE_MIRRORD_FAKE_POD"),
+            "cluster",
+            "--agent",
+            env!("CARGO_BIN_FILE_MIRRORD_AGENT"),
+            "--dir",
+        ]
+        .map(String::from)
+        .to_vec();
+        args.push(dir.path().to_string_lossy().into_owned());
+        for port in ports {
+            args.extend(["--expose".to_string(), port.to_string()]);
+        }
+        for (name, content) in remote_files {
+            args.extend(["--remote-file".to_string(), format!("{name}={content}")]);
+        }
+        args.push("--".into());
+        args.extend(app);
+
+        let mut base_env = HashMap::from([
+            ("RUST_LOG".to_string(), "warn,mirrord=debug".to_string()),
+            (
+                "MIRRORD_AGENT_RUST_LOG".to_string(),
+                "warn,mirrord=debug".to_string(),
+            ),
+        ]);
+        base_env.extend(
+            env.into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string())),
+        );
+
+        let pod = TestProcess::start_process("unshare".into(), args, base_env).await;
+        pod.wait_for_line_stdout(Duration::from_secs(60), FAKE_POD_READY_LINE)
+            .await;
+
+        Self { dir, pod }
+    }
+
+    /// The socket that leads to the agent.
+    pub fn agent_socket(&self) -> PathBuf {
+        self.dir.path().join("agent.sock")
+    }
+
+    /// The socket that leads to the given port of the application.
+    pub fn port_socket(&self, port: u16) -> PathBuf {
+        self.dir.path().join(format!("{port}.sock"))
+    }
+
+    /// Directory with the remote files, empty outside of the pod.
+    pub fn remote_dir(&self) -> PathBuf {
+        self.dir.path().join("remote")
+    }
+
+    /// The fake pod process, with the output of the application and the agent.
+    pub fn pod(&self) -> &TestProcess {
+        &self.pod
+    }
+
+    /// Output of the application, for the [`AccessLog`](super::access_log::AccessLog).
+    pub fn logs(&self) -> RemoteLogs<'_> {
+        RemoteLogs::Process(&self.pod)
+    }
+
+    /// Opens a connection to the given port of the application, as a client in the cluster would.
+    pub async fn connect(&self, port: u16) -> UnixStream {
+        UnixStream::connect(self.port_socket(port)).await.unwrap()
+    }
+
+    /// Sends an HTTP/1.1 request to the given port of the application, as a client in the cluster
+    /// would, and returns the raw response.
+    pub async fn send_http_request(&self, port: u16, method: &str) -> String {
+        let mut stream = self.connect(port).await;
+        let request =
+            format!("{method} / HTTP/1.1\r\nHost: fake-cluster\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    /// Runs `mirrord exec` in targetless mode with the given cmd, mirrord args, and env vars,
+    /// connecting to the agent of this pod.
+    pub async fn run_exec(
+        &self,
+        process_cmd: Vec<String>,
+        args: Option<Vec<&str>>,
+        env: Option<Vec<(&str, &str)>>,
+    ) -> TestProcess {
+        let agent_socket = self.agent_socket();
+        let agent_socket = agent_socket.to_string_lossy();
+
+        let mut mirrord_args = vec!["exec", "-c"];
+        mirrord_args.extend(args.unwrap_or_default());
+        mirrord_args.push("--");
+        mirrord_args.extend(process_cmd.iter().map(String::as_str));
+
+        let mut base_env = HashMap::from([
+            (TEST_AGENT_SOCKET_ENV, agent_socket.as_ref()),
+            ("MIRRORD_CHECK_VERSION", "false"),
+            ("MIRRORD_OPERATOR_ENABLE", "false"),
+            ("RUST_LOG", "warn,mirrord=debug"),
+            ("MIRRORD_PROGRESS_MODE", "off"),
+        ]);
+        base_env.extend(env.unwrap_or_default());
+
+        run_mirrord(mirrord_args, base_env).await
+    }
+}
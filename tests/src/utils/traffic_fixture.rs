@@ -0,0 +1,232 @@
+//! Test side of the in-repo `traffic-fixture` app (`tests/traffic-fixture`).
+//!
+//! [`TrafficFixture`] starts the receivers, either as the local app run with mirrord, or as the
+//! target app in a [`FakeCluster`](super::fake_cluster::FakeCluster) pod. The generators send
+//! traffic through any stream (e.g. a [`PortForwarder`](super::port_forwarder::PortForwarder)
+//! connection), and check the responses. [`assert_received`] and [`assert_not_received`] check
+//! what the receiver printed.
+
+use std::{net::SocketAddr, time::Duration};
+
+use futures::{SinkExt, StreamExt};
+use http_body_util::BodyExt;
+use hyper::{Request, StatusCode};
+use hyper_util::rt::TokioIo;
+use mirrord_test_utils::TestProcess;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::UdpSocket,
+};
+use tokio_tungstenite::tungstenite::Message;
+
+/// Request header with the marker of an HTTP request, must match the one in the fixture.
+pub const MARKER_HEADER: &str = "x-mirrord-test-marker";
+
+/// Response header with the name of the fixture that served the request, must match the one in
+/// the fixture.
+pub const SERVED_BY_HEADER: &str = "x-mirrord-served-by";
+
+/// How long the assertions wait for the receiver output.
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Protocols served by the `traffic-fixture` app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrafficFixture {
+    /// Responds with `<name>:<marker>`, see [`http_request`].
+    Http,
+    /// Echoes the data, see [`tcp_echo`].
+    TcpEcho,
+    /// Echoes the datagrams, see [`udp_echo`].
+    UdpEcho,
+    /// Echoes the messages, see [`websocket_echo`].
+    Websocket,
+}
+
+impl TrafficFixture {
+    /// Name of the protocol in the fixture output.
+    pub fn protocol(&self) -> &'static str {
+        match self {
+            Self::Http => "http",
+            Self::TcpEcho => "tcp",
+            Self::UdpEcho => "udp",
+            Self::Websocket => "websocket",
+        }
+    }
+
+    /// Command that starts the receiver with the given name, on the given port.
+    pub fn command(&self, name: &str, port: u16) -> Vec<String> {
+        let subcommand = match self {
+            Self::Http => "http",
+            Self::TcpEcho => "tcp-echo",
+            Self::UdpEcho => "udp-echo",
+            Self::Websocket => "websocket",
+        };
+
+        [
+            "../target/debug/traffic-fixture",
+            "--name",
+            name,
+            subcommand,
+            "--port",
+            &port.to_string(),
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    /// Waits until the receiver with the given name is listening.
+    pub async fn wait_until_listening(&self, process: &TestProcess, name: &str) {
+        process
+            .wait_for_line_stdout(
+                RECEIVE_TIMEOUT,
+                &format!("{name} listening {}", self.protocol()),
+            )
+            .await;
+    }
+}
+
+/// Response to a request sent with [`http_request`].
+#[derive(Debug)]
+pub struct FixtureResponse {
+    pub status: StatusCode,
+    /// Name of the fixture that served the request, if it was served by a fixture.
+    pub served_by: Option<String>,
+    pub body: String,
+}
+
+impl FixtureResponse {
+    /// Asserts that the request was served by the fixture with the given name.
+    pub fn assert_served_by(&self, name: &str, marker: &str) {
+        assert_eq!(self.status, StatusCode::OK, "{self:?}");
+        assert_eq!(self.served_by.as_deref(), Some(name), "{self:?}");
+        assert_eq!(self.body, format!("{name}:{marker}"), "{self:?}");
+    }
+}
+
+/// Sends an HTTP/1 `GET` request with the given marker through the stream.
+pub async fn http_request<IO>(stream: IO, path: &str, marker: &str) -> FixtureResponse
+where
+    IO: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let (mut sender, connection) = hyper::client::conn::http1::Builder::new()
+        .handshake::<_, String>(TokioIo::new(stream))
+        .await
+        .expect("failed to make an HTTP/1 connection to the fixture");
+    tokio::spawn(connection);
+
+    let request = Request::get(path)
+        .header("host", "traffic-fixture")
+        .header(MARKER_HEADER, marker)
+        .body(String::new())
+        .unwrap();
+    let response = sender
+        .send_request(request)
+        .await
+        .expect("failed to send an HTTP request to the fixture");
+
+    let (parts, body) = response.into_parts();
+    let body = body
+        .collect()
+        .await
+        .expect("failed to read the HTTP response body from the fixture")
+        .to_bytes();
+
+    FixtureResponse {
+        status: parts.status,
+        served_by: parts
+            .headers
+            .get(SERVED_BY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string),
+        body: String::from_utf8_lossy(&body).into_owned(),
+    }
+}
+
+/// Sends the payload through the stream, and asserts that it's echoed back.
+pub async fn tcp_echo<IO>(mut stream: IO, payload: &str)
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    stream.write_all(payload.as_bytes()).await.unwrap();
+
+    let mut echoed = vec![0; payload.len()];
+    tokio::time::timeout(RECEIVE_TIMEOUT, stream.read_exact(&mut echoed))
+        .await
+        .expect("timed out waiting for the TCP echo")
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&echoed), payload);
+}
+
+/// Sends the payload in a datagram to the given address, and asserts that it's echoed back.
+pub async fn udp_echo(address: SocketAddr, payload: &str) {
+    let bind_address = if address.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(bind_address).await.unwrap();
+    socket.send_to(payload.as_bytes(), address).await.unwrap();
+
+    let mut buffer = vec![0; payload.len() + 1];
+    let (read, _) = tokio::time::timeout(RECEIVE_TIMEOUT, socket.recv_from(&mut buffer))
+        .await
+        .expect("timed out waiting for the UDP echo")
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(buffer.get(..read).unwrap()),
+        payload
+    );
+}
+
+/// Opens a WebSocket connection through the stream, sends the message, and asserts that it's
+/// echoed back.
+pub async fn websocket_echo<IO>(stream: IO, message: &str)
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    let (mut websocket, _) = tokio_tungstenite::client_async("ws://traffic-fixture/", stream)
+        .await
+        .expect("failed to make a WebSocket connection to the fixture");
+
+    websocket.send(Message::text(message)).await.unwrap();
+    let echoed = tokio::time::timeout(RECEIVE_TIMEOUT, websocket.next())
+        .await
+        .expect("timed out waiting for the WebSocket echo")
+        .expect("WebSocket connection closed")
+        .unwrap();
+    assert_eq!(echoed, Message::text(message));
+
+    websocket.close(None).await.unwrap();
+}
+
+/// The line printed by the fixture with the given name when it receives the payload.
+///
+/// For [`TrafficFixture::Http`] the payload is `<method> <path> marker=<marker>`.
+pub fn received_line(name: &str, fixture: TrafficFixture, payload: &str) -> String {
+    format!("{name} received {} {payload}", fixture.protocol())
+}
+
+/// Waits until the fixture with the given name, running in the process, receives the payload.
+pub async fn assert_received(
+    process: &TestProcess,
+    name: &str,
+    fixture: TrafficFixture,
+    payload: &str,
+) {
+    process
+        .wait_for_line_stdout(RECEIVE_TIMEOUT, &received_line(name, fixture, payload))
+        .await;
+}
+
+/// Asserts that the fixture with the given name, running in the process, did not receive the
+/// payload so far.
+pub async fn assert_not_received(
+    process: &TestProcess,
+    name: &str,
+    fixture: TrafficFixture,
+    payload: &str,
+) {
+    process
+        .assert_stdout_doesnt_contain(&received_line(name, fixture, payload))
+        .await;
+}
@@ -0,0 +1,198 @@
+//! Traffic receiver for the e2e tests, used instead of the apps published as container images.
+//!
+//! Serves one protocol on the given port, and prints a line for everything it receives:
+//! `<name> received <protocol> <payload>`, e.g. `local received http GET /api marker=abc`. The
+//! `--name` tells apart the instances (e.g. the local app run with mirrord, and the remote one in
+//! the pod), and is also included in the HTTP responses. The test side of the fixtures lives in
+//! `tests/src/utils/traffic_fixture.rs`.
+
+use std::{convert::Infallible, io, net::SocketAddr};
+
+use bytes::Bytes;
+use clap::{Parser, Subcommand};
+use futures::{SinkExt, StreamExt};
+use http_body_util::{BodyExt, Full};
+use hyper::{Request, Response, body::Incoming, server::conn::http1, service::service_fn};
+use hyper_util::rt::TokioIo;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, UdpSocket},
+};
+use tokio_tungstenite::tungstenite::Message;
+
+/// Request header with the marker of an HTTP request, printed and echoed in the response.
+const MARKER_HEADER: &str = "x-mirrord-test-marker";
+
+/// Response header with the `--name` of the fixture that served the request.
+const SERVED_BY_HEADER: &str = "x-mirrord-served-by";
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// Included in the output and in the HTTP responses.
+    #[arg(long, default_value = "local")]
+    name: String,
+
+    #[command(subcommand)]
+    protocol: Protocol,
+}
+
+#[derive(Subcommand, Debug, Clone, Copy)]
+enum Protocol {
+    /// HTTP/1 server, responds with `<name>:<marker>`.
+    Http {
+        #[arg(long, default_value_t = 80)]
+        port: u16,
+    },
+    /// Echoes everything received on TCP connections.
+    TcpEcho {
+        #[arg(long, default_value_t = 80)]
+        port: u16,
+    },
+    /// Echoes every UDP datagram to its sender.
+    UdpEcho {
+        #[arg(long, default_value_t = 31415)]
+        port: u16,
+    },
+    /// Echoes the text and binary messages of WebSocket connections.
+    Websocket {
+        #[arg(long, default_value_t = 80)]
+        port: u16,
+    },
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> io::Result<()> {
+    let Args { name, protocol } = Args::parse();
+    let name: &'static str = name.leak();
+
+    match protocol {
+        Protocol::Http { port } => {
+            let listener = listen(name, "http", port).await?;
+            serve(listener, move |stream| serve_http(name, stream)).await
+        }
+        Protocol::TcpEcho { port } => {
+            let listener = listen(name, "tcp", port).await?;
+            serve(listener, move |stream| echo_tcp(name, stream)).await
+        }
+        Protocol::UdpEcho { port } => echo_udp(name, port).await,
+        Protocol::Websocket { port } => {
+            let listener = listen(name, "websocket", port).await?;
+            serve(listener, move |stream| echo_websocket(name, stream)).await
+        }
+    }
+}
+
+async fn listen(name: &str, protocol: &str, port: u16) -> io::Result<TcpListener> {
+    let listener = TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port))).await?;
+    println!("{name} listening {protocol} on {}", listener.local_addr()?);
+    Ok(listener)
+}
+
+/// Handles every accepted connection in its own task.
+async fn serve<F, Fut>(listener: TcpListener, handler: F) -> io::Result<()>
+where
+    F: Fn(TcpStream) -> Fut,
+    Fut: Future<Output = io::Result<()>> + 'static + Send,
+{
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let connection = handler(stream);
+        tokio::spawn(async move {
+            if let Err(error) = connection.await {
+                eprintln!("connection from {peer} failed: {error}");
+            }
+        });
+    }
+}
+
+async fn serve_http(name: &'static str, stream: TcpStream) -> io::Result<()> {
+    http1::Builder::new()
+        .serve_connection(
+            TokioIo::new(stream),
+            service_fn(move |request| handle_http(name, request)),
+        )
+        .await
+        .map_err(io::Error::other)
+}
+
+async fn handle_http(
+    name: &'static str,
+    request: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let marker = request
+        .headers()
+        .get(MARKER_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    println!(
+        "{name} received http {} {} marker={marker}",
+        request.method(),
+        request.uri().path()
+    );
+
+    // Consume the body, so that the client is not stuck sending it.
+    let _ = request.into_body().collect().await;
+
+    Ok(Response::builder()
+        .header(SERVED_BY_HEADER, name)
+        .body(Full::new(Bytes::from(format!("{name}:{marker}"))))
+        .expect("response is valid"))
+}
+
+async fn echo_tcp(name: &'static str, mut stream: TcpStream) -> io::Result<()> {
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(());
+        }
+
+        let data = buffer.get(..read).unwrap_or_default();
+        println!(
+            "{name} received tcp {}",
+            String::from_utf8_lossy(data).trim_end()
+        );
+        stream.write_all(data).await?;
+    }
+}
+
+async fn echo_udp(name: &'static str, port: u16) -> io::Result<()> {
+    let socket = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], port))).await?;
+    println!("{name} listening udp on {}", socket.local_addr()?);
+
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let (read, peer) = socket.recv_from(&mut buffer).await?;
+        let data = buffer.get(..read).unwrap_or_default();
+        println!(
+            "{name} received udp {}",
+            String::from_utf8_lossy(data).trim_end()
+        );
+        socket.send_to(data, peer).await?;
+    }
+}
+
+async fn echo_websocket(name: &'static str, stream: TcpStream) -> io::Result<()> {
+    let mut websocket = tokio_tungstenite::accept_async(stream)
+        .await
+        .map_err(io::Error::other)?;
+
+    while let Some(message) = websocket.next().await {
+        let message = message.map_err(io::Error::other)?;
+        match &message {
+            Message::Text(text) => println!("{name} received websocket {text}"),
+            Message::Binary(data) => println!(
+                "{name} received websocket {}",
+                String::from_utf8_lossy(data)
+            ),
+            Message::Close(..) => break,
+            _ => continue,
+        }
+
+        websocket.send(message).await.map_err(io::Error::other)?;
+    }
+
+    Ok(())
+}